@@ -0,0 +1,69 @@
+# Deutsche Meldungen für die webshot-CLI. Fehlende Meldungen fallen auf
+# en.ftl zurück.
+
+error-prefix = Fehler
+error-url-required = Ohne Unterbefehl muss eine URL angegeben werden
+error-see-help = Mit 'webshot --help' wird die Verwendung angezeigt
+
+screenshot-saved = Screenshot gespeichert unter: { $path }
+pdf-saved = PDF gespeichert unter: { $path }
+text-saved = Text gespeichert unter: { $path }
+outline-saved = Gliederung gespeichert unter: { $path }
+assets-saved = { $count } Assets gespeichert unter: { $path }
+email-saved = E-Mail mit { $width } im { $scheme }-Modus gespeichert unter: { $path }
+book-saved = Buch mit { $pages } Seiten gespeichert unter: { $path }
+dedupe-summary = { $deduped } von { $scanned } Dateien dedupliziert, { $saved } eingespart
+dedupe-manifest-saved = Dedupe-Manifest gespeichert unter: { $path }
+baseline-accepted = Referenzbild { $name } als Version { $version } übernommen
+
+batch-finished = Stapel beendet: { $done } erledigt, { $failed } fehlgeschlagen, { $skipped } übersprungen
+batch-phases = Zeit je Phase: { $phases }
+batch-failed = Fehlgeschlagen: { $url }: { $error }
+batch-retried = Erfolgreich nach { $attempts } Versuchen: { $url }
+batch-manifest-saved = Lauf-Manifest gespeichert unter: { $path }
+batch-report-saved = Bericht gespeichert unter: { $path }
+
+compare-title = Ergebnis des Bildvergleichs
+compare-algorithm = Algorithmus: { $algorithm }
+compare-threshold = Schwellenwert: { $threshold }
+compare-similarity = Ähnlichkeit: { $similarity } ({ $percent } %)
+compare-similar = Ähnlich: { $similar }
+compare-yes = JA
+compare-no = NEIN
+compare-different-pixels = Abweichende Pixel: { $different }/{ $total } ({ $percent } %)
+compare-total-pixels = Pixel gesamt: { $total }
+compare-regions = Geänderte Bereiche: { $count }
+compare-region = { $width }×{ $height } bei ({ $x }, { $y }): { $pixels } Pixel
+compare-diff-image = Differenzbild: { $path }
+compare-baseline-version = Version des Referenzbilds: { $version }
+
+error-browser = Browserfehler: { $detail }
+error-browser-launch = Browserstart fehlgeschlagen: { $detail }. Prüfen Sie, ob Chrome oder Chromium installiert und erreichbar ist, oder geben Sie den Pfad mit --chrome-path an. In Containern helfen außerdem --chrome-flag=--no-sandbox und ein beschreibbares temporäres Verzeichnis.
+error-browser-connect = Keine Verbindung zum Browser unter { $url }: { $reason }. Prüfen Sie, ob Chrome mit --remote-debugging-port läuft (entfernt zusätzlich mit --remote-debugging-address=0.0.0.0), ob der Port von diesem Rechner erreichbar ist und ob die URL die ws://-URL aus /json/version oder die http://-Adresse des Ports ist.
+error-tab = Tab-Fehler: { $detail }
+error-navigation = Navigation fehlgeschlagen: { $detail }. Prüfen Sie, ob die URL ein unterstütztes Schema wie https:// enthält, die Seite von diesem Rechner erreichbar ist und das Zeitlimit zum Laden der Seite ausreicht.
+error-screenshot = Screenshot-Fehler: { $detail }
+error-element-not-found = Kein Element für den Selektor '{ $selector }' gefunden. Prüfen Sie den Selektor und ob das Element nach dem Laden vorhanden ist, oder verwenden Sie --wait-for/--timeout für nachgeladene Inhalte.
+error-javascript = Fehler bei der JavaScript-Ausführung: { $detail }
+error-io = Datei-E/A-Fehler: { $detail }
+error-image = Fehler bei der Bildverarbeitung: { $detail }
+error-yaml = Fehler beim Lesen von YAML: { $detail }
+error-json = Fehler beim Lesen von JSON: { $detail }
+error-url = Ungültige URL: { $detail }
+error-http = Fehler bei der HTTP-Anfrage: { $detail }
+error-invalid-path = Ungültiger Dateipfad: { $path }
+error-unsupported-format = Nicht unterstütztes Bildformat: { $format }. Unterstützte Formate: png, jpg, jpeg, webp, gif, tif, tiff, pdf
+error-timeout = Zeitüberschreitung beim Warten auf: { $condition }
+error-config = Konfigurationsfehler: { $detail }
+error-pdf = Fehler beim Erzeugen der PDF: { $detail }
+error-invalid-viewport = Ungültige Viewport-Größe: Breite={ $width }, Höhe={ $height }
+error-hook = Hook fehlgeschlagen: { $detail }
+error-plugin = Plugin-Fehler: { $detail }
+error-script = Skriptfehler: { $detail }
+error-queue-full = Die Auftragswarteschlange ist voll ({ $capacity } offene Aufträge); später erneut versuchen
+error-baseline = Fehler im Referenzbild-Speicher: { $detail }
+error-baseline-conflict = Referenzbild '{ $name }' ist inzwischen bei Version { $current }, nicht bei der erwarteten. Ein anderer Lauf hat ein neueres Referenzbild übernommen; vergleichen Sie erneut oder ersetzen Sie es mit --force.
+error-http-status = { $url } antwortete mit HTTP { $status }. Die Aufnahme würde eine Fehlerseite zeigen; korrigieren Sie URL oder Server, oder lassen Sie --fail-on-http-error weg, um Fehlerseiten aufzunehmen.
+error-page-errors =
+    { $url } meldete Fehler beim Laden:{ $errors }
+    Beheben Sie die Fehler der Seite, oder lassen Sie --fail-on-console-error/--fail-on-js-exception weg, um sie trotzdem aufzunehmen.
@@ -0,0 +1,40 @@
+# English messages for the webshot CLI. This catalog is the fallback for
+# every other language, so it must define every message. Errors are shown
+# with their own English text, so only translations define `error-*`
+# messages for them.
+
+error-prefix = Error
+error-url-required = URL is required when no subcommand is provided
+error-see-help = Use 'webshot --help' for usage information
+
+screenshot-saved = Screenshot saved to: { $path }
+pdf-saved = PDF saved to: { $path }
+text-saved = Text saved to: { $path }
+outline-saved = Outline saved to: { $path }
+assets-saved = { $count } assets saved to: { $path }
+email-saved = Email at { $width }, { $scheme } mode, saved to: { $path }
+book-saved = Book of { $pages } pages saved to: { $path }
+dedupe-summary = Deduplicated { $deduped } of { $scanned } files, saving { $saved }
+dedupe-manifest-saved = Dedupe manifest saved to: { $path }
+baseline-accepted = Baseline { $name } accepted as version { $version }
+
+batch-finished = Batch finished: { $done } done, { $failed } failed, { $skipped } skipped
+batch-phases = Time by phase: { $phases }
+batch-failed = Failed: { $url }: { $error }
+batch-retried = Succeeded after { $attempts } attempts: { $url }
+batch-manifest-saved = Run manifest saved to: { $path }
+batch-report-saved = Report saved to: { $path }
+
+compare-title = Image Comparison Results
+compare-algorithm = Algorithm: { $algorithm }
+compare-threshold = Threshold: { $threshold }
+compare-similarity = Similarity: { $similarity } ({ $percent }%)
+compare-similar = Similar: { $similar }
+compare-yes = YES
+compare-no = NO
+compare-different-pixels = Different pixels: { $different }/{ $total } ({ $percent }%)
+compare-total-pixels = Total pixels: { $total }
+compare-regions = Changed regions: { $count }
+compare-region = { $width }×{ $height } at ({ $x }, { $y }): { $pixels } pixels
+compare-diff-image = Difference image: { $path }
+compare-baseline-version = Baseline version: { $version }
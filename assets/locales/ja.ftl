@@ -0,0 +1,69 @@
+# webshot CLI の日本語メッセージ。未翻訳のメッセージは en.ftl の英語で
+# 表示されます。
+
+error-prefix = エラー
+error-url-required = サブコマンドを指定しない場合は URL が必要です
+error-see-help = 使い方は 'webshot --help' で確認できます
+
+screenshot-saved = スクリーンショットを保存しました: { $path }
+pdf-saved = PDF を保存しました: { $path }
+text-saved = テキストを保存しました: { $path }
+outline-saved = 見出し構造を保存しました: { $path }
+assets-saved = { $count } 件のアセットを保存しました: { $path }
+email-saved = メール ({ $width }、{ $scheme } モード) を保存しました: { $path }
+book-saved = { $pages } ページのブックを保存しました: { $path }
+dedupe-summary = { $scanned } 個中 { $deduped } 個のファイルを重複排除し、{ $saved } 削減しました
+dedupe-manifest-saved = 重複排除マニフェストを保存しました: { $path }
+baseline-accepted = ベースライン { $name } をバージョン { $version } として承認しました
+
+batch-finished = バッチ完了: 成功 { $done } 件、失敗 { $failed } 件、スキップ { $skipped } 件
+batch-phases = フェーズ別の所要時間: { $phases }
+batch-failed = 失敗: { $url }: { $error }
+batch-retried = { $attempts } 回目で成功: { $url }
+batch-manifest-saved = 実行マニフェストを保存しました: { $path }
+batch-report-saved = レポートを保存しました: { $path }
+
+compare-title = 画像比較の結果
+compare-algorithm = アルゴリズム: { $algorithm }
+compare-threshold = しきい値: { $threshold }
+compare-similarity = 類似度: { $similarity } ({ $percent }%)
+compare-similar = 一致: { $similar }
+compare-yes = はい
+compare-no = いいえ
+compare-different-pixels = 差分ピクセル: { $different }/{ $total } ({ $percent }%)
+compare-total-pixels = 総ピクセル数: { $total }
+compare-regions = 変更された領域: { $count }
+compare-region = ({ $x }, { $y }) の { $width }×{ $height }: { $pixels } ピクセル
+compare-diff-image = 差分画像: { $path }
+compare-baseline-version = ベースラインのバージョン: { $version }
+
+error-browser = ブラウザーのエラー: { $detail }
+error-browser-launch = ブラウザーを起動できませんでした: { $detail }。Chrome または Chromium がインストールされ実行できることを確認するか、--chrome-path で実行ファイルのパスを指定してください。コンテナー内では --chrome-flag=--no-sandbox を試し、一時ディレクトリに書き込めることも確認してください。
+error-browser-connect = { $url } のブラウザーに接続できません: { $reason }。Chrome が --remote-debugging-port (リモートの場合は --remote-debugging-address=0.0.0.0 も) 付きで動作していること、このマシンからポートに到達できること、URL が /json/version の ws:// URL かポートの http:// アドレスであることを確認してください。
+error-tab = タブのエラー: { $detail }
+error-navigation = ページを開けませんでした: { $detail }。URL に https:// などの対応スキームが含まれていること、このマシンからページに到達できること、タイムアウトが読み込みに十分であることを確認してください。
+error-screenshot = スクリーンショットのエラー: { $detail }
+error-element-not-found = セレクター '{ $selector }' に一致する要素が見つかりません。セレクターが正しく、読み込み後に要素が存在することを確認してください。非同期に表示される場合は --wait-for/--timeout を使ってください。
+error-javascript = JavaScript の実行エラー: { $detail }
+error-io = ファイル入出力のエラー: { $detail }
+error-image = 画像処理のエラー: { $detail }
+error-yaml = YAML の解析エラー: { $detail }
+error-json = JSON の解析エラー: { $detail }
+error-url = URL の解析エラー: { $detail }
+error-http = HTTP リクエストのエラー: { $detail }
+error-invalid-path = 無効なファイルパス: { $path }
+error-unsupported-format = 対応していない画像形式です: { $format }。対応形式: png, jpg, jpeg, webp, gif, tif, tiff, pdf
+error-timeout = 条件の待機がタイムアウトしました: { $condition }
+error-config = 設定エラー: { $detail }
+error-pdf = PDF 生成のエラー: { $detail }
+error-invalid-viewport = 無効なビューポートサイズ: 幅={ $width }、高さ={ $height }
+error-hook = フックが失敗しました: { $detail }
+error-plugin = プラグインのエラー: { $detail }
+error-script = スクリプトのエラー: { $detail }
+error-queue-full = ジョブキューが満杯です (保留中 { $capacity } 件)。後で再試行してください
+error-baseline = ベースラインストアのエラー: { $detail }
+error-baseline-conflict = ベースライン '{ $name }' は既にバージョン { $current } になっており、想定したバージョンではありません。別の実行が新しいベースラインを承認しました。もう一度比較するか、--force で置き換えてください。
+error-http-status = { $url } が HTTP { $status } を返しました。エラーページが撮影されます。URL かサーバーを修正するか、エラーページも撮影する場合は --fail-on-http-error を外してください。
+error-page-errors =
+    { $url } の読み込み中にエラーが報告されました:{ $errors }
+    ページを修正するか、--fail-on-console-error/--fail-on-js-exception を外して撮影してください。
@@ -0,0 +1,579 @@
+use crate::error::{Result, WebshotError};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+/// Ad and tracker filters used by `--adblock` without `--adblock-list`
+const BUNDLED_FILTERS: &str = include_str!("../assets/adblock-filters.txt");
+
+/// Kind of subresource a filter option such as `$script` refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestKind {
+    Script,
+    Image,
+    Stylesheet,
+    XmlHttpRequest,
+    Subdocument,
+    Media,
+    Font,
+    WebSocket,
+    Ping,
+    Other,
+}
+
+impl RequestKind {
+    fn from_option(name: &str) -> Option<Self> {
+        Some(match name {
+            "script" => RequestKind::Script,
+            "image" => RequestKind::Image,
+            "stylesheet" => RequestKind::Stylesheet,
+            "xmlhttprequest" | "xhr" => RequestKind::XmlHttpRequest,
+            "subdocument" | "frame" => RequestKind::Subdocument,
+            "media" => RequestKind::Media,
+            "font" => RequestKind::Font,
+            "websocket" => RequestKind::WebSocket,
+            "ping" => RequestKind::Ping,
+            "other" | "object" => RequestKind::Other,
+            _ => return None,
+        })
+    }
+}
+
+/// A request checked against a filter list
+#[derive(Debug, Clone, Copy)]
+pub struct FilterRequest<'a> {
+    pub url: &'a str,
+    pub kind: RequestKind,
+    /// URL of the top-level page that made the request
+    pub page_url: Option<&'a str>,
+}
+
+/// Request URL lowercased once, with its host located for `||` anchors
+struct PreparedRequest {
+    url: String,
+    host: std::ops::Range<usize>,
+    kind: RequestKind,
+    page_host: Option<String>,
+    third_party: Option<bool>,
+}
+
+impl PreparedRequest {
+    fn new(request: &FilterRequest) -> Self {
+        let url = request.url.to_lowercase();
+        let host = host_range(&url);
+        let page_host = request
+            .page_url
+            .and_then(|page| url::Url::parse(page).ok())
+            .and_then(|page| page.host_str().map(str::to_lowercase));
+        let third_party = page_host
+            .as_deref()
+            .map(|page_host| site(page_host) != site(&url[host.clone()]));
+
+        Self {
+            url,
+            host,
+            kind: request.kind,
+            page_host,
+            third_party,
+        }
+    }
+
+    fn host(&self) -> &str {
+        &self.url[self.host.clone()]
+    }
+}
+
+/// Byte range of the host in a lowercased URL, empty when there is none
+fn host_range(url: &str) -> std::ops::Range<usize> {
+    let Some(scheme_end) = url.find("://") else {
+        return 0..0;
+    };
+    let authority_start = scheme_end + 3;
+    let authority_end = url[authority_start..]
+        .find(['/', '?', '#'])
+        .map_or(url.len(), |end| authority_start + end);
+    let authority = &url[authority_start..authority_end];
+
+    let start = authority
+        .rfind('@')
+        .map_or(authority_start, |at| authority_start + at + 1);
+    let end = url[start..authority_end]
+        .rfind(':')
+        .filter(|_| !url[start..authority_end].ends_with(']'))
+        .map_or(authority_end, |colon| start + colon);
+    start..end
+}
+
+/// Registrable part of a host, approximated as its last two labels (three
+/// under short second-level labels such as `co.uk`)
+fn site(host: &str) -> &str {
+    let labels: Vec<&str> = host.rsplitn(4, '.').collect();
+    let count = match labels.as_slice() {
+        [tld, second, _, ..] if tld.len() == 2 && second.len() <= 3 => 3,
+        _ => 2,
+    };
+    let suffix_len: usize = labels.iter().take(count).map(|label| label.len() + 1).sum();
+    &host[host.len().saturating_sub(suffix_len - 1)..]
+}
+
+/// Whether `host` is `domain` or one of its subdomains
+fn is_subdomain(host: &str, domain: &str) -> bool {
+    host == domain
+        || host
+            .strip_suffix(domain)
+            .is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Anchor {
+    None,
+    /// `|` at the start of the pattern
+    Start,
+    /// `||`: the pattern starts at a label boundary of the host
+    Host,
+}
+
+#[derive(Debug, Clone, Default)]
+struct FilterOptions {
+    /// Request kinds the filter is limited to; empty means all
+    kinds: Vec<RequestKind>,
+    excluded_kinds: Vec<RequestKind>,
+    third_party: Option<bool>,
+    domains: Vec<String>,
+    excluded_domains: Vec<String>,
+}
+
+impl FilterOptions {
+    /// Parse `$` options, or `None` for options this matcher cannot honor
+    fn parse(options: &str) -> Option<Self> {
+        let mut parsed = FilterOptions::default();
+        for option in options
+            .split(',')
+            .map(|option| option.trim().to_lowercase())
+        {
+            let (negated, name) = match option.strip_prefix('~') {
+                Some(name) => (true, name.to_string()),
+                None => (false, option.clone()),
+            };
+
+            if let Some(kind) = RequestKind::from_option(&name) {
+                if negated {
+                    parsed.excluded_kinds.push(kind);
+                } else {
+                    parsed.kinds.push(kind);
+                }
+            } else if name == "third-party" || name == "3p" {
+                parsed.third_party = Some(!negated);
+            } else if name == "first-party" || name == "1p" {
+                parsed.third_party = Some(negated);
+            } else if let Some(domains) = name.strip_prefix("domain=") {
+                for domain in domains.split('|') {
+                    match domain.strip_prefix('~') {
+                        Some(domain) => parsed.excluded_domains.push(domain.to_string()),
+                        None => parsed.domains.push(domain.to_string()),
+                    }
+                }
+            } else if !matches!(name.as_str(), "match-case" | "important") {
+                return None;
+            }
+        }
+        Some(parsed)
+    }
+
+    fn applies_to(&self, request: &PreparedRequest) -> bool {
+        if !self.kinds.is_empty() && !self.kinds.contains(&request.kind) {
+            return false;
+        }
+        if self.excluded_kinds.contains(&request.kind) {
+            return false;
+        }
+        if let Some(third_party) = self.third_party {
+            if request.third_party != Some(third_party) {
+                return false;
+            }
+        }
+        if self.domains.is_empty() && self.excluded_domains.is_empty() {
+            return true;
+        }
+
+        let Some(page_host) = request.page_host.as_deref() else {
+            return self.domains.is_empty();
+        };
+        let on = |domains: &[String]| domains.iter().any(|domain| is_subdomain(page_host, domain));
+        (self.domains.is_empty() || on(&self.domains)) && !on(&self.excluded_domains)
+    }
+}
+
+/// One network filter rule
+#[derive(Debug, Clone)]
+struct Filter {
+    anchor: Anchor,
+    pattern: String,
+    end_anchor: bool,
+    /// Longest literal run of the pattern, checked before full matching
+    literal: String,
+    options: FilterOptions,
+}
+
+impl Filter {
+    fn matches(&self, request: &PreparedRequest) -> bool {
+        if !request.url.contains(&self.literal) || !self.options.applies_to(request) {
+            return false;
+        }
+
+        let pattern = self.pattern.as_bytes();
+        let url = request.url.as_bytes();
+        match self.anchor {
+            Anchor::Start => match_here(pattern, url, self.end_anchor),
+            Anchor::Host => {
+                let host = request.host();
+                std::iter::once(0)
+                    .chain(host.match_indices('.').map(|(dot, _)| dot + 1))
+                    .any(|start| {
+                        match_here(pattern, &url[request.host.start + start..], self.end_anchor)
+                    })
+            }
+            Anchor::None => {
+                (0..=url.len()).any(|start| match_here(pattern, &url[start..], self.end_anchor))
+            }
+        }
+    }
+
+    /// Domain a `||domain^` filter can be looked up by
+    fn domain_key(&self) -> Option<&str> {
+        if self.anchor != Anchor::Host {
+            return None;
+        }
+        let end = self
+            .pattern
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '.' || c == '-'))
+            .unwrap_or(self.pattern.len());
+        let rest = &self.pattern[end..];
+        let complete =
+            rest.is_empty() && self.end_anchor || rest.starts_with('^') || rest.starts_with('/');
+        (end > 0 && complete).then(|| &self.pattern[..end])
+    }
+}
+
+/// Separator for `^`: anything but a letter, digit or one of `_-.%`
+fn is_separator(byte: u8) -> bool {
+    !(byte.is_ascii_alphanumeric() || matches!(byte, b'_' | b'-' | b'.' | b'%'))
+}
+
+/// Match a pattern at the start of `text`; `*` matches any run of bytes and
+/// `^` a separator or the end of the URL
+fn match_here(pattern: &[u8], text: &[u8], end_anchor: bool) -> bool {
+    match pattern.split_first() {
+        None => !end_anchor || text.is_empty(),
+        Some((b'*', rest)) => {
+            (0..=text.len()).any(|skip| match_here(rest, &text[skip..], end_anchor))
+        }
+        Some((b'^', rest)) => match text.split_first() {
+            None => match_here(rest, text, end_anchor),
+            Some((&byte, tail)) => is_separator(byte) && match_here(rest, tail, end_anchor),
+        },
+        Some((&byte, rest)) => {
+            text.first() == Some(&byte) && match_here(rest, &text[1..], end_anchor)
+        }
+    }
+}
+
+/// Parse one filter list line into a filter and whether it is an exception
+fn parse_filter(line: &str) -> Option<(bool, Filter)> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('!') || line.starts_with('[') {
+        return None;
+    }
+    // Element hiding and scriptlet rules
+    if ["##", "#@#", "#?#", "#$#"]
+        .iter()
+        .any(|marker| line.contains(marker))
+    {
+        return None;
+    }
+
+    let (exception, line) = match line.strip_prefix("@@") {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+    let (mut pattern, options) = match line.rfind('$') {
+        Some(dollar) => (&line[..dollar], FilterOptions::parse(&line[dollar + 1..])?),
+        None => (line, FilterOptions::default()),
+    };
+    // Regular expression filters
+    if pattern.len() > 1 && pattern.starts_with('/') && pattern.ends_with('/') {
+        return None;
+    }
+
+    let anchor = if let Some(rest) = pattern.strip_prefix("||") {
+        pattern = rest;
+        Anchor::Host
+    } else if let Some(rest) = pattern.strip_prefix('|') {
+        pattern = rest;
+        Anchor::Start
+    } else {
+        Anchor::None
+    };
+    let end_anchor = match pattern.strip_suffix('|') {
+        Some(rest) => {
+            pattern = rest;
+            true
+        }
+        None => false,
+    };
+
+    let pattern = pattern.to_lowercase();
+    let literal = pattern
+        .split(['*', '^'])
+        .max_by_key(|literal| literal.len())
+        .unwrap_or_default()
+        .to_string();
+    Some((
+        exception,
+        Filter {
+            anchor,
+            pattern,
+            end_anchor,
+            literal,
+            options,
+        },
+    ))
+}
+
+#[derive(Default)]
+struct FilterSet {
+    by_domain: HashMap<String, Vec<Filter>>,
+    generic: Vec<Filter>,
+}
+
+impl FilterSet {
+    fn insert(&mut self, filter: Filter) {
+        match filter.domain_key() {
+            Some(domain) => self
+                .by_domain
+                .entry(domain.to_string())
+                .or_default()
+                .push(filter),
+            None => self.generic.push(filter),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.by_domain.values().map(Vec::len).sum::<usize>() + self.generic.len()
+    }
+
+    fn matches(&self, request: &PreparedRequest) -> bool {
+        let host = request.host();
+        let domain_match = std::iter::once(host)
+            .chain(host.match_indices('.').map(|(dot, _)| &host[dot + 1..]))
+            .filter_map(|suffix| self.by_domain.get(suffix))
+            .flatten()
+            .any(|filter| filter.matches(request));
+        domain_match || self.generic.iter().any(|filter| filter.matches(request))
+    }
+}
+
+/// Network rules of an EasyList-style (Adblock Plus syntax) filter list.
+/// Element hiding, regular expression and unsupported-option rules are skipped.
+#[derive(Default)]
+pub struct FilterList {
+    blocking: FilterSet,
+    exceptions: FilterSet,
+}
+
+impl fmt::Debug for FilterList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FilterList")
+            .field("blocking", &self.blocking.len())
+            .field("exceptions", &self.exceptions.len())
+            .finish()
+    }
+}
+
+impl FilterList {
+    /// Parse filter list text, one rule per line
+    pub fn parse(text: &str) -> Self {
+        let mut list = FilterList::default();
+        for (exception, filter) in text.lines().filter_map(parse_filter) {
+            if exception {
+                list.exceptions.insert(filter);
+            } else {
+                list.blocking.insert(filter);
+            }
+        }
+        list
+    }
+
+    /// The filter list bundled with webshot
+    pub fn bundled() -> Self {
+        Self::parse(BUNDLED_FILTERS)
+    }
+
+    /// Load a filter list file such as EasyList
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|e| {
+            WebshotError::config(format!(
+                "Failed to read filter list {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        let list = Self::parse(&text);
+        if list.is_empty() {
+            return Err(WebshotError::config(format!(
+                "Filter list {} contains no network filters",
+                path.display()
+            )));
+        }
+        Ok(list)
+    }
+
+    /// Load the filter list at `path`, or the bundled list without one
+    pub fn load_or_bundled(path: Option<&Path>) -> Result<Self> {
+        match path {
+            Some(path) => Self::load(path),
+            None => Ok(Self::bundled()),
+        }
+    }
+
+    /// Number of blocking and exception rules
+    pub fn len(&self) -> usize {
+        self.blocking.len() + self.exceptions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether a blocking rule matches the request and no exception does
+    pub fn blocks(&self, request: &FilterRequest) -> bool {
+        let request = PreparedRequest::new(request);
+        self.blocking.matches(&request) && !self.exceptions.matches(&request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn script<'a>(url: &'a str, page_url: &'a str) -> FilterRequest<'a> {
+        FilterRequest {
+            url,
+            kind: RequestKind::Script,
+            page_url: Some(page_url),
+        }
+    }
+
+    #[test]
+    fn test_host_anchored_filters() {
+        let list = FilterList::parse("||ads.example.com^\n||tracker.net/pixel");
+
+        assert!(list.blocks(&script("https://ads.example.com/a.js", "https://site.org/")));
+        assert!(list.blocks(&script(
+            "https://cdn.ads.example.com/a.js",
+            "https://site.org/"
+        )));
+        assert!(!list.blocks(&script(
+            "https://bads.example.com/a.js",
+            "https://site.org/"
+        )));
+        assert!(!list.blocks(&script(
+            "https://ads.example.com.evil/a.js",
+            "https://site.org/"
+        )));
+        assert!(list.blocks(&script(
+            "https://tracker.net/pixel.gif",
+            "https://site.org/"
+        )));
+        assert!(!list.blocks(&script("https://tracker.net/app.js", "https://site.org/")));
+    }
+
+    #[test]
+    fn test_wildcards_separators_and_anchors() {
+        let list = FilterList::parse("/banner/*/ad_\n|http://plain.example/\n.swf|\n&adtype=^");
+
+        assert!(list.blocks(&script(
+            "https://x.org/banner/300/ad_1.png",
+            "https://x.org/"
+        )));
+        assert!(list.blocks(&script("http://plain.example/a", "https://x.org/")));
+        assert!(!list.blocks(&script("https://plain.example/a", "https://x.org/")));
+        assert!(list.blocks(&script("https://x.org/movie.swf", "https://x.org/")));
+        assert!(!list.blocks(&script("https://x.org/movie.swf?x=1", "https://x.org/")));
+        assert!(!list.blocks(&script("https://x.org/q?a=1&adtype=2", "https://x.org/")));
+        assert!(list.blocks(&script("https://x.org/q?b=2&adtype=&a=1", "https://x.org/")));
+        assert!(list.blocks(&script("https://x.org/q?a=1&adtype=", "https://x.org/")));
+    }
+
+    #[test]
+    fn test_options_and_exceptions() {
+        let list = FilterList::parse(
+            "||cdn.example^$script,third-party\n\
+             ||widgets.example^$domain=news.org|~sports.news.org\n\
+             ||stats.example^\n\
+             @@||stats.example/allowed.js\n\
+             ||weird.example^$rewrite=abp-resource:blank-js\n\
+             example.com##.ad-banner",
+        );
+
+        assert!(list.blocks(&script("https://cdn.example/lib.js", "https://site.org/")));
+        assert!(!list.blocks(&script(
+            "https://cdn.example/lib.js",
+            "https://www.cdn.example/"
+        )));
+        let image = FilterRequest {
+            kind: RequestKind::Image,
+            ..script("https://cdn.example/a.png", "https://site.org/")
+        };
+        assert!(!list.blocks(&image));
+
+        assert!(list.blocks(&script(
+            "https://widgets.example/w.js",
+            "https://www.news.org/"
+        )));
+        assert!(!list.blocks(&script(
+            "https://widgets.example/w.js",
+            "https://sports.news.org/"
+        )));
+        assert!(!list.blocks(&script("https://widgets.example/w.js", "https://blog.org/")));
+
+        assert!(list.blocks(&script("https://stats.example/t.js", "https://site.org/")));
+        assert!(!list.blocks(&script(
+            "https://stats.example/allowed.js",
+            "https://site.org/"
+        )));
+        assert!(!list.blocks(&script("https://weird.example/x.js", "https://site.org/")));
+        assert_eq!(list.len(), 4);
+    }
+
+    #[test]
+    fn test_site_approximation() {
+        assert_eq!(site("www.example.com"), "example.com");
+        assert_eq!(site("shop.example.co.uk"), "example.co.uk");
+        assert_eq!(site("localhost"), "localhost");
+        assert_eq!(
+            &"https://user@cdn.example.com:8443/x"
+                [host_range("https://user@cdn.example.com:8443/x")],
+            "cdn.example.com"
+        );
+    }
+
+    #[test]
+    fn test_bundled_list_and_loading() {
+        let bundled = FilterList::bundled();
+        assert!(!bundled.is_empty());
+        assert!(bundled.blocks(&script(
+            "https://securepubads.g.doubleclick.net/tag/js/gpt.js",
+            "https://news.example/"
+        )));
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("list.txt");
+        std::fs::write(&path, "[Adblock Plus 2.0]\n! only comments\n").unwrap();
+        assert!(FilterList::load(&path)
+            .unwrap_err()
+            .to_string()
+            .contains("contains no network filters"));
+    }
+}
@@ -0,0 +1,516 @@
+//! Favicon, touch icon and `og:image` downloads, for `webshot assets`.
+//!
+//! The icons a page declares with `<link rel="icon">` (in every declared
+//! size), `apple-touch-icon` and `mask-icon` links, and the images of its
+//! `og:image` meta tags are read from the loaded page, so links added by
+//! scripts count too. When no favicon is declared, `/favicon.ico` of the
+//! page's origin is tried, as browsers do. Each asset is downloaded into a
+//! directory next to an [`AssetManifest`] recording where it came from, its
+//! content type, size and pixel dimensions, or why it could not be fetched.
+
+use crate::backend::TextOptions;
+use crate::error::{Result, WebshotError};
+use base64::Engine as _;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::info;
+
+/// File name of the manifest written next to downloaded assets
+pub const ASSET_MANIFEST: &str = "assets.json";
+
+/// Evaluates to a JSON object with the page title, its icon links (with
+/// absolute `href`s) and the URLs of its `og:image` meta tags
+const ASSETS_SCRIPT: &str = r#"
+(() => {
+    const absolute = (href) => {
+        try {
+            return new URL(href, document.baseURI).href;
+        } catch (e) {
+            return null;
+        }
+    };
+    const icons = Array.from(document.querySelectorAll("link[rel][href]"))
+        .map((link) => ({
+            rel: link.getAttribute("rel").toLowerCase().trim().replace(/\s+/g, " "),
+            href: absolute(link.getAttribute("href")),
+            sizes: link.getAttribute("sizes"),
+            type: link.getAttribute("type"),
+        }))
+        .filter((link) => link.href
+            && /(^| )(icon|apple-touch-icon|apple-touch-icon-precomposed|mask-icon)( |$)/.test(link.rel));
+    const images = Array.from(document.querySelectorAll(
+        'meta[property="og:image"], meta[property="og:image:url"], meta[property="og:image:secure_url"], meta[name="og:image"]'
+    ))
+        .map((meta) => absolute(meta.getAttribute("content") || ""))
+        .filter((href) => href);
+    return JSON.stringify({ title: document.title, icons, og_images: [...new Set(images)] });
+})()
+"#;
+
+/// Script returning the page's declared icons and `og:image`s as a JSON
+/// string
+pub fn assets_script() -> &'static str {
+    ASSETS_SCRIPT
+}
+
+/// What an asset is used for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AssetKind {
+    /// `icon`, `shortcut icon` and `mask-icon` links, or `/favicon.ico`
+    Favicon,
+    /// `apple-touch-icon` and `apple-touch-icon-precomposed` links
+    TouchIcon,
+    /// `og:image` meta tags
+    OgImage,
+}
+
+impl AssetKind {
+    /// Prefix of the file names downloaded assets of this kind get
+    fn file_stem(self) -> &'static str {
+        match self {
+            AssetKind::Favicon => "favicon",
+            AssetKind::TouchIcon => "apple-touch-icon",
+            AssetKind::OgImage => "og-image",
+        }
+    }
+}
+
+/// An asset a page links to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AssetLink {
+    pub kind: AssetKind,
+    /// Absolute URL of the asset, possibly a `data:` URL
+    pub url: String,
+    /// `rel` of the `<link>` element, `None` for `og:image`s
+    pub rel: Option<String>,
+    /// `sizes` of the `<link>` element, such as `32x32` or `any`
+    pub sizes: Option<String>,
+    /// `type` of the `<link>` element
+    #[serde(rename = "type")]
+    pub mime: Option<String>,
+    /// `false` for the `/favicon.ico` tried when no favicon is declared
+    pub declared: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScriptIcon {
+    rel: String,
+    href: String,
+    sizes: Option<String>,
+    #[serde(rename = "type")]
+    mime: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScriptResult {
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    icons: Vec<ScriptIcon>,
+    #[serde(default)]
+    og_images: Vec<String>,
+}
+
+/// The assets a page links to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PageAssets {
+    pub url: String,
+    /// The page's `<title>`
+    pub title: String,
+    pub links: Vec<AssetLink>,
+}
+
+impl PageAssets {
+    /// Assets from the JSON [`assets_script`] returned for `url`, with the
+    /// origin's `/favicon.ico` added when no favicon is declared
+    pub fn from_script_result(url: &str, json: &str) -> Result<Self> {
+        let result: ScriptResult = serde_json::from_str(json)?;
+        let mut seen = HashSet::new();
+        let mut links = Vec::new();
+        for icon in result.icons {
+            let kind = if icon.rel.contains("apple-touch-icon") {
+                AssetKind::TouchIcon
+            } else {
+                AssetKind::Favicon
+            };
+            if seen.insert((kind, icon.href.clone(), icon.sizes.clone())) {
+                links.push(AssetLink {
+                    kind,
+                    url: icon.href,
+                    rel: Some(icon.rel),
+                    sizes: icon.sizes.filter(|sizes| !sizes.trim().is_empty()),
+                    mime: icon.mime.filter(|mime| !mime.trim().is_empty()),
+                    declared: true,
+                });
+            }
+        }
+
+        if !links.iter().any(|link| link.kind == AssetKind::Favicon) {
+            let mut favicon = url::Url::parse(url)
+                .map_err(|e| WebshotError::config(format!("Invalid URL {}: {}", url, e)))?;
+            favicon.set_path("/favicon.ico");
+            favicon.set_query(None);
+            favicon.set_fragment(None);
+            links.push(AssetLink {
+                kind: AssetKind::Favicon,
+                url: favicon.to_string(),
+                rel: Some("icon".to_string()),
+                sizes: None,
+                mime: None,
+                declared: false,
+            });
+        }
+
+        links.extend(result.og_images.into_iter().map(|image| AssetLink {
+            kind: AssetKind::OgImage,
+            url: image,
+            rel: None,
+            sizes: None,
+            mime: None,
+            declared: true,
+        }));
+
+        Ok(Self {
+            url: url.to_string(),
+            title: result.title,
+            links,
+        })
+    }
+
+    /// Links to favicons and touch icons when `favicons` is set, and to
+    /// `og:image`s when `og_image` is set
+    pub fn select(&self, favicons: bool, og_image: bool) -> Vec<&AssetLink> {
+        self.links
+            .iter()
+            .filter(|link| match link.kind {
+                AssetKind::Favicon | AssetKind::TouchIcon => favicons,
+                AssetKind::OgImage => og_image,
+            })
+            .collect()
+    }
+}
+
+/// A downloaded asset, or one that could not be downloaded
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AssetEntry {
+    #[serde(flatten)]
+    pub link: AssetLink,
+    /// File name within the asset directory, `None` when the download failed
+    pub file: Option<PathBuf>,
+    /// `Content-Type` of the response, or the media type of a `data:` URL
+    pub content_type: Option<String>,
+    /// Size in bytes
+    pub file_size: Option<u64>,
+    /// Pixel dimensions, `None` for formats that can't be decoded (e.g. SVG)
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub error: Option<String>,
+}
+
+/// Every asset `webshot assets` fetched for a page
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AssetManifest {
+    pub url: String,
+    pub title: String,
+    /// When the assets were fetched, as RFC 3339
+    pub fetched_at: String,
+    pub assets: Vec<AssetEntry>,
+}
+
+impl AssetManifest {
+    pub fn new(page: &PageAssets, assets: Vec<AssetEntry>) -> Self {
+        Self {
+            url: page.url.clone(),
+            title: page.title.clone(),
+            fetched_at: chrono::Utc::now().to_rfc3339(),
+            assets,
+        }
+    }
+
+    /// Number of assets that were downloaded
+    pub fn downloaded(&self) -> usize {
+        self.assets
+            .iter()
+            .filter(|asset| asset.file.is_some())
+            .count()
+    }
+
+    /// Read a manifest written by [`AssetManifest::write`]
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// Write the manifest as JSON
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// HTTP client for [`download_assets`] sending the user agent and extra
+/// headers the page was loaded with
+pub fn asset_client(options: &TextOptions) -> Result<reqwest::Client> {
+    let mut headers = reqwest::header::HeaderMap::new();
+    for (name, value) in &options.headers {
+        let invalid = |e: &dyn std::fmt::Display| {
+            WebshotError::config(format!("Invalid header {}: {}", name, e))
+        };
+        headers.insert(
+            reqwest::header::HeaderName::from_bytes(name.as_bytes()).map_err(|e| invalid(&e))?,
+            reqwest::header::HeaderValue::from_str(value).map_err(|e| invalid(&e))?,
+        );
+    }
+    let mut client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(options.timeout.max(1)))
+        .default_headers(headers);
+    if let Some(user_agent) = &options.user_agent {
+        client = client.user_agent(user_agent);
+    }
+    Ok(client.build()?)
+}
+
+/// Download `links` into `dir` with `client`, naming files after their kind
+/// and declared size (`favicon-32x32.png`, `og-image.jpg`). Failed downloads
+/// are recorded in their entry rather than returned as errors, so one broken
+/// icon doesn't lose the others.
+pub async fn download_assets(
+    client: &reqwest::Client,
+    links: &[&AssetLink],
+    dir: &Path,
+) -> Result<Vec<AssetEntry>> {
+    std::fs::create_dir_all(dir)?;
+    let mut used_names = HashSet::new();
+    let mut entries = Vec::new();
+
+    for link in links {
+        info!("Downloading {}", link.url);
+        let mut entry = AssetEntry {
+            link: (*link).clone(),
+            file: None,
+            content_type: None,
+            file_size: None,
+            width: None,
+            height: None,
+            error: None,
+        };
+        match fetch(client, &link.url).await {
+            Ok((content_type, bytes)) => {
+                let name = file_name(link, content_type.as_deref(), &mut used_names);
+                std::fs::write(dir.join(&name), &bytes)?;
+                if let Ok(image) = image::load_from_memory(&bytes) {
+                    entry.width = Some(image.width());
+                    entry.height = Some(image.height());
+                }
+                entry.file = Some(PathBuf::from(name));
+                entry.content_type = content_type;
+                entry.file_size = Some(bytes.len() as u64);
+            }
+            Err(error) => entry.error = Some(error.to_string()),
+        }
+        entries.push(entry);
+    }
+
+    Ok(entries)
+}
+
+/// Body and content type of `url`, decoded in place for `data:` URLs
+async fn fetch(client: &reqwest::Client, url: &str) -> Result<(Option<String>, Vec<u8>)> {
+    if url.starts_with("data:") {
+        return decode_data_url(url);
+    }
+    let response = client.get(url).send().await?.error_for_status()?;
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string());
+    Ok((content_type, response.bytes().await?.to_vec()))
+}
+
+/// Media type and content of a `data:` URL
+fn decode_data_url(url: &str) -> Result<(Option<String>, Vec<u8>)> {
+    let (header, data) = url
+        .trim_start_matches("data:")
+        .split_once(',')
+        .ok_or_else(|| WebshotError::config("Malformed data: URL"))?;
+    let (media_type, base64) = match header.strip_suffix(";base64") {
+        Some(media_type) => (media_type, true),
+        None => (header, false),
+    };
+    let media_type = media_type.split(';').next().unwrap_or_default();
+    let bytes = if base64 {
+        base64::engine::general_purpose::STANDARD
+            .decode(data.trim())
+            .map_err(|e| WebshotError::config(format!("Invalid base64 in data: URL: {}", e)))?
+    } else {
+        percent_decode(data)
+    };
+    Ok((
+        (!media_type.is_empty()).then(|| media_type.to_string()),
+        bytes,
+    ))
+}
+
+fn percent_decode(data: &str) -> Vec<u8> {
+    let bytes = data.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    decoded
+}
+
+/// A file name for `link` that isn't in `used` yet, with an extension from
+/// `content_type`, the declared type or the URL
+fn file_name(link: &AssetLink, content_type: Option<&str>, used: &mut HashSet<String>) -> String {
+    let mut stem = link.kind.file_stem().to_string();
+    if let Some(sizes) = &link.sizes {
+        let sizes: String = sizes
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect();
+        if !sizes.is_empty() {
+            stem = format!("{}-{}", stem, sizes.to_lowercase());
+        }
+    }
+    if link.rel.as_deref() == Some("mask-icon") {
+        stem = format!("{}-mask", stem);
+    }
+    let extension = content_type
+        .and_then(extension_for)
+        .or_else(|| link.mime.as_deref().and_then(extension_for))
+        .or_else(|| url_extension(&link.url))
+        .unwrap_or_else(|| "bin".to_string());
+
+    let mut name = format!("{}.{}", stem, extension);
+    let mut counter = 2;
+    while !used.insert(name.clone()) {
+        name = format!("{}-{}.{}", stem, counter, extension);
+        counter += 1;
+    }
+    name
+}
+
+fn extension_for(mime: &str) -> Option<String> {
+    let extension = match mime.split(';').next()?.trim().to_lowercase().as_str() {
+        "image/png" => "png",
+        "image/jpeg" | "image/jpg" => "jpg",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        "image/avif" => "avif",
+        "image/svg+xml" => "svg",
+        "image/x-icon" | "image/vnd.microsoft.icon" | "image/ico" => "ico",
+        _ => return None,
+    };
+    Some(extension.to_string())
+}
+
+fn url_extension(url: &str) -> Option<String> {
+    let path = url::Url::parse(url).ok()?.path().to_string();
+    let extension = Path::new(&path).extension()?.to_str()?.to_lowercase();
+    (extension.len() <= 4 && extension.chars().all(|c| c.is_ascii_alphanumeric()))
+        .then_some(extension)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: &str = r#"{"title": "Example", "icons": [
+        {"rel": "icon", "href": "https://example.com/icon-32.png", "sizes": "32x32", "type": "image/png"},
+        {"rel": "icon", "href": "https://example.com/icon-16.png", "sizes": "16x16", "type": "image/png"},
+        {"rel": "shortcut icon", "href": "https://example.com/icon-32.png", "sizes": "32x32", "type": null},
+        {"rel": "apple-touch-icon", "href": "https://cdn.example.com/touch", "sizes": "180x180", "type": null},
+        {"rel": "mask-icon", "href": "data:image/svg+xml,%3Csvg%3E%3C/svg%3E", "sizes": null, "type": null}
+    ], "og_images": ["https://cdn.example.com/social.jpg"]}"#;
+
+    #[test]
+    fn test_page_assets_from_script_result() {
+        let page = PageAssets::from_script_result("https://example.com/blog/post", PAGE).unwrap();
+        assert_eq!(page.title, "Example");
+        let kinds: Vec<_> = page.links.iter().map(|link| link.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                AssetKind::Favicon,
+                AssetKind::Favicon,
+                AssetKind::TouchIcon,
+                AssetKind::Favicon,
+                AssetKind::OgImage
+            ]
+        );
+        assert_eq!(page.select(true, false).len(), 4);
+        assert_eq!(
+            page.select(false, true)[0].url,
+            "https://cdn.example.com/social.jpg"
+        );
+
+        let bare = PageAssets::from_script_result(
+            "https://example.com/blog/post?page=2",
+            r#"{"title": "", "icons": [], "og_images": []}"#,
+        )
+        .unwrap();
+        assert_eq!(bare.links.len(), 1);
+        assert_eq!(bare.links[0].url, "https://example.com/favicon.ico");
+        assert!(!bare.links[0].declared);
+    }
+
+    #[test]
+    fn test_file_names_and_data_urls() {
+        let page = PageAssets::from_script_result("https://example.com", PAGE).unwrap();
+        let mut used = HashSet::new();
+        let names: Vec<_> = page
+            .links
+            .iter()
+            .map(|link| file_name(link, None, &mut used))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "favicon-32x32.png",
+                "favicon-16x16.png",
+                "apple-touch-icon-180x180.bin",
+                "favicon-mask.bin",
+                "og-image.jpg"
+            ]
+        );
+        assert_eq!(
+            file_name(&page.links[0], Some("image/webp"), &mut used),
+            "favicon-32x32.webp"
+        );
+        assert_eq!(
+            file_name(&page.links[0], None, &mut used),
+            "favicon-32x32-2.png"
+        );
+
+        let (mime, bytes) = decode_data_url("data:image/svg+xml,%3Csvg%3E%3C/svg%3E").unwrap();
+        assert_eq!(mime.as_deref(), Some("image/svg+xml"));
+        assert_eq!(bytes, b"<svg></svg>");
+        let (mime, bytes) = decode_data_url("data:image/png;base64,aGVsbG8=").unwrap();
+        assert_eq!(
+            (mime.as_deref(), bytes.as_slice()),
+            (Some("image/png"), &b"hello"[..])
+        );
+        assert!(decode_data_url("data:image/png;base64").is_err());
+    }
+}
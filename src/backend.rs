@@ -0,0 +1,251 @@
+//! Engine-independent capture interface.
+//!
+//! [`BrowserBackend`] is what capture commands need from a browser engine.
+//! [`Browser`](crate::Browser) implements it over Chrome's DevTools protocol;
+//! other engines (Firefox over WebDriver BiDi, WebKit through its driver) can
+//! implement it to render the same pages for cross-engine comparison.
+
+use crate::blocking::RequestBlocker;
+use crate::config::AuthConfig;
+use crate::error::Result;
+use crate::network_idle::NetworkIdle;
+use crate::paper::PaperSize;
+use crate::screenshot::ScreenshotOptions;
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+
+/// PDF generation options
+#[derive(Debug, Clone)]
+pub struct PdfOptions {
+    /// Paper size
+    pub paper: PaperSize,
+    /// Landscape orientation
+    pub landscape: bool,
+    /// Print background graphics
+    pub background: bool,
+    /// Scale factor (0.1 to 2.0)
+    pub scale: f64,
+    /// Keep hyperlinks and internal anchors clickable, adding link
+    /// annotations Chrome left out; when off, every link is removed
+    pub links: bool,
+    /// Embed a document outline (bookmarks) built from the page's headings
+    pub outline: bool,
+    /// JavaScript to execute before printing
+    pub javascript: Option<String>,
+    /// Wait condition to satisfy before printing
+    pub wait_for: Option<String>,
+    /// Wait for every frame's network to go quiet before printing
+    pub network_idle: Option<NetworkIdle>,
+    /// Seconds to wait before printing
+    pub wait: u64,
+    /// Timeout in seconds
+    pub timeout: u64,
+    /// Custom user agent
+    pub user_agent: Option<String>,
+    /// HTTP basic auth credentials answered when the page challenges
+    pub auth: Option<AuthConfig>,
+    /// Extra HTTP headers sent with every request
+    pub headers: HashMap<String, String>,
+    /// Requests aborted while the page loads
+    pub blocker: RequestBlocker,
+    /// File that the page's network traffic is recorded to as a HAR
+    pub har: Option<PathBuf>,
+}
+
+impl Default for PdfOptions {
+    fn default() -> Self {
+        Self {
+            paper: PaperSize::default(),
+            landscape: false,
+            background: false,
+            scale: 1.0,
+            links: true,
+            outline: false,
+            javascript: None,
+            wait_for: None,
+            network_idle: None,
+            wait: 0,
+            timeout: 30,
+            user_agent: None,
+            auth: None,
+            headers: HashMap::new(),
+            blocker: RequestBlocker::default(),
+            har: None,
+        }
+    }
+}
+
+/// Text extraction options
+#[derive(Debug, Clone)]
+pub struct TextOptions {
+    /// Only extract the text of the element matching this CSS selector
+    pub selector: Option<String>,
+    /// JavaScript to execute before extracting
+    pub javascript: Option<String>,
+    /// Wait condition to satisfy before extracting
+    pub wait_for: Option<String>,
+    /// Wait for every frame's network to go quiet before extracting
+    pub network_idle: Option<NetworkIdle>,
+    /// Seconds to wait before extracting
+    pub wait: u64,
+    /// Timeout in seconds
+    pub timeout: u64,
+    /// Custom user agent
+    pub user_agent: Option<String>,
+    /// HTTP basic auth credentials answered when the page challenges
+    pub auth: Option<AuthConfig>,
+    /// Extra HTTP headers sent with every request
+    pub headers: HashMap<String, String>,
+    /// Requests aborted while the page loads
+    pub blocker: RequestBlocker,
+}
+
+impl Default for TextOptions {
+    fn default() -> Self {
+        Self {
+            selector: None,
+            javascript: None,
+            wait_for: None,
+            network_idle: None,
+            wait: 0,
+            timeout: 30,
+            user_agent: None,
+            auth: None,
+            headers: HashMap::new(),
+            blocker: RequestBlocker::default(),
+        }
+    }
+}
+
+/// A screenshot written to disk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capture {
+    /// Path the screenshot was written to, page tokens resolved
+    pub path: PathBuf,
+    /// HTTP status of the page's main document, when one was received
+    pub status: Option<u16>,
+}
+
+/// A browser engine that can capture pages
+pub trait BrowserBackend {
+    /// Engine name, such as `chromium`, for reports comparing engines
+    fn engine(&self) -> &str;
+
+    /// Capture `url` to `output_path`, returning the path written and the
+    /// document's HTTP status
+    fn capture(
+        &self,
+        url: &str,
+        output_path: &Path,
+        options: &ScreenshotOptions,
+    ) -> impl Future<Output = Result<Capture>>;
+
+    /// Capture `url` to `output_path`, returning the path written
+    fn screenshot(
+        &self,
+        url: &str,
+        output_path: &Path,
+        options: &ScreenshotOptions,
+    ) -> impl Future<Output = Result<PathBuf>> {
+        async move { Ok(self.capture(url, output_path, options).await?.path) }
+    }
+
+    /// Print `url` to a PDF at `output_path`
+    fn pdf(
+        &self,
+        url: &str,
+        output_path: &Path,
+        options: &PdfOptions,
+    ) -> impl Future<Output = Result<()>>;
+
+    /// Extract the text content of `url`
+    fn extract_text(
+        &self,
+        url: &str,
+        options: &TextOptions,
+    ) -> impl Future<Output = Result<String>>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Records what it was asked to capture
+    #[derive(Default)]
+    struct RecordingBackend {
+        calls: Mutex<Vec<String>>,
+    }
+
+    impl BrowserBackend for RecordingBackend {
+        fn engine(&self) -> &str {
+            "recording"
+        }
+
+        async fn capture(
+            &self,
+            url: &str,
+            output_path: &Path,
+            options: &ScreenshotOptions,
+        ) -> Result<Capture> {
+            self.calls.lock().unwrap().push(format!(
+                "screenshot {} {}x{}",
+                url, options.width, options.height
+            ));
+            Ok(Capture {
+                path: output_path.to_path_buf(),
+                status: Some(200),
+            })
+        }
+
+        async fn pdf(&self, url: &str, _output_path: &Path, options: &PdfOptions) -> Result<()> {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("pdf {} {}", url, options.paper));
+            Ok(())
+        }
+
+        async fn extract_text(&self, url: &str, _options: &TextOptions) -> Result<String> {
+            self.calls.lock().unwrap().push(format!("text {}", url));
+            Ok("Example Domain".to_string())
+        }
+    }
+
+    async fn capture_everything<B: BrowserBackend>(backend: &B, url: &str) -> Result<String> {
+        backend
+            .screenshot(url, Path::new("page.png"), &ScreenshotOptions::new())
+            .await?;
+        backend
+            .pdf(
+                url,
+                Path::new("page.pdf"),
+                &PdfOptions {
+                    paper: PaperSize::A4,
+                    ..Default::default()
+                },
+            )
+            .await?;
+        backend.extract_text(url, &TextOptions::default()).await
+    }
+
+    #[tokio::test]
+    async fn test_captures_run_through_any_backend() {
+        let backend = RecordingBackend::default();
+        let text = capture_everything(&backend, "https://example.com")
+            .await
+            .unwrap();
+
+        assert_eq!(backend.engine(), "recording");
+        assert_eq!(text, "Example Domain");
+        assert_eq!(
+            *backend.calls.lock().unwrap(),
+            [
+                "screenshot https://example.com 1280x800",
+                "pdf https://example.com A4",
+                "text https://example.com",
+            ]
+        );
+    }
+}
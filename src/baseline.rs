@@ -0,0 +1,438 @@
+//! Shared baseline images for visual regression runs from several pipelines.
+//!
+//! Every stored baseline carries an opaque version. Comparisons record the
+//! version they ran against, and accepting a new baseline states which
+//! version it replaces: if another pipeline has accepted a newer baseline in
+//! the meantime, the write is refused with
+//! [`WebshotError::BaselineConflict`] instead of silently clobbering it.
+
+use crate::error::{Result, WebshotError};
+use std::fs::{self, OpenOptions};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Prefix selecting the S3 store, e.g. `s3://bucket/baselines`
+pub const S3_PREFIX: &str = "s3://";
+
+/// How long a local write waits for another writer's lock
+const LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+const LOCK_POLL: Duration = Duration::from_millis(50);
+
+/// A baseline image and the version it was stored as
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Baseline {
+    pub data: Vec<u8>,
+    pub version: String,
+}
+
+/// What a baseline write expects to replace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PutCondition {
+    /// No baseline exists yet
+    Create,
+    /// The stored baseline is still at this version
+    Matches(String),
+    /// Replace whatever is stored (`--force`)
+    Overwrite,
+}
+
+impl PutCondition {
+    /// Condition for replacing the version a comparison ran against, where
+    /// `None` means there was no baseline
+    pub fn expecting(version: Option<String>) -> Self {
+        version.map_or(Self::Create, Self::Matches)
+    }
+}
+
+/// Where baselines live: a local (possibly network-mounted) directory, or an
+/// S3 bucket when built with the `s3-baselines` feature
+#[derive(Debug)]
+pub enum BaselineStore {
+    Local(LocalStore),
+    #[cfg(feature = "s3-baselines")]
+    S3(s3::S3Store),
+}
+
+impl BaselineStore {
+    /// Open `s3://bucket[/prefix]` or a local directory path
+    pub fn open(location: &str) -> Result<Self> {
+        if let Some(bucket) = location.strip_prefix(S3_PREFIX) {
+            return Self::open_s3(bucket);
+        }
+        Ok(Self::Local(LocalStore::new(location)))
+    }
+
+    #[cfg(feature = "s3-baselines")]
+    fn open_s3(bucket: &str) -> Result<Self> {
+        s3::S3Store::new(bucket).map(Self::S3)
+    }
+
+    #[cfg(not(feature = "s3-baselines"))]
+    fn open_s3(bucket: &str) -> Result<Self> {
+        Err(WebshotError::config(format!(
+            "Cannot open s3://{}: webshot was built without the `s3-baselines` feature",
+            bucket
+        )))
+    }
+
+    /// The directory store, unless baselines live elsewhere
+    pub fn local(&self) -> Option<&LocalStore> {
+        match self {
+            Self::Local(store) => Some(store),
+            #[cfg(feature = "s3-baselines")]
+            Self::S3(_) => None,
+        }
+    }
+
+    /// The stored baseline, or `None` if there is none yet
+    pub async fn get(&self, name: &str) -> Result<Option<Baseline>> {
+        validate_name(name)?;
+        match self {
+            Self::Local(store) => store.get(name).await,
+            #[cfg(feature = "s3-baselines")]
+            Self::S3(store) => store.get(name).await,
+        }
+    }
+
+    /// Store a new baseline if `condition` holds, returning its version
+    pub async fn put(&self, name: &str, data: &[u8], condition: &PutCondition) -> Result<String> {
+        validate_name(name)?;
+        match self {
+            Self::Local(store) => store.put(name, data, condition).await,
+            #[cfg(feature = "s3-baselines")]
+            Self::S3(store) => store.put(name, data, condition).await,
+        }
+    }
+}
+
+/// Baseline names are relative paths such as `home/desktop.png`
+fn validate_name(name: &str) -> Result<()> {
+    let path = Path::new(name);
+    let valid = !name.is_empty()
+        && path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+    if valid {
+        Ok(())
+    } else {
+        Err(WebshotError::config(format!(
+            "Invalid baseline name `{}`: expected a relative path without `..`",
+            name
+        )))
+    }
+}
+
+fn conflict(name: &str, current: Option<String>) -> WebshotError {
+    WebshotError::BaselineConflict {
+        name: name.to_string(),
+        current: current.unwrap_or_else(|| "(none)".to_string()),
+    }
+}
+
+/// Baselines in a directory: `<name>` holds the image and `<name>.version` a
+/// counter bumped on every write. Access serializes on `<name>.lock`, which
+/// works across processes and machines sharing the directory.
+#[derive(Debug)]
+pub struct LocalStore {
+    root: PathBuf,
+}
+
+impl LocalStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    async fn get(&self, name: &str) -> Result<Option<Baseline>> {
+        let path = self.root.join(name);
+        if !path.exists() {
+            return Ok(None);
+        }
+        // The image and its version file are written separately
+        let _lock = FileLock::acquire(with_suffix(&path, ".lock")).await?;
+        let Some(version) = self.version(name)? else {
+            return Ok(None);
+        };
+        Ok(Some(Baseline {
+            data: fs::read(path)?,
+            version: version.to_string(),
+        }))
+    }
+
+    async fn put(&self, name: &str, data: &[u8], condition: &PutCondition) -> Result<String> {
+        let path = self.root.join(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let _lock = FileLock::acquire(with_suffix(&path, ".lock")).await?;
+        let current = self.version(name)?;
+        let allowed = match condition {
+            PutCondition::Create => current.is_none(),
+            PutCondition::Matches(expected) => {
+                current.is_some_and(|version| version.to_string() == *expected)
+            }
+            PutCondition::Overwrite => true,
+        };
+        if !allowed {
+            return Err(conflict(name, current.map(|v| v.to_string())));
+        }
+
+        let version = current.unwrap_or(0) + 1;
+        write_atomic(&path, data)?;
+        write_atomic(
+            &with_suffix(&path, ".version"),
+            version.to_string().as_bytes(),
+        )?;
+        Ok(version.to_string())
+    }
+
+    /// The image and version files holding baseline `name`
+    pub fn files(&self, name: &str) -> [PathBuf; 2] {
+        let path = self.root.join(name);
+        let version = with_suffix(&path, ".version");
+        [path, version]
+    }
+
+    fn version(&self, name: &str) -> Result<Option<u64>> {
+        let path = with_suffix(&self.root.join(name), ".version");
+        match fs::read_to_string(&path) {
+            Ok(version) => version.trim().parse().map(Some).map_err(|_| {
+                WebshotError::baseline(format!("Corrupt version file {}", path.display()))
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+/// Write through a temporary file and rename, so readers never see a
+/// partially written baseline
+fn write_atomic(path: &Path, data: &[u8]) -> Result<()> {
+    let temp = with_suffix(path, ".tmp");
+    fs::write(&temp, data)?;
+    fs::rename(&temp, path)?;
+    Ok(())
+}
+
+/// Exclusive lock held by creating a file, removed on drop
+struct FileLock {
+    path: PathBuf,
+}
+
+impl FileLock {
+    async fn acquire(path: PathBuf) -> Result<Self> {
+        let start = Instant::now();
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(_) => return Ok(Self { path }),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    if start.elapsed() > LOCK_TIMEOUT {
+                        return Err(WebshotError::timeout(format!(
+                            "baseline lock {} (remove it if no other webshot is writing)",
+                            path.display()
+                        )));
+                    }
+                    tokio::time::sleep(LOCK_POLL).await;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(feature = "s3-baselines")]
+mod s3 {
+    use super::{conflict, Baseline, PutCondition};
+    use crate::error::{Result, WebshotError};
+    use object_store::aws::{AmazonS3, AmazonS3Builder, S3ConditionalPut};
+    use object_store::path::Path;
+    use object_store::{ObjectStore, PutMode, PutOptions, PutPayload, UpdateVersion};
+
+    /// Baselines as objects under a bucket prefix. Versions are ETags and
+    /// writes use S3 conditional requests, so no lock is needed. Credentials
+    /// and region come from the standard `AWS_*` environment variables.
+    #[derive(Debug)]
+    pub struct S3Store {
+        store: AmazonS3,
+        prefix: String,
+    }
+
+    impl S3Store {
+        pub fn new(location: &str) -> Result<Self> {
+            let (bucket, prefix) = location.split_once('/').unwrap_or((location, ""));
+            let store = AmazonS3Builder::from_env()
+                .with_bucket_name(bucket)
+                .with_conditional_put(S3ConditionalPut::ETagMatch)
+                .build()
+                .map_err(|e| WebshotError::baseline(format!("Invalid S3 store: {}", e)))?;
+            Ok(Self {
+                store,
+                prefix: prefix.trim_matches('/').to_string(),
+            })
+        }
+
+        fn path(&self, name: &str) -> Path {
+            if self.prefix.is_empty() {
+                Path::from(name)
+            } else {
+                Path::from(format!("{}/{}", self.prefix, name))
+            }
+        }
+
+        pub async fn get(&self, name: &str) -> Result<Option<Baseline>> {
+            let result = match self.store.get(&self.path(name)).await {
+                Ok(result) => result,
+                Err(object_store::Error::NotFound { .. }) => return Ok(None),
+                Err(e) => return Err(s3_error(e)),
+            };
+            let version = result.meta.e_tag.clone().unwrap_or_default();
+            let data = result.bytes().await.map_err(s3_error)?;
+            Ok(Some(Baseline {
+                data: data.to_vec(),
+                version,
+            }))
+        }
+
+        pub async fn put(
+            &self,
+            name: &str,
+            data: &[u8],
+            condition: &PutCondition,
+        ) -> Result<String> {
+            let mode = match condition {
+                PutCondition::Create => PutMode::Create,
+                PutCondition::Matches(e_tag) => PutMode::Update(UpdateVersion {
+                    e_tag: Some(e_tag.clone()),
+                    version: None,
+                }),
+                PutCondition::Overwrite => PutMode::Overwrite,
+            };
+            let options = PutOptions {
+                mode,
+                ..Default::default()
+            };
+            match self
+                .store
+                .put_opts(&self.path(name), PutPayload::from(data.to_vec()), options)
+                .await
+            {
+                Ok(result) => Ok(result.e_tag.unwrap_or_default()),
+                Err(
+                    object_store::Error::AlreadyExists { .. }
+                    | object_store::Error::Precondition { .. },
+                ) => {
+                    let current = self.get(name).await?.map(|baseline| baseline.version);
+                    Err(conflict(name, current))
+                }
+                Err(e) => Err(s3_error(e)),
+            }
+        }
+    }
+
+    fn s3_error(e: object_store::Error) -> WebshotError {
+        WebshotError::baseline(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_local_store_versions_and_conflicts() {
+        let dir = TempDir::new().unwrap();
+        let store = BaselineStore::open(dir.path().to_str().unwrap()).unwrap();
+
+        assert_eq!(store.get("home/desktop.png").await.unwrap(), None);
+        let first = store
+            .put("home/desktop.png", b"v1", &PutCondition::Create)
+            .await
+            .unwrap();
+        assert_eq!(first, "1");
+
+        // A second pipeline that also saw no baseline must not clobber it
+        let error = store
+            .put("home/desktop.png", b"other", &PutCondition::Create)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            &error,
+            WebshotError::BaselineConflict { current, .. } if current == "1"
+        ));
+
+        let second = store
+            .put(
+                "home/desktop.png",
+                b"v2",
+                &PutCondition::Matches(first.clone()),
+            )
+            .await
+            .unwrap();
+        assert_eq!(second, "2");
+        assert!(store
+            .put("home/desktop.png", b"stale", &PutCondition::Matches(first))
+            .await
+            .is_err());
+
+        let forced = store
+            .put("home/desktop.png", b"v3", &PutCondition::Overwrite)
+            .await
+            .unwrap();
+        assert_eq!(
+            store.get("home/desktop.png").await.unwrap(),
+            Some(Baseline {
+                data: b"v3".to_vec(),
+                version: forced,
+            })
+        );
+        assert!(!dir.path().join("home/desktop.png.lock").exists());
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_accepts_of_one_version_have_one_winner() {
+        let dir = TempDir::new().unwrap();
+        let store = BaselineStore::open(dir.path().to_str().unwrap()).unwrap();
+        store
+            .put("page.png", b"v1", &PutCondition::Create)
+            .await
+            .unwrap();
+
+        let condition = PutCondition::expecting(Some("1".to_string()));
+        let images: Vec<Vec<u8>> = (0..4).map(|i| vec![i]).collect();
+        let results = futures::future::join_all(
+            images
+                .iter()
+                .map(|image| store.put("page.png", image, &condition)),
+        )
+        .await;
+        assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 1);
+    }
+
+    #[test]
+    fn test_names_must_stay_inside_store() {
+        for name in ["", "../escape.png", "/abs.png", "a/../../b.png"] {
+            assert!(validate_name(name).is_err(), "{}", name);
+        }
+        assert!(validate_name("team/home.png").is_ok());
+    }
+
+    #[cfg(not(feature = "s3-baselines"))]
+    #[test]
+    fn test_s3_requires_feature() {
+        let error = BaselineStore::open("s3://bucket/baselines").unwrap_err();
+        assert!(error.to_string().contains("`s3-baselines` feature"));
+    }
+}
@@ -0,0 +1,218 @@
+use crate::adblock::{FilterList, FilterRequest, RequestKind};
+use crate::error::{Result, WebshotError};
+use headless_chrome::protocol::cdp::Fetch::RequestPattern;
+use headless_chrome::protocol::cdp::Network::ResourceType;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Class of subresource that can be blocked from loading
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlockedResource {
+    #[serde(alias = "image")]
+    Images,
+    #[serde(alias = "font")]
+    Fonts,
+    Media,
+    #[serde(alias = "stylesheet")]
+    Stylesheets,
+    #[serde(alias = "script")]
+    Scripts,
+    /// XHR, fetch and EventSource requests
+    Xhr,
+}
+
+impl BlockedResource {
+    /// CDP resource types covered by this class
+    pub fn resource_types(&self) -> &'static [ResourceType] {
+        match self {
+            BlockedResource::Images => &[ResourceType::Image],
+            BlockedResource::Fonts => &[ResourceType::Font],
+            BlockedResource::Media => &[ResourceType::Media, ResourceType::TextTrack],
+            BlockedResource::Stylesheets => &[ResourceType::Stylesheet],
+            BlockedResource::Scripts => &[ResourceType::Script],
+            BlockedResource::Xhr => &[
+                ResourceType::Xhr,
+                ResourceType::Fetch,
+                ResourceType::EventSource,
+            ],
+        }
+    }
+}
+
+impl fmt::Display for BlockedResource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            BlockedResource::Images => "images",
+            BlockedResource::Fonts => "fonts",
+            BlockedResource::Media => "media",
+            BlockedResource::Stylesheets => "stylesheets",
+            BlockedResource::Scripts => "scripts",
+            BlockedResource::Xhr => "xhr",
+        })
+    }
+}
+
+impl FromStr for BlockedResource {
+    type Err = WebshotError;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "images" | "image" => Ok(Self::Images),
+            "fonts" | "font" => Ok(Self::Fonts),
+            "media" => Ok(Self::Media),
+            "stylesheets" | "stylesheet" | "css" => Ok(Self::Stylesheets),
+            "scripts" | "script" => Ok(Self::Scripts),
+            "xhr" | "fetch" => Ok(Self::Xhr),
+            _ => Err(WebshotError::config(format!(
+                "Unknown resource type: {}. Supported: images, fonts, media, stylesheets, scripts, xhr",
+                value
+            ))),
+        }
+    }
+}
+
+/// Filter list request kind for a subresource type. Documents reaching the
+/// blocker are frames, as main-frame navigations are never blocked.
+fn request_kind(resource_type: &ResourceType) -> RequestKind {
+    match resource_type {
+        ResourceType::Script => RequestKind::Script,
+        ResourceType::Image => RequestKind::Image,
+        ResourceType::Stylesheet => RequestKind::Stylesheet,
+        ResourceType::Xhr | ResourceType::Fetch | ResourceType::EventSource => {
+            RequestKind::XmlHttpRequest
+        }
+        ResourceType::Document => RequestKind::Subdocument,
+        ResourceType::Media | ResourceType::TextTrack => RequestKind::Media,
+        ResourceType::Font => RequestKind::Font,
+        ResourceType::WebSocket => RequestKind::WebSocket,
+        ResourceType::Ping => RequestKind::Ping,
+        _ => RequestKind::Other,
+    }
+}
+
+/// Decides which paused requests are failed instead of continued
+#[derive(Debug, Clone, Default)]
+pub struct RequestBlocker {
+    resources: Vec<BlockedResource>,
+    filters: Option<Arc<FilterList>>,
+}
+
+impl RequestBlocker {
+    /// Block every request of the given resource classes
+    pub fn new(resources: &[BlockedResource]) -> Self {
+        Self {
+            resources: resources.to_vec(),
+            filters: None,
+        }
+    }
+
+    /// Also block requests matched by an ad and tracker filter list
+    pub fn with_filters(mut self, filters: Option<Arc<FilterList>>) -> Self {
+        self.filters = filters;
+        self
+    }
+
+    /// Whether no request is ever blocked
+    pub fn is_empty(&self) -> bool {
+        self.resources.is_empty() && self.filters.is_none()
+    }
+
+    /// Whether a request should be failed. `page_url` is the top-level page,
+    /// used by third-party and `domain=` filter options.
+    pub fn blocks(&self, url: &str, resource_type: &ResourceType, page_url: Option<&str>) -> bool {
+        let blocked_type = self
+            .resources
+            .iter()
+            .any(|resource| resource.resource_types().contains(resource_type));
+
+        blocked_type
+            || self.filters.as_ref().is_some_and(|filters| {
+                filters.blocks(&FilterRequest {
+                    url,
+                    kind: request_kind(resource_type),
+                    page_url,
+                })
+            })
+    }
+
+    /// Fetch patterns pausing only the blocked resource types, so other
+    /// requests are not round-tripped through the interceptor. `None` when
+    /// every request must be paused for filter list matching.
+    pub fn fetch_patterns(&self) -> Option<Vec<RequestPattern>> {
+        if self.filters.is_some() {
+            return None;
+        }
+        Some(
+            self.resources
+                .iter()
+                .flat_map(BlockedResource::resource_types)
+                .map(|resource_type| RequestPattern {
+                    url_pattern: None,
+                    resource_Type: Some(resource_type.clone()),
+                    request_stage: None,
+                })
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blocked_resource_parsing() {
+        assert_eq!(
+            "Images".parse::<BlockedResource>().unwrap(),
+            BlockedResource::Images
+        );
+        assert_eq!(
+            "font".parse::<BlockedResource>().unwrap(),
+            BlockedResource::Fonts
+        );
+        assert!("documents"
+            .parse::<BlockedResource>()
+            .unwrap_err()
+            .to_string()
+            .contains("Unknown resource type"));
+
+        let config: Vec<BlockedResource> = serde_yaml::from_str("[images, font, media]").unwrap();
+        assert_eq!(
+            config,
+            vec![
+                BlockedResource::Images,
+                BlockedResource::Fonts,
+                BlockedResource::Media
+            ]
+        );
+    }
+
+    #[test]
+    fn test_blocker_matches_resource_types() {
+        let blocker = RequestBlocker::new(&[BlockedResource::Images, BlockedResource::Xhr]);
+        let blocks = |resource_type| blocker.blocks("https://example.com/x", &resource_type, None);
+
+        assert!(blocks(ResourceType::Image));
+        assert!(blocks(ResourceType::Fetch));
+        assert!(!blocks(ResourceType::Document));
+        assert!(!blocks(ResourceType::Font));
+        assert_eq!(blocker.fetch_patterns().unwrap().len(), 4);
+        assert!(RequestBlocker::default().is_empty());
+    }
+
+    #[test]
+    fn test_blocker_with_filter_list() {
+        let filters = Arc::new(FilterList::parse("||ads.example^$script"));
+        let blocker = RequestBlocker::default().with_filters(Some(filters));
+        let page = Some("https://news.example/");
+
+        assert!(!blocker.is_empty());
+        assert!(blocker.fetch_patterns().is_none());
+        assert!(blocker.blocks("https://ads.example/a.js", &ResourceType::Script, page));
+        assert!(!blocker.blocks("https://ads.example/a.png", &ResourceType::Image, page));
+        assert!(!blocker.blocks("https://cdn.example/a.js", &ResourceType::Script, page));
+    }
+}
@@ -0,0 +1,364 @@
+//! PDF books merged from many printed pages.
+//!
+//! A `pdfs` section of a batch config prints each of its pages to its own
+//! PDF. With a `merge_output`, those PDFs become the chapters of one book: a
+//! generated table of contents page comes first, listing every chapter with
+//! the page it starts on, followed by the chapters in config order, and the
+//! book's outline (bookmarks) links each chapter's first page.
+
+use crate::backend::PdfOptions;
+use crate::browser::Browser;
+use crate::error::{Result, WebshotError};
+use crate::output::OutputHandler;
+use lopdf::{Bookmark, Dictionary, Document, Object, ObjectId};
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+/// Page attributes a page inherits from its ancestors in the page tree
+const INHERITED_PAGE_ATTRIBUTES: [&[u8]; 4] = [b"Resources", b"MediaBox", b"CropBox", b"Rotate"];
+
+/// A printed page of a book
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chapter {
+    /// Title in the table of contents and the outline
+    pub title: String,
+    pub url: String,
+    pub path: PathBuf,
+}
+
+/// The merged PDF and where each chapter starts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BookReport {
+    pub path: PathBuf,
+    /// Page count, table of contents included
+    pub pages: usize,
+    /// First page of each chapter, 1-based
+    pub chapter_pages: Vec<usize>,
+}
+
+/// Number of pages of a PDF file
+pub fn page_count(path: &Path) -> Result<usize> {
+    Ok(load(path)?.get_pages().len())
+}
+
+/// Title in a PDF's document information, which Chrome fills from the
+/// printed page's `<title>`
+pub fn pdf_title(path: &Path) -> Option<String> {
+    let document = load(path).ok()?;
+    let info = match document.trailer.get(b"Info").ok()? {
+        Object::Reference(id) => document.get_dictionary(*id).ok()?,
+        Object::Dictionary(info) => info,
+        _ => return None,
+    };
+    let title = lopdf::decode_text_string(info.get(b"Title").ok()?).ok()?;
+    let title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!title.is_empty()).then_some(title)
+}
+
+/// HTML of a table of contents listing `chapters` with the pages they
+/// start on
+pub fn toc_html(title: &str, chapters: &[Chapter], first_pages: &[usize]) -> String {
+    let entries: String = chapters
+        .iter()
+        .zip(first_pages)
+        .map(|(chapter, page)| {
+            format!(
+                "<li><span class=\"title\">{}</span><span class=\"page\">{}</span><div class=\"url\">{}</div></li>\n",
+                escape_html(&chapter.title),
+                page,
+                escape_html(&chapter.url)
+            )
+        })
+        .collect();
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<style>
+body {{ font-family: sans-serif; margin: 2cm; color: #222; }}
+h1 {{ font-size: 24pt; margin-bottom: 1cm; }}
+ol {{ list-style: none; padding: 0; }}
+li {{ margin-bottom: 0.4cm; break-inside: avoid; }}
+li > span {{ display: inline-block; }}
+.title {{ width: calc(100% - 2cm); }}
+.page {{ width: 2cm; text-align: right; }}
+.url {{ font-size: 9pt; color: #777; }}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+<ol>
+{entries}</ol>
+</body>
+</html>
+"#,
+        title = escape_html(title),
+        entries = entries
+    )
+}
+
+/// Merge `chapters` into one PDF at `output`, preceded by a table of
+/// contents page printed by `browser` with `options` unless `toc` is off
+pub async fn build_book(
+    browser: &Browser,
+    chapters: &[Chapter],
+    output: &Path,
+    title: &str,
+    toc: bool,
+    options: &PdfOptions,
+) -> Result<BookReport> {
+    let counts = chapters
+        .iter()
+        .map(|chapter| page_count(&chapter.path))
+        .collect::<Result<Vec<_>>>()?;
+
+    let dir = tempfile::tempdir()?;
+    let mut parts = Vec::new();
+    let mut toc_pages = 0;
+    if toc {
+        // Chapter page numbers depend on the length of the table of
+        // contents, which is only known once it has been printed
+        let toc_path = dir.path().join("toc.pdf");
+        toc_pages = 1;
+        loop {
+            let html = toc_html(title, chapters, &first_pages(toc_pages, &counts));
+            browser.pdf_from_html(&html, &toc_path, options).await?;
+            let printed = page_count(&toc_path)?;
+            if printed == toc_pages {
+                break;
+            }
+            toc_pages = printed;
+        }
+        parts.push(("Contents".to_string(), toc_path));
+    }
+    parts.extend(
+        chapters
+            .iter()
+            .map(|chapter| (chapter.title.clone(), chapter.path.clone())),
+    );
+
+    OutputHandler::ensure_output_dir(output)?;
+    let pages = merge_pdfs(&parts, title, output)?;
+    info!("Book of {} pages saved to: {}", pages, output.display());
+    Ok(BookReport {
+        path: output.to_path_buf(),
+        pages,
+        chapter_pages: first_pages(toc_pages, &counts),
+    })
+}
+
+/// First page of each chapter of the given lengths, after `offset` pages
+fn first_pages(offset: usize, counts: &[usize]) -> Vec<usize> {
+    counts
+        .iter()
+        .scan(offset + 1, |next, count| {
+            let first = *next;
+            *next += count;
+            Some(first)
+        })
+        .collect()
+}
+
+/// Concatenate the PDFs of `parts` into `output`, bookmarking the first
+/// page of each under its title, and return the page count
+pub fn merge_pdfs(parts: &[(String, PathBuf)], title: &str, output: &Path) -> Result<usize> {
+    let mut book = Document::with_version("1.5");
+    let mut next_id = 1;
+    let mut kids = Vec::new();
+    let mut bookmarks = Vec::new();
+    let mut pages = Vec::new();
+
+    for (part_title, path) in parts {
+        let mut document = load(path)?;
+        document.renumber_objects_with(next_id);
+        next_id = document.max_id + 1;
+
+        let page_ids: Vec<ObjectId> = document.get_pages().into_values().collect();
+        if let Some(&first) = page_ids.first() {
+            bookmarks.push((part_title.clone(), first));
+        }
+        for &page_id in &page_ids {
+            pages.push((page_id, flattened_page(&document, page_id)?));
+            kids.push(Object::Reference(page_id));
+        }
+
+        for (id, object) in document.objects {
+            let skip = matches!(
+                object.type_name().unwrap_or_default(),
+                "Catalog" | "Pages" | "Page" | "Outlines"
+            );
+            if !skip {
+                book.objects.insert(id, object);
+            }
+        }
+    }
+    book.max_id = next_id;
+
+    let pages_id = book.new_object_id();
+    let page_count = pages.len();
+    for (page_id, mut page) in pages {
+        page.set("Parent", pages_id);
+        book.objects.insert(page_id, Object::Dictionary(page));
+    }
+    book.objects.insert(
+        pages_id,
+        Object::Dictionary(Dictionary::from_iter([
+            ("Type", Object::Name(b"Pages".to_vec())),
+            ("Kids", Object::Array(kids)),
+            ("Count", Object::Integer(page_count as i64)),
+        ])),
+    );
+
+    for (part_title, page_id) in bookmarks {
+        book.add_bookmark(Bookmark::new(part_title, [0.0, 0.0, 0.0], 0, page_id), None);
+    }
+    let mut catalog = Dictionary::from_iter([
+        ("Type", Object::Name(b"Catalog".to_vec())),
+        ("Pages", Object::Reference(pages_id)),
+    ]);
+    if let Some(outline_id) = book.build_outline() {
+        catalog.set("Outlines", outline_id);
+        catalog.set("PageMode", "UseOutlines");
+    }
+    let catalog_id = book.add_object(catalog);
+    let info_id = book.add_object(Dictionary::from_iter([(
+        "Title",
+        lopdf::text_string(title),
+    )]));
+    book.trailer.set("Root", catalog_id);
+    book.trailer.set("Info", info_id);
+
+    book.save(output)
+        .map_err(|e| WebshotError::pdf(format!("Cannot write {}: {}", output.display(), e)))?;
+    Ok(page_count)
+}
+
+/// Copy of a page with the attributes it inherits from the page tree set on
+/// the page itself, so it keeps them under a new parent
+pub(crate) fn flattened_page(document: &Document, page_id: ObjectId) -> Result<Dictionary> {
+    let mut page = document
+        .get_dictionary(page_id)
+        .map_err(|e| WebshotError::pdf(e.to_string()))?
+        .clone();
+    let mut parent = page.get(b"Parent").and_then(Object::as_reference).ok();
+    while let Some(parent_id) = parent {
+        let Ok(node) = document.get_dictionary(parent_id) else {
+            break;
+        };
+        for key in INHERITED_PAGE_ATTRIBUTES {
+            if !page.has(key) {
+                if let Ok(value) = node.get(key) {
+                    page.set(key, value.clone());
+                }
+            }
+        }
+        parent = node.get(b"Parent").and_then(Object::as_reference).ok();
+    }
+    Ok(page)
+}
+
+fn load(path: &Path) -> Result<Document> {
+    Document::load(path)
+        .map_err(|e| WebshotError::pdf(format!("Cannot read {}: {}", path.display(), e)))
+}
+
+pub(crate) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{Stream, StringFormat};
+
+    /// Write a PDF of `pages` empty pages, sized on the page tree like
+    /// Chrome does, titled `title`
+    fn write_pdf(path: &Path, pages: usize, title: &str) {
+        let mut document = Document::with_version("1.4");
+        let pages_id = document.new_object_id();
+        let kids: Vec<Object> = (0..pages)
+            .map(|_| {
+                let content_id = document.add_object(Stream::new(Dictionary::new(), Vec::new()));
+                document
+                    .add_object(Dictionary::from_iter([
+                        ("Type", Object::Name(b"Page".to_vec())),
+                        ("Parent", Object::Reference(pages_id)),
+                        ("Contents", Object::Reference(content_id)),
+                    ]))
+                    .into()
+            })
+            .collect();
+        document.objects.insert(
+            pages_id,
+            Object::Dictionary(Dictionary::from_iter([
+                ("Type", Object::Name(b"Pages".to_vec())),
+                ("Count", Object::Integer(pages as i64)),
+                ("Kids", Object::Array(kids)),
+                (
+                    "MediaBox",
+                    Object::Array(vec![0.into(), 0.into(), 612.into(), 792.into()]),
+                ),
+            ])),
+        );
+        let catalog_id = document.add_object(Dictionary::from_iter([
+            ("Type", Object::Name(b"Catalog".to_vec())),
+            ("Pages", Object::Reference(pages_id)),
+        ]));
+        let info_id = document.add_object(Dictionary::from_iter([(
+            "Title",
+            Object::String(title.as_bytes().to_vec(), StringFormat::Literal),
+        )]));
+        document.trailer.set("Root", catalog_id);
+        document.trailer.set("Info", info_id);
+        document.save(path).unwrap();
+    }
+
+    #[test]
+    fn test_merge_pdfs_keeps_order_and_bookmarks_parts() {
+        let dir = tempfile::tempdir().unwrap();
+        let intro = dir.path().join("intro.pdf");
+        let guide = dir.path().join("guide.pdf");
+        write_pdf(&intro, 1, "Introduction");
+        write_pdf(&guide, 3, "  User  guide ");
+        assert_eq!(pdf_title(&guide).as_deref(), Some("User guide"));
+
+        let book = dir.path().join("book.pdf");
+        let parts = [
+            ("Introduction".to_string(), intro),
+            ("User guide".to_string(), guide),
+        ];
+        assert_eq!(merge_pdfs(&parts, "Docs", &book).unwrap(), 4);
+
+        let merged = Document::load(&book).unwrap();
+        assert_eq!(merged.get_pages().len(), 4);
+        assert_eq!(pdf_title(&book).as_deref(), Some("Docs"));
+        // Inherited media boxes survive the move to the new page tree
+        for page_id in merged.get_pages().into_values() {
+            assert!(merged.get_dictionary(page_id).unwrap().has(b"MediaBox"));
+        }
+        let catalog = merged.catalog().unwrap();
+        assert!(catalog.get(b"Outlines").is_ok());
+    }
+
+    #[test]
+    fn test_toc_lists_chapter_pages() {
+        assert_eq!(first_pages(1, &[1, 3, 2]), [2, 3, 6]);
+        assert_eq!(first_pages(0, &[2, 2]), [1, 3]);
+
+        let chapters = [Chapter {
+            title: "Q&A <draft>".to_string(),
+            url: "https://example.com/faq".to_string(),
+            path: PathBuf::from("faq.pdf"),
+        }];
+        let html = toc_html("Docs", &chapters, &[2]);
+        assert!(html.contains("<h1>Docs</h1>"));
+        assert!(html.contains(
+            "<span class=\"title\">Q&amp;A &lt;draft&gt;</span><span class=\"page\">2</span>"
+        ));
+    }
+}
@@ -1,20 +1,372 @@
-use crate::config::{validate_navigation_url, Config, ScreenshotConfig};
+use crate::adblock::FilterList;
+use crate::assets::{assets_script, PageAssets};
+use crate::backend::{BrowserBackend, Capture, PdfOptions, TextOptions};
+use crate::blocking::RequestBlocker;
+use crate::cache::{CachedCapture, CaptureCache};
+use crate::capabilities::{executable_capabilities, Capabilities};
+#[cfg(feature = "chaos")]
+use crate::chaos::{Chaos, Fault};
+use crate::color::ColorProfile;
+use crate::comparison::ImageComparator;
+use crate::config::{validate_navigation_url, AuthConfig, Config, ScreenshotConfig};
+use crate::console_log::ConsoleLog;
+use crate::crawl::{
+    self, origin_of, robots_path, CrawlOptions, CrawlState, CrawledPage, DuplicateMode, RobotsTxt,
+};
+use crate::elements::{
+    element_box_script, element_manifest_path, element_output, ElementBox, ElementCapture,
+    ElementEntry, ElementManifest,
+};
 use crate::error::{Result, WebshotError};
-use crate::output::OutputHandler;
-use crate::screenshot::{ImageFormat, ScreenshotOptions};
-use headless_chrome::protocol::cdp::Page;
+use crate::flags::default_flags;
+use crate::har::HarRecorder;
+use crate::health::{self, HealthReport};
+use crate::hooks::CaptureMetadata;
+use crate::layout::{layout_script, LayoutSnapshot};
+use crate::mocks::Mocks;
+use crate::monitor::{BatchMonitor, JobEntry, JobStatus};
+use crate::network_idle::NetworkIdleWatch;
+use crate::outline::{outline_script, Outline};
+use crate::output::{format_size_reduction, OutputHandler};
+use crate::overlays::OverlayDismissal;
+use crate::page_errors::PageErrorWatch;
+use crate::paper::draw_page_breaks;
+use crate::pdf_links::{
+    add_missing_links, link_count, strip_links, PageLink, PrintLayout, LINKS_SCRIPT,
+};
+use crate::picker::{picker_script, PickedElement, PICK_RESULT_EXPRESSION};
+use crate::plugins::apply_image_filters;
+use crate::profile::{sets_user_data_dir, TempProfile};
+use crate::rate_limit::{HostRateLimiter, RateLimit};
+use crate::retry::RetryPolicy;
+use crate::screenshot::{
+    read_init_scripts, CaptureMode, ClipRegion, ImageFormat, ScreenshotOptions, DEFAULT_MASK_COLOR,
+};
+use crate::session::{SessionState, StorageSnapshot, SNAPSHOT_STORAGE_SCRIPT};
+use crate::steps::{recorder_script, Step, RECORDER_BINDING};
+use crate::timing::{Phase, PhaseTimer, PhaseTimings};
+use crate::wait::WaitCondition;
+use headless_chrome::browser::context::Context;
+use headless_chrome::browser::tab::RequestPausedDecision;
+use headless_chrome::protocol::cdp::Emulation::MediaFeature;
+use headless_chrome::protocol::cdp::Fetch::events::RequestPausedEvent;
+use headless_chrome::protocol::cdp::{Emulation, Fetch, Network, Page, CSS, DOM};
 use headless_chrome::types::PrintToPdfOptions;
 use headless_chrome::{Browser as ChromeBrowser, LaunchOptions, Tab};
+use lopdf::Document;
+use path_absolutize::Absolutize;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
-use std::time::Duration;
+use std::sync::{Arc, Mutex, RwLock, Weak};
+use std::time::{Duration, Instant};
 use tokio::time::sleep;
-use tracing::{debug, info, warn};
+use tracing::{debug, info, trace, warn};
+
+/// Scrolls in fixed steps (one viewport when the step is 0) to the bottom of
+/// the page so lazy-loaded images and IntersectionObserver content are
+/// requested, settling after each step, then returns to the top.
+const LAZY_SCROLL_SCRIPT: &str = r#"
+(async () => {
+    const delay = (ms) => new Promise((resolve) => setTimeout(resolve, ms));
+    const settle = __SETTLE_MS__;
+    const step = __STEP__ || Math.max(window.innerHeight, 200);
+    let position = 0;
+    while (position < document.documentElement.scrollHeight) {
+        window.scrollTo(0, position);
+        await delay(settle);
+        position += step;
+    }
+    window.scrollTo(0, document.documentElement.scrollHeight);
+    await delay(settle);
+    window.scrollTo(0, 0);
+    return true;
+})()
+"#;
+
+/// Resolves to true once no new resources have started loading and every
+/// image has completed for 500ms, or false when the timeout elapses first.
+const NETWORK_IDLE_SCRIPT: &str = r#"
+(async () => {
+    const delay = (ms) => new Promise((resolve) => setTimeout(resolve, ms));
+    const deadline = Date.now() + __TIMEOUT_MS__;
+    let resources = performance.getEntriesByType('resource').length;
+    let quietSince = Date.now();
+    while (Date.now() < deadline) {
+        await delay(100);
+        const current = performance.getEntriesByType('resource').length;
+        const pending = Array.from(document.images).some((img) => !img.complete);
+        if (current !== resources || pending) {
+            resources = current;
+            quietSince = Date.now();
+        } else if (Date.now() - quietSince >= 500) {
+            return true;
+        }
+    }
+    return false;
+})()
+"#;
+
+/// Freezes the page for deterministic captures: finishes (or cancels, when
+/// infinite) running animations, disables further animations and transitions,
+/// hides the text caret and pauses videos at their first frame.
+const STABILIZE_SCRIPT: &str = r#"
+(() => {
+    const style = document.createElement('style');
+    style.setAttribute('data-webshot-stabilize', '');
+    style.textContent = `*, *::before, *::after {
+        animation-delay: 0s !important;
+        animation-duration: 0s !important;
+        animation-iteration-count: 1 !important;
+        transition-delay: 0s !important;
+        transition-duration: 0s !important;
+        caret-color: transparent !important;
+        scroll-behavior: auto !important;
+    }`;
+    (document.head || document.documentElement).appendChild(style);
+    for (const animation of document.getAnimations()) {
+        try {
+            animation.finish();
+        } catch (e) {
+            animation.cancel();
+        }
+    }
+    for (const video of document.querySelectorAll('video')) {
+        video.pause();
+        video.currentTime = 0;
+    }
+    if (document.activeElement && document.activeElement.blur) {
+        document.activeElement.blur();
+    }
+    return true;
+})()
+"#;
+
+/// Paints a box of `COLOR` over every element matching one of `SELECTORS`,
+/// replacing the boxes of an earlier run, and evaluates to the number of
+/// boxes painted
+const MASK_SCRIPT: &str = r#"
+(() => {
+    for (const mask of document.querySelectorAll('[data-webshot-mask]')) {
+        mask.remove();
+    }
+    let masked = 0;
+    for (const selector of SELECTORS) {
+        for (const element of document.querySelectorAll(selector)) {
+            const rect = element.getBoundingClientRect();
+            if (rect.width <= 0 || rect.height <= 0) {
+                continue;
+            }
+            const mask = document.createElement('div');
+            mask.setAttribute('data-webshot-mask', '');
+            const style = {
+                position: 'absolute',
+                left: `${rect.left + window.scrollX}px`,
+                top: `${rect.top + window.scrollY}px`,
+                width: `${rect.width}px`,
+                height: `${rect.height}px`,
+                margin: '0',
+                padding: '0',
+                border: '0',
+                background: COLOR,
+                'z-index': '2147483647',
+                'pointer-events': 'none',
+            };
+            for (const [name, value] of Object.entries(style)) {
+                mask.style.setProperty(name, value, 'important');
+            }
+            document.documentElement.appendChild(mask);
+            masked += 1;
+        }
+    }
+    return masked;
+})()
+"#;
+
+/// Pins sticky and fixed elements in place for full-page captures, so they
+/// appear once where they are on load instead of repeating or floating
+/// mid-page: sticky elements become static, fixed ones absolute at their
+/// position with the page scrolled to the top, offset from the containing
+/// block they have once absolute. Returns how many changed.
+const FREEZE_STICKY_SCRIPT: &str = r#"
+(() => {
+    window.scrollTo(0, 0);
+    const pinned = [];
+    for (const element of document.querySelectorAll('body *')) {
+        const position = getComputedStyle(element).position;
+        if (position === 'sticky' || position === '-webkit-sticky') {
+            pinned.push([element, null]);
+        } else if (position === 'fixed') {
+            pinned.push([element, element.getBoundingClientRect()]);
+        }
+    }
+    // The ancestor an absolute element is placed in, or null for the page
+    const containingBlock = (element) => {
+        for (let ancestor = element.parentElement; ancestor && ancestor !== document.documentElement; ancestor = ancestor.parentElement) {
+            const style = getComputedStyle(ancestor);
+            if (style.position !== 'static' || style.transform !== 'none' || style.filter !== 'none'
+                || style.perspective !== 'none' || /paint|layout|strict|content/.test(style.contain)) {
+                return ancestor;
+            }
+        }
+        return null;
+    };
+    const set = (element, name, value) => element.style.setProperty(name, value, 'important');
+    for (const [element, rect] of pinned) {
+        set(element, 'position', rect ? 'absolute' : 'static');
+    }
+    // Document order places outer elements before the ones inside them
+    for (const [element, rect] of pinned) {
+        if (!rect) {
+            continue;
+        }
+        let top = rect.top + window.scrollY;
+        let left = rect.left + window.scrollX;
+        const block = containingBlock(element);
+        if (block) {
+            const origin = block.getBoundingClientRect();
+            top = rect.top - origin.top - block.clientTop + block.scrollTop;
+            left = rect.left - origin.left - block.clientLeft + block.scrollLeft;
+        }
+        set(element, 'top', top + 'px');
+        set(element, 'left', left + 'px');
+        set(element, 'right', 'auto');
+        set(element, 'bottom', 'auto');
+        set(element, 'width', rect.width + 'px');
+        set(element, 'height', rect.height + 'px');
+    }
+    return pinned.length;
+})()
+"#;
+
+/// Scrolls the `__FRAGMENT__` target (by id, then by anchor name) to the top
+/// of the viewport, re-applying while layout shifts move it, and resolves to
+/// whether a target was found
+const ANCHOR_SCROLL_SCRIPT: &str = r#"
+(async () => {
+    const delay = (ms) => new Promise((resolve) => setTimeout(resolve, ms));
+    let id = __FRAGMENT__;
+    try {
+        id = decodeURIComponent(id);
+    } catch (e) {}
+    const target = document.getElementById(id) || document.getElementsByName(id)[0];
+    if (!target) {
+        if (id.toLowerCase() !== 'top') {
+            return false;
+        }
+        window.scrollTo({ top: 0, behavior: 'instant' });
+        return true;
+    }
+    for (let attempt = 0; attempt < 10; attempt++) {
+        target.scrollIntoView({ block: 'start', behavior: 'instant' });
+        const top = target.getBoundingClientRect().top;
+        await delay(100);
+        if (Math.abs(target.getBoundingClientRect().top - top) < 1) {
+            break;
+        }
+    }
+    return true;
+})()
+"#;
+
+/// Height of the page's content in CSS pixels. Unlike the scroll height of the
+/// document, this is not stretched to the viewport, so short pages shrink.
+const CONTENT_HEIGHT_SCRIPT: &str = r#"
+(() => {
+    const root = document.documentElement;
+    const body = document.body;
+    const bodyStyle = body ? getComputedStyle(body) : null;
+    const bodyHeight = body
+        ? body.scrollHeight + parseFloat(bodyStyle.marginTop) + parseFloat(bodyStyle.marginBottom)
+        : 0;
+    return Math.max(root.getBoundingClientRect().height, bodyHeight);
+})()
+"#;
+
+/// Region of the document the viewport shows, scrollbars included, as JSON
+/// for a [`ClipRegion`]
+const VIEWPORT_BOX_SCRIPT: &str = r#"
+JSON.stringify({
+    x: Math.max(0, Math.round(window.scrollX)),
+    y: Math.max(0, Math.round(window.scrollY)),
+    width: window.innerWidth,
+    height: window.innerHeight
+})
+"#;
+
+/// Serializes the element tree under `<body>` as tag names and classes, which
+/// stays the same across pages rendered from one template
+const DOM_SIGNATURE_SCRIPT: &str = r#"
+(() => {
+    const parts = [];
+    const stack = [[document.body || document.documentElement, 0]];
+    while (stack.length > 0 && parts.length < 10000) {
+        const [element, depth] = stack.pop();
+        if (element.tagName === 'SCRIPT' || element.tagName === 'STYLE') {
+            continue;
+        }
+        parts.push(depth + element.tagName + '.' + (element.getAttribute('class') || ''));
+        for (let i = element.children.length - 1; i >= 0; i--) {
+            stack.push([element.children[i], depth + 1]);
+        }
+    }
+    return parts.join('|');
+})()
+"#;
+
+/// Upper bound for the network-quiet wait after lazy-load scrolling
+const NETWORK_IDLE_MAX_WAIT_MS: u64 = 10_000;
+
+/// Default interval between rechecks of wait conditions
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long a connected browser may go without commands before the
+/// connection is dropped
+const BROWSER_IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Interval between keep-alive pings of a connected browser, well inside
+/// [`BROWSER_IDLE_TIMEOUT`] and the idle timeouts of proxies in front of it
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Chrome flags rendering text the same on every machine: no subpixel
+/// positioning, no LCD (subpixel) antialiasing and no font hinting
+pub const FONT_RENDERING_FLAGS: [&str; 3] = [
+    "--disable-font-subpixel-positioning",
+    "--disable-lcd-text",
+    "--font-render-hinting=none",
+];
+
+/// Chrome flag opening DevTools next to every tab, for `--devtools`
+pub const DEVTOOLS_FLAG: &str = "--auto-open-devtools-for-tabs";
 
 /// Browser automation wrapper
 pub struct Browser {
-    browser: ChromeBrowser,
+    /// The Chrome instance, replaced when a connected browser is reconnected
+    browser: Arc<RwLock<ChromeBrowser>>,
     javascript_enabled: bool,
+    color_profile: Option<ColorProfile>,
+    /// Chrome version and the DevTools features it has
+    capabilities: Capabilities,
+    /// Task pinging a connected browser and reconnecting when it stops
+    /// answering
+    keep_alive: Option<tokio::task::AbortHandle>,
+    /// Time taken to launch or connect to the browser
+    launch_time: Duration,
+    /// Interval between rechecks of wait conditions
+    poll_interval: Duration,
+    /// Browser contexts for batch entries with a proxy, keyed by proxy URL
+    proxy_contexts: Mutex<HashMap<String, String>>,
+    /// Parsed ad and tracker filter lists for batch entries, keyed by path
+    filter_lists: Mutex<HashMap<Option<PathBuf>, Arc<FilterList>>>,
+    /// Recent screenshots served instead of capturing the same page again
+    capture_cache: Option<Arc<CaptureCache>>,
+    /// Faults injected into batch captures
+    #[cfg(feature = "chaos")]
+    chaos: Option<Arc<Chaos>>,
+    /// Leave the profiles of launched Chrome instances on disk
+    keep_profiles: bool,
+    /// User data directories of the launched Chrome instances, declared
+    /// last so they are removed after the instances are dropped
+    profiles: Mutex<Vec<TempProfile>>,
 }
 
 impl Browser {
@@ -24,78 +376,566 @@ impl Browser {
         chrome_flags: Vec<String>,
         javascript_enabled: bool,
     ) -> Result<Self> {
-        info!("Launching browser...");
-
-        let mut args_str = vec![
-            "--no-sandbox",
-            "--disable-gpu",
-            "--disable-dev-shm-usage",
-            "--disable-setuid-sandbox",
-            "--no-first-run",
-        ];
+        Self::launch(chrome_path, chrome_flags, &[], javascript_enabled, true)
+    }
 
-        // Collect additional flags
-        let mut flag_strings = Vec::new();
-        for flag in chrome_flags {
-            flag_strings.push(flag);
-        }
+    /// Create a browser instance with a visible window, for interactive use
+    pub async fn new_headful(
+        chrome_path: Option<PathBuf>,
+        chrome_flags: Vec<String>,
+        javascript_enabled: bool,
+    ) -> Result<Self> {
+        Self::launch(chrome_path, chrome_flags, &[], javascript_enabled, false)
+    }
 
-        // Disable JavaScript if requested
-        if !javascript_enabled {
-            flag_strings.push("--disable-javascript".to_string());
-        }
+    /// Create a browser instance launched without the default flags named
+    /// in `removed_flags` (see [`crate::flags`]), headless unless `headful`
+    pub async fn new_without_flags(
+        chrome_path: Option<PathBuf>,
+        chrome_flags: Vec<String>,
+        removed_flags: &[String],
+        javascript_enabled: bool,
+        headful: bool,
+    ) -> Result<Self> {
+        Self::launch(
+            chrome_path,
+            chrome_flags,
+            removed_flags,
+            javascript_enabled,
+            !headful,
+        )
+    }
 
-        // Convert to OsStr refs
-        for flag in &flag_strings {
-            args_str.push(flag.as_str());
+    fn launch(
+        chrome_path: Option<PathBuf>,
+        chrome_flags: Vec<String>,
+        removed_flags: &[String],
+        javascript_enabled: bool,
+        headless: bool,
+    ) -> Result<Self> {
+        let mut launch_args = default_flags(removed_flags)?;
+        info!("Launching browser...");
+        let started = Instant::now();
+
+        let color_profile = ColorProfile::from_chrome_flags(&chrome_flags);
+        launch_args.extend(chrome_flags);
+
+        // Pick the headless switch the installed Chrome understands, unless
+        // a --chrome-flag already chose a mode
+        let chooses_headless = launch_args
+            .iter()
+            .any(|arg| arg == "--headless" || arg.starts_with("--headless="));
+        if headless && !chooses_headless {
+            let installed = chrome_path
+                .clone()
+                .or_else(|| headless_chrome::browser::default_executable().ok())
+                .and_then(|path| executable_capabilities(&path))
+                .unwrap_or_else(Capabilities::unknown);
+            launch_args.push(installed.headless_switch().to_string());
         }
 
-        let args_os: Vec<std::ffi::OsString> = args_str.iter().map(|s| (*s).into()).collect();
-        let args_refs: Vec<&std::ffi::OsStr> = args_os.iter().map(|s| s.as_os_str()).collect();
+        let profile = new_profile(&launch_args)?;
+        let browser = launch_chrome(
+            chrome_path.as_ref(),
+            &launch_args,
+            profile.as_ref().map(TempProfile::path),
+        )?;
 
-        let launch_options = if let Some(path) = chrome_path {
-            LaunchOptions::default_builder()
-                .headless(true)
-                .sandbox(false)
-                .args(args_refs)
-                .path(Some(path))
-                .build()
-                .unwrap()
-        } else {
-            LaunchOptions::default_builder()
-                .headless(true)
-                .sandbox(false)
-                .args(args_refs)
-                .build()
-                .unwrap()
-        };
+        let launch_time = started.elapsed();
+        info!("Browser launched in {}ms", launch_time.as_millis());
+        let capabilities = reported_capabilities(&browser);
 
-        let browser = ChromeBrowser::new(launch_options)
-            .map_err(|e| WebshotError::browser_launch(e.to_string()))?;
+        Ok(Self {
+            browser: Arc::new(RwLock::new(browser)),
+            javascript_enabled,
+            color_profile,
+            capabilities,
+            keep_alive: None,
+            launch_time,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            proxy_contexts: Mutex::new(HashMap::new()),
+            filter_lists: Mutex::new(HashMap::new()),
+            capture_cache: None,
+            #[cfg(feature = "chaos")]
+            chaos: None,
+            keep_profiles: false,
+            profiles: Mutex::new(profile.into_iter().collect()),
+        })
+    }
 
-        debug!("Browser launched successfully");
+    /// Attach to an already running Chrome over the DevTools protocol
+    /// instead of launching one. `url` is the browser's `ws://` DevTools URL,
+    /// or the `http://host:port` of its remote debugging port, from which
+    /// the WebSocket URL is looked up.
+    ///
+    /// The connection is pinged every [`KEEP_ALIVE_INTERVAL`] so it is not
+    /// dropped while idle; when the browser stops answering, webshot looks
+    /// it up through `url` again and reconnects.
+    pub async fn connect(url: &str, javascript_enabled: bool) -> Result<Self> {
+        let started = Instant::now();
+        let (browser, _) = connect_chrome(url).await?;
+        let launch_time = started.elapsed();
+        info!("Connected to browser in {}ms", launch_time.as_millis());
+        let capabilities = reported_capabilities(&browser);
+
+        let browser = Arc::new(RwLock::new(browser));
+        let keep_alive = tokio::spawn(keep_alive(Arc::downgrade(&browser), url.to_string()));
 
         Ok(Self {
             browser,
             javascript_enabled,
+            color_profile: None,
+            capabilities,
+            keep_alive: Some(keep_alive.abort_handle()),
+            launch_time,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            proxy_contexts: Mutex::new(HashMap::new()),
+            filter_lists: Mutex::new(HashMap::new()),
+            capture_cache: None,
+            #[cfg(feature = "chaos")]
+            chaos: None,
+            keep_profiles: false,
+            profiles: Mutex::new(Vec::new()),
         })
     }
 
-    /// Take a screenshot of a webpage
+    /// Attach to a Chrome whose remote debugging port is at `host_port`
+    /// (`HOST:PORT`), such as one running in another container, discovering
+    /// its DevTools target over HTTP. See [`Browser::connect`].
+    pub async fn connect_host(host_port: &str, javascript_enabled: bool) -> Result<Self> {
+        Self::connect(&devtools_http_url(host_port)?, javascript_enabled).await
+    }
+
+    /// The current Chrome instance
+    fn chrome(&self) -> ChromeBrowser {
+        self.browser.read().unwrap().clone()
+    }
+
+    /// New tab in a browser context that sends all traffic through `proxy`.
+    /// Contexts are created in the main browser on first use and shared by
+    /// every entry with the same proxy.
+    fn proxy_tab(&self, proxy: &str) -> Result<Arc<Tab>> {
+        let browser = self.chrome();
+        let cached = self.proxy_contexts.lock().unwrap().get(proxy).cloned();
+        if let Some(context_id) = cached {
+            // A reconnected browser no longer has the context; make a new one
+            if let Ok(tab) = Context::new(&browser, context_id).new_tab() {
+                return Ok(tab);
+            }
+        }
+
+        info!("Creating browser context for proxy {}", proxy);
+        let context_id = create_proxy_context(&browser.get_ws_url(), proxy)?;
+        self.proxy_contexts
+            .lock()
+            .unwrap()
+            .insert(proxy.to_string(), context_id.clone());
+        Context::new(&browser, context_id)
+            .new_tab()
+            .map_err(|e| WebshotError::Tab(e.to_string()))
+    }
+
+    /// Filter list at `path` (or the bundled one), parsed once and shared by
+    /// every batch entry that uses it
+    fn filter_list(&self, path: Option<&Path>) -> Result<Arc<FilterList>> {
+        let mut lists = self.filter_lists.lock().unwrap();
+        let key = path.map(Path::to_path_buf);
+        if let Some(list) = lists.get(&key) {
+            return Ok(Arc::clone(list));
+        }
+
+        let list = Arc::new(FilterList::load_or_bundled(path)?);
+        debug!("Loaded {} ad and tracker filters", list.len());
+        lists.insert(key, Arc::clone(&list));
+        Ok(list)
+    }
+
+    /// Chrome version and the DevTools features it has, read when the
+    /// browser was launched or connected to
+    pub fn capabilities(&self) -> &Capabilities {
+        &self.capabilities
+    }
+
+    /// Color profile forced with `--force-color-profile`, which image output
+    /// is tagged with. `None` when Chrome renders in its default color space.
+    pub fn color_profile(&self) -> Option<ColorProfile> {
+        self.color_profile
+    }
+
+    /// Whether Chrome still answers DevTools commands
+    pub fn is_alive(&self) -> bool {
+        match self.chrome().get_version() {
+            Ok(_) => true,
+            Err(e) => {
+                warn!("Browser is not responding: {}", e);
+                false
+            }
+        }
+    }
+
+    /// Close every tab but the first, freeing the pages earlier captures
+    /// left open. Returns how many tabs were closed.
+    pub fn close_tabs(&self) -> Result<usize> {
+        let tabs: Vec<_> = self
+            .chrome()
+            .get_tabs()
+            .lock()
+            .unwrap()
+            .iter()
+            .skip(1)
+            .cloned()
+            .collect();
+        for tab in &tabs {
+            tab.close(false)
+                .map_err(|e| WebshotError::Tab(e.to_string()))?;
+        }
+        Ok(tabs.len())
+    }
+
+    /// Recheck wait conditions every `interval` where the page's DOM
+    /// changes don't reveal them, and when polling replaces in-page waiting
+    pub fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Serve [`Browser::screenshot`] calls repeating a recent capture from
+    /// `cache` instead of loading the page again
+    /// Leave the profiles of this browser's Chrome instances on disk when
+    /// it is dropped, logging their paths, instead of removing them
+    pub fn with_kept_profiles(mut self) -> Self {
+        self.keep_profiles = true;
+        for profile in self.profiles.get_mut().unwrap() {
+            profile.keep();
+        }
+        self
+    }
+
+    pub fn with_capture_cache(mut self, cache: Arc<CaptureCache>) -> Self {
+        self.capture_cache = Some(cache);
+        self
+    }
+
+    /// Inject faults from `chaos` into the captures of batch runs
+    #[cfg(feature = "chaos")]
+    pub fn with_chaos(mut self, chaos: Arc<Chaos>) -> Self {
+        self.chaos = Some(chaos);
+        self
+    }
+
+    /// The cache given with [`Browser::with_capture_cache`]
+    pub fn capture_cache(&self) -> Option<&Arc<CaptureCache>> {
+        self.capture_cache.as_ref()
+    }
+
+    /// Time it took to launch or connect to the browser
+    pub fn launch_time(&self) -> Duration {
+        self.launch_time
+    }
+
+    /// Liveness and readiness report for a process writing into `output_dir`
+    pub fn health(&self, output_dir: &Path, draining: bool) -> HealthReport {
+        HealthReport {
+            browser: self.is_alive(),
+            disk_writable: health::disk_writable(output_dir),
+            draining,
+        }
+    }
+
+    /// Take a screenshot of a webpage, returning the path written with any
+    /// page tokens (`{title}`, `{final_url_path}`) resolved.
+    ///
+    /// With a [`CaptureCache`], a capture of the same URL in the same format
+    /// with the same options is written to `output_path` from the cache
+    /// while it is fresh.
     pub async fn screenshot<P: AsRef<Path>>(
         &self,
         url: &str,
         output_path: P,
         options: &ScreenshotOptions,
-    ) -> Result<()> {
+    ) -> Result<PathBuf> {
+        Ok(self.capture(url, output_path, options).await?.path)
+    }
+
+    /// Take a screenshot like [`Browser::screenshot`], also returning the
+    /// HTTP status of the page's main document
+    pub async fn capture<P: AsRef<Path>>(
+        &self,
+        url: &str,
+        output_path: P,
+        options: &ScreenshotOptions,
+    ) -> Result<Capture> {
         validate_navigation_url(url, "screenshot API")?;
         options.validate()?;
 
-        let tab = self
-            .browser
-            .new_tab()
-            .map_err(|e| WebshotError::Tab(e.to_string()))?;
-        self.setup_tab(&tab, options).await?;
+        let cache_key = self
+            .capture_cache
+            .as_ref()
+            .and_then(|_| CaptureCache::key(url, output_path.as_ref(), options));
+        if let (Some(cache), Some(key)) = (&self.capture_cache, cache_key) {
+            if let Some(cached) = cache.get(key) {
+                // Paths with page tokens are part of the key, so the cached
+                // path is what they resolved to
+                let path = if OutputHandler::has_page_tokens(output_path.as_ref()) {
+                    cached.path
+                } else {
+                    output_path.as_ref().to_path_buf()
+                };
+                OutputHandler::ensure_output_dir(&path)?;
+                OutputHandler::write_output(&path, cached.data.as_slice())?;
+                info!("Screenshot of {} served from cache", url);
+                return Ok(Capture {
+                    path,
+                    status: cached.status,
+                });
+            }
+        }
+
+        let (tab, output_path, status, timings) =
+            self.open_and_capture(url, &output_path, options).await?;
+        info!("Timings for {}: {}", url, timings);
+
+        if let Some(path) = &options.save_session {
+            self.save_session(&tab, options.session.clone(), path)?;
+        }
+
+        if let (Some(cache), Some(key)) = (&self.capture_cache, cache_key) {
+            cache.insert(
+                key,
+                CachedCapture {
+                    data: Arc::new(std::fs::read(&output_path)?),
+                    path: output_path.clone(),
+                    status,
+                    captured_at: Instant::now(),
+                },
+            );
+        }
+
+        info!("Screenshot saved to: {}", output_path.display());
+        Ok(Capture {
+            path: output_path,
+            status,
+        })
+    }
+
+    /// Take a screenshot of a local HTML file, such as an email template.
+    ///
+    /// The file is loaded from a `file://` URL, which the URL checks of
+    /// [`Browser::screenshot`] reject, and never served from the cache.
+    pub async fn screenshot_file<P: AsRef<Path>>(
+        &self,
+        path: &Path,
+        output_path: P,
+        options: &ScreenshotOptions,
+    ) -> Result<PathBuf> {
+        options.validate()?;
+        if !path.is_file() {
+            return Err(WebshotError::InvalidPath {
+                path: path.to_path_buf(),
+            });
+        }
+        let url = url::Url::from_file_path(path.absolutize()?).map_err(|_| {
+            WebshotError::InvalidPath {
+                path: path.to_path_buf(),
+            }
+        })?;
+
+        let (_, output_path, _, timings) = self
+            .open_and_capture(url.as_str(), &output_path, options)
+            .await?;
+        info!("Timings for {}: {}", path.display(), timings);
+        info!("Screenshot saved to: {}", output_path.display());
+        Ok(output_path)
+    }
+
+    /// Save every browser cookie plus the web storage of the tab's current
+    /// origin, keeping storage saved earlier for other origins
+    fn save_session(&self, tab: &Tab, base: Option<SessionState>, path: &Path) -> Result<()> {
+        let mut session = base.unwrap_or_default();
+
+        session.cookies = tab
+            .call_method(Network::GetAllCookies(None))
+            .map_err(WebshotError::Browser)?
+            .cookies
+            .iter()
+            .map(Into::into)
+            .collect();
+
+        if self.javascript_enabled {
+            let snapshot = tab
+                .evaluate(SNAPSHOT_STORAGE_SCRIPT, false)
+                .map_err(|e| WebshotError::javascript(e.to_string()))?;
+            if let Some(json) = snapshot.value.as_ref().and_then(|value| value.as_str()) {
+                session.record_storage(serde_json::from_str::<StorageSnapshot>(json)?);
+            }
+        } else {
+            warn!("JavaScript disabled, saving cookies without web storage");
+        }
+
+        OutputHandler::ensure_output_dir(path)?;
+        session.save(path)?;
+        info!(
+            "Session saved to: {} ({} cookies)",
+            path.display(),
+            session.cookies.len()
+        );
+        Ok(())
+    }
+
+    /// Crawl a site breadth-first from the state's frontier, capturing every
+    /// visited page into `output_dir` and following same-site links.
+    ///
+    /// The state is saved to `state_path` after each page so an interrupted
+    /// crawl can resume where it stopped.
+    pub async fn crawl(
+        &self,
+        state: &mut CrawlState,
+        crawl_options: &CrawlOptions,
+        options: &ScreenshotOptions,
+        output_dir: &Path,
+        name_template: &str,
+        state_path: &Path,
+    ) -> Result<()> {
+        options.validate()?;
+        std::fs::create_dir_all(output_dir)?;
+
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(options.timeout))
+            .build()?;
+        let mut robots: HashMap<String, RobotsTxt> = HashMap::new();
+        let limiter = HostRateLimiter::new(&crawl_options.rate_limit);
+
+        while let Some(entry) = state.next_entry(crawl_options) {
+            if crawl_options.respect_robots {
+                let origin = origin_of(&entry.url)?;
+                if !robots.contains_key(&origin) {
+                    let rules = RobotsTxt::fetch(&client, &entry.url).await;
+                    robots.insert(origin.clone(), rules);
+                }
+                if !robots[&origin].allows(&robots_path(&entry.url)?) {
+                    info!("Disallowed by robots.txt, skipping: {}", entry.url);
+                    state.save(state_path)?;
+                    continue;
+                }
+            }
+
+            let file_name = OutputHandler::render_filename_template(
+                name_template,
+                &entry.url,
+                state.pages.len() + 1,
+            );
+            let output_path = state.unused_output(output_dir.join(file_name));
+
+            info!("Crawling [depth {}]: {}", entry.depth, entry.url);
+            match crawl_options
+                .retry
+                .run(&format!("Crawl of {}", entry.url), |_| async {
+                    limiter.acquire(&entry.url).await;
+                    self.open_and_capture(&entry.url, &output_path, options)
+                        .await
+                })
+                .await
+            {
+                Ok((tab, output_path, status, timings)) => {
+                    info!("Timings for {}: {}", entry.url, timings);
+                    let links = extract_links(&tab).unwrap_or_else(|e| {
+                        warn!("Failed to extract links from {}: {}", entry.url, e);
+                        Vec::new()
+                    });
+
+                    let mut page = CrawledPage::new(entry.url, entry.depth, output_path);
+                    page.status = status;
+                    if let Some(mode) = crawl_options.duplicates {
+                        if let Err(e) =
+                            self.detect_duplicate(&tab, state, &mut page, mode, crawl_options)
+                        {
+                            warn!("Duplicate detection failed for {}: {}", page.url, e);
+                        }
+                    }
+                    let _ = tab.close(true);
+
+                    let added = state.enqueue_links(&links, entry.depth, crawl_options);
+                    debug!("Found {} new links on {}", added, page.url);
+                    state.record_page(page);
+                }
+                Err(e) => warn!("Failed to capture {}: {}", entry.url, e),
+            }
+
+            state.save(state_path)?;
+        }
+
+        Ok(())
+    }
+
+    /// Hash a freshly captured crawl page and mark it as a duplicate of an
+    /// earlier page with the same DOM structure and a similar screenshot
+    fn detect_duplicate(
+        &self,
+        tab: &Tab,
+        state: &CrawlState,
+        page: &mut CrawledPage,
+        mode: DuplicateMode,
+        crawl_options: &CrawlOptions,
+    ) -> Result<()> {
+        let signature = tab
+            .evaluate(DOM_SIGNATURE_SCRIPT, false)
+            .map_err(|e| WebshotError::javascript(e.to_string()))?
+            .value
+            .and_then(|value| value.as_str().map(str::to_string))
+            .unwrap_or_default();
+        let dom_hash = crawl::dom_hash(&signature);
+        let perceptual_hash = ImageComparator::perceptual_hash(&image::open(&page.output)?);
+
+        page.dom_hash = Some(dom_hash);
+        page.perceptual_hash = Some(perceptual_hash);
+
+        if let Some(original) =
+            state.find_duplicate(perceptual_hash, dom_hash, crawl_options.duplicate_threshold)
+        {
+            info!("{} duplicates {}", page.url, original.url);
+            page.duplicate_of = Some(original.url.clone());
+
+            // Never delete the original's screenshot when both pages were
+            // written to the same file
+            if mode == DuplicateMode::Skip && page.output != original.output {
+                std::fs::remove_file(&page.output)?;
+                page.output = original.output.clone();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Open a tab, load the page and write a screenshot, returning the tab,
+    /// the output path with page tokens resolved, the document's HTTP status
+    /// and the time spent in each phase
+    async fn open_and_capture<P: AsRef<Path>>(
+        &self,
+        url: &str,
+        output_path: P,
+        options: &ScreenshotOptions,
+    ) -> Result<(Arc<Tab>, PathBuf, Option<u16>, PhaseTimings)> {
+        let timer = PhaseTimer::start();
+        let tab = self.open_page(options).await?;
+        let document_status = watch_document_status(&tab)?;
+        let page_errors = PageErrorWatch::attach(
+            &tab,
+            options.fail_on_console_error,
+            options.fail_on_js_exception,
+        )?;
+        // Written when dropped, so failed captures keep their log too
+        let _console_log = options
+            .console_log
+            .as_ref()
+            .map(|path| ConsoleLog::attach(&tab, path))
+            .transpose()?;
+        let _har = options
+            .har
+            .as_ref()
+            .map(|path| HarRecorder::attach(&tab, path))
+            .transpose()?;
+        let network_idle = NetworkIdleWatch::attach(&tab, options.network_idle.as_ref())?;
+        timer.mark(Phase::NewTab);
 
         info!("Navigating to: {}", url);
         tab.navigate_to(url)
@@ -104,10 +944,23 @@ impl Browser {
         // Wait for page load
         tab.wait_until_navigated()
             .map_err(|e| WebshotError::navigation(e.to_string()))?;
+        let status = check_http_status(
+            url,
+            *document_status.lock().unwrap(),
+            options.fail_on_http_error,
+        )?;
+        timer.mark(Phase::Navigate);
+
+        self.run_steps(&tab, &options.steps, options.timeout)
+            .await?;
+
+        if let Some(dismissal) = &options.dismiss_overlays {
+            self.dismiss_overlays(&tab, dismissal)?;
+        }
 
         // Execute custom JavaScript if provided
         if let Some(script) = &options.javascript {
-            if self.javascript_enabled {
+            if self.scripts_enabled(options) {
                 info!("Executing JavaScript: {}", script);
                 tab.evaluate(script, false)
                     .map_err(|e| WebshotError::javascript(e.to_string()))?;
@@ -115,6 +968,7 @@ impl Browser {
                 warn!("JavaScript disabled, skipping script execution");
             }
         }
+        timer.mark(Phase::Js);
 
         // Wait for specific element if requested
         if let Some(selector) = &options.wait_for {
@@ -122,6 +976,10 @@ impl Browser {
             self.wait_for_element(&tab, selector, options.timeout)
                 .await?;
         }
+        if let Some(watch) = &network_idle {
+            self.wait_for_network_idle(watch, options.timeout).await;
+        }
+        timer.mark(Phase::WaitFor);
 
         // Additional wait time
         if options.wait > 0 {
@@ -129,6 +987,60 @@ impl Browser {
             sleep(Duration::from_secs(options.wait)).await;
         }
 
+        if let Some(page_errors) = &page_errors {
+            page_errors.check(url)?;
+        }
+
+        let output_path = self
+            .capture_timed(&tab, output_path, options, &timer)
+            .await?;
+
+        Ok((tab, output_path, status, timer.timings()))
+    }
+
+    /// Open a tab set up from `options` (viewport, emulation, auth, headers)
+    /// for driving one step at a time, as the REPL does
+    pub async fn open_page(&self, options: &ScreenshotOptions) -> Result<Arc<Tab>> {
+        let tab = self
+            .chrome()
+            .new_tab()
+            .map_err(|e| WebshotError::Tab(e.to_string()))?;
+        self.setup_tab(&tab, options).await?;
+        Ok(tab)
+    }
+
+    /// Navigate an open tab and wait for the page to load
+    pub fn navigate(&self, tab: &Tab, url: &str) -> Result<()> {
+        validate_navigation_url(url, "navigate API")?;
+        info!("Navigating to: {}", url);
+        tab.navigate_to(url)
+            .map_err(|e| WebshotError::navigation(e.to_string()))?;
+        tab.wait_until_navigated()
+            .map_err(|e| WebshotError::navigation(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Capture the tab's current page without navigating, returning the path
+    /// written with any page tokens resolved
+    pub async fn capture_page<P: AsRef<Path>>(
+        &self,
+        tab: &Tab,
+        output_path: P,
+        options: &ScreenshotOptions,
+    ) -> Result<PathBuf> {
+        self.capture_timed(tab, output_path, options, &PhaseTimer::start())
+            .await
+    }
+
+    /// [`Browser::capture_page`], recording capture phases in `timer`
+    async fn capture_timed<P: AsRef<Path>>(
+        &self,
+        tab: &Tab,
+        output_path: P,
+        options: &ScreenshotOptions,
+        timer: &PhaseTimer,
+    ) -> Result<PathBuf> {
+        let output_path = resolve_page_tokens(tab, output_path.as_ref())?;
         let format = options.output_format(&output_path)?;
 
         match format {
@@ -137,42 +1049,53 @@ impl Browser {
                     "PDF generation not supported in screenshot method, use pdf() method instead",
                 ));
             }
-            ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::WebP => {
-                self.take_image_screenshot(&tab, &output_path, options, format)
+            ImageFormat::Png
+            | ImageFormat::Jpeg
+            | ImageFormat::WebP
+            | ImageFormat::Gif
+            | ImageFormat::Tiff => {
+                self.take_image_screenshot(tab, &output_path, options, format, timer)
                     .await?;
             }
         }
 
-        info!("Screenshot saved to: {}", output_path.as_ref().display());
-        Ok(())
+        Ok(output_path)
     }
 
     /// Generate a PDF from a webpage
-    #[allow(clippy::too_many_arguments)]
     pub async fn pdf<P: AsRef<Path>>(
         &self,
         url: &str,
         output_path: P,
-        _format: &str,
-        landscape: bool,
-        background: bool,
-        scale: f64,
-        javascript: Option<String>,
-        wait_for: Option<String>,
-        timeout: u64,
-        user_agent: Option<String>,
+        options: &PdfOptions,
     ) -> Result<()> {
         validate_navigation_url(url, "pdf API")?;
         let tab = self
-            .browser
+            .chrome()
             .new_tab()
             .map_err(|e| WebshotError::Tab(e.to_string()))?;
+        if !self.javascript_enabled {
+            disable_scripts(&tab)?;
+        }
 
         // Set up the tab
-        if let Some(user_agent) = user_agent {
-            tab.set_user_agent(&user_agent, None, None)
+        if let Some(user_agent) = &options.user_agent {
+            tab.set_user_agent(user_agent, None, None)
                 .map_err(WebshotError::Browser)?;
         }
+        intercept_requests(
+            &tab,
+            options.auth.as_ref(),
+            &options.blocker,
+            &Mocks::default(),
+        )?;
+        set_extra_headers(&tab, &options.headers)?;
+        let _har = options
+            .har
+            .as_deref()
+            .map(|path| HarRecorder::attach(&tab, path))
+            .transpose()?;
+        let network_idle = NetworkIdleWatch::attach(&tab, options.network_idle.as_ref())?;
 
         info!("Navigating to: {}", url);
         tab.navigate_to(url)
@@ -181,7 +1104,7 @@ impl Browser {
             .map_err(|e| WebshotError::navigation(e.to_string()))?;
 
         // Execute custom JavaScript if provided
-        if let Some(script) = &javascript {
+        if let Some(script) = &options.javascript {
             if self.javascript_enabled {
                 info!("Executing JavaScript: {}", script);
                 tab.evaluate(script, false)
@@ -192,20 +1115,71 @@ impl Browser {
         }
 
         // Wait for specific element if requested
-        if let Some(selector) = &wait_for {
+        if let Some(selector) = &options.wait_for {
             info!("Waiting for element: {}", selector);
-            self.wait_for_element(&tab, selector, timeout).await?;
+            self.wait_for_element(&tab, selector, options.timeout)
+                .await?;
+        }
+        if let Some(watch) = &network_idle {
+            self.wait_for_network_idle(watch, options.timeout).await;
+        }
+        if options.wait > 0 {
+            info!("Waiting {} seconds before printing", options.wait);
+            sleep(Duration::from_secs(options.wait)).await;
+        }
+
+        self.save_pdf(&tab, output_path, options)
+    }
+
+    /// Print an HTML document rather than a URL to a PDF file, such as a
+    /// generated table of contents
+    pub async fn pdf_from_html<P: AsRef<Path>>(
+        &self,
+        html: &str,
+        output_path: P,
+        options: &PdfOptions,
+    ) -> Result<()> {
+        use base64::Engine;
+
+        let tab = self
+            .chrome()
+            .new_tab()
+            .map_err(|e| WebshotError::Tab(e.to_string()))?;
+        if !self.javascript_enabled {
+            disable_scripts(&tab)?;
         }
+        let url = format!(
+            "data:text/html;base64,{}",
+            base64::engine::general_purpose::STANDARD.encode(html)
+        );
+        tab.navigate_to(&url)
+            .map_err(|e| WebshotError::navigation(e.to_string()))?;
+        tab.wait_until_navigated()
+            .map_err(|e| WebshotError::navigation(e.to_string()))?;
 
-        info!("Generating PDF...");
+        let result = self.save_pdf(&tab, output_path, options);
+        let _ = tab.close(false);
+        result
+    }
+
+    /// Print the tab's current page to a PDF file with the print settings
+    /// of `options`
+    pub fn save_pdf<P: AsRef<Path>>(
+        &self,
+        tab: &Tab,
+        output_path: P,
+        options: &PdfOptions,
+    ) -> Result<()> {
+        info!("Generating {} PDF...", options.paper);
+        let (paper_width, paper_height) = options.paper.inches();
 
         let pdf_options = PrintToPdfOptions {
-            landscape: Some(landscape),
+            landscape: Some(options.landscape),
             display_header_footer: Some(false),
-            print_background: Some(background),
-            scale: Some(scale),
-            paper_width: None,
-            paper_height: None,
+            print_background: Some(options.background),
+            scale: Some(options.scale),
+            paper_width: Some(paper_width),
+            paper_height: Some(paper_height),
             margin_top: None,
             margin_bottom: None,
             margin_left: None,
@@ -216,41 +1190,170 @@ impl Browser {
             footer_template: None,
             prefer_css_page_size: Some(true),
             transfer_mode: None,
-            generate_document_outline: Some(false),
-            generate_tagged_pdf: Some(false),
+            // Chrome builds the outline from the tagged structure, so it
+            // needs a tagged PDF
+            generate_document_outline: Some(options.outline),
+            generate_tagged_pdf: Some(options.outline),
         };
 
         let pdf_data = tab
             .print_to_pdf(Some(pdf_options))
             .map_err(|e| WebshotError::pdf(e.to_string()))?;
+        let pdf_data = if options.links {
+            self.repair_pdf_links(tab, pdf_data, options)?
+        } else {
+            let mut document =
+                Document::load_mem(&pdf_data).map_err(|e| WebshotError::pdf(e.to_string()))?;
+            let removed = strip_links(&mut document)?;
+            debug!("Removed {} links from the PDF", removed);
+            save_pdf_document(&mut document)?
+        };
         OutputHandler::ensure_output_dir(&output_path)?;
-        std::fs::write(&output_path, pdf_data)?;
+        OutputHandler::write_output(&output_path, &pdf_data)?;
 
         info!("PDF saved to: {}", output_path.as_ref().display());
         Ok(())
     }
 
-    /// Extract text content from a webpage
-    pub async fn extract_text(
+    /// Add link annotations for the links of the tab's page that Chrome
+    /// left out of `pdf_data`
+    fn repair_pdf_links(
         &self,
-        url: &str,
-        selector: Option<String>,
-        javascript: Option<String>,
-        wait_for: Option<String>,
-        timeout: u64,
-        user_agent: Option<String>,
-    ) -> Result<String> {
+        tab: &Tab,
+        pdf_data: Vec<u8>,
+        options: &PdfOptions,
+    ) -> Result<Vec<u8>> {
+        let mut document =
+            Document::load_mem(&pdf_data).map_err(|e| WebshotError::pdf(e.to_string()))?;
+        let annotated = link_count(&document);
+        if page_links(tab)?.len() <= annotated {
+            return Ok(pdf_data);
+        }
+
+        // Measure the links where they are printed: with print media at the
+        // printable width
+        let layout = PrintLayout::new(options.paper, options.landscape, options.scale);
+        tab.call_method(Emulation::SetEmulatedMedia {
+            media: Some("print".to_string()),
+            features: None,
+        })
+        .map_err(WebshotError::Browser)?;
+        tab.call_method(Emulation::SetDeviceMetricsOverride {
+            width: layout.width.round() as u32,
+            height: 0,
+            device_scale_factor: 0.0,
+            mobile: false,
+            scale: None,
+            screen_width: None,
+            screen_height: None,
+            position_x: None,
+            position_y: None,
+            dont_set_visible_size: None,
+            screen_orientation: None,
+            viewport: None,
+            display_feature: None,
+            device_posture: None,
+        })
+        .map_err(WebshotError::Browser)?;
+        let links = page_links(tab);
+        let _ = tab.call_method(Emulation::ClearDeviceMetricsOverride(None));
+        let _ = tab.call_method(Emulation::SetEmulatedMedia {
+            media: Some(String::new()),
+            features: None,
+        });
+
+        let added = add_missing_links(&mut document, &links?, &layout)?;
+        if added == 0 {
+            return Ok(pdf_data);
+        }
+        info!("Added {} links Chrome left out of the PDF", added);
+        save_pdf_document(&mut document)
+    }
+
+    /// Extract text content from a webpage
+    pub async fn extract_text(&self, url: &str, options: &TextOptions) -> Result<String> {
         validate_navigation_url(url, "text API")?;
+        let tab = self.load_text_page(url, options).await?;
+
+        let text = if let Some(selector_str) = &options.selector {
+            info!("Extracting text from element: {}", selector_str);
+            let element = tab
+                .find_element(selector_str)
+                .map_err(|_e| WebshotError::element_not_found(selector_str.clone()))?;
+            element.get_inner_text().map_err(WebshotError::Browser)?
+        } else {
+            info!("Extracting text from entire page");
+            tab.get_content().map_err(WebshotError::Browser)?
+        };
+
+        Ok(text)
+    }
+
+    /// Load `url` and read its heading hierarchy, within the element
+    /// `options.selector` matches if one is given
+    pub async fn extract_outline(&self, url: &str, options: &TextOptions) -> Result<Outline> {
+        validate_navigation_url(url, "outline API")?;
+        let tab = self.load_text_page(url, options).await?;
+
+        info!("Reading headings");
+        let result = tab
+            .evaluate(&outline_script(options.selector.as_deref())?, false)
+            .map_err(|e| WebshotError::javascript(e.to_string()))?;
+        let json = result
+            .value
+            .as_ref()
+            .and_then(|value| value.as_str())
+            .ok_or_else(|| WebshotError::javascript("Outline script returned no headings"))?;
+        let outline = Outline::from_script_result(url, json)?;
+        let _ = tab.close(false);
+        Ok(outline)
+    }
+
+    /// Load `url` with `options` and read the favicons, touch icons and
+    /// `og:image`s it links to, for downloading with
+    /// [`crate::assets::download_assets`]
+    pub async fn asset_links(&self, url: &str, options: &TextOptions) -> Result<PageAssets> {
+        validate_navigation_url(url, "assets API")?;
+        let tab = self.load_text_page(url, options).await?;
+
+        info!("Reading asset links");
+        let result = tab
+            .evaluate(assets_script(), false)
+            .map_err(|e| WebshotError::javascript(e.to_string()))?;
+        let json = result
+            .value
+            .as_ref()
+            .and_then(|value| value.as_str())
+            .ok_or_else(|| WebshotError::javascript("Assets script returned no links"))?;
+        let assets = PageAssets::from_script_result(url, json)?;
+        let _ = tab.close(false);
+        Ok(assets)
+    }
+
+    /// Open a tab set up from `options`, load `url`, run the options'
+    /// JavaScript and wait for their wait condition, network idle and delay
+    async fn load_text_page(&self, url: &str, options: &TextOptions) -> Result<Arc<Tab>> {
         let tab = self
-            .browser
+            .chrome()
             .new_tab()
             .map_err(|e| WebshotError::Tab(e.to_string()))?;
+        if !self.javascript_enabled {
+            disable_scripts(&tab)?;
+        }
 
         // Set up the tab
-        if let Some(user_agent) = user_agent {
-            tab.set_user_agent(&user_agent, None, None)
+        if let Some(user_agent) = &options.user_agent {
+            tab.set_user_agent(user_agent, None, None)
                 .map_err(WebshotError::Browser)?;
         }
+        intercept_requests(
+            &tab,
+            options.auth.as_ref(),
+            &options.blocker,
+            &Mocks::default(),
+        )?;
+        set_extra_headers(&tab, &options.headers)?;
+        let network_idle = NetworkIdleWatch::attach(&tab, options.network_idle.as_ref())?;
 
         info!("Navigating to: {}", url);
         tab.navigate_to(url)
@@ -259,7 +1362,7 @@ impl Browser {
             .map_err(|e| WebshotError::navigation(e.to_string()))?;
 
         // Execute custom JavaScript if provided
-        if let Some(script) = &javascript {
+        if let Some(script) = &options.javascript {
             if self.javascript_enabled {
                 info!("Executing JavaScript: {}", script);
                 tab.evaluate(script, false)
@@ -270,123 +1373,751 @@ impl Browser {
         }
 
         // Wait for specific element if requested
-        if let Some(selector_str) = &wait_for {
+        if let Some(selector_str) = &options.wait_for {
             info!("Waiting for element: {}", selector_str);
-            self.wait_for_element(&tab, selector_str, timeout).await?;
+            self.wait_for_element(&tab, selector_str, options.timeout)
+                .await?;
+        }
+        if let Some(watch) = &network_idle {
+            self.wait_for_network_idle(watch, options.timeout).await;
+        }
+        if options.wait > 0 {
+            sleep(Duration::from_secs(options.wait)).await;
         }
 
-        let text = if let Some(selector_str) = selector {
-            info!("Extracting text from element: {}", selector_str);
-            let element = tab
-                .find_element(&selector_str)
-                .map_err(|_e| WebshotError::element_not_found(selector_str))?;
-            element.get_inner_text().map_err(WebshotError::Browser)?
-        } else {
-            info!("Extracting text from entire page");
-            tab.get_content().map_err(WebshotError::Browser)?
-        };
+        Ok(tab)
+    }
 
-        Ok(text)
+    /// Load `url` set up from `options` and record the bounding box of the
+    /// first element matching each of `selectors`, for layout comparison
+    pub async fn capture_layout(
+        &self,
+        url: &str,
+        selectors: &[String],
+        options: &ScreenshotOptions,
+    ) -> Result<LayoutSnapshot> {
+        let tab = self.open_page(options).await?;
+        self.navigate(&tab, url)?;
+
+        if let Some(selector) = &options.wait_for {
+            self.wait_for_element(&tab, selector, options.timeout)
+                .await?;
+        }
+        if options.wait > 0 {
+            sleep(Duration::from_secs(options.wait)).await;
+        }
+
+        let result = tab
+            .evaluate(&layout_script(selectors)?, false)
+            .map_err(|e| WebshotError::javascript(e.to_string()))?;
+        let json = result
+            .value
+            .as_ref()
+            .and_then(|value| value.as_str())
+            .ok_or_else(|| WebshotError::javascript("Layout script returned no boxes"))?;
+        let snapshot = LayoutSnapshot::from_script_result(
+            url,
+            options.width,
+            options.height,
+            selectors,
+            json,
+        )?;
+        let _ = tab.close(false);
+        Ok(snapshot)
     }
 
-    /// Process multiple screenshots from configuration
+    /// Open the page and wait until the user clicks an element, returning a
+    /// selector for it. Meant for browsers created with
+    /// [`Browser::new_headful`]. Escape in the page cancels the pick.
+    pub async fn pick_element(&self, url: &str, timeout: u64) -> Result<PickedElement> {
+        validate_navigation_url(url, "pick API")?;
+        let tab = self
+            .chrome()
+            .new_tab()
+            .map_err(|e| WebshotError::Tab(e.to_string()))?;
+
+        // Registered before navigation so the picker survives page reloads
+        let script = picker_script();
+        tab.call_method(Page::AddScriptToEvaluateOnNewDocument {
+            source: script.clone(),
+            world_name: None,
+            include_command_line_api: None,
+            run_immediately: None,
+        })
+        .map_err(WebshotError::Browser)?;
+
+        info!("Navigating to: {}", url);
+        tab.navigate_to(url)
+            .map_err(|e| WebshotError::navigation(e.to_string()))?;
+        tab.wait_until_navigated()
+            .map_err(|e| WebshotError::navigation(e.to_string()))?;
+        tab.evaluate(&script, false)
+            .map_err(|e| WebshotError::javascript(e.to_string()))?;
+
+        let start = std::time::Instant::now();
+        let timeout_duration = Duration::from_secs(timeout);
+        loop {
+            if start.elapsed() > timeout_duration {
+                return Err(WebshotError::timeout("waiting for an element pick"));
+            }
+
+            let picked = tab
+                .evaluate(PICK_RESULT_EXPRESSION, false)
+                .map_err(|e| WebshotError::javascript(e.to_string()))?;
+            if let Some(serde_json::Value::String(json)) = picked.value {
+                let element = PickedElement::from_json(&json)?;
+                debug!("Picked <{}> as {}", element.tag, element.selector);
+                return Ok(element);
+            }
+
+            sleep(Duration::from_millis(200)).await;
+        }
+    }
+
+    /// Process multiple screenshots from configuration, retrying entries as
+    /// the config's defaults allow. Returns the final state of every job.
     pub async fn process_config(
         &self,
         config: &Config,
         output_dir: Option<PathBuf>,
         parallel: usize,
-    ) -> Result<()> {
+    ) -> Result<Vec<JobEntry>> {
         config.validate()?;
 
+        let monitor = BatchMonitor::new(config.jobs(), false);
         info!(
             "Processing {} screenshots with {} parallel tasks",
-            config.screenshots.len(),
+            monitor.job_count(),
             parallel
         );
 
-        use futures::stream::{self, StreamExt};
-
-        let semaphore = Arc::new(tokio::sync::Semaphore::new(parallel));
+        self.run_jobs(
+            &monitor,
+            output_dir,
+            parallel,
+            &config.defaults.retry_policy(),
+            &config.defaults.rate_limit,
+        )
+        .await;
+
+        let jobs = monitor.jobs();
+        for (i, job) in jobs.iter().enumerate() {
+            match &job.status {
+                JobStatus::Failed(e) => warn!("Screenshot {} failed: {}", i, e),
+                JobStatus::Done if job.attempts > 1 => {
+                    info!("Screenshot {} succeeded after {} attempts", i, job.attempts)
+                }
+                _ => {}
+            }
+        }
 
-        let tasks = config.screenshots.iter().map(|screenshot_config| {
-            let semaphore = semaphore.clone();
-            let screenshot_config = screenshot_config.clone();
-            let output_dir = output_dir.clone();
+        Ok(jobs)
+    }
 
-            async move {
-                let _permit = semaphore.acquire().await.unwrap();
-                self.process_single_screenshot(screenshot_config, output_dir)
+    /// Run a monitor's jobs on up to `parallel` concurrent tabs until the
+    /// monitor has no more work, retrying transient failures per `retry` and
+    /// pacing page loads per host per `rate_limit`
+    pub async fn run_jobs(
+        &self,
+        monitor: &BatchMonitor,
+        output_dir: Option<PathBuf>,
+        parallel: usize,
+        retry: &RetryPolicy,
+        rate_limit: &RateLimit,
+    ) {
+        let limiter = HostRateLimiter::new(rate_limit);
+        let workers = (0..parallel.max(1)).map(|_| async {
+            while let Some((index, screenshot_config)) = monitor.next_job().await {
+                let what = format!("Screenshot of {}", screenshot_config.url);
+                let result = retry
+                    .run(&what, |attempt| {
+                        if attempt > 1 {
+                            monitor.restart(index);
+                        }
+                        let screenshot_config = screenshot_config.clone();
+                        let output_dir = output_dir.clone();
+                        let limiter = &limiter;
+                        async move {
+                            limiter.acquire(&screenshot_config.url).await;
+                            self.process_single_screenshot(screenshot_config, output_dir)
+                                .await
+                        }
+                    })
                     .await
+                    .map(|(status, timings)| monitor.record_capture(index, status, timings));
+                monitor.finish(index, &result);
             }
         });
 
-        let results: Vec<Result<()>> = stream::iter(tasks)
-            .buffer_unordered(parallel)
-            .collect()
-            .await;
+        futures::future::join_all(workers).await;
+    }
 
-        // Check for errors
-        for (i, result) in results.into_iter().enumerate() {
-            if let Err(e) = result {
-                warn!("Screenshot {} failed: {}", i, e);
+    /// Roll for a `--chaos` fault and inject delays and dropped requests
+    /// before navigation, returning the fault so a crash can follow it
+    #[cfg(feature = "chaos")]
+    async fn inject_fault(&self, tab: &Tab) -> Result<Option<Fault>> {
+        let Some(fault) = self.chaos.as_ref().and_then(|chaos| chaos.next_fault()) else {
+            return Ok(None);
+        };
+        warn!("Injecting {}", fault);
+        match &fault {
+            Fault::Delay(delay) => tokio::time::sleep(*delay).await,
+            Fault::DropRequests(pattern) => {
+                tab.call_method(Network::Enable {
+                    max_total_buffer_size: None,
+                    max_resource_buffer_size: None,
+                    max_post_data_size: None,
+                    report_direct_socket_traffic: None,
+                    enable_durable_messages: None,
+                })
+                .map_err(WebshotError::Browser)?;
+                tab.call_method(Network::SetBlockedURLs {
+                    urls: vec![pattern.to_string()],
+                })
+                .map_err(WebshotError::Browser)?;
             }
+            Fault::CrashTab => {}
         }
+        Ok(Some(fault))
+    }
 
-        Ok(())
+    /// Whether page scripts run in captures with `options`
+    fn scripts_enabled(&self, options: &ScreenshotOptions) -> bool {
+        self.javascript_enabled && !options.disable_javascript
     }
 
     async fn setup_tab(&self, tab: &Tab, options: &ScreenshotOptions) -> Result<()> {
         // Set viewport using emulation
         tab.set_default_timeout(std::time::Duration::from_secs(options.timeout));
 
-        tab.call_method(
-            headless_chrome::protocol::cdp::Emulation::SetDeviceMetricsOverride {
-                width: options.width,
-                height: options.height,
-                device_scale_factor: options.device_scale_factor(),
-                mobile: false,
-                scale: None,
-                screen_width: None,
-                screen_height: None,
-                position_x: None,
-                position_y: None,
-                dont_set_visible_size: None,
-                screen_orientation: None,
-                viewport: None,
-                display_feature: None,
-                device_posture: None,
-            },
-        )
-        .map_err(WebshotError::Browser)?;
+        if !self.scripts_enabled(options) {
+            disable_scripts(tab)?;
+        }
+
+        let (width, height) = options.viewport_size();
+        set_device_metrics(tab, options, width, height)?;
+
+        if options.emulates_touch() {
+            debug!("Enabling touch emulation");
+            tab.call_method(
+                headless_chrome::protocol::cdp::Emulation::SetTouchEmulationEnabled {
+                    enabled: true,
+                    max_touch_points: Some(5),
+                },
+            )
+            .map_err(WebshotError::Browser)?;
+        }
+
+        if options.hides_scrollbars() {
+            debug!("Hiding scrollbars");
+            tab.call_method(
+                headless_chrome::protocol::cdp::Emulation::SetScrollbarsHidden { hidden: true },
+            )
+            .map_err(WebshotError::Browser)?;
+        }
+
+        let media_features = emulated_media_features(options);
+        if !media_features.is_empty() || options.paginate_preview.is_some() {
+            tab.call_method(
+                headless_chrome::protocol::cdp::Emulation::SetEmulatedMedia {
+                    media: options.paginate_preview.map(|_| "print".to_string()),
+                    features: Some(media_features),
+                },
+            )
+            .map_err(WebshotError::Browser)?;
+        }
+
+        if !options.init_scripts.is_empty() && !self.scripts_enabled(options) {
+            warn!("JavaScript disabled, init scripts will not run");
+        }
+        for source in &options.init_scripts {
+            debug!("Adding init script ({} bytes)", source.len());
+            tab.call_method(Page::AddScriptToEvaluateOnNewDocument {
+                source: source.clone(),
+                world_name: None,
+                include_command_line_api: None,
+                run_immediately: None,
+            })
+            .map_err(WebshotError::Browser)?;
+        }
+
+        if let Some(session) = &options.session {
+            self.restore_session(tab, session)?;
+        }
+
+        intercept_requests(
+            tab,
+            options.auth.as_ref(),
+            &RequestBlocker::new(&options.block_resources).with_filters(options.adblock.clone()),
+            &options.mocks,
+        )?;
+
+        set_extra_headers(tab, &options.headers)?;
 
         // Set user agent if provided
-        if let Some(user_agent) = &options.user_agent {
+        if let Some(user_agent) = options.effective_user_agent() {
             tab.set_user_agent(user_agent, None, None)
                 .map_err(WebshotError::Browser)?;
         }
-
-        Ok(())
+
+        Ok(())
+    }
+
+    /// Set saved cookies and register a script restoring saved web storage
+    fn restore_session(&self, tab: &Tab, session: &SessionState) -> Result<()> {
+        debug!("Restoring session ({} cookies)", session.cookies.len());
+        if !session.cookies.is_empty() {
+            tab.call_method(Network::SetCookies {
+                cookies: session
+                    .cookies
+                    .iter()
+                    .map(|cookie| cookie.to_param())
+                    .collect(),
+            })
+            .map_err(WebshotError::Browser)?;
+        }
+
+        if let Some(script) = session.restore_storage_script()? {
+            if !self.javascript_enabled {
+                warn!("JavaScript disabled, saved web storage will not be restored");
+            }
+            tab.call_method(Page::AddScriptToEvaluateOnNewDocument {
+                source: script,
+                world_name: None,
+                include_command_line_api: None,
+                run_immediately: None,
+            })
+            .map_err(WebshotError::Browser)?;
+        }
+
+        Ok(())
+    }
+
+    /// Click away or hide consent overlays matched by the dismissal rules.
+    /// Pages without a matching overlay cost only the rules' wait time.
+    pub fn dismiss_overlays(&self, tab: &Tab, dismissal: &OverlayDismissal) -> Result<()> {
+        if !self.javascript_enabled {
+            warn!("JavaScript disabled, skipping overlay dismissal");
+            return Ok(());
+        }
+
+        let result = tab
+            .evaluate(&dismissal.script()?, true)
+            .map_err(|e| WebshotError::javascript(e.to_string()))?;
+        let matched: Vec<String> = result
+            .value
+            .and_then(|value| value.as_str().map(serde_json::from_str))
+            .transpose()?
+            .unwrap_or_default();
+
+        if matched.is_empty() {
+            debug!("No consent overlay found");
+        } else {
+            info!("Dismissed consent overlays: {}", matched.join(", "));
+        }
+        Ok(())
+    }
+
+    /// Run page interactions in order. Selectors are waited for up to
+    /// `timeout` seconds before they are clicked or typed into.
+    pub async fn run_steps(&self, tab: &Tab, steps: &[Step], timeout: u64) -> Result<()> {
+        for step in steps {
+            debug!("Running step: {:?}", step);
+            match step {
+                Step::Goto(url) => self.navigate(tab, url)?,
+                Step::Click(selector) => {
+                    self.wait_for_element(tab, selector, timeout).await?;
+                    tab.find_element(selector)
+                        .map_err(|_e| WebshotError::element_not_found(selector.clone()))?
+                        .click()
+                        .map_err(WebshotError::Browser)?;
+                }
+                Step::Type { selector, text } => {
+                    self.wait_for_element(tab, selector, timeout).await?;
+                    tab.find_element(selector)
+                        .map_err(|_e| WebshotError::element_not_found(selector.clone()))?
+                        .type_into(text)
+                        .map_err(WebshotError::Browser)?;
+                }
+                Step::Press(key) => {
+                    tab.press_key(key).map_err(WebshotError::Browser)?;
+                }
+                Step::WaitFor(selector) => self.wait_for_element(tab, selector, timeout).await?,
+                Step::Wait(millis) => sleep(Duration::from_millis(*millis)).await,
+            }
+        }
+        Ok(())
+    }
+
+    /// Record trusted clicks, typing and Enter presses in the tab as steps,
+    /// appended to the returned list as they happen, across navigations
+    pub fn record_steps(&self, tab: &Tab) -> Result<Arc<Mutex<Vec<Step>>>> {
+        // Bindings must be registered through `expose_function`: the tab's
+        // event loop panics on calls to bindings it does not know about
+        let steps = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&steps);
+        tab.expose_function(
+            RECORDER_BINDING,
+            Arc::new(move |payload: serde_json::Value| {
+                let Some(payload) = payload.as_str() else {
+                    return;
+                };
+                match Step::from_recorder_payload(payload) {
+                    Ok(step) => {
+                        debug!("Recorded step: {:?}", step);
+                        recorded.lock().unwrap().push(step);
+                    }
+                    Err(e) => warn!("{}", e),
+                }
+            }),
+        )
+        .map_err(WebshotError::Browser)?;
+
+        let script = recorder_script();
+        tab.call_method(Page::AddScriptToEvaluateOnNewDocument {
+            source: script.clone(),
+            world_name: None,
+            include_command_line_api: None,
+            run_immediately: None,
+        })
+        .map_err(WebshotError::Browser)?;
+        tab.evaluate(&script, false)
+            .map_err(|e| WebshotError::javascript(e.to_string()))?;
+
+        Ok(steps)
+    }
+
+    /// Wait until a `wait_for` condition holds, failing after `timeout`
+    /// seconds. A plain selector waits for a matching element; see
+    /// [`WaitCondition`] for visibility, text and combined conditions.
+    pub async fn wait_for_element(&self, tab: &Tab, selector: &str, timeout: u64) -> Result<()> {
+        let condition: WaitCondition = selector.parse()?;
+        self.wait_until(tab, &condition, timeout).await
+    }
+
+    /// Wait until `condition` holds in the page, failing after `timeout`
+    /// seconds. The page rechecks it on every DOM mutation and poll interval;
+    /// when that cannot run (such as after the page navigates), the
+    /// condition is polled over DevTools instead.
+    pub async fn wait_until(
+        &self,
+        tab: &Tab,
+        condition: &WaitCondition,
+        timeout: u64,
+    ) -> Result<()> {
+        let start = std::time::Instant::now();
+        let timeout_duration = Duration::from_secs(timeout);
+
+        match tab.evaluate(
+            &condition.observer_script(timeout_duration, self.poll_interval),
+            true,
+        ) {
+            Ok(result) if result.value == Some(serde_json::Value::Bool(true)) => {
+                debug!("Wait condition met: {}", condition);
+                return Ok(());
+            }
+            Ok(_) => return Err(WebshotError::timeout(format!("waiting for {}", condition))),
+            Err(e) => debug!("In-page wait failed, polling instead: {}", e),
+        }
+
+        let script = condition.script();
+        loop {
+            if start.elapsed() > timeout_duration {
+                return Err(WebshotError::timeout(format!("waiting for {}", condition)));
+            }
+
+            let result = tab
+                .evaluate(&script, false)
+                .map_err(|e| WebshotError::javascript(e.to_string()))?;
+            if result.value == Some(serde_json::Value::Bool(true)) {
+                debug!("Wait condition met: {}", condition);
+                return Ok(());
+            }
+
+            sleep(self.poll_interval).await;
+        }
+    }
+
+    /// Wait for the network to go quiet in every frame, capturing anyway
+    /// when it does not within `timeout` seconds
+    async fn wait_for_network_idle(&self, watch: &NetworkIdleWatch, timeout: u64) {
+        info!("Waiting for network idle");
+        if let Err(busy) = watch
+            .wait(Duration::from_secs(timeout), self.poll_interval)
+            .await
+        {
+            warn!(
+                "Network still busy after {}s, capturing anyway: {}",
+                timeout,
+                busy.join(", ")
+            );
+        }
+    }
+
+    /// Scroll through the page to trigger lazy-loaded content, then wait for
+    /// the network to settle (bounded by the capture timeout).
+    async fn load_lazy_content(&self, tab: &Tab, options: &ScreenshotOptions) -> Result<()> {
+        debug!("Scrolling page to trigger lazy-loaded content");
+        let scroll_script = LAZY_SCROLL_SCRIPT
+            .replace(
+                "__STEP__",
+                &options.lazy_scroll_step.unwrap_or(0).to_string(),
+            )
+            .replace("__SETTLE_MS__", &options.lazy_settle_ms.to_string());
+        tab.evaluate(&scroll_script, true)
+            .map_err(|e| WebshotError::javascript(e.to_string()))?;
+
+        let idle_timeout_ms = (options.timeout * 1000).min(NETWORK_IDLE_MAX_WAIT_MS);
+        let idle_script =
+            NETWORK_IDLE_SCRIPT.replace("__TIMEOUT_MS__", &idle_timeout_ms.to_string());
+        let settled = tab
+            .evaluate(&idle_script, true)
+            .map_err(|e| WebshotError::javascript(e.to_string()))?;
+
+        if settled.value.and_then(|v| v.as_bool()) != Some(true) {
+            warn!("Network did not settle before timeout, capturing anyway");
+        }
+
+        Ok(())
+    }
+
+    /// Capture what the viewport shows at its scroll position. The capture
+    /// is clipped to the viewport, so it is the viewport's size however tall
+    /// the page is and whatever Chrome's surface holds.
+    fn capture_viewport(
+        &self,
+        tab: &Tab,
+        options: &ScreenshotOptions,
+        encoding: &CaptureEncoding,
+    ) -> Result<Vec<u8>> {
+        let json = tab
+            .evaluate(VIEWPORT_BOX_SCRIPT, false)
+            .map_err(|e| WebshotError::javascript(e.to_string()))?
+            .value
+            .and_then(|value| value.as_str().map(str::to_string))
+            .ok_or_else(|| WebshotError::screenshot("Cannot measure the viewport".to_string()))?;
+        let clip: ClipRegion = serde_json::from_str(&json)?;
+        debug!(
+            "Viewport: {}x{} at ({}, {})",
+            clip.width, clip.height, clip.x, clip.y
+        );
+        self.capture_clip(tab, &clip, options, encoding)
+    }
+
+    /// Capture the entire scrollable area of the page
+    fn capture_full_page(
+        &self,
+        tab: &Tab,
+        options: &ScreenshotOptions,
+        encoding: &CaptureEncoding,
+    ) -> Result<Vec<u8>> {
+        let metrics = tab
+            .call_method(Page::GetLayoutMetrics(None))
+            .map_err(WebshotError::Browser)?;
+        let content = metrics.css_content_size;
+        debug!(
+            "Full page content size: {}x{}",
+            content.width, content.height
+        );
+        let region = Page::Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: content.width.ceil(),
+            height: content.height.ceil(),
+            scale: 1.0,
+        };
+
+        if self.capabilities.capture_beyond_viewport() {
+            return self.capture_region(tab, region, encoding);
+        }
+
+        // Chrome without captureBeyondViewport only captures what the
+        // viewport shows, so grow the viewport to the page for the capture
+        debug!("Resizing viewport to the page for {}", self.capabilities);
+        set_device_metrics(tab, options, region.width as u32, region.height as u32)?;
+        let captured = self.capture_region(tab, region, encoding);
+        let (width, height) = options.viewport_size();
+        set_device_metrics(tab, options, width, height)?;
+        captured
+    }
+
+    /// Capture a document region, including parts outside the current viewport
+    fn capture_region(
+        &self,
+        tab: &Tab,
+        region: Page::Viewport,
+        encoding: &CaptureEncoding,
+    ) -> Result<Vec<u8>> {
+        use base64::Engine;
+
+        let data = tab
+            .call_method(Page::CaptureScreenshot {
+                format: Some(encoding.format.clone()),
+                quality: encoding.quality,
+                clip: Some(region),
+                from_surface: Some(true),
+                capture_beyond_viewport: self
+                    .capabilities
+                    .capture_beyond_viewport()
+                    .then_some(true),
+                optimize_for_speed: encoding.optimize_for_speed,
+            })
+            .map_err(|e| WebshotError::screenshot(e.to_string()))?
+            .data;
+
+        base64::engine::general_purpose::STANDARD
+            .decode(data)
+            .map_err(|e| WebshotError::screenshot(format!("Invalid screenshot data: {}", e)))
+    }
+
+    /// Capture a document region at the pixel ratio of `options`, so crops
+    /// are as sharp as page captures in retina mode
+    fn capture_clip(
+        &self,
+        tab: &Tab,
+        clip: &ClipRegion,
+        options: &ScreenshotOptions,
+        encoding: &CaptureEncoding,
+    ) -> Result<Vec<u8>> {
+        let region = |scale: f64| Page::Viewport {
+            x: clip.x as f64,
+            y: clip.y as f64,
+            width: clip.width as f64,
+            height: clip.height as f64,
+            scale,
+        };
+        let data = self.capture_region(tab, region(1.0), encoding)?;
+
+        let scale = options.device_scale_factor();
+        if scale > 1.0 {
+            // Some Chrome builds capture clips in CSS pixels whatever the
+            // emulated pixel ratio; capture those again scaled up
+            let (width, _) = image::io::Reader::new(std::io::Cursor::new(&data))
+                .with_guessed_format()?
+                .into_dimensions()?;
+            let expected = (f64::from(clip.width) * scale).round() as u32;
+            if width < expected {
+                debug!(
+                    "Clip captured {} pixels wide instead of {}, capturing at {}x",
+                    width, expected, scale
+                );
+                return self.capture_region(tab, region(scale), encoding);
+            }
+        }
+        Ok(data)
+    }
+
+    /// Region of the document the first element matching `selector` covers,
+    /// grown by `padding` CSS pixels on every side
+    fn element_clip(&self, tab: &Tab, selector: &str, padding: u32) -> Result<ClipRegion> {
+        let json = tab
+            .evaluate(&element_box_script(selector)?, false)
+            .map_err(|e| WebshotError::javascript(e.to_string()))?
+            .value
+            .and_then(|value| value.as_str().map(str::to_string))
+            .ok_or_else(|| WebshotError::element_not_found(selector.to_string()))?;
+        let element_box: ElementBox = serde_json::from_str(&json)?;
+        element_box
+            .padded(padding)
+            .ok_or_else(|| WebshotError::screenshot(format!("{} has no visible area", selector)))
+    }
+
+    /// Capture the `elements` of `config` from the tab's loaded page next to
+    /// `output_path`, and write their manifest
+    async fn capture_elements(
+        &self,
+        tab: &Tab,
+        config: &ScreenshotConfig,
+        output_path: &Path,
+        options: &ScreenshotOptions,
+        format: ImageFormat,
+        timer: &PhaseTimer,
+    ) -> Result<ElementManifest> {
+        // Elements are cropped from the page as it is; lazy content was
+        // loaded for the page capture already
+        let base_options = ScreenshotOptions {
+            selector: None,
+            capture: CaptureMode::Viewport,
+            trigger_lazy: false,
+            paginate_preview: None,
+            auto_height: false,
+            ..options.clone()
+        };
+        let mut manifest = ElementManifest::new(&config.url, output_path);
+        for (index, element) in config.elements.iter().enumerate() {
+            let mut entry = ElementEntry::new(element, index);
+            let path = element_output(output_path, &entry.name);
+            match self
+                .capture_element(tab, element, &path, &base_options, format, timer)
+                .await
+            {
+                Ok(clip) => {
+                    info!("Element {} saved to: {}", entry.name, path.display());
+                    entry.output = Some(path);
+                    entry.clip = Some(clip);
+                }
+                Err(e) => {
+                    warn!("Could not capture element {}: {}", entry.name, e);
+                    entry.error = Some(e.to_string());
+                }
+            }
+            manifest.elements.push(entry);
+        }
+        manifest.write(&element_manifest_path(output_path))?;
+        Ok(manifest)
     }
 
-    async fn wait_for_element(&self, tab: &Tab, selector: &str, timeout: u64) -> Result<()> {
-        let start = std::time::Instant::now();
-        let timeout_duration = Duration::from_secs(timeout);
-
-        loop {
-            if start.elapsed() > timeout_duration {
-                return Err(WebshotError::timeout(format!(
-                    "waiting for element: {}",
-                    selector
-                )));
+    /// Capture one element, in its forced state if it has one, returning
+    /// the region it was cropped to
+    async fn capture_element(
+        &self,
+        tab: &Tab,
+        element: &ElementCapture,
+        path: &Path,
+        options: &ScreenshotOptions,
+        format: ImageFormat,
+        timer: &PhaseTimer,
+    ) -> Result<ClipRegion> {
+        let node_id = match element.state {
+            Some(state) => {
+                let node = tab
+                    .find_element(&element.selector)
+                    .map_err(|_e| WebshotError::element_not_found(element.selector.clone()))?;
+                tab.call_method(DOM::Enable {
+                    include_whitespace: None,
+                })
+                .map_err(WebshotError::Browser)?;
+                tab.call_method(CSS::Enable(None))
+                    .map_err(WebshotError::Browser)?;
+                tab.call_method(CSS::ForcePseudoState {
+                    node_id: node.node_id,
+                    forced_pseudo_classes: vec![state.pseudo_class().to_string()],
+                })
+                .map_err(WebshotError::Browser)?;
+                Some(node.node_id)
             }
+            None => None,
+        };
 
-            if tab.find_element(selector).is_ok() {
-                debug!("Element found: {}", selector);
-                return Ok(());
-            }
+        let result = async {
+            let clip = self.element_clip(tab, &element.selector, element.padding)?;
+            let options = ScreenshotOptions {
+                clip: Some(clip),
+                ..options.clone()
+            };
+            self.take_image_screenshot(tab, path, &options, format, timer)
+                .await?;
+            Ok(clip)
+        }
+        .await;
 
-            sleep(Duration::from_millis(100)).await;
+        if let Some(node_id) = node_id {
+            let _ = tab.call_method(CSS::ForcePseudoState {
+                node_id,
+                forced_pseudo_classes: Vec::new(),
+            });
         }
+        result
     }
 
     async fn take_image_screenshot<P: AsRef<Path>>(
@@ -395,73 +2126,184 @@ impl Browser {
         output_path: P,
         options: &ScreenshotOptions,
         format: ImageFormat,
+        timer: &PhaseTimer,
     ) -> Result<()> {
+        if options.loads_lazy_content() {
+            self.load_lazy_content(tab, options).await?;
+        }
+
+        if options.stabilize {
+            debug!("Stabilizing page for deterministic capture");
+            tab.evaluate(STABILIZE_SCRIPT, false)
+                .map_err(|e| WebshotError::javascript(e.to_string()))?;
+        }
+
+        if options.freeze_sticky && options.captures_full_page() {
+            let frozen = tab
+                .evaluate(FREEZE_STICKY_SCRIPT, false)
+                .map_err(|e| WebshotError::javascript(e.to_string()))?;
+            debug!(
+                "Froze {} sticky or fixed elements",
+                frozen.value.unwrap_or_default()
+            );
+        }
+
+        if options.auto_height && options.captures_viewport() {
+            fit_viewport_to_content(tab, options)?;
+        }
+
+        if options.anchor_scroll && options.captures_viewport() {
+            scroll_to_anchor(tab)?;
+        }
+
+        if !options.mask_selectors.is_empty() {
+            let masked = tab
+                .evaluate(&mask_script(options)?, false)
+                .map_err(|e| WebshotError::javascript(e.to_string()))?;
+            debug!("Masked {} elements", masked.value.unwrap_or_default());
+        }
+        timer.mark(Phase::Settle);
+
+        let encoding = CaptureEncoding::for_output(format, options, &self.capabilities);
         let screenshot_data = if let Some(selector) = &options.selector {
             info!("Taking element screenshot: {}", selector);
-            let element = tab
-                .find_element(selector)
-                .map_err(|_e| WebshotError::element_not_found(selector.clone()))?;
-            element
-                .capture_screenshot(Page::CaptureScreenshotFormatOption::Png)
-                .map_err(|e| WebshotError::screenshot(e.to_string()))?
+            let clip = self.element_clip(tab, selector, 0)?;
+            self.capture_clip(tab, &clip, options, &encoding)?
+        } else if let Some(clip) = &options.clip {
+            info!(
+                "Taking clipped screenshot: {}x{} at ({}, {})",
+                clip.width, clip.height, clip.x, clip.y
+            );
+            self.capture_clip(tab, clip, options, &encoding)?
         } else {
-            info!("Taking full page screenshot");
-            tab.capture_screenshot(Page::CaptureScreenshotFormatOption::Png, None, None, true)
-                .map_err(|e| WebshotError::screenshot(e.to_string()))?
+            match options.capture {
+                CaptureMode::FullPage => {
+                    info!("Taking full page screenshot");
+                    self.capture_full_page(tab, options, &encoding)?
+                }
+                CaptureMode::Viewport => {
+                    info!("Taking viewport screenshot");
+                    self.capture_viewport(tab, options, &encoding)?
+                }
+            }
         };
+        timer.mark(Phase::Capture);
 
         OutputHandler::ensure_output_dir(&output_path)?;
 
-        match format {
-            ImageFormat::Png => {
-                std::fs::write(&output_path, screenshot_data)?;
+        let screenshot_data = match options.paginate_preview {
+            Some(paper) => {
+                debug!("Drawing {} page breaks", paper);
+                draw_page_breaks(&screenshot_data, paper, options.device_scale_factor())?
+            }
+            None => screenshot_data,
+        };
+
+        let screenshot_data = if options.image_filters.is_empty() {
+            screenshot_data
+        } else {
+            debug!("Applying {} image filter(s)", options.image_filters.len());
+            apply_image_filters(&screenshot_data, &options.image_filters)?
+        };
+
+        if options.reduces_png() && format != ImageFormat::Png {
+            warn!("PNG color reduction only applies to PNG output, ignoring");
+        }
+
+        let icc_profile = self.color_profile.map(|profile| profile.icc_profile());
+        let mut output = match format {
+            _ if encoding.is_direct() => screenshot_data,
+            ImageFormat::Png if options.reduces_png() => {
+                let reduced = OutputHandler::reduce_png(
+                    &screenshot_data,
+                    options.png_colors,
+                    options.grayscale,
+                )?;
+                info!(
+                    "Reduced PNG size: {}",
+                    format_size_reduction(screenshot_data.len() as u64, reduced.len() as u64)
+                );
+                reduced
             }
+            ImageFormat::Png => screenshot_data,
             ImageFormat::Jpeg => {
                 // Convert PNG to JPEG
                 let img = image::load_from_memory(&screenshot_data)?;
-                let mut output = std::fs::File::create(&output_path)?;
+                let mut output = Vec::new();
                 let quality = options.quality.unwrap_or(90);
 
                 let encoder =
                     image::codecs::jpeg::JpegEncoder::new_with_quality(&mut output, quality);
                 img.write_with_encoder(encoder)?;
+                output
             }
             ImageFormat::WebP => {
-                // Convert PNG to WebP
+                // Convert PNG to WebP. Chrome encodes lossy WebP itself, so
+                // only captures that needed processing, or from a browser
+                // that cannot capture WebP, end up here with a quality
+                if options.quality.is_some() {
+                    warn!(
+                        "WebP quality only applies when Chrome encodes the capture, encoding losslessly"
+                    );
+                }
                 let img = image::load_from_memory(&screenshot_data)?;
-                let mut output = std::fs::File::create(&output_path)?;
+                let mut output = Vec::new();
 
                 let encoder = image::codecs::webp::WebPEncoder::new_lossless(&mut output);
                 img.write_with_encoder(encoder)?;
+                output
+            }
+            ImageFormat::Gif => {
+                let img = image::load_from_memory(&screenshot_data)?;
+                OutputHandler::encode_gif(&img, options.quality)?
+            }
+            ImageFormat::Tiff => {
+                let img = image::load_from_memory(&screenshot_data)?;
+                OutputHandler::encode_tiff(&img, options.tiff_compression, icc_profile.as_deref())?
             }
             ImageFormat::Pdf => {
                 return Err(WebshotError::screenshot(
                     "PDF format should be handled by pdf() method",
                 ));
             }
+        };
+
+        if let Some(icc_profile) = &icc_profile {
+            debug!(
+                "Embedding {} color profile",
+                self.color_profile.unwrap_or_default()
+            );
+            output = OutputHandler::embed_icc_profile(&output, format, icc_profile)?;
         }
+        timer.mark(Phase::Encode);
+        OutputHandler::write_output(&output_path, &output)?;
+        timer.mark(Phase::Write);
 
         Ok(())
     }
 
+    /// Capture one batch entry, returning the document's HTTP status and
+    /// the time spent in each phase
     async fn process_single_screenshot(
         &self,
         config: ScreenshotConfig,
         output_dir: Option<PathBuf>,
-    ) -> Result<()> {
+    ) -> Result<(Option<u16>, PhaseTimings)> {
+        let hooks = config.hooks.clone().unwrap_or_default();
+        let config = hooks.before_navigation(config).await?;
         validate_navigation_url(&config.url, "batch screenshot API")?;
-        let tab = self
-            .browser
-            .new_tab()
-            .map_err(|e| WebshotError::Tab(e.to_string()))?;
 
-        // Determine output path
-        let output_path = if let Some(dir) = output_dir {
-            dir.join(&config.output)
-        } else {
-            config.output.clone()
+        let timer = PhaseTimer::start();
+        let tab = match &config.proxy {
+            Some(proxy) => self.proxy_tab(proxy)?,
+            None => self
+                .chrome()
+                .new_tab()
+                .map_err(|e| WebshotError::Tab(e.to_string()))?,
         };
 
+        let output_path = config.resolved_output(output_dir.as_deref());
+
         OutputHandler::ensure_output_dir(&output_path)?;
 
         let options = ScreenshotOptions {
@@ -471,10 +2313,70 @@ impl Browser {
             javascript: config.javascript.clone(),
             wait_for: config.wait_for.clone(),
             timeout: config.timeout,
-            retina: config.retina,
+            retina: config.retina.unwrap_or(false),
             quality: config.quality,
-            wait: config.wait,
+            wait: config.wait.unwrap_or(0),
             user_agent: config.user_agent.clone(),
+            capture: if config.paginate_preview.is_some() {
+                CaptureMode::FullPage
+            } else {
+                config.capture_mode()?
+            },
+            freeze_sticky: config.freeze_sticky,
+            paginate_preview: config.paginate_preview,
+            auto_height: config.auto_height,
+            max_height: config.max_height,
+            touch: config.touch,
+            clip: config.clip,
+            mask_selectors: config.mask_selectors.clone(),
+            mask_color: config
+                .mask_color
+                .clone()
+                .unwrap_or_else(|| DEFAULT_MASK_COLOR.to_string()),
+            trigger_lazy: config.trigger_lazy,
+            lazy_scroll_step: config.lazy_scroll_step,
+            lazy_settle_ms: config.lazy_settle_ms,
+            network_idle: config.network_idle()?,
+            device: config.device.as_deref().map(str::parse).transpose()?,
+            stabilize: config.stabilize,
+            color_scheme: config.color_scheme,
+            hide_scrollbars: config.hide_scrollbars,
+            disable_javascript: config.disable_javascript,
+            anchor_scroll: config.anchor_scroll,
+            init_scripts: read_init_scripts(&config.init_scripts)?,
+            png_colors: config.png_colors,
+            grayscale: config.grayscale,
+            session: config
+                .load_session
+                .as_deref()
+                .map(SessionState::load)
+                .transpose()?,
+            save_session: None,
+            tiff_compression: config.tiff_compression.unwrap_or_default(),
+            image_filters: config.image_filters.clone(),
+            auth: config.auth.clone(),
+            headers: config.headers.clone(),
+            steps: config.steps.clone(),
+            block_resources: config.block_resources.clone(),
+            mocks: Mocks::load(&config.mocks)?,
+            adblock: config
+                .adblock
+                .then(|| self.filter_list(config.adblock_list.as_deref()))
+                .transpose()?,
+            dismiss_overlays: config
+                .auto_dismiss_overlays
+                .then(|| {
+                    OverlayDismissal::with_rules_file(
+                        config.overlay_rules.as_deref(),
+                        config.overlay_choice.unwrap_or_default(),
+                    )
+                })
+                .transpose()?,
+            fail_on_http_error: config.fail_on_http_error,
+            fail_on_console_error: config.fail_on_console_error,
+            fail_on_js_exception: config.fail_on_js_exception,
+            console_log: config.resolved_console_log(output_dir.as_deref()),
+            har: config.resolved_har(output_dir.as_deref()),
         };
 
         self.setup_tab(&tab, &options).await?;
@@ -503,49 +2405,81 @@ impl Browser {
                 .map_err(WebshotError::Browser)?;
         }
 
-        // Set custom headers
-        if !config.headers.is_empty() {
-            let headers: std::collections::HashMap<&str, &str> = config
-                .headers
-                .iter()
-                .map(|(k, v)| (k.as_str(), v.as_str()))
-                .collect();
-            tab.set_extra_http_headers(headers)
-                .map_err(WebshotError::Browser)?;
-        }
-
-        // Handle authentication
-        if let Some(auth) = &config.auth {
-            tab.authenticate(Some(auth.username.clone()), Some(auth.password.clone()))
-                .map_err(WebshotError::Browser)?;
-        }
-
         // Navigate and process
+        let document_status = watch_document_status(&tab)?;
+        let page_errors = PageErrorWatch::attach(
+            &tab,
+            config.fail_on_console_error,
+            config.fail_on_js_exception,
+        )?;
+        let _console_log = options
+            .console_log
+            .as_ref()
+            .map(|path| ConsoleLog::attach(&tab, path))
+            .transpose()?;
+        let _har = options
+            .har
+            .as_ref()
+            .map(|path| HarRecorder::attach(&tab, path))
+            .transpose()?;
+        let network_idle = NetworkIdleWatch::attach(&tab, options.network_idle.as_ref())?;
+        timer.mark(Phase::NewTab);
+        #[cfg(feature = "chaos")]
+        let fault = self.inject_fault(&tab).await?;
         tab.navigate_to(&config.url)
             .map_err(|e| WebshotError::navigation(e.to_string()))?;
         tab.wait_until_navigated()
             .map_err(|e| WebshotError::navigation(e.to_string()))?;
+        #[cfg(feature = "chaos")]
+        if fault == Some(Fault::CrashTab) {
+            let _ = tab.call_method(Page::Crash(None));
+            return Err(WebshotError::Tab(
+                "Tab crashed (injected by --chaos)".into(),
+            ));
+        }
+        let status = check_http_status(
+            &config.url,
+            *document_status.lock().unwrap(),
+            config.fail_on_http_error,
+        )?;
+        timer.mark(Phase::Navigate);
+
+        self.run_steps(&tab, &config.steps, config.timeout).await?;
+
+        if let Some(dismissal) = &options.dismiss_overlays {
+            self.dismiss_overlays(&tab, dismissal)?;
+        }
 
         // Execute JavaScript
         if let Some(script) = &config.javascript {
-            if self.javascript_enabled {
+            if self.scripts_enabled(&options) {
                 tab.evaluate(script, false)
                     .map_err(|e| WebshotError::javascript(e.to_string()))?;
             }
         }
+        timer.mark(Phase::Js);
 
         // Wait for element
         if let Some(selector) = &config.wait_for {
             self.wait_for_element(&tab, selector, config.timeout)
                 .await?;
         }
+        if let Some(watch) = &network_idle {
+            self.wait_for_network_idle(watch, config.timeout).await;
+        }
+        timer.mark(Phase::WaitFor);
 
         // Wait before screenshot
-        if config.wait > 0 {
-            sleep(Duration::from_secs(config.wait)).await;
+        if let Some(wait) = config.wait.filter(|&wait| wait > 0) {
+            sleep(Duration::from_secs(wait)).await;
+        }
+
+        if let Some(page_errors) = &page_errors {
+            page_errors.check(&config.url)?;
         }
 
         // Take screenshot
+        let output_path = resolve_page_tokens(&tab, &output_path)?;
         let format = options.output_format(&output_path)?;
         match format {
             ImageFormat::Pdf => {
@@ -570,17 +2504,903 @@ impl Browser {
                     generate_tagged_pdf: Some(false),
                 };
 
+                timer.mark(Phase::Settle);
                 let pdf_data = tab
                     .print_to_pdf(Some(pdf_options))
                     .map_err(|e| WebshotError::pdf(e.to_string()))?;
-                std::fs::write(&output_path, pdf_data)?;
+                timer.mark(Phase::Capture);
+                OutputHandler::write_output(&output_path, &pdf_data)?;
+                timer.mark(Phase::Write);
             }
             _ => {
-                self.take_image_screenshot(&tab, &output_path, &options, format)
+                self.take_image_screenshot(&tab, &output_path, &options, format, &timer)
                     .await?;
+                if !config.elements.is_empty() {
+                    let manifest = self
+                        .capture_elements(&tab, &config, &output_path, &options, format, &timer)
+                        .await?;
+                    let failed = manifest.failed();
+                    if failed > 0 {
+                        warn!(
+                            "{} of {} elements of {} were not captured",
+                            failed,
+                            manifest.elements.len(),
+                            config.url
+                        );
+                    }
+                }
             }
         }
 
-        Ok(())
+        let timings = timer.timings();
+        info!("Timings for {}: {}", config.url, timings);
+
+        if !hooks.after_capture.is_empty() {
+            let metadata = CaptureMetadata {
+                url: config.url.clone(),
+                final_url: tab.get_url(),
+                status,
+                bytes: std::fs::metadata(&output_path)?.len(),
+                output: output_path,
+                format: format.extension().to_string(),
+                width: options.width,
+                height: options.height,
+                captured_at: chrono::Utc::now().to_rfc3339(),
+                timings: timings.clone(),
+            };
+            hooks.after_capture(&metadata, config.timeout).await?;
+        }
+
+        Ok((status, timings))
+    }
+}
+
+impl BrowserBackend for Browser {
+    fn engine(&self) -> &str {
+        "chromium"
+    }
+
+    async fn capture(
+        &self,
+        url: &str,
+        output_path: &Path,
+        options: &ScreenshotOptions,
+    ) -> Result<Capture> {
+        Browser::capture(self, url, output_path, options).await
+    }
+
+    async fn pdf(&self, url: &str, output_path: &Path, options: &PdfOptions) -> Result<()> {
+        Browser::pdf(self, url, output_path, options).await
+    }
+
+    async fn extract_text(&self, url: &str, options: &TextOptions) -> Result<String> {
+        Browser::extract_text(self, url, options).await
+    }
+}
+
+impl Drop for Browser {
+    fn drop(&mut self) {
+        if let Some(keep_alive) = &self.keep_alive {
+            keep_alive.abort();
+        }
+    }
+}
+
+/// The `http://` URL of the remote debugging port at `host_port`
+pub fn devtools_http_url(host_port: &str) -> Result<String> {
+    let invalid = || {
+        WebshotError::config(format!(
+            "Expected the browser's DevTools address as HOST:PORT, not {}",
+            host_port
+        ))
+    };
+    if host_port.contains("://") {
+        return Err(invalid());
+    }
+    let url = url::Url::parse(&format!("http://{}", host_port)).map_err(|_| invalid())?;
+    if url.host_str().is_none() || url.port().is_none() || url.path() != "/" {
+        return Err(invalid());
+    }
+    Ok(format!("http://{}", host_port))
+}
+
+/// Create a browser context whose traffic goes through `proxy` in the
+/// browser at `ws_url`. headless_chrome keeps browser-level commands
+/// private and page sessions may not create contexts, so this uses a
+/// DevTools connection of its own; the context outlives it.
+fn create_proxy_context(ws_url: &str, proxy: &str) -> Result<String> {
+    let (mut socket, _) =
+        tungstenite::connect(ws_url).map_err(|e| WebshotError::browser_connect(ws_url, e))?;
+    let request = serde_json::json!({
+        "id": 1,
+        "method": "Target.createBrowserContext",
+        "params": { "proxyServer": proxy },
+    });
+    socket
+        .send(tungstenite::Message::text(request.to_string()))
+        .map_err(|e| WebshotError::browser_connect(ws_url, e))?;
+
+    loop {
+        let message = socket
+            .read()
+            .map_err(|e| WebshotError::browser_connect(ws_url, e))?;
+        let tungstenite::Message::Text(text) = message else {
+            continue;
+        };
+        let reply: serde_json::Value = serde_json::from_str(text.as_str())?;
+        if reply["id"] != 1 {
+            continue;
+        }
+        let _ = socket.close(None);
+        return match reply["result"]["browserContextId"].as_str() {
+            Some(context_id) => Ok(context_id.to_string()),
+            None => Err(WebshotError::Browser(anyhow::anyhow!(
+                "Cannot create a browser context for proxy {}: {}",
+                proxy,
+                reply["error"]["message"]
+                    .as_str()
+                    .unwrap_or("no context id")
+            ))),
+        };
+    }
+}
+
+/// Connect to the browser at `url` (see [`resolve_devtools_url`]),
+/// returning it with the WebSocket URL it was reached at
+async fn connect_chrome(url: &str) -> Result<(ChromeBrowser, String)> {
+    let ws_url = resolve_devtools_url(url).await?;
+    info!("Connecting to browser at {}", ws_url);
+    let browser = ChromeBrowser::connect_with_timeout(ws_url.clone(), BROWSER_IDLE_TIMEOUT)
+        .map_err(|e| WebshotError::browser_connect(&ws_url, e))?;
+    Ok((browser, ws_url))
+}
+
+/// Ping the connected browser every [`KEEP_ALIVE_INTERVAL`], keeping the
+/// connection from idling out, and reconnect through `url` whenever it does
+/// not answer. Ends once the [`Browser`] holding it is dropped.
+async fn keep_alive(browser: Weak<RwLock<ChromeBrowser>>, url: String) {
+    loop {
+        sleep(KEEP_ALIVE_INTERVAL).await;
+        let Some(current) = browser
+            .upgrade()
+            .map(|shared| shared.read().unwrap().clone())
+        else {
+            return;
+        };
+        let error = match tokio::task::spawn_blocking(move || current.get_version()).await {
+            Ok(Ok(_)) => continue,
+            Ok(Err(e)) => e.to_string(),
+            Err(e) => e.to_string(),
+        };
+        warn!(
+            "Browser at {} stopped responding ({}), reconnecting",
+            url, error
+        );
+
+        match connect_chrome(&url).await {
+            Ok((reconnected, ws_url)) => match browser.upgrade() {
+                Some(shared) => {
+                    *shared.write().unwrap() = reconnected;
+                    info!("Reconnected to browser at {}", ws_url);
+                }
+                None => return,
+            },
+            // Try again at the next ping
+            Err(e) => warn!("{}", e),
+        }
+    }
+}
+
+/// The DevTools WebSocket URL for `url`: `ws://` and `wss://` URLs as given,
+/// `http://` and `https://` ones looked up from the port's `/json/version`
+async fn resolve_devtools_url(url: &str) -> Result<String> {
+    let parsed = url::Url::parse(url).map_err(|e| WebshotError::browser_connect(url, e))?;
+    match parsed.scheme() {
+        "ws" | "wss" => Ok(url.to_string()),
+        "http" | "https" => {
+            let version_url = parsed
+                .join("/json/version")
+                .map_err(|e| WebshotError::browser_connect(url, e))?;
+            let version: serde_json::Value = reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()?
+                .get(version_url)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                .map_err(|e| WebshotError::browser_connect(url, e))?
+                .json()
+                .await
+                .map_err(|e| WebshotError::browser_connect(url, e))?;
+            let ws_url = version["webSocketDebuggerUrl"].as_str().ok_or_else(|| {
+                WebshotError::browser_connect(url, "/json/version has no webSocketDebuggerUrl")
+            })?;
+            Ok(reachable_ws_url(&parsed, ws_url))
+        }
+        scheme => Err(WebshotError::browser_connect(
+            url,
+            format!(
+                "unsupported scheme {}, expected ws, wss, http or https",
+                scheme
+            ),
+        )),
+    }
+}
+
+/// Chrome reports its WebSocket URL with the host and port it listens on,
+/// often `127.0.0.1` inside a container; point it at the host and port the
+/// `/json/version` request reached instead
+fn reachable_ws_url(http_url: &url::Url, ws_url: &str) -> String {
+    let Ok(mut reachable) = url::Url::parse(ws_url) else {
+        return ws_url.to_string();
+    };
+    let secure = http_url.scheme() == "https";
+    if reachable.set_host(http_url.host_str()).is_err()
+        || reachable
+            .set_port(http_url.port_or_known_default())
+            .is_err()
+        || reachable
+            .set_scheme(if secure { "wss" } else { "ws" })
+            .is_err()
+    {
+        return ws_url.to_string();
+    }
+    reachable.to_string()
+}
+
+/// Stop page scripts from running in `tab`. Chrome has no command line
+/// switch for this; scripts webshot evaluates over the DevTools protocol,
+/// such as wait conditions and masks, still run.
+fn disable_scripts(tab: &Tab) -> Result<()> {
+    debug!("Disabling JavaScript");
+    tab.call_method(Emulation::SetScriptExecutionDisabled { value: true })
+        .map_err(WebshotError::Browser)?;
+    Ok(())
+}
+
+/// Fresh profile for a Chrome launched with `args`, unless they choose a
+/// user data directory themselves
+fn new_profile(args: &[String]) -> Result<Option<TempProfile>> {
+    if sets_user_data_dir(args) {
+        return Ok(None);
+    }
+    TempProfile::create().map(Some)
+}
+
+/// Launch a Chrome process with the given command line flags, which include
+/// the headless switch when it should run headless, using `user_data_dir`
+/// as its profile
+fn launch_chrome(
+    chrome_path: Option<&PathBuf>,
+    args: &[String],
+    user_data_dir: Option<&Path>,
+) -> Result<ChromeBrowser> {
+    let args: Vec<&std::ffi::OsStr> = args.iter().map(std::ffi::OsStr::new).collect();
+    let launch_options = launch_options(chrome_path, &args, user_data_dir)?;
+    ChromeBrowser::new(launch_options).map_err(|e| WebshotError::browser_launch(e.to_string()))
+}
+
+/// How [`launch_chrome`] starts Chrome
+fn launch_options<'a>(
+    chrome_path: Option<&PathBuf>,
+    args: &[&'a std::ffi::OsStr],
+    user_data_dir: Option<&Path>,
+) -> Result<LaunchOptions<'a>> {
+    LaunchOptions::default_builder()
+        // The headless switch is in `args`: the builder's own would come
+        // last and override it
+        .headless(false)
+        // Without the sandbox the builder adds `--no-sandbox` and
+        // `--disable-setuid-sandbox` itself; they are default flags in
+        // `args`, so `--remove-flag` can drop them
+        .sandbox(true)
+        // Certificate errors fail navigation unless `--insecure` adds the
+        // switch back
+        .ignore_certificate_errors(false)
+        .args(args.to_vec())
+        .path(chrome_path.cloned())
+        .user_data_dir(user_data_dir.map(Path::to_path_buf))
+        .build()
+        .map_err(|e| WebshotError::browser_launch(e.to_string()))
+}
+
+/// Capabilities of a running browser from the version it reports
+fn reported_capabilities(browser: &ChromeBrowser) -> Capabilities {
+    let capabilities = match browser.get_version() {
+        Ok(version) => Capabilities::from_product(&version.product),
+        Err(e) => {
+            warn!(
+                "Cannot read the browser version, assuming a current Chrome: {}",
+                e
+            );
+            return Capabilities::unknown();
+        }
+    };
+    info!("{}", capabilities);
+    if capabilities.is_untested() {
+        warn!(
+            "{} is older than any Chrome webshot is tested with; captures may differ or fail",
+            capabilities.product
+        );
+    }
+    capabilities
+}
+
+/// Resolve `{title}` and `{final_url_path}` in an output path from the
+/// loaded page
+fn resolve_page_tokens(tab: &Tab, output_path: &Path) -> Result<PathBuf> {
+    if !OutputHandler::has_page_tokens(output_path) {
+        return Ok(output_path.to_path_buf());
+    }
+
+    let title = tab.get_title().map_err(WebshotError::Browser)?;
+    let resolved = OutputHandler::render_page_tokens(output_path, &title, &tab.get_url());
+    debug!("Resolved output path: {}", resolved.display());
+    Ok(resolved)
+}
+
+/// Answer HTTP basic auth challenges with the given credentials, fulfill
+/// requests matching a mock rule and fail requests the blocker rejects. Auth
+/// challenges only reach the tab's handler while the Fetch domain is enabled
+/// with auth handling for every request; without auth or mocks only the
+/// blocked resource types are paused.
+fn intercept_requests(
+    tab: &Tab,
+    auth: Option<&AuthConfig>,
+    blocker: &RequestBlocker,
+    mocks: &Mocks,
+) -> Result<()> {
+    if let Some(auth) = auth {
+        debug!("Enabling HTTP basic auth as {}", auth.username);
+        tab.authenticate(Some(auth.username.clone()), Some(auth.password.clone()))
+            .map_err(WebshotError::Browser)?;
+    }
+
+    if !blocker.is_empty() || !mocks.is_empty() {
+        let blocker = blocker.clone();
+        let mocks = mocks.clone();
+        // The main frame's id is the target id; its document requests are
+        // never blocked and give the page URL for third-party checks
+        let main_frame = tab.get_target_id().clone();
+        let page_url = Mutex::new(None::<String>);
+        tab.enable_request_interception(Arc::new(
+            move |_transport, _session_id, event: RequestPausedEvent| {
+                let params = &event.params;
+                if let Some(response) = mocks.respond(
+                    params.request_id.clone(),
+                    &params.request.method,
+                    &params.request.url,
+                ) {
+                    debug!("Mocking {} {}", params.request.method, params.request.url);
+                    return RequestPausedDecision::Fulfill(response);
+                }
+
+                if params.resource_Type == Network::ResourceType::Document
+                    && params.frame_id == main_frame
+                {
+                    *page_url.lock().unwrap() = Some(params.request.url.clone());
+                    return RequestPausedDecision::Continue(None);
+                }
+
+                let page_url = page_url.lock().unwrap().clone();
+                if blocker.blocks(
+                    &params.request.url,
+                    &params.resource_Type,
+                    page_url.as_deref(),
+                ) {
+                    trace!("Blocking {}", params.request.url);
+                    RequestPausedDecision::Fail(Fetch::FailRequest {
+                        request_id: event.params.request_id,
+                        error_reason: Network::ErrorReason::BlockedByClient,
+                    })
+                } else {
+                    RequestPausedDecision::Continue(None)
+                }
+            },
+        ))
+        .map_err(WebshotError::Browser)?;
+    }
+
+    if auth.is_some() {
+        tab.enable_fetch(None, Some(true))
+            .map_err(WebshotError::Browser)?;
+    } else if !mocks.is_empty() {
+        tab.enable_fetch(None, None)
+            .map_err(WebshotError::Browser)?;
+    } else if !blocker.is_empty() {
+        tab.enable_fetch(blocker.fetch_patterns().as_deref(), None)
+            .map_err(WebshotError::Browser)?;
+    }
+    Ok(())
+}
+
+/// Record the HTTP status of the tab's main document, replaced on every
+/// navigation so the latest page load wins. Redirects do not produce a
+/// response of their own, so the status is that of the final document.
+fn watch_document_status(tab: &Tab) -> Result<Arc<Mutex<Option<u16>>>> {
+    let status = Arc::new(Mutex::new(None));
+    let recorded = Arc::clone(&status);
+    let main_frame = tab.get_target_id().clone();
+    tab.register_response_handling(
+        "document-status",
+        Box::new(move |params, _body| {
+            if params.Type == Network::ResourceType::Document
+                && params.frame_id.as_ref() == Some(&main_frame)
+            {
+                *recorded.lock().unwrap() = u16::try_from(params.response.status).ok();
+            }
+        }),
+    )
+    .map_err(WebshotError::Browser)?;
+    Ok(status)
+}
+
+/// Pass a page's document status through, failing on 4xx and 5xx when
+/// `fail_on_http_error` is set and warning about them otherwise
+fn check_http_status(
+    url: &str,
+    status: Option<u16>,
+    fail_on_http_error: bool,
+) -> Result<Option<u16>> {
+    match status {
+        Some(code) if code >= 400 && fail_on_http_error => {
+            return Err(WebshotError::http_status(url, code))
+        }
+        Some(code) if code >= 400 => warn!("{} returned HTTP {}, capturing anyway", url, code),
+        Some(code) => debug!("{} returned HTTP {}", url, code),
+        None => debug!("No HTTP status received for {}", url),
+    }
+    Ok(status)
+}
+
+/// Format Chrome encodes a capture in
+#[derive(Debug, Clone, PartialEq)]
+struct CaptureEncoding {
+    format: Page::CaptureScreenshotFormatOption,
+    quality: Option<u32>,
+    /// Trade compression for encoding speed, for captures decoded again
+    optimize_for_speed: Option<bool>,
+}
+
+impl CaptureEncoding {
+    /// Let Chrome encode JPEG and lossy WebP output itself, at the quality
+    /// of `options`, unless page-break previews or image filters need the
+    /// pixels first or the browser cannot capture WebP. Everything else is
+    /// captured as PNG, as fast as the browser can encode it, and converted.
+    fn for_output(
+        format: ImageFormat,
+        options: &ScreenshotOptions,
+        capabilities: &Capabilities,
+    ) -> Self {
+        let processed = options.paginate_preview.is_some() || !options.image_filters.is_empty();
+        let (format, quality) = match (format, options.quality) {
+            (ImageFormat::Jpeg, quality) if !processed => (
+                Page::CaptureScreenshotFormatOption::Jpeg,
+                Some(quality.unwrap_or(90)),
+            ),
+            (ImageFormat::WebP, Some(quality)) if !processed && capabilities.webp_capture() => {
+                (Page::CaptureScreenshotFormatOption::Webp, Some(quality))
+            }
+            _ => (Page::CaptureScreenshotFormatOption::Png, None),
+        };
+        let optimize_for_speed =
+            format == Page::CaptureScreenshotFormatOption::Png && capabilities.optimize_for_speed();
+        Self {
+            format,
+            quality: quality.map(u32::from),
+            optimize_for_speed: optimize_for_speed.then_some(true),
+        }
+    }
+
+    /// Whether the capture is already encoded in the output format
+    fn is_direct(&self) -> bool {
+        self.format != Page::CaptureScreenshotFormatOption::Png
+    }
+}
+
+/// Script painting over the elements of `options.mask_selectors`
+fn mask_script(options: &ScreenshotOptions) -> Result<String> {
+    // Color first, so a selector mentioning COLOR is left alone
+    Ok(MASK_SCRIPT
+        .replacen("COLOR", &serde_json::to_string(&options.mask_color)?, 1)
+        .replacen(
+            "SELECTORS",
+            &serde_json::to_string(&options.mask_selectors)?,
+            1,
+        ))
+}
+
+/// Send extra HTTP headers with every request the tab makes
+fn set_extra_headers(tab: &Tab, headers: &HashMap<String, String>) -> Result<()> {
+    if headers.is_empty() {
+        return Ok(());
+    }
+
+    debug!("Setting {} extra HTTP headers", headers.len());
+    let headers = headers
+        .iter()
+        .map(|(name, value)| (name.as_str(), value.as_str()))
+        .collect();
+    tab.set_extra_http_headers(headers)
+        .map_err(WebshotError::Browser)?;
+    Ok(())
+}
+
+/// Collect the absolute `href` of every link on the page
+fn extract_links(tab: &Tab) -> Result<Vec<String>> {
+    let result = tab
+        .evaluate(
+            "JSON.stringify(Array.from(document.links, link => link.href))",
+            false,
+        )
+        .map_err(|e| WebshotError::javascript(e.to_string()))?;
+
+    match result.value.as_ref().and_then(|value| value.as_str()) {
+        Some(json) => Ok(serde_json::from_str(json)?),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// CSS media features to emulate for the given capture options
+/// Override the tab's viewport size, keeping the scale factor and mobile
+/// emulation from `options`
+fn set_device_metrics(
+    tab: &Tab,
+    options: &ScreenshotOptions,
+    width: u32,
+    height: u32,
+) -> Result<()> {
+    tab.call_method(
+        headless_chrome::protocol::cdp::Emulation::SetDeviceMetricsOverride {
+            width,
+            height,
+            device_scale_factor: options.device_scale_factor(),
+            mobile: options.is_mobile(),
+            scale: None,
+            screen_width: None,
+            screen_height: None,
+            position_x: None,
+            position_y: None,
+            dont_set_visible_size: None,
+            screen_orientation: None,
+            viewport: None,
+            display_feature: None,
+            device_posture: None,
+        },
+    )
+    .map_err(WebshotError::Browser)?;
+    Ok(())
+}
+
+/// The visible links of the tab's page, as laid out now
+fn page_links(tab: &Tab) -> Result<Vec<PageLink>> {
+    let result = tab
+        .evaluate(LINKS_SCRIPT, false)
+        .map_err(|e| WebshotError::javascript(e.to_string()))?;
+    let json = result
+        .value
+        .as_ref()
+        .and_then(|value| value.as_str())
+        .ok_or_else(|| WebshotError::javascript("Links script returned no links"))?;
+    PageLink::from_script_result(json)
+}
+
+/// Serialize an edited PDF
+fn save_pdf_document(document: &mut Document) -> Result<Vec<u8>> {
+    let mut pdf_data = Vec::new();
+    document
+        .save_to(&mut pdf_data)
+        .map_err(|e| WebshotError::pdf(e.to_string()))?;
+    Ok(pdf_data)
+}
+
+/// Resize the viewport to the height of the page's content, up to the
+/// configured maximum
+fn fit_viewport_to_content(tab: &Tab, options: &ScreenshotOptions) -> Result<()> {
+    let content_height = tab
+        .evaluate(CONTENT_HEIGHT_SCRIPT, false)
+        .map_err(|e| WebshotError::javascript(e.to_string()))?
+        .value
+        .and_then(|value| value.as_f64())
+        .ok_or_else(|| WebshotError::javascript("Could not measure the page height"))?;
+    let height = options.fitted_height(content_height);
+    if f64::from(height) < content_height.ceil() {
+        warn!(
+            "Page is {}px tall, capturing the first {}px (raise --max-height to capture more)",
+            content_height.ceil(),
+            height
+        );
+    }
+
+    let (width, _) = options.viewport_size();
+    info!("Fitting viewport to content: {}x{}", width, height);
+    set_device_metrics(tab, options, width, height)
+}
+
+/// The `#fragment` of `url` when it names an in-page anchor rather than
+/// a hash route (`#/path`, `#!/path`)
+fn anchor_fragment(url: &str) -> Option<String> {
+    let url = url::Url::parse(url).ok()?;
+    let fragment = url.fragment()?;
+    if fragment.is_empty() || fragment.starts_with(['/', '!']) {
+        return None;
+    }
+    Some(fragment.to_string())
+}
+
+/// Scroll the viewport to the anchor named by the page URL's fragment, so
+/// captures don't depend on whether the page had laid out when Chrome
+/// first scrolled
+fn scroll_to_anchor(tab: &Tab) -> Result<()> {
+    let Some(fragment) = anchor_fragment(&tab.get_url()) else {
+        return Ok(());
+    };
+    let script = ANCHOR_SCROLL_SCRIPT.replace("__FRAGMENT__", &serde_json::to_string(&fragment)?);
+    let found = tab
+        .evaluate(&script, true)
+        .map_err(|e| WebshotError::javascript(e.to_string()))?;
+    if found.value.and_then(|value| value.as_bool()) == Some(true) {
+        debug!("Scrolled to #{}", fragment);
+    } else {
+        warn!(
+            "No element matches #{}, capturing from the current scroll position",
+            fragment
+        );
+    }
+    Ok(())
+}
+
+fn emulated_media_features(options: &ScreenshotOptions) -> Vec<MediaFeature> {
+    let mut features = Vec::new();
+
+    if options.emulates_touch() {
+        for (name, value) in [
+            ("pointer", "coarse"),
+            ("any-pointer", "coarse"),
+            ("hover", "none"),
+            ("any-hover", "none"),
+        ] {
+            features.push(MediaFeature {
+                name: name.to_string(),
+                value: value.to_string(),
+            });
+        }
+    }
+
+    if options.stabilize {
+        features.push(MediaFeature {
+            name: "prefers-reduced-motion".to_string(),
+            value: "reduce".to_string(),
+        });
+    }
+
+    if let Some(scheme) = options.color_scheme {
+        features.push(MediaFeature {
+            name: "prefers-color-scheme".to_string(),
+            value: scheme.to_string(),
+        });
+    }
+
+    features
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_touch_emulates_coarse_pointer_without_hover() {
+        let features = emulated_media_features(&ScreenshotOptions::new().touch());
+
+        let lookup = |name: &str| {
+            features
+                .iter()
+                .find(|feature| feature.name == name)
+                .map(|feature| feature.value.as_str())
+        };
+        assert_eq!(lookup("pointer"), Some("coarse"));
+        assert_eq!(lookup("hover"), Some("none"));
+        assert!(emulated_media_features(&ScreenshotOptions::new()).is_empty());
+    }
+
+    #[test]
+    fn test_capture_encoding_follows_output_format() {
+        let current = Capabilities::unknown();
+        let options = ScreenshotOptions::new().quality(70);
+        let jpeg = CaptureEncoding::for_output(ImageFormat::Jpeg, &options, &current);
+        assert_eq!(jpeg.format, Page::CaptureScreenshotFormatOption::Jpeg);
+        assert_eq!(jpeg.quality, Some(70));
+        assert_eq!(jpeg.optimize_for_speed, None);
+        let webp = CaptureEncoding::for_output(ImageFormat::WebP, &options, &current);
+        assert_eq!(webp.format, Page::CaptureScreenshotFormatOption::Webp);
+
+        // Lossless WebP and PNG-based formats are converted from PNG
+        let lossless =
+            CaptureEncoding::for_output(ImageFormat::WebP, &ScreenshotOptions::new(), &current);
+        assert!(!lossless.is_direct());
+        assert_eq!(lossless.optimize_for_speed, Some(true));
+        assert!(!CaptureEncoding::for_output(ImageFormat::Tiff, &options, &current).is_direct());
+        assert_eq!(
+            CaptureEncoding::for_output(ImageFormat::Jpeg, &ScreenshotOptions::new(), &current)
+                .quality,
+            Some(90)
+        );
+    }
+
+    #[test]
+    fn test_capture_encoding_respects_capabilities() {
+        let options = ScreenshotOptions::new().quality(70);
+        let chrome_87 = Capabilities::from_product("HeadlessChrome/87.0.4280.88");
+        let webp = CaptureEncoding::for_output(ImageFormat::WebP, &options, &chrome_87);
+        assert_eq!(webp.format, Page::CaptureScreenshotFormatOption::Png);
+        assert_eq!(webp.optimize_for_speed, None);
+
+        let chrome_100 = Capabilities::from_product("HeadlessChrome/100.0.4896.60");
+        let webp = CaptureEncoding::for_output(ImageFormat::WebP, &options, &chrome_100);
+        assert_eq!(webp.format, Page::CaptureScreenshotFormatOption::Webp);
+        let png = CaptureEncoding::for_output(ImageFormat::Png, &options, &chrome_100);
+        assert_eq!(png.optimize_for_speed, None);
+    }
+
+    #[test]
+    fn test_anchor_fragments_skip_hash_routes() {
+        assert_eq!(
+            anchor_fragment("https://example.com/docs#install").as_deref(),
+            Some("install")
+        );
+        assert_eq!(
+            anchor_fragment("https://example.com/#caf%C3%A9").as_deref(),
+            Some("caf%C3%A9")
+        );
+        assert_eq!(anchor_fragment("https://example.com/#/settings"), None);
+        assert_eq!(anchor_fragment("https://example.com/#!/inbox"), None);
+        assert_eq!(anchor_fragment("https://example.com/#"), None);
+        assert_eq!(anchor_fragment("https://example.com/docs"), None);
+    }
+
+    #[test]
+    fn test_check_http_status() {
+        let url = "https://example.com/missing";
+        assert_eq!(check_http_status(url, Some(200), true).unwrap(), Some(200));
+        assert_eq!(check_http_status(url, Some(404), false).unwrap(), Some(404));
+        assert_eq!(check_http_status(url, None, true).unwrap(), None);
+        assert!(matches!(
+            check_http_status(url, Some(503), true),
+            Err(WebshotError::HttpStatus { status: 503, .. })
+        ));
+    }
+
+    #[test]
+    fn test_devtools_url_points_at_reachable_host() {
+        let http_url = url::Url::parse("http://chrome-sidecar:9222").unwrap();
+        assert_eq!(
+            reachable_ws_url(
+                &http_url,
+                "ws://127.0.0.1:9222/devtools/browser/0b6f8c1e-2f1a-4c3e-9d0e-5a7b8c9d0e1f"
+            ),
+            "ws://chrome-sidecar:9222/devtools/browser/0b6f8c1e-2f1a-4c3e-9d0e-5a7b8c9d0e1f"
+        );
+
+        let https_url = url::Url::parse("https://browsers.example.com").unwrap();
+        assert_eq!(
+            reachable_ws_url(&https_url, "ws://localhost:9222/devtools/browser/abc"),
+            "wss://browsers.example.com/devtools/browser/abc"
+        );
+    }
+
+    #[test]
+    fn test_devtools_http_url() {
+        assert_eq!(
+            devtools_http_url("browserless:3000").unwrap(),
+            "http://browserless:3000"
+        );
+        assert_eq!(
+            devtools_http_url("10.0.0.5:9222").unwrap(),
+            "http://10.0.0.5:9222"
+        );
+        for invalid in [
+            "browserless",
+            "http://browserless:3000",
+            "chrome:9222/json",
+            ":9222",
+        ] {
+            assert!(devtools_http_url(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ws_urls_are_used_as_given() {
+        let url = "ws://10.0.0.5:9222/devtools/browser/abc";
+        assert_eq!(resolve_devtools_url(url).await.unwrap(), url);
+        assert!(matches!(
+            resolve_devtools_url("ftp://10.0.0.5:9222").await,
+            Err(WebshotError::BrowserConnect { .. })
+        ));
+    }
+
+    #[test]
+    fn test_removed_sandbox_flags_stay_removed() {
+        let flags = crate::flags::default_flags(&[
+            "no-sandbox".to_string(),
+            "disable-setuid-sandbox".to_string(),
+        ])
+        .unwrap();
+        let args: Vec<&std::ffi::OsStr> = flags.iter().map(std::ffi::OsStr::new).collect();
+        let options = launch_options(None, &args, None).unwrap();
+        assert!(options.sandbox);
+        assert!(!options
+            .args
+            .iter()
+            .any(|arg| arg.to_string_lossy().contains("sandbox")));
+
+        let flags = crate::flags::default_flags(&[]).unwrap();
+        let args: Vec<&std::ffi::OsStr> = flags.iter().map(std::ffi::OsStr::new).collect();
+        let options = launch_options(None, &args, None).unwrap();
+        assert!(options.args.contains(&std::ffi::OsStr::new("--no-sandbox")));
+    }
+
+    #[test]
+    fn test_proxy_contexts_are_created_over_devtools() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let ws_url = format!(
+            "ws://{}/devtools/browser/test",
+            listener.local_addr().unwrap()
+        );
+        let devtools = std::thread::spawn(move || {
+            let mut requests = Vec::new();
+            for reply in [
+                r#"{"id": 1, "result": {"browserContextId": "CTX1"}}"#,
+                r#"{"id": 1, "error": {"code": -32000, "message": "Invalid proxy"}}"#,
+            ] {
+                let (stream, _) = listener.accept().unwrap();
+                let mut socket = tungstenite::accept(stream).unwrap();
+                let request = socket.read().unwrap().into_text().unwrap();
+                requests.push(serde_json::from_str::<serde_json::Value>(&request).unwrap());
+                // Events are skipped while waiting for the reply
+                socket
+                    .send(tungstenite::Message::text(
+                        r#"{"method": "Target.targetCreated", "params": {}}"#,
+                    ))
+                    .unwrap();
+                socket.send(tungstenite::Message::text(reply)).unwrap();
+                let _ = socket.read();
+            }
+            requests
+        });
+
+        assert_eq!(
+            create_proxy_context(&ws_url, "socks5://127.0.0.1:1080").unwrap(),
+            "CTX1"
+        );
+        let error = create_proxy_context(&ws_url, "bogus://proxy").unwrap_err();
+        assert!(error.to_string().contains("Invalid proxy"));
+
+        let requests = devtools.join().unwrap();
+        assert_eq!(requests[0]["method"], "Target.createBrowserContext");
+        assert_eq!(
+            requests[0]["params"]["proxyServer"],
+            "socks5://127.0.0.1:1080"
+        );
+    }
+
+    #[test]
+    fn test_stabilize_emulates_reduced_motion() {
+        let features = emulated_media_features(&ScreenshotOptions::new().stabilize());
+
+        assert!(features
+            .iter()
+            .any(|feature| feature.name == "prefers-reduced-motion" && feature.value == "reduce"));
+    }
+
+    #[test]
+    fn test_mask_script_embeds_selectors_and_color() {
+        let options = ScreenshotOptions::new()
+            .mask(".ad[data-slot=\"COLOR\"]")
+            .mask("time")
+            .mask_color("rgb(0, 0, 0)");
+        let script = mask_script(&options).unwrap();
+
+        assert!(script.contains(r#"for (const selector of [".ad[data-slot=\"COLOR\"]","time"])"#));
+        assert!(script.contains(r#"background: "rgb(0, 0, 0)","#));
     }
 }
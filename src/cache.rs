@@ -0,0 +1,270 @@
+//! In-memory cache of screenshots for embedding applications.
+//!
+//! Applications that render the same previews over and over (a CMS showing
+//! page thumbnails, a link-preview service) can give a [`Browser`] a
+//! [`CaptureCache`] with [`Browser::with_capture_cache`]. Screenshots are
+//! then keyed by URL, image format and a hash of every capture option, not
+//! by where they are written, so callers writing each capture to a fresh
+//! temporary file still hit the cache; a request matching a capture younger
+//! than the cache's TTL writes the cached image to its own output path
+//! instead of navigating again. Output paths with page tokens (`{title}`,
+//! `{final_url_path}`) are part of the key, since they resolve from the page.
+//! The cache holds at most `capacity` captures and evicts the least recently
+//! used one when full.
+//!
+//! The cache hangs off [`Browser`] rather than a separate session type:
+//! `Browser` is what library users already hold and share between captures,
+//! and there is no `Session` API in webshot to attach it to.
+//!
+//! Captures with side effects beyond the image (saving a session, a console
+//! log or a HAR file) always run.
+//!
+//! [`Browser`]: crate::Browser
+//! [`Browser::with_capture_cache`]: crate::Browser::with_capture_cache
+
+use crate::output::OutputHandler;
+use crate::screenshot::ScreenshotOptions;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A screenshot kept in a [`CaptureCache`]
+#[derive(Debug, Clone)]
+pub struct CachedCapture {
+    /// The encoded image, as written to `path`
+    pub data: Arc<Vec<u8>>,
+    /// Path the capture was written to, page tokens resolved
+    pub path: PathBuf,
+    /// HTTP status of the page's main document when it was captured
+    pub status: Option<u16>,
+    /// When the page was captured
+    pub captured_at: Instant,
+}
+
+/// Hit and miss counts of a [`CaptureCache`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Captures currently cached, expired ones included until evicted
+    pub entries: usize,
+}
+
+#[derive(Debug)]
+struct Slot {
+    capture: CachedCapture,
+    /// Value of the use counter when the capture was last read or written
+    last_used: u64,
+}
+
+#[derive(Debug, Default)]
+struct Slots {
+    slots: HashMap<u64, Slot>,
+    clock: u64,
+}
+
+/// LRU cache of screenshots with a time to live
+#[derive(Debug)]
+pub struct CaptureCache {
+    capacity: usize,
+    ttl: Duration,
+    slots: Mutex<Slots>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl CaptureCache {
+    /// Cache of at most `capacity` captures, each served for `ttl` after it
+    /// was taken
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            ttl,
+            slots: Mutex::new(Slots::default()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Key of a screenshot of `url` to `output_path` with `options`, or
+    /// `None` when the capture has side effects and must not be cached. Only
+    /// the image format of `output_path` counts, unless it has page tokens.
+    pub fn key(url: &str, output_path: &Path, options: &ScreenshotOptions) -> Option<u64> {
+        if options.save_session.is_some() || options.console_log.is_some() || options.har.is_some()
+        {
+            return None;
+        }
+        // Header order in a `HashMap` varies between maps, so hash them sorted
+        let mut headers: Vec<_> = options.headers.iter().collect();
+        headers.sort();
+        let mut options = options.clone();
+        options.headers.clear();
+
+        let mut hasher = DefaultHasher::new();
+        url.hash(&mut hasher);
+        if OutputHandler::has_page_tokens(output_path) {
+            output_path.hash(&mut hasher);
+        } else {
+            format!("{:?}", options.output_format(output_path).ok()?).hash(&mut hasher);
+        }
+        headers.hash(&mut hasher);
+        format!("{:?}", options).hash(&mut hasher);
+        Some(hasher.finish())
+    }
+
+    /// The capture stored under `key`, unless it is older than the TTL
+    pub fn get(&self, key: u64) -> Option<CachedCapture> {
+        let mut slots = self.slots.lock().unwrap();
+        slots.clock += 1;
+        let clock = slots.clock;
+        let capture = match slots.slots.get_mut(&key) {
+            Some(slot) if slot.capture.captured_at.elapsed() < self.ttl => {
+                slot.last_used = clock;
+                Some(slot.capture.clone())
+            }
+            Some(_) => {
+                slots.slots.remove(&key);
+                None
+            }
+            None => None,
+        };
+        let counter = if capture.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        capture
+    }
+
+    /// Store `capture` under `key`, evicting the least recently used capture
+    /// when the cache is full
+    pub fn insert(&self, key: u64, capture: CachedCapture) {
+        let mut slots = self.slots.lock().unwrap();
+        slots.clock += 1;
+        let last_used = slots.clock;
+        if !slots.slots.contains_key(&key) && slots.slots.len() >= self.capacity {
+            let ttl = self.ttl;
+            // Expired captures go first, then the least recently used one
+            let oldest = slots
+                .slots
+                .iter()
+                .min_by_key(|(_, slot)| (slot.capture.captured_at.elapsed() < ttl, slot.last_used))
+                .map(|(key, _)| *key);
+            if let Some(oldest) = oldest {
+                slots.slots.remove(&oldest);
+            }
+        }
+        slots.slots.insert(key, Slot { capture, last_used });
+    }
+
+    /// Drop every cached capture
+    pub fn clear(&self) {
+        self.slots.lock().unwrap().slots.clear();
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self.slots.lock().unwrap().slots.len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn capture(name: &str, captured_at: Instant) -> CachedCapture {
+        CachedCapture {
+            data: Arc::new(name.as_bytes().to_vec()),
+            path: PathBuf::from(format!("{}.png", name)),
+            status: Some(200),
+            captured_at,
+        }
+    }
+
+    #[test]
+    fn test_cache_evicts_least_recently_used_and_expired() {
+        let cache = CaptureCache::new(2, Duration::from_secs(60));
+        let now = Instant::now();
+        cache.insert(1, capture("home", now));
+        cache.insert(2, capture("about", now));
+        assert_eq!(cache.get(1).unwrap().path, PathBuf::from("home.png"));
+
+        // 2 was used least recently
+        cache.insert(3, capture("team", now));
+        assert!(cache.get(2).is_none());
+        assert!(cache.get(1).is_some() && cache.get(3).is_some());
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 3,
+                misses: 1,
+                entries: 2
+            }
+        );
+
+        let cache = CaptureCache::new(2, Duration::from_millis(500));
+        let stale = Instant::now();
+        std::thread::sleep(Duration::from_millis(600));
+        cache.insert(1, capture("home", stale));
+        cache.insert(2, capture("about", Instant::now()));
+        cache.get(1);
+        assert_eq!(cache.stats().entries, 1);
+        cache.insert(1, capture("home", stale));
+        cache.get(2);
+        cache.insert(3, capture("team", Instant::now()));
+        // The expired capture goes before the least recently used one
+        assert!(cache.get(2).is_some() && cache.get(3).is_some());
+    }
+
+    #[test]
+    fn test_cache_key_covers_options() {
+        let url = "https://example.com";
+        let path = Path::new("home.png");
+        let options = ScreenshotOptions::default();
+        let key = CaptureCache::key(url, path, &options).unwrap();
+        assert_eq!(CaptureCache::key(url, path, &options), Some(key));
+        assert_ne!(
+            CaptureCache::key(url, Path::new("home.jpg"), &options),
+            Some(key)
+        );
+        // Only the format of the output path counts
+        assert_eq!(
+            CaptureCache::key(url, Path::new("/tmp/webshot-1a2b3c.png"), &options),
+            Some(key)
+        );
+        assert_ne!(
+            CaptureCache::key(url, Path::new("{title}.png"), &options),
+            Some(key)
+        );
+        let mut narrow = options.clone();
+        narrow.width = 375;
+        assert_ne!(CaptureCache::key(url, path, &narrow), Some(key));
+
+        let mut headers = options.clone();
+        let mut reordered = options.clone();
+        for (name, value) in [("A", "1"), ("B", "2"), ("C", "3"), ("D", "4")] {
+            headers.headers.insert(name.to_string(), value.to_string());
+        }
+        for (name, value) in [("D", "4"), ("C", "3"), ("B", "2"), ("A", "1")] {
+            reordered
+                .headers
+                .insert(name.to_string(), value.to_string());
+        }
+        assert_eq!(
+            CaptureCache::key(url, path, &headers),
+            CaptureCache::key(url, path, &reordered)
+        );
+
+        let mut har = options;
+        har.har = Some(PathBuf::from("page.har"));
+        assert_eq!(CaptureCache::key(url, path, &har), None);
+    }
+}
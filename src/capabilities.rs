@@ -0,0 +1,181 @@
+//! Chrome version detection and the DevTools features each version has.
+//!
+//! Chrome changes its command line and DevTools protocol between major
+//! versions: old headless mode was removed in Chrome 132, and
+//! `Page.captureScreenshot` options such as `captureBeyondViewport` or the
+//! WebP format only exist in newer versions. [`Capabilities`] records the
+//! version a browser reported so webshot can pick flags and protocol
+//! parameters that version understands, degrading (with a warning) rather
+//! than failing with a protocol error. A browser whose version cannot be
+//! read is assumed to be current.
+
+use std::fmt;
+use std::path::Path;
+use std::process::Command;
+use tracing::debug;
+
+/// First Chrome version whose `--headless=new` renders like headful Chrome
+pub const NEW_HEADLESS_SINCE: u32 = 112;
+
+/// First Chrome version with `captureBeyondViewport` in `Page.captureScreenshot`
+pub const CAPTURE_BEYOND_VIEWPORT_SINCE: u32 = 87;
+
+/// First Chrome version capturing screenshots as WebP
+pub const WEBP_CAPTURE_SINCE: u32 = 88;
+
+/// First Chrome version with `optimizeForSpeed` in `Page.captureScreenshot`
+pub const OPTIMIZE_FOR_SPEED_SINCE: u32 = 117;
+
+/// Oldest Chrome version webshot is tested against
+pub const OLDEST_TESTED_VERSION: u32 = 100;
+
+/// What a browser supports, derived from the version it reports
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Product string, e.g. `HeadlessChrome/120.0.6099.109`; empty when
+    /// the browser did not report one
+    pub product: String,
+    /// Major version, `None` when it could not be read
+    pub major: Option<u32>,
+}
+
+impl Capabilities {
+    /// Capabilities of the browser reporting `product` (from
+    /// `Browser.getVersion`) or a `--version` line
+    pub fn from_product(product: &str) -> Self {
+        Self {
+            product: product.trim().to_string(),
+            major: parse_major_version(product),
+        }
+    }
+
+    /// Capabilities of a browser of unknown version, assumed to be current
+    pub fn unknown() -> Self {
+        Self {
+            product: String::new(),
+            major: None,
+        }
+    }
+
+    fn at_least(&self, version: u32) -> bool {
+        self.major.is_none_or(|major| major >= version)
+    }
+
+    /// Whether `--headless=new` selects the new headless mode
+    pub fn new_headless(&self) -> bool {
+        self.at_least(NEW_HEADLESS_SINCE)
+    }
+
+    /// Whether screenshots can include content outside the viewport
+    pub fn capture_beyond_viewport(&self) -> bool {
+        self.at_least(CAPTURE_BEYOND_VIEWPORT_SINCE)
+    }
+
+    /// Whether Chrome can encode screenshots as WebP itself
+    pub fn webp_capture(&self) -> bool {
+        self.at_least(WEBP_CAPTURE_SINCE)
+    }
+
+    /// Whether screenshots can trade compression for encoding speed
+    pub fn optimize_for_speed(&self) -> bool {
+        self.at_least(OPTIMIZE_FOR_SPEED_SINCE)
+    }
+
+    /// Whether this version is older than any webshot is tested against
+    pub fn is_untested(&self) -> bool {
+        !self.at_least(OLDEST_TESTED_VERSION)
+    }
+
+    /// Switch starting Chrome headless: the new headless mode where the
+    /// version has it, so rendering matches Chrome 132 and later, which
+    /// have no other
+    pub fn headless_switch(&self) -> &'static str {
+        if self.new_headless() {
+            "--headless=new"
+        } else {
+            "--headless"
+        }
+    }
+}
+
+impl fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.major {
+            Some(major) => write!(f, "Chrome {}", major)?,
+            None => f.write_str("Chrome (unknown version)")?,
+        }
+        let yes_no = |supported: bool| if supported { "yes" } else { "no" };
+        write!(
+            f,
+            " - new headless: {}, capture beyond viewport: {}, WebP capture: {}, optimize for speed: {}",
+            yes_no(self.new_headless()),
+            yes_no(self.capture_beyond_viewport()),
+            yes_no(self.webp_capture()),
+            yes_no(self.optimize_for_speed())
+        )
+    }
+}
+
+/// Major version in a product string (`HeadlessChrome/120.0.6099.109`) or a
+/// `--version` line (`Google Chrome 120.0.6099.109`, `Chromium 119.0.6045.105
+/// built on Debian`)
+pub fn parse_major_version(version: &str) -> Option<u32> {
+    version
+        .split(|c: char| c == '/' || c.is_whitespace())
+        .find(|word| word.contains('.') && word.chars().all(|c| c.is_ascii_digit() || c == '.'))
+        .and_then(|word| word.split('.').next())
+        .and_then(|major| major.parse().ok())
+}
+
+/// Ask the Chrome executable at `path` for its version, before launching it
+pub fn executable_capabilities(path: &Path) -> Option<Capabilities> {
+    let output = Command::new(path).arg("--version").output().ok()?;
+    let version = String::from_utf8_lossy(&output.stdout);
+    debug!("{} --version: {}", path.display(), version.trim());
+    let capabilities = Capabilities::from_product(&version);
+    capabilities.major.is_some().then_some(capabilities)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_major_version() {
+        assert_eq!(
+            parse_major_version("HeadlessChrome/120.0.6099.109"),
+            Some(120)
+        );
+        assert_eq!(
+            parse_major_version("Google Chrome 131.0.6778.85 \n"),
+            Some(131)
+        );
+        assert_eq!(
+            parse_major_version("Chromium 119.0.6045.105 built on Debian 12.2"),
+            Some(119)
+        );
+        assert_eq!(parse_major_version("Chrome/"), None);
+        assert_eq!(parse_major_version(""), None);
+    }
+
+    #[test]
+    fn test_features_follow_version() {
+        let old = Capabilities::from_product("HeadlessChrome/86.0.4240.75");
+        assert!(!old.capture_beyond_viewport());
+        assert!(!old.webp_capture());
+        assert!(old.is_untested());
+        assert_eq!(old.headless_switch(), "--headless");
+
+        let current = Capabilities::from_product("Chrome/131.0.6778.85");
+        assert!(current.capture_beyond_viewport() && current.optimize_for_speed());
+        assert_eq!(current.headless_switch(), "--headless=new");
+
+        // Unknown versions are treated as current
+        let unknown = Capabilities::unknown();
+        assert!(unknown.capture_beyond_viewport() && !unknown.is_untested());
+        assert!(unknown.to_string().starts_with("Chrome (unknown version)"));
+        assert!(current
+            .to_string()
+            .starts_with("Chrome 131 - new headless: yes"));
+    }
+}
@@ -0,0 +1,206 @@
+//! Fault injection for batch runs, for the hidden `--chaos` flag.
+//!
+//! Only built with the `chaos` feature. Before each capture attempt of a
+//! batch, a [`Chaos`] rolls for a fault: a delay before navigation, requests
+//! dropped while the page loads (blocked with `Network.setBlockedURLs`, up to
+//! the page itself), or a renderer crash (`Page.crash`) after navigation.
+//! This exercises the retry and recovery paths and lets users check how
+//! their own pipelines cope with failing captures. A seed makes a run's
+//! faults reproducible.
+
+use crate::error::{Result, WebshotError};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// URL patterns one of which is blocked by a [`Fault::DropRequests`]; `*`
+/// drops the page's own request too
+const DROPPED_PATTERNS: [&str; 5] = ["*.js", "*.css", "*.png", "*.woff2", "*"];
+
+/// Longest delay a [`Fault::Delay`] injects by default
+pub const DEFAULT_MAX_DELAY: Duration = Duration::from_secs(5);
+
+/// Settings of the `--chaos` fault injection
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChaosOptions {
+    /// Chance of a fault per capture attempt (0.0 to 1.0)
+    pub probability: f64,
+    /// Longest delay injected before navigation
+    pub max_delay: Duration,
+    /// Seed of the fault sequence, random when `None`
+    pub seed: Option<u64>,
+}
+
+impl FromStr for ChaosOptions {
+    type Err = WebshotError;
+
+    /// Parse a fault probability, such as `0.2`
+    fn from_str(s: &str) -> Result<Self> {
+        let probability: f64 = s
+            .trim()
+            .parse()
+            .map_err(|_| WebshotError::config(format!("Invalid chaos probability: {}", s)))?;
+        if !(0.0..=1.0).contains(&probability) {
+            return Err(WebshotError::config(format!(
+                "Chaos probability must be between 0.0 and 1.0, got {}",
+                s
+            )));
+        }
+        Ok(Self {
+            probability,
+            max_delay: DEFAULT_MAX_DELAY,
+            seed: None,
+        })
+    }
+}
+
+/// A fault injected into one capture attempt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fault {
+    /// Wait this long before navigating
+    Delay(Duration),
+    /// Block requests matching this URL pattern
+    DropRequests(&'static str),
+    /// Crash the tab's renderer after navigation
+    CrashTab,
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Fault::Delay(delay) => write!(f, "delay of {}ms", delay.as_millis()),
+            Fault::DropRequests(pattern) => write!(f, "dropped requests for {}", pattern),
+            Fault::CrashTab => write!(f, "tab crash"),
+        }
+    }
+}
+
+/// Number of faults of each kind injected so far
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChaosStats {
+    pub delays: u64,
+    pub dropped: u64,
+    pub crashes: u64,
+}
+
+/// Source of injected faults, shared by the workers of a batch
+#[derive(Debug)]
+pub struct Chaos {
+    options: ChaosOptions,
+    /// State of the xorshift generator
+    state: Mutex<u64>,
+    delays: AtomicU64,
+    dropped: AtomicU64,
+    crashes: AtomicU64,
+}
+
+impl Chaos {
+    pub fn new(options: ChaosOptions) -> Self {
+        let seed = options.seed.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_nanos() as u64)
+        });
+        Self {
+            options,
+            // xorshift never leaves zero
+            state: Mutex::new(seed.max(1)),
+            delays: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+            crashes: AtomicU64::new(0),
+        }
+    }
+
+    /// The fault to inject into the next capture attempt, if any
+    pub fn next_fault(&self) -> Option<Fault> {
+        if self.next_unit() >= self.options.probability {
+            return None;
+        }
+        let fault = match self.next_u64() % 3 {
+            0 => {
+                let max = self.options.max_delay.as_millis().max(1) as u64;
+                Fault::Delay(Duration::from_millis(self.next_u64() % max + 1))
+            }
+            1 => {
+                let pattern = DROPPED_PATTERNS[(self.next_u64() % 5) as usize];
+                Fault::DropRequests(pattern)
+            }
+            _ => Fault::CrashTab,
+        };
+        let counter = match fault {
+            Fault::Delay(_) => &self.delays,
+            Fault::DropRequests(_) => &self.dropped,
+            Fault::CrashTab => &self.crashes,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        Some(fault)
+    }
+
+    pub fn stats(&self) -> ChaosStats {
+        ChaosStats {
+            delays: self.delays.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            crashes: self.crashes.load(Ordering::Relaxed),
+        }
+    }
+
+    fn next_u64(&self) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        *state
+    }
+
+    /// Uniform value in `[0, 1)`
+    fn next_unit(&self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chaos(probability: f64, seed: u64) -> Chaos {
+        Chaos::new(ChaosOptions {
+            probability,
+            max_delay: Duration::from_millis(200),
+            seed: Some(seed),
+        })
+    }
+
+    #[test]
+    fn test_faults_follow_probability_and_seed() {
+        let never = chaos(0.0, 7);
+        assert!((0..1000).all(|_| never.next_fault().is_none()));
+
+        let always = chaos(1.0, 7);
+        let faults: Vec<_> = (0..300).map(|_| always.next_fault().unwrap()).collect();
+        let stats = always.stats();
+        assert_eq!(stats.delays + stats.dropped + stats.crashes, 300);
+        assert!(stats.delays > 0 && stats.dropped > 0 && stats.crashes > 0);
+        assert!(faults.iter().all(|fault| match fault {
+            Fault::Delay(delay) => *delay <= Duration::from_millis(200),
+            _ => true,
+        }));
+
+        let replay = chaos(1.0, 7);
+        assert!(faults
+            .iter()
+            .all(|fault| replay.next_fault().as_ref() == Some(fault)));
+
+        let some = chaos(0.25, 42);
+        let injected = (0..4000).filter(|_| some.next_fault().is_some()).count();
+        assert!((800..1200).contains(&injected), "{} faults", injected);
+    }
+
+    #[test]
+    fn test_parse_probability() {
+        assert_eq!("0.2".parse::<ChaosOptions>().unwrap().probability, 0.2);
+        assert!("1.5".parse::<ChaosOptions>().is_err());
+        assert!("often".parse::<ChaosOptions>().is_err());
+    }
+}
@@ -0,0 +1,305 @@
+use crate::error::{Result, WebshotError};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Chrome switch that fixes the color space pages are rendered into
+const FORCE_COLOR_PROFILE_FLAG: &str = "--force-color-profile=";
+
+/// D50 white point of the ICC profile connection space
+const D50: [f64; 3] = [0.9642, 1.0, 0.8249];
+
+/// Bradford adaptation from the D65 display white point to D50
+const D65_TO_D50: [f64; 9] = [
+    1.0478112, 0.0228866, -0.0501270, 0.0295424, 0.9904844, -0.0170491, -0.0092345, 0.0150436,
+    0.7521316,
+];
+
+/// sRGB transfer function as ICC parametric curve type 3 (g, a, b, c, d)
+const SRGB_TRC: [f64; 5] = [2.4, 1.0 / 1.055, 0.055 / 1.055, 1.0 / 12.92, 0.04045];
+
+/// Color space captures are rendered in and tagged with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColorProfile {
+    /// sRGB, the web default
+    #[default]
+    Srgb,
+    /// Display P3, the wide gamut of recent Apple and high-end displays
+    DisplayP3,
+}
+
+impl ColorProfile {
+    /// Profile name as accepted by `--color-profile`
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColorProfile::Srgb => "srgb",
+            ColorProfile::DisplayP3 => "display-p3",
+        }
+    }
+
+    /// Value Chrome's `--force-color-profile` switch uses for this profile
+    fn chrome_value(&self) -> &'static str {
+        match self {
+            ColorProfile::Srgb => "srgb",
+            ColorProfile::DisplayP3 => "display-p3-d65",
+        }
+    }
+
+    /// Chrome launch flag that renders pages in this color space
+    pub fn chrome_flag(&self) -> String {
+        format!("{}{}", FORCE_COLOR_PROFILE_FLAG, self.chrome_value())
+    }
+
+    /// Profile forced by the last `--force-color-profile` flag, or `None` when
+    /// no flag is set or it names a profile that cannot be tagged
+    pub fn from_chrome_flags(flags: &[String]) -> Option<Self> {
+        let value = flags
+            .iter()
+            .rev()
+            .find_map(|flag| flag.strip_prefix(FORCE_COLOR_PROFILE_FLAG))?;
+
+        [ColorProfile::Srgb, ColorProfile::DisplayP3]
+            .into_iter()
+            .find(|profile| profile.chrome_value() == value)
+    }
+
+    /// Primaries as D50-adapted red, green and blue colorant XYZ values
+    fn colorants(&self) -> [[f64; 3]; 3] {
+        match self {
+            ColorProfile::Srgb => [
+                [0.4360747, 0.2225045, 0.0139322],
+                [0.3850649, 0.7168786, 0.0971045],
+                [0.1430804, 0.0606169, 0.7141733],
+            ],
+            ColorProfile::DisplayP3 => [
+                [0.5151215, 0.2411959, -0.0010533],
+                [0.2919769, 0.6922445, 0.0418854],
+                [0.1571045, 0.0665910, 0.7840731],
+            ],
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            ColorProfile::Srgb => "sRGB",
+            ColorProfile::DisplayP3 => "Display P3",
+        }
+    }
+
+    /// ICC v4 matrix/TRC display profile describing this color space, for
+    /// embedding in image output
+    pub fn icc_profile(&self) -> Vec<u8> {
+        let [red, green, blue] = self.colorants();
+        let description = mluc_tag(self.description());
+        let curve = parametric_curve_tag(&SRGB_TRC);
+
+        // Tags sharing the same data point at a single copy
+        let tags: [(&[u8; 4], usize); 10] = [
+            (b"desc", 0),
+            (b"cprt", 1),
+            (b"wtpt", 2),
+            (b"chad", 3),
+            (b"rXYZ", 4),
+            (b"gXYZ", 5),
+            (b"bXYZ", 6),
+            (b"rTRC", 7),
+            (b"gTRC", 7),
+            (b"bTRC", 7),
+        ];
+        let data = [
+            description,
+            mluc_tag("No copyright, use freely"),
+            xyz_tag(&D50),
+            s15_array_tag(&D65_TO_D50),
+            xyz_tag(&red),
+            xyz_tag(&green),
+            xyz_tag(&blue),
+            curve,
+        ];
+
+        let table_end = 128 + 4 + tags.len() * 12;
+        let mut offsets = Vec::with_capacity(data.len());
+        let mut offset = table_end;
+        for block in &data {
+            offsets.push(offset);
+            offset += block.len().next_multiple_of(4);
+        }
+        let size = offset;
+
+        let mut profile = Vec::with_capacity(size);
+        profile.extend_from_slice(&(size as u32).to_be_bytes());
+        profile.extend_from_slice(&[0; 4]); // preferred CMM
+        profile.extend_from_slice(&0x0430_0000u32.to_be_bytes()); // version 4.3
+        profile.extend_from_slice(b"mntrRGB XYZ ");
+        for part in [2024u16, 1, 1, 0, 0, 0] {
+            profile.extend_from_slice(&part.to_be_bytes());
+        }
+        profile.extend_from_slice(b"acsp");
+        profile.extend_from_slice(&[0; 24]); // platform, flags, device and attributes
+        profile.extend_from_slice(&0u32.to_be_bytes()); // perceptual intent
+        for value in D50 {
+            profile.extend_from_slice(&s15_fixed16(value));
+        }
+        profile.extend_from_slice(&[0; 4]); // creator
+        profile.extend_from_slice(&[0; 16]); // profile ID (not computed)
+        profile.extend_from_slice(&[0; 28]);
+
+        profile.extend_from_slice(&(tags.len() as u32).to_be_bytes());
+        for (signature, index) in tags {
+            profile.extend_from_slice(signature);
+            profile.extend_from_slice(&(offsets[index] as u32).to_be_bytes());
+            profile.extend_from_slice(&(data[index].len() as u32).to_be_bytes());
+        }
+        for block in &data {
+            profile.extend_from_slice(block);
+            profile.resize(profile.len().next_multiple_of(4), 0);
+        }
+
+        profile
+    }
+}
+
+impl fmt::Display for ColorProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for ColorProfile {
+    type Err = WebshotError;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_str() {
+            "srgb" => Ok(Self::Srgb),
+            "display-p3" | "p3" => Ok(Self::DisplayP3),
+            _ => Err(WebshotError::config(format!(
+                "Unknown color profile: {}. Supported: srgb, display-p3",
+                value
+            ))),
+        }
+    }
+}
+
+fn s15_fixed16(value: f64) -> [u8; 4] {
+    ((value * 65536.0).round() as i32).to_be_bytes()
+}
+
+fn tag_header(signature: &[u8; 4]) -> Vec<u8> {
+    let mut tag = signature.to_vec();
+    tag.extend_from_slice(&[0; 4]);
+    tag
+}
+
+fn xyz_tag(xyz: &[f64; 3]) -> Vec<u8> {
+    s15_values(b"XYZ ", xyz)
+}
+
+fn s15_array_tag(values: &[f64]) -> Vec<u8> {
+    s15_values(b"sf32", values)
+}
+
+fn s15_values(signature: &[u8; 4], values: &[f64]) -> Vec<u8> {
+    let mut tag = tag_header(signature);
+    for &value in values {
+        tag.extend_from_slice(&s15_fixed16(value));
+    }
+    tag
+}
+
+fn parametric_curve_tag(params: &[f64; 5]) -> Vec<u8> {
+    let mut tag = tag_header(b"para");
+    tag.extend_from_slice(&3u16.to_be_bytes());
+    tag.extend_from_slice(&[0; 2]);
+    for &value in params {
+        tag.extend_from_slice(&s15_fixed16(value));
+    }
+    tag
+}
+
+/// Single-record (en-US) multi-localized Unicode text
+fn mluc_tag(text: &str) -> Vec<u8> {
+    let utf16: Vec<u8> = text.encode_utf16().flat_map(u16::to_be_bytes).collect();
+
+    let mut tag = tag_header(b"mluc");
+    tag.extend_from_slice(&1u32.to_be_bytes());
+    tag.extend_from_slice(&12u32.to_be_bytes());
+    tag.extend_from_slice(b"enUS");
+    tag.extend_from_slice(&(utf16.len() as u32).to_be_bytes());
+    tag.extend_from_slice(&28u32.to_be_bytes());
+    tag.extend_from_slice(&utf16);
+    tag
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_u32(data: &[u8], offset: usize) -> u32 {
+        u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn test_color_profile_from_str() {
+        assert_eq!("sRGB".parse::<ColorProfile>().unwrap(), ColorProfile::Srgb);
+        assert_eq!(
+            "display-p3".parse::<ColorProfile>().unwrap(),
+            ColorProfile::DisplayP3
+        );
+        assert_eq!(
+            "p3".parse::<ColorProfile>().unwrap(),
+            ColorProfile::DisplayP3
+        );
+        assert!("adobe-rgb"
+            .parse::<ColorProfile>()
+            .unwrap_err()
+            .to_string()
+            .contains("Unknown color profile"));
+    }
+
+    #[test]
+    fn test_chrome_flag_round_trip() {
+        for profile in [ColorProfile::Srgb, ColorProfile::DisplayP3] {
+            assert_eq!(
+                ColorProfile::from_chrome_flags(&[profile.chrome_flag()]),
+                Some(profile)
+            );
+        }
+
+        let flags = vec![
+            ColorProfile::Srgb.chrome_flag(),
+            "--hide-scrollbars".to_string(),
+            "--force-color-profile=display-p3-d65".to_string(),
+        ];
+        assert_eq!(
+            ColorProfile::from_chrome_flags(&flags),
+            Some(ColorProfile::DisplayP3)
+        );
+        assert_eq!(
+            ColorProfile::from_chrome_flags(&["--force-color-profile=generic-rgb".to_string()]),
+            None
+        );
+        assert_eq!(ColorProfile::from_chrome_flags(&[]), None);
+    }
+
+    #[test]
+    fn test_icc_profile_structure() {
+        let profile = ColorProfile::DisplayP3.icc_profile();
+
+        assert_eq!(read_u32(&profile, 0) as usize, profile.len());
+        assert_eq!(&profile[12..24], b"mntrRGB XYZ ");
+        assert_eq!(&profile[36..40], b"acsp");
+
+        let tag_count = read_u32(&profile, 128) as usize;
+        assert_eq!(tag_count, 10);
+        for index in 0..tag_count {
+            let entry = 132 + index * 12;
+            let offset = read_u32(&profile, entry + 4) as usize;
+            let size = read_u32(&profile, entry + 8) as usize;
+            assert_eq!(offset % 4, 0);
+            assert!(offset + size <= profile.len());
+        }
+
+        assert_ne!(profile, ColorProfile::Srgb.icc_profile());
+    }
+}
@@ -1,22 +1,111 @@
 use crate::error::{Result, WebshotError};
 use crate::output::OutputHandler;
-use image::{DynamicImage, ImageBuffer, Rgb, RgbImage};
+use crate::plugins::{WasmPlugin, PLUGIN_PREFIX};
+use crate::ssim::{ms_ssim, SsimMap};
+use crate::tiled::{compare_png_bands, is_png};
+use image::{DynamicImage, GrayImage, Rgb, RgbImage, Rgba, RgbaImage};
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use tracing::{debug, info};
 
 /// Image comparison algorithms
-#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ComparisonAlgorithm {
     /// Pixel-by-pixel difference
     #[default]
     PixelDiff,
-    /// Structural Similarity Index (SSIM)
+    /// Mean Structural Similarity Index (SSIM) over Gaussian windows
     SSIM,
+    /// Multi-scale SSIM, weighting differences at five viewing distances
+    MSSSIM,
     /// Mean Squared Error
     MSE,
     /// Peak Signal-to-Noise Ratio
     PSNR,
+    /// Share of pixels within a CIEDE2000 color difference tolerance
+    DeltaE,
+    /// Similarity scored by a WebAssembly plugin module
+    Plugin(PathBuf),
+}
+
+impl FromStr for ComparisonAlgorithm {
+    type Err = WebshotError;
+
+    fn from_str(value: &str) -> Result<Self> {
+        if let Some(path) = value.strip_prefix(PLUGIN_PREFIX) {
+            if path.is_empty() {
+                return Err(WebshotError::config(
+                    "Plugin algorithm needs a module path, e.g. plugin:my_diff.wasm",
+                ));
+            }
+            return Ok(Self::Plugin(PathBuf::from(path)));
+        }
+
+        match value.to_lowercase().as_str() {
+            "pixel-diff" | "pixel" => Ok(Self::PixelDiff),
+            "ssim" => Ok(Self::SSIM),
+            "ms-ssim" | "msssim" => Ok(Self::MSSSIM),
+            "mse" => Ok(Self::MSE),
+            "psnr" => Ok(Self::PSNR),
+            "delta-e" | "deltae" | "ciede2000" => Ok(Self::DeltaE),
+            _ => Err(WebshotError::config(format!(
+                "Unknown algorithm: {}. Supported: pixel-diff, ssim, ms-ssim, mse, psnr, delta-e, plugin:<file.wasm>",
+                value
+            ))),
+        }
+    }
+}
+
+/// Largest CIEDE2000 difference of two pixels that still match in
+/// [`ComparisonAlgorithm::DeltaE`] comparisons, about the smallest color
+/// difference people notice
+pub const DEFAULT_DELTA_E_TOLERANCE: f64 = 2.3;
+
+/// Bytes of raw image data summed per parallel task
+const COMPARE_CHUNK: usize = 64 * 1024;
+
+/// Side in pixels of the grid cells changed pixels are clustered by: changes
+/// in the same or touching cells form one region
+pub const DEFAULT_REGION_GAP: u32 = 16;
+
+/// How the difference image marks changes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffStyle {
+    /// Recolor every changed pixel
+    #[default]
+    Pixels,
+    /// Draw a rectangle around every changed region
+    Boxes,
+}
+
+impl FromStr for DiffStyle {
+    type Err = WebshotError;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_str() {
+            "pixels" => Ok(Self::Pixels),
+            "boxes" => Ok(Self::Boxes),
+            _ => Err(WebshotError::config(format!(
+                "Unknown diff style: {}. Supported: pixels, boxes",
+                value
+            ))),
+        }
+    }
+}
+
+/// Bounding box of a cluster of changed pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiffRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Changed pixels within the box
+    pub pixels: u32,
 }
 
 /// Comparison options
@@ -30,10 +119,20 @@ pub struct ComparisonOptions {
     pub generate_diff_image: bool,
     /// Output path for difference image
     pub diff_output_path: Option<std::path::PathBuf>,
-    /// Ignore minor color differences (useful for anti-aliasing)
+    /// Count pixels that are anti-aliased in either image as unchanged,
+    /// detected from their neighbors as pixelmatch does
     pub ignore_antialiasing: bool,
     /// Color to highlight differences in diff image
     pub diff_color: (u8, u8, u8),
+    /// How the difference image marks changes
+    pub diff_style: DiffStyle,
+    /// Grid cell size in pixels for clustering changes into regions
+    pub region_gap: u32,
+    /// Largest CIEDE2000 difference of matching pixels for `DeltaE`
+    pub delta_e_tolerance: f64,
+    /// Compare PNG files this many rows at a time instead of decoding them
+    /// whole, see [`crate::tiled`]
+    pub band_height: Option<u32>,
 }
 
 impl Default for ComparisonOptions {
@@ -45,6 +144,10 @@ impl Default for ComparisonOptions {
             diff_output_path: None,
             ignore_antialiasing: false,
             diff_color: (255, 0, 0), // Red
+            diff_style: DiffStyle::default(),
+            region_gap: DEFAULT_REGION_GAP,
+            delta_e_tolerance: DEFAULT_DELTA_E_TOLERANCE,
+            band_height: None,
         }
     }
 }
@@ -66,6 +169,13 @@ pub struct ComparisonResult {
     pub threshold: f64,
     /// Path to generated difference image (if created)
     pub diff_image_path: Option<std::path::PathBuf>,
+    /// Version of the stored baseline compared against, for
+    /// `baseline accept --expected-version`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub baseline_version: Option<String>,
+    /// Clusters of changed pixels, largest first
+    #[serde(default)]
+    pub regions: Vec<DiffRegion>,
 }
 
 /// Image comparison engine
@@ -78,6 +188,13 @@ impl ImageComparator {
         image2_path: P2,
         options: &ComparisonOptions,
     ) -> Result<ComparisonResult> {
+        if let Some(band_height) = options.band_height {
+            if is_png(image1_path.as_ref())? && is_png(image2_path.as_ref())? {
+                return compare_png_bands(image1_path, image2_path, options, band_height);
+            }
+            info!("Band comparison reads PNG files only, comparing whole images");
+        }
+
         info!("Loading images for comparison");
         let image1 = image::open(&image1_path)
             .map_err(|e| WebshotError::config(format!("Failed to load first image: {}", e)))?;
@@ -94,8 +211,7 @@ impl ImageComparator {
         options: &ComparisonOptions,
     ) -> Result<ComparisonResult> {
         // Convert to RGB and ensure same dimensions
-        let img1 = image1.to_rgb8();
-        let img2 = image2.to_rgb8();
+        let (img1, img2) = (rgb_image(image1), rgb_image(image2));
 
         if img1.dimensions() != img2.dimensions() {
             return Err(WebshotError::config(format!(
@@ -110,30 +226,51 @@ impl ImageComparator {
 
         info!("Comparing images using {:?} algorithm", options.algorithm);
 
-        let (similarity, different_pixels) = match options.algorithm {
-            ComparisonAlgorithm::PixelDiff => Self::pixel_diff_comparison(&img1, &img2, options),
-            ComparisonAlgorithm::SSIM => (Self::ssim_comparison(&img1, &img2)?, None),
+        let ssim_map = Self::ssim_map(&img1, &img2, options);
+        let changed = Self::changed_pixels(&img1, &img2, options, ssim_map.as_ref());
+        let (similarity, different_pixels) = match &options.algorithm {
+            ComparisonAlgorithm::PixelDiff | ComparisonAlgorithm::DeltaE => {
+                Self::pixel_diff_comparison(&changed)
+            }
+            ComparisonAlgorithm::SSIM => (
+                ssim_map.as_ref().map_or(1.0, SsimMap::mean).clamp(0.0, 1.0),
+                None,
+            ),
+            ComparisonAlgorithm::MSSSIM => (
+                ms_ssim(
+                    &Self::rgb_to_grayscale(&img1),
+                    &Self::rgb_to_grayscale(&img2),
+                ),
+                None,
+            ),
             ComparisonAlgorithm::MSE => (Self::mse_comparison(&img1, &img2), None),
             ComparisonAlgorithm::PSNR => (Self::psnr_comparison(&img1, &img2), None),
+            ComparisonAlgorithm::Plugin(path) => (
+                WasmPlugin::cached(path)?.compare(&image1.to_rgba8(), &image2.to_rgba8())?,
+                None,
+            ),
         };
 
         let similar = similarity >= (1.0 - options.threshold);
+        let regions = Self::regions(&changed, width, height, options.region_gap);
 
         let mut result = ComparisonResult {
             similar,
             similarity,
             different_pixels,
             total_pixels,
-            algorithm: options.algorithm,
+            algorithm: options.algorithm.clone(),
             threshold: options.threshold,
             diff_image_path: None,
+            baseline_version: None,
+            regions,
         };
 
         // Generate difference image if requested
         if options.generate_diff_image {
             if let Some(diff_path) = &options.diff_output_path {
                 info!("Generating difference image");
-                Self::generate_diff_image(&img1, &img2, diff_path, options)?;
+                Self::generate_diff_image(&img1, &changed, &result.regions, diff_path, options)?;
                 result.diff_image_path = Some(diff_path.clone());
             }
         }
@@ -141,27 +278,66 @@ impl ImageComparator {
         Ok(result)
     }
 
-    /// Pixel-by-pixel difference comparison
-    fn pixel_diff_comparison(
+    /// Local SSIM of two images of the same size at every pixel, for the
+    /// SSIM algorithms
+    pub(crate) fn ssim_map(
         img1: &RgbImage,
         img2: &RgbImage,
         options: &ComparisonOptions,
-    ) -> (f64, Option<u32>) {
-        let mut different_pixels = 0u32;
-        let (width, height) = img1.dimensions();
+    ) -> Option<SsimMap> {
+        matches!(
+            options.algorithm,
+            ComparisonAlgorithm::SSIM | ComparisonAlgorithm::MSSSIM
+        )
+        .then(|| SsimMap::compute(&Self::rgb_to_grayscale(img1), &Self::rgb_to_grayscale(img2)))
+    }
 
-        for y in 0..height {
-            for x in 0..width {
-                let pixel1 = img1.get_pixel(x, y);
-                let pixel2 = img2.get_pixel(x, y);
+    /// Whether each pixel of two images of the same size, row by row, counts
+    /// as changed: where the local SSIM in `ssim_map` is below
+    /// `1 - threshold`, or without a map where the pixels don't match.
+    /// Rows are compared in parallel, and rows with identical bytes are
+    /// skipped without looking at their pixels.
+    pub(crate) fn changed_pixels(
+        img1: &RgbImage,
+        img2: &RgbImage,
+        options: &ComparisonOptions,
+        ssim_map: Option<&SsimMap>,
+    ) -> Vec<bool> {
+        if let Some(map) = ssim_map {
+            return map
+                .values()
+                .par_iter()
+                .map(|&ssim| ssim < 1.0 - options.threshold)
+                .collect();
+        }
 
-                if !Self::pixels_similar(pixel1, pixel2, options.ignore_antialiasing) {
-                    different_pixels += 1;
-                }
-            }
+        let width = img1.width() as usize;
+        let mut changed = vec![false; img1.as_raw().len() / 3];
+        if width == 0 {
+            return changed;
         }
+        changed
+            .par_chunks_mut(width)
+            .zip(img1.as_raw().par_chunks(width * 3))
+            .zip(img2.as_raw().par_chunks(width * 3))
+            .enumerate()
+            .for_each(|(y, ((changed, row1), row2))| {
+                if row1 == row2 {
+                    return;
+                }
+                let pixels = row1.chunks_exact(3).zip(row2.chunks_exact(3));
+                for (x, (pixel1, pixel2)) in pixels.enumerate() {
+                    changed[x] = pixel1 != pixel2
+                        && !Self::pixels_match(img1, img2, x as u32, y as u32, options);
+                }
+            });
+        changed
+    }
 
-        let total_pixels = width * height;
+    /// Pixel-by-pixel difference comparison
+    fn pixel_diff_comparison(changed: &[bool]) -> (f64, Option<u32>) {
+        let different_pixels = changed.par_iter().filter(|&&changed| changed).count() as u32;
+        let total_pixels = changed.len() as u32;
         let similarity = 1.0 - (different_pixels as f64 / total_pixels as f64);
 
         debug!(
@@ -171,75 +347,84 @@ impl ImageComparator {
         (similarity, Some(different_pixels))
     }
 
-    /// Check if two pixels are similar (with optional anti-aliasing tolerance)
-    fn pixels_similar(pixel1: &Rgb<u8>, pixel2: &Rgb<u8>, ignore_antialiasing: bool) -> bool {
-        if pixel1 == pixel2 {
-            return true;
-        }
-
-        // For basic comparison, use a small threshold for minor differences
-        let threshold = if ignore_antialiasing { 10 } else { 2 };
-        let r_diff = (pixel1[0] as i16 - pixel2[0] as i16).abs();
-        let g_diff = (pixel1[1] as i16 - pixel2[1] as i16).abs();
-        let b_diff = (pixel1[2] as i16 - pixel2[2] as i16).abs();
+    /// Check if the pixels at (`x`, `y`) of two images of the same size
+    /// count as unchanged: within the CIEDE2000 tolerance for `DeltaE`, else
+    /// within a small per-channel tolerance. With `ignore_antialiasing`,
+    /// pixels that are anti-aliased in either image count as unchanged too.
+    fn pixels_match(
+        img1: &RgbImage,
+        img2: &RgbImage,
+        x: u32,
+        y: u32,
+        options: &ComparisonOptions,
+    ) -> bool {
+        let (pixel1, pixel2) = (img1.get_pixel(x, y), img2.get_pixel(x, y));
+        let close = match options.algorithm {
+            ComparisonAlgorithm::DeltaE => {
+                pixel1 == pixel2
+                    || ciede2000(srgb_to_lab(pixel1), srgb_to_lab(pixel2))
+                        <= options.delta_e_tolerance
+            }
+            _ => Self::pixels_similar(pixel1, pixel2),
+        };
+        close
+            || (options.ignore_antialiasing
+                && (antialiased(img1, img2, x, y) || antialiased(img2, img1, x, y)))
+    }
 
-        r_diff <= threshold && g_diff <= threshold && b_diff <= threshold
+    /// Check if two pixels are equal but for encoding noise of a couple of
+    /// levels per channel
+    fn pixels_similar(pixel1: &Rgb<u8>, pixel2: &Rgb<u8>) -> bool {
+        pixel1
+            .0
+            .iter()
+            .zip(pixel2.0)
+            .all(|(&channel1, channel2)| channel1.abs_diff(channel2) <= 2)
     }
 
-    /// Structural Similarity Index (SSIM) comparison
-    fn ssim_comparison(img1: &RgbImage, img2: &RgbImage) -> Result<f64> {
-        // Convert to grayscale for SSIM calculation
-        let gray1 = Self::rgb_to_grayscale(img1);
-        let gray2 = Self::rgb_to_grayscale(img2);
+    /// Sum of the squared differences of two equally long raw buffers,
+    /// summed exactly over chunks in parallel
+    pub(crate) fn squared_error_sum(raw1: &[u8], raw2: &[u8]) -> u64 {
+        raw1.par_chunks(COMPARE_CHUNK)
+            .zip(raw2.par_chunks(COMPARE_CHUNK))
+            .map(|(chunk1, chunk2)| {
+                // Kept to integer math on plain slices so it vectorizes
+                chunk1
+                    .iter()
+                    .zip(chunk2)
+                    .map(|(&a, &b)| u64::from(a.abs_diff(b)).pow(2))
+                    .sum::<u64>()
+            })
+            .sum()
+    }
 
-        let ssim = Self::calculate_ssim(&gray1, &gray2)?;
-        Ok(ssim)
+    /// Mean squared difference of the channels of two images of the same size
+    fn mean_squared_error(img1: &RgbImage, img2: &RgbImage) -> f64 {
+        let (raw1, raw2) = (img1.as_raw(), img2.as_raw());
+        if raw1.is_empty() {
+            return 0.0;
+        }
+        Self::squared_error_sum(raw1, raw2) as f64 / raw1.len() as f64
     }
 
     /// Mean Squared Error comparison
     fn mse_comparison(img1: &RgbImage, img2: &RgbImage) -> f64 {
-        let (width, height) = img1.dimensions();
-        let mut mse = 0.0;
-
-        for y in 0..height {
-            for x in 0..width {
-                let pixel1 = img1.get_pixel(x, y);
-                let pixel2 = img2.get_pixel(x, y);
-
-                for i in 0..3 {
-                    let diff = pixel1[i] as f64 - pixel2[i] as f64;
-                    mse += diff * diff;
-                }
-            }
-        }
-
-        mse /= (width * height * 3) as f64;
+        Self::mse_similarity(Self::mean_squared_error(img1, img2))
+    }
 
+    /// Similarity of images a mean squared error apart
+    pub(crate) fn mse_similarity(mse: f64) -> f64 {
         // Convert MSE to similarity (lower MSE = higher similarity)
         1.0 / (1.0 + mse / 255.0)
     }
 
     /// Peak Signal-to-Noise Ratio comparison
     fn psnr_comparison(img1: &RgbImage, img2: &RgbImage) -> f64 {
-        let mse = {
-            let (width, height) = img1.dimensions();
-            let mut mse = 0.0;
-
-            for y in 0..height {
-                for x in 0..width {
-                    let pixel1 = img1.get_pixel(x, y);
-                    let pixel2 = img2.get_pixel(x, y);
-
-                    for i in 0..3 {
-                        let diff = pixel1[i] as f64 - pixel2[i] as f64;
-                        mse += diff * diff;
-                    }
-                }
-            }
-
-            mse / (width * height * 3) as f64
-        };
+        Self::psnr_similarity(Self::mean_squared_error(img1, img2))
+    }
 
+    /// Similarity of images a mean squared error apart, by their PSNR
+    pub(crate) fn psnr_similarity(mse: f64) -> f64 {
         if mse == 0.0 {
             return 1.0; // Identical images
         }
@@ -256,113 +441,108 @@ impl ImageComparator {
         }
     }
 
-    /// Convert RGB image to grayscale
-    fn rgb_to_grayscale(img: &RgbImage) -> ImageBuffer<image::Luma<u8>, Vec<u8>> {
-        let (width, height) = img.dimensions();
-        let mut gray = ImageBuffer::new(width, height);
-
-        for y in 0..height {
-            for x in 0..width {
-                let pixel = img.get_pixel(x, y);
-                let gray_value = (0.299 * pixel[0] as f64
-                    + 0.587 * pixel[1] as f64
-                    + 0.114 * pixel[2] as f64) as u8;
-                gray.put_pixel(x, y, image::Luma([gray_value]));
+    /// Compute a 64-bit difference hash (dHash) of an image. Visually similar
+    /// images produce hashes a small Hamming distance apart.
+    pub fn perceptual_hash(img: &DynamicImage) -> u64 {
+        let small = img
+            .resize_exact(9, 8, image::imageops::FilterType::Triangle)
+            .to_luma8();
+
+        let mut hash = 0u64;
+        for y in 0..8 {
+            for x in 0..8 {
+                hash <<= 1;
+                if small.get_pixel(x, y)[0] > small.get_pixel(x + 1, y)[0] {
+                    hash |= 1;
+                }
             }
         }
 
-        gray
+        hash
     }
 
-    /// Calculate SSIM for grayscale images
-    fn calculate_ssim(
-        img1: &ImageBuffer<image::Luma<u8>, Vec<u8>>,
-        img2: &ImageBuffer<image::Luma<u8>, Vec<u8>>,
-    ) -> Result<f64> {
-        let (width, height) = img1.dimensions();
-
-        // Constants for SSIM calculation
-        let k1 = 0.01_f64;
-        let k2 = 0.03_f64;
-        let l = 255.0_f64; // Dynamic range
-        let c1 = (k1 * l).powi(2);
-        let c2 = (k2 * l).powi(2);
-
-        // Calculate means
-        let mut sum1 = 0.0;
-        let mut sum2 = 0.0;
-        let total_pixels = (width * height) as f64;
-
-        for y in 0..height {
-            for x in 0..width {
-                sum1 += img1.get_pixel(x, y)[0] as f64;
-                sum2 += img2.get_pixel(x, y)[0] as f64;
-            }
-        }
-
-        let mean1 = sum1 / total_pixels;
-        let mean2 = sum2 / total_pixels;
+    /// Number of differing bits between two perceptual hashes
+    pub fn hash_distance(hash1: u64, hash2: u64) -> u32 {
+        (hash1 ^ hash2).count_ones()
+    }
 
-        // Calculate variances and covariance
-        let mut var1 = 0.0;
-        let mut var2 = 0.0;
-        let mut covar = 0.0;
+    /// Convert RGB image to grayscale
+    fn rgb_to_grayscale(img: &RgbImage) -> GrayImage {
+        let (width, height) = img.dimensions();
+        let luma = img
+            .as_raw()
+            .par_chunks_exact(3)
+            .map(|pixel| {
+                (0.299 * pixel[0] as f64 + 0.587 * pixel[1] as f64 + 0.114 * pixel[2] as f64) as u8
+            })
+            .collect();
+        GrayImage::from_raw(width, height, luma).expect("one value per pixel")
+    }
 
-        for y in 0..height {
-            for x in 0..width {
-                let val1 = img1.get_pixel(x, y)[0] as f64;
-                let val2 = img2.get_pixel(x, y)[0] as f64;
+    /// Bounding boxes of the changed pixels between two images of the same
+    /// size, clustered on a grid of `options.region_gap` pixel cells so
+    /// changes in the same or touching cells form one region. Regions are
+    /// ordered by changed pixel count, largest first.
+    pub fn diff_regions(
+        img1: &RgbImage,
+        img2: &RgbImage,
+        options: &ComparisonOptions,
+    ) -> Vec<DiffRegion> {
+        let ssim_map = Self::ssim_map(img1, img2, options);
+        let changed = Self::changed_pixels(img1, img2, options, ssim_map.as_ref());
+        Self::regions(&changed, img1.width(), img1.height(), options.region_gap)
+    }
 
-                let diff1 = val1 - mean1;
-                let diff2 = val2 - mean2;
+    /// Regions of the `changed` pixels of a `width` × `height` image, see
+    /// [`Self::diff_regions`]
+    fn regions(changed: &[bool], width: u32, height: u32, region_gap: u32) -> Vec<DiffRegion> {
+        let mut grid = RegionGrid::new(width, height, region_gap);
+        grid.add_rows(changed, 0);
+        grid.into_regions()
+    }
 
-                var1 += diff1 * diff1;
-                var2 += diff2 * diff2;
-                covar += diff1 * diff2;
+    /// Transparent image of `img1`'s size with the pixels that differ from
+    /// `img2` in the diff color, for overlaying on either image
+    pub fn diff_mask(img1: &RgbImage, img2: &RgbImage, options: &ComparisonOptions) -> RgbaImage {
+        let (r, g, b) = options.diff_color;
+        let changed = (img1.dimensions() == img2.dimensions()).then(|| {
+            let ssim_map = Self::ssim_map(img1, img2, options);
+            Self::changed_pixels(img1, img2, options, ssim_map.as_ref())
+        });
+        RgbaImage::from_fn(img1.width(), img1.height(), |x, y| {
+            let differs = if let Some(changed) = &changed {
+                changed[(y * img1.width() + x) as usize]
+            } else {
+                img2.get_pixel_checked(x, y)
+                    .is_none_or(|pixel2| !Self::pixels_similar(img1.get_pixel(x, y), pixel2))
+            };
+            if differs {
+                Rgba([r, g, b, 255])
+            } else {
+                Rgba([0, 0, 0, 0])
             }
-        }
-
-        var1 /= total_pixels - 1.0;
-        var2 /= total_pixels - 1.0;
-        covar /= total_pixels - 1.0;
-
-        // Calculate SSIM
-        let numerator = (2.0 * mean1 * mean2 + c1) * (2.0 * covar + c2);
-        let denominator = (mean1 * mean1 + mean2 * mean2 + c1) * (var1 + var2 + c2);
-
-        let ssim = numerator / denominator;
-        Ok(ssim.clamp(0.0, 1.0))
+        })
     }
 
-    /// Generate a difference image highlighting changes
     fn generate_diff_image<P: AsRef<Path>>(
         img1: &RgbImage,
-        img2: &RgbImage,
+        changed: &[bool],
+        regions: &[DiffRegion],
         output_path: P,
         options: &ComparisonOptions,
     ) -> Result<()> {
         let (width, height) = img1.dimensions();
+        let (r, g, b) = options.diff_color;
         let mut diff_img = RgbImage::new(width, height);
 
-        for y in 0..height {
-            for x in 0..width {
-                let pixel1 = img1.get_pixel(x, y);
-                let pixel2 = img2.get_pixel(x, y);
-
-                if Self::pixels_similar(pixel1, pixel2, options.ignore_antialiasing) {
-                    // Keep original pixel (could be grayscale for subtle effect)
-                    diff_img.put_pixel(x, y, *pixel1);
-                } else {
-                    // Highlight difference
-                    diff_img.put_pixel(
-                        x,
-                        y,
-                        Rgb([
-                            options.diff_color.0,
-                            options.diff_color.1,
-                            options.diff_color.2,
-                        ]),
-                    );
+        match options.diff_style {
+            DiffStyle::Pixels => {
+                paint_changes(&mut diff_img, img1.as_raw(), changed, [r, g, b]);
+            }
+            DiffStyle::Boxes => {
+                diff_img.copy_from_slice(img1);
+                for region in regions {
+                    draw_outline(&mut diff_img, region, Rgb([r, g, b]), 0);
                 }
             }
         }
@@ -381,6 +561,312 @@ impl ImageComparator {
     }
 }
 
+/// Copy the raw RGB `original` into `output`, in `color` where `changed`
+pub(crate) fn paint_changes(output: &mut [u8], original: &[u8], changed: &[bool], color: [u8; 3]) {
+    output
+        .par_chunks_exact_mut(3)
+        .zip(original.par_chunks_exact(3))
+        .zip(changed.par_iter())
+        .for_each(|((pixel, original), &changed)| {
+            pixel.copy_from_slice(if changed { &color } else { original });
+        });
+}
+
+/// `image` as RGB, borrowed when it already is
+fn rgb_image(image: &DynamicImage) -> Cow<'_, RgbImage> {
+    match image.as_rgb8() {
+        Some(img) => Cow::Borrowed(img),
+        None => Cow::Owned(image.to_rgb8()),
+    }
+}
+
+/// Brightness of a pixel, the Y of YIQ
+fn yiq_brightness(pixel: &Rgb<u8>) -> f64 {
+    let [r, g, b] = pixel.0.map(f64::from);
+    r * 0.298_895_31 + g * 0.586_622_47 + b * 0.114_482_23
+}
+
+/// Whether the pixel at (`x`, `y`) of `img` looks anti-aliased, as in
+/// pixelmatch: it lies between its darkest and brightest neighbors in
+/// brightness, with at most two neighbors of the same brightness, and one
+/// of those extremes sits in a flat area of both `img` and `other`, as the
+/// inside of a glyph or shape does
+fn antialiased(img: &RgbImage, other: &RgbImage, x: u32, y: u32) -> bool {
+    let (width, height) = img.dimensions();
+    let brightness = yiq_brightness(img.get_pixel(x, y));
+    // Pixels on the image edge have fewer neighbors to tell them apart
+    let mut zeroes = u32::from(x == 0 || y == 0 || x + 1 == width || y + 1 == height);
+    let (mut darkest, mut brightest) = (0.0, 0.0);
+    let (mut darkest_at, mut brightest_at) = ((x, y), (x, y));
+    for ny in y.saturating_sub(1)..=(y + 1).min(height - 1) {
+        for nx in x.saturating_sub(1)..=(x + 1).min(width - 1) {
+            if (nx, ny) == (x, y) {
+                continue;
+            }
+            let delta = brightness - yiq_brightness(img.get_pixel(nx, ny));
+            if delta == 0.0 {
+                zeroes += 1;
+                if zeroes > 2 {
+                    return false;
+                }
+            } else if delta < brightest {
+                brightest = delta;
+                brightest_at = (nx, ny);
+            } else if delta > darkest {
+                darkest = delta;
+                darkest_at = (nx, ny);
+            }
+        }
+    }
+    if darkest == 0.0 || brightest == 0.0 {
+        return false;
+    }
+    let flat = |(x, y)| has_many_siblings(img, x, y) && has_many_siblings(other, x, y);
+    flat(darkest_at) || flat(brightest_at)
+}
+
+/// Whether more than two neighbors of the pixel at (`x`, `y`) have its
+/// exact color
+fn has_many_siblings(img: &RgbImage, x: u32, y: u32) -> bool {
+    let (width, height) = img.dimensions();
+    let pixel = img.get_pixel(x, y);
+    let mut zeroes = u32::from(x == 0 || y == 0 || x + 1 == width || y + 1 == height);
+    for ny in y.saturating_sub(1)..=(y + 1).min(height - 1) {
+        for nx in x.saturating_sub(1)..=(x + 1).min(width - 1) {
+            if (nx, ny) != (x, y) && img.get_pixel(nx, ny) == pixel {
+                zeroes += 1;
+                if zeroes > 2 {
+                    return true;
+                }
+            }
+        }
+    }
+    false
+}
+
+/// Draw a two pixel wide frame on the edge of `region` and just outside it,
+/// clipped to the image, whose first row is row `top` of the compared images
+pub(crate) fn draw_outline(image: &mut RgbImage, region: &DiffRegion, color: Rgb<u8>, top: u32) {
+    let (width, height) = image.dimensions();
+    let (offset, height) = (i64::from(top), i64::from(height));
+    let left = i64::from(region.x) - 1;
+    let up = i64::from(region.y) - 1;
+    let right = i64::from(region.x + region.width);
+    let bottom = i64::from(region.y + region.height);
+    for y in up.max(offset)..=bottom.min(offset + height - 1) {
+        for x in left.max(0)..=right.min(i64::from(width) - 1) {
+            if x <= left + 1 || x >= right - 1 || y <= up + 1 || y >= bottom - 1 {
+                image.put_pixel(x as u32, (y - offset) as u32, color);
+            }
+        }
+    }
+}
+
+/// CIE L*a*b* coordinates (D65 white) of an sRGB pixel
+pub fn srgb_to_lab(pixel: &Rgb<u8>) -> [f64; 3] {
+    let linear = |channel: u8| {
+        let c = f64::from(channel) / 255.0;
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    let (r, g, b) = (linear(pixel[0]), linear(pixel[1]), linear(pixel[2]));
+    // XYZ relative to the D65 white point
+    let x = (0.4124564 * r + 0.3575761 * g + 0.1804375 * b) / 0.95047;
+    let y = 0.2126729 * r + 0.7151522 * g + 0.0721750 * b;
+    let z = (0.0193339 * r + 0.1191920 * g + 0.9503041 * b) / 1.08883;
+
+    let f = |t: f64| {
+        if t > 216.0 / 24389.0 {
+            t.cbrt()
+        } else {
+            (24389.0 / 27.0 * t + 16.0) / 116.0
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+/// CIEDE2000 color difference of two L*a*b* colors; about 2.3 is the
+/// smallest difference people notice
+pub fn ciede2000(lab1: [f64; 3], lab2: [f64; 3]) -> f64 {
+    let [l1, a1, b1] = lab1;
+    let [l2, a2, b2] = lab2;
+    let pow25_7 = 25f64.powi(7);
+
+    // Stretch a* so neutral colors get their chroma right
+    let c_mean = (a1.hypot(b1) + a2.hypot(b2)) / 2.0;
+    let g = 0.5 * (1.0 - (c_mean.powi(7) / (c_mean.powi(7) + pow25_7)).sqrt());
+    let (a1, a2) = (a1 * (1.0 + g), a2 * (1.0 + g));
+    let (c1, c2) = (a1.hypot(b1), a2.hypot(b2));
+    let hue = |a: f64, b: f64| {
+        if a == 0.0 && b == 0.0 {
+            0.0
+        } else {
+            b.atan2(a).to_degrees().rem_euclid(360.0)
+        }
+    };
+    let (h1, h2) = (hue(a1, b1), hue(a2, b2));
+    let chromatic = c1 * c2 != 0.0;
+
+    let delta_l = l2 - l1;
+    let delta_c = c2 - c1;
+    let delta_h = match h2 - h1 {
+        _ if !chromatic => 0.0,
+        d if d > 180.0 => d - 360.0,
+        d if d < -180.0 => d + 360.0,
+        d => d,
+    };
+    let delta_h = 2.0 * (c1 * c2).sqrt() * (delta_h.to_radians() / 2.0).sin();
+
+    let l_mean = (l1 + l2) / 2.0;
+    let c_mean = (c1 + c2) / 2.0;
+    let h_mean = if !chromatic {
+        h1 + h2
+    } else if (h1 - h2).abs() <= 180.0 {
+        (h1 + h2) / 2.0
+    } else if h1 + h2 < 360.0 {
+        (h1 + h2 + 360.0) / 2.0
+    } else {
+        (h1 + h2 - 360.0) / 2.0
+    };
+
+    let t = 1.0 - 0.17 * (h_mean - 30.0).to_radians().cos()
+        + 0.24 * (2.0 * h_mean).to_radians().cos()
+        + 0.32 * (3.0 * h_mean + 6.0).to_radians().cos()
+        - 0.20 * (4.0 * h_mean - 63.0).to_radians().cos();
+    let l_offset = (l_mean - 50.0).powi(2);
+    let s_l = 1.0 + 0.015 * l_offset / (20.0 + l_offset).sqrt();
+    let s_c = 1.0 + 0.045 * c_mean;
+    let s_h = 1.0 + 0.015 * c_mean * t;
+    let rotation = 30.0 * (-((h_mean - 275.0) / 25.0).powi(2)).exp();
+    let r_c = 2.0 * (c_mean.powi(7) / (c_mean.powi(7) + pow25_7)).sqrt();
+    let r_t = -(2.0 * rotation).to_radians().sin() * r_c;
+
+    let (l, c, h) = (delta_l / s_l, delta_c / s_c, delta_h / s_h);
+    (l * l + c * c + h * h + r_t * c * h).sqrt()
+}
+
+/// Changed pixels of an image collected on a grid of `region_gap` pixel
+/// cells, a band of rows at a time, then clustered into regions
+pub(crate) struct RegionGrid {
+    width: u32,
+    cell: u32,
+    columns: u32,
+    rows: u32,
+    /// Changed pixel bounds and count per grid cell
+    cells: Vec<Option<DiffRegion>>,
+}
+
+impl RegionGrid {
+    pub(crate) fn new(width: u32, height: u32, region_gap: u32) -> Self {
+        let cell = region_gap.max(1);
+        let (columns, rows) = (width.div_ceil(cell), height.div_ceil(cell));
+        Self {
+            width,
+            cell,
+            columns,
+            rows,
+            cells: vec![None; (columns * rows) as usize],
+        }
+    }
+
+    /// Add whole rows of `changed` pixels, the first of them image row `top`
+    pub(crate) fn add_rows(&mut self, changed: &[bool], top: u32) {
+        if self.width == 0 {
+            return;
+        }
+        for (y, row) in (top..).zip(changed.chunks(self.width as usize)) {
+            // Unchanged rows are skipped with one scan
+            if !row.contains(&true) {
+                continue;
+            }
+            for (x, _) in (0..).zip(row).filter(|(_, &changed)| changed) {
+                let pixel = DiffRegion {
+                    x,
+                    y,
+                    width: 1,
+                    height: 1,
+                    pixels: 1,
+                };
+                let slot =
+                    &mut self.cells[((y / self.cell) * self.columns + x / self.cell) as usize];
+                *slot = Some(slot.map_or(pixel, |region| merge_regions(region, pixel)));
+            }
+        }
+    }
+
+    /// Regions of changed cells that touch, diagonals included, ordered by
+    /// changed pixel count, largest first
+    pub(crate) fn into_regions(self) -> Vec<DiffRegion> {
+        let Self {
+            columns,
+            rows,
+            cells,
+            ..
+        } = self;
+        let mut regions = Vec::new();
+        let mut visited = vec![false; cells.len()];
+        for start in 0..cells.len() {
+            if visited[start] || cells[start].is_none() {
+                continue;
+            }
+            visited[start] = true;
+            let mut region: Option<DiffRegion> = None;
+            let mut stack = vec![start];
+            while let Some(index) = stack.pop() {
+                let current = cells[index].expect("only changed cells are queued");
+                region = Some(region.map_or(current, |region| merge_regions(region, current)));
+
+                let (column, row) = (
+                    (index as u32 % columns) as i64,
+                    (index as u32 / columns) as i64,
+                );
+                for (dx, dy) in [
+                    (-1, -1),
+                    (0, -1),
+                    (1, -1),
+                    (-1, 0),
+                    (1, 0),
+                    (-1, 1),
+                    (0, 1),
+                    (1, 1),
+                ] {
+                    let (x, y) = (column + dx, row + dy);
+                    if x < 0 || y < 0 || x >= columns as i64 || y >= rows as i64 {
+                        continue;
+                    }
+                    let neighbor = (y * columns as i64 + x) as usize;
+                    if !visited[neighbor] && cells[neighbor].is_some() {
+                        visited[neighbor] = true;
+                        stack.push(neighbor);
+                    }
+                }
+            }
+            regions.extend(region);
+        }
+
+        regions.sort_by_key(|region| (std::cmp::Reverse(region.pixels), region.y, region.x));
+        regions
+    }
+}
+
+/// Smallest box covering both regions, with their pixel counts added
+fn merge_regions(a: DiffRegion, b: DiffRegion) -> DiffRegion {
+    let (left, top) = (a.x.min(b.x), a.y.min(b.y));
+    let right = (a.x + a.width).max(b.x + b.width);
+    let bottom = (a.y + a.height).max(b.y + b.height);
+    DiffRegion {
+        x: left,
+        y: top,
+        width: right - left,
+        height: bottom - top,
+        pixels: a.pixels + b.pixels,
+    }
+}
+
 impl ComparisonOptions {
     /// Create new comparison options with default values
     pub fn new() -> Self {
@@ -418,6 +904,30 @@ impl ComparisonOptions {
         self
     }
 
+    /// Set how the difference image marks changes
+    pub fn diff_style(mut self, style: DiffStyle) -> Self {
+        self.diff_style = style;
+        self
+    }
+
+    /// Set the grid cell size changed pixels are clustered into regions by
+    pub fn region_gap(mut self, gap: u32) -> Self {
+        self.region_gap = gap;
+        self
+    }
+
+    /// Set the largest CIEDE2000 difference of matching pixels for `DeltaE`
+    pub fn delta_e_tolerance(mut self, tolerance: f64) -> Self {
+        self.delta_e_tolerance = tolerance;
+        self
+    }
+
+    /// Compare PNG files in bands of `rows` rows to bound memory use
+    pub fn band_height(mut self, rows: u32) -> Self {
+        self.band_height = Some(rows);
+        self
+    }
+
     /// Validate the options
     pub fn validate(&self) -> Result<()> {
         if !(0.0..=1.0).contains(&self.threshold) {
@@ -427,12 +937,23 @@ impl ComparisonOptions {
             )));
         }
 
+        if !(self.delta_e_tolerance.is_finite() && self.delta_e_tolerance >= 0.0) {
+            return Err(WebshotError::config(format!(
+                "Delta E tolerance must be 0 or more, got: {}",
+                self.delta_e_tolerance
+            )));
+        }
+
         if self.generate_diff_image && self.diff_output_path.is_none() {
             return Err(WebshotError::config(
                 "Diff output path must be specified when generating diff image".to_string(),
             ));
         }
 
+        if self.band_height == Some(0) {
+            return Err(WebshotError::config("Band height must be at least 1 row"));
+        }
+
         Ok(())
     }
 }
@@ -460,6 +981,22 @@ mod tests {
         assert_eq!(result.different_pixels, Some(0));
     }
 
+    #[test]
+    fn test_perceptual_hash_tolerates_small_changes() {
+        let gradient: RgbImage = ImageBuffer::from_fn(90, 80, |x, _| Rgb([(x * 2) as u8, 0, 0]));
+        let mut tweaked = gradient.clone();
+        tweaked.put_pixel(10, 10, Rgb([255, 255, 255]));
+        let reversed: RgbImage =
+            ImageBuffer::from_fn(90, 80, |x, _| Rgb([(180 - x * 2) as u8, 0, 0]));
+
+        let hash = ImageComparator::perceptual_hash(&gradient.into());
+        let tweaked_hash = ImageComparator::perceptual_hash(&tweaked.into());
+        let reversed_hash = ImageComparator::perceptual_hash(&reversed.into());
+
+        assert!(ImageComparator::hash_distance(hash, tweaked_hash) <= 2);
+        assert!(ImageComparator::hash_distance(hash, reversed_hash) > 32);
+    }
+
     #[test]
     fn test_different_images() {
         let img1 = create_test_image(100, 100, [255, 0, 0]);
@@ -500,19 +1037,40 @@ mod tests {
         assert_eq!(result.diff_image_path, Some(diff_path));
     }
 
+    #[test]
+    fn test_algorithm_parsing() {
+        assert_eq!(
+            "SSIM".parse::<ComparisonAlgorithm>().unwrap(),
+            ComparisonAlgorithm::SSIM
+        );
+        assert_eq!(
+            "plugin:plugins/My_Diff.wasm"
+                .parse::<ComparisonAlgorithm>()
+                .unwrap(),
+            ComparisonAlgorithm::Plugin(PathBuf::from("plugins/My_Diff.wasm"))
+        );
+        assert!("plugin:".parse::<ComparisonAlgorithm>().is_err());
+        assert!("histogram"
+            .parse::<ComparisonAlgorithm>()
+            .unwrap_err()
+            .to_string()
+            .contains("Unknown algorithm"));
+    }
+
     #[test]
     fn test_comparison_algorithms() {
         // Test different algorithms with identical images first
         for algorithm in [
             ComparisonAlgorithm::PixelDiff,
             ComparisonAlgorithm::SSIM,
+            ComparisonAlgorithm::MSSSIM,
             ComparisonAlgorithm::MSE,
             ComparisonAlgorithm::PSNR,
         ] {
             let img1 = create_test_image(50, 50, [255, 0, 0]);
             let img2 = create_test_image(50, 50, [255, 0, 0]); // Identical
 
-            let options = ComparisonOptions::new().algorithm(algorithm);
+            let options = ComparisonOptions::new().algorithm(algorithm.clone());
             let result =
                 ImageComparator::compare_images(&img1.into(), &img2.into(), &options).unwrap();
 
@@ -536,7 +1094,7 @@ mod tests {
             let img1 = create_test_image(50, 50, [255, 0, 0]); // Red
             let img2 = create_test_image(50, 50, [0, 255, 0]); // Green - completely different
 
-            let options = ComparisonOptions::new().algorithm(algorithm);
+            let options = ComparisonOptions::new().algorithm(algorithm.clone());
             let result =
                 ImageComparator::compare_images(&img1.into(), &img2.into(), &options).unwrap();
 
@@ -562,4 +1120,252 @@ mod tests {
             assert_eq!(result.algorithm, algorithm);
         }
     }
+
+    #[test]
+    fn test_large_comparison_matches_per_pixel_math() {
+        let img1 = RgbImage::from_fn(1200, 900, |x, y| {
+            Rgb([(x % 256) as u8, (y % 256) as u8, ((x + y) % 256) as u8])
+        });
+        let mut img2 = img1.clone();
+        // Every 7th row changes in one pixel, by 3 levels in one channel
+        for y in (0..900).step_by(7) {
+            let pixel = img2.get_pixel_mut(y % 1200, y);
+            pixel[0] = if pixel[0] < 128 {
+                pixel[0] + 3
+            } else {
+                pixel[0] - 3
+            };
+        }
+        let changes = (0..900).step_by(7).count() as u32;
+
+        let options = ComparisonOptions::new();
+        let result =
+            ImageComparator::compare_images(&img1.clone().into(), &img2.clone().into(), &options)
+                .unwrap();
+        assert_eq!(result.different_pixels, Some(changes));
+
+        let mse = f64::from(changes * 9) / (1200.0 * 900.0 * 3.0);
+        let options = options.algorithm(ComparisonAlgorithm::MSE);
+        let result = ImageComparator::compare_images(&img1.into(), &img2.into(), &options).unwrap();
+        assert!((result.similarity - 1.0 / (1.0 + mse / 255.0)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_diff_regions_cluster_nearby_changes() {
+        let img1 = create_test_image(100, 100, [255, 255, 255]);
+        let mut img2 = img1.clone();
+        // Two changes a few pixels apart form one region
+        for (x, y) in [(10, 10), (11, 10), (18, 14)] {
+            img2.put_pixel(x, y, Rgb([0, 0, 0]));
+        }
+        // A block far away forms another, larger one
+        for y in 70..75 {
+            for x in 80..84 {
+                img2.put_pixel(x, y, Rgb([0, 0, 0]));
+            }
+        }
+
+        let options = ComparisonOptions::new().threshold(0.0).region_gap(8);
+        let regions = ImageComparator::diff_regions(&img1, &img2, &options);
+        assert_eq!(
+            regions,
+            vec![
+                DiffRegion {
+                    x: 80,
+                    y: 70,
+                    width: 4,
+                    height: 5,
+                    pixels: 20
+                },
+                DiffRegion {
+                    x: 10,
+                    y: 10,
+                    width: 9,
+                    height: 5,
+                    pixels: 3
+                },
+            ]
+        );
+        let result =
+            ImageComparator::compare_images(&img1.clone().into(), &img2.clone().into(), &options)
+                .unwrap();
+        assert_eq!(result.regions, regions);
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!(json["regions"][1]["pixels"], 3);
+
+        // Cells two apart are not neighbors
+        let options = options.region_gap(2);
+        assert_eq!(
+            ImageComparator::diff_regions(&img1, &img2, &options).len(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_ssim_map_drives_regions() {
+        let img1 = RgbImage::from_fn(60, 60, |x, y| {
+            let value = ((x * 37 + y * 91) % 256) as u8;
+            Rgb([value, value, value])
+        });
+        let mut img2 = img1.clone();
+        for y in 30..36 {
+            for x in 20..26 {
+                img2.put_pixel(x, y, Rgb([255, 255, 255]));
+            }
+        }
+
+        let options = ComparisonOptions::new()
+            .algorithm(ComparisonAlgorithm::SSIM)
+            .threshold(0.2);
+        let result =
+            ImageComparator::compare_images(&img1.clone().into(), &img2.clone().into(), &options)
+                .unwrap();
+        assert!(result.similarity < 1.0 && result.similarity > 0.8);
+        // One region around the block, spread by the window but not beyond it
+        assert_eq!(result.regions.len(), 1);
+        let region = &result.regions[0];
+        assert!(
+            region.x <= 20 && region.x + region.width >= 26,
+            "{:?}",
+            region
+        );
+        assert!(
+            region.y > 20 && region.y + region.height < 46,
+            "{:?}",
+            region
+        );
+
+        assert_eq!(
+            "ms-ssim".parse::<ComparisonAlgorithm>().unwrap(),
+            ComparisonAlgorithm::MSSSIM
+        );
+        let options = options.algorithm(ComparisonAlgorithm::MSSSIM);
+        let result = ImageComparator::compare_images(&img1.into(), &img2.into(), &options).unwrap();
+        assert!(result.similarity < 1.0);
+        assert_eq!(result.regions.len(), 1);
+    }
+
+    #[test]
+    fn test_diff_image_boxes() {
+        let temp_dir = TempDir::new().unwrap();
+        let diff_path = temp_dir.path().join("boxes.png");
+        let img1 = create_test_image(20, 20, [255, 255, 255]);
+        let mut img2 = img1.clone();
+        for y in 5..10 {
+            for x in 5..10 {
+                img2.put_pixel(x, y, Rgb([0, 0, 0]));
+            }
+        }
+
+        let options = ComparisonOptions::new()
+            .generate_diff_image(&diff_path)
+            .diff_style("boxes".parse().unwrap());
+        ImageComparator::compare_images(&img1.into(), &img2.into(), &options).unwrap();
+
+        let diff = image::open(&diff_path).unwrap().to_rgb8();
+        for (x, y) in [(5, 5), (4, 4), (9, 7), (10, 10)] {
+            assert_eq!(diff.get_pixel(x, y).0, [255, 0, 0], "({}, {})", x, y);
+        }
+        // Inside and outside the box keep the baseline pixels
+        assert_eq!(diff.get_pixel(7, 7).0, [255, 255, 255]);
+        assert_eq!(diff.get_pixel(15, 15).0, [255, 255, 255]);
+        assert!("outline".parse::<DiffStyle>().is_err());
+    }
+
+    #[test]
+    fn test_antialiasing_detection() {
+        // A black and white edge, then the same edge with one pixel
+        // smoothed to gray as anti-aliasing does
+        let edge = RgbImage::from_fn(8, 8, |x, _| {
+            if x < 4 {
+                Rgb([0, 0, 0])
+            } else {
+                Rgb([255, 255, 255])
+            }
+        });
+        let mut smoothed = edge.clone();
+        smoothed.put_pixel(4, 3, Rgb([128, 128, 128]));
+        let strict = ComparisonOptions::new();
+        let lenient = ComparisonOptions::new().ignore_antialiasing();
+        let (edge, smoothed) = (
+            DynamicImage::ImageRgb8(edge),
+            DynamicImage::ImageRgb8(smoothed),
+        );
+        let differing = |img2: &DynamicImage, options: &ComparisonOptions| {
+            ImageComparator::compare_images(&edge, img2, options)
+                .unwrap()
+                .different_pixels
+        };
+        assert_eq!(differing(&smoothed, &strict), Some(1));
+        assert_eq!(differing(&smoothed, &lenient), Some(0));
+
+        // A gray dot on a flat area is a real change
+        let mut dotted = edge.to_rgb8();
+        dotted.put_pixel(6, 3, Rgb([128, 128, 128]));
+        assert_eq!(
+            differing(&DynamicImage::ImageRgb8(dotted), &lenient),
+            Some(1)
+        );
+
+        // So is a shift of 10 levels, however small
+        let shifted = DynamicImage::ImageRgb8(RgbImage::from_fn(8, 8, |x, _| {
+            if x < 4 {
+                Rgb([10, 10, 10])
+            } else {
+                Rgb([245, 245, 245])
+            }
+        }));
+        assert_eq!(differing(&shifted, &lenient), Some(64));
+    }
+
+    #[test]
+    fn test_ciede2000_reference_pairs() {
+        // Pairs from Sharma, Wu and Dalal's CIEDE2000 test data
+        for (lab1, lab2, expected) in [
+            ([50.0, 2.6772, -79.7751], [50.0, 0.0, -82.7485], 2.0425),
+            ([50.0, -1.0, 2.0], [50.0, 0.0, 0.0], 2.3669),
+            ([50.0, 2.5, 0.0], [73.0, 25.0, -18.0], 27.1492),
+            ([2.0776, 0.0795, -1.135], [0.9033, -0.0636, -0.5514], 0.9082),
+        ] {
+            let delta = ciede2000(lab1, lab2);
+            assert!((delta - expected).abs() < 1e-4, "{} != {}", delta, expected);
+            assert!((ciede2000(lab2, lab1) - delta).abs() < 1e-9);
+        }
+
+        let white = srgb_to_lab(&Rgb([255, 255, 255]));
+        assert!((white[0] - 100.0).abs() < 1e-3 && white[1].abs() < 1e-3);
+        assert_eq!(srgb_to_lab(&Rgb([0, 0, 0])), [0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_delta_e_tolerates_imperceptible_shifts() {
+        // A codec or gamma shift of a few levels on every pixel
+        let baseline = create_test_image(20, 10, [128, 128, 128]);
+        let mut actual = create_test_image(20, 10, [131, 131, 131]);
+        for x in 0..5 {
+            actual.put_pixel(x, 0, Rgb([200, 80, 120]));
+        }
+        let (baseline, actual) = (baseline.into(), actual.into());
+        assert_eq!(
+            "delta-e".parse::<ComparisonAlgorithm>().unwrap(),
+            ComparisonAlgorithm::DeltaE
+        );
+
+        let options = ComparisonOptions::new()
+            .algorithm(ComparisonAlgorithm::DeltaE)
+            .threshold(0.05);
+        let result = ImageComparator::compare_images(&baseline, &actual, &options).unwrap();
+        assert_eq!(result.different_pixels, Some(5));
+        assert!(result.similar);
+
+        // The same shift fails a plain pixel diff
+        let pixel = ComparisonOptions::new().threshold(0.05);
+        let result = ImageComparator::compare_images(&baseline, &actual, &pixel).unwrap();
+        assert_eq!(result.different_pixels, Some(200));
+
+        let strict = options.clone().delta_e_tolerance(0.1);
+        let result = ImageComparator::compare_images(&baseline, &actual, &strict).unwrap();
+        assert_eq!(result.different_pixels, Some(200));
+        assert!(options.delta_e_tolerance(-1.0).validate().is_err());
+    }
 }
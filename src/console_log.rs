@@ -0,0 +1,230 @@
+use crate::error::{Result, WebshotError};
+use crate::page_errors::{describe, location, PageError};
+use headless_chrome::protocol::cdp::types::Event;
+use headless_chrome::protocol::cdp::Log::LogEntry;
+use headless_chrome::protocol::cdp::Runtime::events::ConsoleAPICalledEventParams;
+use headless_chrome::Tab;
+use serde::Serialize;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::{info, warn};
+
+/// One message from the page's console
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConsoleEntry {
+    /// RFC 3339 time the message was emitted
+    pub time: String,
+    /// `log`, `info`, `warning`, `error`, `debug`, ... or `exception`
+    pub level: String,
+    pub message: String,
+    /// `url:line:column` of the code that emitted the message, when known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+impl ConsoleEntry {
+    /// Read a `Runtime.consoleAPICalled` event (`console.log()` and friends)
+    pub fn from_console(params: &ConsoleAPICalledEventParams) -> Self {
+        let source = params
+            .stack_trace
+            .as_ref()
+            .and_then(|trace| trace.call_frames.first())
+            .map(|frame| location(&frame.url, frame.line_number, frame.column_number));
+        Self {
+            time: format_time(params.timestamp),
+            level: serde_name(&params.Type),
+            message: params
+                .args
+                .iter()
+                .map(describe)
+                .collect::<Vec<_>>()
+                .join(" "),
+            source,
+        }
+    }
+
+    /// Read a `Log.entryAdded` event, reported by the browser itself, such as
+    /// failed resource loads and security warnings
+    pub fn from_log(entry: &LogEntry) -> Self {
+        let source = entry
+            .url
+            .as_deref()
+            .filter(|url| !url.is_empty())
+            .map(|url| match entry.line_number {
+                Some(line) => format!("{}:{}", url, line + 1),
+                None => url.to_string(),
+            });
+        Self {
+            time: format_time(entry.timestamp),
+            level: serde_name(&entry.level),
+            message: entry.text.clone(),
+            source,
+        }
+    }
+
+    /// Record an uncaught exception
+    pub fn from_exception(timestamp: f64, error: PageError) -> Self {
+        Self {
+            time: format_time(timestamp),
+            level: "exception".to_string(),
+            message: error.message,
+            source: error.source,
+        }
+    }
+}
+
+impl fmt::Display for ConsoleEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} [{}] {}", self.time, self.level, self.message)?;
+        if let Some(source) = &self.source {
+            write!(f, " (at {})", source)?;
+        }
+        Ok(())
+    }
+}
+
+/// Records everything a tab prints to its console and writes it to a file
+/// once dropped, so the log is kept whether the capture succeeded or not.
+/// Files ending in `.json` get a JSON array; anything else gets one line per
+/// message.
+#[derive(Debug)]
+pub struct ConsoleLog {
+    path: PathBuf,
+    entries: Arc<Mutex<Vec<ConsoleEntry>>>,
+}
+
+impl ConsoleLog {
+    /// Start recording `tab`'s console, to be written to `path`
+    pub fn attach(tab: &Tab, path: impl Into<PathBuf>) -> Result<Self> {
+        let entries = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&entries);
+        tab.add_event_listener(Arc::new(move |event: &Event| {
+            let entry = match event {
+                Event::RuntimeConsoleAPICalled(event) => ConsoleEntry::from_console(&event.params),
+                Event::RuntimeExceptionThrown(event) => ConsoleEntry::from_exception(
+                    event.params.timestamp,
+                    PageError::from_exception(&event.params),
+                ),
+                Event::LogEntryAdded(event) => ConsoleEntry::from_log(&event.params.entry),
+                _ => return,
+            };
+            recorded.lock().unwrap().push(entry);
+        }))
+        .map_err(WebshotError::Browser)?;
+        tab.enable_runtime().map_err(WebshotError::Browser)?;
+        tab.enable_log().map_err(WebshotError::Browser)?;
+
+        Ok(Self {
+            path: path.into(),
+            entries,
+        })
+    }
+
+    /// Messages recorded so far
+    pub fn entries(&self) -> Vec<ConsoleEntry> {
+        self.entries.lock().unwrap().clone()
+    }
+
+    /// Write the messages recorded so far to the log file
+    pub fn save(&self) -> Result<()> {
+        write_entries(&self.path, &self.entries())
+    }
+}
+
+impl Drop for ConsoleLog {
+    fn drop(&mut self) {
+        match self.save() {
+            Ok(()) => info!("Console log saved to: {}", self.path.display()),
+            Err(e) => warn!("Failed to write console log {}: {}", self.path.display(), e),
+        }
+    }
+}
+
+/// Write console entries as JSON (for `.json` paths) or text lines
+pub fn write_entries(path: &Path, entries: &[ConsoleEntry]) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+    let is_json = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+    let contents = if is_json {
+        serde_json::to_string_pretty(entries)?
+    } else {
+        entries.iter().map(|entry| format!("{}\n", entry)).collect()
+    };
+    std::fs::write(path, contents)?;
+    Ok(())
+}
+
+/// DevTools timestamps are milliseconds since the Unix epoch
+fn format_time(timestamp: f64) -> String {
+    chrono::DateTime::from_timestamp_millis(timestamp as i64)
+        .map(|time| time.to_rfc3339_opts(chrono::SecondsFormat::Millis, true))
+        .unwrap_or_default()
+}
+
+/// The protocol name of an enum value, e.g. `warning`
+fn serde_name(value: &impl Serialize) -> String {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(name)) => name,
+        _ => "unknown".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    fn entries() -> Vec<ConsoleEntry> {
+        let console: ConsoleAPICalledEventParams = serde_json::from_value(json!({
+            "type": "warning",
+            "args": [{"type": "string", "value": "Deprecated API"}],
+            "executionContextId": 1,
+            "timestamp": 1_767_225_600_000.0_f64,
+        }))
+        .unwrap();
+        let log: LogEntry = serde_json::from_value(json!({
+            "source": "network",
+            "level": "error",
+            "text": "Failed to load resource: 404",
+            "timestamp": 1_767_225_600_250.0_f64,
+            "url": "https://example.com/missing.css",
+        }))
+        .unwrap();
+        vec![
+            ConsoleEntry::from_console(&console),
+            ConsoleEntry::from_log(&log),
+        ]
+    }
+
+    #[test]
+    fn test_text_log_has_one_line_per_message() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("logs").join("home.log");
+        write_entries(&path, &entries()).unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "2026-01-01T00:00:00.000Z [warning] Deprecated API\n\
+             2026-01-01T00:00:00.250Z [error] Failed to load resource: 404 \
+             (at https://example.com/missing.css)\n"
+        );
+    }
+
+    #[test]
+    fn test_json_log_is_an_array() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("home.console.json");
+        write_entries(&path, &entries()).unwrap();
+
+        let written: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(written[0]["level"], "warning");
+        assert!(written[0].get("source").is_none());
+        assert_eq!(written[1]["source"], "https://example.com/missing.css");
+    }
+}
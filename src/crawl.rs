@@ -0,0 +1,717 @@
+use crate::comparison::ImageComparator;
+use crate::config::validate_navigation_url;
+use crate::dedupe::content_hash;
+use crate::error::{Result, WebshotError};
+use crate::rate_limit::RateLimit;
+use crate::retry::RetryPolicy;
+use crate::sitemap::{glob_matches, matches_any};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tracing::{debug, warn};
+use url::Url;
+
+/// User agent token matched against `User-agent` lines in robots.txt
+pub const ROBOTS_USER_AGENT: &str = "webshot";
+
+/// Options controlling which pages a crawl visits
+#[derive(Debug, Clone, Default)]
+pub struct CrawlOptions {
+    /// Maximum number of pages to capture in total
+    pub max_pages: Option<usize>,
+    /// Maximum link depth from the seed URL (the seed is depth 0)
+    pub max_depth: Option<usize>,
+    /// Maximum number of pages to capture per host
+    pub max_pages_per_domain: Option<usize>,
+    /// Glob patterns a discovered page must match (any of them)
+    pub include: Vec<String>,
+    /// Glob patterns that exclude a discovered page
+    pub exclude: Vec<String>,
+    /// Hosts to follow links to in addition to the seed host
+    pub allowed_domains: Vec<String>,
+    /// Skip pages disallowed by the site's robots.txt
+    pub respect_robots: bool,
+    /// How to handle near-duplicate pages; `None` disables detection
+    pub duplicates: Option<DuplicateMode>,
+    /// Maximum perceptual-hash distance (in bits) for pages to count as near-duplicates
+    pub duplicate_threshold: u32,
+    /// Retries for pages that fail to load
+    pub retry: RetryPolicy,
+    /// Pacing of page loads per host
+    pub rate_limit: RateLimit,
+}
+
+/// Name of the crawl state file in the output directory, unless `--state`
+/// names another
+pub const DEFAULT_STATE_FILE: &str = "crawl-state.json";
+
+/// Default perceptual-hash distance for near-duplicate detection
+pub const DEFAULT_DUPLICATE_THRESHOLD: u32 = 4;
+
+/// What a crawl does with a page that duplicates one already captured
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateMode {
+    /// Delete the duplicate's screenshot, keeping only the first page
+    Skip,
+    /// Keep every screenshot and report duplicates grouped by cluster
+    Group,
+}
+
+impl FromStr for DuplicateMode {
+    type Err = WebshotError;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_str() {
+            "skip" => Ok(Self::Skip),
+            "group" => Ok(Self::Group),
+            _ => Err(WebshotError::config(format!(
+                "Unknown duplicate mode: {}. Supported: skip, group",
+                value
+            ))),
+        }
+    }
+}
+
+/// A captured page and the pages found to duplicate it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateCluster {
+    pub canonical: String,
+    pub duplicates: Vec<String>,
+}
+
+/// A page waiting in the crawl frontier
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CrawlEntry {
+    pub url: String,
+    pub depth: usize,
+}
+
+/// A page the crawl has captured
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrawledPage {
+    pub url: String,
+    pub depth: usize,
+    pub output: PathBuf,
+    /// HTTP status of the page's main document
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    /// Perceptual hash of the screenshot
+    #[serde(default)]
+    pub perceptual_hash: Option<u64>,
+    /// Hash of the page's DOM structure
+    #[serde(default)]
+    pub dom_hash: Option<u64>,
+    /// URL of the earlier page this one duplicates
+    #[serde(default)]
+    pub duplicate_of: Option<String>,
+}
+
+impl CrawledPage {
+    /// Create a record for a captured page without duplicate information
+    pub fn new(url: impl Into<String>, depth: usize, output: impl Into<PathBuf>) -> Self {
+        Self {
+            url: url.into(),
+            depth,
+            output: output.into(),
+            status: None,
+            perceptual_hash: None,
+            dom_hash: None,
+            duplicate_of: None,
+        }
+    }
+}
+
+/// Crawl progress, persisted to disk so interrupted crawls can resume
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CrawlState {
+    /// URL the crawl started from, normalized
+    #[serde(default)]
+    pub seed: Option<String>,
+    /// Hosts the crawl is scoped to (the seed host)
+    pub hosts: BTreeSet<String>,
+    /// Pages waiting to be visited, in breadth-first order
+    pub frontier: VecDeque<CrawlEntry>,
+    /// Every URL ever added to the frontier
+    pub seen: BTreeSet<String>,
+    /// Pages captured so far
+    pub pages: Vec<CrawledPage>,
+    /// Pages captured per host, for per-domain budgets
+    pub domain_pages: BTreeMap<String, usize>,
+}
+
+impl CrawlState {
+    /// Start a crawl from a seed URL
+    pub fn new(seed: &str) -> Result<Self> {
+        validate_navigation_url(seed, "crawl seed")?;
+        let seed = normalize_url(seed)?;
+        let host = host_of(&seed)?;
+
+        let mut state = Self {
+            seed: Some(seed.clone()),
+            ..Self::default()
+        };
+        state.hosts.insert(host);
+        state.seen.insert(seed.clone());
+        state.frontier.push_back(CrawlEntry {
+            url: seed,
+            depth: 0,
+        });
+
+        Ok(state)
+    }
+
+    /// Load a previously saved crawl state
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// Load a saved crawl state to continue the crawl started from `seed`,
+    /// refusing a state file left by a crawl of another seed URL
+    pub fn resume<P: AsRef<Path>>(path: P, seed: &str) -> Result<Self> {
+        let path = path.as_ref();
+        let state = Self::load(path)?;
+        let seed = normalize_url(seed)?;
+        let matches = match &state.seed {
+            Some(saved) => *saved == seed,
+            None => state.hosts.contains(&host_of(&seed)?),
+        };
+        if !matches {
+            return Err(WebshotError::config(format!(
+                "{} belongs to a crawl of {}, not {}; pass --restart to start over or --state to use another file",
+                path.display(),
+                state.seed.as_deref().unwrap_or("another site"),
+                seed
+            )));
+        }
+        Ok(state)
+    }
+
+    /// Save the crawl state, replacing the file atomically
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let temp_path = path.with_extension("tmp");
+        std::fs::write(&temp_path, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+
+    /// Pop the next page to visit, skipping pages over their domain budget.
+    /// Returns `None` once the frontier is empty or the page budget is spent.
+    pub fn next_entry(&mut self, options: &CrawlOptions) -> Option<CrawlEntry> {
+        if options
+            .max_pages
+            .is_some_and(|max_pages| self.pages.len() >= max_pages)
+        {
+            return None;
+        }
+
+        while let Some(entry) = self.frontier.pop_front() {
+            if self.domain_budget_spent(&entry.url, options) {
+                debug!("Domain budget spent, skipping: {}", entry.url);
+                continue;
+            }
+            return Some(entry);
+        }
+
+        None
+    }
+
+    /// `path`, or the first of `name-2.ext`, `name-3.ext`, ... that no
+    /// captured page was written to, so a page never overwrites another's
+    /// screenshot
+    pub fn unused_output(&self, path: PathBuf) -> PathBuf {
+        let taken = |candidate: &Path| self.pages.iter().any(|page| page.output == candidate);
+        let stem = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let extension = path
+            .extension()
+            .map(|extension| format!(".{}", extension.to_string_lossy()))
+            .unwrap_or_default();
+
+        let mut candidate = path.clone();
+        let mut counter = 2;
+        while taken(&candidate) {
+            candidate = path.with_file_name(format!("{}-{}{}", stem, counter, extension));
+            counter += 1;
+        }
+        candidate
+    }
+
+    /// Record a captured page
+    pub fn record_page(&mut self, page: CrawledPage) {
+        if let Ok(host) = host_of(&page.url) {
+            *self.domain_pages.entry(host).or_default() += 1;
+        }
+        self.pages.push(page);
+    }
+
+    /// Add links discovered on a page at `depth` to the frontier, returning
+    /// how many were new and in scope
+    pub fn enqueue_links(
+        &mut self,
+        links: &[String],
+        depth: usize,
+        options: &CrawlOptions,
+    ) -> usize {
+        let depth = depth + 1;
+        if options.max_depth.is_some_and(|max_depth| depth > max_depth) {
+            return 0;
+        }
+
+        let mut added = 0;
+        for link in links {
+            let Ok(url) = normalize_url(link) else {
+                continue;
+            };
+            if self.seen.contains(&url) || !self.in_scope(&url, options) {
+                continue;
+            }
+
+            self.seen.insert(url.clone());
+            self.frontier.push_back(CrawlEntry { url, depth });
+            added += 1;
+        }
+
+        added
+    }
+
+    /// Find an earlier, non-duplicate page with the same DOM structure whose
+    /// screenshot is within `threshold` bits of `perceptual_hash`
+    pub fn find_duplicate(
+        &self,
+        perceptual_hash: u64,
+        dom_hash: u64,
+        threshold: u32,
+    ) -> Option<&CrawledPage> {
+        self.pages.iter().find(|page| {
+            page.duplicate_of.is_none()
+                && page.dom_hash == Some(dom_hash)
+                && page.perceptual_hash.is_some_and(|hash| {
+                    ImageComparator::hash_distance(hash, perceptual_hash) <= threshold
+                })
+        })
+    }
+
+    /// Group captured pages by the page they duplicate
+    pub fn duplicate_clusters(&self) -> Vec<DuplicateCluster> {
+        let mut clusters: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+        for page in &self.pages {
+            if let Some(canonical) = &page.duplicate_of {
+                clusters
+                    .entry(canonical.as_str())
+                    .or_default()
+                    .push(page.url.clone());
+            }
+        }
+
+        clusters
+            .into_iter()
+            .map(|(canonical, duplicates)| DuplicateCluster {
+                canonical: canonical.to_string(),
+                duplicates,
+            })
+            .collect()
+    }
+
+    fn in_scope(&self, url: &str, options: &CrawlOptions) -> bool {
+        if validate_navigation_url(url, "crawl link").is_err() {
+            return false;
+        }
+        let Ok(host) = host_of(url) else {
+            return false;
+        };
+        let host_allowed = self.hosts.contains(&host)
+            || options
+                .allowed_domains
+                .iter()
+                .any(|domain| domain.eq_ignore_ascii_case(&host));
+
+        host_allowed
+            && matches_any(url, &options.include)
+            && (options.exclude.is_empty() || !matches_any(url, &options.exclude))
+    }
+
+    fn domain_budget_spent(&self, url: &str, options: &CrawlOptions) -> bool {
+        let Some(budget) = options.max_pages_per_domain else {
+            return false;
+        };
+        host_of(url)
+            .map(|host| self.domain_pages.get(&host).copied().unwrap_or(0) >= budget)
+            .unwrap_or(false)
+    }
+}
+
+/// Parsed robots.txt rules for one user agent
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RobotsTxt {
+    rules: Vec<RobotsRule>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RobotsRule {
+    allow: bool,
+    pattern: String,
+}
+
+impl RobotsTxt {
+    /// Parse robots.txt, keeping the group for `user_agent` or, if the file
+    /// has none, the `*` group
+    pub fn parse(content: &str, user_agent: &str) -> Self {
+        let user_agent = user_agent.to_lowercase();
+        let mut specific = Vec::new();
+        let mut wildcard = Vec::new();
+        let mut has_specific_group = false;
+
+        let mut group_agents: Vec<String> = Vec::new();
+        let mut in_rules = false;
+
+        for line in content.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let Some((field, value)) = line.split_once(':') else {
+                continue;
+            };
+            let field = field.trim().to_lowercase();
+            let value = value.trim();
+
+            match field.as_str() {
+                "user-agent" => {
+                    if in_rules {
+                        group_agents.clear();
+                        in_rules = false;
+                    }
+                    let agent = value.to_lowercase();
+                    if agent != "*" && user_agent.contains(&agent) {
+                        has_specific_group = true;
+                    }
+                    group_agents.push(agent);
+                }
+                "allow" | "disallow" => {
+                    in_rules = true;
+                    if value.is_empty() {
+                        continue;
+                    }
+                    let rule = RobotsRule {
+                        allow: field == "allow",
+                        pattern: value.to_string(),
+                    };
+                    if group_agents
+                        .iter()
+                        .any(|agent| agent != "*" && user_agent.contains(agent.as_str()))
+                    {
+                        specific.push(rule.clone());
+                    }
+                    if group_agents.iter().any(|agent| agent == "*") {
+                        wildcard.push(rule);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Self {
+            rules: if has_specific_group {
+                specific
+            } else {
+                wildcard
+            },
+        }
+    }
+
+    /// Check whether a URL path (with query) may be fetched. The longest
+    /// matching rule wins; ties go to `Allow`.
+    pub fn allows(&self, path: &str) -> bool {
+        self.rules
+            .iter()
+            .filter(|rule| robots_pattern_matches(&rule.pattern, path))
+            .max_by_key(|rule| (rule.pattern.len(), rule.allow))
+            .map(|rule| rule.allow)
+            .unwrap_or(true)
+    }
+
+    /// Fetch robots.txt for the origin of `url`. Missing or unreachable files
+    /// allow everything.
+    pub async fn fetch(client: &reqwest::Client, url: &str) -> Self {
+        let robots_url = match Url::parse(url).and_then(|url| url.join("/robots.txt")) {
+            Ok(robots_url) => robots_url,
+            Err(_) => return Self::default(),
+        };
+
+        let response = match client.get(robots_url.as_str()).send().await {
+            Ok(response) if response.status().is_success() => response,
+            Ok(response) => {
+                debug!("No robots.txt at {} ({})", robots_url, response.status());
+                return Self::default();
+            }
+            Err(e) => {
+                warn!("Failed to fetch {}: {}", robots_url, e);
+                return Self::default();
+            }
+        };
+
+        match response.text().await {
+            Ok(content) => Self::parse(&content, ROBOTS_USER_AGENT),
+            Err(e) => {
+                warn!("Failed to read {}: {}", robots_url, e);
+                Self::default()
+            }
+        }
+    }
+}
+
+fn robots_pattern_matches(pattern: &str, path: &str) -> bool {
+    match pattern.strip_suffix('$') {
+        Some(anchored) => glob_matches(anchored, path),
+        None => glob_matches(&format!("{}*", pattern), path),
+    }
+}
+
+/// Origin key (`scheme://host[:port]`) used to cache robots.txt per site
+pub fn origin_of(url: &str) -> Result<String> {
+    Ok(Url::parse(url)?.origin().ascii_serialization())
+}
+
+/// Path and query of a URL, as matched by robots.txt rules
+pub fn robots_path(url: &str) -> Result<String> {
+    let url = Url::parse(url)?;
+    Ok(match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    })
+}
+
+/// Hash a DOM structure signature (stable across runs so it can be
+/// persisted in the crawl state)
+pub fn dom_hash(signature: &str) -> u64 {
+    content_hash(signature.as_bytes())
+}
+
+/// Normalize a URL for de-duplication by dropping its fragment
+pub fn normalize_url(url: &str) -> Result<String> {
+    let mut url = Url::parse(url)?;
+    url.set_fragment(None);
+    Ok(url.to_string())
+}
+
+fn host_of(url: &str) -> Result<String> {
+    Url::parse(url)?
+        .host_str()
+        .map(str::to_lowercase)
+        .ok_or_else(|| WebshotError::config(format!("URL has no host: {}", url)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn links(urls: &[&str]) -> Vec<String> {
+        urls.iter().map(|url| url.to_string()).collect()
+    }
+
+    #[test]
+    fn test_enqueue_links_scopes_to_seed_host_and_filters() {
+        let mut state = CrawlState::new("https://example.com/").unwrap();
+        let options = CrawlOptions {
+            exclude: vec!["/private/*".to_string()],
+            ..Default::default()
+        };
+
+        let added = state.enqueue_links(
+            &links(&[
+                "https://example.com/about#team",
+                "https://example.com/about",
+                "https://example.com/private/admin",
+                "https://other.com/",
+                "mailto:hello@example.com",
+            ]),
+            0,
+            &options,
+        );
+
+        assert_eq!(added, 1);
+        assert_eq!(
+            state.frontier.back().unwrap(),
+            &CrawlEntry {
+                url: "https://example.com/about".to_string(),
+                depth: 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_enqueue_links_respects_max_depth() {
+        let mut state = CrawlState::new("https://example.com/").unwrap();
+        let options = CrawlOptions {
+            max_depth: Some(1),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            state.enqueue_links(&links(&["https://example.com/a"]), 0, &options),
+            1
+        );
+        assert_eq!(
+            state.enqueue_links(&links(&["https://example.com/b"]), 1, &options),
+            0
+        );
+    }
+
+    #[test]
+    fn test_next_entry_enforces_page_and_domain_budgets() {
+        let mut state = CrawlState::new("https://example.com/").unwrap();
+        let options = CrawlOptions {
+            max_pages_per_domain: Some(1),
+            allowed_domains: vec!["docs.example.com".to_string()],
+            ..Default::default()
+        };
+        state.enqueue_links(
+            &links(&["https://example.com/a", "https://docs.example.com/"]),
+            0,
+            &options,
+        );
+
+        let seed = state.next_entry(&options).unwrap();
+        state.record_page(CrawledPage::new(seed.url, seed.depth, "seed.png"));
+
+        assert_eq!(
+            state.next_entry(&options).unwrap().url,
+            "https://docs.example.com/"
+        );
+
+        let capped = CrawlOptions {
+            max_pages: Some(1),
+            ..Default::default()
+        };
+        state.frontier.push_back(CrawlEntry {
+            url: "https://example.com/b".to_string(),
+            depth: 1,
+        });
+        assert!(state.next_entry(&capped).is_none());
+    }
+
+    #[test]
+    fn test_state_round_trips_through_disk() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("crawl-state.json");
+
+        let mut state = CrawlState::new("https://example.com/").unwrap();
+        state.enqueue_links(
+            &links(&["https://example.com/a"]),
+            0,
+            &CrawlOptions::default(),
+        );
+        state.save(&path).unwrap();
+
+        let loaded = CrawlState::load(&path).unwrap();
+        assert_eq!(loaded.frontier, state.frontier);
+        assert_eq!(loaded.seen, state.seen);
+        assert!(!path.with_extension("tmp").exists());
+    }
+
+    #[test]
+    fn test_unused_output_skips_files_of_captured_pages() {
+        let mut state = CrawlState::new("https://example.com/").unwrap();
+        let path = PathBuf::from("out/example.com_list.png");
+        assert_eq!(state.unused_output(path.clone()), path);
+
+        state.record_page(CrawledPage::new("https://example.com/list", 1, &path));
+        state.record_page(CrawledPage::new(
+            "https://example.com/list/",
+            1,
+            "out/example.com_list-2.png",
+        ));
+        assert_eq!(
+            state.unused_output(path),
+            PathBuf::from("out/example.com_list-3.png")
+        );
+    }
+
+    #[test]
+    fn test_resume_rejects_another_seed() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("crawl-state.json");
+        CrawlState::new("https://example.com/docs")
+            .unwrap()
+            .save(&path)
+            .unwrap();
+
+        assert!(CrawlState::resume(&path, "https://example.com/docs").is_ok());
+        let error = CrawlState::resume(&path, "https://example.org/").unwrap_err();
+        assert!(error.to_string().contains("--restart"));
+    }
+
+    #[test]
+    fn test_duplicates_require_same_dom_and_similar_screenshot() {
+        let mut state = CrawlState::new("https://example.com/").unwrap();
+        let template = dom_hash("0BODY.|1UL.list|2LI.item");
+
+        let mut first = CrawledPage::new("https://example.com/page/1", 1, "page_1.png");
+        first.perceptual_hash = Some(0b1010_1010);
+        first.dom_hash = Some(template);
+        state.record_page(first);
+
+        assert!(state.find_duplicate(0b1010_1011, template, 4).is_some());
+        assert!(state.find_duplicate(0b0101_0101, template, 4).is_none());
+        assert!(state
+            .find_duplicate(0b1010_1010, dom_hash("0BODY.|1ARTICLE."), 4)
+            .is_none());
+
+        let mut second = CrawledPage::new("https://example.com/page/2", 1, "page_2.png");
+        second.duplicate_of = Some("https://example.com/page/1".to_string());
+        state.record_page(second);
+
+        assert_eq!(
+            state.duplicate_clusters(),
+            vec![DuplicateCluster {
+                canonical: "https://example.com/page/1".to_string(),
+                duplicates: vec!["https://example.com/page/2".to_string()],
+            }]
+        );
+        assert_eq!(
+            "Group".parse::<DuplicateMode>().unwrap(),
+            DuplicateMode::Group
+        );
+        assert!("merge".parse::<DuplicateMode>().is_err());
+    }
+
+    #[test]
+    fn test_robots_selects_matching_group() {
+        let robots = RobotsTxt::parse(
+            "User-agent: *\nDisallow: /\n\nUser-agent: Webshot\nDisallow: /private\nAllow: /private/public$\n",
+            ROBOTS_USER_AGENT,
+        );
+
+        assert!(robots.allows("/"));
+        assert!(!robots.allows("/private/page"));
+        assert!(robots.allows("/private/public"));
+        assert!(!robots.allows("/private/public/more"));
+    }
+
+    #[test]
+    fn test_robots_wildcard_group_and_empty_file() {
+        let robots = RobotsTxt::parse(
+            "User-agent: googlebot\nDisallow: /\n\nUser-agent: *\nDisallow: /*.pdf$\nDisallow:\n",
+            ROBOTS_USER_AGENT,
+        );
+
+        assert!(robots.allows("/docs/"));
+        assert!(!robots.allows("/docs/file.pdf"));
+        assert!(RobotsTxt::parse("", ROBOTS_USER_AGENT).allows("/anything"));
+    }
+
+    #[test]
+    fn test_robots_path_includes_query() {
+        assert_eq!(
+            robots_path("https://example.com/search?q=1#top").unwrap(),
+            "/search?q=1"
+        );
+        assert_eq!(
+            origin_of("https://example.com:8443/a").unwrap(),
+            "https://example.com:8443"
+        );
+    }
+}
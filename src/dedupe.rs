@@ -0,0 +1,312 @@
+use crate::comparison::ImageComparator;
+use crate::error::{Result, WebshotError};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tracing::{debug, info, warn};
+
+/// File name of the manifest written next to deduplicated outputs
+pub const DEDUPE_MANIFEST: &str = "dedupe-manifest.json";
+
+/// How duplicate files are replaced
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkKind {
+    Hard,
+    Symbolic,
+}
+
+impl FromStr for LinkKind {
+    type Err = WebshotError;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_str() {
+            "hard" | "hardlink" => Ok(Self::Hard),
+            "symbolic" | "symlink" | "soft" => Ok(Self::Symbolic),
+            _ => Err(WebshotError::config(format!(
+                "Unknown link kind: {}. Supported: hard, symlink",
+                value
+            ))),
+        }
+    }
+}
+
+/// Options for output deduplication
+#[derive(Debug, Clone)]
+pub struct DedupeOptions {
+    /// Link type used to replace duplicates
+    pub link: LinkKind,
+    /// Also treat images with identical perceptual hashes as duplicates
+    pub perceptual: bool,
+}
+
+impl Default for DedupeOptions {
+    fn default() -> Self {
+        Self {
+            link: LinkKind::Hard,
+            perceptual: false,
+        }
+    }
+}
+
+/// Why a file was considered a duplicate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DuplicateMatch {
+    Bytes,
+    Perceptual,
+}
+
+/// A duplicate file replaced by a link
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DedupeEntry {
+    pub path: PathBuf,
+    pub target: PathBuf,
+    pub matched: DuplicateMatch,
+    pub link: LinkKind,
+    pub bytes_saved: u64,
+}
+
+/// Result of a deduplication pass, written as the dedupe manifest
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DedupeReport {
+    pub files_scanned: usize,
+    pub entries: Vec<DedupeEntry>,
+    pub bytes_saved: u64,
+}
+
+impl DedupeReport {
+    /// Write the report as JSON
+    pub fn write_manifest<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// Replace duplicate files with links to the first occurrence.
+///
+/// Files are compared byte for byte; with `perceptual` set, images whose
+/// perceptual hashes are identical are merged as well. Missing files and
+/// files that are already links to each other are skipped.
+pub fn dedupe_files(paths: &[PathBuf], options: &DedupeOptions) -> Result<DedupeReport> {
+    let mut report = DedupeReport::default();
+    let mut by_content: HashMap<(usize, u64), PathBuf> = HashMap::new();
+    let mut by_hash: HashMap<(String, u64), PathBuf> = HashMap::new();
+
+    for path in paths {
+        let metadata = match std::fs::symlink_metadata(path) {
+            Ok(metadata) if metadata.is_file() => metadata,
+            _ => {
+                debug!("Skipping missing or non-regular file: {}", path.display());
+                continue;
+            }
+        };
+        report.files_scanned += 1;
+
+        let content = std::fs::read(path)?;
+        let content_key = (content.len(), content_hash(&content));
+        let byte_match = match by_content.get(&content_key) {
+            Some(original) if std::fs::read(original)? == content => Some(original.clone()),
+            _ => None,
+        };
+
+        let (target, matched) = if let Some(original) = byte_match {
+            (original, DuplicateMatch::Bytes)
+        } else {
+            by_content
+                .entry(content_key)
+                .or_insert_with(|| path.clone());
+
+            // Perceptual matches stay within one format so links keep their extension
+            let perceptual_key = if options.perceptual {
+                let extension = path
+                    .extension()
+                    .map(|ext| ext.to_string_lossy().to_lowercase())
+                    .unwrap_or_default();
+                match image::open(path) {
+                    Ok(img) => Some((extension, ImageComparator::perceptual_hash(&img))),
+                    Err(e) => {
+                        warn!("Skipping perceptual hash for {}: {}", path.display(), e);
+                        None
+                    }
+                }
+            } else {
+                None
+            };
+
+            match perceptual_key {
+                Some(key) => match by_hash.get(&key) {
+                    Some(original) => (original.clone(), DuplicateMatch::Perceptual),
+                    None => {
+                        by_hash.insert(key, path.clone());
+                        continue;
+                    }
+                },
+                None => continue,
+            }
+        };
+
+        if same_file(path, &target)? {
+            continue;
+        }
+
+        replace_with_link(path, &target, options.link)?;
+        info!("Deduplicated {} -> {}", path.display(), target.display());
+
+        report.bytes_saved += metadata.len();
+        report.entries.push(DedupeEntry {
+            path: path.clone(),
+            target,
+            matched,
+            link: options.link,
+            bytes_saved: metadata.len(),
+        });
+    }
+
+    Ok(report)
+}
+
+/// Replace `path` with a link to `target`, restoring the original on failure
+fn replace_with_link(path: &Path, target: &Path, link: LinkKind) -> Result<()> {
+    let backup = path.with_extension("dedupe-tmp");
+    std::fs::rename(path, &backup)?;
+
+    let linked = match link {
+        LinkKind::Hard => std::fs::hard_link(target, path),
+        LinkKind::Symbolic => symlink(&symlink_target(path, target)?, path),
+    };
+
+    match linked {
+        Ok(()) => {
+            std::fs::remove_file(&backup)?;
+            Ok(())
+        }
+        Err(e) => {
+            std::fs::rename(&backup, path)?;
+            Err(e.into())
+        }
+    }
+}
+
+/// Relative target when both files share a directory, absolute otherwise
+fn symlink_target(path: &Path, target: &Path) -> Result<PathBuf> {
+    if path.parent() == target.parent() {
+        if let Some(file_name) = target.file_name() {
+            return Ok(PathBuf::from(file_name));
+        }
+    }
+    Ok(std::fs::canonicalize(target)?)
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, path: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, path)
+}
+
+#[cfg(windows)]
+fn symlink(target: &Path, path: &Path) -> std::io::Result<()> {
+    std::os::windows::fs::symlink_file(target, path)
+}
+
+/// 64-bit FNV-1a hash, stable across runs and platforms
+pub(crate) fn content_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Whether two paths already refer to the same file (including hard links)
+#[cfg(unix)]
+fn same_file(path: &Path, other: &Path) -> Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+
+    let (a, b) = (std::fs::metadata(path)?, std::fs::metadata(other)?);
+    Ok(a.dev() == b.dev() && a.ino() == b.ino())
+}
+
+#[cfg(not(unix))]
+fn same_file(path: &Path, other: &Path) -> Result<bool> {
+    Ok(std::fs::canonicalize(path)? == std::fs::canonicalize(other)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_dedupe_replaces_identical_files_with_hard_links() {
+        let temp_dir = TempDir::new().unwrap();
+        let first = temp_dir.path().join("a.png");
+        let second = temp_dir.path().join("b.png");
+        let third = temp_dir.path().join("c.png");
+        std::fs::write(&first, b"same bytes").unwrap();
+        std::fs::write(&second, b"same bytes").unwrap();
+        std::fs::write(&third, b"other bytes").unwrap();
+
+        let paths = vec![first.clone(), second.clone(), third.clone()];
+        let report = dedupe_files(&paths, &DedupeOptions::default()).unwrap();
+
+        assert_eq!(report.files_scanned, 3);
+        assert_eq!(report.entries.len(), 1);
+        assert_eq!(report.entries[0].path, second);
+        assert_eq!(report.entries[0].target, first);
+        assert_eq!(report.entries[0].matched, DuplicateMatch::Bytes);
+        assert_eq!(report.bytes_saved, 10);
+        assert_eq!(std::fs::read(&second).unwrap(), b"same bytes");
+
+        // A second pass finds nothing new to link
+        let report = dedupe_files(&paths, &DedupeOptions::default()).unwrap();
+        assert!(report.entries.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_dedupe_with_symlinks_uses_relative_targets() {
+        let temp_dir = TempDir::new().unwrap();
+        let first = temp_dir.path().join("a.png");
+        let second = temp_dir.path().join("b.png");
+        std::fs::write(&first, b"same").unwrap();
+        std::fs::write(&second, b"same").unwrap();
+
+        let options = DedupeOptions {
+            link: LinkKind::Symbolic,
+            perceptual: false,
+        };
+        dedupe_files(&[first, second.clone()], &options).unwrap();
+
+        assert_eq!(std::fs::read_link(&second).unwrap(), PathBuf::from("a.png"));
+    }
+
+    #[test]
+    fn test_dedupe_perceptual_matches_nearly_identical_images() {
+        let temp_dir = TempDir::new().unwrap();
+        let first = temp_dir.path().join("a.png");
+        let second = temp_dir.path().join("b.png");
+        let jpg = temp_dir.path().join("c.jpg");
+        let img = image::RgbImage::from_fn(64, 64, |x, _| image::Rgb([(x * 4) as u8, 0, 0]));
+        let mut tweaked = img.clone();
+        tweaked.put_pixel(0, 0, image::Rgb([1, 0, 0]));
+        img.save(&first).unwrap();
+        tweaked.save(&second).unwrap();
+        img.save(&jpg).unwrap();
+
+        let options = DedupeOptions {
+            link: LinkKind::Hard,
+            perceptual: true,
+        };
+        let report = dedupe_files(&[first, second.clone(), jpg], &options).unwrap();
+
+        assert_eq!(report.entries.len(), 1);
+        assert_eq!(report.entries[0].path, second);
+        assert_eq!(report.entries[0].matched, DuplicateMatch::Perceptual);
+    }
+
+    #[test]
+    fn test_link_kind_parsing() {
+        assert_eq!("symlink".parse::<LinkKind>().unwrap(), LinkKind::Symbolic);
+        assert_eq!("HARD".parse::<LinkKind>().unwrap(), LinkKind::Hard);
+        assert!("copy".parse::<LinkKind>().is_err());
+    }
+}
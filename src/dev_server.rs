@@ -0,0 +1,385 @@
+//! Deterministic fixture pages for `webshot dev-server` and offline tests.
+//!
+//! The fixtures are bundled into the binary and never change between runs,
+//! so smoke tests capture the same pixels every time without depending on
+//! a site on the internet:
+//!
+//! - `GET /` lists the fixtures
+//! - `GET /static` is a static article with a `#card` element
+//! - `GET /spa` renders its content only once `GET /api/items` answers,
+//!   after `?delay=MS` (default 1000) milliseconds, then sets
+//!   `data-ready="true"` on `<body>`
+//! - `GET /lazy` is a long page of `loading="lazy"` images served from
+//!   `GET /images/{n}.svg`
+//! - `GET /login` is a sign-in form; posting user `admin` and password
+//!   `secret` sets a session cookie and redirects to `GET /account`, which
+//!   redirects back to the form without the cookie
+//! - `GET /headers` answers with the request headers as JSON
+//!
+//! [`DevServer::start`] runs the fixtures on a free localhost port in a
+//! background thread, for tests that drive the `webshot` binary.
+
+use crate::error::{Result, WebshotError};
+use hyper::header::{HeaderValue, CONTENT_TYPE, COOKIE, LOCATION, SET_COOKIE};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::net::{SocketAddr, TcpListener};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tracing::{info, warn};
+
+const INDEX_HTML: &str = include_str!("../assets/fixtures/index.html");
+const STATIC_HTML: &str = include_str!("../assets/fixtures/static.html");
+const SPA_HTML: &str = include_str!("../assets/fixtures/spa.html");
+const LAZY_HTML: &str = include_str!("../assets/fixtures/lazy.html");
+const LOGIN_HTML: &str = include_str!("../assets/fixtures/login.html");
+const ACCOUNT_HTML: &str = include_str!("../assets/fixtures/account.html");
+
+/// Credentials the login fixture accepts
+pub const FIXTURE_USER: &str = "admin";
+pub const FIXTURE_PASSWORD: &str = "secret";
+
+/// Cookie the login fixture sets after signing in
+const SESSION_COOKIE: &str = "webshot_session=fixture";
+
+/// Delay of `GET /api/items` without `?delay=`
+const DEFAULT_ITEMS_DELAY_MS: u64 = 1000;
+
+/// Longest delay `?delay=` may ask for
+const MAX_ITEMS_DELAY_MS: u64 = 10_000;
+
+/// Colors of the lazy-loaded images, picked by image number
+const IMAGE_COLORS: &[&str] = &[
+    "#1d4ed8", "#15803d", "#b45309", "#7e22ce", "#be123c", "#0e7490",
+];
+
+/// Answer one request for a fixture
+pub async fn handle(request: Request<Body>) -> Response<Body> {
+    let path = request.uri().path().to_string();
+    match (request.method(), path.as_str()) {
+        (&Method::GET, "/") => html(StatusCode::OK, INDEX_HTML),
+        (&Method::GET, "/static") => html(StatusCode::OK, STATIC_HTML),
+        (&Method::GET, "/spa") => html(StatusCode::OK, SPA_HTML),
+        (&Method::GET, "/api/items") => {
+            let delay = query_param(&request, "delay")
+                .and_then(|delay| delay.parse().ok())
+                .unwrap_or(DEFAULT_ITEMS_DELAY_MS)
+                .min(MAX_ITEMS_DELAY_MS);
+            tokio::time::sleep(Duration::from_millis(delay)).await;
+            let items: Vec<_> = ["Anchor", "Bosun", "Compass", "Deck", "Ensign"]
+                .iter()
+                .enumerate()
+                .map(|(i, name)| serde_json::json!({ "id": i + 1, "name": name }))
+                .collect();
+            json(StatusCode::OK, &serde_json::Value::Array(items))
+        }
+        (&Method::GET, "/lazy") => html(StatusCode::OK, LAZY_HTML),
+        (&Method::GET, path) if path.starts_with("/images/") => {
+            match path["/images/".len()..]
+                .strip_suffix(".svg")
+                .and_then(|n| n.parse::<usize>().ok())
+            {
+                Some(n) => {
+                    let mut response = Response::new(Body::from(image_svg(n)));
+                    response
+                        .headers_mut()
+                        .insert(CONTENT_TYPE, HeaderValue::from_static("image/svg+xml"));
+                    response
+                }
+                None => not_found(),
+            }
+        }
+        (&Method::GET, "/login") => html(StatusCode::OK, &LOGIN_HTML.replace("{error}", "")),
+        (&Method::POST, "/login") => {
+            let body = hyper::body::to_bytes(request.into_body())
+                .await
+                .unwrap_or_default();
+            let form: BTreeMap<String, String> =
+                url::form_urlencoded::parse(&body).into_owned().collect();
+            let signed_in = form.get("user").map(String::as_str) == Some(FIXTURE_USER)
+                && form.get("password").map(String::as_str) == Some(FIXTURE_PASSWORD);
+            if signed_in {
+                let mut response = redirect("/account");
+                response.headers_mut().insert(
+                    SET_COOKIE,
+                    HeaderValue::from_str(&format!("{}; Path=/; HttpOnly", SESSION_COOKIE))
+                        .expect("cookie is a valid header"),
+                );
+                response
+            } else {
+                html(
+                    StatusCode::UNAUTHORIZED,
+                    &LOGIN_HTML.replace(
+                        "{error}",
+                        r#"<p class="error" id="error">Wrong user or password</p>"#,
+                    ),
+                )
+            }
+        }
+        (&Method::GET, "/account") => {
+            let signed_in = request
+                .headers()
+                .get_all(COOKIE)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .flat_map(|value| value.split(';'))
+                .any(|cookie| cookie.trim() == SESSION_COOKIE);
+            if signed_in {
+                html(StatusCode::OK, ACCOUNT_HTML)
+            } else {
+                redirect("/login")
+            }
+        }
+        (&Method::GET, "/headers") => {
+            let headers: BTreeMap<&str, &str> = request
+                .headers()
+                .iter()
+                .filter_map(|(name, value)| Some((name.as_str(), value.to_str().ok()?)))
+                .collect();
+            json(StatusCode::OK, &headers)
+        }
+        _ => not_found(),
+    }
+}
+
+/// A solid image with its number in the middle
+fn image_svg(n: usize) -> String {
+    let color = IMAGE_COLORS[n % IMAGE_COLORS.len()];
+    format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="600" height="400" viewBox="0 0 600 400"><rect width="600" height="400" fill="{}"/><text x="300" y="230" font-family="sans-serif" font-size="120" fill="white" text-anchor="middle">{}</text></svg>"#,
+        color, n
+    )
+}
+
+fn query_param(request: &Request<Body>, name: &str) -> Option<String> {
+    url::form_urlencoded::parse(request.uri().query()?.as_bytes())
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.into_owned())
+}
+
+fn html(status: StatusCode, body: &str) -> Response<Body> {
+    let mut response = Response::new(Body::from(body.to_string()));
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static("text/html; charset=utf-8"),
+    );
+    response
+}
+
+fn json<T: serde::Serialize>(status: StatusCode, body: &T) -> Response<Body> {
+    let mut response = Response::new(Body::from(
+        serde_json::to_vec(body).expect("fixture responses serialize"),
+    ));
+    *response.status_mut() = status;
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    response
+}
+
+fn redirect(location: &'static str) -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = StatusCode::SEE_OTHER;
+    response
+        .headers_mut()
+        .insert(LOCATION, HeaderValue::from_static(location));
+    response
+}
+
+fn not_found() -> Response<Body> {
+    let mut response = Response::new(Body::from("Not found"));
+    *response.status_mut() = StatusCode::NOT_FOUND;
+    response
+}
+
+/// Serve the fixtures on `addr` until SIGTERM or Ctrl-C
+pub async fn serve(addr: SocketAddr) -> Result<()> {
+    let make_service = make_service_fn(|_connection| async {
+        Ok::<_, Infallible>(service_fn(|request| async {
+            Ok::<_, Infallible>(handle(request).await)
+        }))
+    });
+
+    let server = Server::try_bind(&addr)
+        .map_err(|e| WebshotError::config(format!("Cannot listen on {}: {}", addr, e)))?
+        .serve(make_service);
+    info!("Serving fixtures on http://{}", server.local_addr());
+
+    server
+        .with_graceful_shutdown(crate::health::shutdown_signal())
+        .await
+        .map_err(|e| WebshotError::Io(std::io::Error::other(e)))
+}
+
+/// The fixtures served from a background thread, stopped when dropped
+#[derive(Debug)]
+pub struct DevServer {
+    addr: SocketAddr,
+    shutdown: Option<oneshot::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl DevServer {
+    /// Serve the fixtures on a free localhost port
+    pub fn start() -> Result<Self> {
+        Self::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+    }
+
+    /// Serve the fixtures on `addr`
+    pub fn bind(addr: SocketAddr) -> Result<Self> {
+        let listener = TcpListener::bind(addr)
+            .map_err(|e| WebshotError::config(format!("Cannot listen on {}: {}", addr, e)))?;
+        listener.set_nonblocking(true)?;
+        let addr = listener.local_addr()?;
+        let (shutdown, stopped) = oneshot::channel::<()>();
+
+        // The server gets its own runtime so it keeps answering while the
+        // caller blocks, e.g. on a `webshot` child process
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let thread = std::thread::spawn(move || {
+            runtime.block_on(async move {
+                let make_service = make_service_fn(|_connection| async {
+                    Ok::<_, Infallible>(service_fn(|request| async {
+                        Ok::<_, Infallible>(handle(request).await)
+                    }))
+                });
+                let server = match Server::from_tcp(listener) {
+                    Ok(builder) => builder.serve(make_service),
+                    Err(e) => {
+                        warn!("Fixture server failed to start: {}", e);
+                        return;
+                    }
+                };
+                let stopped = async {
+                    let _ = stopped.await;
+                };
+                if let Err(e) = server.with_graceful_shutdown(stopped).await {
+                    warn!("Fixture server failed: {}", e);
+                }
+            })
+        });
+
+        Ok(Self {
+            addr,
+            shutdown: Some(shutdown),
+            thread: Some(thread),
+        })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Absolute URL of a fixture path such as `/static`
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{}", self.addr, path)
+    }
+}
+
+impl Drop for DevServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn get(path: &str, cookie: Option<&str>) -> Response<Body> {
+        let mut request = Request::get(path);
+        if let Some(cookie) = cookie {
+            request = request.header(COOKIE, cookie);
+        }
+        handle(request.body(Body::empty()).unwrap()).await
+    }
+
+    async fn body_text(response: Response<Body>) -> String {
+        let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_login_flow() {
+        let account = get("/account", None).await;
+        assert_eq!(account.status(), StatusCode::SEE_OTHER);
+        assert_eq!(account.headers()[LOCATION], "/login");
+
+        let login = |body: &'static str| {
+            handle(
+                Request::post("/login")
+                    .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+                    .body(Body::from(body))
+                    .unwrap(),
+            )
+        };
+        let rejected = login("user=admin&password=wrong").await;
+        assert_eq!(rejected.status(), StatusCode::UNAUTHORIZED);
+        assert!(body_text(rejected).await.contains("Wrong user or password"));
+
+        let accepted = login("user=admin&password=secret").await;
+        assert_eq!(accepted.status(), StatusCode::SEE_OTHER);
+        let cookie = accepted.headers()[SET_COOKIE].to_str().unwrap();
+        let cookie = cookie.split(';').next().unwrap();
+
+        let account = get("/account", Some(&format!("theme=dark; {}", cookie))).await;
+        assert_eq!(account.status(), StatusCode::OK);
+        assert!(body_text(account).await.contains("Welcome, admin"));
+    }
+
+    #[tokio::test]
+    async fn test_fixture_routes() {
+        let login = body_text(get("/login", None).await).await;
+        assert!(!login.contains("{error}"));
+
+        let items = get("/api/items?delay=0", None).await;
+        let items: serde_json::Value = serde_json::from_str(&body_text(items).await).unwrap();
+        assert_eq!(items[0]["name"], "Anchor");
+
+        let image = get("/images/3.svg", None).await;
+        assert_eq!(image.headers()[CONTENT_TYPE], "image/svg+xml");
+        assert_eq!(body_text(image).await, image_svg(3));
+
+        assert_eq!(
+            get("/images/three.svg", None).await.status(),
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(get("/missing", None).await.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_dev_server_serves_until_dropped() {
+        let server = DevServer::start().unwrap();
+        let url = server.url("/headers");
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let headers: serde_json::Value = runtime.block_on(async {
+            reqwest::Client::new()
+                .get(&url)
+                .header("X-Fixture", "yes")
+                .send()
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap()
+        });
+        assert_eq!(headers["x-fixture"], "yes");
+
+        drop(server);
+        assert!(runtime
+            .block_on(reqwest::Client::new().get(&url).send())
+            .is_err());
+    }
+}
@@ -0,0 +1,178 @@
+use crate::error::{Result, WebshotError};
+use std::str::FromStr;
+
+/// Emulation settings for a known device
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DevicePreset {
+    /// Display name, matched case-insensitively
+    pub name: &'static str,
+    /// Viewport width in CSS pixels
+    pub width: u32,
+    /// Viewport height in CSS pixels
+    pub height: u32,
+    /// Device pixel ratio
+    pub device_scale_factor: f64,
+    /// User agent reported by the device's default browser
+    pub user_agent: &'static str,
+    /// Whether the device uses mobile viewport emulation
+    pub mobile: bool,
+    /// Whether the device has a touch screen
+    pub touch: bool,
+}
+
+const IPHONE_UA: &str = "Mozilla/5.0 (iPhone; CPU iPhone OS 16_0 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/16.0 Mobile/15E148 Safari/604.1";
+const IPAD_UA: &str = "Mozilla/5.0 (iPad; CPU OS 16_0 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/16.0 Mobile/15E148 Safari/604.1";
+const PIXEL_UA: &str = "Mozilla/5.0 (Linux; Android 13; Pixel 7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/116.0.0.0 Mobile Safari/537.36";
+const GALAXY_UA: &str = "Mozilla/5.0 (Linux; Android 13; SM-S911B) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/116.0.0.0 Mobile Safari/537.36";
+
+/// Built-in device registry
+pub const DEVICES: &[DevicePreset] = &[
+    DevicePreset {
+        name: "iPhone SE",
+        width: 375,
+        height: 667,
+        device_scale_factor: 2.0,
+        user_agent: IPHONE_UA,
+        mobile: true,
+        touch: true,
+    },
+    DevicePreset {
+        name: "iPhone 14",
+        width: 390,
+        height: 844,
+        device_scale_factor: 3.0,
+        user_agent: IPHONE_UA,
+        mobile: true,
+        touch: true,
+    },
+    DevicePreset {
+        name: "iPhone 14 Pro",
+        width: 393,
+        height: 852,
+        device_scale_factor: 3.0,
+        user_agent: IPHONE_UA,
+        mobile: true,
+        touch: true,
+    },
+    DevicePreset {
+        name: "iPhone 14 Pro Max",
+        width: 430,
+        height: 932,
+        device_scale_factor: 3.0,
+        user_agent: IPHONE_UA,
+        mobile: true,
+        touch: true,
+    },
+    DevicePreset {
+        name: "Pixel 7",
+        width: 412,
+        height: 915,
+        device_scale_factor: 2.625,
+        user_agent: PIXEL_UA,
+        mobile: true,
+        touch: true,
+    },
+    DevicePreset {
+        name: "Galaxy S23",
+        width: 360,
+        height: 780,
+        device_scale_factor: 3.0,
+        user_agent: GALAXY_UA,
+        mobile: true,
+        touch: true,
+    },
+    DevicePreset {
+        name: "iPad Mini",
+        width: 768,
+        height: 1024,
+        device_scale_factor: 2.0,
+        user_agent: IPAD_UA,
+        mobile: true,
+        touch: true,
+    },
+    DevicePreset {
+        name: "iPad Air",
+        width: 820,
+        height: 1180,
+        device_scale_factor: 2.0,
+        user_agent: IPAD_UA,
+        mobile: true,
+        touch: true,
+    },
+    DevicePreset {
+        name: "iPad Pro 12.9",
+        width: 1024,
+        height: 1366,
+        device_scale_factor: 2.0,
+        user_agent: IPAD_UA,
+        mobile: true,
+        touch: true,
+    },
+];
+
+impl DevicePreset {
+    /// Look up a device preset by name (case-insensitive)
+    pub fn find(name: &str) -> Option<Self> {
+        let name = name.trim();
+        DEVICES
+            .iter()
+            .find(|device| device.name.eq_ignore_ascii_case(name))
+            .copied()
+    }
+
+    /// Names of all built-in devices
+    pub fn names() -> Vec<&'static str> {
+        DEVICES.iter().map(|device| device.name).collect()
+    }
+}
+
+impl FromStr for DevicePreset {
+    type Err = WebshotError;
+
+    fn from_str(name: &str) -> Result<Self> {
+        Self::find(name).ok_or_else(|| {
+            WebshotError::config(format!(
+                "Unknown device: {}. Supported devices: {}",
+                name,
+                Self::names().join(", ")
+            ))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_device_is_case_insensitive() {
+        let device = DevicePreset::find("iphone 14 pro").unwrap();
+        assert_eq!(device.name, "iPhone 14 Pro");
+        assert_eq!((device.width, device.height), (393, 852));
+        assert!(device.mobile);
+        assert!(device.touch);
+    }
+
+    #[test]
+    fn test_unknown_device_lists_supported_names() {
+        let error = "Nokia 3310".parse::<DevicePreset>().unwrap_err();
+        let message = error.to_string();
+
+        assert!(message.contains("Unknown device: Nokia 3310"));
+        assert!(message.contains("iPhone 14 Pro"));
+        assert!(message.contains("Pixel 7"));
+    }
+
+    #[test]
+    fn test_device_names_are_unique() {
+        let mut names: Vec<String> = DevicePreset::names()
+            .iter()
+            .map(|name| name.to_lowercase())
+            .collect();
+        let total = names.len();
+        names.sort();
+        names.dedup();
+
+        assert_eq!(names.len(), total);
+    }
+}
@@ -0,0 +1,184 @@
+//! Interactive HTML report of an image comparison, for `compare --html-report`.
+//!
+//! A diff image alone makes subtle changes hard to judge. The report shows
+//! the baseline and actual image three ways: side by side, as an onion skin
+//! with a slider fading from one to the other, and with the differing
+//! pixels overlaid on the actual image. It is a single HTML file with the
+//! images embedded, so it can be attached to a CI run or sent around as is.
+
+use crate::book::escape_html;
+use crate::comparison::{ComparisonOptions, ComparisonResult, ImageComparator};
+use crate::error::Result;
+use crate::output::OutputHandler;
+use base64::Engine as _;
+use image::DynamicImage;
+use std::io::Cursor;
+use std::path::Path;
+
+const STYLE: &str = "\
+body { font-family: system-ui, sans-serif; margin: 2rem; color: #222; }
+.verdict { font-weight: 700; } .similar { color: #2a7; } .different { color: #d33; }
+nav button { font: inherit; padding: 0.4rem 0.9rem; border: 1px solid #bbb; background: #f6f6f6; cursor: pointer; }
+nav button.active { background: #222; color: #fff; border-color: #222; }
+section { display: none; margin-top: 1rem; } section.active { display: block; }
+.side-by-side { display: flex; gap: 1rem; align-items: flex-start; }
+.side-by-side figure { flex: 1; margin: 0; }
+figcaption, label { font-size: 0.85rem; color: #555; }
+img { max-width: 100%; display: block; border: 1px solid #ddd; }
+.stack { position: relative; display: inline-block; max-width: 100%; }
+.stack img + img { position: absolute; top: 0; left: 0; }
+.controls { margin: 0.6rem 0; }
+";
+
+const SCRIPT: &str = "\
+document.querySelectorAll('nav button').forEach((button) => {
+  button.addEventListener('click', () => {
+    document.querySelectorAll('nav button, section').forEach((el) => el.classList.remove('active'));
+    button.classList.add('active');
+    document.getElementById(button.dataset.view).classList.add('active');
+  });
+});
+const fade = document.getElementById('fade');
+fade.addEventListener('input', () => {
+  document.getElementById('onion-actual').style.opacity = fade.value / 100;
+});
+const dim = document.getElementById('dim');
+dim.addEventListener('input', () => {
+  document.getElementById('overlay-actual').style.opacity = dim.value / 100;
+});
+document.getElementById('show-diff').addEventListener('change', (event) => {
+  document.getElementById('overlay-diff').style.display = event.target.checked ? '' : 'none';
+});
+";
+
+/// Write an HTML report of `result`, the comparison of `baseline` with
+/// `actual` under `options`, to `output`
+pub fn write_diff_report(
+    baseline: &DynamicImage,
+    actual: &DynamicImage,
+    result: &ComparisonResult,
+    options: &ComparisonOptions,
+    labels: (&str, &str),
+    output: &Path,
+) -> Result<()> {
+    let mask = ImageComparator::diff_mask(&baseline.to_rgb8(), &actual.to_rgb8(), options);
+    let html = render(
+        &png_data_url(baseline)?,
+        &png_data_url(actual)?,
+        &png_data_url(&DynamicImage::ImageRgba8(mask))?,
+        result,
+        labels,
+    );
+    OutputHandler::ensure_output_dir(output)?;
+    std::fs::write(output, html)?;
+    Ok(())
+}
+
+fn png_data_url(image: &DynamicImage) -> Result<String> {
+    let mut png = Vec::new();
+    image.write_to(&mut Cursor::new(&mut png), image::ImageOutputFormat::Png)?;
+    Ok(format!(
+        "data:image/png;base64,{}",
+        base64::engine::general_purpose::STANDARD.encode(png)
+    ))
+}
+
+fn render(
+    baseline: &str,
+    actual: &str,
+    diff: &str,
+    result: &ComparisonResult,
+    (baseline_label, actual_label): (&str, &str),
+) -> String {
+    let (verdict, class) = if result.similar {
+        ("Similar", "similar")
+    } else {
+        ("Different", "different")
+    };
+    let mut summary = format!(
+        "<span class=\"verdict {class}\">{verdict}</span>: similarity {:.4} \
+         ({}, threshold {})",
+        result.similarity,
+        escape_html(&format!("{:?}", result.algorithm)),
+        result.threshold
+    );
+    if let Some(different) = result.different_pixels {
+        summary.push_str(&format!(
+            ", {} of {} pixels differ ({:.2}%)",
+            different,
+            result.total_pixels,
+            different as f64 / result.total_pixels.max(1) as f64 * 100.0
+        ));
+    }
+    if let Some(version) = &result.baseline_version {
+        summary.push_str(&format!(", baseline version {}", escape_html(version)));
+    }
+    let baseline_label = escape_html(baseline_label);
+    let actual_label = escape_html(actual_label);
+
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>Comparison: {baseline_label} vs {actual_label}</title>\n<style>\n{STYLE}</style>\n\
+         </head>\n<body>\n<h1>{baseline_label} vs {actual_label}</h1>\n<p>{summary}</p>\n\
+         <nav><button class=\"active\" data-view=\"side-by-side\">Side by side</button>\
+         <button data-view=\"onion-skin\">Onion skin</button>\
+         <button data-view=\"overlay\">Diff overlay</button></nav>\n\
+         <section id=\"side-by-side\" class=\"active\"><div class=\"side-by-side\">\
+         <figure><figcaption>Baseline: {baseline_label}</figcaption><img src=\"{baseline}\" alt=\"Baseline\"></figure>\
+         <figure><figcaption>Actual: {actual_label}</figcaption><img src=\"{actual}\" alt=\"Actual\"></figure>\
+         </div></section>\n\
+         <section id=\"onion-skin\"><div class=\"controls\"><label>Baseline \
+         <input id=\"fade\" type=\"range\" min=\"0\" max=\"100\" value=\"50\"> Actual</label></div>\
+         <div class=\"stack\"><img src=\"{baseline}\" alt=\"Baseline\">\
+         <img id=\"onion-actual\" src=\"{actual}\" alt=\"Actual\" style=\"opacity: 0.5\"></div></section>\n\
+         <section id=\"overlay\"><div class=\"controls\"><label><input id=\"show-diff\" type=\"checkbox\" checked> \
+         Show differences</label> <label>Actual image opacity \
+         <input id=\"dim\" type=\"range\" min=\"0\" max=\"100\" value=\"40\"></label></div>\
+         <div class=\"stack\"><img id=\"overlay-actual\" src=\"{actual}\" alt=\"Actual\" style=\"opacity: 0.4\">\
+         <img id=\"overlay-diff\" src=\"{diff}\" alt=\"Differences\"></div></section>\n\
+         <script>\n{SCRIPT}</script>\n</body>\n</html>\n"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    #[test]
+    fn test_report_embeds_all_views() {
+        let baseline = RgbImage::from_pixel(8, 8, Rgb([255, 255, 255]));
+        let mut actual = baseline.clone();
+        actual.put_pixel(3, 4, Rgb([0, 0, 0]));
+        let (baseline, actual) = (
+            DynamicImage::ImageRgb8(baseline),
+            DynamicImage::ImageRgb8(actual),
+        );
+        let options = ComparisonOptions::new().threshold(0.0);
+        let result = ImageComparator::compare_images(&baseline, &actual, &options).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("reports/compare.html");
+        write_diff_report(
+            &baseline,
+            &actual,
+            &result,
+            &options,
+            ("home <old>.png", "home.png"),
+            &output,
+        )
+        .unwrap();
+        let html = std::fs::read_to_string(&output).unwrap();
+        assert!(html.contains("<span class=\"verdict different\">Different</span>"));
+        assert!(html.contains("1 of 64 pixels differ (1.56%)"));
+        assert!(html.contains("Baseline: home &lt;old&gt;.png"));
+        assert_eq!(html.matches("data:image/png;base64,").count(), 6);
+        for view in ["side-by-side", "onion-skin", "overlay"] {
+            assert!(html.contains(&format!("<section id=\"{}\"", view)));
+        }
+
+        let mask = ImageComparator::diff_mask(&baseline.to_rgb8(), &actual.to_rgb8(), &options);
+        assert_eq!(mask.get_pixel(3, 4).0, [255, 0, 0, 255]);
+        assert_eq!(mask.get_pixel(0, 0).0[3], 0);
+    }
+}
@@ -0,0 +1,462 @@
+//! Comparison of two directories of images, for `compare --dir A --dir B`.
+//!
+//! Images are matched by their path relative to each directory, so
+//! `baseline/home/desktop.png` is compared with `current/home/desktop.png`.
+//! Pairs are compared in parallel on rayon's thread pool, which
+//! `RAYON_NUM_THREADS` sizes. Images only in the baseline directory are
+//! reported as missing and fail the comparison; images only in the current
+//! directory are listed as added.
+//!
+//! The [`DirComparison`] has the counts, the result of every pair ordered
+//! worst first and can be written as JSON or as an HTML table linking the
+//! images and difference images of each pair.
+
+use crate::book::escape_html;
+use crate::comparison::{ComparisonOptions, ComparisonResult, ImageComparator};
+use crate::error::{Result, WebshotError};
+use crate::gallery::relative_path;
+use crate::output::OutputHandler;
+use path_absolutize::Absolutize;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+/// Differing pairs listed in the text summary
+pub const WORST_OFFENDERS: usize = 10;
+
+const STYLE: &str = "\
+body { font-family: system-ui, sans-serif; margin: 2rem; color: #222; }
+header p { color: #555; }
+table { border-collapse: collapse; width: 100%; font-size: 0.9rem; }
+th, td { text-align: left; padding: 0.4rem 0.6rem; border-bottom: 1px solid #ddd; }
+td.number { text-align: right; font-variant-numeric: tabular-nums; }
+.status { text-transform: uppercase; font-size: 0.75rem; font-weight: 700; }
+.similar { color: #2a7; } .different, .error { color: #d33; }
+.message { color: #d33; white-space: pre-wrap; }
+";
+
+/// Comparison of an image found in both directories
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileComparison {
+    /// Path relative to both directories, with `/` separators
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<ComparisonResult>,
+    /// Why the images could not be compared, such as differing sizes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl FileComparison {
+    /// Whether the images compared as similar
+    pub fn passed(&self) -> bool {
+        self.result.as_ref().is_some_and(|result| result.similar)
+    }
+
+    /// Similarity score, 0 when the images could not be compared
+    pub fn similarity(&self) -> f64 {
+        self.result.as_ref().map_or(0.0, |result| result.similarity)
+    }
+
+    pub fn status(&self) -> &'static str {
+        match &self.result {
+            Some(result) if result.similar => "similar",
+            Some(_) => "different",
+            None => "error",
+        }
+    }
+}
+
+/// Comparison of every image of a baseline directory with the image of the
+/// same name in a current directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DirComparison {
+    pub baseline: PathBuf,
+    pub current: PathBuf,
+    /// Image pairs compared
+    pub compared: usize,
+    pub similar: usize,
+    pub different: usize,
+    /// Pairs that could not be compared
+    pub errors: usize,
+    /// Images only in the baseline directory
+    pub missing: Vec<String>,
+    /// Images only in the current directory
+    pub added: Vec<String>,
+    /// Every pair, worst first: pairs that could not be compared, then by
+    /// similarity
+    pub files: Vec<FileComparison>,
+}
+
+impl DirComparison {
+    /// Whether every baseline image has a similar counterpart
+    pub fn passed(&self) -> bool {
+        self.different == 0 && self.errors == 0 && self.missing.is_empty()
+    }
+
+    /// Up to `count` pairs that differ or could not be compared, worst first
+    pub fn worst(&self, count: usize) -> impl Iterator<Item = &FileComparison> {
+        self.files.iter().filter(|file| !file.passed()).take(count)
+    }
+
+    /// Write the comparison as JSON
+    pub fn write_json<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        OutputHandler::ensure_output_dir(&path)?;
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Write the comparison as an HTML page with a row per pair, linking
+    /// the images relative to the page
+    pub fn write_html<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        OutputHandler::ensure_output_dir(path)?;
+        let report_dir = path
+            .absolutize()?
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        let link = |target: &Path, label: &str| -> Result<String> {
+            let target = target.absolutize()?.to_path_buf();
+            Ok(format!(
+                "<a href=\"{}\">{}</a>",
+                escape_html(&relative_path(&report_dir, &target)),
+                label
+            ))
+        };
+
+        let mut rows = String::new();
+        for file in &self.files {
+            let mut links = vec![
+                link(&self.baseline.join(&file.name), "baseline")?,
+                link(&self.current.join(&file.name), "current")?,
+            ];
+            let diff = file
+                .result
+                .as_ref()
+                .and_then(|result| result.diff_image_path.as_deref());
+            if let Some(diff) = diff {
+                links.push(link(diff, "diff")?);
+            }
+            let (similarity, pixels) = match &file.result {
+                Some(result) => (
+                    format!("{:.2}%", result.similarity * 100.0),
+                    result
+                        .different_pixels
+                        .map(|pixels| pixels.to_string())
+                        .unwrap_or_default(),
+                ),
+                None => (String::new(), String::new()),
+            };
+            let error = file
+                .error
+                .as_deref()
+                .map(|error| format!("<div class=\"message\">{}</div>", escape_html(error)))
+                .unwrap_or_default();
+            rows.push_str(&format!(
+                "<tr class=\"{status}\"><td>{name}{error}</td><td class=\"status\">{status}</td>\
+                 <td class=\"number\">{similarity}</td><td class=\"number\">{pixels}</td>\
+                 <td>{links}</td></tr>\n",
+                status = file.status(),
+                name = escape_html(&file.name),
+                links = links.join(" · "),
+            ));
+        }
+
+        let list = |title: &str, names: &[String]| {
+            if names.is_empty() {
+                return String::new();
+            }
+            let items: String = names
+                .iter()
+                .map(|name| format!("<li>{}</li>", escape_html(name)))
+                .collect();
+            format!("<h2>{}</h2>\n<ul>{}</ul>\n", title, items)
+        };
+        let title = format!(
+            "webshot comparison: {} vs {}",
+            self.baseline.display(),
+            self.current.display()
+        );
+        let html = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+             <style>\n{STYLE}</style>\n</head>\n<body>\n<header>\n<h1>{title}</h1>\n<p>{summary}</p>\n\
+             </header>\n<table>\n<tr><th>File</th><th>Status</th><th>Similarity</th>\
+             <th>Differing pixels</th><th>Images</th></tr>\n{rows}</table>\n{missing}{added}\
+             </body>\n</html>\n",
+            title = escape_html(&title),
+            summary = escape_html(&self.summary()),
+            missing = list("Missing from current", &self.missing),
+            added = list("Added in current", &self.added),
+        );
+        std::fs::write(path, html)?;
+        Ok(())
+    }
+
+    /// One line with the counts
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "Compared {} image pairs: {} similar, {} different, {} failed",
+            self.compared, self.similar, self.different, self.errors
+        );
+        if !self.missing.is_empty() {
+            summary.push_str(&format!(", {} missing", self.missing.len()));
+        }
+        if !self.added.is_empty() {
+            summary.push_str(&format!(", {} added", self.added.len()));
+        }
+        summary
+    }
+}
+
+impl fmt::Display for DirComparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.summary())?;
+        if !self.missing.is_empty() {
+            writeln!(f, "Missing from current: {}", self.missing.join(", "))?;
+        }
+        if !self.added.is_empty() {
+            writeln!(f, "Added in current: {}", self.added.join(", "))?;
+        }
+        let worst: Vec<_> = self.worst(WORST_OFFENDERS).collect();
+        if !worst.is_empty() {
+            writeln!(f, "Worst offenders:")?;
+        }
+        for file in worst {
+            match (&file.result, &file.error) {
+                (Some(result), _) => {
+                    write!(
+                        f,
+                        "  {}: {:.2}% similar",
+                        file.name,
+                        result.similarity * 100.0
+                    )?;
+                    if let Some(pixels) = result.different_pixels {
+                        write!(f, ", {} pixels differ", pixels)?;
+                    }
+                    writeln!(f)?;
+                }
+                (None, error) => writeln!(
+                    f,
+                    "  {}: {}",
+                    file.name,
+                    error.as_deref().unwrap_or("not compared")
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Compare every image in `baseline` with the image of the same relative
+/// path in `current`. With a `diff_dir`, a difference image is written
+/// there, under the same relative path, for each pair that differs.
+pub fn compare_dirs(
+    baseline: &Path,
+    current: &Path,
+    options: &ComparisonOptions,
+    diff_dir: Option<&Path>,
+) -> Result<DirComparison> {
+    let baseline_images = image_files(baseline)?;
+    let current_images = image_files(current)?;
+    let names: Vec<&String> = baseline_images.intersection(&current_images).collect();
+    info!(
+        "Comparing {} image pairs in {} and {}",
+        names.len(),
+        baseline.display(),
+        current.display()
+    );
+
+    let mut files: Vec<FileComparison> = names
+        .par_iter()
+        .map(|name| {
+            let mut options = options.clone();
+            if let Some(dir) = diff_dir {
+                options = options.generate_diff_image(dir.join(name.as_str()));
+            }
+            let compared = ImageComparator::compare_files(
+                baseline.join(name.as_str()),
+                current.join(name.as_str()),
+                &options,
+            )
+            .and_then(|mut result| {
+                // Only differing pairs keep their difference image
+                if result.similar {
+                    if let Some(diff) = result.diff_image_path.take() {
+                        std::fs::remove_file(diff)?;
+                    }
+                }
+                Ok(result)
+            });
+            match compared {
+                Ok(result) => FileComparison {
+                    name: name.to_string(),
+                    result: Some(result),
+                    error: None,
+                },
+                Err(e) => FileComparison {
+                    name: name.to_string(),
+                    result: None,
+                    error: Some(e.to_string()),
+                },
+            }
+        })
+        .collect();
+    files.sort_by(|a, b| {
+        a.result
+            .is_some()
+            .cmp(&b.result.is_some())
+            .then(a.similarity().total_cmp(&b.similarity()))
+            .then_with(|| a.name.cmp(&b.name))
+    });
+
+    let similar = files.iter().filter(|file| file.passed()).count();
+    let errors = files.iter().filter(|file| file.result.is_none()).count();
+    Ok(DirComparison {
+        baseline: baseline.to_path_buf(),
+        current: current.to_path_buf(),
+        compared: files.len(),
+        similar,
+        different: files.len() - similar - errors,
+        errors,
+        missing: baseline_images
+            .difference(&current_images)
+            .cloned()
+            .collect(),
+        added: current_images
+            .difference(&baseline_images)
+            .cloned()
+            .collect(),
+        files,
+    })
+}
+
+/// Paths of the images under `dir` relative to it, with `/` separators
+fn image_files(dir: &Path) -> Result<BTreeSet<String>> {
+    if !dir.is_dir() {
+        return Err(WebshotError::InvalidPath {
+            path: dir.to_path_buf(),
+        });
+    }
+    let mut images = BTreeSet::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(next) = pending.pop() {
+        for entry in std::fs::read_dir(&next)? {
+            let entry = entry?;
+            let path = entry.path();
+            if entry.file_type()?.is_dir() {
+                pending.push(path);
+            } else if image::ImageFormat::from_path(&path).is_ok() {
+                let relative = path.strip_prefix(dir).unwrap_or(&path);
+                let parts: Vec<_> = relative
+                    .components()
+                    .map(|part| part.as_os_str().to_string_lossy())
+                    .collect();
+                images.insert(parts.join("/"));
+            }
+        }
+    }
+    Ok(images)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+    use tempfile::TempDir;
+
+    fn save(dir: &Path, name: &str, image: &RgbImage) {
+        let path = dir.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        image.save(path).unwrap();
+    }
+
+    #[test]
+    fn test_compare_dirs_matches_files_by_name() {
+        let root = TempDir::new().unwrap();
+        let (baseline, current) = (root.path().join("baseline"), root.path().join("current"));
+        let plain = RgbImage::from_pixel(20, 20, Rgb([200, 200, 200]));
+        let mut changed = plain.clone();
+        for x in 0..10 {
+            changed.put_pixel(x, 5, Rgb([255, 0, 0]));
+        }
+        save(&baseline, "home.png", &plain);
+        save(&current, "home.png", &plain);
+        save(&baseline, "mobile/pricing.png", &plain);
+        save(&current, "mobile/pricing.png", &changed);
+        save(&baseline, "wide.png", &plain);
+        save(&current, "wide.png", &RgbImage::new(30, 20));
+        save(&baseline, "about.png", &plain);
+        save(&current, "contact.png", &plain);
+        std::fs::write(current.join("notes.txt"), "not an image").unwrap();
+
+        let diffs = root.path().join("diffs");
+        let options = ComparisonOptions::new().threshold(0.0);
+        let comparison = compare_dirs(&baseline, &current, &options, Some(&diffs)).unwrap();
+        assert_eq!(
+            (
+                comparison.compared,
+                comparison.similar,
+                comparison.different,
+                comparison.errors
+            ),
+            (3, 1, 1, 1)
+        );
+        assert_eq!(comparison.missing, vec!["about.png"]);
+        assert_eq!(comparison.added, vec!["contact.png"]);
+        assert!(!comparison.passed());
+
+        // Pairs that could not be compared come first, then the least similar
+        let names: Vec<_> = comparison
+            .files
+            .iter()
+            .map(|file| file.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["wide.png", "mobile/pricing.png", "home.png"]);
+        assert_eq!(
+            comparison.files[1]
+                .result
+                .as_ref()
+                .unwrap()
+                .different_pixels,
+            Some(10)
+        );
+        assert_eq!(comparison.worst(1).count(), 1);
+
+        // Only the differing pair keeps a difference image
+        assert!(diffs.join("mobile/pricing.png").is_file());
+        assert!(!diffs.join("home.png").exists());
+
+        let text = comparison.to_string();
+        assert!(text.contains("Compared 3 image pairs: 1 similar, 1 different, 1 failed"));
+        assert!(text.contains("mobile/pricing.png: 97.50% similar, 10 pixels differ"));
+
+        let json = root.path().join("report/comparison.json");
+        comparison.write_json(&json).unwrap();
+        let read: DirComparison =
+            serde_json::from_str(&std::fs::read_to_string(&json).unwrap()).unwrap();
+        assert_eq!(read.files.len(), 3);
+
+        let html = root.path().join("report/index.html");
+        comparison.write_html(&html).unwrap();
+        let html = std::fs::read_to_string(html).unwrap();
+        assert!(html.contains("href=\"../baseline/mobile/pricing.png\""));
+        assert!(html.contains("href=\"../diffs/mobile/pricing.png\""));
+        assert!(html.contains("<li>about.png</li>"));
+    }
+
+    #[test]
+    fn test_compare_dirs_requires_directories() {
+        let root = TempDir::new().unwrap();
+        let missing = root.path().join("missing");
+        let options = ComparisonOptions::new();
+        assert!(compare_dirs(root.path(), &missing, &options, None).is_err());
+
+        let comparison = compare_dirs(root.path(), root.path(), &options, None).unwrap();
+        assert_eq!(comparison.compared, 0);
+        assert!(comparison.passed());
+    }
+}
@@ -0,0 +1,355 @@
+//! Sets of element screenshots taken from one page load.
+//!
+//! A batch entry's `elements:` list names elements of its page to capture
+//! on their own, each cropped to the element's box plus an optional
+//! `padding` and optionally in a forced CSS `state` such as `hover`. All of
+//! them are taken after the entry's own capture, without navigating again,
+//! and written next to its output as `<stem>-<name>.<ext>`. A
+//! `<stem>-elements.json` manifest lists every element with the region it
+//! was cropped to, or the error that kept it from being captured.
+
+use crate::error::{Result, WebshotError};
+use crate::screenshot::ClipRegion;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Evaluates to the document box of the first element `SELECTOR` matches
+/// and the document size as a JSON string, or `null` when nothing matches
+const ELEMENT_BOX_SCRIPT: &str = r#"
+(() => {
+    const element = document.querySelector(SELECTOR);
+    if (!element) {
+        return null;
+    }
+    const rect = element.getBoundingClientRect();
+    const root = document.documentElement;
+    return JSON.stringify({
+        x: rect.left + window.scrollX,
+        y: rect.top + window.scrollY,
+        width: rect.width,
+        height: rect.height,
+        document_width: Math.max(root.scrollWidth, root.clientWidth),
+        document_height: Math.max(root.scrollHeight, root.clientHeight),
+    });
+})()
+"#;
+
+/// CSS pseudo-class state forced on an element while it is captured
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ElementState {
+    Hover,
+    Focus,
+    FocusVisible,
+    FocusWithin,
+    Active,
+}
+
+impl ElementState {
+    /// Pseudo-class name for `CSS.forcePseudoState`
+    pub fn pseudo_class(self) -> &'static str {
+        match self {
+            ElementState::Hover => "hover",
+            ElementState::Focus => "focus",
+            ElementState::FocusVisible => "focus-visible",
+            ElementState::FocusWithin => "focus-within",
+            ElementState::Active => "active",
+        }
+    }
+}
+
+impl fmt::Display for ElementState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.pseudo_class())
+    }
+}
+
+/// One entry of an `elements:` list
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ElementCapture {
+    /// CSS selector of the element; the first match is captured
+    pub selector: String,
+    /// Output name suffix; `element-<n>` when omitted
+    pub name: Option<String>,
+    /// CSS pixels of page kept around the element on every side
+    #[serde(default)]
+    pub padding: u32,
+    /// Pseudo-class state forced on the element while it is captured
+    pub state: Option<ElementState>,
+}
+
+impl ElementCapture {
+    pub fn new(selector: impl Into<String>) -> Self {
+        Self {
+            selector: selector.into(),
+            name: None,
+            padding: 0,
+            state: None,
+        }
+    }
+
+    /// Name of the element at `index` (0-based) of its list
+    pub fn name(&self, index: usize) -> String {
+        self.name
+            .clone()
+            .unwrap_or_else(|| format!("element-{}", index + 1))
+    }
+}
+
+/// Check an `elements:` list of entry `index` writing to `output`
+pub fn validate_elements(elements: &[ElementCapture], output: &Path, index: usize) -> Result<()> {
+    if elements.is_empty() {
+        return Ok(());
+    }
+    let is_pdf = output
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("pdf"));
+    if is_pdf {
+        return Err(WebshotError::config(format!(
+            "Screenshot {} cannot capture elements into a PDF",
+            index
+        )));
+    }
+
+    let mut names = Vec::new();
+    for (position, element) in elements.iter().enumerate() {
+        if element.selector.trim().is_empty() {
+            return Err(WebshotError::config(format!(
+                "Screenshot {} has an element without a selector",
+                index
+            )));
+        }
+        let name = element.name(position);
+        let invalid = name.is_empty()
+            || name.starts_with('.')
+            || name.chars().any(|c| {
+                matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') || c.is_control()
+            });
+        if invalid {
+            return Err(WebshotError::config(format!(
+                "Screenshot {} has an invalid element name: {:?}",
+                index, name
+            )));
+        }
+        if names.contains(&name) {
+            return Err(WebshotError::config(format!(
+                "Screenshot {} names two elements {}",
+                index, name
+            )));
+        }
+        names.push(name);
+    }
+    Ok(())
+}
+
+/// Script returning the box of the first element `selector` matches
+pub fn element_box_script(selector: &str) -> Result<String> {
+    Ok(ELEMENT_BOX_SCRIPT.replace("SELECTOR", &serde_json::to_string(selector)?))
+}
+
+/// Result of [`element_box_script`], in CSS pixels
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct ElementBox {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    pub document_width: f64,
+    pub document_height: f64,
+}
+
+impl ElementBox {
+    /// The element's box grown by `padding` on every side and cut to the
+    /// document, or `None` when nothing of it is left
+    pub fn padded(&self, padding: u32) -> Option<ClipRegion> {
+        let padding = f64::from(padding);
+        let left = (self.x - padding).floor().max(0.0);
+        let top = (self.y - padding).floor().max(0.0);
+        let right = (self.x + self.width + padding)
+            .ceil()
+            .min(self.document_width);
+        let bottom = (self.y + self.height + padding)
+            .ceil()
+            .min(self.document_height);
+        if right <= left || bottom <= top || self.width <= 0.0 || self.height <= 0.0 {
+            return None;
+        }
+        Some(ClipRegion {
+            x: left as u32,
+            y: top as u32,
+            width: (right - left) as u32,
+            height: (bottom - top) as u32,
+        })
+    }
+}
+
+/// Output of the element `name` of an entry writing to `output`
+pub fn element_output(output: &Path, name: &str) -> PathBuf {
+    let stem = output
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let file_name = match output.extension() {
+        Some(ext) => format!("{}-{}.{}", stem, name, ext.to_string_lossy()),
+        None => format!("{}-{}", stem, name),
+    };
+    output.with_file_name(file_name)
+}
+
+/// Path of the element manifest of an entry writing to `output`
+pub fn element_manifest_path(output: &Path) -> PathBuf {
+    let stem = output
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    output.with_file_name(format!("{}-elements.json", stem))
+}
+
+/// One element of an [`ElementManifest`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ElementEntry {
+    pub name: String,
+    pub selector: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<ElementState>,
+    pub padding: u32,
+    /// Written image, unless the element could not be captured
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<PathBuf>,
+    /// Page region the image was cropped to, in CSS pixels
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clip: Option<ClipRegion>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ElementEntry {
+    /// Entry of `element`, the `index`th of its list, before it is captured
+    pub fn new(element: &ElementCapture, index: usize) -> Self {
+        Self {
+            name: element.name(index),
+            selector: element.selector.clone(),
+            state: element.state,
+            padding: element.padding,
+            output: None,
+            clip: None,
+            error: None,
+        }
+    }
+}
+
+/// The elements captured from one page
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ElementManifest {
+    pub url: String,
+    /// The entry's own capture the elements were taken with
+    pub page: PathBuf,
+    pub captured_at: String,
+    pub elements: Vec<ElementEntry>,
+}
+
+impl ElementManifest {
+    pub fn new(url: impl Into<String>, page: impl Into<PathBuf>) -> Self {
+        Self {
+            url: url.into(),
+            page: page.into(),
+            captured_at: chrono::Utc::now().to_rfc3339(),
+            elements: Vec::new(),
+        }
+    }
+
+    /// Number of elements that could not be captured
+    pub fn failed(&self) -> usize {
+        self.elements
+            .iter()
+            .filter(|element| element.error.is_some())
+            .count()
+    }
+
+    /// Write the manifest to `path` as pretty-printed JSON
+    pub fn write(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_elements_parse_and_validate() {
+        let elements: Vec<ElementCapture> = serde_yaml::from_str(
+            r#"
+- selector: header nav
+  name: nav
+  padding: 8
+- selector: .cta
+  state: hover
+- selector: input[type=email]
+  state: focus-visible
+"#,
+        )
+        .unwrap();
+        assert_eq!(elements[0].padding, 8);
+        assert_eq!(elements[1].state, Some(ElementState::Hover));
+        assert_eq!(elements[1].name(1), "element-2");
+        assert_eq!(elements[2].state.unwrap().pseudo_class(), "focus-visible");
+        let output = Path::new("shots/home.png");
+        validate_elements(&elements, output, 0).unwrap();
+
+        assert!(validate_elements(&elements, Path::new("home.pdf"), 0).is_err());
+        let mut duplicate = elements.clone();
+        duplicate[1].name = Some("nav".to_string());
+        assert!(validate_elements(&duplicate, output, 0).is_err());
+        let mut nested = elements.clone();
+        nested[0].name = Some("../nav".to_string());
+        assert!(validate_elements(&nested, output, 0).is_err());
+        assert!(validate_elements(&[ElementCapture::new(" ")], output, 0).is_err());
+
+        assert_eq!(
+            element_output(output, "nav"),
+            PathBuf::from("shots/home-nav.png")
+        );
+        assert_eq!(
+            element_manifest_path(output),
+            PathBuf::from("shots/home-elements.json")
+        );
+    }
+
+    #[test]
+    fn test_padding_is_cut_to_the_document() {
+        let element = ElementBox {
+            x: 10.5,
+            y: 300.0,
+            width: 200.0,
+            height: 40.25,
+            document_width: 1280.0,
+            document_height: 336.0,
+        };
+        assert_eq!(
+            element.padded(16),
+            Some(ClipRegion {
+                x: 0,
+                y: 284,
+                width: 227,
+                height: 52,
+            })
+        );
+        assert_eq!(
+            element.padded(0),
+            Some(ClipRegion {
+                x: 10,
+                y: 300,
+                width: 201,
+                height: 36,
+            })
+        );
+        let hidden = ElementBox {
+            width: 0.0,
+            ..element
+        };
+        assert_eq!(hidden.padded(8), None);
+    }
+}
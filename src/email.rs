@@ -0,0 +1,222 @@
+//! Screenshots of HTML email templates, for `webshot email`.
+//!
+//! Email is laid out for a handful of fixed widths rather than a browser
+//! window: about 600 pixels in desktop clients and the width of a phone in
+//! mobile ones. A template is captured full height at each requested
+//! [`EmailWidth`], once with `prefers-color-scheme: light` and once with
+//! `dark`, so the template's own dark mode styles can be reviewed next to
+//! the light ones. Outputs are named `<stem>-<width>-<scheme>.<ext>`.
+//!
+//! [`check_css`] looks through the template for markup and CSS that
+//! common clients drop or ignore, such as `<style>` blocks, external
+//! stylesheets, flexbox or CSS variables, as warnings worth a look before
+//! sending.
+
+use crate::error::{Result, WebshotError};
+use crate::screenshot::ColorScheme;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Named email widths, in CSS pixels
+pub const EMAIL_WIDTH_PRESETS: [(&str, u32); 3] =
+    [("desktop", 600), ("mobile", 375), ("small", 320)];
+
+/// Widths captured when none are given
+pub const DEFAULT_EMAIL_WIDTHS: &str = "desktop,mobile";
+
+/// Viewport width an email is rendered at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EmailWidth {
+    /// Preset the width was given as, if any
+    pub preset: Option<&'static str>,
+    pub width: u32,
+}
+
+impl FromStr for EmailWidth {
+    type Err = WebshotError;
+
+    /// Parse a preset name (`desktop`, `mobile`, `small`) or a width in
+    /// pixels such as `480` or `480px`
+    fn from_str(s: &str) -> Result<Self> {
+        let value = s.trim().to_lowercase();
+        if let Some(&(preset, width)) = EMAIL_WIDTH_PRESETS
+            .iter()
+            .find(|(preset, _)| *preset == value)
+        {
+            return Ok(Self {
+                preset: Some(preset),
+                width,
+            });
+        }
+        match value.trim_end_matches("px").parse::<u32>() {
+            Ok(width) if (100..=2000).contains(&width) => Ok(Self {
+                preset: None,
+                width,
+            }),
+            _ => Err(WebshotError::config(format!(
+                "Invalid email width: {}. Use desktop, mobile, small or a width in pixels (100-2000)",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for EmailWidth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.preset {
+            Some(preset) => write!(f, "{} ({}px)", preset, self.width),
+            None => write!(f, "{}px", self.width),
+        }
+    }
+}
+
+/// Output of the capture of a template at `width` in `scheme`, derived from
+/// `output`
+pub fn email_output(output: &Path, width: EmailWidth, scheme: ColorScheme) -> PathBuf {
+    let stem = output
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "email".to_string());
+    let extension = output
+        .extension()
+        .map(|ext| ext.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "png".to_string());
+    output.with_file_name(format!("{}-{}-{}.{}", stem, width.width, scheme, extension))
+}
+
+/// Markup or CSS that email clients commonly drop or ignore
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CssWarning {
+    /// Line of the first use, 1-based
+    pub line: usize,
+    /// Number of lines using it
+    pub count: usize,
+    pub message: &'static str,
+}
+
+impl fmt::Display for CssWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)?;
+        if self.count > 1 {
+            write!(f, " ({} lines)", self.count)?;
+        }
+        Ok(())
+    }
+}
+
+/// Patterns looked for in the lowercased template with spaces removed,
+/// and why they are a problem
+const CSS_RULES: [(&[&str], &str); 9] = [
+    (
+        &["<style"],
+        "<style> blocks are removed by some webmail clients; inline the styles that matter",
+    ),
+    (
+        &["rel=\"stylesheet\"", "rel='stylesheet'", "rel=stylesheet"],
+        "external stylesheets are not loaded by email clients; inline the CSS",
+    ),
+    (&["@import"], "@import is not supported by most email clients"),
+    (&["<script"], "scripts are removed by every email client"),
+    (
+        &["display:flex", "display:inline-flex", "display:grid", "display:inline-grid"],
+        "flexbox and grid are not supported by Outlook for Windows; lay out with tables",
+    ),
+    (
+        &["position:absolute", "position:fixed", "position:relative", "position:sticky"],
+        "position is removed by Gmail and Outlook",
+    ),
+    (
+        &["var(--"],
+        "CSS custom properties are not supported by Gmail and Outlook",
+    ),
+    (
+        &["background-image:", "background:url("],
+        "CSS background images are not shown by Outlook for Windows; add a background attribute or VML fallback",
+    ),
+    (
+        &["<svg", ".svg\""],
+        "SVG images are not shown by Gmail and Outlook; use PNG",
+    ),
+];
+
+/// Warnings about markup and CSS of an email template that common clients
+/// drop or ignore, in the order of their first use
+pub fn check_css(html: &str) -> Vec<CssWarning> {
+    let mut warnings: Vec<CssWarning> = Vec::new();
+    for (index, line) in html.lines().enumerate() {
+        let compact: String = line
+            .to_lowercase()
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect();
+        for (patterns, message) in CSS_RULES {
+            if !patterns.iter().any(|pattern| compact.contains(pattern)) {
+                continue;
+            }
+            match warnings
+                .iter_mut()
+                .find(|warning| warning.message == message)
+            {
+                Some(warning) => warning.count += 1,
+                None => warnings.push(CssWarning {
+                    line: index + 1,
+                    count: 1,
+                    message,
+                }),
+            }
+        }
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_email_widths_and_outputs() {
+        let desktop: EmailWidth = "Desktop".parse().unwrap();
+        assert_eq!(desktop.width, 600);
+        assert_eq!(desktop.to_string(), "desktop (600px)");
+        assert_eq!("480px".parse::<EmailWidth>().unwrap().width, 480);
+        assert!("phone".parse::<EmailWidth>().is_err());
+        assert!("20".parse::<EmailWidth>().is_err());
+
+        assert_eq!(
+            email_output(Path::new("out/welcome.png"), desktop, ColorScheme::Dark),
+            PathBuf::from("out/welcome-600-dark.png")
+        );
+        assert_eq!(
+            email_output(Path::new("welcome"), desktop, ColorScheme::Light),
+            PathBuf::from("welcome-600-light.png")
+        );
+    }
+
+    #[test]
+    fn test_check_css_reports_first_use() {
+        let html = r#"<html>
+<head>
+<link rel="stylesheet" href="https://example.com/email.css">
+<style>.cta { color: var(--brand); }</style>
+</head>
+<body>
+<div style="display: flex; position: absolute">
+<table style="width: 600px"><tr><td style="display:flex">Hi</td></tr></table>
+</div>
+</body>
+</html>"#;
+        let warnings = check_css(html);
+        let lines: Vec<_> = warnings.iter().map(|warning| warning.line).collect();
+        assert_eq!(lines, vec![3, 4, 4, 7, 7]);
+        let flex = warnings
+            .iter()
+            .find(|warning| warning.message.starts_with("flexbox"))
+            .unwrap();
+        assert_eq!((flex.line, flex.count), (7, 2));
+        assert!(flex.to_string().starts_with("line 7: flexbox"));
+        assert!(flex.to_string().ends_with("(2 lines)"));
+
+        assert!(check_css("<table><tr><td style=\"padding: 8px\">Hi</td></tr></table>").is_empty());
+    }
+}
@@ -1,3 +1,4 @@
+use crate::page_errors::PageError;
 use std::path::PathBuf;
 use thiserror::Error;
 
@@ -10,6 +11,9 @@ pub enum WebshotError {
     #[error("Browser launch failed: {0}. Verify that Chrome or Chromium is installed and reachable, or pass --chrome-path with the executable path. In containers, also try --chrome-flag=--no-sandbox and confirm the process can write to its temporary directory.")]
     BrowserLaunch(String),
 
+    #[error("Cannot connect to the browser at {url}: {reason}. Check that Chrome runs with --remote-debugging-port (and --remote-debugging-address=0.0.0.0 when remote), that the port is reachable from this machine, and that the URL is the ws:// URL from /json/version or the http:// address of the port.")]
+    BrowserConnect { url: String, reason: String },
+
     #[error("Tab error: {0}")]
     Tab(String),
 
@@ -46,7 +50,7 @@ pub enum WebshotError {
     #[error("Invalid file path: {path}")]
     InvalidPath { path: PathBuf },
 
-    #[error("Unsupported image format: {format}. Supported formats: png, jpg, jpeg, webp, pdf")]
+    #[error("Unsupported image format: {format}. Supported formats: png, jpg, jpeg, webp, gif, tif, tiff, pdf")]
     UnsupportedFormat { format: String },
 
     #[error("Timeout waiting for condition: {condition}")]
@@ -60,6 +64,37 @@ pub enum WebshotError {
 
     #[error("Invalid viewport dimensions: width={width}, height={height}")]
     InvalidViewport { width: u32, height: u32 },
+
+    #[error("Hook failed: {0}")]
+    Hook(String),
+
+    #[error("Plugin error: {0}")]
+    Plugin(String),
+
+    #[error("Script error: {0}")]
+    Script(String),
+
+    #[error("Job queue is full ({capacity} pending jobs); retry later")]
+    QueueFull { capacity: usize },
+
+    #[error("Baseline store error: {0}")]
+    Baseline(String),
+
+    #[error("Baseline '{name}' is now at version {current}, not the version expected. Another run accepted a newer baseline; compare against it again, or pass --force to replace it anyway.")]
+    BaselineConflict { name: String, current: String },
+
+    #[error("{url} returned HTTP {status}. The capture would show an error page; fix the URL or server, or drop --fail-on-http-error to capture error pages anyway.")]
+    HttpStatus { url: String, status: u16 },
+
+    #[error("{url} reported errors while loading:{}\nFix the page, or drop --fail-on-console-error/--fail-on-js-exception to capture it anyway.", list_page_errors(.errors))]
+    PageErrors { url: String, errors: Vec<PageError> },
+}
+
+pub(crate) fn list_page_errors(errors: &[PageError]) -> String {
+    errors
+        .iter()
+        .map(|error| format!("\n  - {}", error))
+        .collect()
 }
 
 /// Result type alias
@@ -76,6 +111,14 @@ impl WebshotError {
         Self::BrowserLaunch(msg.into())
     }
 
+    /// Create an error for a browser that could not be connected to
+    pub fn browser_connect(url: impl Into<String>, reason: impl ToString) -> Self {
+        Self::BrowserConnect {
+            url: url.into(),
+            reason: reason.to_string(),
+        }
+    }
+
     /// Create a screenshot error
     pub fn screenshot(msg: impl Into<String>) -> Self {
         Self::Screenshot(msg.into())
@@ -96,6 +139,42 @@ impl WebshotError {
         Self::Pdf(msg.into())
     }
 
+    /// Create a hook error
+    pub fn hook(msg: impl Into<String>) -> Self {
+        Self::Hook(msg.into())
+    }
+
+    /// Create a plugin error
+    pub fn plugin(msg: impl Into<String>) -> Self {
+        Self::Plugin(msg.into())
+    }
+
+    /// Create a script error
+    pub fn script(msg: impl Into<String>) -> Self {
+        Self::Script(msg.into())
+    }
+
+    /// Create a baseline store error
+    pub fn baseline(msg: impl Into<String>) -> Self {
+        Self::Baseline(msg.into())
+    }
+
+    /// Create an error for a page whose main document failed with `status`
+    pub fn http_status(url: impl Into<String>, status: u16) -> Self {
+        Self::HttpStatus {
+            url: url.into(),
+            status,
+        }
+    }
+
+    /// Create an error listing the console errors and exceptions of a page
+    pub fn page_errors(url: impl Into<String>, errors: Vec<PageError>) -> Self {
+        Self::PageErrors {
+            url: url.into(),
+            errors,
+        }
+    }
+
     /// Create a timeout error
     pub fn timeout(condition: impl Into<String>) -> Self {
         Self::Timeout {
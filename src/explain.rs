@@ -0,0 +1,265 @@
+//! Where each effective capture option came from, for `--explain`.
+//!
+//! An [`Explanation`] lists every option of one capture with its value and
+//! source: a command-line flag, an environment variable, the capture's own
+//! entry in a config file, a `defaults` section or webshot's builtin
+//! default. Options are listed one per line in a stable order, so the
+//! explanations of two runs can be diffed.
+
+use crate::config::Config;
+use crate::error::Result;
+use serde_yaml::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+
+/// Entry options a `defaults` section fills in (see `DefaultConfig::apply_to`)
+const DEFAULTED: &[&str] = &[
+    "width",
+    "height",
+    "timeout",
+    "wait",
+    "retina",
+    "user_agent",
+    "quality",
+    "output_layout",
+    "headers",
+    "cookies",
+];
+
+/// Where an option's value came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OptionSource {
+    /// A command-line flag
+    Cli,
+    /// An environment variable read for a flag
+    Env,
+    /// The capture's entry in a config file
+    Entry(PathBuf),
+    /// The `defaults` section of a config file
+    Defaults(PathBuf),
+    /// webshot's builtin default
+    Builtin,
+}
+
+impl fmt::Display for OptionSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OptionSource::Cli => f.write_str("cli flag"),
+            OptionSource::Env => f.write_str("env"),
+            OptionSource::Entry(file) => write!(f, "entry in {}", file.display()),
+            OptionSource::Defaults(file) => write!(f, "defaults in {}", file.display()),
+            OptionSource::Builtin => f.write_str("builtin default"),
+        }
+    }
+}
+
+/// One option of a capture
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExplainedOption {
+    pub name: String,
+    pub value: String,
+    pub source: OptionSource,
+}
+
+/// The options of one capture and their sources
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Explanation {
+    pub url: String,
+    pub output: String,
+    pub options: Vec<ExplainedOption>,
+}
+
+impl Explanation {
+    pub fn new(url: impl Into<String>, output: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            output: output.into(),
+            options: Vec::new(),
+        }
+    }
+
+    /// Add an option
+    pub fn option(
+        mut self,
+        name: impl Into<String>,
+        value: impl Into<String>,
+        source: OptionSource,
+    ) -> Self {
+        self.options.push(ExplainedOption {
+            name: name.into(),
+            value: value.into(),
+            source,
+        });
+        self
+    }
+
+    /// Source of the option called `name`
+    pub fn source(&self, name: &str) -> Option<&OptionSource> {
+        self.options
+            .iter()
+            .find(|option| option.name == name)
+            .map(|option| &option.source)
+    }
+}
+
+impl fmt::Display for Explanation {
+    /// `url -> output` followed by an indented `name = value  (source)` line
+    /// per option
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} -> {}", self.url, self.output)?;
+        for option in &self.options {
+            writeln!(
+                f,
+                "  {} = {}  ({})",
+                option.name, option.value, option.source
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Explain every capture of a batch config.
+///
+/// `layers` are the config files merged into `config`, outermost first,
+/// with the last one holding the `screenshots` entries; captures after
+/// those entries were generated (from a sitemap or URL list) and take
+/// `cli` sources, the options set on the command line for them, over
+/// `defaults`.
+pub fn explain_config(
+    config: &Config,
+    layers: &[(PathBuf, Value)],
+    cli: &HashMap<String, OptionSource>,
+) -> Result<Vec<Explanation>> {
+    let entries = layers
+        .last()
+        .and_then(|(_, value)| value.get("screenshots"))
+        .and_then(Value::as_sequence);
+    let entry_file = layers.last().map(|(file, _)| file.clone());
+
+    let mut explanations = Vec::new();
+    for (index, screenshot) in config.screenshots.iter().enumerate() {
+        let entry = entries.and_then(|entries| entries.get(index));
+        let serde_json::Value::Object(options) = serde_json::to_value(screenshot)? else {
+            continue;
+        };
+
+        let mut explanation = Explanation::new(
+            screenshot.url.clone(),
+            screenshot.output.display().to_string(),
+        );
+        for (name, value) in options {
+            if name == "url" || name == "output" {
+                continue;
+            }
+            let defaults_file = DEFAULTED
+                .contains(&name.as_str())
+                .then(|| {
+                    layers.iter().rev().find(|(_, layer)| {
+                        layer
+                            .get("defaults")
+                            .and_then(|defaults| defaults.get(&name))
+                            .is_some()
+                    })
+                })
+                .flatten()
+                .map(|(file, _)| file.clone());
+
+            let source = match entry {
+                Some(entry) => match entry.get(&name) {
+                    // The defaults section overrides entries left at a
+                    // builtin default
+                    Some(raw)
+                        if defaults_file.is_none()
+                            || serde_json::to_value(raw).ok().as_ref() == Some(&value) =>
+                    {
+                        entry_file.clone().map(OptionSource::Entry)
+                    }
+                    _ => defaults_file.map(OptionSource::Defaults),
+                },
+                None => cli
+                    .get(&name)
+                    .cloned()
+                    .or_else(|| defaults_file.map(OptionSource::Defaults)),
+            };
+            explanation = explanation.option(
+                name,
+                value.to_string(),
+                source.unwrap_or(OptionSource::Builtin),
+            );
+        }
+        explanations.push(explanation);
+    }
+    Ok(explanations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_options_are_attributed_to_their_layer() {
+        let shared: Value = serde_yaml::from_str(
+            r#"
+defaults:
+  width: 1440
+  retina: true
+"#,
+        )
+        .unwrap();
+        let own: Value = serde_yaml::from_str(
+            r#"
+defaults:
+  timeout: 60
+screenshots:
+  - url: https://example.com
+    output: home.png
+    width: 1280
+    wait: 2
+"#,
+        )
+        .unwrap();
+
+        // What `project` merges the two files into
+        let mut merged = own.clone();
+        merged["defaults"]["width"] = 1440.into();
+        merged["defaults"]["retina"] = true.into();
+        let mut config = Config::from_yaml(merged).unwrap();
+        config.extend_with_urls(&["https://example.com/about".to_string()], "{path}.png");
+
+        let layers = [
+            (PathBuf::from("webshot.yaml"), shared),
+            (PathBuf::from("site/webshot.yaml"), own),
+        ];
+        let cli = HashMap::from([("height".to_string(), OptionSource::Cli)]);
+        let explanations = explain_config(&config, &layers, &cli).unwrap();
+
+        let entry = &explanations[0];
+        assert_eq!(entry.url, "https://example.com");
+        // An entry left at the builtin width takes the defaults' width
+        assert_eq!(
+            entry.source("width"),
+            Some(&OptionSource::Defaults(PathBuf::from("webshot.yaml")))
+        );
+        assert_eq!(
+            entry.source("wait"),
+            Some(&OptionSource::Entry(PathBuf::from("site/webshot.yaml")))
+        );
+        assert_eq!(
+            entry.source("timeout"),
+            Some(&OptionSource::Defaults(PathBuf::from("site/webshot.yaml")))
+        );
+        assert_eq!(entry.source("height"), Some(&OptionSource::Builtin));
+        assert_eq!(entry.source("full_page"), Some(&OptionSource::Builtin));
+
+        let generated = &explanations[1];
+        assert_eq!(generated.source("height"), Some(&OptionSource::Cli));
+        assert_eq!(
+            generated.source("timeout"),
+            Some(&OptionSource::Defaults(PathBuf::from("site/webshot.yaml")))
+        );
+        assert!(generated
+            .to_string()
+            .contains("  width = 1440  (defaults in webshot.yaml)\n"));
+    }
+}
@@ -0,0 +1,105 @@
+//! The default set of Chrome launch flags.
+//!
+//! Every Chrome webshot launches gets [`DEFAULT_FLAGS`] ahead of the flags
+//! of the run, chosen for headless captures in containers and CI. Some
+//! environments need the opposite, such as a desktop where Chrome's sandbox
+//! works or a GPU that should render WebGL, so each default can be removed
+//! by name with `--remove-flag`, for example `--remove-flag disable-gpu`.
+//! `webshot info --flags` lists the set, what each flag is for and the
+//! flags the run adds.
+
+use crate::error::{Result, WebshotError};
+
+/// A flag of the default set
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DefaultFlag {
+    /// Switch name without the leading dashes, as given to `--remove-flag`
+    pub name: &'static str,
+    /// Why webshot launches Chrome with it
+    pub reason: &'static str,
+}
+
+impl DefaultFlag {
+    /// The switch as passed to Chrome
+    pub fn flag(&self) -> String {
+        format!("--{}", self.name)
+    }
+}
+
+/// Flags every Chrome instance is launched with unless removed
+pub const DEFAULT_FLAGS: [DefaultFlag; 5] = [
+    DefaultFlag {
+        name: "no-sandbox",
+        reason:
+            "Chrome's sandbox needs user namespaces, which containers and root users often lack",
+    },
+    DefaultFlag {
+        name: "disable-gpu",
+        reason: "render in software, the same on machines with and without a GPU",
+    },
+    DefaultFlag {
+        name: "disable-dev-shm-usage",
+        reason: "keep shared memory in /tmp, as Docker's /dev/shm is only 64 MB",
+    },
+    DefaultFlag {
+        name: "disable-setuid-sandbox",
+        reason: "the setuid sandbox helper is rarely installed outside desktop Chrome",
+    },
+    DefaultFlag {
+        name: "no-first-run",
+        reason: "skip first-run dialogs and imports",
+    },
+];
+
+/// Default flag named by a `--remove-flag` value, given with or without its
+/// leading dashes
+pub fn default_flag(name: &str) -> Result<&'static DefaultFlag> {
+    let name = name.trim().trim_start_matches('-');
+    DEFAULT_FLAGS
+        .iter()
+        .find(|flag| flag.name == name)
+        .ok_or_else(|| {
+            let names: Vec<_> = DEFAULT_FLAGS.iter().map(|flag| flag.name).collect();
+            WebshotError::config(format!(
+                "Unknown default flag: {}. Default flags: {}",
+                name,
+                names.join(", ")
+            ))
+        })
+}
+
+/// The default flags left after removing the ones named in `removed`
+pub fn default_flags(removed: &[String]) -> Result<Vec<String>> {
+    let removed = removed
+        .iter()
+        .map(|name| default_flag(name))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(DEFAULT_FLAGS
+        .iter()
+        .filter(|flag| !removed.contains(flag))
+        .map(DefaultFlag::flag)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remove_default_flags() {
+        assert_eq!(default_flags(&[]).unwrap().len(), DEFAULT_FLAGS.len());
+        let flags =
+            default_flags(&["disable-gpu".to_string(), "--no-sandbox".to_string()]).unwrap();
+        assert_eq!(
+            flags,
+            vec![
+                "--disable-dev-shm-usage",
+                "--disable-setuid-sandbox",
+                "--no-first-run"
+            ]
+        );
+
+        let error = default_flags(&["enable-gpu".to_string()]).unwrap_err();
+        assert!(error.to_string().contains("no-sandbox, disable-gpu"));
+    }
+}
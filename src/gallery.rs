@@ -0,0 +1,291 @@
+//! Static HTML gallery of a batch run, for `--report html:DIR`.
+//!
+//! The gallery is built from the run's [`RunManifest`]: an `index.html`
+//! with a card per capture showing a thumbnail linked to the full image,
+//! the page URL, status, dimensions, file size, duration and any error.
+//! Thumbnails are written to a `thumbnails/` directory next to it and full
+//! images are linked relative to the report, so the report directory can
+//! be opened straight from disk or served together with the outputs.
+
+use crate::book::escape_html;
+use crate::error::{Result, WebshotError};
+use crate::manifest::{ManifestEntry, RunManifest};
+use crate::output::format_file_size;
+use path_absolutize::Absolutize;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+/// Longest side of the gallery thumbnails in pixels
+pub const THUMBNAIL_SIZE: u32 = 400;
+
+/// Directory `--report html` writes to when no path is given
+const DEFAULT_REPORT_DIR: &str = "report";
+
+const STYLE: &str = "\
+body { font-family: system-ui, sans-serif; margin: 2rem; color: #222; background: #f6f6f6; }
+header p { color: #555; }
+main { display: grid; grid-template-columns: repeat(auto-fill, minmax(280px, 1fr)); gap: 1rem; }
+figure { margin: 0; background: #fff; border: 1px solid #ddd; border-radius: 6px; overflow: hidden; }
+figure.failed { border-color: #d33; }
+figure img { display: block; width: 100%; height: 200px; object-fit: cover; object-position: top; background: #eee; }
+figure .missing { display: flex; align-items: center; justify-content: center; height: 200px; background: #eee; color: #888; }
+figcaption { padding: 0.6rem 0.8rem; font-size: 0.85rem; }
+figcaption a.url { display: block; font-weight: 600; word-break: break-all; }
+.status { text-transform: uppercase; font-size: 0.75rem; font-weight: 700; }
+.done { color: #2a7; } .failed .status { color: #d33; } .skipped { color: #888; }
+.meta { color: #666; } .error { color: #d33; white-space: pre-wrap; }
+";
+
+/// Format of a `--report`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Html,
+}
+
+/// Report written after a batch run, parsed from `FORMAT[:PATH]` such as
+/// `html:report/`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchReport {
+    pub format: ReportFormat,
+    /// Directory the report is written to
+    pub path: PathBuf,
+}
+
+impl FromStr for BatchReport {
+    type Err = WebshotError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (format, path) = match s.split_once(':') {
+            Some((format, path)) => (format, path),
+            None => (s, DEFAULT_REPORT_DIR),
+        };
+        let format = match format.to_lowercase().as_str() {
+            "html" => ReportFormat::Html,
+            _ => {
+                return Err(WebshotError::config(format!(
+                    "Unknown report format: {}. Supported formats: html",
+                    format
+                )))
+            }
+        };
+        if path.is_empty() {
+            return Err(WebshotError::config(format!(
+                "Report {} needs a directory after the colon",
+                s
+            )));
+        }
+        Ok(Self {
+            format,
+            path: PathBuf::from(path),
+        })
+    }
+}
+
+impl BatchReport {
+    /// Write the report of `manifest`, returning the path of its index page
+    pub fn write(&self, manifest: &RunManifest) -> Result<PathBuf> {
+        match self.format {
+            ReportFormat::Html => write_gallery(manifest, &self.path),
+        }
+    }
+}
+
+/// Write an HTML gallery of `manifest` to `dir`, returning the path of its
+/// `index.html`
+pub fn write_gallery(manifest: &RunManifest, dir: &Path) -> Result<PathBuf> {
+    let thumbnails = dir.join("thumbnails");
+    std::fs::create_dir_all(&thumbnails)?;
+    let report_dir = dir.absolutize()?.to_path_buf();
+
+    let mut cards = String::new();
+    for (index, entry) in manifest.entries.iter().enumerate() {
+        let image = entry
+            .output
+            .absolutize()
+            .ok()
+            .filter(|output| output.is_file())
+            .map(|output| output.to_path_buf());
+        let thumbnail = image.as_deref().and_then(|image| {
+            let name = format!("{:04}.png", index + 1);
+            let thumbnail = image::open(image)
+                .ok()?
+                .thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE);
+            thumbnail.save(thumbnails.join(&name)).ok()?;
+            Some(format!("thumbnails/{}", name))
+        });
+        let link = image.map(|image| relative_path(&report_dir, &image));
+        cards.push_str(&card(entry, thumbnail.as_deref(), link.as_deref()));
+    }
+
+    let title = format!("webshot report: {} captures", manifest.total);
+    let html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+         <style>\n{STYLE}</style>\n</head>\n<body>\n<header>\n<h1>{title}</h1>\n\
+         <p>{done} done, {failed} failed, {skipped} skipped in {seconds:.1}s, finished {finished}</p>\n\
+         </header>\n<main>\n{cards}</main>\n</body>\n</html>\n",
+        title = escape_html(&title),
+        done = manifest.done,
+        failed = manifest.failed,
+        skipped = manifest.skipped,
+        seconds = manifest.duration_ms as f64 / 1000.0,
+        finished = escape_html(&manifest.finished_at),
+    );
+    let index = dir.join("index.html");
+    std::fs::write(&index, html)?;
+    Ok(index)
+}
+
+/// The `<figure>` of one capture
+fn card(entry: &ManifestEntry, thumbnail: Option<&str>, link: Option<&str>) -> String {
+    let preview = match (thumbnail, link) {
+        (Some(thumbnail), Some(link)) => format!(
+            "<a href=\"{}\"><img src=\"{}\" loading=\"lazy\" alt=\"\"></a>",
+            escape_html(link),
+            escape_html(thumbnail)
+        ),
+        (None, Some(link)) => format!(
+            "<a class=\"missing\" href=\"{}\">No preview</a>",
+            escape_html(link)
+        ),
+        _ => "<div class=\"missing\">No image</div>".to_string(),
+    };
+
+    let mut meta = Vec::new();
+    if let (Some(width), Some(height)) = (entry.width, entry.height) {
+        meta.push(format!("{}×{}", width, height));
+    }
+    if let Some(size) = entry.file_size {
+        meta.push(format_file_size(size));
+    }
+    if let Some(duration) = entry.duration_ms {
+        meta.push(format!("{:.1}s", duration as f64 / 1000.0));
+    }
+    if entry.attempts > 1 {
+        meta.push(format!("{} attempts", entry.attempts));
+    }
+    let error = entry
+        .error
+        .as_deref()
+        .map(|error| format!("<div class=\"error\">{}</div>", escape_html(error)))
+        .unwrap_or_default();
+
+    format!(
+        "<figure class=\"{status}\">{preview}<figcaption>\
+         <a class=\"url\" href=\"{url}\">{url}</a>\
+         <span class=\"status {status}\">{status}</span> \
+         <span class=\"meta\">{output}{separator}{meta}</span>{error}\
+         </figcaption></figure>\n",
+        status = escape_html(&entry.status),
+        url = escape_html(&entry.url),
+        output = escape_html(&entry.output.display().to_string()),
+        separator = if meta.is_empty() { "" } else { " · " },
+        meta = escape_html(&meta.join(" · ")),
+    )
+}
+
+/// `path` relative to the directory `from`, with `/` separators, both
+/// absolute
+pub(crate) fn relative_path(from: &Path, path: &Path) -> String {
+    let from: Vec<Component> = from.components().collect();
+    let to: Vec<Component> = path.components().collect();
+    let common = from
+        .iter()
+        .zip(&to)
+        .take_while(|(from, to)| from == to)
+        .count();
+    let mut parts = vec!["..".to_string(); from.len() - common];
+    parts.extend(
+        to[common..]
+            .iter()
+            .map(|part| part.as_os_str().to_string_lossy().into_owned()),
+    );
+    parts.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::ManifestEntry;
+
+    fn entry(url: &str, output: PathBuf, status: &str, error: Option<&str>) -> ManifestEntry {
+        ManifestEntry {
+            url: url.to_string(),
+            output,
+            status: status.to_string(),
+            error: error.map(|error| error.to_string()),
+            width: None,
+            height: None,
+            file_size: None,
+            duration_ms: Some(1500),
+            attempts: 1,
+            http_status: None,
+        }
+    }
+
+    #[test]
+    fn test_report_spec_parsing() {
+        let report: BatchReport = "html:out/report".parse().unwrap();
+        assert_eq!(report.format, ReportFormat::Html);
+        assert_eq!(report.path, PathBuf::from("out/report"));
+        assert_eq!(
+            "HTML".parse::<BatchReport>().unwrap().path,
+            PathBuf::from("report")
+        );
+        assert!("pdf:out".parse::<BatchReport>().is_err());
+        assert!("html:".parse::<BatchReport>().is_err());
+
+        assert_eq!(
+            relative_path(Path::new("/runs/report"), Path::new("/runs/shots/home.png")),
+            "../shots/home.png"
+        );
+    }
+
+    #[test]
+    fn test_gallery_links_thumbnails_and_images() {
+        let dir = tempfile::tempdir().unwrap();
+        let shots = dir.path().join("shots");
+        std::fs::create_dir_all(&shots).unwrap();
+        image::RgbImage::new(1280, 2400)
+            .save(shots.join("home.png"))
+            .unwrap();
+
+        let mut home = entry("https://example.com/", shots.join("home.png"), "done", None);
+        home.width = Some(1280);
+        home.height = Some(2400);
+        let manifest = RunManifest {
+            finished_at: "2026-10-16T12:00:00Z".to_string(),
+            duration_ms: 4200,
+            total: 2,
+            done: 1,
+            failed: 1,
+            skipped: 0,
+            entries: vec![
+                home,
+                entry(
+                    "https://example.com/?a=<b>",
+                    shots.join("broken.png"),
+                    "failed",
+                    Some("Navigation failed: timeout"),
+                ),
+            ],
+        };
+
+        let report: BatchReport = format!("html:{}", dir.path().join("report").display())
+            .parse()
+            .unwrap();
+        let index = report.write(&manifest).unwrap();
+        let html = std::fs::read_to_string(&index).unwrap();
+        assert!(html.contains("<a href=\"../shots/home.png\"><img src=\"thumbnails/0001.png\""));
+        assert!(html.contains("1280×2400"));
+        assert!(html.contains("https://example.com/?a=&lt;b&gt;"));
+        assert!(html.contains("<div class=\"missing\">No image</div>"));
+        assert!(html.contains("Navigation failed: timeout"));
+        assert!(html.contains("1 done, 1 failed, 0 skipped in 4.2s"));
+
+        let thumbnail = image::open(dir.path().join("report/thumbnails/0001.png")).unwrap();
+        assert_eq!(
+            (thumbnail.width(), thumbnail.height()),
+            (213, THUMBNAIL_SIZE)
+        );
+    }
+}
@@ -0,0 +1,332 @@
+//! Baselines kept in the git repository next to the code they cover.
+//!
+//! `compare --against-ref` reads a baseline as it was at another ref with
+//! `git show`, and `baseline accept --git` commits accepted baselines with a
+//! message listing which pages changed and by how much.
+
+use crate::comparison::{ComparisonOptions, ImageComparator};
+use crate::error::{Result, WebshotError};
+use std::ffi::OsStr;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::process::Command;
+use tracing::debug;
+
+/// How an accepted baseline differs from the one it replaced
+#[derive(Debug, Clone, PartialEq)]
+pub enum Difference {
+    /// There was no baseline before
+    New,
+    /// Percentage of pixels that changed
+    Pixels(f64),
+    /// The image changed size, from and to `(width, height)`
+    Resized((u32, u32), (u32, u32)),
+}
+
+/// One accepted baseline, summarized in the commit message
+#[derive(Debug, Clone, PartialEq)]
+pub struct BaselineChange {
+    pub name: String,
+    pub previous_version: Option<String>,
+    pub version: String,
+    pub difference: Difference,
+}
+
+impl BaselineChange {
+    /// Measure how `current` differs from the `previous` baseline image
+    pub fn measure(
+        name: impl Into<String>,
+        previous: Option<(&[u8], String)>,
+        current: &[u8],
+        version: impl Into<String>,
+    ) -> Result<Self> {
+        let (difference, previous_version) = match previous {
+            None => (Difference::New, None),
+            Some((data, version)) => {
+                let before = image::load_from_memory(data)?;
+                let after = image::load_from_memory(current)?;
+                let sizes = (
+                    (before.width(), before.height()),
+                    (after.width(), after.height()),
+                );
+                let difference = if sizes.0 != sizes.1 {
+                    Difference::Resized(sizes.0, sizes.1)
+                } else {
+                    let result = ImageComparator::compare_images(
+                        &before,
+                        &after,
+                        &ComparisonOptions::new(),
+                    )?;
+                    let changed = result.different_pixels.unwrap_or(0);
+                    Difference::Pixels(changed as f64 * 100.0 / result.total_pixels.max(1) as f64)
+                };
+                (difference, Some(version))
+            }
+        };
+        Ok(Self {
+            name: name.into(),
+            previous_version,
+            version: version.into(),
+            difference,
+        })
+    }
+}
+
+/// Commit message for accepted baselines: a subject naming the page (or the
+/// number of pages) and one line per baseline with how much it changed
+pub fn commit_message(changes: &[BaselineChange]) -> String {
+    let mut message = match changes {
+        [change] if change.difference == Difference::New => {
+            format!("Add baseline {}\n\n", change.name)
+        }
+        [change] => format!("Update baseline {}\n\n", change.name),
+        _ => format!("Update {} baselines\n\n", changes.len()),
+    };
+    for change in changes {
+        let _ = write!(message, "- {}: ", change.name);
+        let _ = match &change.difference {
+            Difference::New => write!(message, "new"),
+            Difference::Pixels(percent) => write!(message, "{:.2}% of pixels changed", percent),
+            Difference::Resized(from, to) => {
+                write!(
+                    message,
+                    "resized {}x{} -> {}x{}",
+                    from.0, from.1, to.0, to.1
+                )
+            }
+        };
+        let _ = match &change.previous_version {
+            Some(previous) => writeln!(message, " (version {} -> {})", previous, change.version),
+            None => writeln!(message, " (version {})", change.version),
+        };
+    }
+    message
+}
+
+/// Contents of `path` at `git_ref`, e.g. the baseline on `main`. Relative
+/// paths are resolved against the current directory, as in the work tree.
+pub async fn show(git_ref: &str, path: &Path) -> Result<Vec<u8>> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| WebshotError::config(format!("Not a file path: {}", path.display())))?;
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    // `./` makes git resolve the path from `dir` instead of the repository root
+    let spec = format!("{}:./{}", git_ref, file_name.to_string_lossy());
+    git(dir, [OsStr::new("show"), OsStr::new(&spec)]).await
+}
+
+/// Stage `paths` and commit them, and only them, with `message`
+pub async fn commit(paths: &[PathBuf], message: &str) -> Result<()> {
+    let Some(first) = paths.first() else {
+        return Ok(());
+    };
+    let dir = first
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    // Paths are made absolute so they resolve the same from `dir`
+    let paths = paths
+        .iter()
+        .map(std::path::absolute)
+        .collect::<std::io::Result<Vec<_>>>()?;
+
+    let mut add = vec![OsStr::new("add"), OsStr::new("--")];
+    add.extend(paths.iter().map(|path| path.as_os_str()));
+    git(dir, add).await?;
+
+    let mut commit = vec![
+        OsStr::new("commit"),
+        OsStr::new("--quiet"),
+        OsStr::new("--message"),
+        OsStr::new(message),
+        OsStr::new("--"),
+    ];
+    commit.extend(paths.iter().map(|path| path.as_os_str()));
+    git(dir, commit).await?;
+    Ok(())
+}
+
+/// Files changed on this branch since it forked from `since`, including
+/// uncommitted changes to tracked files, as absolute paths. Untracked files
+/// are left out, so captures written into a project (and anything else not
+/// yet added) never mark it changed.
+pub async fn changed_files(dir: &Path, since: &str) -> Result<Vec<PathBuf>> {
+    let root = text(
+        git(
+            dir,
+            [OsStr::new("rev-parse"), OsStr::new("--show-toplevel")],
+        )
+        .await?,
+    );
+    let fork_point = text(
+        git(
+            dir,
+            [
+                OsStr::new("merge-base"),
+                OsStr::new(since),
+                OsStr::new("HEAD"),
+            ],
+        )
+        .await?,
+    );
+    let changed = git(
+        dir,
+        [
+            OsStr::new("diff"),
+            OsStr::new("--name-only"),
+            OsStr::new("--no-relative"),
+            OsStr::new(&fork_point),
+            OsStr::new("--"),
+        ],
+    )
+    .await?;
+
+    let root = Path::new(&root);
+    Ok(String::from_utf8_lossy(&changed)
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| root.join(line))
+        .collect())
+}
+
+fn text(output: Vec<u8>) -> String {
+    String::from_utf8_lossy(&output).trim().to_string()
+}
+
+/// Run git in `dir`, returning its stdout or failing with its stderr
+async fn git<'a>(dir: &Path, args: impl IntoIterator<Item = &'a OsStr>) -> Result<Vec<u8>> {
+    let args: Vec<_> = args.into_iter().collect();
+    debug!("Running git {:?} in {}", args, dir.display());
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(&args)
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .map_err(|e| WebshotError::config(format!("Cannot run git: {}", e)))?;
+    if !output.status.success() {
+        return Err(WebshotError::config(format!(
+            "git {} failed: {}",
+            args.first().map_or("".into(), |arg| arg.to_string_lossy()),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+    use std::io::Cursor;
+    use tempfile::TempDir;
+
+    fn png(width: u32, height: u32, changed: u32) -> Vec<u8> {
+        let mut img = RgbImage::from_pixel(width, height, Rgb([255, 255, 255]));
+        for x in 0..changed {
+            img.put_pixel(x, 0, Rgb([0, 0, 0]));
+        }
+        let mut data = Vec::new();
+        img.write_to(&mut Cursor::new(&mut data), image::ImageOutputFormat::Png)
+            .unwrap();
+        data
+    }
+
+    fn init_repo(dir: &Path) {
+        for args in [
+            &["init", "--quiet"][..],
+            &["config", "user.name", "Webshot Test"],
+            &["config", "user.email", "test@example.com"],
+        ] {
+            let status = std::process::Command::new("git")
+                .arg("-C")
+                .arg(dir)
+                .args(args)
+                .status()
+                .unwrap();
+            assert!(status.success());
+        }
+    }
+
+    #[test]
+    fn test_commit_message_summarizes_changes() {
+        let changes = [
+            BaselineChange::measure(
+                "home.png",
+                Some((&png(10, 10, 0), "3".to_string())),
+                &png(10, 10, 5),
+                "4",
+            )
+            .unwrap(),
+            BaselineChange::measure("about.png", None, &png(10, 10, 0), "1").unwrap(),
+            BaselineChange::measure(
+                "docs.png",
+                Some((&png(10, 10, 0), "1".to_string())),
+                &png(10, 20, 0),
+                "2",
+            )
+            .unwrap(),
+        ];
+        assert_eq!(
+            commit_message(&changes),
+            "Update 3 baselines\n\n\
+             - home.png: 5.00% of pixels changed (version 3 -> 4)\n\
+             - about.png: new (version 1)\n\
+             - docs.png: resized 10x10 -> 10x20 (version 1 -> 2)\n"
+        );
+        assert!(commit_message(&changes[1..2]).starts_with("Add baseline about.png\n\n"));
+    }
+
+    #[tokio::test]
+    async fn test_changed_files_skip_untracked_files() {
+        let repo = TempDir::new().unwrap();
+        init_repo(repo.path());
+        let page = repo.path().join("page.html");
+        let style = repo.path().join("style.css");
+        std::fs::write(&page, "<h1>first</h1>").unwrap();
+        std::fs::write(&style, "h1 {}").unwrap();
+        commit(&[page.clone(), style.clone()], "Add page")
+            .await
+            .unwrap();
+        std::fs::write(&page, "<h1>second</h1>").unwrap();
+        commit(std::slice::from_ref(&page), "Change page")
+            .await
+            .unwrap();
+        std::fs::write(&style, "h1 { color: red }").unwrap();
+        std::fs::write(repo.path().join("page.png"), b"capture").unwrap();
+
+        let mut changed: Vec<_> = changed_files(repo.path(), "HEAD~1")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|path| path.file_name().unwrap().to_owned())
+            .collect();
+        changed.sort();
+        assert_eq!(changed, ["page.html", "style.css"]);
+    }
+
+    #[tokio::test]
+    async fn test_commit_and_show_baselines() {
+        let repo = TempDir::new().unwrap();
+        init_repo(repo.path());
+        let baseline = repo.path().join("baselines").join("home.png");
+        std::fs::create_dir_all(baseline.parent().unwrap()).unwrap();
+        let unrelated = repo.path().join("notes.txt");
+        std::fs::write(&unrelated, "staged elsewhere").unwrap();
+
+        std::fs::write(&baseline, b"first").unwrap();
+        commit(std::slice::from_ref(&baseline), "Add baseline home.png")
+            .await
+            .unwrap();
+        std::fs::write(&baseline, b"second").unwrap();
+
+        assert_eq!(show("HEAD", &baseline).await.unwrap(), b"first");
+        assert!(show("HEAD", &unrelated).await.is_err());
+        assert!(show("no-such-ref", &baseline).await.is_err());
+    }
+}
@@ -0,0 +1,721 @@
+//! HTTP Archive (HAR 1.2) recording of a page load, built from DevTools
+//! `Network` and `Page` events.
+
+use crate::error::{Result, WebshotError};
+use headless_chrome::protocol::cdp::types::Event;
+use headless_chrome::protocol::cdp::Network::{self, Headers, ResourceTiming, Response};
+use headless_chrome::Tab;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tracing::{info, warn};
+
+/// The `log` object of a HAR file
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarLog {
+    pub version: String,
+    pub creator: HarCreator,
+    pub pages: Vec<HarPage>,
+    pub entries: Vec<HarEntry>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HarCreator {
+    pub name: String,
+    pub version: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarPage {
+    pub started_date_time: String,
+    pub id: String,
+    pub title: String,
+    pub page_timings: HarPageTimings,
+}
+
+/// Milliseconds from the start of the page load; -1 when not reached
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarPageTimings {
+    pub on_content_load: f64,
+    pub on_load: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarEntry {
+    pub pageref: String,
+    pub started_date_time: String,
+    /// Total time of the request in milliseconds
+    pub time: f64,
+    pub request: HarRequest,
+    pub response: HarResponse,
+    pub cache: serde_json::Map<String, serde_json::Value>,
+    pub timings: HarTimings,
+    #[serde(rename = "serverIPAddress", skip_serializing_if = "Option::is_none")]
+    pub server_ip_address: Option<String>,
+    #[serde(rename = "_resourceType", skip_serializing_if = "Option::is_none")]
+    pub resource_type: Option<String>,
+    /// Why the request failed, when it did
+    #[serde(rename = "_error", skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarRequest {
+    pub method: String,
+    pub url: String,
+    pub http_version: String,
+    pub cookies: Vec<HarNameValue>,
+    pub headers: Vec<HarNameValue>,
+    pub query_string: Vec<HarNameValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_data: Option<HarPostData>,
+    pub headers_size: i64,
+    pub body_size: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarResponse {
+    pub status: u32,
+    pub status_text: String,
+    pub http_version: String,
+    pub cookies: Vec<HarNameValue>,
+    pub headers: Vec<HarNameValue>,
+    pub content: HarContent,
+    #[serde(rename = "redirectURL")]
+    pub redirect_url: String,
+    pub headers_size: i64,
+    pub body_size: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarContent {
+    pub size: i64,
+    pub mime_type: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarPostData {
+    pub mime_type: String,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HarNameValue {
+    pub name: String,
+    pub value: String,
+}
+
+/// Phases of a request in milliseconds; -1 for phases that did not apply
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HarTimings {
+    pub blocked: f64,
+    pub dns: f64,
+    pub connect: f64,
+    pub send: f64,
+    pub wait: f64,
+    pub receive: f64,
+    pub ssl: f64,
+}
+
+impl Default for HarTimings {
+    fn default() -> Self {
+        Self {
+            blocked: -1.0,
+            dns: -1.0,
+            connect: -1.0,
+            send: 0.0,
+            wait: 0.0,
+            receive: 0.0,
+            ssl: -1.0,
+        }
+    }
+}
+
+impl HarTimings {
+    /// Split DevTools resource timing (milliseconds from `request_time`) into
+    /// HAR phases, ending `receive` at `finished` (a monotonic timestamp in
+    /// seconds)
+    fn from_resource_timing(timing: &ResourceTiming, finished: Option<f64>) -> Self {
+        let phase = |start: f64, end: f64| {
+            if start >= 0.0 && end >= start {
+                end - start
+            } else {
+                -1.0
+            }
+        };
+        let blocked = [timing.dns_start, timing.connect_start, timing.send_start]
+            .into_iter()
+            .find(|start| *start >= 0.0)
+            .unwrap_or(-1.0);
+        let receive = finished
+            .map(|finished| {
+                ((finished - timing.request_time) * 1000.0 - timing.receive_headers_end).max(0.0)
+            })
+            .unwrap_or(0.0);
+        Self {
+            blocked,
+            dns: phase(timing.dns_start, timing.dns_end),
+            connect: phase(timing.connect_start, timing.connect_end),
+            send: phase(timing.send_start, timing.send_end).max(0.0),
+            wait: phase(timing.send_end, timing.receive_headers_end).max(0.0),
+            receive,
+            ssl: phase(timing.ssl_start, timing.ssl_end),
+        }
+    }
+
+    /// Total time; `ssl` is already part of `connect`
+    fn total(&self) -> f64 {
+        [
+            self.blocked,
+            self.dns,
+            self.connect,
+            self.send,
+            self.wait,
+            self.receive,
+        ]
+        .into_iter()
+        .filter(|time| *time > 0.0)
+        .sum()
+    }
+}
+
+/// A request seen so far, completed as its events arrive
+#[derive(Debug)]
+struct Pending {
+    entry: HarEntry,
+    /// Monotonic time the request was sent, in seconds
+    sent: f64,
+    timing: Option<ResourceTiming>,
+    received: i64,
+}
+
+/// Builds a HAR log from DevTools events
+#[derive(Debug, Default)]
+pub struct HarBuilder {
+    requests: Vec<Pending>,
+    by_id: HashMap<String, usize>,
+    /// Monotonic and wall-clock time of the first request, in seconds
+    start: Option<(f64, f64)>,
+    title: String,
+    on_content_load: Option<f64>,
+    on_load: Option<f64>,
+}
+
+const PAGE_ID: &str = "page_1";
+
+impl HarBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Update the log with a DevTools event; events of other domains are ignored
+    pub fn handle(&mut self, event: &Event) {
+        match event {
+            Event::NetworkRequestWillBeSent(event) => {
+                let params = &event.params;
+                // A redirect reuses the request id; its response ends the
+                // previous hop
+                if let Some(redirect) = &params.redirect_response {
+                    if let Some(&index) = self.by_id.get(&params.request_id) {
+                        self.apply_response(index, redirect);
+                        self.finish(index, params.timestamp, None);
+                        self.requests[index].entry.response.redirect_url =
+                            params.request.url.clone();
+                    }
+                }
+
+                let (start_monotonic, start_wall) = *self
+                    .start
+                    .get_or_insert((params.timestamp, params.wall_time));
+                if self.title.is_empty() {
+                    self.title = params.document_url.clone();
+                }
+                let wall = start_wall + (params.timestamp - start_monotonic);
+                let request = &params.request;
+                let headers = header_list(&request.headers);
+                let post_data = request.post_data.as_ref().map(|text| HarPostData {
+                    mime_type: header_value(&headers, "content-type").unwrap_or_default(),
+                    text: text.clone(),
+                });
+                let entry = HarEntry {
+                    pageref: PAGE_ID.to_string(),
+                    started_date_time: format_wall_time(wall),
+                    time: 0.0,
+                    request: HarRequest {
+                        method: request.method.clone(),
+                        url: request.url.clone(),
+                        http_version: String::new(),
+                        cookies: cookies(header_value(&headers, "cookie").as_deref()),
+                        query_string: query_string(&request.url),
+                        body_size: post_data.as_ref().map_or(0, |data| data.text.len() as i64),
+                        post_data,
+                        headers,
+                        headers_size: -1,
+                    },
+                    response: HarResponse {
+                        status: 0,
+                        status_text: String::new(),
+                        http_version: String::new(),
+                        cookies: Vec::new(),
+                        headers: Vec::new(),
+                        content: HarContent {
+                            size: 0,
+                            mime_type: String::new(),
+                        },
+                        redirect_url: String::new(),
+                        headers_size: -1,
+                        body_size: -1,
+                    },
+                    cache: serde_json::Map::new(),
+                    timings: HarTimings::default(),
+                    server_ip_address: None,
+                    resource_type: params.Type.as_ref().map(protocol_name),
+                    error: None,
+                };
+                self.by_id
+                    .insert(params.request_id.clone(), self.requests.len());
+                self.requests.push(Pending {
+                    entry,
+                    sent: params.timestamp,
+                    timing: None,
+                    received: 0,
+                });
+            }
+            Event::NetworkResponseReceived(event) => {
+                if let Some(&index) = self.by_id.get(&event.params.request_id) {
+                    self.apply_response(index, &event.params.response);
+                    if self.requests[index].entry.resource_type.is_none() {
+                        self.requests[index].entry.resource_type =
+                            Some(protocol_name(&event.params.Type));
+                    }
+                }
+            }
+            Event::NetworkDataReceived(event) => {
+                if let Some(&index) = self.by_id.get(&event.params.request_id) {
+                    self.requests[index].received += i64::from(event.params.data_length);
+                }
+            }
+            Event::NetworkLoadingFinished(event) => {
+                let params = &event.params;
+                if let Some(&index) = self.by_id.get(&params.request_id) {
+                    self.finish(index, params.timestamp, Some(params.encoded_data_length));
+                }
+            }
+            Event::NetworkLoadingFailed(event) => {
+                let params = &event.params;
+                if let Some(&index) = self.by_id.get(&params.request_id) {
+                    self.requests[index].entry.error = Some(params.error_text.clone());
+                    self.finish(index, params.timestamp, None);
+                }
+            }
+            Event::PageDomContentEventFired(event) => {
+                self.on_content_load = self.since_start(event.params.timestamp);
+            }
+            Event::PageLoadEventFired(event) => {
+                self.on_load = self.since_start(event.params.timestamp);
+            }
+            _ => {}
+        }
+    }
+
+    fn since_start(&self, timestamp: f64) -> Option<f64> {
+        self.start
+            .map(|(monotonic, _)| (timestamp - monotonic) * 1000.0)
+    }
+
+    fn apply_response(&mut self, index: usize, response: &Response) {
+        let pending = &mut self.requests[index];
+        let http_version = http_version(response.protocol.as_deref());
+        let headers = header_list(&response.headers);
+        let set_cookies: Vec<_> = headers
+            .iter()
+            .filter(|header| header.name.eq_ignore_ascii_case("set-cookie"))
+            .filter_map(|header| set_cookie(&header.value))
+            .collect();
+
+        // Request headers as actually sent, when the browser reports them
+        if let Some(sent) = &response.request_headers {
+            let sent = header_list(sent);
+            if !sent.is_empty() {
+                pending.entry.request.cookies = cookies(header_value(&sent, "cookie").as_deref());
+                pending.entry.request.headers = sent;
+            }
+        }
+        pending.entry.request.http_version = http_version.clone();
+        pending.entry.response = HarResponse {
+            status: response.status,
+            status_text: response.status_text.clone(),
+            http_version,
+            cookies: set_cookies,
+            headers,
+            content: HarContent {
+                size: 0,
+                mime_type: response.mime_type.clone(),
+            },
+            redirect_url: String::new(),
+            headers_size: -1,
+            body_size: -1,
+        };
+        pending.entry.server_ip_address = response.remote_ip_address.clone();
+        pending.timing = response.timing.clone();
+    }
+
+    fn finish(&mut self, index: usize, timestamp: f64, encoded_length: Option<f64>) {
+        let pending = &mut self.requests[index];
+        pending.entry.timings = match &pending.timing {
+            Some(timing) => HarTimings::from_resource_timing(timing, Some(timestamp)),
+            None => HarTimings {
+                wait: ((timestamp - pending.sent) * 1000.0).max(0.0),
+                ..HarTimings::default()
+            },
+        };
+        pending.entry.time = pending.entry.timings.total();
+        pending.entry.response.content.size = pending.received;
+        if let Some(length) = encoded_length {
+            pending.entry.response.body_size = length as i64;
+        }
+    }
+
+    /// The HAR log of everything recorded so far
+    pub fn log(&self) -> HarLog {
+        let started = self
+            .start
+            .map(|(_, wall)| format_wall_time(wall))
+            .unwrap_or_else(|| chrono::Utc::now().to_rfc3339());
+        HarLog {
+            version: "1.2".to_string(),
+            creator: HarCreator {
+                name: "webshot".to_string(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+            },
+            pages: vec![HarPage {
+                started_date_time: started,
+                id: PAGE_ID.to_string(),
+                title: self.title.clone(),
+                page_timings: HarPageTimings {
+                    on_content_load: self.on_content_load.unwrap_or(-1.0),
+                    on_load: self.on_load.unwrap_or(-1.0),
+                },
+            }],
+            entries: self
+                .requests
+                .iter()
+                .map(|pending| pending.entry.clone())
+                .collect(),
+        }
+    }
+
+    /// The HAR file contents: `{"log": ...}`
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(
+            &serde_json::json!({ "log": self.log() }),
+        )?)
+    }
+}
+
+/// Records a tab's network traffic and writes it as a HAR file once
+/// dropped, so the archive is kept whether the capture succeeded or not
+#[derive(Debug)]
+pub struct HarRecorder {
+    path: PathBuf,
+    builder: Arc<Mutex<HarBuilder>>,
+}
+
+impl HarRecorder {
+    /// Start recording `tab`'s network traffic, to be written to `path`
+    pub fn attach(tab: &Tab, path: impl Into<PathBuf>) -> Result<Self> {
+        let builder = Arc::new(Mutex::new(HarBuilder::new()));
+        let recorded = Arc::clone(&builder);
+        tab.add_event_listener(Arc::new(move |event: &Event| {
+            recorded.lock().unwrap().handle(event);
+        }))
+        .map_err(WebshotError::Browser)?;
+        tab.call_method(Network::Enable {
+            max_total_buffer_size: None,
+            max_resource_buffer_size: None,
+            max_post_data_size: None,
+            report_direct_socket_traffic: None,
+            enable_durable_messages: None,
+        })
+        .map_err(WebshotError::Browser)?;
+
+        Ok(Self {
+            path: path.into(),
+            builder,
+        })
+    }
+
+    /// Write everything recorded so far to the HAR file
+    pub fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let json = self.builder.lock().unwrap().to_json()?;
+        std::fs::write(&self.path, json)?;
+        Ok(())
+    }
+}
+
+impl Drop for HarRecorder {
+    fn drop(&mut self) {
+        match self.save() {
+            Ok(()) => info!("HAR saved to: {}", self.path.display()),
+            Err(e) => warn!("Failed to write HAR {}: {}", self.path.display(), e),
+        }
+    }
+}
+
+/// Headers as name/value pairs; DevTools joins repeated headers with newlines
+fn header_list(headers: &Headers) -> Vec<HarNameValue> {
+    let Some(serde_json::Value::Object(map)) = &headers.0 else {
+        return Vec::new();
+    };
+    map.iter()
+        .flat_map(|(name, value)| {
+            let value = match value {
+                serde_json::Value::String(value) => value.clone(),
+                other => other.to_string(),
+            };
+            value
+                .split('\n')
+                .map(|value| HarNameValue {
+                    name: name.clone(),
+                    value: value.to_string(),
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+fn header_value(headers: &[HarNameValue], name: &str) -> Option<String> {
+    headers
+        .iter()
+        .find(|header| header.name.eq_ignore_ascii_case(name))
+        .map(|header| header.value.clone())
+}
+
+fn query_string(url: &str) -> Vec<HarNameValue> {
+    url::Url::parse(url)
+        .map(|url| {
+            url.query_pairs()
+                .map(|(name, value)| HarNameValue {
+                    name: name.into_owned(),
+                    value: value.into_owned(),
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Cookies from a `Cookie` request header
+fn cookies(header: Option<&str>) -> Vec<HarNameValue> {
+    header
+        .unwrap_or_default()
+        .split(';')
+        .filter_map(|pair| {
+            let (name, value) = pair.trim().split_once('=')?;
+            Some(HarNameValue {
+                name: name.to_string(),
+                value: value.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// The cookie set by a `Set-Cookie` response header, without its attributes
+fn set_cookie(header: &str) -> Option<HarNameValue> {
+    let pair = header.split(';').next()?;
+    let (name, value) = pair.trim().split_once('=')?;
+    Some(HarNameValue {
+        name: name.to_string(),
+        value: value.to_string(),
+    })
+}
+
+/// HAR spelling of a DevTools protocol name such as `h2`
+fn http_version(protocol: Option<&str>) -> String {
+    match protocol {
+        Some("h2") => "HTTP/2".to_string(),
+        Some("h3") => "HTTP/3".to_string(),
+        Some(other) => other.to_uppercase(),
+        None => String::new(),
+    }
+}
+
+fn protocol_name(value: &impl Serialize) -> String {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(name)) => name,
+        _ => String::new(),
+    }
+}
+
+/// ISO 8601 time from DevTools wall time in seconds since the Unix epoch
+fn format_wall_time(seconds: f64) -> String {
+    chrono::DateTime::from_timestamp_millis((seconds * 1000.0) as i64)
+        .map(|time| time.to_rfc3339_opts(chrono::SecondsFormat::Millis, true))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn event(method: &str, params: Value) -> Event {
+        serde_json::from_value(json!({ "method": method, "params": params })).unwrap()
+    }
+
+    fn request(id: &str, url: &str, timestamp: f64, redirect: Option<Value>) -> Event {
+        let mut params = json!({
+            "requestId": id,
+            "loaderId": "loader",
+            "documentURL": "https://example.com/",
+            "request": {
+                "url": url,
+                "method": "GET",
+                "headers": {"Accept": "text/html", "Cookie": "session=abc; theme=dark"},
+                "initialPriority": "VeryHigh",
+                "referrerPolicy": "no-referrer"
+            },
+            "timestamp": timestamp,
+            "wallTime": 1_767_225_600.0 + timestamp - 100.0,
+            "initiator": {"type": "other"},
+            "redirectHasExtraInfo": false,
+            "type": "Document"
+        });
+        if let Some(redirect) = redirect {
+            params["redirectResponse"] = redirect;
+        }
+        event("Network.requestWillBeSent", params)
+    }
+
+    fn response(url: &str, status: u32, headers: Value) -> Value {
+        json!({
+            "url": url,
+            "status": status,
+            "statusText": "",
+            "headers": headers,
+            "mimeType": "text/html",
+            "charset": "utf-8",
+            "connectionReused": false,
+            "connectionId": 1.0,
+            "remoteIPAddress": "93.184.216.34",
+            "encodedDataLength": 120.0,
+            "securityState": "secure",
+            "protocol": "h2",
+            "timing": {
+                "requestTime": 100.0,
+                "proxyStart": -1.0, "proxyEnd": -1.0,
+                "dnsStart": 0.0, "dnsEnd": 10.0,
+                "connectStart": 10.0, "connectEnd": 40.0,
+                "sslStart": 20.0, "sslEnd": 40.0,
+                "workerStart": -1.0, "workerReady": -1.0,
+                "workerFetchStart": -1.0, "workerRespondWithSettled": -1.0,
+                "sendStart": 40.0, "sendEnd": 41.0,
+                "pushStart": 0.0, "pushEnd": 0.0,
+                "receiveHeadersStart": 90.0, "receiveHeadersEnd": 100.0
+            }
+        })
+    }
+
+    #[test]
+    fn test_redirected_document_load() {
+        let mut har = HarBuilder::new();
+        har.handle(&request("1", "http://example.com/", 100.0, None));
+        har.handle(&request(
+            "1",
+            "https://example.com/?lang=en",
+            100.2,
+            Some(response(
+                "http://example.com/",
+                301,
+                json!({"Location": "https://example.com/?lang=en"}),
+            )),
+        ));
+        har.handle(&event(
+            "Network.responseReceived",
+            json!({
+                "requestId": "1",
+                "loaderId": "loader",
+                "timestamp": 100.3,
+                "type": "Document",
+                "response": response(
+                    "https://example.com/?lang=en",
+                    200,
+                    json!({"Content-Type": "text/html", "Set-Cookie": "a=1; Path=/\nb=2"}),
+                ),
+                "hasExtraInfo": false
+            }),
+        ));
+        har.handle(&event(
+            "Network.dataReceived",
+            json!({"requestId": "1", "timestamp": 100.35, "dataLength": 2048, "encodedDataLength": 900}),
+        ));
+        har.handle(&event(
+            "Network.loadingFinished",
+            json!({"requestId": "1", "timestamp": 100.4, "encodedDataLength": 1024.0}),
+        ));
+        har.handle(&event("Page.loadEventFired", json!({"timestamp": 100.5})));
+
+        let log = har.log();
+        assert_eq!(log.version, "1.2");
+        assert_eq!(log.pages[0].page_timings.on_load, 500.0);
+        assert_eq!(log.entries.len(), 2);
+
+        let redirect = &log.entries[0];
+        assert_eq!(redirect.response.status, 301);
+        assert_eq!(
+            redirect.response.redirect_url,
+            "https://example.com/?lang=en"
+        );
+
+        let page = &log.entries[1];
+        assert_eq!(page.started_date_time, "2026-01-01T00:00:00.200Z");
+        assert_eq!(page.request.http_version, "HTTP/2");
+        assert_eq!(page.request.query_string[0].name, "lang");
+        assert_eq!(page.request.cookies.len(), 2);
+        assert_eq!(page.response.status, 200);
+        assert_eq!(page.response.cookies.len(), 2);
+        assert_eq!(page.response.content.size, 2048);
+        assert_eq!(page.response.body_size, 1024);
+        assert_eq!(page.server_ip_address.as_deref(), Some("93.184.216.34"));
+        assert_eq!(page.resource_type.as_deref(), Some("Document"));
+        assert_eq!(page.timings.dns, 10.0);
+        assert_eq!(page.timings.connect, 30.0);
+        assert_eq!(page.timings.ssl, 20.0);
+        assert_eq!(page.timings.wait, 59.0);
+        assert!((page.timings.receive - 300.0).abs() < 1e-6);
+        assert!((page.time - 400.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_failed_requests_keep_their_error() {
+        let mut har = HarBuilder::new();
+        har.handle(&request("7", "https://ads.example.com/x.js", 100.0, None));
+        har.handle(&event(
+            "Network.loadingFailed",
+            json!({
+                "requestId": "7",
+                "timestamp": 100.1,
+                "type": "Script",
+                "errorText": "net::ERR_BLOCKED_BY_CLIENT"
+            }),
+        ));
+
+        let json: Value = serde_json::from_str(&har.to_json().unwrap()).unwrap();
+        let entry = &json["log"]["entries"][0];
+        assert_eq!(entry["response"]["status"], 0);
+        assert_eq!(entry["_error"], "net::ERR_BLOCKED_BY_CLIENT");
+        assert_eq!(entry["response"]["redirectURL"], "");
+        assert_eq!(json["log"]["creator"]["name"], "webshot");
+    }
+}
@@ -0,0 +1,152 @@
+use crate::monitor::BatchMonitor;
+use serde::Serialize;
+use std::future::Future;
+use std::path::Path;
+use tracing::warn;
+
+/// Liveness and readiness of a webshot process, as reported by
+/// `webshot health` and meant for `/healthz` and `/readyz` style probes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct HealthReport {
+    /// The browser answers DevTools commands
+    pub browser: bool,
+    /// A file can be created in the output directory
+    pub disk_writable: bool,
+    /// Shutdown has begun; in-flight captures finish but no new work starts
+    pub draining: bool,
+}
+
+impl HealthReport {
+    /// Whether the process can do its work at all (liveness)
+    pub fn is_healthy(&self) -> bool {
+        self.browser && self.disk_writable
+    }
+
+    /// Whether the process should be sent new work (readiness)
+    pub fn is_ready(&self) -> bool {
+        self.is_healthy() && !self.draining
+    }
+}
+
+/// Whether a file can be created in `dir`, which must exist
+pub fn disk_writable(dir: &Path) -> bool {
+    match tempfile::Builder::new()
+        .prefix(".webshot-health")
+        .tempfile_in(dir)
+    {
+        Ok(_) => true,
+        Err(e) => {
+            warn!("Output directory {} is not writable: {}", dir.display(), e);
+            false
+        }
+    }
+}
+
+/// Resolve when the process receives SIGTERM (as sent by Kubernetes and
+/// other supervisors before stopping a container) or Ctrl-C
+pub async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = terminate.recv() => {}
+                    _ = tokio::signal::ctrl_c() => {}
+                }
+                return;
+            }
+            Err(e) => warn!("Cannot listen for SIGTERM: {}", e),
+        }
+    }
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+/// Run `work` (typically [`crate::Browser::run_jobs`]) until it completes.
+/// On a shutdown signal the monitor is closed, skipping pending jobs, and
+/// `work` runs on until the captures already in flight have finished.
+pub async fn drain_on_shutdown<F: Future>(monitor: &BatchMonitor, work: F) -> F::Output {
+    drain_on(monitor, work, shutdown_signal()).await
+}
+
+async fn drain_on<F: Future>(
+    monitor: &BatchMonitor,
+    work: F,
+    shutdown: impl Future<Output = ()>,
+) -> F::Output {
+    tokio::pin!(work);
+    tokio::select! {
+        output = &mut work => output,
+        _ = shutdown => {
+            warn!("Shutdown requested, finishing in-flight captures");
+            monitor.close();
+            work.await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ScreenshotConfig;
+    use crate::monitor::JobStatus;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_report_readiness() {
+        let report = HealthReport {
+            browser: true,
+            disk_writable: true,
+            draining: false,
+        };
+        assert!(report.is_healthy() && report.is_ready());
+
+        let draining = HealthReport {
+            draining: true,
+            ..report
+        };
+        assert!(draining.is_healthy() && !draining.is_ready());
+
+        let read_only = HealthReport {
+            disk_writable: false,
+            ..report
+        };
+        assert!(!read_only.is_healthy() && !read_only.is_ready());
+    }
+
+    #[test]
+    fn test_disk_writable_leaves_no_files() {
+        let dir = TempDir::new().unwrap();
+        assert!(disk_writable(dir.path()));
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+        assert!(!disk_writable(&dir.path().join("missing")));
+    }
+
+    #[tokio::test]
+    async fn test_drain_finishes_running_jobs_and_skips_pending() {
+        let configs = (0..3)
+            .map(|i| ScreenshotConfig::new("https://example.com", format!("{}.png", i)))
+            .collect();
+        let monitor = BatchMonitor::new(configs, false);
+        let (release, released) = tokio::sync::oneshot::channel::<()>();
+
+        let work = async {
+            // Still capturing when the shutdown arrives
+            let (index, _) = monitor.next_job().await.unwrap();
+            let _ = released.await;
+            monitor.finish(index, &Ok(()));
+            assert!(monitor.next_job().await.is_none());
+        };
+        let shutdown = async {
+            tokio::task::yield_now().await;
+            let _ = release.send(());
+        };
+        drain_on(&monitor, work, shutdown).await;
+
+        let statuses: Vec<_> = monitor.jobs().into_iter().map(|job| job.status).collect();
+        assert_eq!(
+            statuses,
+            [JobStatus::Done, JobStatus::Skipped, JobStatus::Skipped]
+        );
+    }
+}
@@ -0,0 +1,387 @@
+use crate::config::{validate_navigation_url, ScreenshotConfig};
+use crate::error::{Result, WebshotError};
+use crate::plugins::{WasmPlugin, PLUGIN_PREFIX};
+use crate::timing::PhaseTimings;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tracing::debug;
+
+/// External commands run around each batch capture. Commands run through the
+/// shell with `WEBSHOT_HOOK`, `WEBSHOT_URL` and `WEBSHOT_OUTPUT` set;
+/// `plugin:path.wasm` entries instead call the stage's export of a
+/// WebAssembly plugin (see [`crate::plugins`]) with the same input.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Hooks {
+    /// Run before navigation with the entry as JSON on stdin. A JSON object
+    /// printed to stdout overrides entry fields such as `url` or `headers`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub before_navigation: Vec<String>,
+    /// Run after capture with `CaptureMetadata` as JSON on stdin
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub after_capture: Vec<String>,
+}
+
+/// What an `after_capture` hook receives about a finished capture
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaptureMetadata {
+    /// URL the entry navigated to, after `before_navigation` rewrites
+    pub url: String,
+    /// Page URL after redirects and steps
+    pub final_url: String,
+    /// HTTP status of the page's main document, when one was received
+    pub status: Option<u16>,
+    /// Path the capture was written to
+    pub output: PathBuf,
+    /// Output format, e.g. `png` or `pdf`
+    pub format: String,
+    /// Size of the output file in bytes
+    pub bytes: u64,
+    /// Viewport size the capture was taken at
+    pub width: u32,
+    pub height: u32,
+    /// RFC 3339 capture time
+    pub captured_at: String,
+    /// Milliseconds spent in each capture phase
+    #[serde(default)]
+    pub timings: PhaseTimings,
+}
+
+impl Hooks {
+    pub fn is_empty(&self) -> bool {
+        self.before_navigation.is_empty() && self.after_capture.is_empty()
+    }
+
+    /// Run the `before_navigation` commands in order, each seeing the entry
+    /// as rewritten by the previous one
+    pub async fn before_navigation(&self, entry: ScreenshotConfig) -> Result<ScreenshotConfig> {
+        let mut entry = entry;
+        for command in &self.before_navigation {
+            let mut input = serde_json::to_value(&entry)?;
+            if let Some(fields) = input.as_object_mut() {
+                fields.remove("hooks");
+            }
+
+            let output = run_hook(
+                command,
+                "before_navigation",
+                &input.to_string(),
+                &entry.url,
+                &entry.output.to_string_lossy(),
+                entry.timeout,
+            )
+            .await?;
+            entry = apply_overrides(entry, input, &output)
+                .map_err(|e| WebshotError::hook(format!("`{}`: {}", command, e)))?;
+        }
+        Ok(entry)
+    }
+
+    /// Run the `after_capture` commands in order
+    pub async fn after_capture(&self, metadata: &CaptureMetadata, timeout: u64) -> Result<()> {
+        let input = serde_json::to_string(metadata)?;
+        for command in &self.after_capture {
+            run_hook(
+                command,
+                "after_capture",
+                &input,
+                &metadata.url,
+                &metadata.output.to_string_lossy(),
+                timeout,
+            )
+            .await?;
+        }
+        Ok(())
+    }
+}
+
+/// Merge the JSON object a hook printed into the entry. Empty output keeps
+/// the entry unchanged; the entry's own hooks cannot be replaced.
+fn apply_overrides(
+    entry: ScreenshotConfig,
+    mut input: serde_json::Value,
+    output: &str,
+) -> std::result::Result<ScreenshotConfig, String> {
+    let output = output.trim();
+    if output.is_empty() {
+        return Ok(entry);
+    }
+
+    let overrides: serde_json::Map<String, serde_json::Value> = serde_json::from_str(output)
+        .map_err(|e| format!("expected a JSON object of entry fields on stdout: {}", e))?;
+    let fields = input
+        .as_object_mut()
+        .ok_or_else(|| "entry is not a JSON object".to_string())?;
+    for (key, value) in overrides {
+        if key != "hooks" {
+            fields.insert(key, value);
+        }
+    }
+
+    let mut rewritten: ScreenshotConfig =
+        serde_json::from_value(input).map_err(|e| format!("invalid entry fields: {}", e))?;
+    validate_navigation_url(&rewritten.url, "before_navigation hook").map_err(|e| e.to_string())?;
+    rewritten.validate(0).map_err(|e| e.to_string())?;
+    rewritten.hooks = entry.hooks;
+    Ok(rewritten)
+}
+
+/// Shell invocation for a hook command
+fn shell_command(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.args(["/C", command]);
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.args(["-c", command]);
+        shell
+    }
+}
+
+/// Run one hook command with `input` on stdin, returning its stdout. Fails
+/// on a non-zero exit or when the command outlives `timeout` seconds.
+async fn run_hook(
+    command: &str,
+    stage: &str,
+    input: &str,
+    url: &str,
+    output: &str,
+    timeout: u64,
+) -> Result<String> {
+    if let Some(path) = command.strip_prefix(PLUGIN_PREFIX) {
+        return run_plugin_hook(path, stage, input, timeout).await;
+    }
+
+    debug!("Running {} hook: {}", stage, command);
+    let mut child = shell_command(command)
+        .env("WEBSHOT_HOOK", stage)
+        .env("WEBSHOT_URL", url)
+        .env("WEBSHOT_OUTPUT", output)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| {
+            WebshotError::hook(format!("{} `{}` could not start: {}", stage, command, e))
+        })?;
+
+    let mut stdin = child.stdin.take();
+    let input = input.as_bytes().to_vec();
+    let write = async move {
+        if let Some(stdin) = &mut stdin {
+            // Hooks that ignore their input may exit before reading it
+            let _ = stdin.write_all(&input).await;
+        }
+    };
+
+    let (_, result) = tokio::time::timeout(Duration::from_secs(timeout), async {
+        tokio::join!(write, child.wait_with_output())
+    })
+    .await
+    .map_err(|_| {
+        WebshotError::hook(format!(
+            "{} `{}` did not finish within {} seconds",
+            stage, command, timeout
+        ))
+    })?;
+
+    let result = result?;
+    if !result.status.success() {
+        return Err(WebshotError::hook(format!(
+            "{} `{}` exited with {}",
+            stage, command, result.status
+        )));
+    }
+    Ok(String::from_utf8_lossy(&result.stdout).into_owned())
+}
+
+/// Run a plugin's `stage` export on `input` off the async runtime. Fuel
+/// bounds the call itself; `timeout` seconds bound the wait for it.
+async fn run_plugin_hook(path: &str, stage: &str, input: &str, timeout: u64) -> Result<String> {
+    debug!("Running {} plugin hook: {}", stage, path);
+    let plugin = WasmPlugin::cached(path)?;
+    let export = stage.to_string();
+    let input = input.as_bytes().to_vec();
+    let call = tokio::task::spawn_blocking(move || plugin.hook(&export, &input));
+
+    let output = tokio::time::timeout(Duration::from_secs(timeout), call)
+        .await
+        .map_err(|_| {
+            WebshotError::hook(format!(
+                "{} plugin {} did not finish within {} seconds",
+                stage, path, timeout
+            ))
+        })?
+        .map_err(|e| WebshotError::hook(format!("{} plugin {} panicked: {}", stage, path, e)))??;
+    String::from_utf8(output).map_err(|_| {
+        WebshotError::hook(format!("{} plugin {} returned invalid UTF-8", stage, path))
+    })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::timing::Phase;
+    use tempfile::TempDir;
+
+    fn entry() -> ScreenshotConfig {
+        ScreenshotConfig::new(
+            "https://example.com/".to_string(),
+            PathBuf::from("home.png"),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_before_navigation_rewrites_entry() {
+        let hooks = Hooks {
+            before_navigation: vec![
+                r#"cat > /dev/null; echo '{"url": "https://example.com/?sig=abc", "width": 640}'"#
+                    .to_string(),
+                "cat > /dev/null".to_string(),
+            ],
+            ..Default::default()
+        };
+
+        let entry = hooks.before_navigation(entry()).await.unwrap();
+        assert_eq!(entry.url, "https://example.com/?sig=abc");
+        assert_eq!(entry.width, 640);
+        assert_eq!(entry.output, PathBuf::from("home.png"));
+    }
+
+    #[tokio::test]
+    async fn test_before_navigation_rejects_unsafe_urls() {
+        let hooks = Hooks {
+            before_navigation: vec![r#"echo '{"url": "file:///etc/passwd"}'"#.to_string()],
+            ..Default::default()
+        };
+        let error = hooks.before_navigation(entry()).await.unwrap_err();
+        assert!(error.to_string().contains("Unsupported URL scheme"));
+    }
+
+    #[tokio::test]
+    async fn test_before_navigation_revalidates_the_entry() {
+        let hooks = Hooks {
+            before_navigation: vec![r#"echo '{"width": 0}'"#.to_string()],
+            ..Default::default()
+        };
+        let error = hooks.before_navigation(entry()).await.unwrap_err();
+        assert!(error.to_string().contains("Invalid viewport dimensions"));
+
+        let hooks = Hooks {
+            before_navigation: vec![r#"echo '{"quality": 0, "output": "home.jpg"}'"#.to_string()],
+            ..Default::default()
+        };
+        let error = hooks.before_navigation(entry()).await.unwrap_err();
+        assert!(error.to_string().contains("JPEG quality"));
+    }
+
+    #[tokio::test]
+    async fn test_before_navigation_rejects_non_json_output() {
+        let hooks = Hooks {
+            before_navigation: vec!["echo not json".to_string()],
+            ..Default::default()
+        };
+        let error = hooks.before_navigation(entry()).await.unwrap_err();
+        assert!(error.to_string().contains("expected a JSON object"));
+    }
+
+    #[tokio::test]
+    async fn test_after_capture_receives_metadata() {
+        let temp_dir = TempDir::new().unwrap();
+        let record = temp_dir.path().join("record.json");
+        let hooks = Hooks {
+            after_capture: vec![format!(
+                "cat > {} && test \"$WEBSHOT_OUTPUT\" = out/home.png",
+                record.display()
+            )],
+            ..Default::default()
+        };
+        let metadata = CaptureMetadata {
+            url: "https://example.com/".to_string(),
+            final_url: "https://example.com/home".to_string(),
+            status: Some(200),
+            output: PathBuf::from("out/home.png"),
+            format: "png".to_string(),
+            bytes: 1024,
+            width: 1280,
+            height: 800,
+            captured_at: "2026-01-01T00:00:00Z".to_string(),
+            timings: serde_json::from_str(r#"{"navigate": 412, "capture": 95}"#).unwrap(),
+        };
+
+        hooks.after_capture(&metadata, 10).await.unwrap();
+        let received: CaptureMetadata =
+            serde_json::from_str(&std::fs::read_to_string(&record).unwrap()).unwrap();
+        assert_eq!(received.final_url, "https://example.com/home");
+        assert_eq!(received.bytes, 1024);
+        assert_eq!(received.status, Some(200));
+        assert_eq!(received.timings.get(Phase::Navigate), 412);
+    }
+
+    #[tokio::test]
+    async fn test_failing_and_slow_hooks_are_errors() {
+        let failing = Hooks {
+            before_navigation: vec!["exit 3".to_string()],
+            ..Default::default()
+        };
+        assert!(failing
+            .before_navigation(entry())
+            .await
+            .unwrap_err()
+            .to_string()
+            .contains("exited with"));
+
+        let slow = run_hook("sleep 5", "after_capture", "", "", "", 1)
+            .await
+            .unwrap_err();
+        assert!(slow.to_string().contains("did not finish within 1 seconds"));
+    }
+
+    #[cfg(not(feature = "wasm-plugins"))]
+    #[tokio::test]
+    async fn test_plugin_hooks_need_the_feature() {
+        let hooks = Hooks {
+            before_navigation: vec!["plugin:sign.wasm".to_string()],
+            ..Default::default()
+        };
+        let error = hooks.before_navigation(entry()).await.unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("without the `wasm-plugins` feature"));
+    }
+
+    #[cfg(feature = "wasm-plugins")]
+    #[tokio::test]
+    async fn test_plugin_hooks_rewrite_the_entry() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("sign.wat");
+        // Ignore the input and return a fixed override
+        let output = r#"{"url": "https://example.com/?sig=wasm"}"#;
+        std::fs::write(
+            &path,
+            format!(
+                r#"(module
+                    (memory (export "memory") 1)
+                    (data (i32.const 0) "{}")
+                    (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+                    (func (export "before_navigation") (param i32 i32) (result i64)
+                        (i64.const {})))"#,
+                output.replace('"', "\\22"),
+                output.len()
+            ),
+        )
+        .unwrap();
+
+        let hooks = Hooks {
+            before_navigation: vec![format!("plugin:{}", path.display())],
+            ..Default::default()
+        };
+        let entry = hooks.before_navigation(entry()).await.unwrap();
+        assert_eq!(entry.url, "https://example.com/?sig=wasm");
+    }
+}
@@ -0,0 +1,392 @@
+//! Localized CLI messages and report labels.
+//!
+//! Messages live in Fluent (`.ftl`) catalogs embedded from
+//! `assets/locales`, one per [`Lang`], and are formatted with
+//! `fluent-bundle`. Messages missing from a catalog fall back to English.
+//! Errors are described by their `Display` text, which is the English
+//! wording, so only the other catalogs carry `error-*` messages; errors
+//! without a translation fall back to `Display` as well.
+
+use crate::error::{Result, WebshotError};
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use unic_langid::LanguageIdentifier;
+
+const EN: &str = include_str!("../assets/locales/en.ftl");
+const DE: &str = include_str!("../assets/locales/de.ftl");
+const JA: &str = include_str!("../assets/locales/ja.ftl");
+
+/// Language of CLI messages and reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Lang {
+    #[default]
+    En,
+    De,
+    Ja,
+}
+
+impl Lang {
+    pub const ALL: [Lang; 3] = [Lang::En, Lang::De, Lang::Ja];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Lang::En => "en",
+            Lang::De => "de",
+            Lang::Ja => "ja",
+        }
+    }
+
+    fn catalog_source(self) -> &'static str {
+        match self {
+            Lang::En => EN,
+            Lang::De => DE,
+            Lang::Ja => JA,
+        }
+    }
+}
+
+impl fmt::Display for Lang {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+impl FromStr for Lang {
+    type Err = WebshotError;
+
+    /// Accepts language codes with a region or encoding, such as `de-AT`
+    /// or `ja_JP.UTF-8`
+    fn from_str(value: &str) -> Result<Self> {
+        let language = value
+            .split(['-', '_', '.'])
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        Lang::ALL
+            .into_iter()
+            .find(|lang| lang.code() == language)
+            .ok_or_else(|| {
+                WebshotError::config(format!(
+                    "Unknown language: {}. Supported: en, de, ja",
+                    value
+                ))
+            })
+    }
+}
+
+/// Messages of one language by id
+pub struct Catalog {
+    bundle: FluentBundle<FluentResource>,
+}
+
+impl fmt::Debug for Catalog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Catalog")
+            .field("locales", &self.bundle.locales)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Catalog {
+    /// Parse a Fluent catalog of `lang`
+    pub fn parse(lang: Lang, source: &str) -> Result<Self> {
+        let resource = FluentResource::try_new(source.to_string()).map_err(|(_, errors)| {
+            WebshotError::config(format!(
+                "Invalid {} catalog: {}",
+                lang,
+                join_errors(&errors)
+            ))
+        })?;
+
+        let locale: LanguageIdentifier = lang
+            .code()
+            .parse()
+            .expect("language codes are valid identifiers");
+        let mut bundle = FluentBundle::new_concurrent(vec![locale]);
+        // Unicode isolation marks around arguments show up as stray
+        // characters in terminals and logs
+        bundle.set_use_isolating(false);
+        bundle.add_resource(resource).map_err(|errors| {
+            WebshotError::config(format!(
+                "Invalid {} catalog: {}",
+                lang,
+                join_errors(&errors)
+            ))
+        })?;
+        Ok(Self { bundle })
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.bundle.has_message(id)
+    }
+
+    /// Format a message, leaving unknown variables as `{$name}`
+    pub fn format(&self, id: &str, args: &[(&str, &dyn fmt::Display)]) -> Option<String> {
+        let pattern = self.bundle.get_message(id)?.value()?;
+        let mut fluent_args = FluentArgs::new();
+        for (name, value) in args {
+            fluent_args.set(*name, value.to_string());
+        }
+        // Missing variables are reported here but already rendered as
+        // `{$name}`, which is all the CLI needs
+        let mut errors = Vec::new();
+        let message = self
+            .bundle
+            .format_pattern(pattern, Some(&fluent_args), &mut errors);
+        Some(message.into_owned())
+    }
+}
+
+fn join_errors(errors: &[impl fmt::Display]) -> String {
+    errors
+        .iter()
+        .map(|error| error.to_string())
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Messages of a language with English fallback
+#[derive(Debug)]
+pub struct Localizer {
+    lang: Lang,
+    messages: Catalog,
+    fallback: Catalog,
+}
+
+impl Localizer {
+    pub fn new(lang: Lang) -> Self {
+        let catalog = |lang: Lang| {
+            Catalog::parse(lang, lang.catalog_source()).expect("embedded catalogs are valid")
+        };
+        Self {
+            lang,
+            messages: catalog(lang),
+            fallback: catalog(Lang::En),
+        }
+    }
+
+    pub fn lang(&self) -> Lang {
+        self.lang
+    }
+
+    /// Format a message, falling back to English and then to its id
+    pub fn message(&self, id: &str, args: &[(&str, &dyn fmt::Display)]) -> String {
+        self.messages
+            .format(id, args)
+            .or_else(|| self.fallback.format(id, args))
+            .unwrap_or_else(|| id.to_string())
+    }
+
+    /// Describe an error in this language; in English that is its
+    /// `Display` text
+    pub fn error(&self, error: &WebshotError) -> String {
+        if self.lang == Lang::En {
+            return error.to_string();
+        }
+        let (id, args) = error_args(error);
+        let args: Vec<(&str, &dyn fmt::Display)> = args
+            .iter()
+            .map(|(name, value)| (*name, value as &dyn fmt::Display))
+            .collect();
+        self.messages
+            .format(id, &args)
+            .unwrap_or_else(|| error.to_string())
+    }
+}
+
+/// Message id and arguments describing an error
+fn error_args(error: &WebshotError) -> (&'static str, Vec<(&'static str, String)>) {
+    let detail = |detail: &dyn fmt::Display| vec![("detail", detail.to_string())];
+    match error {
+        WebshotError::Browser(e) => ("error-browser", detail(e)),
+        WebshotError::BrowserLaunch(e) => ("error-browser-launch", detail(e)),
+        WebshotError::BrowserConnect { url, reason } => (
+            "error-browser-connect",
+            vec![("url", url.clone()), ("reason", reason.clone())],
+        ),
+        WebshotError::Tab(e) => ("error-tab", detail(e)),
+        WebshotError::Navigation(e) => ("error-navigation", detail(e)),
+        WebshotError::Screenshot(e) => ("error-screenshot", detail(e)),
+        WebshotError::ElementNotFound { selector } => (
+            "error-element-not-found",
+            vec![("selector", selector.clone())],
+        ),
+        WebshotError::JavaScript(e) => ("error-javascript", detail(e)),
+        WebshotError::Io(e) => ("error-io", detail(e)),
+        WebshotError::Image(e) => ("error-image", detail(e)),
+        WebshotError::Yaml(e) => ("error-yaml", detail(e)),
+        WebshotError::Json(e) => ("error-json", detail(e)),
+        WebshotError::Url(e) => ("error-url", detail(e)),
+        WebshotError::Http(e) => ("error-http", detail(e)),
+        WebshotError::InvalidPath { path } => (
+            "error-invalid-path",
+            vec![("path", path.display().to_string())],
+        ),
+        WebshotError::UnsupportedFormat { format } => {
+            ("error-unsupported-format", vec![("format", format.clone())])
+        }
+        WebshotError::Timeout { condition } => {
+            ("error-timeout", vec![("condition", condition.clone())])
+        }
+        WebshotError::Config(e) => ("error-config", detail(e)),
+        WebshotError::Pdf(e) => ("error-pdf", detail(e)),
+        WebshotError::InvalidViewport { width, height } => (
+            "error-invalid-viewport",
+            vec![("width", width.to_string()), ("height", height.to_string())],
+        ),
+        WebshotError::Hook(e) => ("error-hook", detail(e)),
+        WebshotError::Plugin(e) => ("error-plugin", detail(e)),
+        WebshotError::Script(e) => ("error-script", detail(e)),
+        WebshotError::QueueFull { capacity } => {
+            ("error-queue-full", vec![("capacity", capacity.to_string())])
+        }
+        WebshotError::Baseline(e) => ("error-baseline", detail(e)),
+        WebshotError::BaselineConflict { name, current } => (
+            "error-baseline-conflict",
+            vec![("name", name.clone()), ("current", current.clone())],
+        ),
+        WebshotError::HttpStatus { url, status } => (
+            "error-http-status",
+            vec![("url", url.clone()), ("status", status.to_string())],
+        ),
+        WebshotError::PageErrors { url, errors } => (
+            "error-page-errors",
+            vec![
+                ("url", url.clone()),
+                ("errors", crate::error::list_page_errors(errors)),
+            ],
+        ),
+    }
+}
+
+static LOCALIZER: OnceLock<Localizer> = OnceLock::new();
+
+/// Set the language of messages for the rest of the process. Only the
+/// first call has an effect.
+pub fn init(lang: Lang) {
+    let _ = LOCALIZER.set(Localizer::new(lang));
+}
+
+fn localizer() -> &'static Localizer {
+    LOCALIZER.get_or_init(|| Localizer::new(Lang::En))
+}
+
+/// Format a message in the language set with [`init`] (English by default)
+pub fn tr(id: &str, args: &[(&str, &dyn fmt::Display)]) -> String {
+    localizer().message(id, args)
+}
+
+/// Describe an error in the language set with [`init`]
+pub fn error_message(error: &WebshotError) -> String {
+    localizer().error(error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_format_messages() {
+        let catalog = Catalog::parse(
+            Lang::En,
+            "# comment\n\
+             saved = Saved to: { $path }\n\
+             braces = Use {\"{\"} and {\"}\"}\n\
+             multi =\n    first line\n    second { $n }\n",
+        )
+        .unwrap();
+        assert_eq!(
+            catalog.format("saved", &[("path", &"a.png")]).unwrap(),
+            "Saved to: a.png"
+        );
+        assert_eq!(catalog.format("braces", &[]).unwrap(), "Use { and }");
+        assert_eq!(
+            catalog.format("multi", &[("n", &2)]).unwrap(),
+            "first line\nsecond 2"
+        );
+        assert_eq!(catalog.format("saved", &[]).unwrap(), "Saved to: {$path}");
+        assert!(catalog.format("missing", &[]).is_none());
+
+        assert!(Catalog::parse(Lang::En, "bad line").is_err());
+        assert!(Catalog::parse(Lang::En, "id = { $unclosed").is_err());
+        assert!(Catalog::parse(Lang::En, "id = a\nid = b").is_err());
+    }
+
+    fn message_ids(lang: Lang) -> Vec<String> {
+        let resource = FluentResource::try_new(lang.catalog_source().to_string()).unwrap();
+        resource
+            .entries()
+            .filter_map(|entry| match entry {
+                fluent_syntax::ast::Entry::Message(message) => Some(message.id.name.to_string()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_catalogs_fall_back_to_english() {
+        let english = Localizer::new(Lang::En);
+        for lang in Lang::ALL {
+            // Every translated message but the error translations exists
+            // in English
+            for id in message_ids(lang) {
+                let error = id.starts_with("error-") && lang != Lang::En;
+                assert!(english.messages.contains(&id) || error, "{}: {}", lang, id);
+            }
+        }
+
+        let german = Localizer::new(Lang::De);
+        assert_eq!(
+            german.message("pdf-saved", &[("path", &"out.pdf")]),
+            "PDF gespeichert unter: out.pdf"
+        );
+        assert_eq!(german.message("no-such-message", &[]), "no-such-message");
+        assert_eq!("ja_JP.UTF-8".parse::<Lang>().unwrap(), Lang::Ja);
+        assert_eq!("DE-at".parse::<Lang>().unwrap(), Lang::De);
+        assert!("fr".parse::<Lang>().is_err());
+    }
+
+    #[test]
+    fn test_errors_are_translated_or_displayed() {
+        let english = Localizer::new(Lang::En);
+        let errors = [
+            WebshotError::browser_launch("no chrome"),
+            WebshotError::browser_connect("ws://localhost:9222", "refused"),
+            WebshotError::navigation("net::ERR_NAME_NOT_RESOLVED"),
+            WebshotError::ElementNotFound {
+                selector: "#main".to_string(),
+            },
+            WebshotError::UnsupportedFormat {
+                format: "bmp".to_string(),
+            },
+            WebshotError::config("Unknown device"),
+            WebshotError::InvalidViewport {
+                width: 0,
+                height: 800,
+            },
+            WebshotError::QueueFull { capacity: 10 },
+            WebshotError::HttpStatus {
+                url: "https://example.com".to_string(),
+                status: 404,
+            },
+            WebshotError::PageErrors {
+                url: "https://example.com".to_string(),
+                errors: Vec::new(),
+            },
+        ];
+        let german = Localizer::new(Lang::De);
+        for error in &errors {
+            assert_eq!(english.error(error), error.to_string());
+            assert_ne!(german.error(error), error.to_string());
+        }
+
+        let japanese = Localizer::new(Lang::Ja);
+        assert_eq!(
+            japanese.error(&WebshotError::config("Unknown device")),
+            "設定エラー: Unknown device"
+        );
+    }
+}
@@ -0,0 +1,852 @@
+//! Asynchronous capture jobs for `webshot serve`.
+//!
+//! `POST /jobs` queues captures and answers with their ids right away, so
+//! bulk requests are not bound by HTTP request timeouts; `GET /jobs/{id}`
+//! reports progress and `GET /jobs/{id}/result` returns the output. Each job
+//! is recorded as `<id>.json` in the jobs directory with its output next to
+//! it, and jobs still queued or running when the server stopped are queued
+//! again when it starts with the same directory. Finished jobs are removed
+//! once they are older than [`JobQueueOptions::retention`].
+//!
+//! Queued jobs run highest `priority` first, oldest first among equals, and
+//! a job with a `webhook` has its final record POSTed there once it is done
+//! or failed. Webhooks may only point at the hosts in
+//! [`JobQueueOptions::webhook_hosts`], so clients cannot make the server
+//! send requests into its own network.
+
+use crate::error::{Result, WebshotError};
+use crate::server::{PdfRequest, ScreenshotRequest, TextRequest};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tracing::{debug, info, warn};
+
+/// A capture to run in the background
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum JobRequest {
+    Screenshot(ScreenshotRequest),
+    Pdf(PdfRequest),
+    Text(TextRequest),
+}
+
+impl JobRequest {
+    /// Fail now for requests that could never run
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            JobRequest::Screenshot(request) => request.options().map(|_| ()),
+            JobRequest::Pdf(request) => request.options().map(|_| ()),
+            JobRequest::Text(_) => Ok(()),
+        }
+    }
+}
+
+/// A request as submitted to `POST /jobs`: the capture plus how to queue it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobSubmission {
+    #[serde(flatten)]
+    pub request: JobRequest,
+    /// Higher-priority jobs run first (default: 0)
+    #[serde(default)]
+    pub priority: i32,
+    /// URL the finished job's record is POSTed to
+    #[serde(default)]
+    pub webhook: Option<String>,
+}
+
+impl From<JobRequest> for JobSubmission {
+    fn from(request: JobRequest) -> Self {
+        Self {
+            request,
+            priority: 0,
+            webhook: None,
+        }
+    }
+}
+
+impl JobSubmission {
+    /// Fail now for submissions that could never run
+    pub fn validate(&self) -> Result<()> {
+        self.request.validate()?;
+        if let Some(webhook) = &self.webhook {
+            let url = url::Url::parse(webhook)?;
+            if !matches!(url.scheme(), "http" | "https") {
+                return Err(WebshotError::config(format!(
+                    "Webhook {} must be an http or https URL",
+                    webhook
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Where a job is in its lifecycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobState {
+    Queued,
+    Running,
+    Done,
+    Failed,
+}
+
+impl JobState {
+    pub fn is_finished(self) -> bool {
+        matches!(self, JobState::Done | JobState::Failed)
+    }
+}
+
+/// A queued job and, once it finished, its outcome
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub id: String,
+    pub state: JobState,
+    pub request: JobRequest,
+    #[serde(default)]
+    pub priority: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webhook: Option<String>,
+    /// RFC 3339 submission time
+    pub submitted_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<String>,
+    /// MIME type of the result
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    /// HTTP status of the captured page's main document
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http_status: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// What a finished job produced
+#[derive(Debug)]
+pub struct JobOutput {
+    pub content_type: String,
+    pub body: JobBody,
+    /// HTTP status of the captured page's main document
+    pub http_status: Option<u16>,
+}
+
+/// Bytes of a job's output
+#[derive(Debug)]
+pub enum JobBody {
+    Bytes(Vec<u8>),
+    /// A temporary file, removed when the output is dropped, so large
+    /// captures are never held in memory
+    File(tempfile::TempPath),
+}
+
+impl JobBody {
+    /// Write the output to `path`
+    fn store(&self, path: &Path) -> std::io::Result<()> {
+        match self {
+            JobBody::Bytes(bytes) => std::fs::write(path, bytes),
+            JobBody::File(file) => std::fs::copy(file, path).map(|_| ()),
+        }
+    }
+}
+
+/// Worker count, queue bound and allowed webhook hosts
+#[derive(Debug, Clone)]
+pub struct JobQueueOptions {
+    /// Jobs run at once
+    pub concurrency: usize,
+    /// Most jobs waiting to run before submissions are refused
+    pub capacity: usize,
+    /// Hosts webhooks may be sent to; jobs with other webhooks are refused
+    pub webhook_hosts: Vec<String>,
+    /// How long finished jobs and their results are kept (default: a day);
+    /// `None` keeps them
+    pub retention: Option<Duration>,
+}
+
+impl Default for JobQueueOptions {
+    fn default() -> Self {
+        Self {
+            concurrency: 2,
+            capacity: 1000,
+            webhook_hosts: Vec::new(),
+            retention: Some(Duration::from_secs(24 * 60 * 60)),
+        }
+    }
+}
+
+/// How long a webhook may take to answer
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Jobs by id, recorded in a directory so queued work survives restarts
+pub struct JobQueue {
+    dir: PathBuf,
+    /// Holds the directory when none was given, removing it on drop
+    _temp_dir: Option<tempfile::TempDir>,
+    options: JobQueueOptions,
+    jobs: Mutex<HashMap<String, Job>>,
+    /// Priority and id of each queued job, in submission order
+    queue: Mutex<VecDeque<(i32, String)>>,
+    queued: Notify,
+    /// Set once workers should stop taking jobs
+    closed: AtomicBool,
+    random: SystemRandom,
+    webhooks: reqwest::Client,
+}
+
+impl JobQueue {
+    /// Open the queue recorded in `dir`, queueing unfinished jobs again, or
+    /// keep jobs in a temporary directory when `dir` is `None`
+    pub fn open(dir: Option<PathBuf>, options: JobQueueOptions) -> Result<Self> {
+        let (dir, temp_dir) = match dir {
+            Some(dir) => {
+                std::fs::create_dir_all(&dir)?;
+                (dir, None)
+            }
+            None => {
+                let temp_dir = tempfile::Builder::new().prefix("webshot-jobs-").tempdir()?;
+                (temp_dir.path().to_path_buf(), Some(temp_dir))
+            }
+        };
+
+        let mut jobs = HashMap::new();
+        let mut unfinished = Vec::new();
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                let mut job: Job = match std::fs::read(&path)
+                    .map_err(WebshotError::from)
+                    .and_then(|json| Ok(serde_json::from_slice(&json)?))
+                {
+                    Ok(job) => job,
+                    Err(e) => {
+                        warn!("Skipping unreadable job record {}: {}", path.display(), e);
+                        continue;
+                    }
+                };
+                if !job.state.is_finished() {
+                    job.state = JobState::Queued;
+                    unfinished.push((job.submitted_at.clone(), job.priority, job.id.clone()));
+                }
+                jobs.insert(job.id.clone(), job);
+            }
+        }
+        unfinished.sort();
+        if !unfinished.is_empty() {
+            info!("Resuming {} unfinished job(s)", unfinished.len());
+        }
+
+        let queue = Self {
+            dir,
+            _temp_dir: temp_dir,
+            options: JobQueueOptions {
+                concurrency: options.concurrency.max(1),
+                ..options
+            },
+            jobs: Mutex::new(jobs),
+            queue: Mutex::new(
+                unfinished
+                    .into_iter()
+                    .map(|(_, priority, id)| (priority, id))
+                    .collect(),
+            ),
+            queued: Notify::new(),
+            closed: AtomicBool::new(false),
+            random: SystemRandom::new(),
+            webhooks: reqwest::Client::builder()
+                .timeout(WEBHOOK_TIMEOUT)
+                // A redirect could lead past the allowed hosts
+                .redirect(reqwest::redirect::Policy::none())
+                .build()
+                .map_err(|e| {
+                    WebshotError::config(format!("Cannot create webhook client: {}", e))
+                })?,
+        };
+        queue.prune();
+        Ok(queue)
+    }
+
+    /// Queue jobs, returning their ids in order. Nothing is queued when
+    /// the batch does not fit in the queue.
+    pub fn submit(&self, submissions: Vec<JobSubmission>) -> Result<Vec<String>> {
+        for submission in &submissions {
+            submission.validate()?;
+            if let Some(webhook) = &submission.webhook {
+                self.check_webhook_host(webhook)?;
+            }
+        }
+        // Retrying could never help a batch larger than the whole queue
+        if submissions.len() > self.options.capacity {
+            return Err(WebshotError::config(format!(
+                "A batch of {} jobs does not fit in the job queue of {}; split it up",
+                submissions.len(),
+                self.options.capacity
+            )));
+        }
+
+        let mut queue = self.queue.lock().unwrap();
+        if queue.len() + submissions.len() > self.options.capacity {
+            return Err(WebshotError::QueueFull {
+                capacity: self.options.capacity,
+            });
+        }
+
+        let mut ids = Vec::with_capacity(submissions.len());
+        for submission in submissions {
+            let job = Job {
+                id: self.new_id()?,
+                state: JobState::Queued,
+                request: submission.request,
+                priority: submission.priority,
+                webhook: submission.webhook,
+                submitted_at: chrono::Utc::now().to_rfc3339(),
+                finished_at: None,
+                content_type: None,
+                http_status: None,
+                error: None,
+            };
+            self.save(&job)?;
+            queue.push_back((job.priority, job.id.clone()));
+            ids.push(job.id.clone());
+            self.jobs.lock().unwrap().insert(job.id.clone(), job);
+        }
+        drop(queue);
+
+        for _ in &ids {
+            self.queued.notify_one();
+        }
+        Ok(ids)
+    }
+
+    pub fn get(&self, id: &str) -> Option<Job> {
+        self.jobs.lock().unwrap().get(id).cloned()
+    }
+
+    /// Directory the jobs are recorded in
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Output of a finished job
+    pub fn result(&self, id: &str) -> Result<Vec<u8>> {
+        Ok(std::fs::read(self.result_path(id))?)
+    }
+
+    /// File a finished job's output is stored in, for streaming it
+    pub fn result_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.result", id))
+    }
+
+    /// Remove finished jobs older than the retention, with their results,
+    /// returning how many were removed
+    pub fn prune(&self) -> usize {
+        let Some(retention) = self
+            .options
+            .retention
+            .and_then(|retention| chrono::Duration::from_std(retention).ok())
+        else {
+            return 0;
+        };
+        let cutoff = chrono::Utc::now() - retention;
+        let expired: Vec<String> = {
+            let mut jobs = self.jobs.lock().unwrap();
+            let expired: Vec<String> = jobs
+                .values()
+                .filter(|job| {
+                    job.finished_at
+                        .as_deref()
+                        .and_then(|time| chrono::DateTime::parse_from_rfc3339(time).ok())
+                        .is_some_and(|finished| finished < cutoff)
+                })
+                .map(|job| job.id.clone())
+                .collect();
+            for id in &expired {
+                jobs.remove(id);
+            }
+            expired
+        };
+
+        for id in &expired {
+            for path in [self.record_path(id), self.result_path(id)] {
+                match std::fs::remove_file(&path) {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                        warn!("Cannot remove expired job file {}: {}", path.display(), e)
+                    }
+                    _ => {}
+                }
+            }
+        }
+        if !expired.is_empty() {
+            debug!("Removed {} expired job(s)", expired.len());
+        }
+        expired.len()
+    }
+
+    /// Stop starting jobs: [`JobQueue::run`] returns once the running ones
+    /// have finished, and queued jobs stay recorded for the next start
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        self.queued.notify_waiters();
+    }
+
+    /// Run queued jobs with `execute`, `concurrency` at a time, until the
+    /// queue is [closed](JobQueue::close)
+    pub async fn run<F, Fut, E>(&self, execute: F)
+    where
+        F: Fn(JobRequest) -> Fut,
+        Fut: Future<Output = std::result::Result<JobOutput, E>>,
+        E: fmt::Display,
+    {
+        let workers = (0..self.options.concurrency).map(|_| self.work(&execute));
+        futures::future::join_all(workers).await;
+    }
+
+    async fn work<F, Fut, E>(&self, execute: &F)
+    where
+        F: Fn(JobRequest) -> Fut,
+        Fut: Future<Output = std::result::Result<JobOutput, E>>,
+        E: fmt::Display,
+    {
+        while let Some(id) = self.next().await {
+            let Some(job) = self.update(&id, |job| job.state = JobState::Running) else {
+                continue;
+            };
+            debug!("Running job {}", id);
+
+            let outcome = match execute(job.request).await {
+                Ok(output) => output
+                    .body
+                    .store(&self.result_path(&id))
+                    .map(|()| output)
+                    .map_err(|e| format!("Failed to store result: {}", e)),
+                Err(e) => Err(e.to_string()),
+            };
+            let finished = self.update(&id, |job| {
+                job.finished_at = Some(chrono::Utc::now().to_rfc3339());
+                match outcome {
+                    Ok(output) => {
+                        job.state = JobState::Done;
+                        job.content_type = Some(output.content_type);
+                        job.http_status = output.http_status;
+                    }
+                    Err(error) => {
+                        warn!("Job {} failed: {}", job.id, error);
+                        job.state = JobState::Failed;
+                        job.error = Some(error);
+                    }
+                }
+            });
+            if let Some(job) = finished {
+                self.call_webhook(&job).await;
+            }
+            self.prune();
+        }
+    }
+
+    /// Wait for the highest-priority queued job, oldest first among equals,
+    /// or `None` once the queue is closed
+    async fn next(&self) -> Option<String> {
+        loop {
+            // Registered before checking, so a close in between still wakes us
+            let queued = self.queued.notified();
+            if self.closed.load(Ordering::SeqCst) {
+                return None;
+            }
+            {
+                let mut queue = self.queue.lock().unwrap();
+                if let Some(index) = queue
+                    .iter()
+                    .enumerate()
+                    .max_by_key(|&(index, (priority, _))| (*priority, Reverse(index)))
+                    .map(|(index, _)| index)
+                {
+                    return queue.remove(index).map(|(_, id)| id);
+                }
+            }
+            queued.await;
+        }
+    }
+
+    /// Refuse webhooks to hosts outside [`JobQueueOptions::webhook_hosts`]
+    fn check_webhook_host(&self, webhook: &str) -> Result<()> {
+        let url = url::Url::parse(webhook)?;
+        let host = url.host_str().unwrap_or_default();
+        if self
+            .options
+            .webhook_hosts
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(host))
+        {
+            return Ok(());
+        }
+        Err(WebshotError::config(format!(
+            "Webhook host {} is not allowed; start serve with --webhook-allow {} to send webhooks there",
+            host, host
+        )))
+    }
+
+    /// POST a finished job's record to its webhook. Failures are logged,
+    /// since the job's outcome is recorded either way.
+    async fn call_webhook(&self, job: &Job) {
+        let Some(webhook) = &job.webhook else {
+            return;
+        };
+        let sent = self
+            .webhooks
+            .post(webhook)
+            .json(job)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        match sent {
+            Ok(_) => debug!("Notified {} of job {}", webhook, job.id),
+            Err(e) => warn!("Webhook {} for job {} failed: {}", webhook, job.id, e),
+        }
+    }
+
+    /// Change a job and record it, returning the changed job
+    fn update(&self, id: &str, change: impl FnOnce(&mut Job)) -> Option<Job> {
+        let mut jobs = self.jobs.lock().unwrap();
+        let job = jobs.get_mut(id)?;
+        change(job);
+        if let Err(e) = self.save(job) {
+            warn!("Failed to record job {}: {}", id, e);
+        }
+        Some(job.clone())
+    }
+
+    fn save(&self, job: &Job) -> Result<()> {
+        let json = serde_json::to_vec_pretty(job)?;
+        std::fs::write(self.record_path(&job.id), json)?;
+        Ok(())
+    }
+
+    fn record_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+
+    fn new_id(&self) -> Result<String> {
+        let mut bytes = [0u8; 12];
+        self.random
+            .fill(&mut bytes)
+            .map_err(|_| WebshotError::config("No randomness available for job ids"))?;
+        Ok(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    fn text_job(url: &str) -> JobSubmission {
+        serde_json::from_value(serde_json::json!({"kind": "text", "url": url})).unwrap()
+    }
+
+    async fn run_until_finished(queue: &JobQueue, ids: &[String]) {
+        let execute = |request: JobRequest| async move {
+            match request {
+                JobRequest::Text(request) if request.url.contains("broken") => {
+                    Err("page crashed".to_string())
+                }
+                JobRequest::Text(request) => Ok(JobOutput {
+                    content_type: "text/plain".to_string(),
+                    body: JobBody::Bytes(request.url.into_bytes()),
+                    http_status: None,
+                }),
+                _ => unreachable!(),
+            }
+        };
+        let finished = async {
+            while !ids
+                .iter()
+                .all(|id| queue.get(id).unwrap().state.is_finished())
+            {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), async {
+            tokio::select! {
+                _ = queue.run(execute) => {}
+                _ = finished => {}
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_jobs_run_to_results() {
+        let queue = JobQueue::open(None, JobQueueOptions::default()).unwrap();
+        let ids = queue
+            .submit(vec![
+                text_job("https://example.com"),
+                text_job("https://broken.example.com"),
+            ])
+            .unwrap();
+        assert_eq!(queue.get(&ids[0]).unwrap().state, JobState::Queued);
+
+        run_until_finished(&queue, &ids).await;
+        let done = queue.get(&ids[0]).unwrap();
+        assert_eq!(done.state, JobState::Done);
+        assert_eq!(done.content_type.as_deref(), Some("text/plain"));
+        assert_eq!(queue.result(&ids[0]).unwrap(), b"https://example.com");
+
+        let failed = queue.get(&ids[1]).unwrap();
+        assert_eq!(failed.state, JobState::Failed);
+        assert_eq!(failed.error.as_deref(), Some("page crashed"));
+    }
+
+    #[tokio::test]
+    async fn test_file_outputs_are_stored_and_removed() {
+        let queue = JobQueue::open(None, JobQueueOptions::default()).unwrap();
+        let ids = queue.submit(vec![text_job("https://example.com")]).unwrap();
+        let output = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(output.path(), b"captured").unwrap();
+        let output = output.into_temp_path();
+        let output_path = output.to_path_buf();
+
+        let output = Mutex::new(Some(output));
+        let execute = |_| {
+            let output = output.lock().unwrap().take().unwrap();
+            queue.close();
+            async move {
+                Ok::<_, String>(JobOutput {
+                    content_type: "image/png".to_string(),
+                    body: JobBody::File(output),
+                    http_status: Some(200),
+                })
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), queue.run(execute))
+            .await
+            .unwrap();
+        assert_eq!(queue.get(&ids[0]).unwrap().state, JobState::Done);
+        assert_eq!(queue.result(&ids[0]).unwrap(), b"captured");
+        assert!(!output_path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_unfinished_jobs_resume_after_restart() {
+        let dir = TempDir::new().unwrap();
+        let options = JobQueueOptions {
+            concurrency: 1,
+            capacity: 2,
+            ..Default::default()
+        };
+        let ids = {
+            let queue = JobQueue::open(Some(dir.path().to_path_buf()), options.clone()).unwrap();
+            let ids = queue
+                .submit(vec![
+                    text_job("https://a.example"),
+                    text_job("https://b.example"),
+                ])
+                .unwrap();
+            assert!(matches!(
+                queue.submit(vec![text_job("https://c.example")]),
+                Err(WebshotError::QueueFull { capacity: 2 })
+            ));
+            ids
+        };
+
+        let queue = JobQueue::open(Some(dir.path().to_path_buf()), options).unwrap();
+        assert_eq!(queue.get(&ids[1]).unwrap().state, JobState::Queued);
+        run_until_finished(&queue, &ids).await;
+        assert_eq!(queue.result(&ids[1]).unwrap(), b"https://b.example");
+    }
+
+    #[tokio::test]
+    async fn test_finished_jobs_expire_after_the_retention() {
+        let dir = TempDir::new().unwrap();
+        let options = JobQueueOptions {
+            retention: Some(Duration::from_secs(60)),
+            ..Default::default()
+        };
+        let queue = JobQueue::open(Some(dir.path().to_path_buf()), options.clone()).unwrap();
+        let ids = queue
+            .submit(vec![
+                text_job("https://old.example"),
+                text_job("https://new.example"),
+            ])
+            .unwrap();
+        run_until_finished(&queue, &ids).await;
+        assert_eq!(queue.prune(), 0);
+
+        queue.update(&ids[0], |job| {
+            job.finished_at = Some((chrono::Utc::now() - chrono::Duration::hours(1)).to_rfc3339());
+        });
+        drop(queue);
+        let queue = JobQueue::open(Some(dir.path().to_path_buf()), options).unwrap();
+        assert!(queue.get(&ids[0]).is_none());
+        assert!(!dir.path().join(format!("{}.json", ids[0])).exists());
+        assert!(!dir.path().join(format!("{}.result", ids[0])).exists());
+        assert_eq!(queue.result(&ids[1]).unwrap(), b"https://new.example");
+    }
+
+    #[test]
+    fn test_invalid_requests_are_refused_up_front() {
+        let queue = JobQueue::open(None, JobQueueOptions::default()).unwrap();
+        let request = serde_json::from_value(
+            serde_json::json!({"kind": "screenshot", "url": "https://example.com", "format": "bmp"}),
+        )
+        .unwrap();
+        assert!(queue.submit(vec![request]).is_err());
+        let mut submission = text_job("https://example.com");
+        submission.webhook = Some("ftp://hooks.example".to_string());
+        assert!(queue.submit(vec![submission]).is_err());
+        assert!(queue.queue.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_webhooks_only_go_to_allowed_hosts() {
+        let options = JobQueueOptions {
+            webhook_hosts: vec!["hooks.example.com".to_string()],
+            ..Default::default()
+        };
+        let queue = JobQueue::open(None, options).unwrap();
+        for (webhook, allowed) in [
+            ("https://hooks.example.com/done", true),
+            ("https://HOOKS.example.com/done", true),
+            ("http://127.0.0.1:8080/admin", false),
+            ("http://169.254.169.254/latest/meta-data", false),
+            ("https://hooks.example.com.evil.example/done", false),
+        ] {
+            let mut submission = text_job("https://example.com");
+            submission.webhook = Some(webhook.to_string());
+            let submitted = queue.submit(vec![submission]);
+            assert_eq!(submitted.is_ok(), allowed, "{}", webhook);
+        }
+    }
+
+    #[test]
+    fn test_batches_larger_than_the_queue_are_refused() {
+        let options = JobQueueOptions {
+            capacity: 1,
+            ..Default::default()
+        };
+        let queue = JobQueue::open(None, options).unwrap();
+        let error = queue
+            .submit(vec![
+                text_job("https://a.example"),
+                text_job("https://b.example"),
+            ])
+            .unwrap_err();
+        assert!(matches!(error, WebshotError::Config(_)), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_higher_priority_jobs_run_first() {
+        let queue = JobQueue::open(None, JobQueueOptions::default()).unwrap();
+        let submission: JobSubmission = serde_json::from_value(
+            serde_json::json!({"kind": "text", "url": "https://urgent.example", "priority": 5}),
+        )
+        .unwrap();
+        assert_eq!(submission.priority, 5);
+        let ids = queue
+            .submit(vec![
+                text_job("https://a.example"),
+                submission,
+                text_job("https://b.example"),
+            ])
+            .unwrap();
+
+        let mut order = Vec::new();
+        for _ in &ids {
+            order.push(queue.next().await.unwrap());
+        }
+        assert_eq!(order, [ids[1].clone(), ids[0].clone(), ids[2].clone()]);
+    }
+
+    #[tokio::test]
+    async fn test_closing_lets_running_jobs_finish() {
+        let options = JobQueueOptions {
+            concurrency: 1,
+            capacity: 2,
+            ..Default::default()
+        };
+        let queue = JobQueue::open(None, options).unwrap();
+        let ids = queue
+            .submit(vec![
+                text_job("https://a.example"),
+                text_job("https://b.example"),
+            ])
+            .unwrap();
+
+        let execute = |request: JobRequest| {
+            queue.close();
+            async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                Ok::<_, String>(JobOutput {
+                    content_type: "text/plain".to_string(),
+                    body: JobBody::Bytes(serde_json::to_vec(&request).unwrap()),
+                    http_status: None,
+                })
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), queue.run(execute))
+            .await
+            .unwrap();
+        assert_eq!(queue.get(&ids[0]).unwrap().state, JobState::Done);
+        assert_eq!(queue.get(&ids[1]).unwrap().state, JobState::Queued);
+    }
+
+    #[tokio::test]
+    async fn test_webhook_receives_finished_job() {
+        use hyper::service::{make_service_fn, service_fn};
+        use hyper::{Body, Request, Response, Server};
+
+        let (sender, mut received) = tokio::sync::mpsc::unbounded_channel();
+        let make_service = make_service_fn(move |_| {
+            let sender = sender.clone();
+            async move {
+                Ok::<_, std::convert::Infallible>(service_fn(move |request: Request<Body>| {
+                    let sender = sender.clone();
+                    async move {
+                        let body = hyper::body::to_bytes(request.into_body()).await?;
+                        sender.send(body).unwrap();
+                        Ok::<_, hyper::Error>(Response::new(Body::empty()))
+                    }
+                }))
+            }
+        });
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let webhook = format!("http://{}/done", server.local_addr());
+        tokio::spawn(server);
+
+        let options = JobQueueOptions {
+            webhook_hosts: vec!["127.0.0.1".to_string()],
+            ..Default::default()
+        };
+        let queue = JobQueue::open(None, options).unwrap();
+        let mut submission = text_job("https://example.com");
+        submission.webhook = Some(webhook);
+        let ids = queue.submit(vec![submission]).unwrap();
+
+        let execute = |_| async {
+            Ok::<_, String>(JobOutput {
+                content_type: "text/plain".to_string(),
+                body: JobBody::Bytes(Vec::new()),
+                http_status: None,
+            })
+        };
+        let body = tokio::time::timeout(Duration::from_secs(5), async {
+            tokio::select! {
+                _ = queue.run(execute) => unreachable!(),
+                body = received.recv() => body.unwrap(),
+            }
+        })
+        .await
+        .unwrap();
+        let job: Job = serde_json::from_slice(&body).unwrap();
+        assert_eq!(job.id, ids[0]);
+        assert_eq!(job.state, JobState::Done);
+    }
+}
@@ -0,0 +1,363 @@
+//! Structural layout comparison: element position and size drift.
+//!
+//! Pixel diffs flag every anti-aliasing change and say little about *what*
+//! moved. A [`LayoutSnapshot`] records the bounding box of the first element
+//! matching each of a list of selectors, in page coordinates; comparing two
+//! snapshots (two URLs, or a saved snapshot and a fresh capture) reports how
+//! far each element moved and resized in pixels, failing elements whose
+//! drift exceeds a threshold or that exist on one side only.
+
+use crate::error::{Result, WebshotError};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+
+/// Drift in pixels tolerated by default, absorbing subpixel rounding
+pub const DEFAULT_DRIFT_THRESHOLD: f64 = 1.0;
+
+/// Evaluates to a JSON array with the page-coordinate bounding box of the
+/// first match of each selector in `SELECTORS`, or `null` where none matches
+const LAYOUT_SCRIPT: &str = r#"
+(() => {
+    const selectors = SELECTORS;
+    return JSON.stringify(selectors.map((selector) => {
+        const element = document.querySelector(selector);
+        if (!element) {
+            return null;
+        }
+        const rect = element.getBoundingClientRect();
+        return {
+            x: rect.left + window.scrollX,
+            y: rect.top + window.scrollY,
+            width: rect.width,
+            height: rect.height,
+        };
+    }));
+})()
+"#;
+
+/// Script returning the bounding boxes of `selectors` as a JSON string
+pub fn layout_script(selectors: &[String]) -> Result<String> {
+    Ok(LAYOUT_SCRIPT.replace("SELECTORS", &serde_json::to_string(selectors)?))
+}
+
+/// Position and size of an element in CSS pixels, relative to the top left
+/// of the page
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ElementBox {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// Where the element a selector matched was laid out
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ElementLayout {
+    pub selector: String,
+    /// `None` when no element matched
+    pub bounds: Option<ElementBox>,
+}
+
+/// Bounding boxes of the elements a list of selectors matched on one page
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LayoutSnapshot {
+    pub url: String,
+    /// Viewport width the page was laid out at
+    pub width: u32,
+    /// Viewport height the page was laid out at
+    pub height: u32,
+    pub elements: Vec<ElementLayout>,
+}
+
+impl LayoutSnapshot {
+    /// Snapshot from the JSON [`layout_script`] returned for `selectors`
+    pub fn from_script_result(
+        url: &str,
+        width: u32,
+        height: u32,
+        selectors: &[String],
+        json: &str,
+    ) -> Result<Self> {
+        let bounds: Vec<Option<ElementBox>> = serde_json::from_str(json)?;
+        if bounds.len() != selectors.len() {
+            return Err(WebshotError::javascript(format!(
+                "Layout script returned {} boxes for {} selectors",
+                bounds.len(),
+                selectors.len()
+            )));
+        }
+        Ok(Self {
+            url: url.to_string(),
+            width,
+            height,
+            elements: selectors
+                .iter()
+                .zip(bounds)
+                .map(|(selector, bounds)| ElementLayout {
+                    selector: selector.clone(),
+                    bounds,
+                })
+                .collect(),
+        })
+    }
+
+    /// Read a snapshot saved with [`LayoutSnapshot::save`]
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// Write the snapshot as JSON, to compare later captures against
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Bounds of the element `selector` matched, `None` when the snapshot
+    /// has no such selector or it matched nothing
+    pub fn bounds(&self, selector: &str) -> Option<ElementBox> {
+        self.elements
+            .iter()
+            .find(|element| element.selector == selector)
+            .and_then(|element| element.bounds)
+    }
+}
+
+/// How one element's layout changed between two snapshots
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ElementDrift {
+    pub selector: String,
+    pub expected: Option<ElementBox>,
+    pub actual: Option<ElementBox>,
+    /// Largest of the position and size changes in pixels; `None` when the
+    /// element is missing on a side
+    pub drift: Option<f64>,
+    pub passed: bool,
+}
+
+impl ElementDrift {
+    fn new(
+        selector: &str,
+        expected: Option<ElementBox>,
+        actual: Option<ElementBox>,
+        threshold: f64,
+    ) -> Self {
+        let drift = match (expected, actual) {
+            (Some(expected), Some(actual)) => Some(
+                [
+                    actual.x - expected.x,
+                    actual.y - expected.y,
+                    actual.width - expected.width,
+                    actual.height - expected.height,
+                ]
+                .iter()
+                .fold(0.0, |max: f64, delta| max.max(delta.abs())),
+            ),
+            _ => None,
+        };
+        let passed = match drift {
+            Some(drift) => drift <= threshold,
+            // Missing on both sides is no change
+            None => expected.is_none() && actual.is_none(),
+        };
+        Self {
+            selector: selector.to_string(),
+            expected,
+            actual,
+            drift,
+            passed,
+        }
+    }
+}
+
+/// Per-element drift between an expected and an actual layout
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LayoutComparison {
+    /// Drift in pixels tolerated per element
+    pub threshold: f64,
+    pub elements: Vec<ElementDrift>,
+    pub passed: bool,
+}
+
+impl LayoutComparison {
+    /// Largest drift of an element found on both sides
+    pub fn max_drift(&self) -> f64 {
+        self.elements
+            .iter()
+            .filter_map(|element| element.drift)
+            .fold(0.0, f64::max)
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &ElementDrift> {
+        self.elements.iter().filter(|element| !element.passed)
+    }
+}
+
+/// Compare the elements of `actual` with those of `expected`, failing
+/// elements that moved or resized by more than `threshold` pixels or exist
+/// on one side only. Selectors are listed in `expected`'s order, followed
+/// by any only `actual` has.
+pub fn compare_layouts(
+    expected: &LayoutSnapshot,
+    actual: &LayoutSnapshot,
+    threshold: f64,
+) -> LayoutComparison {
+    let mut selectors: Vec<&str> = expected
+        .elements
+        .iter()
+        .map(|element| element.selector.as_str())
+        .collect();
+    for element in &actual.elements {
+        if !selectors.contains(&element.selector.as_str()) {
+            selectors.push(&element.selector);
+        }
+    }
+
+    let elements: Vec<ElementDrift> = selectors
+        .into_iter()
+        .map(|selector| {
+            ElementDrift::new(
+                selector,
+                expected.bounds(selector),
+                actual.bounds(selector),
+                threshold,
+            )
+        })
+        .collect();
+    LayoutComparison {
+        threshold,
+        passed: elements.iter().all(|element| element.passed),
+        elements,
+    }
+}
+
+impl fmt::Display for LayoutComparison {
+    /// A line per element with its position and size changes, then a
+    /// summary line
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for element in &self.elements {
+            let verdict = if element.passed { "ok" } else { "FAIL" };
+            match (element.expected, element.actual) {
+                (Some(expected), Some(actual)) => writeln!(
+                    f,
+                    "{}  x {:+.1} y {:+.1} width {:+.1} height {:+.1}  {}",
+                    element.selector,
+                    actual.x - expected.x,
+                    actual.y - expected.y,
+                    actual.width - expected.width,
+                    actual.height - expected.height,
+                    verdict
+                )?,
+                (Some(_), None) => writeln!(f, "{}  missing  {}", element.selector, verdict)?,
+                (None, Some(_)) => {
+                    writeln!(f, "{}  only in actual  {}", element.selector, verdict)?
+                }
+                (None, None) => writeln!(f, "{}  not found on either side", element.selector)?,
+            }
+        }
+        writeln!(
+            f,
+            "{} of {} elements drifted beyond {}px (max drift {:.1}px)",
+            self.failures().count(),
+            self.elements.len(),
+            self.threshold,
+            self.max_drift()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `x`, `y`, `width` and `height` of an element, `None` when missing
+    type Bounds = Option<(f64, f64, f64, f64)>;
+
+    fn snapshot(elements: &[(&str, Bounds)]) -> LayoutSnapshot {
+        LayoutSnapshot {
+            url: "https://example.com".to_string(),
+            width: 1280,
+            height: 800,
+            elements: elements
+                .iter()
+                .map(|(selector, bounds)| ElementLayout {
+                    selector: selector.to_string(),
+                    bounds: bounds.map(|(x, y, width, height)| ElementBox {
+                        x,
+                        y,
+                        width,
+                        height,
+                    }),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_compare_layouts_reports_drift_per_element() {
+        let expected = snapshot(&[
+            ("header", Some((0.0, 0.0, 1280.0, 80.0))),
+            ("nav", Some((0.0, 80.0, 1280.0, 40.0))),
+            ("footer", Some((0.0, 2000.0, 1280.0, 200.0))),
+            (".promo", None),
+        ]);
+        let actual = snapshot(&[
+            ("header", Some((0.0, 0.0, 1280.0, 80.4))),
+            ("nav", Some((0.0, 96.0, 1280.0, 40.0))),
+            (".promo", None),
+            (".cookie-banner", Some((0.0, 700.0, 1280.0, 100.0))),
+        ]);
+
+        let comparison = compare_layouts(&expected, &actual, 1.0);
+        assert!(!comparison.passed);
+        let verdicts: Vec<_> = comparison
+            .elements
+            .iter()
+            .map(|element| (element.selector.as_str(), element.passed))
+            .collect();
+        assert_eq!(
+            verdicts,
+            [
+                ("header", true),
+                ("nav", false),
+                ("footer", false),
+                (".promo", true),
+                (".cookie-banner", false),
+            ]
+        );
+        assert_eq!(comparison.elements[1].drift, Some(16.0));
+        assert_eq!(comparison.max_drift(), 16.0);
+
+        let report = comparison.to_string();
+        assert!(report.contains("nav  x +0.0 y +16.0 width +0.0 height +0.0  FAIL\n"));
+        assert!(report.contains("footer  missing  FAIL\n"));
+        assert!(report.ends_with("3 of 5 elements drifted beyond 1px (max drift 16.0px)\n"));
+
+        assert!(compare_layouts(&expected, &expected, 0.0).passed);
+    }
+
+    #[test]
+    fn test_snapshot_from_script_result() {
+        let selectors = vec!["header".to_string(), "#gone".to_string()];
+        let snapshot = LayoutSnapshot::from_script_result(
+            "https://example.com",
+            1280,
+            800,
+            &selectors,
+            r#"[{"x":0,"y":10.5,"width":1280,"height":80},null]"#,
+        )
+        .unwrap();
+        assert_eq!(snapshot.bounds("header").unwrap().y, 10.5);
+        assert_eq!(snapshot.bounds("#gone"), None);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("layout.json");
+        snapshot.save(&path).unwrap();
+        assert_eq!(LayoutSnapshot::load(&path).unwrap(), snapshot);
+
+        assert!(LayoutSnapshot::from_script_result("u", 1, 1, &selectors, "[null]").is_err());
+        assert!(layout_script(&selectors)
+            .unwrap()
+            .contains(r##"const selectors = ["header","#gone"];"##));
+    }
+}
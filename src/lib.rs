@@ -1,14 +1,81 @@
+pub mod adblock;
+pub mod assets;
+pub mod backend;
+pub mod baseline;
+pub mod blocking;
+pub mod book;
 pub mod browser;
+pub mod cache;
+pub mod capabilities;
+#[cfg(feature = "chaos")]
+pub mod chaos;
+pub mod color;
 pub mod comparison;
 pub mod config;
+pub mod console_log;
+pub mod crawl;
+pub mod dedupe;
+pub mod dev_server;
+pub mod devices;
+pub mod diff_report;
+pub mod dir_compare;
+pub mod elements;
+pub mod email;
 pub mod error;
+pub mod explain;
+pub mod flags;
+pub mod gallery;
+pub mod git;
+pub mod har;
+pub mod health;
+pub mod hooks;
+pub mod i18n;
+pub mod jobs;
+pub mod layout;
+pub mod manifest;
+pub mod mocks;
+pub mod monitor;
+pub mod network_idle;
+pub mod outline;
 pub mod output;
+pub mod overlays;
+pub mod page_errors;
+pub mod paper;
+pub mod pdf_links;
+pub mod picker;
+pub mod plugins;
+pub mod pool;
+pub mod porcelain;
+pub mod profile;
+pub mod project;
+pub mod rate_limit;
+pub mod repl;
+pub mod retention;
+pub mod retry;
 pub mod screenshot;
+pub mod script;
+pub mod server;
+pub mod session;
+pub mod signing;
+pub mod sitemap;
+pub mod ssim;
+pub mod steps;
+pub mod suite;
+pub mod testkit;
+pub mod tiled;
+pub mod timing;
+pub mod tls;
+pub mod tui;
+pub mod validate;
+pub mod wait;
 
 pub use error::{Result, WebshotError};
 
 // Re-export commonly used types
+pub use backend::BrowserBackend;
 pub use browser::Browser;
 pub use comparison::{ComparisonOptions, ComparisonResult, ImageComparator};
 pub use config::{Config, ScreenshotConfig};
-pub use screenshot::ScreenshotOptions;
+pub use devices::DevicePreset;
+pub use pool::{BrowserPool, PoolOptions};
+pub use screenshot::{CaptureMode, ScreenshotOptions};
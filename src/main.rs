@@ -1,10 +1,75 @@
-use clap::{Parser, Subcommand};
-use std::path::PathBuf;
-use tracing::info;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use clap::{
+    error::ErrorKind, parser::ValueSource, ArgMatches, Args, CommandFactory, FromArgMatches,
+    Parser, Subcommand,
+};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tracing::{info, warn};
+use tracing_subscriber::{
+    fmt::writer::BoxMakeWriter, layer::SubscriberExt, util::SubscriberInitExt,
+};
 use webshot::{
-    config::validate_navigation_url, output::OutputHandler, Browser, ComparisonOptions, Config,
-    ImageComparator, Result, ScreenshotOptions,
+    adblock::FilterList,
+    assets::{asset_client, download_assets, AssetManifest, ASSET_MANIFEST},
+    backend::{PdfOptions, TextOptions},
+    baseline::{BaselineStore, PutCondition},
+    blocking::{BlockedResource, RequestBlocker},
+    book::{build_book, pdf_title, Chapter},
+    browser::{devtools_http_url, DEVTOOLS_FLAG, FONT_RENDERING_FLAGS},
+    capabilities::executable_capabilities,
+    color::ColorProfile,
+    comparison::{DiffStyle, DEFAULT_DELTA_E_TOLERANCE, DEFAULT_REGION_GAP},
+    config::{
+        validate_navigation_url, validate_proxy_url, AuthConfig, ConfigFormat, DefaultConfig,
+        HttpHeader, PdfBatchConfig,
+    },
+    crawl::{
+        CrawlOptions, CrawlState, DuplicateMode, DEFAULT_DUPLICATE_THRESHOLD, DEFAULT_STATE_FILE,
+    },
+    dedupe::{dedupe_files, DedupeOptions, DedupeReport, LinkKind, DEDUPE_MANIFEST},
+    diff_report::write_diff_report,
+    dir_compare::compare_dirs,
+    email::{check_css, email_output, EmailWidth, DEFAULT_EMAIL_WIDTHS},
+    explain::{explain_config, Explanation, OptionSource},
+    flags::{default_flag, DEFAULT_FLAGS},
+    gallery::BatchReport,
+    git::BaselineChange,
+    health::{disk_writable, drain_on_shutdown, HealthReport},
+    i18n::{self, tr, Lang},
+    jobs::{JobQueue, JobQueueOptions},
+    layout::{compare_layouts, LayoutSnapshot, DEFAULT_DRIFT_THRESHOLD},
+    manifest::{RunManifest, RUN_MANIFEST},
+    mocks::Mocks,
+    monitor::{BatchMonitor, JobStatus},
+    network_idle::NetworkIdle,
+    outline::OutlineFormat,
+    output::{format_file_size, OutputHandler, OutputLayout},
+    overlays::{OverlayChoice, OverlayDismissal},
+    paper::PaperSize,
+    pool::PoolOptions,
+    porcelain::{PorcelainFormat, Record},
+    project,
+    rate_limit::RateLimit,
+    repl::{Repl, ReplCommand},
+    retention::{parse_age, parse_size, prune, PruneReport, RetentionPolicy},
+    retry::RetryPolicy,
+    screenshot::{
+        read_init_scripts, CaptureMode, ClipRegion, ColorScheme, TiffCompression,
+        DEFAULT_MASK_COLOR,
+    },
+    script::{run_script, SCRIPT_FUNCTIONS},
+    server::{serve, Api},
+    session::SessionState,
+    signing::UrlSigner,
+    sitemap::{fetch_sitemap_urls, SitemapOptions},
+    suite::{score_run, SuiteReport, SUITE_REPORT},
+    timing::Phase,
+    validate::validate_config,
+    wait::{parse_duration, WaitCondition},
+    Browser, BrowserPool, ComparisonOptions, Config, DevicePreset, ImageComparator, Result,
+    ScreenshotConfig, ScreenshotOptions,
 };
 
 #[derive(Parser)]
@@ -27,14 +92,6 @@ struct Cli {
     #[arg(short, long, value_name = "FILE")]
     output: Option<PathBuf>,
 
-    /// Viewport width
-    #[arg(short, long, default_value = "1280")]
-    width: u32,
-
-    /// Viewport height
-    #[arg(short = 'H', long, default_value = "800")]
-    height: u32,
-
     /// CSS selector for element screenshot
     #[arg(short, long, value_name = "SELECTOR")]
     selector: Option<String>,
@@ -43,48 +100,505 @@ struct Cli {
     #[arg(short, long, value_name = "SCRIPT")]
     javascript: Option<String>,
 
-    /// Wait for element to appear (CSS selector)
-    #[arg(long, value_name = "SELECTOR")]
-    wait_for: Option<String>,
+    /// Capture the full scrollable page, loading lazy content first (same as --capture full-page)
+    #[arg(long)]
+    full_page: bool,
 
-    /// Timeout in seconds
-    #[arg(short, long, default_value = "30")]
-    timeout: u64,
+    /// Part of the page to capture: viewport (the --width x --height window as shown, default) or full-page
+    #[arg(long, value_name = "MODE", conflicts_with = "full_page")]
+    capture: Option<CaptureMode>,
+
+    /// Pin sticky and fixed elements (headers, cookie bars) where they are on load, for full-page captures
+    #[arg(long)]
+    freeze_sticky: bool,
+
+    /// Capture the full page as printed on SIZE (default A4), marking where each PDF page ends
+    #[arg(
+        long,
+        value_name = "SIZE",
+        num_args = 0..=1,
+        default_missing_value = "A4",
+        conflicts_with_all = ["selector", "clip", "auto_height", "widths", "capture"]
+    )]
+    paginate_preview: Option<PaperSize>,
+
+    /// Resize the viewport to the page's content height before capturing
+    #[arg(long, conflicts_with_all = ["full_page", "clip", "selector"])]
+    auto_height: bool,
+
+    /// Largest viewport height --auto-height may resize to
+    #[arg(long, value_name = "PX", requires = "auto_height", value_parser = clap::value_parser!(u32).range(1..))]
+    max_height: Option<u32>,
+
+    /// Emulate a touch screen (touch events, coarse pointer, no hover)
+    #[arg(long)]
+    touch: bool,
+
+    /// Capture only a page region (format: x,y,width,height)
+    #[arg(long, value_name = "X,Y,WIDTH,HEIGHT")]
+    clip: Option<ClipRegion>,
+
+    /// Paint elements matching this CSS selector over before capture (repeatable)
+    #[arg(long = "mask", value_name = "SELECTOR", action = clap::ArgAction::Append)]
+    mask_selectors: Vec<String>,
+
+    /// CSS color masked elements are painted with
+    #[arg(long, value_name = "COLOR", default_value = DEFAULT_MASK_COLOR)]
+    mask_color: String,
+
+    /// Scroll through the page once before capture to trigger lazy content
+    #[arg(long)]
+    trigger_lazy: bool,
+
+    /// Lazy-load scroll step in pixels (default: viewport height)
+    #[arg(long, value_name = "PIXELS")]
+    lazy_step: Option<u32>,
+
+    /// Settle delay after each lazy-load scroll step in milliseconds
+    #[arg(long, value_name = "MS", default_value = "100")]
+    lazy_settle: u64,
+
+    /// Emulate a device preset (e.g. "iPhone 14 Pro"), overriding size and retina
+    #[arg(long, value_name = "NAME")]
+    device: Option<DevicePreset>,
+
+    /// Capture at several viewport widths (e.g. 375,768,1280), suffixing outputs with the width
+    #[arg(
+        long,
+        value_name = "WIDTHS",
+        value_delimiter = ',',
+        conflicts_with = "device"
+    )]
+    widths: Vec<u32>,
+
+    /// Freeze animations, transitions, carets and videos for deterministic captures
+    #[arg(long)]
+    stabilize: bool,
+
+    /// Hide scrollbars (implied by --stabilize)
+    #[arg(long)]
+    hide_scrollbars: bool,
+
+    /// Emulate prefers-color-scheme (light, dark)
+    #[arg(long, value_name = "SCHEME")]
+    color_scheme: Option<ColorScheme>,
+
+    /// Don't scroll to the URL's #fragment target before capturing the viewport
+    #[arg(long)]
+    no_anchor_scroll: bool,
+
+    /// JavaScript file to run before any page script on every navigation (repeatable)
+    #[arg(long, value_name = "FILE", action = clap::ArgAction::Append)]
+    init_script: Vec<PathBuf>,
+
+    /// Quantize PNG output to a dithered palette of at most this many colors (2-256)
+    #[arg(
+        long,
+        value_name = "COLORS",
+        value_parser = clap::value_parser!(u16).range(2..=256),
+        conflicts_with = "grayscale"
+    )]
+    png_colors: Option<u16>,
+
+    /// Write PNG output as 8-bit grayscale
+    #[arg(long)]
+    grayscale: bool,
+
+    /// Restore cookies, localStorage and sessionStorage from a session file before navigation
+    #[arg(long, value_name = "FILE")]
+    load_session: Option<PathBuf>,
+
+    /// Save cookies, localStorage and sessionStorage to a session file after capture
+    #[arg(long, value_name = "FILE")]
+    save_session: Option<PathBuf>,
+
+    /// Compression for TIFF output (none, lzw, deflate, packbits)
+    #[arg(long, value_name = "METHOD", default_value = "lzw")]
+    tiff_compression: TiffCompression,
+
+    /// YAML file of mock rules answering matching requests with canned responses
+    #[arg(long, value_name = "FILE")]
+    mocks: Option<PathBuf>,
+
+    /// Click away or hide cookie banners and other consent overlays before capture
+    #[arg(long)]
+    auto_dismiss_overlays: bool,
+
+    /// YAML file of extra overlay rules (name, accept, reject, hide selectors)
+    #[arg(long, value_name = "FILE", requires = "auto_dismiss_overlays")]
+    overlay_rules: Option<PathBuf>,
+
+    /// Consent button to press when dismissing overlays (accept, reject)
+    #[arg(long, value_name = "CHOICE", default_value = "accept")]
+    overlay_choice: OverlayChoice,
+
+    /// Fail instead of capturing when the page returns HTTP 4xx or 5xx
+    #[arg(long)]
+    fail_on_http_error: bool,
+
+    /// Fail instead of capturing when the page logs a console error
+    #[arg(long)]
+    fail_on_console_error: bool,
+
+    /// Fail instead of capturing when the page throws an uncaught exception
+    #[arg(long)]
+    fail_on_js_exception: bool,
+
+    /// Write console messages, warnings and exceptions to this file (JSON for .json)
+    #[arg(long, value_name = "PATH")]
+    console_log: Option<PathBuf>,
+
+    /// Record network requests and responses to this HAR 1.2 file
+    #[arg(long, value_name = "PATH")]
+    har: Option<PathBuf>,
+
+    /// Inject delays, dropped requests and tab crashes into batch captures with this
+    /// probability per attempt (0.0-1.0), to test retries and pipelines
+    #[cfg(feature = "chaos")]
+    #[arg(long, value_name = "PROBABILITY", hide = true)]
+    chaos: Option<webshot::chaos::ChaosOptions>,
+
+    /// Seed of the --chaos fault sequence, to replay a run's faults
+    #[cfg(feature = "chaos")]
+    #[arg(long, value_name = "SEED", hide = true, requires = "chaos")]
+    chaos_seed: Option<u64>,
+
+    /// Verbose logging
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Language of messages, errors and reports (en, de, ja)
+    #[arg(long, value_name = "LANG", env = "WEBSHOT_LANG", default_value = "en")]
+    lang: Lang,
+
+    /// Print exactly one machine-readable line per operation on stdout (tsv or json) and no prose
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "tsv"
+    )]
+    porcelain: Option<PorcelainFormat>,
+
+    /// Before capturing, print where every option of each capture came from (flag, env, config entry, defaults, builtin)
+    #[arg(long)]
+    explain: bool,
+
+    #[command(flatten)]
+    image: ImageArgs,
+
+    #[command(flatten)]
+    waits: WaitArgs,
+
+    #[command(flatten)]
+    request: RequestArgs,
+
+    #[command(flatten)]
+    browser: BrowserArgs,
+
+    #[command(flatten)]
+    retry: RetryArgs,
+}
+
+/// Viewport and image options of the commands that capture screenshots
+#[derive(Args)]
+#[command(next_help_heading = "Image options")]
+struct ImageArgs {
+    /// Viewport width
+    #[arg(short, long, default_value = "1280")]
+    width: u32,
+
+    /// Viewport height
+    #[arg(short = 'H', long, default_value = "800")]
+    height: u32,
 
     /// Enable retina/high-DPI mode
     #[arg(long)]
     retina: bool,
 
-    /// JPEG/WebP quality (1-100, only for JPEG and WebP output)
+    /// JPEG/WebP/GIF quality (1-100, only for JPEG, WebP and GIF output)
     #[arg(short, long, value_parser = clap::value_parser!(u8).range(1..=100))]
     quality: Option<u8>,
+}
+
+/// When a loaded page is ready, for the commands that load one
+#[derive(Args)]
+#[command(next_help_heading = "Wait options")]
+struct WaitArgs {
+    /// Wait for an element or page state: SELECTOR, visible:SELECTOR, hidden:SELECTOR or
+    /// text:SELECTOR=TEXT, joined with " && " (all) or " || " (any)
+    #[arg(long, value_name = "CONDITION", value_parser = parse_wait_condition)]
+    wait_for: Option<String>,
 
-    /// Wait time in seconds before taking screenshot
+    /// Wait until no frame, iframes included, has had requests in flight for this long (e.g. 500ms)
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    idle_time: Option<Duration>,
+
+    /// Requests a frame may keep in flight (long polls, event streams) while counting as idle
+    #[arg(long, value_name = "N", default_value = "0", requires = "idle_time")]
+    max_inflight: usize,
+
+    /// Wait time in seconds once the page is ready, before capturing
     #[arg(long, default_value = "0")]
     wait: u64,
 
-    /// Custom user agent string
+    /// Timeout in seconds
+    #[arg(short, long, default_value = "30")]
+    timeout: u64,
+}
+
+impl WaitArgs {
+    /// Network idle wait of `--idle-time` and `--max-inflight`
+    fn network_idle(&self) -> Option<NetworkIdle> {
+        self.idle_time
+            .map(|idle_time| NetworkIdle::new(idle_time).max_inflight(self.max_inflight))
+    }
+}
+
+/// Credentials, headers and request blocking of the commands that load a page
+#[derive(Args)]
+#[command(next_help_heading = "Request options")]
+struct RequestArgs {
+    /// HTTP basic auth credentials (format: user:pass)
+    #[arg(long, value_name = "USER:PASS", conflicts_with_all = ["auth_user", "auth_pass"])]
+    auth: Option<AuthConfig>,
+
+    /// HTTP basic auth username
+    #[arg(long, value_name = "USER", requires = "auth_pass")]
+    auth_user: Option<String>,
+
+    /// HTTP basic auth password
+    #[arg(long, value_name = "PASS", requires = "auth_user")]
+    auth_pass: Option<String>,
+
+    /// Extra HTTP request header (format: "Name: value", repeatable)
+    #[arg(long = "header", value_name = "HEADER", action = clap::ArgAction::Append)]
+    headers: Vec<HttpHeader>,
+
+    /// Abort requests for these resource types (images, fonts, media, stylesheets, scripts, xhr)
+    #[arg(long, value_name = "TYPES", value_delimiter = ',')]
+    block_resources: Vec<BlockedResource>,
+
+    /// Block ad and tracker requests with the bundled filter list
     #[arg(long)]
-    user_agent: Option<String>,
+    adblock: bool,
 
-    /// Verbose logging
-    #[arg(short, long, action = clap::ArgAction::Count)]
-    verbose: u8,
+    /// EasyList-style filter list to use with --adblock instead of the bundled one
+    #[arg(long, value_name = "FILE", requires = "adblock")]
+    adblock_list: Option<PathBuf>,
+}
+
+impl RequestArgs {
+    /// Basic auth credentials from `--auth user:pass` or `--auth-user`/`--auth-pass`
+    fn auth(&self) -> Option<AuthConfig> {
+        self.auth.clone().or_else(|| {
+            self.auth_user.clone().map(|username| {
+                AuthConfig::new(username, self.auth_pass.clone().unwrap_or_default())
+            })
+        })
+    }
+
+    /// `--header` values as a header map; later duplicates win
+    fn header_map(&self) -> HashMap<String, String> {
+        self.headers
+            .iter()
+            .map(|header| (header.name.clone(), header.value.clone()))
+            .collect()
+    }
+
+    /// Filter list for `--adblock`: the `--adblock-list` file or the bundled list
+    fn adblock_filters(&self) -> Result<Option<Arc<FilterList>>> {
+        if !self.adblock {
+            return Ok(None);
+        }
+        let filters = FilterList::load_or_bundled(self.adblock_list.as_deref())?;
+        info!("Loaded {} ad and tracker filters", filters.len());
+        Ok(Some(Arc::new(filters)))
+    }
+
+    /// Requests `--block-resources` and `--adblock` abort
+    fn blocker(&self) -> Result<RequestBlocker> {
+        Ok(RequestBlocker::new(&self.block_resources).with_filters(self.adblock_filters()?))
+    }
+}
+
+/// The browser every command runs in. The options are global, so they can
+/// be given before or after the subcommand.
+#[derive(Args)]
+#[command(next_help_heading = "Browser options")]
+struct BrowserArgs {
+    /// Custom user agent string
+    #[arg(long, global = true)]
+    user_agent: Option<String>,
 
     /// Disable JavaScript
-    #[arg(long)]
+    #[arg(long, global = true)]
     no_javascript: bool,
 
     /// Custom Chrome/Chromium executable path
-    #[arg(long)]
+    #[arg(long, global = true)]
     chrome_path: Option<PathBuf>,
 
+    /// Attach to a running Chrome at this DevTools URL (ws://... or http://host:9222) instead of launching one
+    #[arg(
+        long,
+        value_name = "URL",
+        env = "WEBSHOT_CONNECT",
+        global = true,
+        conflicts_with_all = ["chrome_path", "headful", "devtools"]
+    )]
+    connect: Option<String>,
+
+    /// Attach to a Chrome in another container or host by the HOST:PORT of its remote debugging port (e.g. a browserless/chrome container), pinging it to keep the connection open and reconnecting when it drops
+    #[arg(
+        long,
+        value_name = "HOST:PORT",
+        env = "WEBSHOT_CONNECT_HOST",
+        global = true,
+        conflicts_with_all = ["connect", "chrome_path", "headful", "devtools"]
+    )]
+    connect_host: Option<String>,
+
+    /// Show the Chrome window while capturing, for debugging selectors, waits and scripts
+    #[arg(long, global = true)]
+    headful: bool,
+
+    /// Open DevTools for every tab (implies --headful)
+    #[arg(long, global = true)]
+    devtools: bool,
+
+    /// Render fonts without subpixel positioning, LCD antialiasing or hinting, for baselines shared across machines
+    #[arg(long, global = true)]
+    force_font_rendering: bool,
+
     /// Additional Chrome flags
-    #[arg(long, action = clap::ArgAction::Append)]
+    #[arg(long, action = clap::ArgAction::Append, global = true)]
     chrome_flag: Vec<String>,
+
+    /// Launch Chrome without this flag of the default set (e.g. disable-gpu); repeatable, see `webshot info --flags`
+    #[arg(long, value_name = "NAME", action = clap::ArgAction::Append, global = true)]
+    remove_flag: Vec<String>,
+
+    /// Leave the temporary profile of every launched Chrome on disk and log its path, for debugging
+    #[arg(long, global = true)]
+    keep_profiles: bool,
+
+    /// Color space to render in and tag image output with (srgb, display-p3)
+    #[arg(long, value_name = "PROFILE", default_value = "srgb", global = true)]
+    color_profile: ColorProfile,
+
+    /// Proxy server for all browser traffic (http, https, socks4 or socks5 URL)
+    #[arg(long, value_name = "URL", global = true)]
+    proxy: Option<String>,
+
+    /// Accept invalid and self-signed TLS certificates
+    #[arg(long, global = true)]
+    insecure: bool,
+
+    /// Trust the CA certificates in this PEM file for the browser session (by public key, for chains that include them)
+    #[arg(long, value_name = "PEM", global = true)]
+    ca_cert: Option<PathBuf>,
+
+    /// Milliseconds between rechecks of --wait-for conditions the page's DOM changes don't reveal
+    #[arg(
+        long,
+        value_name = "MS",
+        default_value = "100",
+        value_parser = clap::value_parser!(u64).range(1..),
+        global = true
+    )]
+    poll_interval: u64,
+}
+
+impl BrowserArgs {
+    /// Flags to launch Chrome with on top of the default set. Flags given
+    /// with --chrome-flag come last so they can override the profile, proxy
+    /// and TLS settings.
+    fn chrome_flags(&self) -> Result<Vec<String>> {
+        let mut chrome_flags = vec![self.color_profile.chrome_flag()];
+        if let Some(proxy) = &self.proxy {
+            validate_proxy_url(proxy)?;
+            chrome_flags.push(format!("--proxy-server={}", proxy));
+        }
+        if self.insecure {
+            chrome_flags.push(webshot::tls::INSECURE_FLAG.to_string());
+        }
+        if let Some(ca_cert) = &self.ca_cert {
+            chrome_flags.push(webshot::tls::ca_cert_flag(ca_cert)?);
+        }
+        if self.devtools {
+            chrome_flags.push(DEVTOOLS_FLAG.to_string());
+        }
+        if self.force_font_rendering {
+            chrome_flags.extend(FONT_RENDERING_FLAGS.iter().map(|flag| flag.to_string()));
+        }
+        chrome_flags.extend(self.chrome_flag.iter().cloned());
+        Ok(chrome_flags)
+    }
+}
+
+/// `--retries`, `--retry-delay`, `--rate-limit` and `--host-delay`, overriding
+/// how a batch config's defaults retry and pace page loads. The options are
+/// global, so they can be given before or after the subcommand.
+#[derive(Args, Clone, Copy)]
+#[command(next_help_heading = "Retry options")]
+struct RetryArgs {
+    /// Retry captures failing with navigation, timeout or browser errors up to N times
+    /// (overrides `retries` in batch configs)
+    #[arg(long, global = true, value_name = "N")]
+    retries: Option<u32>,
+
+    /// Delay before the first retry in milliseconds, doubled for each further retry
+    /// (overrides `retry_delay_ms` in batch configs)
+    #[arg(long, global = true, value_name = "MS")]
+    retry_delay: Option<u64>,
+
+    /// Load at most this many pages per second from one host in batch runs and crawls
+    /// (overrides `rate_limit.requests_per_second` in batch configs)
+    #[arg(long, global = true, value_name = "RPS")]
+    rate_limit: Option<f64>,
+
+    /// Wait at least this many milliseconds between page loads from one host in batch
+    /// runs and crawls (overrides `rate_limit.delay_ms` in batch configs)
+    #[arg(long, global = true, value_name = "MS")]
+    host_delay: Option<u64>,
+}
+
+impl RetryArgs {
+    fn apply(self, defaults: &mut DefaultConfig) {
+        if let Some(retries) = self.retries {
+            defaults.retries = retries;
+        }
+        if let Some(delay_ms) = self.retry_delay {
+            defaults.retry_delay_ms = delay_ms;
+        }
+        if let Some(rps) = self.rate_limit {
+            defaults.rate_limit.requests_per_second = Some(rps);
+        }
+        if let Some(delay_ms) = self.host_delay {
+            defaults.rate_limit.delay_ms = delay_ms;
+        }
+    }
+
+    /// Page load pacing for commands without a batch config
+    fn rate_limit(self) -> Result<RateLimit> {
+        let mut defaults = DefaultConfig::default();
+        self.apply(&mut defaults);
+        defaults.rate_limit.validate()?;
+        Ok(defaults.rate_limit)
+    }
+
+    /// Retry policy for commands without a batch config
+    fn policy(self) -> RetryPolicy {
+        let mut defaults = DefaultConfig::default();
+        self.apply(&mut defaults);
+        defaults.retry_policy()
+    }
 }
 
 #[derive(Subcommand)]
+#[allow(clippy::large_enum_variant)]
 enum Commands {
     /// Take a single screenshot
     #[command(alias = "shot")]
@@ -94,33 +608,136 @@ enum Commands {
         /// Output file path
         #[arg(short, long)]
         output: Option<PathBuf>,
-        /// Viewport width
-        #[arg(short, long, default_value = "1280")]
-        width: u32,
-        /// Viewport height
-        #[arg(short = 'H', long, default_value = "800")]
-        height: u32,
         /// CSS selector for element screenshot
         #[arg(short, long)]
         selector: Option<String>,
         /// JavaScript to execute
         #[arg(short, long)]
         javascript: Option<String>,
-        /// Wait for element
+        /// Capture the full scrollable page, loading lazy content first (same as --capture full-page)
         #[arg(long)]
-        wait_for: Option<String>,
-        /// Timeout in seconds
-        #[arg(short, long, default_value = "30")]
-        timeout: u64,
-        /// Enable retina mode
+        full_page: bool,
+        /// Part of the page to capture: viewport (the --width x --height window as shown, default) or full-page
+        #[arg(long, value_name = "MODE", conflicts_with = "full_page")]
+        capture: Option<CaptureMode>,
+        /// Pin sticky and fixed elements (headers, cookie bars) where they are on load, for full-page captures
         #[arg(long)]
-        retina: bool,
-        /// JPEG/WebP quality
-        #[arg(short, long)]
-        quality: Option<u8>,
-        /// Wait time before screenshot
-        #[arg(long, default_value = "0")]
-        wait: u64,
+        freeze_sticky: bool,
+        /// Capture the full page as printed on SIZE (default A4), marking where each PDF page ends
+        #[arg(
+            long,
+            value_name = "SIZE",
+            num_args = 0..=1,
+            default_missing_value = "A4",
+            conflicts_with_all = ["selector", "clip", "auto_height", "widths", "capture"]
+        )]
+        paginate_preview: Option<PaperSize>,
+        /// Resize the viewport to the page's content height before capturing
+        #[arg(long, conflicts_with_all = ["full_page", "clip", "selector"])]
+        auto_height: bool,
+        /// Largest viewport height --auto-height may resize to
+        #[arg(long, value_name = "PX", requires = "auto_height", value_parser = clap::value_parser!(u32).range(1..))]
+        max_height: Option<u32>,
+        /// Emulate a touch screen (touch events, coarse pointer, no hover)
+        #[arg(long)]
+        touch: bool,
+        /// Capture only a page region (format: x,y,width,height)
+        #[arg(long, value_name = "X,Y,WIDTH,HEIGHT")]
+        clip: Option<ClipRegion>,
+        /// Paint elements matching this CSS selector over before capture (repeatable)
+        #[arg(long = "mask", value_name = "SELECTOR", action = clap::ArgAction::Append)]
+        mask_selectors: Vec<String>,
+        /// CSS color masked elements are painted with
+        #[arg(long, value_name = "COLOR", default_value = DEFAULT_MASK_COLOR)]
+        mask_color: String,
+        /// Scroll through the page once before capture to trigger lazy content
+        #[arg(long)]
+        trigger_lazy: bool,
+        /// Lazy-load scroll step in pixels (default: viewport height)
+        #[arg(long, value_name = "PIXELS")]
+        lazy_step: Option<u32>,
+        /// Settle delay after each lazy-load scroll step in milliseconds
+        #[arg(long, value_name = "MS", default_value = "100")]
+        lazy_settle: u64,
+        /// Emulate a device preset (e.g. "iPhone 14 Pro"), overriding size and retina
+        #[arg(long, value_name = "NAME")]
+        device: Option<DevicePreset>,
+        /// Capture at several viewport widths (e.g. 375,768,1280), suffixing outputs with the width
+        #[arg(
+            long,
+            value_name = "WIDTHS",
+            value_delimiter = ',',
+            conflicts_with = "device"
+        )]
+        widths: Vec<u32>,
+        /// Freeze animations, transitions, carets and videos for deterministic captures
+        #[arg(long)]
+        stabilize: bool,
+        /// Hide scrollbars (implied by --stabilize)
+        #[arg(long)]
+        hide_scrollbars: bool,
+        /// Emulate prefers-color-scheme (light, dark)
+        #[arg(long, value_name = "SCHEME")]
+        color_scheme: Option<ColorScheme>,
+        /// Don't scroll to the URL's #fragment target before capturing the viewport
+        #[arg(long)]
+        no_anchor_scroll: bool,
+        /// JavaScript file to run before any page script on every navigation (repeatable)
+        #[arg(long, value_name = "FILE", action = clap::ArgAction::Append)]
+        init_script: Vec<PathBuf>,
+        /// Quantize PNG output to a dithered palette of at most this many colors (2-256)
+        #[arg(
+            long,
+            value_name = "COLORS",
+            value_parser = clap::value_parser!(u16).range(2..=256),
+            conflicts_with = "grayscale"
+        )]
+        png_colors: Option<u16>,
+        /// Write PNG output as 8-bit grayscale
+        #[arg(long)]
+        grayscale: bool,
+        /// Restore cookies, localStorage and sessionStorage from a session file before navigation
+        #[arg(long, value_name = "FILE")]
+        load_session: Option<PathBuf>,
+        /// Save cookies, localStorage and sessionStorage to a session file after capture
+        #[arg(long, value_name = "FILE")]
+        save_session: Option<PathBuf>,
+        /// Compression for TIFF output (none, lzw, deflate, packbits)
+        #[arg(long, value_name = "METHOD", default_value = "lzw")]
+        tiff_compression: TiffCompression,
+        /// YAML file of mock rules answering matching requests with canned responses
+        #[arg(long, value_name = "FILE")]
+        mocks: Option<PathBuf>,
+        /// Click away or hide cookie banners and other consent overlays before capture
+        #[arg(long)]
+        auto_dismiss_overlays: bool,
+        /// YAML file of extra overlay rules (name, accept, reject, hide selectors)
+        #[arg(long, value_name = "FILE", requires = "auto_dismiss_overlays")]
+        overlay_rules: Option<PathBuf>,
+        /// Consent button to press when dismissing overlays (accept, reject)
+        #[arg(long, value_name = "CHOICE", default_value = "accept")]
+        overlay_choice: OverlayChoice,
+        /// Fail instead of capturing when the page returns HTTP 4xx or 5xx
+        #[arg(long)]
+        fail_on_http_error: bool,
+        /// Fail instead of capturing when the page logs a console error
+        #[arg(long)]
+        fail_on_console_error: bool,
+        /// Fail instead of capturing when the page throws an uncaught exception
+        #[arg(long)]
+        fail_on_js_exception: bool,
+        /// Write console messages, warnings and exceptions to this file (JSON for .json)
+        #[arg(long, value_name = "PATH")]
+        console_log: Option<PathBuf>,
+        /// Record network requests and responses to this HAR 1.2 file
+        #[arg(long, value_name = "PATH")]
+        har: Option<PathBuf>,
+        #[command(flatten)]
+        image: ImageArgs,
+        #[command(flatten)]
+        waits: WaitArgs,
+        #[command(flatten)]
+        request: RequestArgs,
     },
     /// Generate PDF from webpage
     Pdf {
@@ -129,9 +746,9 @@ enum Commands {
         /// Output PDF file path
         #[arg(short, long)]
         output: Option<PathBuf>,
-        /// Page format (A4, Letter, etc.)
-        #[arg(long, default_value = "A4")]
-        format: String,
+        /// Paper size (letter, legal, tabloid, a3, a4, a5)
+        #[arg(long, value_name = "SIZE", default_value = "A4")]
+        format: PaperSize,
         /// Landscape orientation
         #[arg(long)]
         landscape: bool,
@@ -141,26 +758,250 @@ enum Commands {
         /// Scale factor (0.1 to 2.0)
         #[arg(long, default_value = "1.0")]
         scale: f64,
+        /// Remove hyperlinks and internal anchors instead of keeping them clickable
+        #[arg(long)]
+        no_links: bool,
+        /// Embed a document outline (bookmarks) built from the page's headings
+        #[arg(long)]
+        outline: bool,
         /// JavaScript to execute
         #[arg(short, long)]
         javascript: Option<String>,
-        /// Wait for element
-        #[arg(long)]
-        wait_for: Option<String>,
-        /// Timeout in seconds
-        #[arg(short, long, default_value = "30")]
-        timeout: u64,
+        /// Record network requests and responses to this HAR 1.2 file
+        #[arg(long, value_name = "PATH")]
+        har: Option<PathBuf>,
+        #[command(flatten)]
+        waits: WaitArgs,
+        #[command(flatten)]
+        request: RequestArgs,
     },
     /// Process multiple screenshots from YAML config
     Multi {
-        /// Configuration file path
-        config_file: PathBuf,
+        /// Configuration file path (YAML, or JSON/TOML for .json/.toml files)
+        #[arg(required_unless_present = "from_sitemap")]
+        config_file: Option<PathBuf>,
+        /// Configuration file format (yaml, json, toml), instead of guessing it from the extension
+        #[arg(long = "format", value_name = "FORMAT", requires = "config_file")]
+        config_format: Option<ConfigFormat>,
         /// Override output directory
         #[arg(short, long)]
         output_dir: Option<PathBuf>,
+        /// Organize outputs into YYYY/MM/DD/ (date) or per-host (host) directories, or write them as given (flat)
+        #[arg(long, value_name = "LAYOUT")]
+        output_layout: Option<OutputLayout>,
+        /// Parallel processing (number of concurrent tasks)
+        #[arg(short, long, default_value = "4")]
+        parallel: usize,
+        /// Add a capture for every page in a sitemap.xml (nested sitemap indexes are expanded)
+        #[arg(long, visible_alias = "sitemap", value_name = "URL")]
+        from_sitemap: Option<String>,
+        /// Maximum number of sitemap pages to capture
+        #[arg(long, requires = "from_sitemap")]
+        limit: Option<usize>,
+        /// Only capture sitemap pages whose path matches this glob (repeatable)
+        #[arg(long, value_name = "GLOB", requires = "from_sitemap")]
+        include: Vec<String>,
+        /// Output filename template for sitemap pages ({host}, {path}, {index}, {date}, {timestamp}, {title}, {final_url_path})
+        #[arg(long, default_value = "{host}_{path}.png")]
+        name_template: String,
+        /// Viewport width of sitemap pages, overriding the config's defaults
+        #[arg(short, long, requires = "from_sitemap")]
+        width: Option<u32>,
+        /// Viewport height of sitemap pages, overriding the config's defaults
+        #[arg(short = 'H', long, requires = "from_sitemap")]
+        height: Option<u32>,
+        /// Timeout in seconds for sitemap pages, overriding the config's defaults
+        #[arg(short, long, requires = "from_sitemap")]
+        timeout: Option<u64>,
+        /// Wait time in seconds before capturing sitemap pages
+        #[arg(long, requires = "from_sitemap")]
+        wait: Option<u64>,
+        /// Capture sitemap pages in retina mode
+        #[arg(long, requires = "from_sitemap")]
+        retina: bool,
+        /// JPEG/WebP/GIF quality of sitemap pages
+        #[arg(short, long, requires = "from_sitemap", value_parser = clap::value_parser!(u8).range(1..=100))]
+        quality: Option<u8>,
+        /// Replace identical output files with links and write a dedupe manifest
+        #[arg(long)]
+        dedupe: bool,
+        /// Also deduplicate images with identical perceptual hashes
+        #[arg(long, requires = "dedupe")]
+        dedupe_perceptual: bool,
+        /// Link type for deduplicated files (hard, symlink)
+        #[arg(long, value_name = "KIND", default_value = "hard", requires = "dedupe")]
+        dedupe_link: LinkKind,
+        /// After the run, prune captures in the output directory older than this (e.g. 30d, 12h, 2w)
+        #[arg(long, value_name = "AGE", value_parser = parse_age, requires = "output_dir")]
+        retention: Option<Duration>,
+        /// After the run, prune the oldest captures until the output directory fits this size (e.g. 10GB)
+        #[arg(long, value_name = "SIZE", value_parser = parse_size, requires = "output_dir")]
+        max_archive_size: Option<u64>,
+        /// Show a live dashboard with per-job status, throughput and retry/skip keys
+        #[arg(long)]
+        tui: bool,
+        /// Write a report after the run: html:DIR for a static HTML gallery of the captures
+        #[arg(long, value_name = "FORMAT:PATH")]
+        report: Option<BatchReport>,
+        /// Merge the PDFs of the config's `pdfs` section into this file, after a table of
+        /// contents page (placed under --output-dir like the PDFs)
+        #[arg(long, value_name = "PATH", requires = "config_file")]
+        merge_output: Option<PathBuf>,
+    },
+    /// Check a batch config without launching Chrome: validate it, resolve defaults and
+    /// print every entry's output paths, formats and effective options
+    Validate {
+        /// Configuration file path (YAML, or JSON/TOML for .json/.toml files)
+        config_file: PathBuf,
+        /// Configuration file format (yaml, json, toml), instead of guessing it from the extension
+        #[arg(long = "format", value_name = "FORMAT")]
+        config_format: Option<ConfigFormat>,
+        /// Resolve outputs under this directory, as `multi --output-dir` would
+        #[arg(short, long)]
+        output_dir: Option<PathBuf>,
+        /// Resolve outputs in this directory layout, as `multi --output-layout` would
+        #[arg(long, value_name = "LAYOUT")]
+        output_layout: Option<OutputLayout>,
+    },
+    /// Discover webshot.yaml project files in and below a directory, merge them with the
+    /// ones above it and capture each project, optionally only those with changed files
+    Run {
+        /// Directory to search for projects
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+        /// Only run projects with files changed since this git ref (e.g. main)
+        #[arg(long, value_name = "REF")]
+        changed_since: Option<String>,
+        /// Parallel processing (number of concurrent tasks)
+        #[arg(short, long, default_value = "4")]
+        parallel: usize,
+        /// List the projects that would run without capturing
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Capture every URL in a text file (one URL per line, optionally followed by an output path)
+    List {
+        /// URL list file path
+        url_file: PathBuf,
+        /// Output directory
+        #[arg(short, long)]
+        output_dir: Option<PathBuf>,
         /// Parallel processing (number of concurrent tasks)
         #[arg(short, long, default_value = "4")]
         parallel: usize,
+        /// Organize outputs into YYYY/MM/DD/ (date) or per-host (host) directories, or write them as given (flat)
+        #[arg(long, value_name = "LAYOUT")]
+        output_layout: Option<OutputLayout>,
+        /// Output format for entries without an explicit output path (png, jpg, webp, gif, tiff, pdf)
+        #[arg(short, long, default_value = "png")]
+        format: String,
+        /// Capture the full scrollable page, loading lazy content first
+        #[arg(long)]
+        full_page: bool,
+        /// Emulate a device preset (e.g. "iPhone 14 Pro")
+        #[arg(long, value_name = "NAME")]
+        device: Option<DevicePreset>,
+        /// Replace identical output files with links and write a dedupe manifest
+        #[arg(long)]
+        dedupe: bool,
+        /// Also deduplicate images with identical perceptual hashes
+        #[arg(long, requires = "dedupe")]
+        dedupe_perceptual: bool,
+        /// Link type for deduplicated files (hard, symlink)
+        #[arg(long, value_name = "KIND", default_value = "hard", requires = "dedupe")]
+        dedupe_link: LinkKind,
+        /// After the run, prune captures in the output directory older than this (e.g. 30d, 12h, 2w)
+        #[arg(long, value_name = "AGE", value_parser = parse_age, requires = "output_dir")]
+        retention: Option<Duration>,
+        /// After the run, prune the oldest captures until the output directory fits this size (e.g. 10GB)
+        #[arg(long, value_name = "SIZE", value_parser = parse_size, requires = "output_dir")]
+        max_archive_size: Option<u64>,
+        /// Show a live dashboard with per-job status, throughput and retry/skip keys
+        #[arg(long)]
+        tui: bool,
+        /// Write a report after the run: html:DIR for a static HTML gallery of the captures
+        #[arg(long, value_name = "FORMAT:PATH")]
+        report: Option<BatchReport>,
+        #[command(flatten)]
+        image: ImageArgs,
+        #[command(flatten)]
+        waits: WaitArgs,
+    },
+    /// Crawl a site from a seed URL, capturing every visited page
+    Crawl {
+        /// HTTP(S) URL to start crawling from
+        url: String,
+        /// Output directory for captured pages
+        #[arg(short, long, default_value = "crawl")]
+        output_dir: PathBuf,
+        /// Maximum number of pages to capture
+        #[arg(long, default_value = "100")]
+        max_pages: usize,
+        /// Maximum link depth from the start URL
+        #[arg(long, default_value = "3")]
+        max_depth: usize,
+        /// Maximum number of pages to capture per host
+        #[arg(long, value_name = "PAGES")]
+        max_pages_per_domain: Option<usize>,
+        /// Only follow links whose path matches this glob (repeatable)
+        #[arg(long, value_name = "GLOB")]
+        include: Vec<String>,
+        /// Never follow links whose path matches this glob (repeatable)
+        #[arg(long, value_name = "GLOB")]
+        exclude: Vec<String>,
+        /// Also follow links to this host (repeatable; the start host is always allowed)
+        #[arg(long, value_name = "HOST")]
+        allow_domain: Vec<String>,
+        /// Skip pages disallowed by robots.txt
+        #[arg(long)]
+        respect_robots: bool,
+        /// Detect near-duplicate pages (same DOM structure, similar screenshot): skip or group
+        #[arg(long, value_name = "MODE")]
+        duplicates: Option<DuplicateMode>,
+        /// Maximum perceptual-hash distance in bits for near-duplicates
+        #[arg(long, value_name = "BITS", default_value_t = DEFAULT_DUPLICATE_THRESHOLD)]
+        duplicate_threshold: u32,
+        /// Crawl state file used to resume interrupted crawls (default: <OUTPUT_DIR>/crawl-state.json)
+        #[arg(long, value_name = "FILE")]
+        state: Option<PathBuf>,
+        /// Discard any saved crawl state and start over
+        #[arg(long)]
+        restart: bool,
+        /// Output filename template ({host}, {path}, {index}, {date}, {timestamp}, {title}, {final_url_path})
+        #[arg(long, default_value = "{host}_{path}.png")]
+        name_template: String,
+        /// Capture the full scrollable page, loading lazy content first
+        #[arg(long)]
+        full_page: bool,
+        #[command(flatten)]
+        image: ImageArgs,
+        #[command(flatten)]
+        waits: WaitArgs,
+    },
+    /// Replace identical screenshots in a directory tree with links
+    Dedupe {
+        /// Directory to scan recursively
+        dir: PathBuf,
+        /// Also deduplicate images with identical perceptual hashes
+        #[arg(long)]
+        perceptual: bool,
+        /// Link type for deduplicated files (hard, symlink)
+        #[arg(long, value_name = "KIND", default_value = "hard")]
+        link: LinkKind,
+    },
+    /// Remove old captures from an archive directory, keeping baselines and flagged runs
+    Prune {
+        /// Archive directory to prune recursively
+        dir: PathBuf,
+        /// Prune captures older than this (e.g. 30d, 12h, 2w)
+        #[arg(long, value_name = "AGE", value_parser = parse_age, required_unless_present = "max_archive_size")]
+        retention: Option<Duration>,
+        /// Prune the oldest captures until the directory fits this size (e.g. 10GB, 500MB)
+        #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+        max_archive_size: Option<u64>,
+        /// List what would be pruned without deleting anything
+        #[arg(long)]
+        dry_run: bool,
     },
     /// Extract text content from webpage
     Text {
@@ -175,91 +1016,677 @@ enum Commands {
         /// JavaScript to execute
         #[arg(short, long)]
         javascript: Option<String>,
-        /// Wait for element
-        #[arg(long)]
-        wait_for: Option<String>,
-        /// Timeout in seconds
-        #[arg(short, long, default_value = "30")]
-        timeout: u64,
+        #[command(flatten)]
+        waits: WaitArgs,
+        #[command(flatten)]
+        request: RequestArgs,
     },
-    /// Compare two images for differences
-    #[command(alias = "diff")]
-    Compare {
-        /// First image to compare
-        image1: PathBuf,
-        /// Second image to compare
-        image2: PathBuf,
-        /// Output file for comparison results (JSON format)
+    /// Print a page's heading hierarchy (h1-h6 with text, anchors and nesting) as JSON
+    /// or Markdown
+    Outline {
+        /// HTTP(S) URL to read headings from
+        url: String,
+        /// Only read headings inside the element matching this CSS selector
+        #[arg(short, long)]
+        selector: Option<String>,
+        /// Output file (stdout if not specified)
         #[arg(short, long)]
         output: Option<PathBuf>,
-        /// Comparison algorithm
-        #[arg(short, long, default_value = "pixel-diff")]
-        algorithm: String,
-        /// Similarity threshold (0.0-1.0, higher means more strict)
-        #[arg(short, long, default_value = "0.1")]
-        threshold: f64,
-        /// Generate difference image highlighting changes
-        #[arg(long)]
-        diff_image: bool,
-        /// Path for difference image (required if --diff-image is used)
+        /// Output format (json, markdown)
+        #[arg(long, default_value = "json")]
+        format: OutlineFormat,
+        /// JavaScript to execute
+        #[arg(short, long)]
+        javascript: Option<String>,
+        #[command(flatten)]
+        waits: WaitArgs,
+        #[command(flatten)]
+        request: RequestArgs,
+    },
+    /// Download a page's favicons, touch icons and og:image into a directory with a
+    /// manifest
+    Assets {
+        /// HTTP(S) URL whose assets to download
+        url: String,
+        /// Directory to download into
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Download favicons in every declared size and touch icons
         #[arg(long)]
-        diff_path: Option<PathBuf>,
-        /// Ignore anti-aliasing differences
+        favicons: bool,
+        /// Download the og:image
         #[arg(long)]
-        ignore_antialiasing: bool,
+        og_image: bool,
+        /// JavaScript to execute
+        #[arg(short, long)]
+        javascript: Option<String>,
+        #[command(flatten)]
+        waits: WaitArgs,
+        #[command(flatten)]
+        request: RequestArgs,
+    },
+    /// Capture an HTML email template at email client widths, in light and dark mode
+    Email {
+        /// HTML file of the email template
+        template: PathBuf,
+        /// Output path; captures are named <stem>-<width>-<light|dark>.<ext>
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Widths to capture: desktop (600), mobile (375), small (320) or pixels
+        #[arg(long = "width", value_name = "WIDTHS", value_delimiter = ',', default_value = DEFAULT_EMAIL_WIDTHS)]
+        widths: Vec<EmailWidth>,
+        /// Only capture the light color scheme
+        #[arg(long)]
+        no_dark: bool,
+        /// Warn about markup and CSS that email clients drop or ignore
+        #[arg(long)]
+        check_css: bool,
+        /// Capture at 2x device scale factor
+        #[arg(long)]
+        retina: bool,
+        #[command(flatten)]
+        waits: WaitArgs,
+    },
+    /// Open a browser window, click an element and print a CSS selector for it
+    Pick {
+        /// HTTP(S) URL to pick an element on
+        url: String,
+        /// Capture the picked element to this file
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Seconds to wait for a pick
+        #[arg(short, long, default_value = "300")]
+        timeout: u64,
+    },
+    /// Interactive session on a persistent browser (goto, wait, click, shot, pdf, eval, compare)
+    Repl {
+        /// Page to open before the first prompt
+        url: Option<String>,
+        /// Timeout in seconds for waits
+        #[arg(short, long, default_value = "30")]
+        timeout: u64,
+        /// Record clicks, typing and goto/wait commands as config steps to this YAML file
+        #[arg(long, value_name = "FILE")]
+        record_steps: Option<PathBuf>,
+        #[command(flatten)]
+        image: ImageArgs,
+    },
+    /// Run a Rhai script driving a browser session (navigate, wait, click, exists, shot, compare, assert)
+    #[command(after_help = SCRIPT_FUNCTIONS)]
+    Script {
+        /// Script file, e.g. flow.rhai
+        file: PathBuf,
+        /// Timeout in seconds for waits
+        #[arg(short, long, default_value = "30")]
+        timeout: u64,
+        #[command(flatten)]
+        image: ImageArgs,
+    },
+    /// Compare two images for differences
+    #[command(alias = "diff")]
+    Compare {
+        /// First image to compare (a baseline name with --baseline-store)
+        #[arg(required_unless_present = "dirs")]
+        image1: Option<PathBuf>,
+        /// Second image to compare
+        #[arg(required_unless_present = "dirs")]
+        image2: Option<PathBuf>,
+        /// Compare two directories instead, matching images by relative path: give
+        /// --dir twice, baseline first
+        #[arg(
+            long = "dir",
+            value_name = "DIR",
+            conflicts_with_all = ["image1", "image2", "baseline_store", "against_ref"]
+        )]
+        dirs: Vec<PathBuf>,
+        /// Output file for comparison results (JSON format)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Comparison algorithm (pixel-diff, ssim, ms-ssim, mse, psnr, delta-e, or plugin:FILE.wasm)
+        #[arg(short, long, default_value = "pixel-diff")]
+        algorithm: String,
+        /// Similarity threshold (0.0-1.0, higher means more strict)
+        #[arg(short, long, default_value = "0.1")]
+        threshold: f64,
+        /// Largest CIEDE2000 color difference of pixels that still match, for delta-e
+        #[arg(long, value_name = "DELTA_E", default_value_t = DEFAULT_DELTA_E_TOLERANCE)]
+        tolerance: f64,
+        /// Generate difference image highlighting changes
+        #[arg(long)]
+        diff_image: bool,
+        /// Path for difference image (required if --diff-image is used; a directory with --dir)
+        #[arg(long)]
+        diff_path: Option<PathBuf>,
+        /// Ignore pixels that are anti-aliased in either image, detected from their neighbors
+        #[arg(long)]
+        ignore_antialiasing: bool,
         /// Color for highlighting differences (RGB format: 255,0,0)
         #[arg(long, default_value = "255,0,0")]
         diff_color: String,
+        /// How the difference image marks changes: recolored pixels, or boxes around
+        /// changed regions (pixels, boxes)
+        #[arg(long, value_name = "STYLE", default_value = "pixels")]
+        diff_style: DiffStyle,
+        /// Changes within this many pixels of each other are reported as one region
+        #[arg(long, value_name = "PIXELS", default_value_t = DEFAULT_REGION_GAP)]
+        region_gap: u32,
+        /// Decode and compare PNG images this many rows at a time, bounding memory use
+        /// for very tall full-page captures
+        #[arg(long, value_name = "ROWS")]
+        band_height: Option<u32>,
+        /// Output format for results (json, text)
+        #[arg(long, default_value = "text")]
+        format: String,
+        /// Read the first image from this baseline store (directory or s3://bucket/prefix),
+        /// recording the baseline version in the results
+        #[arg(long, value_name = "STORE", env = "WEBSHOT_BASELINE_STORE")]
+        baseline_store: Option<String>,
+        /// Read the first image as committed at this git ref (branch, tag or commit)
+        #[arg(long, value_name = "REF", conflicts_with = "baseline_store")]
+        against_ref: Option<String>,
+        /// Write an HTML report with side-by-side, onion skin and diff overlay views
+        /// (a table of every pair with --dir)
+        #[arg(long, value_name = "PATH")]
+        html_report: Option<PathBuf>,
+    },
+    /// Compare element positions and sizes between two pages or saved layout snapshots,
+    /// failing when an element drifts further than the threshold
+    LayoutDiff {
+        /// Expected layout: an HTTP(S) URL to capture, or a layout snapshot JSON file
+        expected: String,
+        /// Actual layout: an HTTP(S) URL to capture, or a layout snapshot JSON file
+        actual: String,
+        /// CSS selector of an element to compare (repeatable; needed to capture URLs)
+        #[arg(short, long = "selector", value_name = "SELECTOR")]
+        selectors: Vec<String>,
+        /// File with a CSS selector per line to compare, `#` starting a comment
+        #[arg(long, value_name = "PATH")]
+        selectors_file: Option<PathBuf>,
+        /// Pixels an element may move or resize by
+        #[arg(short, long, default_value_t = DEFAULT_DRIFT_THRESHOLD)]
+        threshold: f64,
+        /// Viewport width
+        #[arg(short, long, default_value = "1280")]
+        width: u32,
+        /// Viewport height
+        #[arg(short = 'H', long, default_value = "800")]
+        height: u32,
+        /// Wait for an element or page state before measuring (see --wait-for on the root command)
+        #[arg(long, value_name = "CONDITION", value_parser = parse_wait_condition)]
+        wait_for: Option<String>,
+        /// Wait time in seconds before measuring
+        #[arg(long, default_value = "0")]
+        wait: u64,
+        /// Timeout in seconds
+        #[arg(long, default_value = "30")]
+        timeout: u64,
+        /// Output file for the results
+        #[arg(short, long)]
+        output: Option<PathBuf>,
         /// Output format for results (json, text)
         #[arg(long, default_value = "text")]
         format: String,
+        /// Save the expected layout snapshot to this file, to compare against later
+        #[arg(long, value_name = "PATH")]
+        save_expected: Option<PathBuf>,
+        /// Save the actual layout snapshot to this file
+        #[arg(long, value_name = "PATH")]
+        save_actual: Option<PathBuf>,
+    },
+    /// Manage baselines in a shared baseline store
+    Baseline {
+        #[command(subcommand)]
+        action: BaselineAction,
+    },
+    /// Check that the browser starts and responds and the output directory is writable,
+    /// printing a JSON report; exits non-zero when unhealthy (for container probes)
+    Health {
+        /// Output directory that captures are written to
+        #[arg(short, long, default_value = ".")]
+        output_dir: PathBuf,
+    },
+    /// Serve an HTTP API (POST /screenshot, /pdf, /text, /compare) backed by a pool of
+    /// warm browsers
+    Serve {
+        /// Port to listen on
+        #[arg(short, long, default_value = "8080", env = "WEBSHOT_PORT")]
+        port: u16,
+        /// Address to listen on (0.0.0.0 to accept outside connections)
+        #[arg(long, default_value = "127.0.0.1")]
+        host: std::net::IpAddr,
+        /// Most captures running at once, one browser each
+        #[arg(long, value_name = "N", default_value = "4",
+              value_parser = clap::value_parser!(u16).range(1..))]
+        pool_size: u16,
+        /// Relaunch each browser after this many captures
+        #[arg(long, value_name = "N", default_value = "100")]
+        max_uses: u32,
+        /// Background jobs (POST /jobs) run at once
+        #[arg(long, value_name = "N", default_value = "2",
+              value_parser = clap::value_parser!(u16).range(1..))]
+        job_concurrency: u16,
+        /// Most background jobs waiting to run before POST /jobs answers 429
+        #[arg(long, value_name = "N", default_value = "1000")]
+        max_queued_jobs: usize,
+        /// Host that job webhooks may be sent to (repeatable); webhooks to other hosts are refused
+        #[arg(long = "webhook-allow", value_name = "HOST")]
+        webhook_allow: Vec<String>,
+        /// Seconds finished jobs and their results are kept before they are removed
+        #[arg(long, value_name = "SECONDS", default_value = "86400")]
+        job_retention: u64,
+        /// Directory recording background jobs and their results, so queued jobs survive
+        /// restarts (default: a temporary directory)
+        #[arg(long, value_name = "DIR", env = "WEBSHOT_JOBS_DIR")]
+        jobs_dir: Option<PathBuf>,
+        /// Only answer requests signed with this secret (see sign-url); health probes stay
+        /// open
+        #[arg(
+            long,
+            value_name = "SECRET",
+            env = "WEBSHOT_SIGNING_SECRET",
+            hide_env_values = true
+        )]
+        signing_secret: Option<String>,
+        /// Seconds to keep accepting requests after SIGTERM while /readyz reports draining,
+        /// so load balancers take the server out of rotation first
+        #[arg(long, value_name = "SECONDS", default_value = "5")]
+        drain_delay: u64,
+    },
+    /// Serve deterministic fixture pages (static page, slow SPA, lazy images, login form)
+    /// on localhost for offline smoke tests
+    DevServer {
+        /// Port to listen on
+        #[arg(short, long, default_value = "8081")]
+        port: u16,
+        /// Address to listen on (0.0.0.0 to accept outside connections)
+        #[arg(long, default_value = "127.0.0.1")]
+        host: std::net::IpAddr,
+    },
+    /// Print an HMAC-signed capture URL that expires after a while
+    SignUrl {
+        /// Capture URL (https://host/path?query) or request path (/path?query) to sign
+        url: String,
+        /// Shared signing secret
+        #[arg(long, env = "WEBSHOT_SIGNING_SECRET", hide_env_values = true)]
+        secret: String,
+        /// Seconds until the signed URL expires
+        #[arg(long, value_name = "SECONDS", default_value = "3600")]
+        expires_in: u32,
+        /// File holding the body of the POST request the URL is for; a signed server refuses other bodies
+        #[arg(long, value_name = "FILE")]
+        body: Option<PathBuf>,
+    },
+    /// Print the webshot version and the Chrome it launches
+    Info {
+        /// List the default Chrome flags (marking those removed with --remove-flag) and the flags this run adds
+        #[arg(long)]
+        flags: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum BaselineAction {
+    /// Store an image as the new baseline, refusing to replace a baseline newer than
+    /// the version compared against
+    Accept {
+        /// Baseline name, e.g. home/desktop.png
+        name: String,
+        /// Image to store
+        image: PathBuf,
+        /// Baseline store (directory or s3://bucket/prefix)
+        #[arg(long, value_name = "STORE", env = "WEBSHOT_BASELINE_STORE")]
+        store: String,
+        /// Baseline version the comparison ran against (`baseline_version` in JSON results);
+        /// omit when there was no baseline yet
+        #[arg(long, value_name = "VERSION", conflicts_with = "force")]
+        expected_version: Option<String>,
+        /// Replace the stored baseline whatever its version
+        #[arg(long)]
+        force: bool,
+        /// Commit the baseline to the git repository holding the store, with a message
+        /// saying how much it changed
+        #[arg(long)]
+        git: bool,
     },
 }
 
 #[tokio::main]
-async fn main() -> Result<()> {
-    let cli = Cli::parse();
+async fn main() {
+    let matches = Cli::command().get_matches();
+    check_freeze_sticky(&matches);
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    i18n::init(cli.lang);
+    let _ = PORCELAIN.set(cli.porcelain);
+    let (operation, target) = cli.operation();
+
+    if let Err(error) = run(cli, &matches).await {
+        let message = i18n::error_message(&error);
+        let http_status = match &error {
+            webshot::WebshotError::HttpStatus { status, .. } => Some(*status),
+            _ => None,
+        };
+        emit(
+            Record::failed(operation, &message)
+                .target(target)
+                .http_status(http_status),
+        );
+        eprintln!("{}: {}", tr("error-prefix", &[]), message);
+        std::process::exit(1);
+    }
+}
+
+/// Exit with a usage error when `--freeze-sticky` is given without a
+/// full-page capture; clap's `requires` rules cannot look at the value of
+/// `--capture`
+fn check_freeze_sticky(matches: &ArgMatches) {
+    let matches = match matches.subcommand() {
+        Some(("screenshot", screenshot)) => screenshot,
+        _ => matches,
+    };
+    if !matches.get_flag("freeze_sticky")
+        || matches.get_flag("full_page")
+        || matches.get_one::<CaptureMode>("capture") == Some(&CaptureMode::FullPage)
+        || matches.get_one::<PaperSize>("paginate_preview").is_some()
+    {
+        return;
+    }
+    Cli::command()
+        .error(
+            ErrorKind::MissingRequiredArgument,
+            "--freeze-sticky requires --full-page or --capture full-page",
+        )
+        .exit()
+}
+
+/// `--porcelain` format, set once at startup
+static PORCELAIN: OnceLock<Option<PorcelainFormat>> = OnceLock::new();
+
+fn porcelain() -> Option<PorcelainFormat> {
+    PORCELAIN.get().copied().flatten()
+}
+
+/// Print `record` in porcelain mode and `message` otherwise
+fn report(record: Record, message: impl FnOnce() -> String) {
+    emit(record);
+    say(message);
+}
+
+/// Print `record` in porcelain mode only
+fn emit(record: Record) {
+    if let Some(format) = porcelain() {
+        println!("{}", record.line(format));
+    }
+}
+
+/// Print prose that porcelain mode leaves out
+fn say(message: impl FnOnce() -> String) {
+    if porcelain().is_none() {
+        println!("{}", message());
+    }
+}
+
+/// Print `--explain` output, on stderr in porcelain mode
+fn print_explanations(explanations: &[Explanation]) {
+    for explanation in explanations {
+        if porcelain().is_some() {
+            eprint!("{}", explanation);
+        } else {
+            print!("{}", explanation);
+        }
+    }
+}
+
+/// Explanation of a capture configured entirely by the flags in `matches`
+fn explain_flags(url: &str, output: Option<&Path>, matches: &ArgMatches) -> Explanation {
+    let output = output.map_or_else(
+        || "(generated)".to_string(),
+        |path| path.display().to_string(),
+    );
+    let mut ids: Vec<&str> = matches
+        .ids()
+        .map(|id| id.as_str())
+        // Skip the argument groups clap derives, named after the struct or
+        // variant holding the flags
+        .filter(|id| !id.starts_with(char::is_uppercase) && !matches!(*id, "url" | "output"))
+        .collect();
+    ids.sort_unstable();
+
+    let mut explanation = Explanation::new(url, output);
+    for id in ids {
+        let Ok(Some(values)) = matches.try_get_raw(id) else {
+            continue;
+        };
+        let value = values
+            .map(|value| value.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(",");
+        explanation = explanation.option(id, value, flag_source(matches, id));
+    }
+    explanation
+}
+
+fn flag_source(matches: &ArgMatches, id: &str) -> OptionSource {
+    match matches.value_source(id) {
+        Some(ValueSource::CommandLine) => OptionSource::Cli,
+        Some(ValueSource::EnvVariable) => OptionSource::Env,
+        _ => OptionSource::Builtin,
+    }
+}
+
+/// Sources of the flags among `ids` set on the command line or from the
+/// environment, by flag name
+fn flag_sources(matches: &ArgMatches, ids: &[&str]) -> HashMap<String, OptionSource> {
+    ids.iter()
+        .map(|id| (id.to_string(), flag_source(matches, id)))
+        .filter(|(_, source)| *source != OptionSource::Builtin)
+        .collect()
+}
+
+impl Cli {
+    /// Porcelain operation name and target of the command, for reporting
+    /// the error it fails with
+    fn operation(&self) -> (&'static str, String) {
+        match &self.command {
+            None => ("screenshot", self.url.clone().unwrap_or_default()),
+            Some(Commands::Screenshot { url, .. }) => ("screenshot", url.clone()),
+            Some(Commands::Pdf { url, .. }) => ("pdf", url.clone()),
+            Some(Commands::Text { url, .. }) => ("text", url.clone()),
+            Some(Commands::Outline { url, .. }) => ("outline", url.clone()),
+            Some(Commands::Assets { url, .. }) => ("assets", url.clone()),
+            Some(Commands::Email { template, .. }) => ("email", template.display().to_string()),
+            Some(Commands::Multi {
+                config_file,
+                from_sitemap,
+                ..
+            }) => (
+                "batch",
+                config_file
+                    .as_ref()
+                    .map(|file| file.display().to_string())
+                    .or_else(|| from_sitemap.clone())
+                    .unwrap_or_default(),
+            ),
+            Some(Commands::Validate { config_file, .. }) => {
+                ("validate", config_file.display().to_string())
+            }
+            Some(Commands::Run { dir, .. }) => ("batch", dir.display().to_string()),
+            Some(Commands::List { url_file, .. }) => ("batch", url_file.display().to_string()),
+            Some(Commands::Crawl { url, .. }) => ("crawl", url.clone()),
+            Some(Commands::Dedupe { dir, .. }) => ("dedupe", dir.display().to_string()),
+            Some(Commands::Prune { dir, .. }) => ("prune", dir.display().to_string()),
+            Some(Commands::Pick { url, .. }) => ("pick", url.clone()),
+            Some(Commands::Repl { .. }) => ("repl", String::new()),
+            Some(Commands::Script { file, .. }) => ("script", file.display().to_string()),
+            Some(Commands::Compare { image2, dirs, .. }) => (
+                "compare",
+                image2
+                    .as_ref()
+                    .or(dirs.last())
+                    .map(|path| path.display().to_string())
+                    .unwrap_or_default(),
+            ),
+            Some(Commands::LayoutDiff { actual, .. }) => ("layout-diff", actual.clone()),
+            Some(Commands::Baseline {
+                action: BaselineAction::Accept { name, .. },
+            }) => ("baseline", name.clone()),
+            Some(Commands::Health { .. }) => ("health", String::new()),
+            Some(Commands::Serve { .. }) => ("serve", String::new()),
+            Some(Commands::DevServer { .. }) => ("dev-server", String::new()),
+            Some(Commands::SignUrl { url, .. }) => ("sign-url", url.clone()),
+            Some(Commands::Info { .. }) => ("info", String::new()),
+        }
+    }
+}
 
+async fn run(cli: Cli, matches: &ArgMatches) -> Result<()> {
     // Initialize logging
-    init_logging(cli.verbose);
+    let tui = matches!(
+        cli.command,
+        Some(Commands::Multi { tui: true, .. }) | Some(Commands::List { tui: true, .. })
+    );
+    init_logging(cli.verbose, tui, cli.porcelain.is_some());
 
     // Extract values we need from cli to avoid borrow checker issues
-    let chrome_path = cli.chrome_path.clone();
-    let chrome_flags = cli.chrome_flag.clone();
-    let no_javascript = cli.no_javascript;
-    let user_agent = cli.user_agent.clone();
+    let browser = &cli.browser;
+    let chrome_path = browser.chrome_path.clone();
+    let chrome_flags = browser.chrome_flags()?;
+    let no_javascript = browser.no_javascript;
+    let target = BrowserTarget {
+        connect: match &browser.connect_host {
+            Some(host_port) => Some(devtools_http_url(host_port)?),
+            None => browser.connect.clone(),
+        },
+        headful: browser.headful || browser.devtools,
+        poll_interval: Duration::from_millis(browser.poll_interval),
+        removed_flags: browser.remove_flag.clone(),
+        keep_profiles: browser.keep_profiles,
+        #[cfg(feature = "chaos")]
+        chaos: cli.chaos.clone().map(|mut options| {
+            options.seed = cli.chaos_seed;
+            Arc::new(webshot::chaos::Chaos::new(options))
+        }),
+    };
+    let user_agent = browser.user_agent.clone();
+    let retry = cli.retry;
 
     // Handle the command
     match cli.command {
         Some(Commands::Screenshot {
             url,
             output,
-            width,
-            height,
+            image,
             selector,
             javascript,
-            wait_for,
-            timeout,
-            retina,
-            quality,
-            wait,
+            waits,
+            full_page,
+            capture,
+            freeze_sticky,
+            paginate_preview,
+            auto_height,
+            max_height,
+            touch,
+            clip,
+            mask_selectors,
+            mask_color,
+            trigger_lazy,
+            lazy_step,
+            lazy_settle,
+            device,
+            widths,
+            stabilize,
+            hide_scrollbars,
+            color_scheme,
+            no_anchor_scroll,
+            init_script,
+            png_colors,
+            grayscale,
+            load_session,
+            save_session,
+            tiff_compression,
+            request,
+            mocks,
+            auto_dismiss_overlays,
+            overlay_rules,
+            overlay_choice,
+            fail_on_http_error,
+            fail_on_console_error,
+            fail_on_js_exception,
+            console_log,
+            har,
         }) => {
+            let network_idle = waits.network_idle();
+            let options = ScreenshotOptions {
+                width: image.width,
+                height: image.height,
+                selector,
+                javascript,
+                wait_for: waits.wait_for,
+                timeout: waits.timeout,
+                retina: image.retina,
+                quality: image.quality,
+                wait: waits.wait,
+                user_agent,
+                capture: if paginate_preview.is_some() {
+                    CaptureMode::FullPage
+                } else {
+                    CaptureMode::resolve(capture, full_page)?
+                },
+                freeze_sticky,
+                paginate_preview,
+                auto_height,
+                max_height,
+                touch,
+                clip,
+                mask_selectors,
+                mask_color,
+                trigger_lazy,
+                lazy_scroll_step: lazy_step,
+                lazy_settle_ms: lazy_settle,
+                network_idle,
+                device,
+                stabilize,
+                hide_scrollbars,
+                disable_javascript: no_javascript,
+                color_scheme,
+                anchor_scroll: !no_anchor_scroll,
+                init_scripts: read_init_scripts(&init_script)?,
+                png_colors,
+                grayscale,
+                session: load_session.map(SessionState::load).transpose()?,
+                save_session,
+                tiff_compression,
+                image_filters: Vec::new(),
+                auth: request.auth(),
+                headers: request.header_map(),
+                steps: Vec::new(),
+                adblock: request.adblock_filters()?,
+                block_resources: request.block_resources,
+                mocks: mocks.map(Mocks::from_file).transpose()?.unwrap_or_default(),
+                dismiss_overlays: overlay_dismissal(
+                    auto_dismiss_overlays,
+                    overlay_rules.as_deref(),
+                    overlay_choice,
+                )?,
+                fail_on_http_error,
+                fail_on_console_error,
+                fail_on_js_exception,
+                console_log,
+                har,
+            };
+            if cli.explain {
+                let matches = matches.subcommand_matches("screenshot").unwrap_or(matches);
+                print_explanations(&[explain_flags(&url, output.as_deref(), matches)]);
+            }
             take_screenshot(
                 &url,
                 output,
-                width,
-                height,
-                selector,
-                javascript,
-                wait_for,
-                timeout,
-                retina,
-                quality,
-                wait,
+                options,
+                &widths,
+                &retry.policy(),
                 chrome_path,
                 chrome_flags,
                 no_javascript,
-                user_agent,
+                target,
             )
             .await
         }
@@ -270,364 +1697,2283 @@ async fn main() -> Result<()> {
             landscape,
             background,
             scale,
+            no_links,
+            outline,
             javascript,
-            wait_for,
-            timeout,
+            waits,
+            request,
+            har,
         }) => {
-            generate_pdf(
-                &url,
-                output,
-                &format,
+            let options = PdfOptions {
+                paper: format,
                 landscape,
                 background,
                 scale,
+                links: !no_links,
+                outline,
                 javascript,
-                wait_for,
-                timeout,
+                network_idle: waits.network_idle(),
+                wait_for: waits.wait_for,
+                wait: waits.wait,
+                timeout: waits.timeout,
+                user_agent,
+                auth: request.auth(),
+                headers: request.header_map(),
+                blocker: request.blocker()?,
+                har,
+            };
+            generate_pdf(
+                &url,
+                output,
+                &options,
                 chrome_path,
                 chrome_flags,
                 no_javascript,
-                user_agent,
+                target,
+                &retry.policy(),
             )
             .await
         }
         Some(Commands::Multi {
             config_file,
+            config_format,
             output_dir,
+            output_layout,
             parallel,
+            from_sitemap,
+            limit,
+            include,
+            name_template,
+            width,
+            height,
+            timeout,
+            wait,
+            retina,
+            quality,
+            dedupe,
+            dedupe_perceptual,
+            dedupe_link,
+            retention,
+            max_archive_size,
+            tui,
+            report: batch_report,
+            merge_output,
         }) => {
+            let dedupe = dedupe.then_some(DedupeOptions {
+                link: dedupe_link,
+                perceptual: dedupe_perceptual,
+            });
+            let retention = RetentionPolicy {
+                max_age: retention,
+                max_size: max_archive_size,
+            };
+            let sitemap = from_sitemap.map(|url| SitemapSource {
+                url,
+                options: SitemapOptions {
+                    limit,
+                    include,
+                    timeout: 30,
+                },
+                name_template,
+                defaults: DefaultsArgs {
+                    width,
+                    height,
+                    timeout,
+                    wait,
+                    retina,
+                    quality,
+                },
+            });
+            let explain = cli.explain.then(|| {
+                let multi = matches.subcommand_matches("multi").unwrap_or(matches);
+                let mut sources = flag_sources(
+                    multi,
+                    &["width", "height", "timeout", "wait", "retina", "quality"],
+                );
+                sources.extend(flag_sources(matches, &["user_agent"]));
+                sources
+            });
             process_config(
-                &config_file,
+                config_file.as_deref(),
+                config_format,
+                sitemap,
+                output_dir,
+                output_layout,
+                merge_output,
+                parallel,
+                dedupe,
+                retention,
+                tui,
+                batch_report,
+                explain,
+                retry,
+                user_agent.as_deref(),
+                chrome_path,
+                chrome_flags,
+                no_javascript,
+                target,
+            )
+            .await
+        }
+        Some(Commands::Validate {
+            config_file,
+            config_format,
+            output_dir,
+            output_layout,
+        }) => validate_file(
+            &config_file,
+            config_format,
+            output_dir.as_deref(),
+            output_layout,
+            retry,
+        ),
+        Some(Commands::Run {
+            dir,
+            changed_since,
+            parallel,
+            dry_run,
+        }) => {
+            run_projects(
+                &dir,
+                changed_since.as_deref(),
+                parallel,
+                dry_run,
+                cli.explain,
+                retry,
+                user_agent.as_deref(),
+                chrome_path,
+                chrome_flags,
+                no_javascript,
+                target,
+            )
+            .await
+        }
+        Some(Commands::List {
+            url_file,
+            output_dir,
+            parallel,
+            output_layout,
+            format,
+            image,
+            waits,
+            full_page,
+            device,
+            dedupe,
+            dedupe_perceptual,
+            dedupe_link,
+            retention,
+            max_archive_size,
+            tui,
+            report: batch_report,
+        }) => {
+            let dedupe = dedupe.then_some(DedupeOptions {
+                link: dedupe_link,
+                perceptual: dedupe_perceptual,
+            });
+            let retention = RetentionPolicy {
+                max_age: retention,
+                max_size: max_archive_size,
+            };
+            let mut template = ScreenshotConfig::new("", "");
+            template.width = image.width;
+            template.height = image.height;
+            template.timeout = waits.timeout;
+            template.retina = Some(image.retina);
+            template.quality = image.quality;
+            template.wait = Some(waits.wait);
+            template.wait_for = waits.wait_for;
+            template.idle_time = waits
+                .idle_time
+                .map(|idle_time| format!("{}ms", idle_time.as_millis()));
+            template.max_inflight = waits.max_inflight;
+            template.full_page = full_page;
+            template.device = device.map(|device| device.name.to_string());
+            template.user_agent = user_agent;
+            template.output_layout = output_layout;
+
+            let mut config = Config::from_url_list(&url_file, &template, &format)?;
+            retry.apply(&mut config.defaults);
+            if cli.explain {
+                let list = matches.subcommand_matches("list").unwrap_or(matches);
+                let mut sources = flag_sources(
+                    list,
+                    &[
+                        "width",
+                        "height",
+                        "timeout",
+                        "retina",
+                        "quality",
+                        "wait",
+                        "wait_for",
+                        "idle_time",
+                        "max_inflight",
+                        "full_page",
+                        "device",
+                        "output_layout",
+                    ],
+                );
+                sources.extend(flag_sources(matches, &["user_agent"]));
+                print_explanations(&explain_config(&config, &[], &sources)?);
+            }
+            run_batch(
+                &config,
                 output_dir,
                 parallel,
+                dedupe,
+                retention,
+                tui,
+                batch_report,
+                chrome_path,
+                chrome_flags,
+                no_javascript,
+                target,
+            )
+            .await?;
+            Ok(())
+        }
+        Some(Commands::Crawl {
+            url,
+            output_dir,
+            max_pages,
+            max_depth,
+            max_pages_per_domain,
+            include,
+            exclude,
+            allow_domain,
+            respect_robots,
+            duplicates,
+            duplicate_threshold,
+            state,
+            restart,
+            name_template,
+            image,
+            waits,
+            full_page,
+        }) => {
+            let crawl_options = CrawlOptions {
+                max_pages: Some(max_pages),
+                max_depth: Some(max_depth),
+                max_pages_per_domain,
+                include,
+                exclude,
+                allowed_domains: allow_domain,
+                respect_robots,
+                duplicates,
+                duplicate_threshold,
+                retry: retry.policy(),
+                rate_limit: retry.rate_limit()?,
+            };
+            let mut options = ScreenshotOptions::new()
+                .viewport(image.width, image.height)
+                .timeout(waits.timeout)
+                .wait(waits.wait);
+            options.user_agent = user_agent;
+            options.quality = image.quality;
+            options.network_idle = waits.network_idle();
+            if let Some(condition) = waits.wait_for {
+                options = options.wait_for(condition);
+            }
+            if image.retina {
+                options = options.retina();
+            }
+            if full_page {
+                options = options.full_page();
+            }
+            let state_path = state.unwrap_or_else(|| output_dir.join(DEFAULT_STATE_FILE));
+
+            crawl_site(
+                &url,
+                &output_dir,
+                &state_path,
+                restart,
+                &crawl_options,
+                &options,
+                &name_template,
                 chrome_path,
                 chrome_flags,
                 no_javascript,
+                target,
             )
             .await
         }
+        Some(Commands::Dedupe {
+            dir,
+            perceptual,
+            link,
+        }) => dedupe_directory(&dir, &DedupeOptions { link, perceptual }),
+        Some(Commands::Prune {
+            dir,
+            retention,
+            max_archive_size,
+            dry_run,
+        }) => {
+            let policy = RetentionPolicy {
+                max_age: retention,
+                max_size: max_archive_size,
+            };
+            print_prune_report(&prune(&dir, &policy, dry_run)?);
+            Ok(())
+        }
         Some(Commands::Text {
             url,
             selector,
             output,
             javascript,
-            wait_for,
-            timeout,
+            waits,
+            request,
         }) => {
+            let options = text_options(selector, javascript, waits, &request, user_agent)?;
             extract_text(
                 &url,
-                selector,
                 output,
-                javascript,
-                wait_for,
-                timeout,
+                &options,
                 chrome_path,
                 chrome_flags,
                 no_javascript,
-                user_agent,
+                target,
+                &retry.policy(),
             )
             .await
         }
-        Some(Commands::Compare {
-            image1,
-            image2,
+        Some(Commands::Outline {
+            url,
+            selector,
             output,
-            algorithm,
-            threshold,
-            diff_image,
-            diff_path,
-            ignore_antialiasing,
-            diff_color,
             format,
+            javascript,
+            waits,
+            request,
         }) => {
-            compare_images(
-                &image1,
-                &image2,
+            let options = text_options(selector, javascript, waits, &request, user_agent)?;
+            extract_outline(
+                &url,
                 output,
-                &algorithm,
-                threshold,
-                diff_image,
-                diff_path,
-                ignore_antialiasing,
-                &diff_color,
-                &format,
+                format,
+                &options,
+                chrome_path,
+                chrome_flags,
+                no_javascript,
+                target,
+                &retry.policy(),
             )
             .await
         }
-        None => {
-            // Default behavior: screenshot with URL as positional argument
-            if let Some(url) = &cli.url {
-                take_screenshot(
-                    url,
-                    cli.output,
-                    cli.width,
-                    cli.height,
-                    cli.selector,
-                    cli.javascript,
-                    cli.wait_for,
-                    cli.timeout,
-                    cli.retina,
-                    cli.quality,
-                    cli.wait,
-                    chrome_path,
-                    chrome_flags,
-                    no_javascript,
-                    user_agent,
-                )
-                .await
-            } else {
-                eprintln!("Error: URL is required when no subcommand is provided");
-                eprintln!("Use 'webshot --help' for usage information");
-                std::process::exit(1);
-            }
+        Some(Commands::Assets {
+            url,
+            output,
+            favicons,
+            og_image,
+            javascript,
+            waits,
+            request,
+        }) => {
+            let options = text_options(None, javascript, waits, &request, user_agent)?;
+            // Without either flag, download everything
+            let all = !favicons && !og_image;
+            capture_assets(
+                &url,
+                &output,
+                favicons || all,
+                og_image || all,
+                &options,
+                chrome_path,
+                chrome_flags,
+                no_javascript,
+                target,
+                &retry.policy(),
+            )
+            .await
         }
-    }
-}
-
-fn init_logging(verbose: u8) {
-    let filter = match verbose {
-        0 => "webshot=warn",
-        1 => "webshot=info",
-        2 => "webshot=debug",
-        _ => "webshot=trace,headless_chrome=debug",
-    };
+        Some(Commands::Email {
+            template,
+            output,
+            widths,
+            no_dark,
+            check_css,
+            retina,
+            waits,
+        }) => {
+            let mut options = ScreenshotOptions::new()
+                .full_page()
+                .timeout(waits.timeout)
+                .wait(waits.wait);
+            options.user_agent = user_agent;
+            options.network_idle = waits.network_idle();
+            if let Some(condition) = waits.wait_for {
+                options = options.wait_for(condition);
+            }
+            if retina {
+                options = options.retina();
+            }
+            capture_email(
+                &template,
+                output,
+                &widths,
+                !no_dark,
+                check_css,
+                &options,
+                chrome_path,
+                chrome_flags,
+                no_javascript,
+                target,
+                &retry.policy(),
+            )
+            .await
+        }
+        Some(Commands::Pick {
+            url,
+            output,
+            timeout,
+        }) => {
+            pick_element(
+                &url,
+                output,
+                timeout,
+                chrome_path,
+                chrome_flags,
+                no_javascript,
+            )
+            .await
+        }
+        Some(Commands::Repl {
+            url,
+            image,
+            timeout,
+            record_steps,
+        }) => {
+            let options = ScreenshotOptions {
+                width: image.width,
+                height: image.height,
+                retina: image.retina,
+                quality: image.quality,
+                timeout,
+                user_agent,
+                ..Default::default()
+            };
+            run_repl(
+                url,
+                options,
+                target,
+                record_steps,
+                chrome_path,
+                chrome_flags,
+                no_javascript,
+            )
+            .await
+        }
+        Some(Commands::Script {
+            file,
+            image,
+            timeout,
+        }) => {
+            let options = ScreenshotOptions {
+                width: image.width,
+                height: image.height,
+                retina: image.retina,
+                quality: image.quality,
+                timeout,
+                user_agent,
+                ..Default::default()
+            };
+            run_script_file(
+                &file,
+                options,
+                target,
+                chrome_path,
+                chrome_flags,
+                no_javascript,
+            )
+            .await
+        }
+        Some(Commands::Compare {
+            image1,
+            image2,
+            dirs,
+            output,
+            algorithm,
+            threshold,
+            tolerance,
+            diff_image,
+            diff_path,
+            ignore_antialiasing,
+            diff_color,
+            diff_style,
+            region_gap,
+            band_height,
+            format,
+            baseline_store,
+            against_ref,
+            html_report,
+        }) => {
+            let options = comparison_options(
+                &algorithm,
+                threshold,
+                tolerance,
+                ignore_antialiasing,
+                &diff_color,
+                diff_style,
+                region_gap,
+                band_height,
+            )?;
+            // Validate inputs
+            if diff_image && diff_path.is_none() {
+                return Err(webshot::WebshotError::config(
+                    "Diff path must be specified when --diff-image is used".to_string(),
+                ));
+            }
+            let diff_path = diff_path.filter(|_| diff_image);
+            match (image1, image2) {
+                (Some(image1), Some(image2)) => {
+                    let options = match diff_path {
+                        Some(path) => options.generate_diff_image(path),
+                        None => options,
+                    };
+                    options.validate()?;
+                    compare_images(
+                        &image1,
+                        &image2,
+                        output,
+                        &options,
+                        &format,
+                        baseline_store.as_deref(),
+                        against_ref.as_deref(),
+                        html_report.as_deref(),
+                    )
+                    .await
+                }
+                _ => {
+                    options.validate()?;
+                    compare_directories(
+                        &dirs,
+                        output,
+                        &options,
+                        diff_path.as_deref(),
+                        &format,
+                        html_report.as_deref(),
+                    )
+                }
+            }
+        }
+        Some(Commands::LayoutDiff {
+            expected,
+            actual,
+            mut selectors,
+            selectors_file,
+            threshold,
+            width,
+            height,
+            wait_for,
+            wait,
+            timeout,
+            output,
+            format,
+            save_expected,
+            save_actual,
+        }) => {
+            if let Some(path) = selectors_file {
+                selectors.extend(read_selectors(&path)?);
+            }
+            let options = ScreenshotOptions {
+                width,
+                height,
+                wait_for,
+                wait,
+                timeout,
+                user_agent,
+                ..Default::default()
+            };
+            let sides = [(expected, save_expected), (actual, save_actual)];
+            let browser = if sides.iter().any(|(side, _)| is_http_url(side)) {
+                if selectors.is_empty() {
+                    return Err(webshot::WebshotError::config(
+                        "Capturing a layout needs at least one --selector or a --selectors-file",
+                    ));
+                }
+                Some(launch_browser(chrome_path, chrome_flags, no_javascript, &target).await?)
+            } else {
+                None
+            };
+
+            let mut snapshots = Vec::new();
+            for (side, save) in sides {
+                let snapshot = match &browser {
+                    Some(browser) if is_http_url(&side) => {
+                        browser.capture_layout(&side, &selectors, &options).await?
+                    }
+                    _ => LayoutSnapshot::load(&side)?,
+                };
+                if let Some(path) = save {
+                    OutputHandler::ensure_output_dir(&path)?;
+                    snapshot.save(&path)?;
+                    info!("Layout snapshot saved to {}", path.display());
+                }
+                snapshots.push(snapshot);
+            }
+            report_layout_diff(&snapshots[0], &snapshots[1], threshold, output, &format)
+        }
+        Some(Commands::Baseline {
+            action:
+                BaselineAction::Accept {
+                    name,
+                    image,
+                    store,
+                    expected_version,
+                    force,
+                    git,
+                },
+        }) => {
+            let condition = if force {
+                PutCondition::Overwrite
+            } else {
+                // An empty version (e.g. from a result without a baseline) means none
+                PutCondition::expecting(expected_version.filter(|version| !version.is_empty()))
+            };
+            accept_baseline(&name, &image, &store, &condition, git).await
+        }
+        Some(Commands::Health { output_dir }) => {
+            check_health(
+                &output_dir,
+                chrome_path,
+                chrome_flags,
+                no_javascript,
+                target,
+            )
+            .await
+        }
+        Some(Commands::Serve {
+            port,
+            host,
+            pool_size,
+            max_uses,
+            job_concurrency,
+            max_queued_jobs,
+            webhook_allow,
+            job_retention,
+            jobs_dir,
+            signing_secret,
+            drain_delay,
+        }) => {
+            let options = PoolOptions::new()
+                .size(usize::from(pool_size))
+                .max_uses(max_uses);
+            let jobs = JobQueue::open(
+                jobs_dir,
+                JobQueueOptions {
+                    concurrency: usize::from(job_concurrency),
+                    capacity: max_queued_jobs,
+                    webhook_hosts: webhook_allow,
+                    retention: Some(Duration::from_secs(job_retention)),
+                },
+            )?;
+            run_server(
+                std::net::SocketAddr::new(host, port),
+                options,
+                jobs,
+                signing_secret.map(UrlSigner::new),
+                Duration::from_secs(drain_delay),
+                chrome_path,
+                chrome_flags,
+                no_javascript,
+                target,
+            )
+            .await
+        }
+        Some(Commands::DevServer { port, host }) => {
+            webshot::dev_server::serve(std::net::SocketAddr::new(host, port)).await
+        }
+        Some(Commands::SignUrl {
+            url,
+            secret,
+            expires_in,
+            body,
+        }) => {
+            let expires = chrono::Utc::now().timestamp() + i64::from(expires_in);
+            let target = match body {
+                Some(path) => webshot::signing::with_body_hash(&url, &std::fs::read(path)?),
+                None => url.clone(),
+            };
+            let signed = UrlSigner::new(secret).sign_url(&target, expires)?;
+            report(Record::ok("sign-url").target(&url).detail(&signed), || {
+                signed.clone()
+            });
+            Ok(())
+        }
+        Some(Commands::Info { flags: true }) => print_flags(&chrome_flags, &target.removed_flags),
+        Some(Commands::Info { flags: false }) => {
+            print_info(chrome_path);
+            Ok(())
+        }
+        None => {
+            // Default behavior: screenshot with URL as positional argument
+            if let Some(url) = &cli.url {
+                let network_idle = cli.waits.network_idle();
+                let options = ScreenshotOptions {
+                    width: cli.image.width,
+                    height: cli.image.height,
+                    selector: cli.selector,
+                    javascript: cli.javascript,
+                    wait_for: cli.waits.wait_for,
+                    timeout: cli.waits.timeout,
+                    retina: cli.image.retina,
+                    quality: cli.image.quality,
+                    wait: cli.waits.wait,
+                    user_agent,
+                    capture: if cli.paginate_preview.is_some() {
+                        CaptureMode::FullPage
+                    } else {
+                        CaptureMode::resolve(cli.capture, cli.full_page)?
+                    },
+                    freeze_sticky: cli.freeze_sticky,
+                    paginate_preview: cli.paginate_preview,
+                    auto_height: cli.auto_height,
+                    max_height: cli.max_height,
+                    touch: cli.touch,
+                    clip: cli.clip,
+                    mask_selectors: cli.mask_selectors.clone(),
+                    mask_color: cli.mask_color.clone(),
+                    trigger_lazy: cli.trigger_lazy,
+                    lazy_scroll_step: cli.lazy_step,
+                    lazy_settle_ms: cli.lazy_settle,
+                    network_idle,
+                    device: cli.device,
+                    stabilize: cli.stabilize,
+                    hide_scrollbars: cli.hide_scrollbars,
+                    disable_javascript: no_javascript,
+                    color_scheme: cli.color_scheme,
+                    anchor_scroll: !cli.no_anchor_scroll,
+                    init_scripts: read_init_scripts(&cli.init_script)?,
+                    png_colors: cli.png_colors,
+                    grayscale: cli.grayscale,
+                    session: cli.load_session.map(SessionState::load).transpose()?,
+                    save_session: cli.save_session,
+                    tiff_compression: cli.tiff_compression,
+                    image_filters: Vec::new(),
+                    auth: cli.request.auth(),
+                    headers: cli.request.header_map(),
+                    steps: Vec::new(),
+                    adblock: cli.request.adblock_filters()?,
+                    block_resources: cli.request.block_resources,
+                    mocks: cli
+                        .mocks
+                        .map(Mocks::from_file)
+                        .transpose()?
+                        .unwrap_or_default(),
+                    dismiss_overlays: overlay_dismissal(
+                        cli.auto_dismiss_overlays,
+                        cli.overlay_rules.as_deref(),
+                        cli.overlay_choice,
+                    )?,
+                    fail_on_http_error: cli.fail_on_http_error,
+                    fail_on_console_error: cli.fail_on_console_error,
+                    fail_on_js_exception: cli.fail_on_js_exception,
+                    console_log: cli.console_log,
+                    har: cli.har,
+                };
+                if cli.explain {
+                    print_explanations(&[explain_flags(url, cli.output.as_deref(), matches)]);
+                }
+                take_screenshot(
+                    url,
+                    cli.output,
+                    options,
+                    &cli.widths,
+                    &retry.policy(),
+                    chrome_path,
+                    chrome_flags,
+                    no_javascript,
+                    target,
+                )
+                .await
+            } else {
+                eprintln!(
+                    "{}: {}",
+                    tr("error-prefix", &[]),
+                    tr("error-url-required", &[])
+                );
+                eprintln!("{}", tr("error-see-help", &[]));
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+/// Page loading options of the text, outline and assets commands
+fn text_options(
+    selector: Option<String>,
+    javascript: Option<String>,
+    waits: WaitArgs,
+    request: &RequestArgs,
+    user_agent: Option<String>,
+) -> Result<TextOptions> {
+    Ok(TextOptions {
+        selector,
+        javascript,
+        network_idle: waits.network_idle(),
+        wait_for: waits.wait_for,
+        wait: waits.wait,
+        timeout: waits.timeout,
+        user_agent,
+        auth: request.auth(),
+        headers: request.header_map(),
+        blocker: request.blocker()?,
+    })
+}
+
+/// Overlay dismissal settings for `--auto-dismiss-overlays`, with any
+/// `--overlay-rules` appended to the built-in rules
+fn overlay_dismissal(
+    enabled: bool,
+    rules_file: Option<&Path>,
+    choice: OverlayChoice,
+) -> Result<Option<OverlayDismissal>> {
+    enabled
+        .then(|| OverlayDismissal::with_rules_file(rules_file, choice))
+        .transpose()
+}
+
+fn init_logging(verbose: u8, tui: bool, porcelain: bool) {
+    let filter = match verbose {
+        0 => "webshot=warn",
+        1 => "webshot=info",
+        2 => "webshot=debug",
+        _ => "webshot=trace,headless_chrome=debug",
+    };
 
     tracing_subscriber::registry()
         .with(
             tracing_subscriber::EnvFilter::try_from_default_env()
                 .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new(filter)),
         )
-        .with(tracing_subscriber::fmt::layer().with_target(false))
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_target(false)
+                .with_writer(
+                    // Log lines would tear through the dashboard, and stdout
+                    // is reserved for records in porcelain mode
+                    if tui {
+                        BoxMakeWriter::new(std::io::sink)
+                    } else if porcelain {
+                        BoxMakeWriter::new(std::io::stderr)
+                    } else {
+                        BoxMakeWriter::new(std::io::stdout)
+                    },
+                ),
+        )
         .init();
 }
 
 #[allow(clippy::too_many_arguments)]
-async fn take_screenshot(
+async fn take_screenshot(
+    url: &str,
+    output: Option<PathBuf>,
+    options: ScreenshotOptions,
+    widths: &[u32],
+    retry: &RetryPolicy,
+    chrome_path: Option<PathBuf>,
+    chrome_flags: Vec<String>,
+    no_javascript: bool,
+    target: BrowserTarget,
+) -> Result<()> {
+    validate_navigation_url(url, "screenshot command")?;
+    info!("Taking screenshot of: {}", url);
+
+    let browser = launch_browser(chrome_path, chrome_flags, no_javascript, &target).await?;
+
+    let output_path = output.clone().unwrap_or_else(|| {
+        // Determine format from output path or default to PNG
+        let format = if let Some(ref output_path) = output {
+            if let Some(ext) = output_path.extension() {
+                match ext.to_str().unwrap_or("").to_lowercase().as_str() {
+                    "jpg" | "jpeg" => "jpg",
+                    "webp" => "webp",
+                    "gif" => "gif",
+                    "tif" | "tiff" => "tiff",
+                    "pdf" => "pdf",
+                    _ => "png",
+                }
+            } else {
+                "png"
+            }
+        } else {
+            "png"
+        };
+
+        PathBuf::from(format!(
+            "screenshot_{}.{}",
+            chrono::Utc::now().format("%Y%m%d_%H%M%S"),
+            format
+        ))
+    });
+
+    let what = format!("Screenshot of {}", url);
+    if widths.is_empty() {
+        let saved = retry
+            .run(&what, |_| browser.capture(url, &output_path, &options))
+            .await?;
+        report(
+            Record::ok("screenshot")
+                .target(url)
+                .output(&saved.path)
+                .http_status(saved.status),
+            || tr("screenshot-saved", &[("path", &saved.path.display())]),
+        );
+        return Ok(());
+    }
+
+    for &width in widths {
+        let options = ScreenshotOptions {
+            width,
+            ..options.clone()
+        };
+        let width_path = OutputHandler::with_width_suffix(&output_path, width);
+
+        let saved = retry
+            .run(&what, |_| browser.capture(url, &width_path, &options))
+            .await?;
+        report(
+            Record::ok("screenshot")
+                .target(url)
+                .output(&saved.path)
+                .http_status(saved.status),
+            || tr("screenshot-saved", &[("path", &saved.path.display())]),
+        );
+    }
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn generate_pdf(
+    url: &str,
+    output: Option<PathBuf>,
+    options: &PdfOptions,
+    chrome_path: Option<PathBuf>,
+    chrome_flags: Vec<String>,
+    no_javascript: bool,
+    target: BrowserTarget,
+    retry: &RetryPolicy,
+) -> Result<()> {
+    validate_navigation_url(url, "pdf command")?;
+    info!("Generating PDF of: {}", url);
+
+    let browser = launch_browser(chrome_path, chrome_flags, no_javascript, &target).await?;
+
+    let output_path = output.unwrap_or_else(|| {
+        PathBuf::from(format!(
+            "page_{}.pdf",
+            chrono::Utc::now().format("%Y%m%d_%H%M%S")
+        ))
+    });
+
+    retry
+        .run(&format!("PDF of {}", url), |_| {
+            browser.pdf(url, &output_path, options)
+        })
+        .await?;
+
+    report(Record::ok("pdf").target(url).output(&output_path), || {
+        tr("pdf-saved", &[("path", &output_path.display())])
+    });
+    Ok(())
+}
+
+/// Sitemap to expand into batch capture entries
+struct SitemapSource {
+    url: String,
+    options: SitemapOptions,
+    name_template: String,
+    defaults: DefaultsArgs,
+}
+
+/// Capture options given on the command line for generated batch entries,
+/// overriding a batch config's defaults
+struct DefaultsArgs {
+    width: Option<u32>,
+    height: Option<u32>,
+    timeout: Option<u64>,
+    wait: Option<u64>,
+    retina: bool,
+    quality: Option<u8>,
+}
+
+impl DefaultsArgs {
+    fn apply(self, defaults: &mut DefaultConfig) {
+        if let Some(width) = self.width {
+            defaults.width = width;
+        }
+        if let Some(height) = self.height {
+            defaults.height = height;
+        }
+        if let Some(timeout) = self.timeout {
+            defaults.timeout = timeout;
+        }
+        if let Some(wait) = self.wait {
+            defaults.wait = wait;
+        }
+        if self.retina {
+            defaults.retina = true;
+        }
+        if self.quality.is_some() {
+            defaults.quality = self.quality;
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn process_config(
+    config_file: Option<&Path>,
+    config_format: Option<ConfigFormat>,
+    sitemap: Option<SitemapSource>,
+    output_dir: Option<PathBuf>,
+    output_layout: Option<OutputLayout>,
+    merge_output: Option<PathBuf>,
+    parallel: usize,
+    dedupe: Option<DedupeOptions>,
+    retention: RetentionPolicy,
+    tui: bool,
+    batch_report: Option<BatchReport>,
+    explain: Option<HashMap<String, OptionSource>>,
+    retry: RetryArgs,
+    user_agent: Option<&str>,
+    chrome_path: Option<PathBuf>,
+    chrome_flags: Vec<String>,
+    no_javascript: bool,
+    target: BrowserTarget,
+) -> Result<()> {
+    let mut layers = Vec::new();
+    let mut config = match config_file {
+        Some(config_file) => {
+            info!("Processing config file: {}", config_file.display());
+            let raw = Config::read_value(config_file, config_format)?;
+            layers.push((config_file.to_path_buf(), raw.clone()));
+            Config::from_yaml(raw)?
+        }
+        None => Config {
+            screenshots: Vec::new(),
+            defaults: Default::default(),
+            proxies: Vec::new(),
+            hooks: Default::default(),
+            mocks: Vec::new(),
+            pdfs: None,
+            suite: Default::default(),
+        },
+    };
+
+    if let Some(sitemap) = sitemap {
+        let urls = fetch_sitemap_urls(&sitemap.url, &sitemap.options).await?;
+        info!("Sitemap expanded to {} pages", urls.len());
+        // Entries from the config file already have their defaults applied
+        sitemap.defaults.apply(&mut config.defaults);
+        config.extend_with_urls(&urls, &sitemap.name_template);
+    }
+
+    if let Some(layout) = output_layout {
+        config.set_output_layout(layout);
+    }
+    if let Some(user_agent) = user_agent {
+        config.set_user_agent(user_agent);
+    }
+    if let Some(merge_output) = merge_output {
+        let Some(pdfs) = &mut config.pdfs else {
+            return Err(webshot::WebshotError::config(
+                "--merge-output needs a pdfs section in the config",
+            ));
+        };
+        pdfs.merge_output = Some(merge_output);
+    }
+    retry.apply(&mut config.defaults);
+    config.validate()?;
+    if let Some(cli) = explain {
+        print_explanations(&explain_config(&config, &layers, &cli)?);
+    }
+    run_batch(
+        &config,
+        output_dir,
+        parallel,
+        dedupe,
+        retention,
+        tui,
+        batch_report,
+        chrome_path,
+        chrome_flags,
+        no_javascript,
+        target,
+    )
+    .await?;
+    Ok(())
+}
+
+/// Print the validation report of a batch config, failing when an entry has errors
+fn validate_file(
+    config_file: &Path,
+    config_format: Option<ConfigFormat>,
+    output_dir: Option<&Path>,
+    output_layout: Option<OutputLayout>,
+    retry: RetryArgs,
+) -> Result<()> {
+    let raw = Config::read_value(config_file, config_format)?;
+    let mut config = Config::from_yaml(raw.clone())?;
+    if let Some(layout) = output_layout {
+        config.set_output_layout(layout);
+    }
+    retry.apply(&mut config.defaults);
+    let report = validate_config(&config, &[(config_file.to_path_buf(), raw)], output_dir)?;
+    print!("{}", report);
+    if !report.is_valid() {
+        return Err(webshot::WebshotError::config(format!(
+            "{} has {}",
+            config_file.display(),
+            webshot::validate::counted(report.error_count(), "error", "errors")
+        )));
+    }
+    Ok(())
+}
+
+/// Run every project found from `dir`, or only those with files changed
+/// since `changed_since`, writing relative outputs into each project's
+/// directory. Fails once all projects ran when any capture failed.
+#[allow(clippy::too_many_arguments)]
+async fn run_projects(
+    dir: &Path,
+    changed_since: Option<&str>,
+    parallel: usize,
+    dry_run: bool,
+    explain: bool,
+    retry: RetryArgs,
+    user_agent: Option<&str>,
+    chrome_path: Option<PathBuf>,
+    chrome_flags: Vec<String>,
+    no_javascript: bool,
+    target: BrowserTarget,
+) -> Result<()> {
+    let projects = project::discover(dir)?;
+    if projects.is_empty() {
+        return Err(webshot::WebshotError::config(format!(
+            "No {} with screenshots found in or below {}",
+            project::PROJECT_FILE,
+            dir.display()
+        )));
+    }
+
+    let selected = match changed_since {
+        Some(since) => {
+            let changed = webshot::git::changed_files(dir, since).await?;
+            info!("{} files changed since {}", changed.len(), since);
+            project::affected(&projects, &changed)
+        }
+        None => projects.iter().collect(),
+    };
+    if selected.is_empty() {
+        say(|| {
+            format!(
+                "No projects changed since {}",
+                changed_since.unwrap_or_default()
+            )
+        });
+        return Ok(());
+    }
+
+    let mut failed = 0;
+    for project in selected {
+        say(|| {
+            format!(
+                "Project {} ({} captures)",
+                project.dir.display(),
+                project.config.jobs().len()
+            )
+        });
+        if explain {
+            let layers = project
+                .sources
+                .iter()
+                .map(|file| {
+                    let raw = serde_yaml::from_str(&std::fs::read_to_string(file)?)?;
+                    Ok((file.clone(), raw))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            print_explanations(&explain_config(&project.config, &layers, &HashMap::new())?);
+        }
+        if dry_run {
+            continue;
+        }
+        let mut config = project.config.clone();
+        retry.apply(&mut config.defaults);
+        if let Some(user_agent) = user_agent {
+            config.set_user_agent(user_agent);
+        }
+        failed += run_batch(
+            &config,
+            Some(project.dir.clone()),
+            parallel,
+            None,
+            RetentionPolicy::default(),
+            false,
+            None,
+            chrome_path.clone(),
+            chrome_flags.clone(),
+            no_javascript,
+            target.clone(),
+        )
+        .await?;
+    }
+
+    if failed > 0 {
+        return Err(webshot::WebshotError::screenshot(format!(
+            "{} failed",
+            webshot::validate::counted(failed, "capture", "captures")
+        )));
+    }
+    Ok(())
+}
+
+/// Run a batch config, returning how many of its captures failed
+#[allow(clippy::too_many_arguments)]
+async fn run_batch(
+    config: &Config,
+    output_dir: Option<PathBuf>,
+    parallel: usize,
+    dedupe: Option<DedupeOptions>,
+    retention: RetentionPolicy,
+    tui: bool,
+    batch_report: Option<BatchReport>,
+    chrome_path: Option<PathBuf>,
+    chrome_flags: Vec<String>,
+    no_javascript: bool,
+    target: BrowserTarget,
+) -> Result<usize> {
+    if tui {
+        webshot::tui::ensure_terminal()?;
+    }
+
+    let browser = launch_browser(chrome_path, chrome_flags, no_javascript, &target).await?;
+
+    config.validate()?;
+    let retry = config.defaults.retry_policy();
+    let mut suite_passed = true;
+    let mut failed = 0;
+    if !config.screenshots.is_empty() {
+        let monitor = BatchMonitor::new(config.jobs(), tui);
+        info!(
+            "Processing {} screenshots with {} parallel tasks",
+            monitor.job_count(),
+            parallel
+        );
+        // SIGTERM or Ctrl-C skips queued entries but lets running captures finish
+        let run = drain_on_shutdown(
+            &monitor,
+            browser.run_jobs(
+                &monitor,
+                output_dir.clone(),
+                parallel,
+                &retry,
+                &config.defaults.rate_limit,
+            ),
+        );
+        if tui {
+            let (_, dashboard) = tokio::join!(run, webshot::tui::run_dashboard(&monitor));
+            dashboard?;
+        } else {
+            run.await;
+        }
+        print_batch_summary(&monitor, browser.launch_time(), output_dir.as_deref());
+        failed = monitor.stats().failed;
+        #[cfg(feature = "chaos")]
+        if let Some(chaos) = &target.chaos {
+            let stats = chaos.stats();
+            info!(
+                "Chaos injected {} delays, {} dropped requests and {} tab crashes",
+                stats.delays, stats.dropped, stats.crashes
+            );
+        }
+
+        let manifest = output_dir
+            .as_deref()
+            .unwrap_or(Path::new("."))
+            .join(RUN_MANIFEST);
+        let outputs = config.output_paths(output_dir.as_deref());
+        let run_manifest = RunManifest::from_monitor(&monitor, &outputs);
+        run_manifest.write(&manifest)?;
+        say(|| tr("batch-manifest-saved", &[("path", &manifest.display())]));
+
+        if let Some(suite) = score_run(config, &monitor.jobs(), &outputs) {
+            let path = manifest.with_file_name(SUITE_REPORT);
+            suite.write(&path)?;
+            print_suite_report(&suite, &path);
+            suite_passed = suite.passed;
+        }
+
+        if let Some(batch_report) = &batch_report {
+            let index = batch_report.write(&run_manifest)?;
+            report(Record::ok("report").output(&index), || {
+                tr("batch-report-saved", &[("path", &index.display())])
+            });
+        }
+    }
+
+    if let Some(pdfs) = &config.pdfs {
+        print_pdf_batch(&browser, pdfs, output_dir.as_deref(), parallel, &retry).await?;
+    }
+
+    if let Some(dedupe) = dedupe {
+        let report = dedupe_files(&config.output_paths(output_dir.as_deref()), &dedupe)?;
+        let manifest = output_dir
+            .as_deref()
+            .unwrap_or(Path::new("."))
+            .join(DEDUPE_MANIFEST);
+        report.write_manifest(&manifest)?;
+        print_dedupe_report(&report, &manifest);
+    }
+
+    if let Some(dir) = output_dir.filter(|_| retention.is_set()) {
+        print_prune_report(&prune(&dir, &retention, false)?);
+    }
+
+    if !suite_passed {
+        return Err(webshot::WebshotError::baseline(
+            "Suite comparison failed its thresholds",
+        ));
+    }
+    Ok(failed)
+}
+
+/// Print the outcome of a batch run's baseline comparisons, saved to `path`
+fn print_suite_report(suite: &SuiteReport, path: &Path) {
+    for page in suite.pages.iter().filter(|page| !page.similar) {
+        match &page.error {
+            Some(error) => warn!("{} not compared: {}", page.url, error),
+            None => warn!(
+                "{} differs from {} (similarity {:.4}, weight {})",
+                page.url,
+                page.baseline.display(),
+                page.similarity,
+                page.weight
+            ),
+        }
+    }
+    report(
+        Record::new("suite", if suite.passed { "passed" } else { "failed" })
+            .output(path)
+            .detail(format!("{:.6}", suite.score)),
+        || suite.to_string(),
+    );
+}
+
+/// Print every page of a `pdfs` section, `parallel` at a time, then merge
+/// them into a book when the section has a `merge_output`
+async fn print_pdf_batch(
+    browser: &Browser,
+    pdfs: &PdfBatchConfig,
+    output_dir: Option<&Path>,
+    parallel: usize,
+    retry: &RetryPolicy,
+) -> Result<()> {
+    use futures::StreamExt;
+
+    let options = pdfs.options();
+    let outputs = pdfs.page_outputs(output_dir);
+    info!(
+        "Printing {} PDFs with {} parallel tasks",
+        outputs.len(),
+        parallel
+    );
+    let results: Vec<Result<()>> = futures::stream::iter(pdfs.pages.iter().zip(&outputs))
+        .map(|(page, output)| {
+            let options = &options;
+            async move {
+                retry
+                    .run(&format!("PDF of {}", page.url), |_| {
+                        browser.pdf(&page.url, output, options)
+                    })
+                    .await
+            }
+        })
+        .buffered(parallel.max(1))
+        .collect()
+        .await;
+
+    let mut failed = 0;
+    for ((page, output), result) in pdfs.pages.iter().zip(&outputs).zip(&results) {
+        match result {
+            Ok(()) => report(Record::ok("pdf").target(&page.url).output(output), || {
+                tr("pdf-saved", &[("path", &output.display())])
+            }),
+            Err(e) => {
+                failed += 1;
+                report(Record::failed("pdf", e).target(&page.url), || {
+                    tr("batch-failed", &[("url", &page.url), ("error", e)])
+                });
+            }
+        }
+    }
+
+    let Some(merge_output) = pdfs.resolved_merge_output(output_dir) else {
+        return Ok(());
+    };
+    if failed > 0 {
+        return Err(webshot::WebshotError::pdf(format!(
+            "{} of {} PDFs failed, not merging {}",
+            failed,
+            outputs.len(),
+            merge_output.display()
+        )));
+    }
+    let chapters: Vec<Chapter> = pdfs
+        .pages
+        .iter()
+        .zip(outputs)
+        .map(|(page, path)| Chapter {
+            title: page
+                .title
+                .clone()
+                .or_else(|| pdf_title(&path))
+                .unwrap_or_else(|| page.url.clone()),
+            url: page.url.clone(),
+            path,
+        })
+        .collect();
+    let title = pdfs.title.as_deref().unwrap_or("Contents");
+    let book = build_book(browser, &chapters, &merge_output, title, pdfs.toc, &options).await?;
+    report(
+        Record::ok("book")
+            .target(title)
+            .output(&book.path)
+            .detail(book.pages),
+        || {
+            tr(
+                "book-saved",
+                &[("pages", &book.pages), ("path", &book.path.display())],
+            )
+        },
+    );
+    Ok(())
+}
+
+/// Print a health report and exit non-zero unless healthy. A browser that
+/// fails to launch counts as unhealthy rather than as an error.
+async fn check_health(
+    output_dir: &Path,
+    chrome_path: Option<PathBuf>,
+    chrome_flags: Vec<String>,
+    no_javascript: bool,
+    target: BrowserTarget,
+) -> Result<()> {
+    let health = match launch_browser(chrome_path, chrome_flags, no_javascript, &target).await {
+        Ok(browser) => browser.health(output_dir, false),
+        Err(e) => {
+            eprintln!("{}", e);
+            HealthReport {
+                browser: false,
+                disk_writable: disk_writable(output_dir),
+                draining: false,
+            }
+        }
+    };
+
+    let status = if health.is_healthy() { "ok" } else { "failed" };
+    let record = Record::new("health", status)
+        .target(output_dir.display())
+        .detail(serde_json::to_string(&health)?);
+    let pretty = serde_json::to_string_pretty(&health)?;
+    report(record, || pretty);
+    if !health.is_healthy() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Print the webshot version and the Chrome executable launched, with the
+/// version it reports
+fn print_info(chrome_path: Option<PathBuf>) {
+    let version = format!("webshot {}", env!("CARGO_PKG_VERSION"));
+    let chrome = match chrome_path.or_else(|| headless_chrome::browser::default_executable().ok()) {
+        Some(path) => match executable_capabilities(&path) {
+            Some(capabilities) => format!("{} ({})", path.display(), capabilities),
+            None => format!("{} (version unknown)", path.display()),
+        },
+        None => "not found (set --chrome-path or CHROME)".to_string(),
+    };
+    report(Record::ok("info").target(&version).detail(&chrome), || {
+        format!("{}\nChrome: {}", version, chrome)
+    });
+}
+
+/// Print the default Chrome flags with why each is set, marking those in
+/// `removed`, followed by the flags the run adds, or a record per flag in
+/// porcelain mode
+fn print_flags(chrome_flags: &[String], removed: &[String]) -> Result<()> {
+    let removed = removed
+        .iter()
+        .map(|name| default_flag(name))
+        .collect::<Result<Vec<_>>>()?;
+    let width = DEFAULT_FLAGS
+        .iter()
+        .map(|flag| flag.flag().len())
+        .max()
+        .unwrap_or_default();
+    say(|| "Default flags:".to_string());
+    for flag in &DEFAULT_FLAGS {
+        let status = if removed.contains(&flag) {
+            "removed"
+        } else {
+            "default"
+        };
+        report(
+            Record::new("flag", status)
+                .target(flag.flag())
+                .detail(flag.reason),
+            || {
+                let mark = if status == "removed" {
+                    " (removed)"
+                } else {
+                    ""
+                };
+                format!("  {:width$}  {}{}", flag.flag(), flag.reason, mark)
+            },
+        );
+    }
+    if !chrome_flags.is_empty() {
+        say(|| "Added flags:".to_string());
+        for flag in chrome_flags {
+            report(Record::new("flag", "added").target(flag), || {
+                format!("  {}", flag)
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Print job counts, the time spent in each capture phase, every failure
+/// and the jobs that succeeded only after being retried, or a record per
+/// job in porcelain mode
+fn print_batch_summary(monitor: &BatchMonitor, launch_time: Duration, output_dir: Option<&Path>) {
+    if porcelain().is_some() {
+        for job in monitor.jobs() {
+            let output = match output_dir {
+                Some(dir) => dir.join(&job.output),
+                None => job.output.clone(),
+            };
+            let record = match &job.status {
+                JobStatus::Failed(error) => Record::failed("screenshot", error),
+                status => Record::new("screenshot", status.label()).output(&output),
+            };
+            emit(record.target(&job.url).http_status(job.http_status));
+        }
+        return;
+    }
+
+    let stats = monitor.stats();
+    println!(
+        "{}",
+        tr(
+            "batch-finished",
+            &[
+                ("done", &stats.done),
+                ("failed", &stats.failed),
+                ("skipped", &stats.skipped),
+            ]
+        )
+    );
+    let mut phases = monitor.phase_totals();
+    if !phases.is_empty() {
+        phases.record(Phase::Launch, launch_time);
+        println!("{}", tr("batch-phases", &[("phases", &phases)]));
+    }
+    for job in monitor.jobs() {
+        match &job.status {
+            JobStatus::Failed(error) => println!(
+                "{}",
+                tr("batch-failed", &[("url", &job.url), ("error", error)])
+            ),
+            JobStatus::Done if job.attempts > 1 => println!(
+                "{}",
+                tr(
+                    "batch-retried",
+                    &[("attempts", &job.attempts), ("url", &job.url)]
+                )
+            ),
+            _ => {}
+        }
+    }
+}
+
+/// Deduplicate every file under a directory and write the manifest there
+fn dedupe_directory(dir: &Path, options: &DedupeOptions) -> Result<()> {
+    if !dir.is_dir() {
+        return Err(webshot::WebshotError::InvalidPath {
+            path: dir.to_path_buf(),
+        });
+    }
+
+    let mut files = Vec::new();
+    collect_files(dir, &mut files)?;
+    files.sort();
+
+    let manifest = dir.join(DEDUPE_MANIFEST);
+    files.retain(|path| path != &manifest);
+
+    let report = dedupe_files(&files, options)?;
+    report.write_manifest(&manifest)?;
+    print_dedupe_report(&report, &manifest);
+    Ok(())
+}
+
+/// Print what pruning an archive directory removed, to stderr in porcelain
+/// mode so stdout only has capture records
+fn print_prune_report(report: &PruneReport) {
+    if porcelain().is_some() {
+        eprint!("{}", report);
+    } else {
+        print!("{}", report);
+    }
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            collect_files(&entry.path(), files)?;
+        } else if file_type.is_file() {
+            files.push(entry.path());
+        }
+    }
+    Ok(())
+}
+
+fn print_dedupe_report(report: &DedupeReport, manifest: &Path) {
+    let dir = manifest
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    emit(
+        Record::ok("dedupe")
+            .target(dir.display())
+            .output(manifest)
+            .detail(report.entries.len()),
+    );
+    say(|| {
+        tr(
+            "dedupe-summary",
+            &[
+                ("deduped", &report.entries.len()),
+                ("scanned", &report.files_scanned),
+                ("saved", &format_file_size(report.bytes_saved)),
+            ],
+        )
+    });
+    say(|| tr("dedupe-manifest-saved", &[("path", &manifest.display())]));
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn crawl_site(
+    url: &str,
+    output_dir: &Path,
+    state_path: &Path,
+    restart: bool,
+    crawl_options: &CrawlOptions,
+    options: &ScreenshotOptions,
+    name_template: &str,
+    chrome_path: Option<PathBuf>,
+    chrome_flags: Vec<String>,
+    no_javascript: bool,
+    target: BrowserTarget,
+) -> Result<()> {
+    validate_navigation_url(url, "crawl command")?;
+    options.validate()?;
+
+    let mut state = if state_path.exists() && !restart {
+        let state = CrawlState::resume(state_path, url)?;
+        say(|| {
+            format!(
+                "Resuming crawl from {} ({} pages captured, {} queued)",
+                state_path.display(),
+                state.pages.len(),
+                state.frontier.len()
+            )
+        });
+        state
+    } else {
+        CrawlState::new(url)?
+    };
+
+    OutputHandler::ensure_output_dir(state_path)?;
+    let browser = launch_browser(chrome_path, chrome_flags, no_javascript, &target).await?;
+    browser
+        .crawl(
+            &mut state,
+            crawl_options,
+            options,
+            output_dir,
+            name_template,
+            state_path,
+        )
+        .await?;
+
+    report(
+        Record::ok("crawl")
+            .target(url)
+            .output(output_dir)
+            .detail(state.pages.len()),
+        || {
+            format!(
+                "Crawl completed: {} pages captured to {}",
+                state.pages.len(),
+                output_dir.display()
+            )
+        },
+    );
+
+    let clusters = state.duplicate_clusters();
+    if !clusters.is_empty() && porcelain().is_none() {
+        println!("Near-duplicate clusters: {}", clusters.len());
+        for cluster in clusters {
+            println!(
+                "  {} ({} duplicates)",
+                cluster.canonical,
+                cluster.duplicates.len()
+            );
+            for duplicate in cluster.duplicates {
+                println!("    {}", duplicate);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn pick_element(
+    url: &str,
+    output: Option<PathBuf>,
+    timeout: u64,
+    chrome_path: Option<PathBuf>,
+    chrome_flags: Vec<String>,
+    no_javascript: bool,
+) -> Result<()> {
+    validate_navigation_url(url, "pick command")?;
+    if let Some(output) = &output {
+        OutputHandler::validate_output_path(output)?;
+    }
+
+    let browser = Browser::new_headful(chrome_path, chrome_flags, !no_javascript).await?;
+
+    eprintln!("Click an element in the browser window to pick it (Esc cancels)");
+    let element = browser.pick_element(url, timeout).await?;
+    if !element.is_unique() {
+        eprintln!(
+            "Warning: selector matches {} elements; the first match is used for captures",
+            element.matches
+        );
+    }
+    say(|| element.selector.clone());
+
+    let mut record = Record::ok("pick").target(url).detail(&element.selector);
+    if let Some(output) = output {
+        let options = ScreenshotOptions::new().selector(element.selector);
+        let saved_path = browser.screenshot(url, &output, &options).await?;
+        eprintln!(
+            "{}",
+            tr("screenshot-saved", &[("path", &saved_path.display())])
+        );
+        record = record.output(&saved_path);
+    }
+    emit(record);
+
+    Ok(())
+}
+
+/// The browser captures run in and how it is driven
+#[derive(Debug, Clone)]
+struct BrowserTarget {
+    /// DevTools URL of a running Chrome to attach to (`--connect` or
+    /// `--connect-host`) instead of launching one
+    connect: Option<String>,
+    /// Show the window of a launched Chrome
+    headful: bool,
+    /// How often wait conditions are rechecked (`--poll-interval`)
+    poll_interval: Duration,
+    /// Default Chrome flags launched browsers leave out (`--remove-flag`)
+    removed_flags: Vec<String>,
+    /// Leave the profiles of launched browsers on disk (`--keep-profiles`)
+    keep_profiles: bool,
+    /// Faults injected into batch captures (`--chaos`), shared by every
+    /// browser launched for the target
+    #[cfg(feature = "chaos")]
+    chaos: Option<Arc<webshot::chaos::Chaos>>,
+}
+
+/// Launch or connect to the browser for `target`
+async fn launch_browser(
+    chrome_path: Option<PathBuf>,
+    chrome_flags: Vec<String>,
+    no_javascript: bool,
+    target: &BrowserTarget,
+) -> Result<Browser> {
+    let browser = match &target.connect {
+        Some(url) => Browser::connect(url, !no_javascript).await?,
+        None => {
+            Browser::new_without_flags(
+                chrome_path,
+                chrome_flags,
+                &target.removed_flags,
+                !no_javascript,
+                target.headful,
+            )
+            .await?
+        }
+    };
+    let browser = browser.with_poll_interval(target.poll_interval);
+    let browser = if target.keep_profiles {
+        browser.with_kept_profiles()
+    } else {
+        browser
+    };
+    #[cfg(feature = "chaos")]
+    let browser = match &target.chaos {
+        Some(chaos) => browser.with_chaos(chaos.clone()),
+        None => browser,
+    };
+    Ok(browser)
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run_server(
+    addr: std::net::SocketAddr,
+    options: PoolOptions,
+    jobs: JobQueue,
+    signer: Option<UrlSigner>,
+    drain_delay: Duration,
+    chrome_path: Option<PathBuf>,
+    chrome_flags: Vec<String>,
+    no_javascript: bool,
+    target: BrowserTarget,
+) -> Result<()> {
+    let pool = BrowserPool::with_launcher(options, move || {
+        let (chrome_path, chrome_flags, target) =
+            (chrome_path.clone(), chrome_flags.clone(), target.clone());
+        Box::pin(
+            async move { launch_browser(chrome_path, chrome_flags, no_javascript, &target).await },
+        )
+    });
+    // Start one browser up front so the first request does not wait for Chrome
+    pool.warm_up(1).await?;
+    info!("Recording jobs in {}", jobs.dir().display());
+    let mut api = Api::new(pool, jobs);
+    if let Some(signer) = signer {
+        api = api.with_signer(signer);
+    }
+    serve(addr, Arc::new(api), drain_delay).await
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run_repl(
+    url: Option<String>,
+    options: ScreenshotOptions,
+    target: BrowserTarget,
+    record_steps: Option<PathBuf>,
+    chrome_path: Option<PathBuf>,
+    chrome_flags: Vec<String>,
+    no_javascript: bool,
+) -> Result<()> {
+    if let Some(url) = &url {
+        validate_navigation_url(url, "repl command")?;
+    }
+
+    let browser = launch_browser(chrome_path, chrome_flags, no_javascript, &target).await?;
+    let mut repl = Repl::new(&browser, options).await?;
+    if let Some(path) = record_steps {
+        repl.record_steps(path)?;
+    }
+    println!("webshot REPL. Type :help for commands, :quit to exit.");
+
+    if let Some(url) = url {
+        if let Some(message) = repl.execute(ReplCommand::Goto(url)).await? {
+            println!("{}", message);
+        }
+    }
+    repl.run().await
+}
+
+async fn run_script_file(
+    file: &Path,
+    options: ScreenshotOptions,
+    target: BrowserTarget,
+    chrome_path: Option<PathBuf>,
+    chrome_flags: Vec<String>,
+    no_javascript: bool,
+) -> Result<()> {
+    if !file.is_file() {
+        return Err(webshot::WebshotError::script(format!(
+            "Script not found: {}",
+            file.display()
+        )));
+    }
+
+    let browser = launch_browser(chrome_path, chrome_flags, no_javascript, &target).await?;
+    let mut repl = Repl::new(&browser, options).await?;
+    run_script(&mut repl, file).await
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn extract_text(
     url: &str,
     output: Option<PathBuf>,
-    width: u32,
-    height: u32,
-    selector: Option<String>,
-    javascript: Option<String>,
-    wait_for: Option<String>,
-    timeout: u64,
-    retina: bool,
-    quality: Option<u8>,
-    wait: u64,
+    options: &TextOptions,
     chrome_path: Option<PathBuf>,
     chrome_flags: Vec<String>,
     no_javascript: bool,
-    user_agent: Option<String>,
+    target: BrowserTarget,
+    retry: &RetryPolicy,
 ) -> Result<()> {
-    validate_navigation_url(url, "screenshot command")?;
-    info!("Taking screenshot of: {}", url);
-
-    let browser = Browser::new(chrome_path, chrome_flags, !no_javascript).await?;
-
-    let options = ScreenshotOptions {
-        width,
-        height,
-        selector,
-        javascript,
-        wait_for,
-        timeout,
-        retina,
-        quality,
-        wait,
-        user_agent,
-    };
+    validate_navigation_url(url, "text command")?;
+    info!("Extracting text from: {}", url);
 
-    let output_path = output.clone().unwrap_or_else(|| {
-        // Determine format from output path or default to PNG
-        let format = if let Some(ref output_path) = output {
-            if let Some(ext) = output_path.extension() {
-                match ext.to_str().unwrap_or("").to_lowercase().as_str() {
-                    "jpg" | "jpeg" => "jpg",
-                    "webp" => "webp",
-                    "pdf" => "pdf",
-                    _ => "png",
-                }
-            } else {
-                "png"
-            }
-        } else {
-            "png"
-        };
+    let browser = launch_browser(chrome_path, chrome_flags, no_javascript, &target).await?;
 
-        PathBuf::from(format!(
-            "screenshot_{}.{}",
-            chrono::Utc::now().format("%Y%m%d_%H%M%S"),
-            format
-        ))
-    });
+    let text = retry
+        .run(&format!("Text extraction from {}", url), |_| {
+            browser.extract_text(url, options)
+        })
+        .await?;
 
-    browser.screenshot(url, &output_path, &options).await?;
+    match output {
+        Some(path) => {
+            OutputHandler::ensure_output_dir(&path)?;
+            std::fs::write(&path, &text)?;
+            report(Record::ok("text").target(url).output(&path), || {
+                tr("text-saved", &[("path", &path.display())])
+            });
+        }
+        None => {
+            report(Record::ok("text").target(url).detail(&text), || {
+                text.clone()
+            });
+        }
+    }
 
-    println!("Screenshot saved to: {}", output_path.display());
     Ok(())
 }
 
 #[allow(clippy::too_many_arguments)]
-async fn generate_pdf(
+async fn extract_outline(
     url: &str,
     output: Option<PathBuf>,
-    format: &str,
-    landscape: bool,
-    background: bool,
-    scale: f64,
-    javascript: Option<String>,
-    wait_for: Option<String>,
-    timeout: u64,
+    format: OutlineFormat,
+    options: &TextOptions,
     chrome_path: Option<PathBuf>,
     chrome_flags: Vec<String>,
     no_javascript: bool,
-    user_agent: Option<String>,
+    target: BrowserTarget,
+    retry: &RetryPolicy,
 ) -> Result<()> {
-    validate_navigation_url(url, "pdf command")?;
-    info!("Generating PDF of: {}", url);
-
-    let browser = Browser::new(chrome_path, chrome_flags, !no_javascript).await?;
-
-    let output_path = output.unwrap_or_else(|| {
-        PathBuf::from(format!(
-            "page_{}.pdf",
-            chrono::Utc::now().format("%Y%m%d_%H%M%S")
-        ))
-    });
+    validate_navigation_url(url, "outline command")?;
+    info!("Reading outline of: {}", url);
+    let browser = launch_browser(chrome_path, chrome_flags, no_javascript, &target).await?;
 
-    browser
-        .pdf(
-            url,
-            &output_path,
-            format,
-            landscape,
-            background,
-            scale,
-            javascript,
-            wait_for,
-            timeout,
-            user_agent,
-        )
+    let outline = retry
+        .run(&format!("Outline of {}", url), |_| {
+            browser.extract_outline(url, options)
+        })
         .await?;
+    for issue in &outline.issues {
+        warn!("{}: {}", url, issue);
+    }
+
+    let rendered = outline.render(format)?;
+    match output {
+        Some(path) => {
+            OutputHandler::ensure_output_dir(&path)?;
+            std::fs::write(&path, &rendered)?;
+            report(
+                Record::ok("outline")
+                    .target(url)
+                    .output(&path)
+                    .detail(outline.heading_count()),
+                || tr("outline-saved", &[("path", &path.display())]),
+            );
+        }
+        None => {
+            report(Record::ok("outline").target(url).detail(&rendered), || {
+                rendered.trim_end().to_string()
+            });
+        }
+    }
 
-    println!("PDF saved to: {}", output_path.display());
     Ok(())
 }
 
-async fn process_config(
-    config_file: &PathBuf,
-    output_dir: Option<PathBuf>,
-    parallel: usize,
+/// Capture an email template at each of `widths`, in light and, with
+/// `dark`, dark color scheme
+#[allow(clippy::too_many_arguments)]
+async fn capture_email(
+    template: &Path,
+    output: Option<PathBuf>,
+    widths: &[EmailWidth],
+    dark: bool,
+    css: bool,
+    options: &ScreenshotOptions,
     chrome_path: Option<PathBuf>,
     chrome_flags: Vec<String>,
     no_javascript: bool,
+    target: BrowserTarget,
+    retry: &RetryPolicy,
 ) -> Result<()> {
-    info!("Processing config file: {}", config_file.display());
+    let html = std::fs::read_to_string(template)?;
+    if css {
+        for warning in check_css(&html) {
+            warn!("{}: {}", template.display(), warning);
+        }
+    }
 
-    let config = Config::from_file(config_file)?;
-    let browser = Browser::new(chrome_path, chrome_flags, !no_javascript).await?;
+    info!("Capturing email template: {}", template.display());
+    let browser = launch_browser(chrome_path, chrome_flags, no_javascript, &target).await?;
+    let output = output.unwrap_or_else(|| template.with_extension("png"));
+    let schemes: &[ColorScheme] = if dark {
+        &[ColorScheme::Light, ColorScheme::Dark]
+    } else {
+        &[ColorScheme::Light]
+    };
 
-    browser
-        .process_config(&config, output_dir, parallel)
-        .await?;
+    for &width in widths {
+        for &scheme in schemes {
+            let options = ScreenshotOptions {
+                width: width.width,
+                color_scheme: Some(scheme),
+                ..options.clone()
+            };
+            let path = email_output(&output, width, scheme);
+            let saved_path = retry
+                .run(
+                    &format!("Email {} at {} ({})", template.display(), width, scheme),
+                    |_| browser.screenshot_file(template, &path, &options),
+                )
+                .await?;
+            report(
+                Record::ok("email")
+                    .target(template.display())
+                    .output(&saved_path),
+                || {
+                    tr(
+                        "email-saved",
+                        &[
+                            ("path", &saved_path.display()),
+                            ("width", &width),
+                            ("scheme", &scheme),
+                        ],
+                    )
+                },
+            );
+        }
+    }
 
-    println!("Batch processing completed successfully");
     Ok(())
 }
 
 #[allow(clippy::too_many_arguments)]
-async fn extract_text(
+async fn capture_assets(
     url: &str,
-    selector: Option<String>,
-    output: Option<PathBuf>,
-    javascript: Option<String>,
-    wait_for: Option<String>,
-    timeout: u64,
+    output_dir: &Path,
+    favicons: bool,
+    og_image: bool,
+    options: &TextOptions,
     chrome_path: Option<PathBuf>,
     chrome_flags: Vec<String>,
     no_javascript: bool,
-    user_agent: Option<String>,
+    target: BrowserTarget,
+    retry: &RetryPolicy,
 ) -> Result<()> {
-    validate_navigation_url(url, "text command")?;
-    info!("Extracting text from: {}", url);
-
-    let browser = Browser::new(chrome_path, chrome_flags, !no_javascript).await?;
+    validate_navigation_url(url, "assets command")?;
+    info!("Reading assets of: {}", url);
+    let browser = launch_browser(chrome_path, chrome_flags, no_javascript, &target).await?;
 
-    let text = browser
-        .extract_text(url, selector, javascript, wait_for, timeout, user_agent)
+    let page = retry
+        .run(&format!("Assets of {}", url), |_| {
+            browser.asset_links(url, options)
+        })
         .await?;
+    let links = page.select(favicons, og_image);
+    if links.is_empty() {
+        // Only possible with --og-image alone, as favicons fall back to /favicon.ico
+        warn!("{} declares no og:image", url);
+    }
 
-    match output {
-        Some(path) => {
-            OutputHandler::ensure_output_dir(&path)?;
-            std::fs::write(&path, &text)?;
-            println!("Text saved to: {}", path.display());
-        }
-        None => {
-            println!("{}", text);
+    let client = asset_client(options)?;
+    let entries = download_assets(&client, &links, output_dir).await?;
+    for entry in &entries {
+        match (&entry.file, &entry.error) {
+            (Some(file), _) => emit(
+                Record::ok("asset")
+                    .target(&entry.link.url)
+                    .output(&output_dir.join(file)),
+            ),
+            (None, error) => {
+                warn!(
+                    "Could not download {}: {}",
+                    entry.link.url,
+                    error.as_deref().unwrap_or_default()
+                );
+                emit(
+                    Record::failed("asset", error.as_deref().unwrap_or_default())
+                        .target(&entry.link.url),
+                );
+            }
         }
     }
 
+    let manifest = AssetManifest::new(&page, entries);
+    let manifest_path = output_dir.join(ASSET_MANIFEST);
+    manifest.write(&manifest_path)?;
+    report(
+        Record::ok("assets")
+            .target(url)
+            .output(&manifest_path)
+            .detail(manifest.downloaded()),
+        || {
+            tr(
+                "assets-saved",
+                &[
+                    ("count", &manifest.downloaded()),
+                    ("path", &output_dir.display()),
+                ],
+            )
+        },
+    );
+
     Ok(())
 }
 
-/// Compare two images and output results
+/// Store `image` as baseline `name`, committing it to git when `git` is set
+async fn accept_baseline(
+    name: &str,
+    image: &Path,
+    location: &str,
+    condition: &PutCondition,
+    git: bool,
+) -> Result<()> {
+    let store = BaselineStore::open(location)?;
+    let local = store.local();
+    if git && local.is_none() {
+        return Err(webshot::WebshotError::config(format!(
+            "--git needs a baseline store directory inside a git repository, not {}",
+            location
+        )));
+    }
+
+    let data = std::fs::read(image)?;
+    let previous = if git { store.get(name).await? } else { None };
+    let version = store.put(name, &data, condition).await?;
+    say(|| {
+        tr(
+            "baseline-accepted",
+            &[("name", &name), ("version", &version)],
+        )
+    });
+
+    if let Some(local) = local.filter(|_| git) {
+        let change = BaselineChange::measure(
+            name,
+            previous
+                .as_ref()
+                .map(|baseline| (baseline.data.as_slice(), baseline.version.clone())),
+            &data,
+            &version,
+        )?;
+        webshot::git::commit(&local.files(name), &webshot::git::commit_message(&[change])).await?;
+        say(|| format!("Committed baseline {}", name));
+    }
+    emit(Record::ok("baseline").target(name).detail(&version));
+    Ok(())
+}
+
+/// Comparison options from the flags of `compare`, without a difference
+/// image
 #[allow(clippy::too_many_arguments)]
-async fn compare_images(
-    image1_path: &std::path::Path,
-    image2_path: &std::path::Path,
-    output: Option<PathBuf>,
+fn comparison_options(
     algorithm: &str,
     threshold: f64,
-    diff_image: bool,
-    diff_path: Option<PathBuf>,
+    tolerance: f64,
     ignore_antialiasing: bool,
     diff_color: &str,
-    output_format: &str,
-) -> Result<()> {
+    diff_style: DiffStyle,
+    region_gap: u32,
+    band_height: Option<u32>,
+) -> Result<ComparisonOptions> {
     use webshot::comparison::ComparisonAlgorithm;
 
     // Parse algorithm
-    let algorithm = match algorithm.to_lowercase().as_str() {
-        "pixel-diff" | "pixel" => ComparisonAlgorithm::PixelDiff,
-        "ssim" => ComparisonAlgorithm::SSIM,
-        "mse" => ComparisonAlgorithm::MSE,
-        "psnr" => ComparisonAlgorithm::PSNR,
-        _ => {
-            return Err(webshot::WebshotError::config(format!(
-                "Unknown algorithm: {}. Supported: pixel-diff, ssim, mse, psnr",
-                algorithm
-            )))
-        }
-    };
+    let algorithm: ComparisonAlgorithm = algorithm.parse()?;
 
     // Parse diff color
     let diff_color = parse_rgb_color(diff_color)?;
 
-    // Validate inputs
-    if diff_image && diff_path.is_none() {
-        return Err(webshot::WebshotError::config(
-            "Diff path must be specified when --diff-image is used".to_string(),
-        ));
-    }
-
     // Build comparison options
     let mut options = ComparisonOptions::new()
         .algorithm(algorithm)
         .threshold(threshold)
-        .diff_color(diff_color.0, diff_color.1, diff_color.2);
+        .delta_e_tolerance(tolerance)
+        .diff_color(diff_color.0, diff_color.1, diff_color.2)
+        .diff_style(diff_style)
+        .region_gap(region_gap);
 
     if ignore_antialiasing {
         options = options.ignore_antialiasing();
     }
 
-    if diff_image {
-        if let Some(path) = diff_path {
-            options = options.generate_diff_image(path);
-        }
+    if let Some(rows) = band_height {
+        options = options.band_height(rows);
     }
 
-    options.validate()?;
+    Ok(options)
+}
+
+/// Compare two images and output results
+#[allow(clippy::too_many_arguments)]
+async fn compare_images(
+    image1_path: &std::path::Path,
+    image2_path: &std::path::Path,
+    output: Option<PathBuf>,
+    options: &ComparisonOptions,
+    output_format: &str,
+    baseline_store: Option<&str>,
+    against_ref: Option<&str>,
+    html_report: Option<&Path>,
+) -> Result<()> {
+    // Baselines from a store or another git ref are compared from a
+    // temporary copy
+    let mut baseline_version = None;
+    let baseline_data = match (baseline_store, against_ref) {
+        (Some(location), _) => {
+            let name = image1_path.to_string_lossy();
+            let baseline = BaselineStore::open(location)?
+                .get(&name)
+                .await?
+                .ok_or_else(|| {
+                    webshot::WebshotError::baseline(format!(
+                        "No baseline {} in {}; store one with `webshot baseline accept`",
+                        name, location
+                    ))
+                })?;
+            baseline_version = Some(baseline.version);
+            Some(baseline.data)
+        }
+        (None, Some(git_ref)) => Some(webshot::git::show(git_ref, image1_path).await?),
+        (None, None) => None,
+    };
+    let baseline_file = match baseline_data {
+        Some(data) => {
+            let extension = image1_path
+                .extension()
+                .map(|ext| format!(".{}", ext.to_string_lossy()))
+                .unwrap_or_default();
+            let file = tempfile::Builder::new().suffix(&extension).tempfile()?;
+            std::fs::write(file.path(), data)?;
+            Some(file)
+        }
+        None => None,
+    };
+    let baseline_label = image1_path.display().to_string();
+    let image1_path = baseline_file
+        .as_ref()
+        .map_or(image1_path, |file| file.path());
 
     info!(
         "Comparing images: {} vs {}",
@@ -636,7 +3982,20 @@ async fn compare_images(
     );
 
     // Perform comparison
-    let result = ImageComparator::compare_files(image1_path, image2_path, &options)?;
+    let mut result = ImageComparator::compare_files(image1_path, image2_path, options)?;
+    result.baseline_version = baseline_version;
+
+    if let Some(report) = html_report {
+        write_diff_report(
+            &image::open(image1_path)?,
+            &image::open(image2_path)?,
+            &result,
+            options,
+            (&baseline_label, &image2_path.display().to_string()),
+            report,
+        )?;
+        info!("Comparison report saved to: {}", report.display());
+    }
 
     // Output results
     match output_format.to_lowercase().as_str() {
@@ -650,7 +4009,7 @@ async fn compare_images(
                 std::fs::write(output_path, json)?;
                 info!("Comparison results saved to JSON file");
             } else {
-                println!("{}", json);
+                say(|| json);
             }
         }
         "text" => {
@@ -661,7 +4020,7 @@ async fn compare_images(
                 std::fs::write(output_path, text_output)?;
                 info!("Comparison results saved to text file");
             } else {
-                println!("{}", text_output);
+                say(|| text_output);
             }
         }
         _ => {
@@ -672,6 +4031,21 @@ async fn compare_images(
         }
     }
 
+    let mut record = Record::new(
+        "compare",
+        if result.similar {
+            "similar"
+        } else {
+            "different"
+        },
+    )
+    .target(image2_path.display())
+    .detail(format!("{:.6}", result.similarity));
+    if let Some(diff_path) = &result.diff_image_path {
+        record = record.output(diff_path);
+    }
+    emit(record);
+
     // Exit with appropriate code
     if result.similar {
         info!(
@@ -688,6 +4062,146 @@ async fn compare_images(
     }
 }
 
+/// Compare the images of a baseline and a current directory, given as
+/// `--dir` twice, and output the aggregate results
+fn compare_directories(
+    dirs: &[PathBuf],
+    output: Option<PathBuf>,
+    options: &ComparisonOptions,
+    diff_dir: Option<&Path>,
+    output_format: &str,
+    html_report: Option<&Path>,
+) -> Result<()> {
+    let [baseline, current] = dirs else {
+        return Err(webshot::WebshotError::config(format!(
+            "Directory comparison takes --dir twice, the baseline directory and then the \
+             current one, got {}",
+            dirs.len()
+        )));
+    };
+    let comparison = compare_dirs(baseline, current, options, diff_dir)?;
+
+    if let Some(report) = html_report {
+        comparison.write_html(report)?;
+        info!("Comparison report saved to: {}", report.display());
+    }
+
+    match output_format.to_lowercase().as_str() {
+        "json" => {
+            if let Some(output_path) = output {
+                comparison.write_json(&output_path)?;
+                info!("Comparison results saved to JSON file");
+            } else {
+                let json = serde_json::to_string_pretty(&comparison)?;
+                say(|| json);
+            }
+        }
+        "text" => {
+            let text_output = comparison.to_string();
+            if let Some(output_path) = output {
+                OutputHandler::ensure_output_dir(&output_path)?;
+                std::fs::write(output_path, text_output)?;
+                info!("Comparison results saved to text file");
+            } else {
+                say(|| text_output.trim_end().to_string());
+            }
+        }
+        _ => {
+            return Err(webshot::WebshotError::config(format!(
+                "Unknown output format: {}. Supported: json, text",
+                output_format
+            )))
+        }
+    }
+
+    emit(
+        Record::new(
+            "compare",
+            if comparison.passed() {
+                "similar"
+            } else {
+                "different"
+            },
+        )
+        .target(current.display())
+        .detail(format!("{}/{}", comparison.similar, comparison.compared)),
+    );
+
+    info!("{}", comparison.summary());
+    std::process::exit(if comparison.passed() { 0 } else { 1 });
+}
+
+/// Whether a `layout-diff` side is a page to capture rather than a snapshot file
+fn is_http_url(side: &str) -> bool {
+    side.starts_with("http://") || side.starts_with("https://")
+}
+
+/// Selectors listed one per line, skipping blank lines and `#` comments
+fn read_selectors(path: &Path) -> Result<Vec<String>> {
+    Ok(std::fs::read_to_string(path)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+/// Compare two layout snapshots, print or save the result and exit
+/// non-zero when an element drifted beyond `threshold`
+fn report_layout_diff(
+    expected: &LayoutSnapshot,
+    actual: &LayoutSnapshot,
+    threshold: f64,
+    output: Option<PathBuf>,
+    output_format: &str,
+) -> Result<()> {
+    let comparison = compare_layouts(expected, actual, threshold);
+    let report = match output_format.to_lowercase().as_str() {
+        "json" => serde_json::to_string_pretty(&comparison)?,
+        "text" => comparison.to_string(),
+        _ => {
+            return Err(webshot::WebshotError::config(format!(
+                "Unknown output format: {}. Supported: json, text",
+                output_format
+            )))
+        }
+    };
+    match output {
+        Some(output_path) => {
+            OutputHandler::ensure_output_dir(&output_path)?;
+            std::fs::write(&output_path, report)?;
+            info!("Layout comparison saved to {}", output_path.display());
+        }
+        None => say(|| report.trim_end().to_string()),
+    }
+
+    emit(
+        Record::new(
+            "layout-diff",
+            if comparison.passed {
+                "within"
+            } else {
+                "drifted"
+            },
+        )
+        .target(&actual.url)
+        .detail(format!("{:.1}", comparison.max_drift())),
+    );
+
+    if !comparison.passed {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Validate a `--wait-for` condition while keeping it as written
+fn parse_wait_condition(value: &str) -> std::result::Result<String, String> {
+    value
+        .parse::<WaitCondition>()
+        .map(|_| value.to_string())
+        .map_err(|e| e.to_string())
+}
+
 /// Parse RGB color string (format: "255,0,0")
 fn parse_rgb_color(color_str: &str) -> Result<(u8, u8, u8)> {
     let parts: Vec<&str> = color_str.split(',').collect();
@@ -718,34 +4232,89 @@ fn parse_rgb_color(color_str: &str) -> Result<(u8, u8, u8)> {
 fn format_comparison_result(result: &webshot::ComparisonResult) -> String {
     let mut output = String::new();
 
-    output.push_str("Image Comparison Results\n");
-    output.push_str("========================\n\n");
+    let title = tr("compare-title", &[]);
+    output.push_str(&format!("{}\n", title));
+    output.push_str(&format!("{}\n\n", "=".repeat(title.chars().count())));
 
-    output.push_str(&format!("Algorithm: {:?}\n", result.algorithm));
-    output.push_str(&format!("Threshold: {:.2}\n", result.threshold));
-    output.push_str(&format!(
-        "Similarity: {:.4} ({:.2}%)\n",
-        result.similarity,
-        result.similarity * 100.0
+    let line = |id: &str, args: &[(&str, &dyn std::fmt::Display)]| format!("{}\n", tr(id, args));
+    output.push_str(&line(
+        "compare-algorithm",
+        &[("algorithm", &format!("{:?}", result.algorithm))],
     ));
-    output.push_str(&format!(
-        "Similar: {}\n",
-        if result.similar { "YES" } else { "NO" }
+    output.push_str(&line(
+        "compare-threshold",
+        &[("threshold", &format!("{:.2}", result.threshold))],
     ));
+    output.push_str(&line(
+        "compare-similarity",
+        &[
+            ("similarity", &format!("{:.4}", result.similarity)),
+            ("percent", &format!("{:.2}", result.similarity * 100.0)),
+        ],
+    ));
+    let similar = tr(
+        if result.similar {
+            "compare-yes"
+        } else {
+            "compare-no"
+        },
+        &[],
+    );
+    output.push_str(&line("compare-similar", &[("similar", &similar)]));
 
     if let Some(diff_pixels) = result.different_pixels {
-        output.push_str(&format!(
-            "Different pixels: {}/{} ({:.2}%)\n",
-            diff_pixels,
-            result.total_pixels,
-            (diff_pixels as f64 / result.total_pixels as f64) * 100.0
+        output.push_str(&line(
+            "compare-different-pixels",
+            &[
+                ("different", &diff_pixels),
+                ("total", &result.total_pixels),
+                (
+                    "percent",
+                    &format!(
+                        "{:.2}",
+                        (diff_pixels as f64 / result.total_pixels as f64) * 100.0
+                    ),
+                ),
+            ],
         ));
     }
 
-    output.push_str(&format!("Total pixels: {}\n", result.total_pixels));
+    output.push_str(&line(
+        "compare-total-pixels",
+        &[("total", &result.total_pixels)],
+    ));
+
+    if !result.regions.is_empty() {
+        output.push_str(&line(
+            "compare-regions",
+            &[("count", &result.regions.len())],
+        ));
+        for region in &result.regions {
+            output.push_str(&format!(
+                "  {}\n",
+                tr(
+                    "compare-region",
+                    &[
+                        ("x", &region.x),
+                        ("y", &region.y),
+                        ("width", &region.width),
+                        ("height", &region.height),
+                        ("pixels", &region.pixels),
+                    ],
+                )
+            ));
+        }
+    }
 
     if let Some(diff_path) = &result.diff_image_path {
-        output.push_str(&format!("Difference image: {}\n", diff_path.display()));
+        output.push_str(&line(
+            "compare-diff-image",
+            &[("path", &diff_path.display())],
+        ));
+    }
+
+    if let Some(version) = &result.baseline_version {
+        output.push_str(&line("compare-baseline-version", &[("version", version)]));
     }
 
     output
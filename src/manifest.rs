@@ -0,0 +1,174 @@
+//! JSON index of a batch run's results.
+//!
+//! After `multi` and `list` runs, a [`RunManifest`] is written to
+//! [`RUN_MANIFEST`] in the output directory. It lists every capture with its
+//! URL, output path, image dimensions, file size, duration, status, the
+//! page's HTTP status and error,
+//! so downstream tooling can act on a run without parsing log lines.
+
+use crate::error::Result;
+use crate::monitor::{BatchMonitor, JobEntry, JobStatus};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// File name of the manifest written next to batch outputs
+pub const RUN_MANIFEST: &str = "manifest.json";
+
+/// One capture of a batch run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub url: String,
+    pub output: PathBuf,
+    /// `done`, `failed` or `skipped`
+    pub status: String,
+    /// HTTP status of the page's main document, when one was received
+    #[serde(default)]
+    pub http_status: Option<u16>,
+    pub error: Option<String>,
+    /// Image dimensions, `None` when the output is missing or not an image
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Size of the output in bytes, `None` when it is missing
+    pub file_size: Option<u64>,
+    /// Run time of the last attempt in milliseconds
+    pub duration_ms: Option<u64>,
+    pub attempts: u32,
+}
+
+impl ManifestEntry {
+    /// Entry for `job`, whose output was written to `output`
+    pub fn new(job: &JobEntry, output: PathBuf) -> Self {
+        let file_size = std::fs::metadata(&output)
+            .ok()
+            .filter(|metadata| metadata.is_file())
+            .map(|metadata| metadata.len());
+        let dimensions = file_size.and_then(|_| image::image_dimensions(&output).ok());
+        Self {
+            url: job.url.clone(),
+            status: job.status.label().to_string(),
+            http_status: job.http_status,
+            error: match &job.status {
+                JobStatus::Failed(error) => Some(error.clone()),
+                _ => None,
+            },
+            width: dimensions.map(|(width, _)| width),
+            height: dimensions.map(|(_, height)| height),
+            file_size,
+            duration_ms: job.elapsed.map(|elapsed| elapsed.as_millis() as u64),
+            attempts: job.attempts,
+            output,
+        }
+    }
+}
+
+/// Every capture of a batch run and how it went
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunManifest {
+    /// When the manifest was written, as RFC 3339
+    pub finished_at: String,
+    /// Run time of the whole batch in milliseconds
+    pub duration_ms: u64,
+    pub total: usize,
+    pub done: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    pub entries: Vec<ManifestEntry>,
+}
+
+impl RunManifest {
+    /// Manifest of the jobs of `monitor`, whose outputs were written to
+    /// `outputs` in job order (see [`crate::config::Config::output_paths`])
+    pub fn from_monitor(monitor: &BatchMonitor, outputs: &[PathBuf]) -> Self {
+        let stats = monitor.stats();
+        Self {
+            finished_at: chrono::Utc::now().to_rfc3339(),
+            duration_ms: monitor.elapsed().as_millis() as u64,
+            total: stats.total,
+            done: stats.done,
+            failed: stats.failed,
+            skipped: stats.skipped,
+            entries: monitor
+                .jobs()
+                .iter()
+                .zip(outputs)
+                .map(|(job, output)| ManifestEntry::new(job, output.clone()))
+                .collect(),
+        }
+    }
+
+    /// Read a manifest written by [`RunManifest::write`]
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// Write the manifest as JSON
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ScreenshotConfig;
+    use crate::error::WebshotError;
+
+    #[tokio::test]
+    async fn test_manifest_lists_every_job() {
+        let dir = tempfile::tempdir().unwrap();
+        let configs: Vec<_> = ["home", "about", "team"]
+            .iter()
+            .map(|name| {
+                ScreenshotConfig::new(
+                    format!("https://example.com/{}", name),
+                    format!("{}.png", name),
+                )
+            })
+            .collect();
+        let outputs: Vec<_> = configs
+            .iter()
+            .map(|config| config.resolved_output(Some(dir.path())))
+            .collect();
+        let monitor = BatchMonitor::new(configs, false);
+
+        let (index, _) = monitor.next_job().await.unwrap();
+        image::RgbImage::new(32, 24).save(&outputs[index]).unwrap();
+        monitor.record_capture(index, Some(200), Default::default());
+        monitor.finish(index, &Ok(()));
+        let (index, _) = monitor.next_job().await.unwrap();
+        monitor.finish(index, &Err(WebshotError::navigation("timeout")));
+        monitor.skip(2);
+
+        let manifest = RunManifest::from_monitor(&monitor, &outputs);
+        assert_eq!(
+            (
+                manifest.total,
+                manifest.done,
+                manifest.failed,
+                manifest.skipped
+            ),
+            (3, 1, 1, 1)
+        );
+        let home = &manifest.entries[0];
+        assert_eq!(
+            (home.status.as_str(), home.error.as_deref()),
+            ("done", None)
+        );
+        assert_eq!((home.width, home.height), (Some(32), Some(24)));
+        assert_eq!(home.http_status, Some(200));
+        assert!(home.file_size.unwrap() > 0 && home.duration_ms.is_some());
+        assert_eq!(home.output, dir.path().join("home.png"));
+
+        let about = &manifest.entries[1];
+        assert_eq!(about.status, "failed");
+        assert!(about.error.as_deref().unwrap().contains("timeout"));
+        assert_eq!((about.width, about.file_size), (None, None));
+        assert_eq!(about.http_status, None);
+        assert_eq!(manifest.entries[2].attempts, 0);
+
+        let path = dir.path().join(RUN_MANIFEST);
+        manifest.write(&path).unwrap();
+        assert_eq!(RunManifest::load(&path).unwrap(), manifest);
+    }
+}
@@ -0,0 +1,307 @@
+//! Canned network responses for deterministic captures.
+//!
+//! Requests matching a mock rule are answered from the rule (or its fixture
+//! file) with `Fetch.fulfillRequest` and never reach the network.
+
+use crate::error::{Result, WebshotError};
+use crate::sitemap::glob_matches;
+use headless_chrome::protocol::cdp::Fetch::{FulfillRequest, HeaderEntry, RequestId};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// One canned response and the requests it answers
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MockRule {
+    /// URL glob (`*` any run of characters, `?` one). Patterns with a scheme
+    /// match the full URL, others its path and query.
+    pub url: String,
+    /// Only answer requests with this method (default: any)
+    pub method: Option<String>,
+    /// Response status code
+    #[serde(default = "default_status")]
+    pub status: u16,
+    /// Response headers; `Content-Type` is guessed when missing
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// Response body
+    pub body: Option<String>,
+    /// File whose contents are the response body
+    pub file: Option<PathBuf>,
+}
+
+fn default_status() -> u16 {
+    200
+}
+
+impl MockRule {
+    /// Check the rule can be served
+    pub fn validate(&self) -> Result<()> {
+        if self.url.is_empty() {
+            return Err(WebshotError::config("Mock rule needs a url pattern"));
+        }
+        if !(100..=599).contains(&self.status) {
+            return Err(WebshotError::config(format!(
+                "Mock for {} has invalid status {}",
+                self.url, self.status
+            )));
+        }
+        if self.body.is_some() && self.file.is_some() {
+            return Err(WebshotError::config(format!(
+                "Mock for {} sets both body and file",
+                self.url
+            )));
+        }
+        Ok(())
+    }
+
+    /// Whether this rule answers a `method` request for `url`
+    pub fn matches(&self, method: &str, url: &str) -> bool {
+        if self
+            .method
+            .as_ref()
+            .is_some_and(|expected| !expected.eq_ignore_ascii_case(method))
+        {
+            return false;
+        }
+        if self.url.contains("://") {
+            return glob_matches(&self.url, url);
+        }
+        url::Url::parse(url).is_ok_and(|parsed| {
+            let path = parsed.path();
+            glob_matches(&self.url, path)
+                || parsed
+                    .query()
+                    .is_some_and(|query| glob_matches(&self.url, &format!("{}?{}", path, query)))
+        })
+    }
+}
+
+/// A rule with its body read, ready to answer requests
+#[derive(Debug, Clone, PartialEq)]
+struct LoadedMock {
+    rule: MockRule,
+    body: Vec<u8>,
+    headers: Vec<HeaderEntry>,
+}
+
+impl LoadedMock {
+    fn load(rule: &MockRule) -> Result<Self> {
+        rule.validate()?;
+        let body = match (&rule.body, &rule.file) {
+            (_, Some(file)) => std::fs::read(file).map_err(|e| {
+                WebshotError::config(format!(
+                    "Failed to read mock fixture {}: {}",
+                    file.display(),
+                    e
+                ))
+            })?,
+            (Some(body), None) => body.clone().into_bytes(),
+            (None, None) => Vec::new(),
+        };
+
+        let mut headers: Vec<_> = rule
+            .headers
+            .iter()
+            .map(|(name, value)| HeaderEntry {
+                name: name.clone(),
+                value: value.clone(),
+            })
+            .collect();
+        let has_header = |headers: &[HeaderEntry], name: &str| {
+            headers
+                .iter()
+                .any(|header| header.name.eq_ignore_ascii_case(name))
+        };
+        if !has_header(&headers, "content-type") {
+            headers.push(HeaderEntry {
+                name: "Content-Type".to_string(),
+                value: content_type(rule.file.as_deref(), &body).to_string(),
+            });
+        }
+        // Mocked APIs are usually on another origin than the page
+        if !has_header(&headers, "access-control-allow-origin") {
+            headers.push(HeaderEntry {
+                name: "Access-Control-Allow-Origin".to_string(),
+                value: "*".to_string(),
+            });
+        }
+
+        Ok(Self {
+            rule: rule.clone(),
+            body,
+            headers,
+        })
+    }
+
+    fn fulfill(&self, request_id: RequestId) -> FulfillRequest {
+        use base64::Engine;
+
+        FulfillRequest {
+            request_id,
+            response_code: u32::from(self.rule.status),
+            response_headers: Some(self.headers.clone()),
+            binary_response_headers: None,
+            body: Some(base64::engine::general_purpose::STANDARD.encode(&self.body)),
+            response_phrase: None,
+        }
+    }
+}
+
+/// Mock rules with their bodies loaded, shared by the request interceptor
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Mocks {
+    mocks: Arc<Vec<LoadedMock>>,
+}
+
+impl Mocks {
+    /// Validate `rules` and read their fixture files. Earlier rules win when
+    /// several match a request.
+    pub fn load(rules: &[MockRule]) -> Result<Self> {
+        Ok(Self {
+            mocks: Arc::new(rules.iter().map(LoadedMock::load).collect::<Result<_>>()?),
+        })
+    }
+
+    /// Load the rules in a YAML file holding a list of mock rules
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            WebshotError::config(format!("Failed to read mocks {}: {}", path.display(), e))
+        })?;
+        let rules: Vec<MockRule> = serde_yaml::from_str(&content)?;
+        Self::load(&rules)
+    }
+
+    /// Whether no request is ever mocked
+    pub fn is_empty(&self) -> bool {
+        self.mocks.is_empty()
+    }
+
+    /// The response for a paused request, if a rule matches it
+    pub fn respond(
+        &self,
+        request_id: RequestId,
+        method: &str,
+        url: &str,
+    ) -> Option<FulfillRequest> {
+        self.mocks
+            .iter()
+            .find(|mock| mock.rule.matches(method, url))
+            .map(|mock| mock.fulfill(request_id))
+    }
+}
+
+/// Content type of a mocked body, from the fixture's extension or the body
+fn content_type(file: Option<&Path>, body: &[u8]) -> &'static str {
+    let extension = file
+        .and_then(Path::extension)
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+    match extension.as_deref() {
+        Some("json") => "application/json",
+        Some("html" | "htm") => "text/html; charset=utf-8",
+        Some("css") => "text/css",
+        Some("js" | "mjs") => "text/javascript",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("webp") => "image/webp",
+        Some("gif") => "image/gif",
+        Some("woff2") => "font/woff2",
+        Some("txt") => "text/plain; charset=utf-8",
+        Some(_) => "application/octet-stream",
+        None if serde_json::from_slice::<serde_json::Value>(body).is_ok() => "application/json",
+        None => "text/plain; charset=utf-8",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn rules(yaml: &str) -> Vec<MockRule> {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_rules_match_method_and_url() {
+        let rules = rules(
+            r#"
+- url: /api/users*
+  method: GET
+  body: '[]'
+- url: "https://cdn.example.com/*.js"
+  status: 404
+"#,
+        );
+        assert!(rules[0].matches("GET", "https://app.example.com/api/users?page=2"));
+        assert!(rules[0].matches("get", "https://app.example.com/api/users/7"));
+        assert!(!rules[0].matches("POST", "https://app.example.com/api/users"));
+        assert!(!rules[0].matches("GET", "https://app.example.com/v2/api/users"));
+        assert!(rules[1].matches("POST", "https://cdn.example.com/lib/app.js"));
+        assert!(!rules[1].matches("GET", "https://example.com/app.js"));
+    }
+
+    #[test]
+    fn test_first_matching_rule_answers() {
+        use base64::Engine;
+
+        let dir = TempDir::new().unwrap();
+        let fixture = dir.path().join("user.json");
+        std::fs::write(&fixture, r#"{"name": "Ada"}"#).unwrap();
+        let mut rules = rules(
+            r#"
+- url: /api/users/1
+  headers:
+    Cache-Control: no-store
+- url: /api/*
+  status: 503
+  body: down
+"#,
+        );
+        rules[0].file = Some(fixture);
+        let mocks = Mocks::load(&rules).unwrap();
+
+        let user = mocks
+            .respond("1".to_string(), "GET", "https://example.com/api/users/1")
+            .unwrap();
+        assert_eq!(user.response_code, 200);
+        let body = base64::engine::general_purpose::STANDARD
+            .decode(user.body.unwrap())
+            .unwrap();
+        assert_eq!(body, br#"{"name": "Ada"}"#);
+        let headers = user.response_headers.unwrap();
+        assert!(headers
+            .iter()
+            .any(|h| h.name == "Content-Type" && h.value == "application/json"));
+        assert!(headers.iter().any(|h| h.name == "Cache-Control"));
+
+        let down = mocks
+            .respond("2".to_string(), "GET", "https://example.com/api/orders")
+            .unwrap();
+        assert_eq!(down.response_code, 503);
+        assert!(mocks
+            .respond("3".to_string(), "GET", "https://example.com/")
+            .is_none());
+    }
+
+    #[test]
+    fn test_invalid_rules_are_rejected() {
+        let both = rules("- url: /a\n  body: x\n  file: a.json\n");
+        assert!(Mocks::load(&both)
+            .unwrap_err()
+            .to_string()
+            .contains("both body and file"));
+
+        let status = rules("- url: /a\n  status: 42\n");
+        assert!(Mocks::load(&status).is_err());
+
+        let missing = rules("- url: /a\n  file: /no/such/fixture.json\n");
+        assert!(Mocks::load(&missing)
+            .unwrap_err()
+            .to_string()
+            .contains("mock fixture"));
+    }
+}
@@ -0,0 +1,411 @@
+use crate::config::ScreenshotConfig;
+use crate::error::{Result, WebshotError};
+use crate::timing::PhaseTimings;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+/// State of one batch job
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JobStatus {
+    Pending,
+    Running,
+    Done,
+    Failed(String),
+    Skipped,
+}
+
+impl JobStatus {
+    /// Short label for status displays
+    pub fn label(&self) -> &'static str {
+        match self {
+            JobStatus::Pending => "pending",
+            JobStatus::Running => "running",
+            JobStatus::Done => "done",
+            JobStatus::Failed(_) => "failed",
+            JobStatus::Skipped => "skipped",
+        }
+    }
+}
+
+/// A batch job as shown in progress displays
+#[derive(Debug, Clone)]
+pub struct JobEntry {
+    pub url: String,
+    pub output: PathBuf,
+    pub status: JobStatus,
+    /// Number of times the job has been started
+    pub attempts: u32,
+    /// Run time of the current or last attempt
+    pub elapsed: Option<Duration>,
+    /// Time spent in each phase of the last successful capture
+    pub timings: Option<PhaseTimings>,
+    /// HTTP status of the page's main document in the last attempt that
+    /// received one
+    pub http_status: Option<u16>,
+    started: Option<Instant>,
+}
+
+/// Job counts by status plus completion throughput
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BatchStats {
+    pub total: usize,
+    pub pending: usize,
+    pub running: usize,
+    pub done: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    /// Finished (done or failed) jobs per minute since the batch started
+    pub per_minute: f64,
+}
+
+struct MonitorState {
+    configs: Vec<ScreenshotConfig>,
+    jobs: Vec<JobEntry>,
+    closed: bool,
+}
+
+impl JobEntry {
+    fn new(config: &ScreenshotConfig) -> Self {
+        Self {
+            url: config.url.clone(),
+            output: config.output.clone(),
+            status: JobStatus::Pending,
+            attempts: 0,
+            elapsed: None,
+            timings: None,
+            http_status: None,
+            started: None,
+        }
+    }
+}
+
+/// Shared progress and control state for a batch run.
+///
+/// Workers take pending jobs with [`BatchMonitor::next_job`] and report back
+/// with [`BatchMonitor::finish`]; a dashboard can read [`BatchMonitor::jobs`]
+/// and reschedule entries with [`BatchMonitor::retry`] or
+/// [`BatchMonitor::skip`]. A monitor that stays open keeps idle workers
+/// waiting for retries until it is closed.
+pub struct BatchMonitor {
+    state: Mutex<MonitorState>,
+    changed: Notify,
+    keep_open: bool,
+    started: Instant,
+}
+
+impl BatchMonitor {
+    /// Monitor for the given jobs. With `keep_open`, workers wait for
+    /// retries once every job has finished, until [`BatchMonitor::close`].
+    pub fn new(configs: Vec<ScreenshotConfig>, keep_open: bool) -> Self {
+        let jobs = configs.iter().map(JobEntry::new).collect();
+
+        Self {
+            state: Mutex::new(MonitorState {
+                configs,
+                jobs,
+                closed: false,
+            }),
+            changed: Notify::new(),
+            keep_open,
+            started: Instant::now(),
+        }
+    }
+
+    /// Take the first pending job and mark it running. Returns `None` once
+    /// the batch is over: immediately when no job is pending, or for monitors
+    /// kept open, after [`BatchMonitor::close`].
+    pub async fn next_job(&self) -> Option<(usize, ScreenshotConfig)> {
+        loop {
+            let notified = self.changed.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            {
+                let mut state = self.state.lock().unwrap();
+                if let Some(index) = state
+                    .jobs
+                    .iter()
+                    .position(|job| job.status == JobStatus::Pending)
+                {
+                    let job = &mut state.jobs[index];
+                    job.status = JobStatus::Running;
+                    job.attempts += 1;
+                    job.started = Some(Instant::now());
+                    job.elapsed = None;
+                    return Some((index, state.configs[index].clone()));
+                }
+                if state.closed || !self.keep_open {
+                    return None;
+                }
+            }
+            notified.await;
+        }
+    }
+
+    /// Count another automatic attempt of a running job, restarting its
+    /// elapsed time
+    pub fn restart(&self, index: usize) {
+        let mut state = self.state.lock().unwrap();
+        let job = &mut state.jobs[index];
+        if job.status == JobStatus::Running {
+            job.attempts += 1;
+            job.started = Some(Instant::now());
+        }
+        drop(state);
+        self.changed.notify_waiters();
+    }
+
+    /// Record the document's HTTP status and the phase timings of a job's
+    /// capture
+    pub fn record_capture(&self, index: usize, http_status: Option<u16>, timings: PhaseTimings) {
+        let mut state = self.state.lock().unwrap();
+        let job = &mut state.jobs[index];
+        job.http_status = http_status.or(job.http_status);
+        job.timings = Some(timings);
+    }
+
+    /// Total time spent in each phase by every job's last capture
+    pub fn phase_totals(&self) -> PhaseTimings {
+        let state = self.state.lock().unwrap();
+        let mut totals = PhaseTimings::default();
+        for timings in state.jobs.iter().filter_map(|job| job.timings.as_ref()) {
+            totals.add(timings);
+        }
+        totals
+    }
+
+    /// Record the result of a running job
+    pub fn finish(&self, index: usize, result: &Result<()>) {
+        let mut state = self.state.lock().unwrap();
+        let job = &mut state.jobs[index];
+        job.elapsed = job.started.map(|started| started.elapsed());
+        if let Err(WebshotError::HttpStatus { status, .. }) = result {
+            job.http_status = Some(*status);
+        }
+        job.status = match result {
+            Ok(()) => JobStatus::Done,
+            Err(e) => JobStatus::Failed(e.to_string()),
+        };
+        drop(state);
+        self.changed.notify_waiters();
+    }
+
+    /// Queue a failed or skipped job again. Returns whether the job was requeued.
+    pub fn retry(&self, index: usize) -> bool {
+        self.update(index, |status| {
+            matches!(status, JobStatus::Failed(_) | JobStatus::Skipped)
+                .then_some(JobStatus::Pending)
+        })
+    }
+
+    /// Skip a pending job. Returns whether the job was skipped.
+    pub fn skip(&self, index: usize) -> bool {
+        self.update(index, |status| {
+            (*status == JobStatus::Pending).then_some(JobStatus::Skipped)
+        })
+    }
+
+    /// Queue every failed job again, returning how many were requeued
+    pub fn retry_failed(&self) -> usize {
+        let count = self.job_count();
+        (0..count)
+            .filter(|&index| {
+                self.update(index, |status| {
+                    matches!(status, JobStatus::Failed(_)).then_some(JobStatus::Pending)
+                })
+            })
+            .count()
+    }
+
+    /// Skip every pending job and let workers exit once running jobs finish
+    pub fn close(&self) {
+        let mut state = self.state.lock().unwrap();
+        for job in &mut state.jobs {
+            if job.status == JobStatus::Pending {
+                job.status = JobStatus::Skipped;
+            }
+        }
+        state.closed = true;
+        drop(state);
+        self.changed.notify_waiters();
+    }
+
+    /// Snapshot of every job with live elapsed times for running jobs
+    pub fn jobs(&self) -> Vec<JobEntry> {
+        let state = self.state.lock().unwrap();
+        state
+            .jobs
+            .iter()
+            .map(|job| {
+                let mut job = job.clone();
+                if job.status == JobStatus::Running {
+                    job.elapsed = job.started.map(|started| started.elapsed());
+                }
+                job
+            })
+            .collect()
+    }
+
+    pub fn job_count(&self) -> usize {
+        self.state.lock().unwrap().jobs.len()
+    }
+
+    /// Time since the monitor was created
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    pub fn stats(&self) -> BatchStats {
+        let state = self.state.lock().unwrap();
+        let mut stats = BatchStats {
+            total: state.jobs.len(),
+            ..Default::default()
+        };
+        for job in &state.jobs {
+            match job.status {
+                JobStatus::Pending => stats.pending += 1,
+                JobStatus::Running => stats.running += 1,
+                JobStatus::Done => stats.done += 1,
+                JobStatus::Failed(_) => stats.failed += 1,
+                JobStatus::Skipped => stats.skipped += 1,
+            }
+        }
+
+        let minutes = self.started.elapsed().as_secs_f64() / 60.0;
+        if minutes > 0.0 {
+            stats.per_minute = (stats.done + stats.failed) as f64 / minutes;
+        }
+        stats
+    }
+
+    /// Whether no job is pending or running
+    pub fn is_idle(&self) -> bool {
+        let stats = self.stats();
+        stats.pending == 0 && stats.running == 0
+    }
+
+    /// Wait until a job finishes or the queue changes
+    pub async fn changed(&self) {
+        self.changed.notified().await;
+    }
+
+    fn update(&self, index: usize, next: impl Fn(&JobStatus) -> Option<JobStatus>) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let Some(job) = state.jobs.get_mut(index) else {
+            return false;
+        };
+        let Some(status) = next(&job.status) else {
+            return false;
+        };
+        job.status = status;
+        drop(state);
+        self.changed.notify_waiters();
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::WebshotError;
+    use crate::timing::Phase;
+
+    fn monitor(count: usize, keep_open: bool) -> BatchMonitor {
+        let configs = (0..count)
+            .map(|index| {
+                ScreenshotConfig::new(
+                    format!("https://example.com/{}", index),
+                    format!("{}.png", index),
+                )
+            })
+            .collect();
+        BatchMonitor::new(configs, keep_open)
+    }
+
+    #[tokio::test]
+    async fn test_jobs_run_in_order_and_record_results() {
+        let monitor = monitor(3, false);
+
+        let (first, config) = monitor.next_job().await.unwrap();
+        assert_eq!((first, config.url.as_str()), (0, "https://example.com/0"));
+        assert!(monitor.skip(1));
+        let mut timings = PhaseTimings::default();
+        timings.record(Phase::Navigate, Duration::from_millis(300));
+        monitor.record_capture(first, Some(200), timings);
+        monitor.finish(first, &Ok(()));
+
+        let (second, _) = monitor.next_job().await.unwrap();
+        assert_eq!(second, 2);
+        monitor.finish(second, &Err(WebshotError::http_status(&config.url, 503)));
+        assert!(monitor.next_job().await.is_none());
+
+        let stats = monitor.stats();
+        assert_eq!((stats.done, stats.failed, stats.skipped), (1, 1, 1));
+        assert!(monitor.is_idle());
+        assert!(matches!(
+            &monitor.jobs()[2].status,
+            JobStatus::Failed(message) if message.contains("503")
+        ));
+        assert_eq!(monitor.jobs()[0].http_status, Some(200));
+        assert_eq!(monitor.jobs()[2].http_status, Some(503));
+        assert_eq!(monitor.phase_totals().get(Phase::Navigate), 300);
+    }
+
+    #[tokio::test]
+    async fn test_retry_requeues_failed_and_skipped_jobs() {
+        let monitor = monitor(2, false);
+
+        let (index, _) = monitor.next_job().await.unwrap();
+        assert!(!monitor.retry(index));
+        assert!(!monitor.skip(index));
+        monitor.finish(index, &Err(WebshotError::screenshot("blank page")));
+        assert!(monitor.skip(1));
+
+        assert_eq!(monitor.retry_failed(), 1);
+        assert!(monitor.retry(1));
+        assert!(!monitor.retry(5));
+
+        assert_eq!(monitor.next_job().await.unwrap().0, 0);
+        assert_eq!(monitor.jobs()[0].attempts, 2);
+        assert_eq!(monitor.next_job().await.unwrap().0, 1);
+    }
+
+    #[tokio::test]
+    async fn test_restart_counts_automatic_attempts() {
+        let monitor = monitor(1, false);
+        let (index, _) = monitor.next_job().await.unwrap();
+        monitor.restart(index);
+        monitor.finish(index, &Ok(()));
+        monitor.restart(index);
+
+        let job = &monitor.jobs()[index];
+        assert_eq!(job.status, JobStatus::Done);
+        assert_eq!(job.attempts, 2);
+    }
+
+    #[tokio::test]
+    async fn test_open_monitor_waits_for_retries_until_closed() {
+        let monitor = std::sync::Arc::new(monitor(1, true));
+
+        let (index, _) = monitor.next_job().await.unwrap();
+        monitor.finish(index, &Err(WebshotError::screenshot("blank page")));
+
+        let worker = {
+            let monitor = monitor.clone();
+            tokio::spawn(async move { monitor.next_job().await.map(|(index, _)| index) })
+        };
+        tokio::task::yield_now().await;
+        assert!(monitor.retry(index));
+        assert_eq!(worker.await.unwrap(), Some(0));
+        monitor.finish(index, &Ok(()));
+
+        let worker = {
+            let monitor = monitor.clone();
+            tokio::spawn(async move { monitor.next_job().await.map(|(index, _)| index) })
+        };
+        tokio::task::yield_now().await;
+        monitor.close();
+        assert_eq!(worker.await.unwrap(), None);
+    }
+}
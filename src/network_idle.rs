@@ -0,0 +1,288 @@
+//! Network idle detection across every frame of a page.
+//!
+//! Requests are tracked from DevTools `Network` events, grouped by the frame
+//! that issued them, so iframes (ads, maps, chat widgets) count as much as
+//! the top document. A page is idle once no frame has had more than
+//! [`NetworkIdle::max_inflight`] requests in flight for
+//! [`NetworkIdle::idle_time`]. Out-of-process iframes (cross-site frames
+//! under site isolation) report to their own DevTools target and are not
+//! seen.
+
+use crate::error::{Result, WebshotError};
+use headless_chrome::protocol::cdp::types::Event;
+use headless_chrome::protocol::cdp::Network;
+use headless_chrome::Tab;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+use tracing::debug;
+
+/// Default quiet window before a page counts as idle
+pub const DEFAULT_IDLE_TIME: Duration = Duration::from_millis(500);
+
+/// When a page counts as idle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NetworkIdle {
+    /// How long every frame must stay at or under `max_inflight`
+    pub idle_time: Duration,
+    /// Requests a frame may keep in flight while idle, for long polls and
+    /// event streams that never finish
+    pub max_inflight: usize,
+}
+
+impl Default for NetworkIdle {
+    fn default() -> Self {
+        Self {
+            idle_time: DEFAULT_IDLE_TIME,
+            max_inflight: 0,
+        }
+    }
+}
+
+impl NetworkIdle {
+    pub fn new(idle_time: Duration) -> Self {
+        Self {
+            idle_time,
+            ..Default::default()
+        }
+    }
+
+    /// Count frames with up to `max_inflight` requests as quiet
+    pub fn max_inflight(mut self, max_inflight: usize) -> Self {
+        self.max_inflight = max_inflight;
+        self
+    }
+}
+
+/// In-flight requests of one frame
+#[derive(Debug, Default)]
+struct FrameActivity {
+    /// URLs of in-flight requests by request id
+    inflight: HashMap<String, String>,
+    /// When the frame last dropped to the in-flight threshold; `None` while
+    /// it is above it
+    quiet_since: Option<Instant>,
+}
+
+/// Requests in flight per frame, updated from DevTools events
+#[derive(Debug)]
+pub struct NetworkActivity {
+    max_inflight: usize,
+    frames: HashMap<String, FrameActivity>,
+    /// Frame of each in-flight request
+    request_frames: HashMap<String, String>,
+}
+
+impl NetworkActivity {
+    pub fn new(max_inflight: usize) -> Self {
+        Self {
+            max_inflight,
+            frames: HashMap::new(),
+            request_frames: HashMap::new(),
+        }
+    }
+
+    pub fn request_started(&mut self, request_id: &str, frame_id: &str, url: &str, now: Instant) {
+        // Redirects reuse the request id of the previous hop
+        self.request_finished(request_id, now);
+        let frame = self.frames.entry(frame_id.to_string()).or_default();
+        frame
+            .inflight
+            .insert(request_id.to_string(), url.to_string());
+        if frame.inflight.len() > self.max_inflight {
+            frame.quiet_since = None;
+        } else if frame.quiet_since.is_none() {
+            // A frame first seen with a request it is allowed to keep
+            frame.quiet_since = Some(now);
+        }
+        self.request_frames
+            .insert(request_id.to_string(), frame_id.to_string());
+    }
+
+    pub fn request_finished(&mut self, request_id: &str, now: Instant) {
+        let Some(frame_id) = self.request_frames.remove(request_id) else {
+            return;
+        };
+        if let Some(frame) = self.frames.get_mut(&frame_id) {
+            frame.inflight.remove(request_id);
+            if frame.inflight.len() <= self.max_inflight && frame.quiet_since.is_none() {
+                frame.quiet_since = Some(now);
+            }
+        }
+    }
+
+    /// Forget a removed iframe and whatever it still had in flight
+    pub fn frame_detached(&mut self, frame_id: &str) {
+        if let Some(frame) = self.frames.remove(frame_id) {
+            for request_id in frame.inflight.keys() {
+                self.request_frames.remove(request_id);
+            }
+        }
+    }
+
+    /// Since when every frame has been quiet, no earlier than `floor`, or
+    /// `None` while any frame is busy
+    pub fn quiet_since(&self, floor: Instant) -> Option<Instant> {
+        self.frames
+            .values()
+            .try_fold(floor, |latest, frame| Some(latest.max(frame.quiet_since?)))
+    }
+
+    /// In-flight request URLs of frames over the threshold
+    pub fn busy_requests(&self) -> Vec<&str> {
+        let mut urls: Vec<&str> = self
+            .frames
+            .values()
+            .filter(|frame| frame.quiet_since.is_none())
+            .flat_map(|frame| frame.inflight.values().map(String::as_str))
+            .collect();
+        urls.sort_unstable();
+        urls
+    }
+
+    /// Update from a DevTools event; events of other kinds are ignored
+    pub fn handle(&mut self, event: &Event, now: Instant) {
+        match event {
+            Event::NetworkRequestWillBeSent(event) => {
+                let params = &event.params;
+                // Requests without a frame (service workers) belong to the page
+                let frame_id = params.frame_id.as_deref().unwrap_or("");
+                self.request_started(&params.request_id, frame_id, &params.request.url, now);
+            }
+            Event::NetworkLoadingFinished(event) => {
+                self.request_finished(&event.params.request_id, now);
+            }
+            Event::NetworkLoadingFailed(event) => {
+                self.request_finished(&event.params.request_id, now);
+            }
+            Event::PageFrameDetached(event) => self.frame_detached(&event.params.frame_id),
+            _ => {}
+        }
+    }
+}
+
+/// Tracks a tab's requests so a capture can wait for the network to go
+/// quiet in every frame
+#[derive(Debug, Clone)]
+pub struct NetworkIdleWatch {
+    idle: NetworkIdle,
+    activity: Arc<Mutex<NetworkActivity>>,
+}
+
+impl NetworkIdleWatch {
+    /// Start tracking requests on `tab`, or return `None` when no network
+    /// idle wait is configured
+    pub fn attach(tab: &Tab, idle: Option<&NetworkIdle>) -> Result<Option<Self>> {
+        let Some(idle) = idle else {
+            return Ok(None);
+        };
+
+        let activity = Arc::new(Mutex::new(NetworkActivity::new(idle.max_inflight)));
+        let tracked = Arc::clone(&activity);
+        tab.add_event_listener(Arc::new(move |event: &Event| {
+            tracked.lock().unwrap().handle(event, Instant::now());
+        }))
+        .map_err(WebshotError::Browser)?;
+        tab.call_method(Network::Enable {
+            max_total_buffer_size: None,
+            max_resource_buffer_size: None,
+            max_post_data_size: None,
+            report_direct_socket_traffic: None,
+            enable_durable_messages: None,
+        })
+        .map_err(WebshotError::Browser)?;
+
+        Ok(Some(Self {
+            idle: *idle,
+            activity,
+        }))
+    }
+
+    /// Wait until every frame has been quiet for the idle window, checking
+    /// every `poll_interval`. Returns the requests still in flight when
+    /// `timeout` passes first.
+    pub async fn wait(
+        &self,
+        timeout: Duration,
+        poll_interval: Duration,
+    ) -> std::result::Result<(), Vec<String>> {
+        let start = Instant::now();
+        loop {
+            let now = Instant::now();
+            {
+                let activity = self.activity.lock().unwrap();
+                if let Some(since) = activity.quiet_since(start) {
+                    if now.duration_since(since) >= self.idle.idle_time {
+                        debug!("Network idle after {}ms", start.elapsed().as_millis());
+                        return Ok(());
+                    }
+                }
+                if now.duration_since(start) >= timeout {
+                    return Err(activity
+                        .busy_requests()
+                        .into_iter()
+                        .map(str::to_string)
+                        .collect());
+                }
+            }
+            sleep(poll_interval).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(start: Instant, offset: u64) -> Instant {
+        start + Duration::from_millis(offset)
+    }
+
+    #[test]
+    fn test_every_frame_must_be_quiet() {
+        let start = Instant::now();
+        let mut activity = NetworkActivity::new(0);
+        activity.request_started("1", "main", "https://example.com/", start);
+        activity.request_started("2", "widget", "https://chat.example/poll", ms(start, 10));
+        activity.request_finished("1", ms(start, 50));
+        assert_eq!(activity.quiet_since(start), None);
+        assert_eq!(activity.busy_requests(), ["https://chat.example/poll"]);
+
+        activity.request_finished("2", ms(start, 300));
+        assert_eq!(activity.quiet_since(start), Some(ms(start, 300)));
+
+        // A poll restarts the quiet window of its frame
+        activity.request_started("3", "widget", "https://chat.example/poll", ms(start, 400));
+        activity.request_finished("3", ms(start, 420));
+        assert_eq!(activity.quiet_since(start), Some(ms(start, 420)));
+        assert_eq!(activity.quiet_since(ms(start, 900)), Some(ms(start, 900)));
+    }
+
+    #[test]
+    fn test_threshold_allows_long_lived_requests() {
+        let start = Instant::now();
+        let mut activity = NetworkActivity::new(1);
+        activity.request_started("stream", "main", "https://example.com/events", start);
+        assert_eq!(activity.quiet_since(start), Some(start));
+
+        activity.request_started("img", "main", "https://example.com/a.png", ms(start, 5));
+        assert_eq!(activity.quiet_since(start), None);
+        activity.request_finished("img", ms(start, 80));
+        assert_eq!(activity.quiet_since(start), Some(ms(start, 80)));
+    }
+
+    #[test]
+    fn test_detached_frames_stop_counting() {
+        let start = Instant::now();
+        let mut activity = NetworkActivity::new(0);
+        activity.request_started("1", "ad", "https://ads.example/slot", start);
+        activity.request_started("1", "ad", "https://ads.example/redirected", ms(start, 5));
+        assert_eq!(activity.busy_requests(), ["https://ads.example/redirected"]);
+
+        activity.frame_detached("ad");
+        assert_eq!(activity.quiet_since(start), Some(start));
+        activity.request_finished("1", ms(start, 20));
+        assert!(activity.busy_requests().is_empty());
+    }
+}
@@ -0,0 +1,300 @@
+//! Heading structure of a page, for `webshot outline`.
+//!
+//! The `h1`–`h6` elements of a page (or of one element of it) are read in
+//! document order and nested by level: a heading becomes a child of the
+//! closest preceding heading with a lower level. Each heading keeps its text
+//! and an anchor to link to it, taken from its own `id` or that of an anchor
+//! inside it. The result is written as JSON or as a Markdown list of links,
+//! together with structural issues worth a look in docs QA, such as skipped
+//! levels or empty headings.
+
+use crate::error::{Result, WebshotError};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Evaluates to a JSON array of the visible headings within `ROOT` (a
+/// selector, or `null` for the whole document) as `{level, text, anchor}`
+const OUTLINE_SCRIPT: &str = r#"
+(() => {
+    const selector = ROOT;
+    const root = selector === null ? document : document.querySelector(selector);
+    if (!root) {
+        return JSON.stringify({ error: "No element matches " + selector });
+    }
+    const headings = Array.from(root.querySelectorAll("h1, h2, h3, h4, h5, h6"))
+        .filter((heading) => heading.getClientRects().length > 0)
+        .map((heading) => {
+            const anchor = heading.id
+                || (heading.querySelector("[id]") || {}).id
+                || (heading.querySelector("a[name]") || { name: null }).name;
+            return {
+                level: Number(heading.tagName.substring(1)),
+                text: (heading.innerText || heading.textContent || "").replace(/\s+/g, " ").trim(),
+                anchor: anchor || null,
+            };
+        });
+    return JSON.stringify({ title: document.title, headings });
+})()
+"#;
+
+/// Script returning the headings within the element `root` matches, or
+/// within the whole page, as a JSON string
+pub fn outline_script(root: Option<&str>) -> Result<String> {
+    Ok(OUTLINE_SCRIPT.replace("ROOT", &serde_json::to_string(&root)?))
+}
+
+/// Format `webshot outline` writes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutlineFormat {
+    #[default]
+    Json,
+    Markdown,
+}
+
+impl FromStr for OutlineFormat {
+    type Err = WebshotError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(OutlineFormat::Json),
+            "markdown" | "md" => Ok(OutlineFormat::Markdown),
+            _ => Err(WebshotError::config(format!(
+                "Unknown outline format: {}. Supported formats: json, markdown",
+                s
+            ))),
+        }
+    }
+}
+
+/// A heading and the headings nested under it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Heading {
+    /// 1 for `h1` through 6 for `h6`
+    pub level: u8,
+    pub text: String,
+    /// Fragment linking to the heading, without `#`
+    pub anchor: Option<String>,
+    pub children: Vec<Heading>,
+}
+
+/// A heading as the outline script reports it, before nesting
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+struct FlatHeading {
+    level: u8,
+    text: String,
+    anchor: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScriptResult {
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    headings: Vec<FlatHeading>,
+    error: Option<String>,
+}
+
+/// Heading hierarchy of a page
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Outline {
+    pub url: String,
+    /// The page's `<title>`
+    pub title: String,
+    pub headings: Vec<Heading>,
+    /// Structural problems, such as skipped heading levels
+    pub issues: Vec<String>,
+}
+
+impl Outline {
+    /// Outline from the JSON [`outline_script`] returned for `url`
+    pub fn from_script_result(url: &str, json: &str) -> Result<Self> {
+        let result: ScriptResult = serde_json::from_str(json)?;
+        if let Some(error) = result.error {
+            return Err(WebshotError::element_not_found(
+                error.trim_start_matches("No element matches "),
+            ));
+        }
+        Ok(Self::new(url, &result.title, result.headings))
+    }
+
+    fn new(url: &str, title: &str, flat: Vec<FlatHeading>) -> Self {
+        let issues = find_issues(&flat);
+        let mut headings = Vec::new();
+        for heading in flat {
+            insert(
+                &mut headings,
+                Heading {
+                    level: heading.level,
+                    text: heading.text,
+                    anchor: heading.anchor,
+                    children: Vec::new(),
+                },
+            );
+        }
+        Self {
+            url: url.to_string(),
+            title: title.to_string(),
+            headings,
+            issues,
+        }
+    }
+
+    /// Number of headings, nested ones included
+    pub fn heading_count(&self) -> usize {
+        fn count(headings: &[Heading]) -> usize {
+            headings
+                .iter()
+                .map(|heading| 1 + count(&heading.children))
+                .sum()
+        }
+        count(&self.headings)
+    }
+
+    /// The outline as a nested Markdown list, headings with an anchor
+    /// linking to it on the page
+    pub fn to_markdown(&self) -> String {
+        fn write(headings: &[Heading], url: &str, depth: usize, markdown: &mut String) {
+            for heading in headings {
+                let text = heading.text.replace('[', "\\[").replace(']', "\\]");
+                let item = match &heading.anchor {
+                    Some(anchor) => format!("[{}]({}#{})", text, url, anchor),
+                    None => text,
+                };
+                markdown.push_str(&format!("{}- {}\n", "  ".repeat(depth), item));
+                write(&heading.children, url, depth + 1, markdown);
+            }
+        }
+
+        let base = self.url.split('#').next().unwrap_or_default();
+        let mut markdown = String::new();
+        write(&self.headings, base, 0, &mut markdown);
+        markdown
+    }
+
+    /// The outline in `format`
+    pub fn render(&self, format: OutlineFormat) -> Result<String> {
+        match format {
+            OutlineFormat::Json => Ok(serde_json::to_string_pretty(self)?),
+            OutlineFormat::Markdown => Ok(self.to_markdown()),
+        }
+    }
+}
+
+impl fmt::Display for Outline {
+    /// One line per heading, indented by nesting depth
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn write(headings: &[Heading], depth: usize, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            for heading in headings {
+                writeln!(
+                    f,
+                    "{}h{} {}",
+                    "  ".repeat(depth),
+                    heading.level,
+                    heading.text
+                )?;
+                write(&heading.children, depth + 1, f)?;
+            }
+            Ok(())
+        }
+        write(&self.headings, 0, f)
+    }
+}
+
+/// Append `heading` as the last heading of `headings`, or nested under the
+/// last one when that has a lower level
+fn insert(headings: &mut Vec<Heading>, heading: Heading) {
+    match headings.last_mut() {
+        Some(last) if last.level < heading.level => insert(&mut last.children, heading),
+        _ => headings.push(heading),
+    }
+}
+
+fn find_issues(headings: &[FlatHeading]) -> Vec<String> {
+    let mut issues = Vec::new();
+    match headings.iter().filter(|heading| heading.level == 1).count() {
+        0 if !headings.is_empty() => issues.push("No h1 heading".to_string()),
+        0 | 1 => {}
+        count => issues.push(format!("{} h1 headings", count)),
+    }
+
+    let mut previous = 0;
+    for heading in headings {
+        if heading.text.is_empty() {
+            issues.push(format!("Empty h{} heading", heading.level));
+        }
+        if heading.level > previous + 1 {
+            issues.push(format!(
+                "h{} \"{}\" skips a level after {}",
+                heading.level,
+                heading.text,
+                match previous {
+                    0 => "the start of the page".to_string(),
+                    level => format!("h{}", level),
+                }
+            ));
+        }
+        previous = heading.level;
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOCS: &str = r#"{"title": "Install", "headings": [
+        {"level": 1, "text": "Install", "anchor": null},
+        {"level": 2, "text": "Requirements", "anchor": "requirements"},
+        {"level": 3, "text": "Linux", "anchor": "linux"},
+        {"level": 2, "text": "Steps [1-3]", "anchor": "steps"},
+        {"level": 4, "text": "Verify", "anchor": "verify"},
+        {"level": 2, "text": "", "anchor": null}
+    ]}"#;
+
+    #[test]
+    fn test_headings_nest_by_level() {
+        let outline =
+            Outline::from_script_result("https://docs.example.com/install", DOCS).unwrap();
+        assert_eq!(outline.title, "Install");
+        assert_eq!(outline.heading_count(), 6);
+        assert_eq!(outline.headings.len(), 1);
+        let sections: Vec<_> = outline.headings[0]
+            .children
+            .iter()
+            .map(|heading| (heading.text.as_str(), heading.children.len()))
+            .collect();
+        assert_eq!(sections, [("Requirements", 1), ("Steps [1-3]", 1), ("", 0)]);
+
+        assert_eq!(
+            outline.issues,
+            ["h4 \"Verify\" skips a level after h2", "Empty h2 heading"]
+        );
+        assert_eq!(outline.to_string().lines().nth(2), Some("    h3 Linux"));
+    }
+
+    #[test]
+    fn test_markdown_links_anchors() {
+        let outline =
+            Outline::from_script_result("https://docs.example.com/install#top", DOCS).unwrap();
+        let markdown = outline.render(OutlineFormat::Markdown).unwrap();
+        assert!(markdown.starts_with(
+            "- Install\n  - [Requirements](https://docs.example.com/install#requirements)\n    - [Linux]"
+        ));
+        assert!(
+            markdown.contains("  - [Steps \\[1-3\\]](https://docs.example.com/install#steps)\n")
+        );
+
+        assert_eq!(
+            "md".parse::<OutlineFormat>().unwrap(),
+            OutlineFormat::Markdown
+        );
+        assert!("yaml".parse::<OutlineFormat>().is_err());
+        assert!(outline_script(Some("main"))
+            .unwrap()
+            .contains("const selector = \"main\";"));
+
+        let missing = Outline::from_script_result("u", r#"{"error": "No element matches main"}"#);
+        assert!(missing.unwrap_err().to_string().contains("'main'"));
+    }
+}
@@ -1,8 +1,46 @@
 use crate::error::{Result, WebshotError};
-use crate::screenshot::ImageFormat;
+use crate::screenshot::{ImageFormat, TiffCompression};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use tracing::{debug, info};
 
+/// Filename tokens resolved from the loaded page rather than the input URL
+const PAGE_TOKENS: [&str; 2] = ["{title}", "{final_url_path}"];
+
+/// Longest page title used in a file name, in characters
+const MAX_TITLE_CHARS: usize = 100;
+
+/// How batch outputs are organized into directories
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputLayout {
+    /// Outputs are written where their paths say
+    #[default]
+    Flat,
+    /// Under a `YYYY/MM/DD/` directory of the capture date (UTC)
+    Date,
+    /// Under a directory named after the URL's host
+    Host,
+}
+
+impl FromStr for OutputLayout {
+    type Err = WebshotError;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_str() {
+            "flat" => Ok(Self::Flat),
+            "date" => Ok(Self::Date),
+            "host" => Ok(Self::Host),
+            _ => Err(WebshotError::config(format!(
+                "Unknown output layout: {}. Supported: date, host, flat",
+                value
+            ))),
+        }
+    }
+}
+
 /// Output handler for managing file operations and format conversions
 pub struct OutputHandler;
 
@@ -42,6 +80,145 @@ impl OutputHandler {
         format!("{}_{}.{}", sanitized_domain, timestamp, format.extension())
     }
 
+    /// Generate a stable, human-readable filename from a URL's host and path
+    pub fn url_to_filename(url: &str, format: ImageFormat) -> String {
+        use url::Url;
+
+        let stem = match Url::parse(url) {
+            Ok(parsed) => {
+                let host = parsed.host_str().unwrap_or("unknown");
+                let path = parsed.path().trim_matches('/');
+                if path.is_empty() {
+                    host.to_string()
+                } else {
+                    format!("{}_{}", host, path)
+                }
+            }
+            Err(_) => "unknown".to_string(),
+        };
+
+        format!("{}.{}", sanitize_filename(&stem), format.extension())
+    }
+
+    /// Render a filename template for a URL.
+    ///
+    /// Supported tokens: `{host}`, `{path}` (slashes become underscores, `index`
+    /// for the root, followed by a short hash of the query string when there is
+    /// one), `{index}` (1-based position in the batch), `{date}`
+    /// (`YYYY-MM-DD`) and `{timestamp}` (`YYYYmmdd_HHMMSS`). Token values are
+    /// sanitized so they cannot introduce directory separators. Page tokens
+    /// (`{title}`, `{final_url_path}`) are left for
+    /// [`OutputHandler::render_page_tokens`] once the page has loaded.
+    pub fn render_filename_template(template: &str, url: &str, index: usize) -> String {
+        use chrono::Utc;
+        use url::Url;
+
+        let parsed_url = Url::parse(url).ok();
+        let host = parsed_url
+            .as_ref()
+            .and_then(|u| u.host_str())
+            .unwrap_or("unknown")
+            .to_string();
+        let path = parsed_url
+            .as_ref()
+            .map(|u| u.path().trim_matches('/').to_string())
+            .filter(|path| !path.is_empty())
+            .unwrap_or_else(|| "index".to_string());
+        let path = match parsed_url.as_ref().and_then(|u| u.query()) {
+            Some(query) if !query.is_empty() => format!("{}_{}", path, query_hash(query)),
+            _ => path,
+        };
+        let now = Utc::now();
+
+        template
+            .replace("{host}", &sanitize_filename(&host))
+            .replace("{path}", &sanitize_filename(&path))
+            .replace("{index}", &index.to_string())
+            .replace("{date}", &now.format("%Y-%m-%d").to_string())
+            .replace("{timestamp}", &now.format("%Y%m%d_%H%M%S").to_string())
+    }
+
+    /// Whether an output path contains tokens that can only be resolved after
+    /// navigation
+    pub fn has_page_tokens<P: AsRef<Path>>(path: P) -> bool {
+        let path = path.as_ref().to_string_lossy();
+        PAGE_TOKENS.iter().any(|token| path.contains(token))
+    }
+
+    /// Resolve page tokens in an output path from the loaded page: `{title}`
+    /// (the document title, `untitled` when empty) and `{final_url_path}` (the
+    /// path of the URL after redirects, `index` for the root). Values are
+    /// sanitized like the other template tokens and titles are truncated to
+    /// keep file names within filesystem limits.
+    pub fn render_page_tokens<P: AsRef<Path>>(path: P, title: &str, final_url: &str) -> PathBuf {
+        let title: String = title.split_whitespace().collect::<Vec<_>>().join(" ");
+        let title = sanitize_filename(&title.chars().take(MAX_TITLE_CHARS).collect::<String>());
+        let title = if title.is_empty() {
+            "untitled".to_string()
+        } else {
+            title
+        };
+        let final_url_path = url::Url::parse(final_url)
+            .ok()
+            .map(|u| u.path().trim_matches('/').to_string())
+            .filter(|path| !path.is_empty())
+            .unwrap_or_else(|| "index".to_string());
+
+        PathBuf::from(
+            path.as_ref()
+                .to_string_lossy()
+                .replace("{title}", &title)
+                .replace("{final_url_path}", &sanitize_filename(&final_url_path)),
+        )
+    }
+
+    /// Place an output in the directory `layout` gives it, inserted just
+    /// before the file name (`shots/home.png` -> `shots/2024/05/31/home.png`
+    /// or `shots/example.com/home.png`), dated today
+    pub fn apply_layout<P: AsRef<Path>>(path: P, layout: OutputLayout, url: &str) -> PathBuf {
+        Self::apply_layout_on(path, layout, url, chrono::Utc::now().date_naive())
+    }
+
+    /// [`OutputHandler::apply_layout`] for captures taken on `date`
+    pub fn apply_layout_on<P: AsRef<Path>>(
+        path: P,
+        layout: OutputLayout,
+        url: &str,
+        date: NaiveDate,
+    ) -> PathBuf {
+        let path = path.as_ref();
+        let directory = match layout {
+            OutputLayout::Flat => return path.to_path_buf(),
+            OutputLayout::Date => PathBuf::from(date.format("%Y/%m/%d").to_string()),
+            OutputLayout::Host => {
+                let host = url::Url::parse(url)
+                    .ok()
+                    .and_then(|u| u.host_str().map(str::to_string))
+                    .unwrap_or_else(|| "unknown".to_string());
+                PathBuf::from(sanitize_filename(&host))
+            }
+        };
+        let Some(file_name) = path.file_name() else {
+            return path.to_path_buf();
+        };
+        path.with_file_name(directory).join(file_name)
+    }
+
+    /// Insert a `-<width>` suffix before the extension (`home.png` -> `home-375.png`)
+    pub fn with_width_suffix<P: AsRef<Path>>(path: P, width: u32) -> PathBuf {
+        let path = path.as_ref();
+        let stem = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let file_name = match path.extension() {
+            Some(ext) => format!("{}-{}.{}", stem, width, ext.to_string_lossy()),
+            None => format!("{}-{}", stem, width),
+        };
+
+        path.with_file_name(file_name)
+    }
+
     /// Validate that the output path has a supported extension
     pub fn validate_output_path<P: AsRef<Path>>(path: P) -> Result<ImageFormat> {
         let path = path.as_ref();
@@ -56,6 +233,8 @@ impl OutputHandler {
             "png" => Ok(ImageFormat::Png),
             "jpg" | "jpeg" => Ok(ImageFormat::Jpeg),
             "webp" => Ok(ImageFormat::WebP),
+            "gif" => Ok(ImageFormat::Gif),
+            "tif" | "tiff" => Ok(ImageFormat::Tiff),
             "pdf" => Ok(ImageFormat::Pdf),
             _ => Err(WebshotError::UnsupportedFormat { format: extension }),
         }
@@ -80,6 +259,10 @@ impl OutputHandler {
             ImageFormat::WebP => {
                 image::load_from_memory_with_format(data, image::ImageFormat::WebP)?
             }
+            ImageFormat::Gif => image::load_from_memory_with_format(data, image::ImageFormat::Gif)?,
+            ImageFormat::Tiff => {
+                image::load_from_memory_with_format(data, image::ImageFormat::Tiff)?
+            }
             ImageFormat::Pdf => {
                 return Err(WebshotError::config(
                     "Cannot convert from PDF format".to_string(),
@@ -104,6 +287,12 @@ impl OutputHandler {
                 let encoder = image::codecs::webp::WebPEncoder::new_lossless(&mut output);
                 img.write_with_encoder(encoder)?;
             }
+            ImageFormat::Gif => {
+                output = Self::encode_gif(&img, quality)?;
+            }
+            ImageFormat::Tiff => {
+                output = Self::encode_tiff(&img, TiffCompression::default(), None)?;
+            }
             ImageFormat::Pdf => {
                 return Err(WebshotError::config(
                     "Cannot convert to PDF format using image conversion".to_string(),
@@ -114,6 +303,109 @@ impl OutputHandler {
         Ok(output)
     }
 
+    /// Encode an image as GIF. GIF is limited to a 256-color palette; `quality`
+    /// (1-100, default 90) trades quantization speed for palette accuracy.
+    pub fn encode_gif(img: &image::DynamicImage, quality: Option<u8>) -> Result<Vec<u8>> {
+        let quality = i32::from(quality.unwrap_or(90).clamp(1, 100));
+        let speed = 30 - (quality - 1) * 29 / 99;
+        let rgba = img.to_rgba8();
+
+        let mut output = Vec::new();
+        {
+            let mut encoder = image::codecs::gif::GifEncoder::new_with_speed(&mut output, speed);
+            encoder.encode(
+                rgba.as_raw(),
+                rgba.width(),
+                rgba.height(),
+                image::ColorType::Rgba8,
+            )?;
+        }
+        Ok(output)
+    }
+
+    /// Encode an image as an RGBA TIFF with the given compression, embedding
+    /// `icc_profile` when given
+    pub fn encode_tiff(
+        img: &image::DynamicImage,
+        compression: TiffCompression,
+        icc_profile: Option<&[u8]>,
+    ) -> Result<Vec<u8>> {
+        use tiff::encoder::{compression as tiff_compression, TiffEncoder};
+
+        let rgba = img.to_rgba8();
+        let mut output = std::io::Cursor::new(Vec::new());
+        let mut encoder = TiffEncoder::new(&mut output)
+            .map_err(|e| WebshotError::screenshot(format!("Failed to encode TIFF: {}", e)))?;
+
+        let result = match compression {
+            TiffCompression::None => write_tiff(
+                &mut encoder,
+                &rgba,
+                tiff_compression::Uncompressed,
+                icc_profile,
+            ),
+            TiffCompression::Lzw => {
+                write_tiff(&mut encoder, &rgba, tiff_compression::Lzw, icc_profile)
+            }
+            TiffCompression::Deflate => write_tiff(
+                &mut encoder,
+                &rgba,
+                tiff_compression::Deflate::default(),
+                icc_profile,
+            ),
+            TiffCompression::Packbits => {
+                write_tiff(&mut encoder, &rgba, tiff_compression::Packbits, icc_profile)
+            }
+        };
+        result.map_err(|e| WebshotError::screenshot(format!("Failed to encode TIFF: {}", e)))?;
+
+        Ok(output.into_inner())
+    }
+
+    /// Embed an ICC color profile in encoded PNG or JPEG data.
+    ///
+    /// PNG gets an `iCCP` chunk (grayscale PNGs are returned unchanged, since
+    /// an RGB profile does not apply to them) and JPEG an `APP2` segment. TIFF
+    /// profiles are written by [`OutputHandler::encode_tiff`]; other formats
+    /// are returned unchanged.
+    pub fn embed_icc_profile(
+        data: &[u8],
+        format: ImageFormat,
+        icc_profile: &[u8],
+    ) -> Result<Vec<u8>> {
+        match format {
+            ImageFormat::Png => embed_png_icc_profile(data, icc_profile),
+            ImageFormat::Jpeg => embed_jpeg_icc_profile(data, icc_profile),
+            _ => Ok(data.to_vec()),
+        }
+    }
+
+    /// Re-encode a PNG screenshot with fewer colors to shrink archival output.
+    ///
+    /// With `colors`, the image is quantized to a palette of at most that many
+    /// colors (NeuQuant with Floyd-Steinberg dithering) and written as an indexed
+    /// PNG at the smallest bit depth that fits. With `grayscale`, it is written
+    /// as 8-bit luma instead. Without either, the data is returned unchanged.
+    pub fn reduce_png(data: &[u8], colors: Option<u16>, grayscale: bool) -> Result<Vec<u8>> {
+        let img = image::load_from_memory_with_format(data, image::ImageFormat::Png)?;
+        let mut output = Vec::new();
+
+        if grayscale {
+            let encoder = image::codecs::png::PngEncoder::new(&mut output);
+            image::DynamicImage::ImageLuma8(img.to_luma8()).write_with_encoder(encoder)?;
+        } else if let Some(colors) = colors {
+            let mut rgba = img.to_rgba8();
+            let quantizer = color_quant::NeuQuant::new(10, colors as usize, rgba.as_raw());
+            image::imageops::dither(&mut rgba, &quantizer);
+            let indices = image::imageops::index_colors(&rgba, &quantizer);
+            write_indexed_png(&mut output, &indices, &quantizer.color_map_rgba(), colors)?;
+        } else {
+            return Ok(data.to_vec());
+        }
+
+        Ok(output)
+    }
+
     /// Optimize image file size
     pub fn optimize_image<P: AsRef<Path>>(path: P, format: ImageFormat) -> Result<()> {
         let path = path.as_ref();
@@ -130,8 +422,8 @@ impl OutputHandler {
                 // For now, just validate the file is readable
                 let _img = image::open(path)?;
             }
-            ImageFormat::WebP => {
-                // For WebP, we could implement WebP optimization here
+            ImageFormat::WebP | ImageFormat::Gif | ImageFormat::Tiff => {
+                // For WebP, GIF and TIFF, we could implement optimization here
                 // For now, just validate the file is readable
                 let _img = image::open(path)?;
             }
@@ -201,6 +493,19 @@ impl OutputHandler {
         }
     }
 
+    /// Write a capture to `path`, replacing any existing file instead of
+    /// writing into it, so files hard-linked to it by an earlier dedupe
+    /// keep their own content
+    pub fn write_output<P: AsRef<Path>>(path: P, data: &[u8]) -> Result<()> {
+        let path = path.as_ref();
+        match std::fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+        std::fs::write(path, data)?;
+        Ok(())
+    }
+
     /// Check if file already exists and handle overwrites
     pub fn handle_existing_file<P: AsRef<Path>>(path: P, overwrite: bool) -> Result<()> {
         let path = path.as_ref();
@@ -216,6 +521,175 @@ impl OutputHandler {
     }
 }
 
+/// Write palette indices as an indexed PNG, packing pixels at the smallest
+/// bit depth that holds `colors` entries
+fn write_indexed_png(
+    output: &mut Vec<u8>,
+    indices: &image::GrayImage,
+    palette_rgba: &[u8],
+    colors: u16,
+) -> Result<()> {
+    let bit_depth = match colors {
+        0..=2 => png::BitDepth::One,
+        3..=4 => png::BitDepth::Two,
+        5..=16 => png::BitDepth::Four,
+        _ => png::BitDepth::Eight,
+    };
+    let bits = bit_depth as usize;
+    let pixels_per_byte = 8 / bits;
+
+    let (width, height) = indices.dimensions();
+    let row_bytes = (width as usize).div_ceil(pixels_per_byte);
+    let mut packed = vec![0u8; row_bytes * height as usize];
+    for (y, row) in indices.rows().enumerate() {
+        for (x, pixel) in row.enumerate() {
+            let shift = 8 - bits * (x % pixels_per_byte + 1);
+            packed[y * row_bytes + x / pixels_per_byte] |= pixel[0] << shift;
+        }
+    }
+
+    let palette: Vec<u8> = palette_rgba
+        .chunks_exact(4)
+        .flat_map(|color| [color[0], color[1], color[2]])
+        .collect();
+    let alpha: Vec<u8> = palette_rgba.chunks_exact(4).map(|color| color[3]).collect();
+
+    let mut encoder = png::Encoder::new(output, width, height);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(bit_depth);
+    encoder.set_palette(palette);
+    if alpha.iter().any(|&value| value < 255) {
+        encoder.set_trns(alpha);
+    }
+
+    let encode_error = |e: png::EncodingError| {
+        WebshotError::screenshot(format!("Failed to encode indexed PNG: {}", e))
+    };
+    let mut writer = encoder.write_header().map_err(encode_error)?;
+    writer.write_image_data(&packed).map_err(encode_error)?;
+    writer.finish().map_err(encode_error)
+}
+
+/// TIFF tag holding an embedded ICC profile
+const TIFF_ICC_PROFILE_TAG: u16 = 34675;
+
+/// ICC profile bytes, stored with the `UNDEFINED` field type the TIFF
+/// specification requires for this tag
+struct TiffIccProfile<'a>(&'a [u8]);
+
+impl tiff::encoder::TiffValue for TiffIccProfile<'_> {
+    const BYTE_LEN: u8 = 1;
+    const FIELD_TYPE: tiff::tags::Type = tiff::tags::Type::UNDEFINED;
+
+    fn count(&self) -> usize {
+        self.0.len()
+    }
+
+    fn data(&self) -> std::borrow::Cow<'_, [u8]> {
+        std::borrow::Cow::Borrowed(self.0)
+    }
+}
+
+fn write_tiff<W, D>(
+    encoder: &mut tiff::encoder::TiffEncoder<W>,
+    rgba: &image::RgbaImage,
+    compression: D,
+    icc_profile: Option<&[u8]>,
+) -> tiff::TiffResult<()>
+where
+    W: std::io::Write + std::io::Seek,
+    D: tiff::encoder::compression::Compression,
+{
+    let (width, height) = rgba.dimensions();
+    let mut image = encoder.new_image_with_compression::<tiff::encoder::colortype::RGBA8, D>(
+        width,
+        height,
+        compression,
+    )?;
+    if let Some(icc_profile) = icc_profile {
+        image.encoder().write_tag(
+            tiff::tags::Tag::Unknown(TIFF_ICC_PROFILE_TAG),
+            TiffIccProfile(icc_profile),
+        )?;
+    }
+    image.write_data(rgba.as_raw())
+}
+
+/// Re-encode a PNG with an `iCCP` chunk, keeping its pixel data and color type
+fn embed_png_icc_profile(data: &[u8], icc_profile: &[u8]) -> Result<Vec<u8>> {
+    let decode_error =
+        |e: png::DecodingError| WebshotError::screenshot(format!("Invalid PNG data: {}", e));
+    let mut reader = png::Decoder::new(data).read_info().map_err(decode_error)?;
+    if matches!(
+        reader.info().color_type,
+        png::ColorType::Grayscale | png::ColorType::GrayscaleAlpha
+    ) {
+        return Ok(data.to_vec());
+    }
+
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut pixels).map_err(decode_error)?;
+    pixels.truncate(frame.buffer_size());
+
+    let mut info = reader.info().clone();
+    info.icc_profile = Some(std::borrow::Cow::Owned(icc_profile.to_vec()));
+    // An embedded profile supersedes the sRGB, gamma and chromaticity chunks
+    info.srgb = None;
+    info.source_gamma = None;
+    info.source_chromaticities = None;
+
+    let encode_error =
+        |e: png::EncodingError| WebshotError::screenshot(format!("Failed to encode PNG: {}", e));
+    let mut output = Vec::new();
+    let mut writer = png::Encoder::with_info(&mut output, info)
+        .and_then(png::Encoder::write_header)
+        .map_err(encode_error)?;
+    writer.write_image_data(&pixels).map_err(encode_error)?;
+    writer.finish().map_err(encode_error)?;
+
+    Ok(output)
+}
+
+/// Insert an `APP2` ICC profile segment after the JPEG's SOI marker and
+/// JFIF header
+fn embed_jpeg_icc_profile(data: &[u8], icc_profile: &[u8]) -> Result<Vec<u8>> {
+    const ICC_MARKER: &[u8] = b"ICC_PROFILE\0";
+
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        return Err(WebshotError::screenshot(
+            "Invalid JPEG data: missing SOI marker",
+        ));
+    }
+    let segment_length = 2 + ICC_MARKER.len() + 2 + icc_profile.len();
+    let segment_length = u16::try_from(segment_length)
+        .map_err(|_| WebshotError::screenshot("ICC profile too large for one JPEG segment"))?;
+
+    let mut insert_at = 2;
+    if data.get(2..4) == Some(&[0xFF, 0xE0]) {
+        let jfif_length = data
+            .get(4..6)
+            .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]) as usize)
+            .ok_or_else(|| WebshotError::screenshot("Invalid JPEG data: truncated header"))?;
+        insert_at += 2 + jfif_length;
+    }
+    if insert_at > data.len() {
+        return Err(WebshotError::screenshot(
+            "Invalid JPEG data: truncated header",
+        ));
+    }
+
+    let mut output = Vec::with_capacity(data.len() + segment_length as usize + 2);
+    output.extend_from_slice(&data[..insert_at]);
+    output.extend_from_slice(&[0xFF, 0xE2]);
+    output.extend_from_slice(&segment_length.to_be_bytes());
+    output.extend_from_slice(ICC_MARKER);
+    output.extend_from_slice(&[1, 1]); // segment 1 of 1
+    output.extend_from_slice(icc_profile);
+    output.extend_from_slice(&data[insert_at..]);
+
+    Ok(output)
+}
+
 /// Sanitize a filename by removing or replacing invalid characters
 fn sanitize_filename(filename: &str) -> String {
     filename
@@ -230,8 +704,18 @@ fn sanitize_filename(filename: &str) -> String {
         .to_string()
 }
 
+/// Eight hex digits identifying a query string, so URLs differing only in
+/// their query render to different file names
+fn query_hash(query: &str) -> String {
+    let digest = ring::digest::digest(&ring::digest::SHA256, query.as_bytes());
+    digest.as_ref()[..4]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
 /// Format file size in human-readable format
-fn format_file_size(size: u64) -> String {
+pub fn format_file_size(size: u64) -> String {
     const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
     let mut size = size as f64;
     let mut unit_index = 0;
@@ -248,11 +732,42 @@ fn format_file_size(size: u64) -> String {
     }
 }
 
+/// Describe the size change from re-encoding a file, e.g.
+/// `1.2 MB -> 300.0 KB (75.0% smaller)`
+pub fn format_size_reduction(original: u64, reduced: u64) -> String {
+    let saved = if original == 0 {
+        0.0
+    } else {
+        (original as f64 - reduced as f64) / original as f64 * 100.0
+    };
+
+    format!(
+        "{} -> {} ({:.1}% smaller)",
+        format_file_size(original),
+        format_file_size(reduced),
+        saved
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::color::ColorProfile;
     use tempfile::TempDir;
 
+    #[test]
+    fn test_write_output_leaves_hard_links_alone() {
+        let dir = TempDir::new().unwrap();
+        let home = dir.path().join("home.png");
+        let linked = dir.path().join("about.png");
+        std::fs::write(&home, b"same").unwrap();
+        std::fs::hard_link(&home, &linked).unwrap();
+
+        OutputHandler::write_output(&linked, b"changed").unwrap();
+        assert_eq!(std::fs::read(&linked).unwrap(), b"changed");
+        assert_eq!(std::fs::read(&home).unwrap(), b"same");
+    }
+
     #[test]
     fn test_sanitize_filename() {
         assert_eq!(sanitize_filename("test.com"), "test.com");
@@ -270,6 +785,117 @@ mod tests {
         assert_eq!(format_file_size(1073741824), "1.0 GB");
     }
 
+    #[test]
+    fn test_format_size_reduction() {
+        assert_eq!(
+            format_size_reduction(4096, 1024),
+            "4.0 KB -> 1.0 KB (75.0% smaller)"
+        );
+        assert_eq!(format_size_reduction(0, 0), "0 B -> 0 B (0.0% smaller)");
+    }
+
+    fn gradient_png() -> Vec<u8> {
+        let img = image::RgbaImage::from_fn(64, 16, |x, y| {
+            image::Rgba([(x * 4) as u8, (y * 16) as u8, 128, 255])
+        });
+        let mut data = Vec::new();
+        image::DynamicImage::ImageRgba8(img)
+            .write_with_encoder(image::codecs::png::PngEncoder::new(&mut data))
+            .unwrap();
+        data
+    }
+
+    #[test]
+    fn test_reduce_png_to_palette() {
+        let reduced = OutputHandler::reduce_png(&gradient_png(), Some(16), false).unwrap();
+
+        let reader = png::Decoder::new(reduced.as_slice()).read_info().unwrap();
+        assert_eq!(reader.info().color_type, png::ColorType::Indexed);
+        assert_eq!(reader.info().bit_depth, png::BitDepth::Four);
+        assert_eq!(reader.info().width, 64);
+
+        let decoded = image::load_from_memory(&reduced).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (64, 16));
+    }
+
+    #[test]
+    fn test_reduce_png_to_grayscale() {
+        let reduced = OutputHandler::reduce_png(&gradient_png(), None, true).unwrap();
+
+        let decoded = image::load_from_memory(&reduced).unwrap();
+        assert_eq!(decoded.color(), image::ColorType::L8);
+
+        let original = gradient_png();
+        assert_eq!(
+            OutputHandler::reduce_png(&original, None, false).unwrap(),
+            original
+        );
+    }
+
+    #[test]
+    fn test_convert_image_to_gif_and_tiff() {
+        let png = gradient_png();
+
+        let gif = OutputHandler::convert_image(&png, ImageFormat::Png, ImageFormat::Gif, Some(50))
+            .unwrap();
+        let decoded = image::load_from_memory_with_format(&gif, image::ImageFormat::Gif).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (64, 16));
+
+        for compression in [
+            TiffCompression::None,
+            TiffCompression::Lzw,
+            TiffCompression::Deflate,
+            TiffCompression::Packbits,
+        ] {
+            let img = image::load_from_memory(&png).unwrap();
+            let tiff = OutputHandler::encode_tiff(&img, compression, None).unwrap();
+            let decoded =
+                image::load_from_memory_with_format(&tiff, image::ImageFormat::Tiff).unwrap();
+            assert_eq!(decoded.to_rgba8(), img.to_rgba8());
+        }
+    }
+
+    #[test]
+    fn test_embed_icc_profile() {
+        use image::ImageDecoder;
+
+        let icc_profile = ColorProfile::DisplayP3.icc_profile();
+        let png = gradient_png();
+
+        let tagged =
+            OutputHandler::embed_icc_profile(&png, ImageFormat::Png, &icc_profile).unwrap();
+        let reader = png::Decoder::new(tagged.as_slice()).read_info().unwrap();
+        assert_eq!(
+            reader.info().icc_profile.as_deref(),
+            Some(icc_profile.as_slice())
+        );
+
+        let grayscale = OutputHandler::reduce_png(&png, None, true).unwrap();
+        assert_eq!(
+            OutputHandler::embed_icc_profile(&grayscale, ImageFormat::Png, &icc_profile).unwrap(),
+            grayscale
+        );
+
+        let jpeg =
+            OutputHandler::convert_image(&png, ImageFormat::Png, ImageFormat::Jpeg, None).unwrap();
+        let tagged =
+            OutputHandler::embed_icc_profile(&jpeg, ImageFormat::Jpeg, &icc_profile).unwrap();
+        let mut decoder =
+            image::codecs::jpeg::JpegDecoder::new(std::io::Cursor::new(&tagged)).unwrap();
+        assert_eq!(decoder.icc_profile(), Some(icc_profile.clone()));
+
+        let img = image::load_from_memory(&png).unwrap();
+        let tiff =
+            OutputHandler::encode_tiff(&img, TiffCompression::Lzw, Some(&icc_profile)).unwrap();
+        let mut decoder = tiff::decoder::Decoder::new(std::io::Cursor::new(tiff)).unwrap();
+        assert_eq!(
+            decoder
+                .get_tag_u8_vec(tiff::tags::Tag::Unknown(TIFF_ICC_PROFILE_TAG))
+                .unwrap(),
+            icc_profile
+        );
+    }
+
     #[test]
     fn test_generate_filename() {
         let filename =
@@ -282,6 +908,125 @@ mod tests {
         assert!(filename.ends_with(".jpg"));
     }
 
+    #[test]
+    fn test_url_to_filename() {
+        assert_eq!(
+            OutputHandler::url_to_filename("https://example.com/", ImageFormat::Png),
+            "example.com.png"
+        );
+        assert_eq!(
+            OutputHandler::url_to_filename("https://example.com/blog/post?id=1", ImageFormat::Jpeg),
+            "example.com_blog_post.jpg"
+        );
+        assert_eq!(
+            OutputHandler::url_to_filename("not a url", ImageFormat::Png),
+            "unknown.png"
+        );
+    }
+
+    #[test]
+    fn test_render_filename_template() {
+        assert_eq!(
+            OutputHandler::render_filename_template(
+                "{index}-{host}_{path}.png",
+                "https://example.com/blog/post",
+                7
+            ),
+            "7-example.com_blog_post.png"
+        );
+        assert_eq!(
+            OutputHandler::render_filename_template("{host}_{path}.png", "https://example.com", 1),
+            "example.com_index.png"
+        );
+
+        let page_1 =
+            OutputHandler::render_filename_template("{path}.png", "https://example.com/list", 1);
+        let page_2 = OutputHandler::render_filename_template(
+            "{path}.png",
+            "https://example.com/list?page=2",
+            1,
+        );
+        let page_3 = OutputHandler::render_filename_template(
+            "{path}.png",
+            "https://example.com/list?page=3",
+            1,
+        );
+        assert_eq!(page_1, "list.png");
+        assert!(page_2.starts_with("list_") && page_2.len() == "list_12345678.png".len());
+        assert_ne!(page_2, page_3);
+
+        let dated = OutputHandler::render_filename_template("{date}.png", "https://example.com", 1);
+        assert_eq!(dated.len(), "YYYY-MM-DD.png".len());
+    }
+
+    #[test]
+    fn test_render_page_tokens() {
+        let path = OutputHandler::render_filename_template(
+            "reports/{title}_{final_url_path}_{host}.png",
+            "https://example.com/old",
+            1,
+        );
+        assert!(OutputHandler::has_page_tokens(&path));
+        assert!(!OutputHandler::has_page_tokens("reports/{host}.png"));
+
+        assert_eq!(
+            OutputHandler::render_page_tokens(
+                &path,
+                "  Pricing  –\nAcme/Inc ",
+                "https://example.com/plans/pro?ref=1",
+            ),
+            PathBuf::from("reports/Pricing – Acme_Inc_plans_pro_example.com.png")
+        );
+        assert_eq!(
+            OutputHandler::render_page_tokens(
+                "{title}-{final_url_path}.png",
+                "",
+                "https://example.com/"
+            ),
+            PathBuf::from("untitled-index.png")
+        );
+
+        let long_title = "a".repeat(300);
+        let rendered = OutputHandler::render_page_tokens("{title}.png", &long_title, "");
+        assert_eq!(rendered.as_os_str().len(), MAX_TITLE_CHARS + ".png".len());
+    }
+
+    #[test]
+    fn test_apply_layout() {
+        let date = NaiveDate::from_ymd_opt(2024, 5, 31).unwrap();
+        let url = "https://www.Example.com:8443/pricing";
+        assert_eq!(
+            OutputHandler::apply_layout_on("shots/home.png", OutputLayout::Date, url, date),
+            PathBuf::from("shots/2024/05/31/home.png")
+        );
+        assert_eq!(
+            OutputHandler::apply_layout_on("home.png", OutputLayout::Host, url, date),
+            PathBuf::from("www.example.com/home.png")
+        );
+        assert_eq!(
+            OutputHandler::apply_layout_on("shots/home.png", OutputLayout::Flat, url, date),
+            PathBuf::from("shots/home.png")
+        );
+        assert_eq!("HOST".parse::<OutputLayout>().unwrap(), OutputLayout::Host);
+        assert!("monthly".parse::<OutputLayout>().is_err());
+    }
+
+    #[test]
+    fn test_with_width_suffix() {
+        assert_eq!(
+            OutputHandler::with_width_suffix("out/home.png", 375),
+            PathBuf::from("out/home-375.png")
+        );
+        assert_eq!(
+            OutputHandler::with_width_suffix("page.v2.jpg", 1920),
+            PathBuf::from("page.v2-1920.jpg")
+        );
+        assert_eq!(
+            OutputHandler::with_width_suffix("shot", 768),
+            PathBuf::from("shot-768")
+        );
+    }
+
     #[test]
     fn test_validate_output_path() {
         assert_eq!(
@@ -305,7 +1050,16 @@ mod tests {
             ImageFormat::WebP
         );
 
-        assert!(OutputHandler::validate_output_path("test.gif").is_err());
+        assert_eq!(
+            OutputHandler::validate_output_path("test.gif").unwrap(),
+            ImageFormat::Gif
+        );
+        assert_eq!(
+            OutputHandler::validate_output_path("test.tif").unwrap(),
+            ImageFormat::Tiff
+        );
+
+        assert!(OutputHandler::validate_output_path("test.bmp").is_err());
         assert!(OutputHandler::validate_output_path("test").is_err());
     }
 
@@ -0,0 +1,330 @@
+use crate::error::{Result, WebshotError};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// How long the dismissal script waits for a consent overlay to appear
+pub const DEFAULT_OVERLAY_WAIT_MS: u64 = 1500;
+
+/// Waits up to `__WAIT_MS__` for any rule's elements to appear, then clicks
+/// the first visible button of each matching rule and hides its containers.
+/// Restores page scrolling that consent dialogs commonly lock. Resolves to a
+/// JSON array of the names of the rules that matched.
+const DISMISS_OVERLAYS_SCRIPT: &str = r#"
+(async () => {
+    const rules = __RULES__;
+    const choice = __CHOICE__;
+    const deadline = Date.now() + __WAIT_MS__;
+    const delay = (ms) => new Promise((resolve) => setTimeout(resolve, ms));
+
+    const query = (selector) => {
+        try {
+            return Array.from(document.querySelectorAll(selector));
+        } catch (e) {
+            return [];
+        }
+    };
+    const visible = (el) => {
+        const rect = el.getBoundingClientRect();
+        const style = getComputedStyle(el);
+        return rect.width > 0 && rect.height > 0 && style.visibility !== 'hidden' && style.display !== 'none';
+    };
+    const buttons = (rule) => (rule[choice].length > 0 ? rule[choice] : rule.accept)
+        .flatMap(query)
+        .filter(visible);
+    const present = (rule) => buttons(rule).length > 0 || rule.hide.some((selector) => query(selector).length > 0);
+
+    let matched = rules.filter(present);
+    while (matched.length === 0 && Date.now() < deadline) {
+        await delay(100);
+        matched = rules.filter(present);
+    }
+
+    for (const rule of matched) {
+        const [button] = buttons(rule);
+        if (button) button.click();
+    }
+    if (matched.length > 0) {
+        await delay(150);
+        for (const rule of matched) {
+            for (const el of rule.hide.flatMap(query)) {
+                el.style.setProperty('display', 'none', 'important');
+            }
+        }
+        for (const el of [document.documentElement, document.body]) {
+            if (el && getComputedStyle(el).overflow === 'hidden') {
+                el.style.setProperty('overflow', 'auto', 'important');
+            }
+        }
+    }
+    return JSON.stringify(matched.map((rule) => rule.name));
+})()
+"#;
+
+/// Which consent button to press when a rule offers both
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OverlayChoice {
+    #[default]
+    Accept,
+    Reject,
+}
+
+impl fmt::Display for OverlayChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            OverlayChoice::Accept => "accept",
+            OverlayChoice::Reject => "reject",
+        })
+    }
+}
+
+impl FromStr for OverlayChoice {
+    type Err = WebshotError;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_str() {
+            "accept" => Ok(Self::Accept),
+            "reject" => Ok(Self::Reject),
+            _ => Err(WebshotError::config(format!(
+                "Unknown overlay choice: {}. Supported: accept, reject",
+                value
+            ))),
+        }
+    }
+}
+
+/// Selectors recognizing one consent framework or overlay. Rules without
+/// `reject` buttons fall back to `accept`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OverlayRule {
+    pub name: String,
+    /// Buttons that accept the overlay, tried in order
+    #[serde(default)]
+    pub accept: Vec<String>,
+    /// Buttons that reject or close the overlay, tried in order
+    #[serde(default)]
+    pub reject: Vec<String>,
+    /// Containers hidden after the click, or instead of it
+    #[serde(default)]
+    pub hide: Vec<String>,
+}
+
+impl OverlayRule {
+    fn new(name: &str, accept: &[&str], reject: &[&str], hide: &[&str]) -> Self {
+        let owned = |selectors: &[&str]| selectors.iter().map(|s| s.to_string()).collect();
+        Self {
+            name: name.to_string(),
+            accept: owned(accept),
+            reject: owned(reject),
+            hide: owned(hide),
+        }
+    }
+}
+
+/// Rules for common consent management platforms
+pub fn builtin_rules() -> Vec<OverlayRule> {
+    vec![
+        OverlayRule::new(
+            "onetrust",
+            &["#onetrust-accept-btn-handler"],
+            &[
+                "#onetrust-reject-all-handler",
+                ".onetrust-close-btn-handler",
+            ],
+            &["#onetrust-consent-sdk"],
+        ),
+        OverlayRule::new(
+            "cookiebot",
+            &[
+                "#CybotCookiebotDialogBodyLevelButtonLevelOptinAllowAll",
+                "#CybotCookiebotDialogBodyButtonAccept",
+            ],
+            &["#CybotCookiebotDialogBodyButtonDecline"],
+            &["#CybotCookiebotDialog", "#CybotCookiebotDialogBodyUnderlay"],
+        ),
+        OverlayRule::new(
+            "quantcast",
+            &[".qc-cmp2-summary-buttons button[mode=\"primary\"]"],
+            &[".qc-cmp2-summary-buttons button[mode=\"secondary\"]"],
+            &[".qc-cmp2-container"],
+        ),
+        OverlayRule::new(
+            "trustarc",
+            &["#truste-consent-button"],
+            &["#truste-consent-required"],
+            &[
+                "#truste-consent-track",
+                ".truste_overlay",
+                ".truste_box_overlay",
+            ],
+        ),
+        OverlayRule::new(
+            "didomi",
+            &["#didomi-notice-agree-button"],
+            &[
+                "#didomi-notice-disagree-button",
+                ".didomi-continue-without-agreeing",
+            ],
+            &["#didomi-host"],
+        ),
+        OverlayRule::new(
+            "usercentrics",
+            &[],
+            &[],
+            &["#usercentrics-root", "#usercentrics-cmp-ui"],
+        ),
+        OverlayRule::new(
+            "osano",
+            &[".osano-cm-accept-all"],
+            &[".osano-cm-denyAll"],
+            &[".osano-cm-window"],
+        ),
+        OverlayRule::new(
+            "cookieyes",
+            &[".cky-btn-accept"],
+            &[".cky-btn-reject"],
+            &[".cky-consent-container", ".cky-overlay"],
+        ),
+        OverlayRule::new(
+            "complianz",
+            &[".cmplz-accept"],
+            &[".cmplz-deny"],
+            &["#cmplz-cookiebanner-container", ".cmplz-cookiebanner"],
+        ),
+        OverlayRule::new(
+            "klaro",
+            &[".klaro .cm-btn-accept-all", ".klaro .cm-btn-success"],
+            &[".klaro .cm-btn-decline"],
+            &[".klaro"],
+        ),
+        OverlayRule::new(
+            "cookie-notice",
+            &["#cn-accept-cookie"],
+            &["#cn-refuse-cookie"],
+            &["#cookie-notice"],
+        ),
+        OverlayRule::new(
+            "generic",
+            &[],
+            &[],
+            &[
+                "#cookie-banner",
+                ".cookie-banner",
+                "#cookie-consent",
+                ".cookie-consent",
+                "#cookieConsent",
+                "#gdpr-cookie-notice",
+            ],
+        ),
+    ]
+}
+
+/// Load extra rules from a YAML list
+pub fn load_rules<P: AsRef<Path>>(path: P) -> Result<Vec<OverlayRule>> {
+    let path = path.as_ref();
+    let content = std::fs::read_to_string(path).map_err(|e| {
+        WebshotError::config(format!(
+            "Failed to read overlay rules {}: {}",
+            path.display(),
+            e
+        ))
+    })?;
+    Ok(serde_yaml::from_str(&content)?)
+}
+
+/// Consent overlay dismissal settings for a capture
+#[derive(Debug, Clone, PartialEq)]
+pub struct OverlayDismissal {
+    pub rules: Vec<OverlayRule>,
+    pub choice: OverlayChoice,
+    /// How long to wait for an overlay to appear, in milliseconds
+    pub wait_ms: u64,
+}
+
+impl Default for OverlayDismissal {
+    fn default() -> Self {
+        Self {
+            rules: builtin_rules(),
+            choice: OverlayChoice::default(),
+            wait_ms: DEFAULT_OVERLAY_WAIT_MS,
+        }
+    }
+}
+
+impl OverlayDismissal {
+    /// Built-in rules followed by the rules in an optional YAML file
+    pub fn with_rules_file(rules_file: Option<&Path>, choice: OverlayChoice) -> Result<Self> {
+        let mut rules = builtin_rules();
+        if let Some(path) = rules_file {
+            rules.extend(load_rules(path)?);
+        }
+        Ok(Self {
+            rules,
+            choice,
+            ..Default::default()
+        })
+    }
+
+    /// Dismissal script for these rules
+    pub fn script(&self) -> Result<String> {
+        Ok(DISMISS_OVERLAYS_SCRIPT
+            .replace("__RULES__", &serde_json::to_string(&self.rules)?)
+            .replace("__CHOICE__", &serde_json::to_string(&self.choice)?)
+            .replace("__WAIT_MS__", &self.wait_ms.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_overlay_choice_parsing() {
+        assert_eq!(
+            "Reject".parse::<OverlayChoice>().unwrap(),
+            OverlayChoice::Reject
+        );
+        assert!("ignore"
+            .parse::<OverlayChoice>()
+            .unwrap_err()
+            .to_string()
+            .contains("Unknown overlay choice"));
+    }
+
+    #[test]
+    fn test_rules_file_extends_builtin_rules() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("rules.yaml");
+        std::fs::write(
+            &path,
+            "- name: intranet\n  accept: ['#consent-ok']\n  hide: ['.consent-modal']\n",
+        )
+        .unwrap();
+
+        let dismissal =
+            OverlayDismissal::with_rules_file(Some(&path), OverlayChoice::Reject).unwrap();
+        let custom = dismissal.rules.last().unwrap();
+        assert_eq!(dismissal.rules.len(), builtin_rules().len() + 1);
+        assert_eq!(custom.name, "intranet");
+        assert!(custom.reject.is_empty());
+
+        assert!(OverlayDismissal::with_rules_file(
+            Some(&temp_dir.path().join("none.yaml")),
+            OverlayChoice::Accept
+        )
+        .unwrap_err()
+        .to_string()
+        .contains("Failed to read overlay rules"));
+    }
+
+    #[test]
+    fn test_dismissal_script_embeds_rules() {
+        let script = OverlayDismissal::default().script().unwrap();
+        assert!(script.contains("#onetrust-accept-btn-handler"));
+        assert!(script.contains(r#"const choice = "accept";"#));
+        assert!(!script.contains("__"));
+    }
+}
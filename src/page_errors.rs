@@ -0,0 +1,258 @@
+use crate::error::{Result, WebshotError};
+use headless_chrome::protocol::cdp::types::Event;
+use headless_chrome::protocol::cdp::Runtime::events::{
+    ConsoleAPICalledEventParams, ExceptionThrownEventParams,
+};
+use headless_chrome::protocol::cdp::Runtime::{ConsoleAPICalledEventTypeOption, RemoteObject};
+use headless_chrome::Tab;
+use serde::Serialize;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use tracing::debug;
+
+/// Where a page error was reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PageErrorKind {
+    /// `console.error()` or a failed `console.assert()`
+    ConsoleError,
+    /// An uncaught exception or unhandled promise rejection
+    Exception,
+}
+
+/// An error the page reported while loading
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PageError {
+    pub kind: PageErrorKind,
+    pub message: String,
+    /// `url:line:column` of the code that reported the error, when known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+impl PageError {
+    /// Read a `Runtime.consoleAPICalled` event, ignoring everything but
+    /// errors and failed assertions
+    pub fn from_console(params: &ConsoleAPICalledEventParams) -> Option<Self> {
+        if !matches!(
+            params.Type,
+            ConsoleAPICalledEventTypeOption::Error | ConsoleAPICalledEventTypeOption::Assert
+        ) {
+            return None;
+        }
+
+        let message = params
+            .args
+            .iter()
+            .map(describe)
+            .collect::<Vec<_>>()
+            .join(" ");
+        let source = params
+            .stack_trace
+            .as_ref()
+            .and_then(|trace| trace.call_frames.first())
+            .map(|frame| location(&frame.url, frame.line_number, frame.column_number));
+        Some(Self {
+            kind: PageErrorKind::ConsoleError,
+            message,
+            source,
+        })
+    }
+
+    /// Read a `Runtime.exceptionThrown` event
+    pub fn from_exception(params: &ExceptionThrownEventParams) -> Self {
+        let details = &params.exception_details;
+        // The description holds the error's stack; its first line is the
+        // `TypeError: ...` message
+        let message = details
+            .exception
+            .as_ref()
+            .and_then(|exception| exception.description.as_deref())
+            .and_then(|description| description.lines().next())
+            .unwrap_or(&details.text)
+            .to_string();
+        let source = details
+            .url
+            .as_deref()
+            .filter(|url| !url.is_empty())
+            .map(|url| location(url, details.line_number, details.column_number));
+        Self {
+            kind: PageErrorKind::Exception,
+            message,
+            source,
+        }
+    }
+}
+
+impl fmt::Display for PageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            PageErrorKind::ConsoleError => "console error",
+            PageErrorKind::Exception => "uncaught exception",
+        };
+        write!(f, "{}: {}", kind, self.message)?;
+        if let Some(source) = &self.source {
+            write!(f, " (at {})", source)?;
+        }
+        Ok(())
+    }
+}
+
+/// Collects console errors and uncaught exceptions from a tab so a capture
+/// can fail when the page is broken
+#[derive(Debug, Clone, Default)]
+pub struct PageErrorWatch {
+    errors: Arc<Mutex<Vec<PageError>>>,
+}
+
+impl PageErrorWatch {
+    /// Start collecting the kinds of errors enabled on `tab`, or return
+    /// `None` when neither is
+    pub fn attach(tab: &Tab, console_errors: bool, exceptions: bool) -> Result<Option<Self>> {
+        if !console_errors && !exceptions {
+            return Ok(None);
+        }
+
+        let watch = Self::default();
+        let errors = Arc::clone(&watch.errors);
+        tab.add_event_listener(Arc::new(move |event: &Event| {
+            let error = match event {
+                Event::RuntimeConsoleAPICalled(event) if console_errors => {
+                    PageError::from_console(&event.params)
+                }
+                Event::RuntimeExceptionThrown(event) if exceptions => {
+                    Some(PageError::from_exception(&event.params))
+                }
+                _ => None,
+            };
+            if let Some(error) = error {
+                debug!("Page reported {}", error);
+                errors.lock().unwrap().push(error);
+            }
+        }))
+        .map_err(WebshotError::Browser)?;
+        tab.enable_runtime().map_err(WebshotError::Browser)?;
+        Ok(Some(watch))
+    }
+
+    /// Errors collected so far
+    pub fn errors(&self) -> Vec<PageError> {
+        self.errors.lock().unwrap().clone()
+    }
+
+    /// Fail with every error collected so far, if there are any
+    pub fn check(&self, url: &str) -> Result<()> {
+        let errors = self.errors();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(WebshotError::page_errors(url, errors))
+        }
+    }
+}
+
+/// Text of a console argument, as the DevTools console would print it
+pub(crate) fn describe(arg: &RemoteObject) -> String {
+    match &arg.value {
+        Some(serde_json::Value::String(text)) => text.clone(),
+        Some(value) => value.to_string(),
+        None => arg
+            .description
+            .clone()
+            .or_else(|| arg.unserializable_value.clone())
+            .unwrap_or_else(|| "undefined".to_string()),
+    }
+}
+
+/// Human `url:line:column` location from zero-based DevTools positions
+pub(crate) fn location(url: &str, line: u32, column: u32) -> String {
+    format!("{}:{}:{}", url, line + 1, column + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn console(kind: &str, args: serde_json::Value) -> ConsoleAPICalledEventParams {
+        serde_json::from_value(json!({
+            "type": kind,
+            "args": args,
+            "executionContextId": 1,
+            "timestamp": 0.0,
+            "stackTrace": {
+                "callFrames": [{
+                    "functionName": "init",
+                    "scriptId": "12",
+                    "url": "https://example.com/app.js",
+                    "lineNumber": 9,
+                    "columnNumber": 4
+                }]
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_console_errors_are_collected() {
+        let params = console(
+            "error",
+            json!([
+                {"type": "string", "value": "Failed to load"},
+                {"type": "number", "value": 42},
+                {"type": "object", "description": "Error: boom"}
+            ]),
+        );
+        let error = PageError::from_console(&params).unwrap();
+        assert_eq!(error.message, "Failed to load 42 Error: boom");
+        assert_eq!(
+            error.to_string(),
+            "console error: Failed to load 42 Error: boom (at https://example.com/app.js:10:5)"
+        );
+
+        let log = console("log", json!([{"type": "string", "value": "ready"}]));
+        assert_eq!(PageError::from_console(&log), None);
+    }
+
+    #[test]
+    fn test_exception_message_is_first_line_of_stack() {
+        let params: ExceptionThrownEventParams = serde_json::from_value(json!({
+            "timestamp": 0.0,
+            "exceptionDetails": {
+                "exceptionId": 1,
+                "text": "Uncaught",
+                "lineNumber": 0,
+                "columnNumber": 17,
+                "url": "https://example.com/",
+                "exception": {
+                    "type": "object",
+                    "subtype": "error",
+                    "description": "TypeError: x is undefined\n    at https://example.com/:1:18"
+                }
+            }
+        }))
+        .unwrap();
+
+        let error = PageError::from_exception(&params);
+        assert_eq!(error.kind, PageErrorKind::Exception);
+        assert_eq!(
+            error.to_string(),
+            "uncaught exception: TypeError: x is undefined (at https://example.com/:1:18)"
+        );
+    }
+
+    #[test]
+    fn test_check_fails_with_collected_errors() {
+        let watch = PageErrorWatch::default();
+        assert!(watch.check("https://example.com").is_ok());
+
+        watch.errors.lock().unwrap().push(PageError {
+            kind: PageErrorKind::ConsoleError,
+            message: "boom".to_string(),
+            source: None,
+        });
+        let message = watch.check("https://example.com").unwrap_err().to_string();
+        assert!(message.contains("https://example.com"), "{}", message);
+        assert!(message.contains("console error: boom"), "{}", message);
+    }
+}
@@ -0,0 +1,186 @@
+//! Print paper sizes and page-break previews.
+//!
+//! The preview lays the page out at the printable width of a sheet with
+//! print media emulated, then marks every sheet height on the full-page
+//! screenshot. It assumes Chrome's default print margins and ignores CSS
+//! `break-*` rules, so treat the guides as an estimate of the PDF.
+
+use crate::error::{Result, WebshotError};
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// CSS pixels per inch
+const CSS_PX_PER_INCH: f64 = 96.0;
+
+/// Chrome's default margin on each side of a printed page, in inches
+pub const DEFAULT_PRINT_MARGIN: f64 = 0.4;
+
+/// Color of the page-break guides
+const GUIDE_COLOR: Rgba<u8> = Rgba([230, 0, 122, 255]);
+
+/// Length of a guide dash and of the gap after it, in CSS pixels
+const GUIDE_DASH: f64 = 8.0;
+
+/// Paper sizes for PDFs and page-break previews
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PaperSize {
+    /// US Letter, 8.5 x 11 in (Chrome's default)
+    #[default]
+    Letter,
+    /// US Legal, 8.5 x 14 in
+    Legal,
+    /// Tabloid, 11 x 17 in
+    Tabloid,
+    /// ISO A3, 297 x 420 mm
+    A3,
+    /// ISO A4, 210 x 297 mm
+    A4,
+    /// ISO A5, 148 x 210 mm
+    A5,
+}
+
+impl PaperSize {
+    /// Portrait width and height in inches
+    pub fn inches(self) -> (f64, f64) {
+        const MM: f64 = 1.0 / 25.4;
+        match self {
+            PaperSize::Letter => (8.5, 11.0),
+            PaperSize::Legal => (8.5, 14.0),
+            PaperSize::Tabloid => (11.0, 17.0),
+            PaperSize::A3 => (297.0 * MM, 420.0 * MM),
+            PaperSize::A4 => (210.0 * MM, 297.0 * MM),
+            PaperSize::A5 => (148.0 * MM, 210.0 * MM),
+        }
+    }
+
+    /// Width and height of the area inside the default margins, in CSS pixels
+    pub fn printable_size(self) -> (f64, f64) {
+        let (width, height) = self.inches();
+        let margins = 2.0 * DEFAULT_PRINT_MARGIN;
+        (
+            (width - margins) * CSS_PX_PER_INCH,
+            (height - margins) * CSS_PX_PER_INCH,
+        )
+    }
+
+    /// Document offsets, in CSS pixels, where pages end in a document of
+    /// `content_height` CSS pixels
+    pub fn page_breaks(self, content_height: f64) -> Vec<f64> {
+        let (_, page_height) = self.printable_size();
+        (1..)
+            .map(|page| page as f64 * page_height)
+            .take_while(|offset| *offset < content_height)
+            .collect()
+    }
+}
+
+impl FromStr for PaperSize {
+    type Err = WebshotError;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_str() {
+            "letter" => Ok(Self::Letter),
+            "legal" => Ok(Self::Legal),
+            "tabloid" => Ok(Self::Tabloid),
+            "a3" => Ok(Self::A3),
+            "a4" => Ok(Self::A4),
+            "a5" => Ok(Self::A5),
+            _ => Err(WebshotError::config(format!(
+                "Unknown paper size: {}. Supported: letter, legal, tabloid, a3, a4, a5",
+                value
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for PaperSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            PaperSize::Letter => "Letter",
+            PaperSize::Legal => "Legal",
+            PaperSize::Tabloid => "Tabloid",
+            PaperSize::A3 => "A3",
+            PaperSize::A4 => "A4",
+            PaperSize::A5 => "A5",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Draw a dashed guide across a full-page PNG at every page break of
+/// `paper`. `scale` is the device pixel ratio of the screenshot.
+pub fn draw_page_breaks(png: &[u8], paper: PaperSize, scale: f64) -> Result<Vec<u8>> {
+    let mut img = image::load_from_memory_with_format(png, image::ImageFormat::Png)?.to_rgba8();
+    let breaks = paper.page_breaks(f64::from(img.height()) / scale);
+    for offset in &breaks {
+        draw_guide(&mut img, (offset * scale).round() as u32, scale);
+    }
+
+    let mut output = Vec::new();
+    image::DynamicImage::ImageRgba8(img).write_to(
+        &mut std::io::Cursor::new(&mut output),
+        image::ImageFormat::Png,
+    )?;
+    Ok(output)
+}
+
+/// Draw one dashed horizontal line centered on row `y`
+fn draw_guide(img: &mut RgbaImage, y: u32, scale: f64) {
+    let thickness = (2.0 * scale).round().max(1.0) as u32;
+    let dash = (GUIDE_DASH * scale).round().max(1.0) as u32;
+    let top = y.saturating_sub(thickness / 2);
+    for row in top..(top + thickness).min(img.height()) {
+        for x in (0..img.width()).filter(|x| (x / dash).is_multiple_of(2)) {
+            img.put_pixel(x, row, GUIDE_COLOR);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paper_sizes_parse_case_insensitively() {
+        assert_eq!("A4".parse::<PaperSize>().unwrap(), PaperSize::A4);
+        assert_eq!("letter".parse::<PaperSize>().unwrap(), PaperSize::Letter);
+        assert_eq!(PaperSize::Tabloid.to_string(), "Tabloid");
+        assert!("b5"
+            .parse::<PaperSize>()
+            .unwrap_err()
+            .to_string()
+            .contains("Unknown paper size"));
+    }
+
+    #[test]
+    fn test_page_breaks_follow_printable_height() {
+        let (width, height) = PaperSize::Letter.printable_size();
+        assert!((width - 739.2).abs() < 1e-9);
+        assert!((height - 979.2).abs() < 1e-9);
+
+        assert!(PaperSize::Letter.page_breaks(900.0).is_empty());
+        let breaks = PaperSize::Letter.page_breaks(2500.0);
+        assert_eq!(breaks.len(), 2);
+        assert!((breaks[1] - 2.0 * 979.2).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_guides_are_drawn_at_breaks() {
+        let (_, page_height) = PaperSize::A5.printable_size();
+        let img = RgbaImage::from_pixel(100, 2 * page_height as u32, Rgba([255; 4]));
+        let mut png = Vec::new();
+        image::DynamicImage::ImageRgba8(img)
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+
+        let marked = draw_page_breaks(&png, PaperSize::A5, 1.0).unwrap();
+        let marked = image::load_from_memory(&marked).unwrap().to_rgba8();
+        let row = page_height.round() as u32;
+        assert_eq!(*marked.get_pixel(0, row), GUIDE_COLOR);
+        assert_eq!(*marked.get_pixel(GUIDE_DASH as u32, row), Rgba([255; 4]));
+        assert_eq!(*marked.get_pixel(0, 10), Rgba([255; 4]));
+    }
+}
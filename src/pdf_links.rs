@@ -0,0 +1,468 @@
+//! Hyperlinks and internal anchors in printed PDFs.
+//!
+//! Chrome turns the links of a printed page into PDF link annotations, but
+//! omits some of them, for example links inside elements laid out with
+//! `display: contents` or anchors pointing at `<a name>` targets. After
+//! printing, the page's links are counted against the annotations in the
+//! PDF; when some are missing, the links are measured with print media
+//! emulated at the printable width and an annotation is added for every
+//! link no existing annotation covers. Like the page-break preview this
+//! assumes Chrome's default print margins and ignores CSS `break-*` rules,
+//! so repaired links can be off where the page sets its own breaks.
+//!
+//! `--no-links` goes the other way and removes every link annotation and
+//! named destination from the PDF.
+
+use crate::error::{Result, WebshotError};
+use crate::paper::{PaperSize, DEFAULT_PRINT_MARGIN};
+use lopdf::{Dictionary, Document, Object, ObjectId, StringFormat};
+use serde::{Deserialize, Serialize};
+
+/// Evaluates to a JSON array of the page's visible links as
+/// `{href, target_top, rects}`, in document coordinates. `target_top` is
+/// set for links to an anchor on the page itself; links to anchors that do
+/// not exist and `javascript:` links are left out.
+pub const LINKS_SCRIPT: &str = r##"
+(() => {
+    const base = location.href.split("#")[0];
+    const links = [];
+    for (const link of document.querySelectorAll("a[href], area[href]")) {
+        const href = link.href;
+        if (!href || /^javascript:/i.test(href)) {
+            continue;
+        }
+        const rects = Array.from(link.getClientRects())
+            .filter((rect) => rect.width > 0 && rect.height > 0)
+            .map((rect) => ({
+                x: rect.left + window.scrollX,
+                y: rect.top + window.scrollY,
+                width: rect.width,
+                height: rect.height,
+            }));
+        if (rects.length === 0) {
+            continue;
+        }
+        let targetTop = null;
+        if (href.startsWith(base + "#")) {
+            const id = decodeURIComponent(href.substring(base.length + 1));
+            const target = id === "" || id.toLowerCase() === "top"
+                ? document.documentElement
+                : document.getElementById(id) || document.getElementsByName(id)[0];
+            if (!target) {
+                continue;
+            }
+            targetTop = target.getBoundingClientRect().top + window.scrollY;
+        }
+        links.push({ href, target_top: targetTop, rects });
+    }
+    return JSON.stringify(links);
+})()
+"##;
+
+/// PDF points per CSS pixel
+const POINTS_PER_CSS_PX: f64 = 0.75;
+
+/// Box of a link in document coordinates, in CSS pixels
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LinkRect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// A link on the printed page, as measured by [`LINKS_SCRIPT`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PageLink {
+    pub href: String,
+    /// Document offset of the anchor an internal link points at
+    pub target_top: Option<f64>,
+    /// One box per line the link is laid out on
+    pub rects: Vec<LinkRect>,
+}
+
+impl PageLink {
+    /// Parse the result of [`LINKS_SCRIPT`]
+    pub fn from_script_result(json: &str) -> Result<Vec<Self>> {
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
+/// How a page laid out for print maps onto PDF pages
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PrintLayout {
+    /// Width the page is laid out at, in CSS pixels
+    pub width: f64,
+    /// Height of the content area of a PDF page, in layout CSS pixels
+    pub page_height: f64,
+    /// Print scale factor
+    pub scale: f64,
+}
+
+impl PrintLayout {
+    pub fn new(paper: PaperSize, landscape: bool, scale: f64) -> Self {
+        let (width, height) = paper.printable_size();
+        // The margins are the same on every side, so turning the sheet
+        // turns the printable area
+        let (width, height) = if landscape {
+            (height, width)
+        } else {
+            (width, height)
+        };
+        Self {
+            width: width / scale,
+            page_height: height / scale,
+            scale,
+        }
+    }
+
+    /// Layout CSS pixels to PDF points
+    fn points(&self, css_px: f64) -> f64 {
+        css_px * self.scale * POINTS_PER_CSS_PX
+    }
+}
+
+/// Number of link annotations in `document`
+pub fn link_count(document: &Document) -> usize {
+    document
+        .get_pages()
+        .into_values()
+        .map(|page_id| {
+            annotations(document, page_id)
+                .iter()
+                .filter(|annotation| is_link(document, annotation))
+                .count()
+        })
+        .sum()
+}
+
+/// Add an annotation for every box of `links` that no link annotation of
+/// its PDF page overlaps, returning the number added
+pub fn add_missing_links(
+    document: &mut Document,
+    links: &[PageLink],
+    layout: &PrintLayout,
+) -> Result<usize> {
+    let pages: Vec<ObjectId> = document.get_pages().into_values().collect();
+    let margin = DEFAULT_PRINT_MARGIN * 72.0;
+    let page_heights = pages
+        .iter()
+        .map(|&page_id| page_height(document, page_id))
+        .collect::<Result<Vec<_>>>()?;
+    // Where each page of the layout lands: page index and PDF coordinates
+    let locate = |y: f64| {
+        let page = (y / layout.page_height).floor().max(0.0) as usize;
+        let height = *page_heights.get(page)?;
+        let offset = y - page as f64 * layout.page_height;
+        Some((page, height - margin - layout.points(offset)))
+    };
+
+    let mut added = 0;
+    for link in links {
+        let action = match link.target_top {
+            Some(top) => {
+                let Some((page, y)) = locate(top) else {
+                    continue;
+                };
+                (
+                    "Dest",
+                    Object::Array(vec![
+                        Object::Reference(pages[page]),
+                        Object::Name(b"XYZ".to_vec()),
+                        Object::Null,
+                        Object::Real(y as f32),
+                        Object::Null,
+                    ]),
+                )
+            }
+            None => (
+                "A",
+                Object::Dictionary(Dictionary::from_iter([
+                    ("S", Object::Name(b"URI".to_vec())),
+                    (
+                        "URI",
+                        Object::String(link.href.as_bytes().to_vec(), StringFormat::Literal),
+                    ),
+                ])),
+            ),
+        };
+
+        for rect in &link.rects {
+            let Some((page, top)) = locate(rect.y) else {
+                continue;
+            };
+            let left = margin + layout.points(rect.x);
+            // Boxes running past the end of a page are cut at its margin
+            let bottom = (top - layout.points(rect.height)).max(margin);
+            let bounds = [left, bottom, left + layout.points(rect.width), top];
+
+            let existing = annotations(document, pages[page]);
+            let covered = existing.iter().any(|annotation| {
+                is_link(document, annotation)
+                    && annotation_rect(document, annotation)
+                        .is_some_and(|other| overlaps(bounds, other))
+            });
+            if covered {
+                continue;
+            }
+
+            let annotation = document.add_object(Dictionary::from_iter([
+                ("Type", Object::Name(b"Annot".to_vec())),
+                ("Subtype", Object::Name(b"Link".to_vec())),
+                (
+                    "Rect",
+                    Object::Array(
+                        bounds
+                            .iter()
+                            .map(|&value| Object::Real(value as f32))
+                            .collect(),
+                    ),
+                ),
+                ("Border", Object::Array(vec![0.into(), 0.into(), 0.into()])),
+                (action.0, action.1.clone()),
+            ]));
+            let mut annots = existing;
+            annots.push(Object::Reference(annotation));
+            document
+                .get_dictionary_mut(pages[page])
+                .map_err(|e| WebshotError::pdf(e.to_string()))?
+                .set("Annots", Object::Array(annots));
+            added += 1;
+        }
+    }
+    Ok(added)
+}
+
+/// Remove every link annotation and named destination from `document`,
+/// returning the number of annotations removed
+pub fn strip_links(document: &mut Document) -> Result<usize> {
+    let mut removed = 0;
+    for page_id in document.get_pages().into_values().collect::<Vec<_>>() {
+        let existing = annotations(document, page_id);
+        let kept: Vec<Object> = existing
+            .iter()
+            .filter(|annotation| !is_link(document, annotation))
+            .cloned()
+            .collect();
+        if kept.len() == existing.len() {
+            continue;
+        }
+        removed += existing.len() - kept.len();
+        let page = document
+            .get_dictionary_mut(page_id)
+            .map_err(|e| WebshotError::pdf(e.to_string()))?;
+        if kept.is_empty() {
+            page.remove(b"Annots");
+        } else {
+            page.set("Annots", Object::Array(kept));
+        }
+    }
+
+    let catalog = document
+        .catalog_mut()
+        .map_err(|e| WebshotError::pdf(e.to_string()))?;
+    catalog.remove(b"Dests");
+    if let Ok(Object::Dictionary(names)) = catalog.get_mut(b"Names") {
+        names.remove(b"Dests");
+    }
+    Ok(removed)
+}
+
+/// The entries of a page's `Annots` array, which may be stored inline or
+/// as a reference
+fn annotations(document: &Document, page_id: ObjectId) -> Vec<Object> {
+    document
+        .get_dictionary(page_id)
+        .and_then(|page| page.get(b"Annots"))
+        .and_then(|annots| document.dereference(annots))
+        .and_then(|(_, annots)| annots.as_array())
+        .cloned()
+        .unwrap_or_default()
+}
+
+fn is_link(document: &Document, annotation: &Object) -> bool {
+    document
+        .dereference(annotation)
+        .and_then(|(_, annotation)| annotation.as_dict())
+        .and_then(|annotation| annotation.get(b"Subtype"))
+        .and_then(Object::as_name)
+        .is_ok_and(|subtype| subtype == b"Link")
+}
+
+fn annotation_rect(document: &Document, annotation: &Object) -> Option<[f64; 4]> {
+    let (_, annotation) = document.dereference(annotation).ok()?;
+    let rect = annotation
+        .as_dict()
+        .ok()?
+        .get(b"Rect")
+        .ok()?
+        .as_array()
+        .ok()?;
+    let mut values = rect.iter().map(|value| value.as_float().map(f64::from));
+    let [x1, y1, x2, y2] = [
+        values.next()?.ok()?,
+        values.next()?.ok()?,
+        values.next()?.ok()?,
+        values.next()?.ok()?,
+    ];
+    Some([x1.min(x2), y1.min(y2), x1.max(x2), y1.max(y2)])
+}
+
+fn overlaps(a: [f64; 4], b: [f64; 4]) -> bool {
+    a[0] < b[2] && b[0] < a[2] && a[1] < b[3] && b[1] < a[3]
+}
+
+/// Height of a page's media box in points
+fn page_height(document: &Document, page_id: ObjectId) -> Result<f64> {
+    let page = crate::book::flattened_page(document, page_id)?;
+    let media_box = page
+        .get(b"MediaBox")
+        .and_then(Object::as_array)
+        .map_err(|_| WebshotError::pdf("PDF page has no media box"))?;
+    match media_box.as_slice() {
+        [_, y1, _, y2] => match (y1.as_float(), y2.as_float()) {
+            (Ok(y1), Ok(y2)) => Ok(f64::from((y2 - y1).abs())),
+            _ => Err(WebshotError::pdf("PDF page has an invalid media box")),
+        },
+        _ => Err(WebshotError::pdf("PDF page has an invalid media box")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::Stream;
+
+    /// Two Letter pages, the first with a link annotation in its top left
+    /// corner and a named destination in the catalog
+    fn document() -> Document {
+        let mut document = Document::with_version("1.4");
+        let pages_id = document.new_object_id();
+        let link = document.add_object(Dictionary::from_iter([
+            ("Type", Object::Name(b"Annot".to_vec())),
+            ("Subtype", Object::Name(b"Link".to_vec())),
+            (
+                "Rect",
+                Object::Array(vec![28.8.into(), 740.into(), 100.into(), 763.2.into()]),
+            ),
+        ]));
+        let kids: Vec<Object> = (0..2)
+            .map(|page| {
+                let content_id = document.add_object(Stream::new(Dictionary::new(), Vec::new()));
+                let mut page_dict = Dictionary::from_iter([
+                    ("Type", Object::Name(b"Page".to_vec())),
+                    ("Parent", Object::Reference(pages_id)),
+                    ("Contents", Object::Reference(content_id)),
+                ]);
+                if page == 0 {
+                    page_dict.set("Annots", Object::Array(vec![Object::Reference(link)]));
+                }
+                document.add_object(page_dict).into()
+            })
+            .collect();
+        document.objects.insert(
+            pages_id,
+            Object::Dictionary(Dictionary::from_iter([
+                ("Type", Object::Name(b"Pages".to_vec())),
+                ("Count", Object::Integer(2)),
+                ("Kids", Object::Array(kids)),
+                (
+                    "MediaBox",
+                    Object::Array(vec![0.into(), 0.into(), 612.into(), 792.into()]),
+                ),
+            ])),
+        );
+        let catalog_id = document.add_object(Dictionary::from_iter([
+            ("Type", Object::Name(b"Catalog".to_vec())),
+            ("Pages", Object::Reference(pages_id)),
+            ("Dests", Object::Dictionary(Dictionary::new())),
+        ]));
+        document.trailer.set("Root", catalog_id);
+        document
+    }
+
+    fn rect(x: f64, y: f64) -> LinkRect {
+        LinkRect {
+            x,
+            y,
+            width: 80.0,
+            height: 20.0,
+        }
+    }
+
+    #[test]
+    fn test_missing_links_are_added_on_their_page() {
+        let mut document = document();
+        assert_eq!(link_count(&document), 1);
+        let layout = PrintLayout::new(PaperSize::Letter, false, 1.0);
+        assert_eq!(layout.page_height, (11.0 - 0.8) * 96.0);
+
+        let links = PageLink::from_script_result(
+            &serde_json::to_string(&[
+                // Already annotated by Chrome
+                PageLink {
+                    href: "https://example.com/".to_string(),
+                    target_top: None,
+                    rects: vec![rect(10.0, 5.0)],
+                },
+                // On the second page, pointing back at an anchor on the first
+                PageLink {
+                    href: "https://example.com/#intro".to_string(),
+                    target_top: Some(400.0),
+                    rects: vec![rect(0.0, layout.page_height + 100.0)],
+                },
+                PageLink {
+                    href: "https://example.org/".to_string(),
+                    target_top: None,
+                    rects: vec![rect(200.0, 50.0)],
+                },
+            ])
+            .unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            add_missing_links(&mut document, &links, &layout).unwrap(),
+            2
+        );
+        assert_eq!(link_count(&document), 3);
+
+        let pages: Vec<ObjectId> = document.get_pages().into_values().collect();
+        let second = annotations(&document, pages[1]);
+        assert_eq!(second.len(), 1);
+        let (_, annotation) = document.dereference(&second[0]).unwrap();
+        let annotation = annotation.as_dict().unwrap();
+        // 100 CSS px (75pt) below the top margin of a 792pt page
+        let bounds = annotation_rect(&document, &second[0]).unwrap();
+        let expected = [28.8, 792.0 - 28.8 - 90.0, 28.8 + 60.0, 792.0 - 28.8 - 75.0];
+        assert!(bounds
+            .iter()
+            .zip(expected)
+            .all(|(value, expected)| (value - expected).abs() < 0.01));
+        let dest = annotation.get(b"Dest").unwrap().as_array().unwrap();
+        assert_eq!(dest[0], Object::Reference(pages[0]));
+        assert!((dest[3].as_float().unwrap() - (792.0 - 28.8 - 300.0)).abs() < 0.01);
+
+        // Links that resolve to no page are skipped
+        let past_the_end = [PageLink {
+            href: "https://example.com/".to_string(),
+            target_top: None,
+            rects: vec![rect(0.0, layout.page_height * 5.0)],
+        }];
+        assert_eq!(
+            add_missing_links(&mut document, &past_the_end, &layout).unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_strip_links_removes_annotations_and_destinations() {
+        let mut document = document();
+        assert_eq!(strip_links(&mut document).unwrap(), 1);
+        assert_eq!(link_count(&document), 0);
+        assert!(!document.catalog().unwrap().has(b"Dests"));
+        let pages: Vec<ObjectId> = document.get_pages().into_values().collect();
+        assert!(!document.get_dictionary(pages[0]).unwrap().has(b"Annots"));
+        assert_eq!(strip_links(&mut document).unwrap(), 0);
+    }
+}
@@ -0,0 +1,216 @@
+use crate::error::{Result, WebshotError};
+use serde::Deserialize;
+
+/// Defines `selectorFor(element)`, which builds a short selector matching
+/// only that element, preferring stable ids and test attributes over
+/// structural paths. Spliced into the picker and step recorder scripts.
+pub const SELECTOR_FUNCTIONS: &str = r#"
+    const TEST_ATTRIBUTES = ['data-testid', 'data-test', 'data-cy', 'data-qa', 'data-test-id'];
+
+    // Ids and classes with long digit runs or hashes are usually generated
+    // per build and would not survive a redeploy
+    const looksGenerated = (value) =>
+        /\d{3,}/.test(value) || /^(css|sc|jsx|svelte|emotion)-/.test(value) || /[A-Za-z0-9]{6,}_[A-Za-z0-9]{4,}/.test(value);
+
+    const isUnique = (selector) => {
+        try {
+            return document.querySelectorAll(selector).length === 1;
+        } catch (e) {
+            return false;
+        }
+    };
+
+    const anchor = (el) => {
+        if (el.id && !looksGenerated(el.id)) {
+            const selector = '#' + CSS.escape(el.id);
+            if (isUnique(selector)) return selector;
+        }
+        for (const attribute of TEST_ATTRIBUTES) {
+            const value = el.getAttribute(attribute);
+            if (value) {
+                const selector = `[${attribute}="${CSS.escape(value)}"]`;
+                if (isUnique(selector)) return selector;
+            }
+        }
+        return null;
+    };
+
+    const step = (el) => {
+        const tag = el.localName;
+        const classes = Array.from(el.classList)
+            .filter((name) => !looksGenerated(name))
+            .slice(0, 2)
+            .map((name) => '.' + CSS.escape(name))
+            .join('');
+        let selector = tag + classes;
+        const parent = el.parentElement;
+        if (parent) {
+            const siblings = Array.from(parent.children).filter((sibling) => sibling.matches(selector));
+            if (siblings.length > 1) {
+                const sameTag = Array.from(parent.children).filter((sibling) => sibling.localName === tag);
+                selector += `:nth-of-type(${sameTag.indexOf(el) + 1})`;
+            }
+        }
+        return selector;
+    };
+
+    const selectorFor = (el) => {
+        const parts = [];
+        for (let node = el; node && node.nodeType === 1; node = node.parentElement) {
+            const own = anchor(node);
+            if (own) {
+                parts.unshift(own);
+                break;
+            }
+            parts.unshift(step(node));
+            const candidate = parts.join(' > ');
+            if (isUnique(candidate)) return candidate;
+            if (node.localName === 'html') break;
+        }
+        return parts.join(' > ');
+    };
+"#;
+
+/// Highlights the hovered element and records the first clicked one in
+/// `window.__webshotPick`. Escape records a cancellation instead. Clicks are
+/// swallowed so picking a link or button does not navigate away.
+const PICKER_SCRIPT: &str = r#"
+(() => {
+    if (window.__webshotPickerInstalled) return;
+    window.__webshotPickerInstalled = true;
+
+    __SELECTOR_FUNCTIONS__
+
+    const overlay = document.createElement('div');
+    overlay.style.cssText = 'position:fixed;pointer-events:none;z-index:2147483647;' +
+        'background:rgba(66,133,244,0.25);outline:2px solid #4285f4;display:none';
+
+    const show = (el) => {
+        const rect = el.getBoundingClientRect();
+        Object.assign(overlay.style, {
+            display: 'block',
+            left: rect.left + 'px',
+            top: rect.top + 'px',
+            width: rect.width + 'px',
+            height: rect.height + 'px',
+        });
+        if (!overlay.isConnected) document.documentElement.appendChild(overlay);
+    };
+
+    const stop = (event) => {
+        event.preventDefault();
+        event.stopImmediatePropagation();
+    };
+
+    document.addEventListener('mouseover', (event) => {
+        if (!window.__webshotPick && event.target !== overlay) show(event.target);
+    }, true);
+    for (const type of ['mousedown', 'mouseup', 'pointerdown', 'pointerup']) {
+        document.addEventListener(type, (event) => {
+            if (!window.__webshotPick) stop(event);
+        }, true);
+    }
+    document.addEventListener('click', (event) => {
+        if (window.__webshotPick) return;
+        stop(event);
+        const el = event.target;
+        const selector = selectorFor(el);
+        window.__webshotPick = JSON.stringify({
+            selector,
+            tag: el.localName,
+            text: (el.innerText || '').trim().slice(0, 80),
+            matches: document.querySelectorAll(selector).length,
+        });
+        overlay.style.background = 'rgba(52,168,83,0.3)';
+        overlay.style.outlineColor = '#34a853';
+    }, true);
+    document.addEventListener('keydown', (event) => {
+        if (event.key === 'Escape' && !window.__webshotPick) {
+            window.__webshotPick = JSON.stringify({ cancelled: true });
+            overlay.remove();
+        }
+    }, true);
+})()
+"#;
+
+/// Picker script with the selector functions filled in
+pub fn picker_script() -> String {
+    PICKER_SCRIPT.replace("__SELECTOR_FUNCTIONS__", SELECTOR_FUNCTIONS)
+}
+
+/// Expression returning the recorded pick as a JSON string, or `null`
+pub const PICK_RESULT_EXPRESSION: &str = "window.__webshotPick || null";
+
+/// Element clicked in picker mode
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct PickedElement {
+    /// CSS selector for the element, preferring ids and test attributes
+    pub selector: String,
+    /// Lowercase tag name
+    pub tag: String,
+    /// Start of the element's visible text
+    #[serde(default)]
+    pub text: String,
+    /// Number of elements the selector matched when picked
+    pub matches: usize,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PickOutcome {
+    Cancelled {
+        #[allow(dead_code)]
+        cancelled: bool,
+    },
+    Picked(PickedElement),
+}
+
+impl PickedElement {
+    /// Parse the JSON recorded by `PICKER_SCRIPT`
+    pub fn from_json(json: &str) -> Result<Self> {
+        match serde_json::from_str(json)? {
+            PickOutcome::Picked(element) => Ok(element),
+            PickOutcome::Cancelled { .. } => {
+                Err(WebshotError::config("Element pick cancelled".to_string()))
+            }
+        }
+    }
+
+    /// Whether the selector matched only the picked element
+    pub fn is_unique(&self) -> bool {
+        self.matches == 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_picked_element_from_json() {
+        let element = PickedElement::from_json(
+            r##"{"selector":"#main > button.primary","tag":"button","text":"Sign in","matches":1}"##,
+        )
+        .unwrap();
+
+        assert_eq!(element.selector, "#main > button.primary");
+        assert_eq!(element.tag, "button");
+        assert!(element.is_unique());
+    }
+
+    #[test]
+    fn test_picker_script_includes_selector_functions() {
+        let script = picker_script();
+        assert!(script.contains("const selectorFor"));
+        assert!(!script.contains("__SELECTOR_FUNCTIONS__"));
+    }
+
+    #[test]
+    fn test_cancelled_pick_is_an_error() {
+        assert!(PickedElement::from_json(r#"{"cancelled":true}"#)
+            .unwrap_err()
+            .to_string()
+            .contains("cancelled"));
+        assert!(PickedElement::from_json("not json").is_err());
+    }
+}
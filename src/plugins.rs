@@ -0,0 +1,432 @@
+//! WebAssembly plugins for custom comparison algorithms, image filters and
+//! batch hooks.
+//!
+//! A plugin is a core WebAssembly module without imports. It exports its
+//! linear memory as `memory` and an allocator `alloc(len: i32) -> i32`, plus
+//! one or both of:
+//!
+//! - `compare(a: i32, b: i32, width: i32, height: i32) -> f64`, returning the
+//!   similarity (0.0 = completely different, 1.0 = identical) of two RGBA8
+//!   images of the same size written at `a` and `b`
+//! - `filter(pixels: i32, width: i32, height: i32)`, rewriting an RGBA8 image
+//!   in place
+//! - `before_navigation(input: i32, len: i32) -> i64` and
+//!   `after_capture(input: i32, len: i32) -> i64`, run as `hooks:` entries
+//!   (see [`crate::hooks`]) with the hook's JSON input written at `input`,
+//!   returning the address of their output in the high 32 bits and its
+//!   length in the low 32 bits (`0` for no output)
+//!
+//! Plugins run through wasmtime when webshot is built with the `wasm-plugins`
+//! feature; without it loading a plugin is an error. Modules are compiled
+//! once per process (see [`WasmPlugin::cached`]), and every call runs on a
+//! fuel budget that grows with the image size, so a plugin that loops
+//! forever fails instead of hanging the run.
+
+use crate::error::{Result, WebshotError};
+use image::RgbaImage;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+
+/// Prefix selecting a plugin comparison algorithm, e.g. `plugin:my_diff.wasm`
+pub const PLUGIN_PREFIX: &str = "plugin:";
+
+/// Fuel (roughly, WebAssembly instructions) every plugin call starts with
+pub const BASE_FUEL: u64 = 10_000_000;
+/// Fuel added per pixel of the images a call gets
+pub const FUEL_PER_PIXEL: u64 = 1_000;
+
+/// Compiled plugins by path, with the modification time they were compiled at
+type PluginCache = Mutex<HashMap<PathBuf, (Option<SystemTime>, Arc<WasmPlugin>)>>;
+
+/// A compiled plugin module
+pub struct WasmPlugin {
+    path: PathBuf,
+    #[cfg(feature = "wasm-plugins")]
+    engine: wasmtime::Engine,
+    #[cfg(feature = "wasm-plugins")]
+    module: wasmtime::Module,
+}
+
+impl std::fmt::Debug for WasmPlugin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WasmPlugin")
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+impl WasmPlugin {
+    /// The plugin at `path`, compiled on first use and again only when the
+    /// file changes, so repeated comparisons and filters reuse the module
+    pub fn cached<P: AsRef<Path>>(path: P) -> Result<Arc<Self>> {
+        static PLUGINS: OnceLock<PluginCache> = OnceLock::new();
+        let path = path.as_ref();
+        let modified = std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        let plugins = PLUGINS.get_or_init(Default::default);
+        if let Some((compiled_at, plugin)) = plugins.lock().unwrap().get(path) {
+            if *compiled_at == modified {
+                return Ok(Arc::clone(plugin));
+            }
+        }
+
+        let plugin = Arc::new(Self::load(path)?);
+        plugins
+            .lock()
+            .unwrap()
+            .insert(path.to_path_buf(), (modified, Arc::clone(&plugin)));
+        Ok(plugin)
+    }
+
+    /// Path the plugin was loaded from
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Similarity of two images of the same size as scored by the plugin's
+    /// `compare` export
+    pub fn compare(&self, image1: &RgbaImage, image2: &RgbaImage) -> Result<f64> {
+        if image1.dimensions() != image2.dimensions() {
+            return Err(WebshotError::config(format!(
+                "Image dimensions don't match: {:?} vs {:?}",
+                image1.dimensions(),
+                image2.dimensions()
+            )));
+        }
+
+        let similarity = self.call_compare(image1, image2)?;
+        if !(0.0..=1.0).contains(&similarity) {
+            return Err(self.error(format!(
+                "compare returned {}, expected a similarity between 0.0 and 1.0",
+                similarity
+            )));
+        }
+        Ok(similarity)
+    }
+
+    fn error(&self, msg: impl std::fmt::Display) -> WebshotError {
+        WebshotError::plugin(format!("{}: {}", self.path.display(), msg))
+    }
+}
+
+#[cfg(feature = "wasm-plugins")]
+impl WasmPlugin {
+    /// Compile a plugin from a `.wasm` (or `.wat`) file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let mut config = wasmtime::Config::new();
+        config.consume_fuel(true);
+        let engine = wasmtime::Engine::new(&config)
+            .map_err(|e| WebshotError::plugin(format!("Failed to set up WebAssembly: {:#}", e)))?;
+        let module = wasmtime::Module::from_file(&engine, path).map_err(|e| {
+            WebshotError::plugin(format!("Failed to load {}: {:#}", path.display(), e))
+        })?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            engine,
+            module,
+        })
+    }
+
+    /// Rewrite an image with the plugin's `filter` export
+    pub fn filter(&self, image: &mut RgbaImage) -> Result<()> {
+        let (width, height) = image.dimensions();
+        let mut instance = self.instantiate(image.len() as u64 / 4)?;
+        let pixels = instance.write(image.as_raw())?;
+        let filter = instance.func::<(i32, i32, i32), ()>("filter")?;
+        filter
+            .call(&mut instance.store, (pixels, width as i32, height as i32))
+            .map_err(|e| self.call_error("filter", e))?;
+        instance.read(pixels, image)
+    }
+
+    /// Run the plugin's `stage` export (`before_navigation` or
+    /// `after_capture`) on a hook's JSON input, returning what it printed
+    pub fn hook(&self, stage: &str, input: &[u8]) -> Result<Vec<u8>> {
+        let mut instance = self.instantiate(input.len() as u64)?;
+        let ptr = instance.write(input)?;
+        let hook = instance.func::<(i32, i32), i64>(stage)?;
+        let packed = hook
+            .call(&mut instance.store, (ptr, input.len() as i32))
+            .map_err(|e| self.call_error(stage, e))?;
+        let mut output = vec![0; packed as u32 as usize];
+        instance
+            .memory
+            .read(&instance.store, (packed >> 32) as u32 as usize, &mut output)
+            .map_err(|e| self.error(format!("reading {} output: {}", stage, e)))?;
+        Ok(output)
+    }
+
+    fn call_compare(&self, image1: &RgbaImage, image2: &RgbaImage) -> Result<f64> {
+        let (width, height) = image1.dimensions();
+        let mut instance = self.instantiate(2 * image1.len() as u64 / 4)?;
+        let first = instance.write(image1.as_raw())?;
+        let second = instance.write(image2.as_raw())?;
+        let compare = instance.func::<(i32, i32, i32, i32), f64>("compare")?;
+        compare
+            .call(
+                &mut instance.store,
+                (first, second, width as i32, height as i32),
+            )
+            .map_err(|e| self.call_error("compare", e))
+    }
+
+    fn call_error(&self, export: &str, error: wasmtime::Error) -> WebshotError {
+        if error.downcast_ref::<wasmtime::Trap>() == Some(&wasmtime::Trap::OutOfFuel) {
+            return self.error(format!(
+                "{} ran out of fuel; the plugin is too slow for this image or never returns",
+                export
+            ));
+        }
+        self.error(format!("{} failed: {:#}", export, error))
+    }
+
+    /// Fresh instance per call, so plugins cannot leak state between
+    /// images, with fuel for `pixels` pixels of input
+    fn instantiate(&self, pixels: u64) -> Result<PluginInstance<'_>> {
+        let mut store = wasmtime::Store::new(&self.engine, ());
+        store
+            .set_fuel(BASE_FUEL.saturating_add(pixels.saturating_mul(FUEL_PER_PIXEL)))
+            .map_err(|e| self.error(format!("cannot meter fuel: {:#}", e)))?;
+        let instance = wasmtime::Instance::new(&mut store, &self.module, &[])
+            .map_err(|e| self.error(format!("instantiation failed: {:#}", e)))?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| self.error("missing `memory` export"))?;
+        Ok(PluginInstance {
+            plugin: self,
+            store,
+            instance,
+            memory,
+        })
+    }
+}
+
+#[cfg(feature = "wasm-plugins")]
+struct PluginInstance<'a> {
+    plugin: &'a WasmPlugin,
+    store: wasmtime::Store<()>,
+    instance: wasmtime::Instance,
+    memory: wasmtime::Memory,
+}
+
+#[cfg(feature = "wasm-plugins")]
+impl PluginInstance<'_> {
+    fn func<Params, Results>(&mut self, name: &str) -> Result<wasmtime::TypedFunc<Params, Results>>
+    where
+        Params: wasmtime::WasmParams,
+        Results: wasmtime::WasmResults,
+    {
+        self.instance
+            .get_typed_func(&mut self.store, name)
+            .map_err(|e| self.plugin.error(format!("`{}` export: {:#}", name, e)))
+    }
+
+    /// Copy bytes into plugin memory through its `alloc` export
+    fn write(&mut self, bytes: &[u8]) -> Result<i32> {
+        let len = i32::try_from(bytes.len())
+            .map_err(|_| self.plugin.error("input too large for a 32-bit plugin"))?;
+        let alloc = self.func::<i32, i32>("alloc")?;
+        let ptr = alloc
+            .call(&mut self.store, len)
+            .map_err(|e| self.plugin.error(format!("alloc failed: {:#}", e)))?;
+        self.memory
+            .write(&mut self.store, ptr as u32 as usize, bytes)
+            .map_err(|e| self.plugin.error(format!("alloc returned {}: {}", ptr, e)))?;
+        Ok(ptr)
+    }
+
+    fn read(&mut self, ptr: i32, image: &mut RgbaImage) -> Result<()> {
+        self.memory
+            .read(&self.store, ptr as u32 as usize, image.as_mut())
+            .map_err(|e| self.plugin.error(format!("reading filtered image: {}", e)))
+    }
+}
+
+#[cfg(not(feature = "wasm-plugins"))]
+impl WasmPlugin {
+    /// Compile a plugin from a `.wasm` file. Always fails, as this build has
+    /// no WebAssembly runtime.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Err(WebshotError::plugin(format!(
+            "Cannot load {}: webshot was built without the `wasm-plugins` feature",
+            path.as_ref().display()
+        )))
+    }
+
+    /// Rewrite an image with the plugin's `filter` export
+    pub fn filter(&self, _image: &mut RgbaImage) -> Result<()> {
+        Err(self.error("built without the `wasm-plugins` feature"))
+    }
+
+    /// Run the plugin's `stage` export on a hook's JSON input
+    pub fn hook(&self, _stage: &str, _input: &[u8]) -> Result<Vec<u8>> {
+        Err(self.error("built without the `wasm-plugins` feature"))
+    }
+
+    fn call_compare(&self, _image1: &RgbaImage, _image2: &RgbaImage) -> Result<f64> {
+        Err(self.error("built without the `wasm-plugins` feature"))
+    }
+}
+
+/// Run PNG screenshot data through each filter plugin in order
+pub fn apply_image_filters(png: &[u8], filters: &[PathBuf]) -> Result<Vec<u8>> {
+    let mut image = image::load_from_memory_with_format(png, image::ImageFormat::Png)?.to_rgba8();
+    for path in filters {
+        WasmPlugin::cached(path)?.filter(&mut image)?;
+    }
+
+    let mut output = Vec::new();
+    image::DynamicImage::ImageRgba8(image)
+        .write_with_encoder(image::codecs::png::PngEncoder::new(&mut output))?;
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(not(feature = "wasm-plugins"))]
+    #[test]
+    fn test_plugins_require_feature() {
+        let error = WasmPlugin::load("my_diff.wasm").unwrap_err().to_string();
+        assert!(error.contains("without the `wasm-plugins` feature"));
+    }
+
+    #[cfg(feature = "wasm-plugins")]
+    mod wasm {
+        use super::*;
+        use tempfile::TempDir;
+
+        /// Bump allocator shared by the test plugins
+        const ALLOC: &str = r#"
+            (memory (export "memory") 1)
+            (global $next (mut i32) (i32.const 0))
+            (func (export "alloc") (param $len i32) (result i32)
+                (local $ptr i32)
+                (local.set $ptr (global.get $next))
+                (global.set $next (i32.add (local.get $ptr) (local.get $len)))
+                (drop (memory.grow (i32.add (i32.shr_u (local.get $len) (i32.const 16)) (i32.const 1))))
+                (local.get $ptr))
+        "#;
+
+        fn plugin(dir: &TempDir, body: &str) -> WasmPlugin {
+            let path = dir.path().join("plugin.wat");
+            std::fs::write(&path, format!("(module {} {})", ALLOC, body)).unwrap();
+            WasmPlugin::load(&path).unwrap()
+        }
+
+        #[test]
+        fn test_compare_plugin_scores_images() {
+            let dir = TempDir::new().unwrap();
+            // Fraction of bytes that are equal
+            let plugin = plugin(
+                &dir,
+                r#"(func (export "compare") (param $a i32) (param $b i32) (param $w i32) (param $h i32) (result f64)
+                    (local $i i32) (local $len i32) (local $same i32)
+                    (local.set $len (i32.mul (i32.mul (local.get $w) (local.get $h)) (i32.const 4)))
+                    (block $done (loop $next
+                        (br_if $done (i32.ge_u (local.get $i) (local.get $len)))
+                        (if (i32.eq (i32.load8_u (i32.add (local.get $a) (local.get $i)))
+                                    (i32.load8_u (i32.add (local.get $b) (local.get $i))))
+                            (then (local.set $same (i32.add (local.get $same) (i32.const 1)))))
+                        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                        (br $next)))
+                    (f64.div (f64.convert_i32_u (local.get $same)) (f64.convert_i32_u (local.get $len))))"#,
+            );
+
+            let red = RgbaImage::from_pixel(4, 4, image::Rgba([255, 0, 0, 255]));
+            let blue = RgbaImage::from_pixel(4, 4, image::Rgba([0, 0, 255, 255]));
+            assert_eq!(plugin.compare(&red, &red).unwrap(), 1.0);
+            assert_eq!(plugin.compare(&red, &blue).unwrap(), 0.5);
+            assert!(plugin
+                .compare(&red, &RgbaImage::new(2, 2))
+                .unwrap_err()
+                .to_string()
+                .contains("dimensions don't match"));
+        }
+
+        #[test]
+        fn test_filter_plugin_rewrites_pixels() {
+            let dir = TempDir::new().unwrap();
+            // Zero the red channel of every pixel
+            let plugin = plugin(
+                &dir,
+                r#"(func (export "filter") (param $p i32) (param $w i32) (param $h i32)
+                    (local $end i32)
+                    (local.set $end (i32.add (local.get $p) (i32.mul (i32.mul (local.get $w) (local.get $h)) (i32.const 4))))
+                    (block $done (loop $next
+                        (br_if $done (i32.ge_u (local.get $p) (local.get $end)))
+                        (i32.store8 (local.get $p) (i32.const 0))
+                        (local.set $p (i32.add (local.get $p) (i32.const 4)))
+                        (br $next))))"#,
+            );
+
+            let mut image = RgbaImage::from_pixel(3, 2, image::Rgba([200, 100, 50, 255]));
+            plugin.filter(&mut image).unwrap();
+            assert!(image.pixels().all(|p| p.0 == [0, 100, 50, 255]));
+        }
+
+        #[test]
+        fn test_plugin_errors_name_the_problem() {
+            let dir = TempDir::new().unwrap();
+            let out_of_range = plugin(
+                &dir,
+                r#"(func (export "compare") (param i32 i32 i32 i32) (result f64) (f64.const 2))"#,
+            );
+            let image = RgbaImage::new(1, 1);
+            assert!(out_of_range
+                .compare(&image, &image)
+                .unwrap_err()
+                .to_string()
+                .contains("expected a similarity between 0.0 and 1.0"));
+            assert!(out_of_range
+                .filter(&mut image.clone())
+                .unwrap_err()
+                .to_string()
+                .contains("`filter` export"));
+
+            let endless = plugin(
+                &dir,
+                r#"(func (export "compare") (param i32 i32 i32 i32) (result f64)
+                    (loop $forever (br $forever))
+                    (f64.const 1))"#,
+            );
+            assert!(endless
+                .compare(&image, &image)
+                .unwrap_err()
+                .to_string()
+                .contains("compare ran out of fuel"));
+        }
+
+        #[test]
+        fn test_hook_plugin_returns_its_output() {
+            let dir = TempDir::new().unwrap();
+            // Echo the input back
+            let plugin = plugin(
+                &dir,
+                r#"(func (export "before_navigation") (param $p i32) (param $len i32) (result i64)
+                    (i64.or (i64.shl (i64.extend_i32_u (local.get $p)) (i64.const 32))
+                            (i64.extend_i32_u (local.get $len))))
+                (func (export "after_capture") (param i32 i32) (result i64) (i64.const 0))"#,
+            );
+
+            let output = plugin
+                .hook("before_navigation", br#"{"width": 640}"#)
+                .unwrap();
+            assert_eq!(output, br#"{"width": 640}"#);
+            assert!(plugin.hook("after_capture", b"{}").unwrap().is_empty());
+        }
+
+        #[test]
+        fn test_cached_plugins_compile_once() {
+            let dir = TempDir::new().unwrap();
+            let path = plugin(&dir, "").path().to_path_buf();
+            let first = WasmPlugin::cached(&path).unwrap();
+            assert!(Arc::ptr_eq(&first, &WasmPlugin::cached(&path).unwrap()));
+        }
+    }
+}
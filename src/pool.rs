@@ -0,0 +1,439 @@
+//! A pool of browsers shared by concurrent captures.
+//!
+//! Services embedding webshot check a browser out of a [`BrowserPool`] for
+//! each request instead of launching Chrome every time. Browsers are
+//! checked back in when the [`PooledBrowser`] guard drops: their tabs are
+//! closed, and browsers that stopped responding or reached
+//! [`PoolOptions::max_uses`] are replaced with fresh ones on a later
+//! checkout.
+
+use crate::browser::Browser;
+use crate::error::{Result, WebshotError};
+use futures::future::BoxFuture;
+use serde::Serialize;
+use std::ops::Deref;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::{debug, warn};
+
+/// Pool sizing and browser launch settings
+#[derive(Debug, Clone)]
+pub struct PoolOptions {
+    /// Most browsers checked out at once
+    pub size: usize,
+    /// Relaunch a browser after this many checkouts (default: 100)
+    pub max_uses: Option<u32>,
+    /// How long `checkout` waits for a free browser
+    pub checkout_timeout: Duration,
+    /// Chrome executable for launched browsers
+    pub chrome_path: Option<PathBuf>,
+    /// Extra Chrome flags for launched browsers
+    pub chrome_flags: Vec<String>,
+    /// Whether launched browsers run page JavaScript
+    pub javascript_enabled: bool,
+}
+
+impl Default for PoolOptions {
+    fn default() -> Self {
+        Self {
+            size: 4,
+            max_uses: Some(100),
+            checkout_timeout: Duration::from_secs(30),
+            chrome_path: None,
+            chrome_flags: Vec::new(),
+            javascript_enabled: true,
+        }
+    }
+}
+
+impl PoolOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allow up to `size` browsers checked out at once
+    pub fn size(mut self, size: usize) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Relaunch browsers after `uses` checkouts, bounding memory growth
+    pub fn max_uses(mut self, uses: u32) -> Self {
+        self.max_uses = Some(uses);
+        self
+    }
+
+    /// Keep browsers for as long as they respond
+    pub fn unlimited_uses(mut self) -> Self {
+        self.max_uses = None;
+        self
+    }
+
+    /// Wait at most `timeout` for a free browser
+    pub fn checkout_timeout(mut self, timeout: Duration) -> Self {
+        self.checkout_timeout = timeout;
+        self
+    }
+
+    /// Launch this Chrome executable
+    pub fn chrome_path<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.chrome_path = Some(path.into());
+        self
+    }
+
+    /// Add a Chrome flag for launched browsers
+    pub fn chrome_flag<S: Into<String>>(mut self, flag: S) -> Self {
+        self.chrome_flags.push(flag.into());
+        self
+    }
+
+    /// Launch browsers with JavaScript disabled
+    pub fn disable_javascript(mut self) -> Self {
+        self.javascript_enabled = false;
+        self
+    }
+}
+
+/// What the pool needs from the browsers it holds
+pub trait Poolable: Send + Sync + 'static {
+    /// Whether the browser still responds
+    fn is_alive(&self) -> bool;
+
+    /// Prepare the browser for its next user
+    fn recycle(&self) -> Result<()>;
+}
+
+impl Poolable for Browser {
+    fn is_alive(&self) -> bool {
+        Browser::is_alive(self)
+    }
+
+    fn recycle(&self) -> Result<()> {
+        let closed = self.close_tabs()?;
+        debug!("Closed {} tabs of pooled browser", closed);
+        Ok(())
+    }
+}
+
+type Launcher<B> = Box<dyn Fn() -> BoxFuture<'static, Result<B>> + Send + Sync>;
+
+/// An idle browser and how often it has been checked out
+struct Idle<B> {
+    browser: B,
+    uses: u32,
+}
+
+/// Checked-out and idle browser counts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct PoolStatus {
+    pub size: usize,
+    pub checked_out: usize,
+    pub idle: usize,
+}
+
+/// Browsers shared by concurrent captures, launched on demand up to
+/// [`PoolOptions::size`]
+pub struct BrowserPool<B: Poolable = Browser> {
+    options: PoolOptions,
+    launcher: Launcher<B>,
+    idle: Mutex<Vec<Idle<B>>>,
+    permits: Arc<Semaphore>,
+    /// Whether the latest browser launch failed
+    launch_failed: AtomicBool,
+}
+
+impl BrowserPool<Browser> {
+    /// Pool launching headless Chrome with `options`
+    pub fn new(options: PoolOptions) -> Self {
+        let launch = options.clone();
+        Self::with_launcher(options, move || {
+            let launch = launch.clone();
+            Box::pin(async move {
+                Browser::new(
+                    launch.chrome_path,
+                    launch.chrome_flags,
+                    launch.javascript_enabled,
+                )
+                .await
+            })
+        })
+    }
+}
+
+impl<B: Poolable> BrowserPool<B> {
+    /// Pool creating its browsers with `launcher`, for browsers set up
+    /// differently, such as ones attached with [`Browser::connect`]
+    pub fn with_launcher<F>(options: PoolOptions, launcher: F) -> Self
+    where
+        F: Fn() -> BoxFuture<'static, Result<B>> + Send + Sync + 'static,
+    {
+        let size = options.size.max(1);
+        Self {
+            options: PoolOptions { size, ..options },
+            launcher: Box::new(launcher),
+            idle: Mutex::new(Vec::new()),
+            permits: Arc::new(Semaphore::new(size)),
+            launch_failed: AtomicBool::new(false),
+        }
+    }
+
+    /// Take a browser, waiting up to the checkout timeout while all are in
+    /// use. Idle browsers that stopped responding are replaced.
+    pub async fn checkout(&self) -> Result<PooledBrowser<'_, B>> {
+        let permit = tokio::time::timeout(
+            self.options.checkout_timeout,
+            Arc::clone(&self.permits).acquire_owned(),
+        )
+        .await
+        .map_err(|_| {
+            WebshotError::timeout(format!(
+                "a free browser in the pool of {}",
+                self.options.size
+            ))
+        })?
+        .map_err(|_| WebshotError::config("Browser pool is closed"))?;
+
+        loop {
+            let idle = self.idle.lock().unwrap().pop();
+            match idle {
+                Some(idle) if idle.browser.is_alive() => {
+                    return Ok(PooledBrowser::new(self, idle, permit));
+                }
+                Some(_) => warn!("Replacing a pooled browser that stopped responding"),
+                None => break,
+            }
+        }
+
+        debug!("Launching a browser for the pool");
+        let browser = self.launch().await?;
+        Ok(PooledBrowser::new(self, Idle { browser, uses: 0 }, permit))
+    }
+
+    /// Launch browsers until `count` are idle, so the first requests do
+    /// not wait for Chrome to start
+    pub async fn warm_up(&self, count: usize) -> Result<()> {
+        let count = count.min(self.options.size);
+        while self.idle.lock().unwrap().len() < count {
+            let browser = self.launch().await?;
+            self.idle.lock().unwrap().push(Idle { browser, uses: 0 });
+        }
+        Ok(())
+    }
+
+    /// Whether the pool has a responding browser, for liveness probes. One
+    /// idle browser is asked (off the async runtime, since that is a
+    /// DevTools round trip) and dropped if it stopped responding; browsers
+    /// in use count as alive. Nothing is launched: with no browser running,
+    /// the pool is alive unless its latest launch failed.
+    pub async fn check_alive(&self) -> bool {
+        // Every browser is in use, so they are running
+        let Ok(_permit) = self.permits.try_acquire() else {
+            return true;
+        };
+        loop {
+            let Some(idle) = self.idle.lock().unwrap().pop() else {
+                return !self.launch_failed.load(Ordering::SeqCst);
+            };
+            let checked = tokio::task::spawn_blocking(move || {
+                let alive = idle.browser.is_alive();
+                (idle, alive)
+            })
+            .await;
+            match checked {
+                Ok((idle, true)) => {
+                    self.idle.lock().unwrap().push(idle);
+                    return true;
+                }
+                Ok((_, false)) => warn!("Dropping a pooled browser that stopped responding"),
+                Err(e) => {
+                    warn!("Cannot check a pooled browser: {}", e);
+                    return false;
+                }
+            }
+        }
+    }
+
+    async fn launch(&self) -> Result<B> {
+        let launched = (self.launcher)().await;
+        self.launch_failed
+            .store(launched.is_err(), Ordering::SeqCst);
+        launched
+    }
+
+    pub fn status(&self) -> PoolStatus {
+        PoolStatus {
+            size: self.options.size,
+            checked_out: self.options.size - self.permits.available_permits(),
+            idle: self.idle.lock().unwrap().len(),
+        }
+    }
+
+    /// Return a browser after use, keeping it for the next checkout if it
+    /// recycles cleanly and has uses left
+    fn checkin(&self, mut idle: Idle<B>) {
+        idle.uses += 1;
+        if self
+            .options
+            .max_uses
+            .is_some_and(|max_uses| idle.uses >= max_uses)
+        {
+            debug!("Retiring pooled browser after {} uses", idle.uses);
+            return;
+        }
+        if let Err(e) = idle.browser.recycle() {
+            warn!("Dropping pooled browser that failed to recycle: {}", e);
+            return;
+        }
+        self.idle.lock().unwrap().push(idle);
+    }
+}
+
+/// A browser checked out of a [`BrowserPool`], checked back in on drop
+pub struct PooledBrowser<'a, B: Poolable = Browser> {
+    pool: &'a BrowserPool<B>,
+    idle: Option<Idle<B>>,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl<'a, B: Poolable> PooledBrowser<'a, B> {
+    fn new(pool: &'a BrowserPool<B>, idle: Idle<B>, permit: OwnedSemaphorePermit) -> Self {
+        Self {
+            pool,
+            idle: Some(idle),
+            _permit: permit,
+        }
+    }
+
+    /// Drop the browser instead of returning it to the pool, after an
+    /// error that may have left it in a bad state
+    pub fn discard(mut self) {
+        self.idle = None;
+    }
+}
+
+impl<B: Poolable> Deref for PooledBrowser<'_, B> {
+    type Target = B;
+
+    fn deref(&self) -> &B {
+        &self
+            .idle
+            .as_ref()
+            .expect("pooled browser is present")
+            .browser
+    }
+}
+
+impl<B: Poolable> Drop for PooledBrowser<'_, B> {
+    fn drop(&mut self) {
+        if let Some(idle) = self.idle.take() {
+            self.pool.checkin(idle);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    struct FakeBrowser {
+        id: usize,
+        alive: Arc<AtomicBool>,
+    }
+
+    impl Poolable for FakeBrowser {
+        fn is_alive(&self) -> bool {
+            self.alive.load(Ordering::SeqCst)
+        }
+
+        fn recycle(&self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn pool(options: PoolOptions) -> (BrowserPool<FakeBrowser>, Arc<AtomicBool>) {
+        let launched = Arc::new(AtomicUsize::new(0));
+        let alive = Arc::new(AtomicBool::new(true));
+        let launch_alive = Arc::clone(&alive);
+        let pool = BrowserPool::with_launcher(options, move || {
+            let id = launched.fetch_add(1, Ordering::SeqCst);
+            let alive = Arc::clone(&launch_alive);
+            Box::pin(async move { Ok(FakeBrowser { id, alive }) })
+        });
+        (pool, alive)
+    }
+
+    #[tokio::test]
+    async fn test_browsers_are_reused_after_checkin() {
+        let (pool, _) = pool(PoolOptions::new().size(2));
+        let first = pool.checkout().await.unwrap();
+        let second = pool.checkout().await.unwrap();
+        assert_eq!((first.id, second.id), (0, 1));
+        assert_eq!(
+            pool.status(),
+            PoolStatus {
+                size: 2,
+                checked_out: 2,
+                idle: 0
+            }
+        );
+
+        drop(first);
+        assert_eq!(pool.checkout().await.unwrap().id, 0);
+        drop(second);
+        assert_eq!(pool.status().idle, 2);
+    }
+
+    #[tokio::test]
+    async fn test_checkout_times_out_when_exhausted() {
+        let (pool, _) = pool(
+            PoolOptions::new()
+                .size(1)
+                .checkout_timeout(Duration::from_millis(20)),
+        );
+        let _held = pool.checkout().await.unwrap();
+        let error = pool.checkout().await.err().unwrap();
+        assert!(error.to_string().contains("pool of 1"));
+    }
+
+    #[tokio::test]
+    async fn test_worn_out_and_dead_browsers_are_replaced() {
+        let (pool, alive) = pool(PoolOptions::new().size(1).max_uses(2));
+        drop(pool.checkout().await.unwrap());
+        drop(pool.checkout().await.unwrap());
+        // Retired after two uses
+        assert_eq!(pool.checkout().await.unwrap().id, 1);
+
+        alive.store(false, Ordering::SeqCst);
+        let replacement = pool.checkout().await.unwrap();
+        assert_eq!(replacement.id, 2);
+        replacement.discard();
+        assert_eq!(pool.status().idle, 0);
+    }
+
+    #[tokio::test]
+    async fn test_check_alive_drops_dead_idle_browsers_without_launching() {
+        let (pool, alive) = pool(PoolOptions::new().size(2));
+        assert!(pool.check_alive().await);
+        assert_eq!(pool.status().idle, 0);
+
+        pool.warm_up(1).await.unwrap();
+        assert!(pool.check_alive().await);
+        assert_eq!(pool.status().idle, 1);
+
+        alive.store(false, Ordering::SeqCst);
+        assert!(pool.check_alive().await);
+        assert_eq!(pool.status().idle, 0);
+
+        let failing: BrowserPool<FakeBrowser> =
+            BrowserPool::with_launcher(PoolOptions::new(), || {
+                Box::pin(async { Err(WebshotError::config("no Chrome")) })
+            });
+        assert!(failing.check_alive().await);
+        assert!(failing.checkout().await.is_err());
+        assert!(!failing.check_alive().await);
+    }
+}
@@ -0,0 +1,183 @@
+//! Machine-readable `--porcelain` output.
+//!
+//! In porcelain mode the CLI prints exactly one [`Record`] line on stdout
+//! per operation (a capture, a batch job, a comparison) and nothing else;
+//! logs and prose go to stderr. Records always have the same six fields
+//! in the same order, and new fields are only ever appended:
+//!
+//! 1. `operation` - `screenshot`, `pdf`, `text`, `compare`, ...
+//! 2. `status` - `ok`, `failed` or `skipped`; `compare` reports `similar`
+//!    or `different`
+//! 3. `target` - the URL or input the operation worked on
+//! 4. `output` - the file written, empty when there is none
+//! 5. `detail` - the error message, extracted text, similarity, ...
+//! 6. `http_status` - the HTTP status of the captured page's main
+//!    document, empty (`null` in JSON) when there is none
+//!
+//! TSV records separate fields with tabs and escape backslashes, tabs,
+//! carriage returns and newlines in values as `\\`, `\t`, `\r` and `\n`.
+//! JSON records are one compact object per line with the same keys.
+
+use crate::error::{Result, WebshotError};
+use serde::Serialize;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// How porcelain records are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PorcelainFormat {
+    #[default]
+    Tsv,
+    Json,
+}
+
+impl fmt::Display for PorcelainFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PorcelainFormat::Tsv => "tsv",
+            PorcelainFormat::Json => "json",
+        })
+    }
+}
+
+impl FromStr for PorcelainFormat {
+    type Err = WebshotError;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_str() {
+            "tsv" => Ok(Self::Tsv),
+            "json" => Ok(Self::Json),
+            _ => Err(WebshotError::config(format!(
+                "Unknown porcelain format: {}. Supported: tsv, json",
+                value
+            ))),
+        }
+    }
+}
+
+/// The outcome of one operation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Record {
+    pub operation: String,
+    pub status: String,
+    pub target: String,
+    pub output: String,
+    pub detail: String,
+    pub http_status: Option<u16>,
+}
+
+impl Record {
+    pub fn new(operation: impl Into<String>, status: impl Into<String>) -> Self {
+        Self {
+            operation: operation.into(),
+            status: status.into(),
+            target: String::new(),
+            output: String::new(),
+            detail: String::new(),
+            http_status: None,
+        }
+    }
+
+    /// A successful operation
+    pub fn ok(operation: impl Into<String>) -> Self {
+        Self::new(operation, "ok")
+    }
+
+    /// A failed operation, with the error as detail
+    pub fn failed(operation: impl Into<String>, error: impl fmt::Display) -> Self {
+        Self::new(operation, "failed").detail(error)
+    }
+
+    pub fn target(mut self, target: impl fmt::Display) -> Self {
+        self.target = target.to_string();
+        self
+    }
+
+    pub fn output(mut self, output: &Path) -> Self {
+        self.output = output.display().to_string();
+        self
+    }
+
+    pub fn detail(mut self, detail: impl fmt::Display) -> Self {
+        self.detail = detail.to_string();
+        self
+    }
+
+    pub fn http_status(mut self, status: Option<u16>) -> Self {
+        self.http_status = status;
+        self
+    }
+
+    /// The record as a single line, without the trailing newline
+    pub fn line(&self, format: PorcelainFormat) -> String {
+        match format {
+            PorcelainFormat::Tsv => [
+                &self.operation,
+                &self.status,
+                &self.target,
+                &self.output,
+                &self.detail,
+                &self
+                    .http_status
+                    .map(|status| status.to_string())
+                    .unwrap_or_default(),
+            ]
+            .map(|field| escape_tsv(field))
+            .join("\t"),
+            PorcelainFormat::Json => {
+                serde_json::to_string(self).expect("records serialize to JSON")
+            }
+        }
+    }
+}
+
+fn escape_tsv(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_records_keep_field_order() {
+        let record = Record::ok("screenshot")
+            .target("https://example.com")
+            .output(Path::new("out/home.png"))
+            .http_status(Some(200));
+        assert_eq!(
+            record.line(PorcelainFormat::Tsv),
+            "screenshot\tok\thttps://example.com\tout/home.png\t\t200"
+        );
+        assert_eq!(
+            record.line(PorcelainFormat::Json),
+            r#"{"operation":"screenshot","status":"ok","target":"https://example.com","output":"out/home.png","detail":"","http_status":200}"#
+        );
+    }
+
+    #[test]
+    fn test_tsv_values_stay_on_one_line() {
+        let record = Record::failed("text", "line one\nline\ttwo \\ end").target("https://a.test");
+        assert_eq!(
+            record.line(PorcelainFormat::Tsv),
+            "text\tfailed\thttps://a.test\t\tline one\\nline\\ttwo \\\\ end\t"
+        );
+        assert!(!record.line(PorcelainFormat::Json).contains('\n'));
+        assert_eq!(
+            "JSON".parse::<PorcelainFormat>().unwrap(),
+            PorcelainFormat::Json
+        );
+        assert!("csv".parse::<PorcelainFormat>().is_err());
+    }
+}
@@ -0,0 +1,135 @@
+//! Temporary Chrome profiles, one per launched browser.
+//!
+//! Chrome locks its user data directory, so a second instance started with
+//! the directory of a running one fails to launch or hands its pages to the
+//! first. Every Chrome webshot launches — the main browser, the one for each
+//! proxy and every browser of a server pool — gets a fresh [`TempProfile`]
+//! under `<temp dir>/webshot-profiles`, named after the webshot process, a
+//! per-process counter and the launch time so that no two instances, in
+//! one process or several, ever share one.
+//!
+//! A profile is removed when its browser is dropped. With `--keep-profiles`
+//! it is left on disk and its path is logged, for looking at the cache,
+//! cookies or crash dumps of a capture afterwards.
+
+use crate::error::Result;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{debug, info};
+
+/// Directory under the system temp dir that profiles are created in
+pub const PROFILE_ROOT: &str = "webshot-profiles";
+
+/// Profiles created by this process so far, for naming the next one
+static CREATED: AtomicU64 = AtomicU64::new(0);
+
+/// A Chrome user data directory owned by one browser instance
+#[derive(Debug)]
+pub struct TempProfile {
+    path: PathBuf,
+    keep: bool,
+}
+
+impl TempProfile {
+    /// Create a profile under `<temp dir>/webshot-profiles`
+    pub fn create() -> Result<Self> {
+        Self::create_in(&std::env::temp_dir().join(PROFILE_ROOT))
+    }
+
+    /// Create a profile in `root`, which is created when missing
+    pub fn create_in(root: &Path) -> Result<Self> {
+        std::fs::create_dir_all(root)?;
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.subsec_nanos())
+            .unwrap_or_default();
+        loop {
+            let name = format!(
+                "profile-{}-{}-{:08x}",
+                std::process::id(),
+                CREATED.fetch_add(1, Ordering::Relaxed),
+                nanos
+            );
+            let path = root.join(name);
+            // Creating the directory itself claims the name, so a clash with
+            // a leftover of an earlier process with the same id moves on to
+            // the next one
+            match std::fs::create_dir(&path) {
+                Ok(()) => {
+                    debug!("Created browser profile {}", path.display());
+                    return Ok(Self { path, keep: false });
+                }
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Leave the profile on disk when it is dropped
+    pub fn keep(&mut self) {
+        self.keep = true;
+    }
+}
+
+impl Drop for TempProfile {
+    fn drop(&mut self) {
+        if self.keep {
+            info!("Kept browser profile {}", self.path.display());
+            return;
+        }
+        if let Err(e) = std::fs::remove_dir_all(&self.path) {
+            debug!(
+                "Cannot remove browser profile {}: {}",
+                self.path.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Whether Chrome flags already choose a user data directory, in which case
+/// webshot does not create a profile of its own
+pub fn sets_user_data_dir(flags: &[String]) -> bool {
+    flags.iter().any(|flag| flag.starts_with("--user-data-dir"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_profiles_are_unique_and_removed() {
+        let root = TempDir::new().unwrap();
+        let first = TempProfile::create_in(root.path()).unwrap();
+        let second = TempProfile::create_in(root.path()).unwrap();
+        assert_ne!(first.path(), second.path());
+        assert!(first.path().is_dir() && second.path().is_dir());
+
+        let removed = first.path().to_path_buf();
+        std::fs::write(removed.join("Local State"), "{}").unwrap();
+        drop(first);
+        assert!(!removed.exists());
+
+        let mut kept = second;
+        kept.keep();
+        let kept_path = kept.path().to_path_buf();
+        drop(kept);
+        assert!(kept_path.is_dir());
+    }
+
+    #[test]
+    fn test_user_data_dir_flag() {
+        assert!(sets_user_data_dir(&[
+            "--lang=en".to_string(),
+            "--user-data-dir=/tmp/chrome".to_string()
+        ]));
+        assert!(!sets_user_data_dir(&["--lang=en".to_string()]));
+    }
+}
@@ -0,0 +1,295 @@
+//! Project configuration discovery for `webshot run` in monorepos.
+//!
+//! Every directory holding a `webshot.yaml` with `screenshots` is a project.
+//! A project's configuration is merged from every `webshot.yaml` between the
+//! filesystem root and its directory, nearest last, so a repository-wide
+//! file can set `defaults` and `hooks` once. A file with `root: true` stops
+//! the search for files further up. `screenshots` are never inherited.
+
+use crate::config::Config;
+use crate::error::{Result, WebshotError};
+use serde_yaml::{Mapping, Value};
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+/// Project configuration file name
+pub const PROJECT_FILE: &str = "webshot.yaml";
+
+/// Directories never searched for project files
+const SKIPPED_DIRS: &[&str] = &["node_modules", "target"];
+
+/// A directory with its own captures
+#[derive(Debug, Clone)]
+pub struct Project {
+    /// Directory holding the project file; relative outputs are placed here
+    pub dir: PathBuf,
+    /// Configuration merged from the project file and the files above it
+    pub config: Config,
+    /// Project files merged into `config`, outermost first
+    pub sources: Vec<PathBuf>,
+}
+
+impl Project {
+    /// Whether a change to `path` should re-run this project: a file merged
+    /// into its configuration, or a file in its directory that no nested
+    /// project claims
+    pub fn is_affected_by(&self, path: &Path, projects: &[Project]) -> bool {
+        if self.sources.iter().any(|source| source == path) {
+            return true;
+        }
+        let owner = projects
+            .iter()
+            .filter(|project| path.starts_with(&project.dir))
+            .max_by_key(|project| project.dir.components().count());
+        owner.is_some_and(|owner| owner.dir == self.dir)
+    }
+}
+
+/// Find the projects in `start` and the directories below it, with their
+/// configurations merged from the project files in the directories above
+pub fn discover(start: &Path) -> Result<Vec<Project>> {
+    let start = start.canonicalize()?;
+
+    let mut inherited = Vec::new();
+    for dir in start.ancestors().skip(1) {
+        let file = dir.join(PROJECT_FILE);
+        if file.is_file() {
+            let value = load(&file)?;
+            let is_root = is_root(&value);
+            inherited.push((file, value));
+            if is_root {
+                break;
+            }
+        }
+    }
+    inherited.reverse();
+
+    let mut projects = Vec::new();
+    walk(&start, &mut inherited, &mut projects)?;
+    Ok(projects)
+}
+
+/// Only the projects affected by any of the `changed` files
+pub fn affected<'a>(projects: &'a [Project], changed: &[PathBuf]) -> Vec<&'a Project> {
+    projects
+        .iter()
+        .filter(|project| {
+            changed
+                .iter()
+                .any(|path| project.is_affected_by(path, projects))
+        })
+        .collect()
+}
+
+fn walk(
+    dir: &Path,
+    inherited: &mut Vec<(PathBuf, Value)>,
+    projects: &mut Vec<Project>,
+) -> Result<()> {
+    let file = dir.join(PROJECT_FILE);
+    let pushed = file.is_file();
+    if pushed {
+        let value = load(&file)?;
+        if is_root(&value) {
+            inherited.clear();
+        }
+        inherited.push((file, value));
+
+        if let Some(project) = project(dir, inherited)? {
+            debug!("Found project {}", project.dir.display());
+            projects.push(project);
+        }
+    }
+
+    let mut children: Vec<_> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_dir()))
+        .map(|entry| entry.path())
+        .filter(|path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            !name.starts_with('.') && !SKIPPED_DIRS.contains(&name.as_ref())
+        })
+        .collect();
+    children.sort();
+    for child in children {
+        walk(&child, inherited, projects)?;
+    }
+
+    if pushed {
+        inherited.pop();
+    }
+    Ok(())
+}
+
+/// The project in `dir`, if its own file (the last of `files`) has captures
+fn project(dir: &Path, files: &[(PathBuf, Value)]) -> Result<Option<Project>> {
+    let Some((file, own)) = files.last() else {
+        return Ok(None);
+    };
+    let has_screenshots = own
+        .get("screenshots")
+        .and_then(Value::as_sequence)
+        .is_some_and(|screenshots| !screenshots.is_empty());
+    if !has_screenshots {
+        return Ok(None);
+    }
+
+    let mut merged = Value::Mapping(Mapping::new());
+    for (index, (_, value)) in files.iter().enumerate() {
+        let mut value = value.clone();
+        if let Value::Mapping(mapping) = &mut value {
+            mapping.remove("root");
+            if index + 1 < files.len() {
+                mapping.remove("screenshots");
+            }
+        }
+        merge(&mut merged, value);
+    }
+
+    let config = Config::from_yaml(merged)
+        .map_err(|e| WebshotError::config(format!("{}: {}", file.display(), e)))?;
+    Ok(Some(Project {
+        dir: dir.to_path_buf(),
+        config,
+        sources: files.iter().map(|(file, _)| file.clone()).collect(),
+    }))
+}
+
+/// Merge `overlay` into `base`: mappings key by key, anything else replaced
+fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Mapping(base), Value::Mapping(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+fn load(file: &Path) -> Result<Value> {
+    let content = std::fs::read_to_string(file)?;
+    let value: Value = serde_yaml::from_str(&content)
+        .map_err(|e| WebshotError::config(format!("{}: {}", file.display(), e)))?;
+    match value {
+        Value::Mapping(_) => Ok(value),
+        Value::Null => Ok(Value::Mapping(Mapping::new())),
+        _ => Err(WebshotError::config(format!(
+            "{}: expected a mapping of configuration keys",
+            file.display()
+        ))),
+    }
+}
+
+fn is_root(value: &Value) -> bool {
+    value.get("root").and_then(Value::as_bool).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write(dir: &Path, relative: &str, content: &str) -> PathBuf {
+        let path = dir.join(relative);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn monorepo() -> TempDir {
+        let repo = TempDir::new().unwrap();
+        let root = repo.path();
+        write(
+            root,
+            PROJECT_FILE,
+            "root: true\ndefaults:\n  width: 1440\n  timeout: 60\n",
+        );
+        write(
+            root,
+            "apps/shop/webshot.yaml",
+            "defaults:\n  timeout: 10\nscreenshots:\n  - url: https://shop.example.com\n    output: home.png\n",
+        );
+        write(
+            root,
+            "apps/shop/admin/webshot.yaml",
+            "screenshots:\n  - url: https://admin.example.com\n    output: admin.png\n",
+        );
+        write(root, "apps/docs/webshot.yaml", "defaults:\n  height: 900\n");
+        write(
+            root,
+            "node_modules/lib/webshot.yaml",
+            "screenshots:\n  - url: https://lib.example.com\n    output: lib.png\n",
+        );
+        repo
+    }
+
+    #[test]
+    fn test_discover_merges_nested_configs() {
+        let repo = monorepo();
+        let projects = discover(repo.path()).unwrap();
+        let dirs: Vec<_> = projects
+            .iter()
+            .map(|project| {
+                project
+                    .dir
+                    .strip_prefix(repo.path().canonicalize().unwrap())
+                    .unwrap()
+            })
+            .collect();
+        assert_eq!(dirs, [Path::new("apps/shop"), Path::new("apps/shop/admin")]);
+
+        let shop = &projects[0].config;
+        assert_eq!(shop.screenshots.len(), 1);
+        assert_eq!(shop.screenshots[0].width, 1440);
+        assert_eq!(shop.defaults.timeout, 10);
+
+        // Screenshots are not inherited, defaults are
+        let admin = &projects[1];
+        assert_eq!(admin.config.screenshots.len(), 1);
+        assert_eq!(admin.config.screenshots[0].url, "https://admin.example.com");
+        assert_eq!(admin.config.defaults.timeout, 10);
+        assert_eq!(admin.sources.len(), 3);
+    }
+
+    #[test]
+    fn test_discover_from_subdirectory_inherits_parents() {
+        let repo = monorepo();
+        let projects = discover(&repo.path().join("apps/shop/admin")).unwrap();
+        assert_eq!(projects.len(), 1);
+        assert_eq!(projects[0].config.screenshots[0].width, 1440);
+        assert_eq!(projects[0].config.defaults.timeout, 10);
+    }
+
+    #[test]
+    fn test_changed_files_select_owning_projects() {
+        let repo = monorepo();
+        let root = repo.path().canonicalize().unwrap();
+        let projects = discover(&root).unwrap();
+        let names = |changed: &[&str]| {
+            let changed: Vec<_> = changed.iter().map(|path| root.join(path)).collect();
+            affected(&projects, &changed)
+                .iter()
+                .map(|project| {
+                    project
+                        .dir
+                        .file_name()
+                        .unwrap()
+                        .to_string_lossy()
+                        .to_string()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(names(&["apps/shop/src/cart.js"]), ["shop"]);
+        assert_eq!(names(&["apps/shop/admin/users.js"]), ["admin"]);
+        assert_eq!(names(&["apps/shop/webshot.yaml"]), ["shop", "admin"]);
+        assert_eq!(names(&[PROJECT_FILE]), ["shop", "admin"]);
+        assert!(names(&["apps/docs/index.md", "README.md"]).is_empty());
+    }
+}
@@ -0,0 +1,145 @@
+//! Per-host pacing of page loads.
+//!
+//! Batch runs and crawls load pages from many workers at once; a
+//! [`HostRateLimiter`] spaces out the navigations to each host so a high
+//! `--parallel` does not hammer a single origin. Only page navigations are
+//! paced, not the subresources a page loads.
+
+use crate::error::{Result, WebshotError};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+/// How often pages may be loaded from one host
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct RateLimit {
+    /// Most page loads per second from one host
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requests_per_second: Option<f64>,
+    /// Least time between two page loads from one host, in milliseconds
+    #[serde(default)]
+    pub delay_ms: u64,
+}
+
+impl RateLimit {
+    /// Time between page loads from one host; zero when unlimited
+    pub fn interval(&self) -> Duration {
+        let rate = self
+            .requests_per_second
+            .map_or(Duration::ZERO, |rps| Duration::from_secs_f64(1.0 / rps));
+        rate.max(Duration::from_millis(self.delay_ms))
+    }
+
+    pub fn is_limited(&self) -> bool {
+        !self.interval().is_zero()
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        match self.requests_per_second {
+            Some(rps) if !(rps.is_finite() && rps > 0.0) => Err(WebshotError::config(format!(
+                "rate_limit.requests_per_second must be a positive number, not {}",
+                rps
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Hands out page-load slots per host, spaced by a [`RateLimit`]
+#[derive(Debug)]
+pub struct HostRateLimiter {
+    interval: Duration,
+    /// Earliest time the next page of each host may load
+    next_slot: Mutex<HashMap<String, Instant>>,
+}
+
+impl HostRateLimiter {
+    pub fn new(limit: &RateLimit) -> Self {
+        Self {
+            interval: limit.interval(),
+            next_slot: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Wait until a page of `url`'s host may load
+    pub async fn acquire(&self, url: &str) {
+        if self.interval.is_zero() {
+            return;
+        }
+        let host = host_key(url);
+        let wait = self.reserve(&host, Instant::now());
+        if !wait.is_zero() {
+            debug!(
+                "Waiting {}ms before loading {} again",
+                wait.as_millis(),
+                host
+            );
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Book the next free slot of `host` and return how long until it starts
+    fn reserve(&self, host: &str, now: Instant) -> Duration {
+        let mut next_slot = self.next_slot.lock().unwrap();
+        let slot = next_slot.get(host).map_or(now, |next| (*next).max(now));
+        next_slot.insert(host.to_string(), slot + self.interval);
+        slot - now
+    }
+}
+
+/// Host (with port) a URL loads from, or the URL itself when it has none
+fn host_key(url: &str) -> String {
+    url::Url::parse(url)
+        .ok()
+        .and_then(|parsed| {
+            let host = parsed.host_str()?.to_lowercase();
+            Some(match parsed.port() {
+                Some(port) => format!("{}:{}", host, port),
+                None => host,
+            })
+        })
+        .unwrap_or_else(|| url.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slots_are_spaced_per_host() {
+        let limiter = HostRateLimiter::new(&RateLimit {
+            requests_per_second: Some(4.0),
+            delay_ms: 0,
+        });
+        let start = Instant::now();
+        let ms = Duration::from_millis;
+
+        assert_eq!(limiter.reserve("example.com", start), ms(0));
+        assert_eq!(limiter.reserve("example.com", start), ms(250));
+        assert_eq!(limiter.reserve("example.com", start + ms(100)), ms(400));
+        assert_eq!(limiter.reserve("other.com", start), ms(0));
+        // Idle hosts do not bank unused slots
+        assert_eq!(limiter.reserve("other.com", start + ms(2000)), ms(0));
+    }
+
+    #[test]
+    fn test_interval_takes_the_stricter_setting() {
+        let limit = RateLimit {
+            requests_per_second: Some(10.0),
+            delay_ms: 500,
+        };
+        assert_eq!(limit.interval(), Duration::from_millis(500));
+        assert!(!RateLimit::default().is_limited());
+        assert!(RateLimit {
+            requests_per_second: Some(0.0),
+            delay_ms: 0
+        }
+        .validate()
+        .is_err());
+
+        assert_eq!(host_key("https://Example.com/a?b"), "example.com");
+        assert_eq!(host_key("http://localhost:8080/"), "localhost:8080");
+    }
+}
@@ -0,0 +1,504 @@
+use crate::backend::PdfOptions;
+use crate::browser::Browser;
+use crate::comparison::{ComparisonOptions, ImageComparator};
+use crate::config::DefaultConfig;
+use crate::error::{Result, WebshotError};
+use crate::output::OutputHandler;
+use crate::paper::PaperSize;
+use crate::screenshot::ScreenshotOptions;
+use crate::steps::{write_steps, Step};
+use headless_chrome::Tab;
+use serde_yaml::{Mapping, Value};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::time::sleep;
+
+pub const HELP: &str = "\
+Commands:
+  goto <url>                 Navigate to a page
+  wait <selector|seconds>    Wait for an element or a number of seconds
+  click <selector>           Click an element
+  shot <file> [selector]     Capture the page, or one element
+  pdf <file>                 Print the page to PDF
+  eval <javascript>          Evaluate JavaScript and print the result
+  compare <image> <image>    Compare two images
+  :export <file>             Write the captures so far as a batch config
+  :help                      Show this help
+  :quit                      Exit (also Ctrl-D)";
+
+/// What a `wait` command waits for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WaitTarget {
+    Selector(String),
+    Seconds(u64),
+}
+
+/// A parsed REPL input line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplCommand {
+    Goto(String),
+    Wait(WaitTarget),
+    Click(String),
+    Shot {
+        output: PathBuf,
+        selector: Option<String>,
+    },
+    Pdf(PathBuf),
+    Eval(String),
+    Compare(PathBuf, PathBuf),
+    Export(PathBuf),
+    Help,
+    Quit,
+}
+
+impl FromStr for ReplCommand {
+    type Err = WebshotError;
+
+    fn from_str(line: &str) -> Result<Self> {
+        let line = line.trim();
+        let (name, rest) = line
+            .split_once(char::is_whitespace)
+            .map(|(name, rest)| (name, rest.trim()))
+            .unwrap_or((line, ""));
+
+        let required = |what: &str| {
+            if rest.is_empty() {
+                Err(WebshotError::config(format!("Usage: {} {}", name, what)))
+            } else {
+                Ok(rest.to_string())
+            }
+        };
+
+        match name {
+            "goto" => Ok(Self::Goto(required("<url>")?)),
+            "wait" => {
+                let target = required("<selector|seconds>")?;
+                Ok(Self::Wait(match target.parse() {
+                    Ok(seconds) => WaitTarget::Seconds(seconds),
+                    Err(_) => WaitTarget::Selector(target),
+                }))
+            }
+            "click" => Ok(Self::Click(required("<selector>")?)),
+            "shot" => {
+                let args = required("<file> [selector]")?;
+                let (output, selector) = match args.split_once(char::is_whitespace) {
+                    Some((output, selector)) => (output, Some(selector.trim().to_string())),
+                    None => (args.as_str(), None),
+                };
+                Ok(Self::Shot {
+                    output: PathBuf::from(output),
+                    selector,
+                })
+            }
+            "pdf" => Ok(Self::Pdf(PathBuf::from(required("<file>")?))),
+            "eval" => Ok(Self::Eval(required("<javascript>")?)),
+            "compare" => match rest.split_whitespace().collect::<Vec<_>>()[..] {
+                [first, second] => Ok(Self::Compare(first.into(), second.into())),
+                _ => Err(WebshotError::config(
+                    "Usage: compare <image> <image>".to_string(),
+                )),
+            },
+            ":export" => Ok(Self::Export(PathBuf::from(required("<file>")?))),
+            ":help" | "help" => Ok(Self::Help),
+            ":quit" | "quit" | "exit" => Ok(Self::Quit),
+            _ => Err(WebshotError::config(format!(
+                "Unknown command: {}. Type :help for a list of commands",
+                name
+            ))),
+        }
+    }
+}
+
+/// Transcribes a REPL session into batch config entries. Each capture gets
+/// the URL of the last `goto` plus the clicks, scripts and waits since then,
+/// replayed as a single `javascript` step followed by `wait_for` and `wait`.
+#[derive(Debug, Clone, Default)]
+pub struct SessionRecorder {
+    width: u32,
+    height: u32,
+    start_url: Option<String>,
+    url: Option<String>,
+    scripts: Vec<String>,
+    wait_for: Option<String>,
+    wait: u64,
+    entries: Vec<Mapping>,
+}
+
+impl SessionRecorder {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            ..Default::default()
+        }
+    }
+
+    /// Current page URL, if any page has been loaded
+    pub fn url(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
+
+    /// URL of the first page loaded in the session
+    pub fn start_url(&self) -> Option<&str> {
+        self.start_url.as_deref()
+    }
+
+    pub fn goto(&mut self, url: &str) {
+        self.start_url.get_or_insert_with(|| url.to_string());
+        self.url = Some(url.to_string());
+        self.scripts.clear();
+        self.wait_for = None;
+        self.wait = 0;
+    }
+
+    pub fn wait(&mut self, target: &WaitTarget) {
+        match target {
+            WaitTarget::Selector(selector) => self.wait_for = Some(selector.clone()),
+            WaitTarget::Seconds(seconds) => self.wait += seconds,
+        }
+    }
+
+    pub fn click(&mut self, selector: &str) {
+        self.scripts.push(format!(
+            "document.querySelector({}).click();",
+            serde_json::Value::from(selector)
+        ));
+    }
+
+    pub fn eval(&mut self, script: &str) {
+        self.scripts.push(script.to_string());
+    }
+
+    /// Record a capture of the current page
+    pub fn capture(&mut self, output: &Path, selector: Option<&str>) -> Result<()> {
+        let url = self.url.clone().ok_or_else(|| {
+            WebshotError::config("No page loaded; use goto <url> first".to_string())
+        })?;
+
+        let mut entry = Mapping::new();
+        entry.insert("url".into(), url.into());
+        entry.insert("output".into(), output.display().to_string().into());
+
+        let defaults = DefaultConfig::default();
+        if self.width != defaults.width {
+            entry.insert("width".into(), self.width.into());
+        }
+        if self.height != defaults.height {
+            entry.insert("height".into(), self.height.into());
+        }
+        if let Some(selector) = selector {
+            entry.insert("selector".into(), selector.into());
+        }
+        if !self.scripts.is_empty() {
+            entry.insert("javascript".into(), self.scripts.join("\n").into());
+        }
+        if let Some(wait_for) = &self.wait_for {
+            entry.insert("wait_for".into(), wait_for.as_str().into());
+        }
+        if self.wait > 0 {
+            entry.insert("wait".into(), self.wait.into());
+        }
+
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// Number of captures recorded so far
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Recorded captures as a batch config YAML document
+    pub fn to_yaml(&self) -> Result<String> {
+        let mut config = Mapping::new();
+        config.insert(
+            "screenshots".into(),
+            Value::Sequence(self.entries.iter().cloned().map(Value::Mapping).collect()),
+        );
+        Ok(serde_yaml::to_string(&config)?)
+    }
+}
+
+/// Steps recorded from page interactions, written out when the session ends
+struct StepRecording {
+    path: PathBuf,
+    steps: Arc<Mutex<Vec<Step>>>,
+}
+
+/// Interactive session driving a single persistent browser tab
+pub struct Repl<'a> {
+    browser: &'a Browser,
+    tab: Arc<Tab>,
+    options: ScreenshotOptions,
+    recorder: SessionRecorder,
+    exported: usize,
+    recording: Option<StepRecording>,
+}
+
+impl<'a> Repl<'a> {
+    /// Open the session tab with the viewport and emulation from `options`
+    pub async fn new(browser: &'a Browser, options: ScreenshotOptions) -> Result<Self> {
+        let tab = browser.open_page(&options).await?;
+        let recorder = SessionRecorder::new(options.width, options.height);
+        Ok(Self {
+            browser,
+            tab,
+            options,
+            recorder,
+            exported: 0,
+            recording: None,
+        })
+    }
+
+    /// Record clicks and typing in the page, plus `goto` and `wait`
+    /// commands after the first page load, as config `steps` written to
+    /// `path` when the session ends
+    pub fn record_steps(&mut self, path: PathBuf) -> Result<()> {
+        let steps = self.browser.record_steps(&self.tab)?;
+        self.recording = Some(StepRecording { path, steps });
+        Ok(())
+    }
+
+    fn push_step(&self, step: Step) {
+        if let Some(recording) = &self.recording {
+            recording.steps.lock().unwrap().push(step);
+        }
+    }
+
+    /// Read commands from stdin until `:quit` or end of input. Command errors
+    /// are reported and the session continues.
+    pub async fn run(&mut self) -> Result<()> {
+        let mut lines = BufReader::new(tokio::io::stdin()).lines();
+        loop {
+            print!("webshot> ");
+            std::io::stdout().flush()?;
+
+            let Some(line) = lines.next_line().await? else {
+                println!();
+                break;
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            match line.parse::<ReplCommand>() {
+                Ok(ReplCommand::Quit) => break,
+                Ok(command) => match self.execute(command).await {
+                    Ok(Some(message)) => println!("{}", message),
+                    Ok(None) => {}
+                    Err(e) => eprintln!("error: {}", e),
+                },
+                Err(e) => eprintln!("error: {}", e),
+            }
+        }
+
+        if self.recorder.len() > self.exported {
+            eprintln!(
+                "{} capture(s) were not exported",
+                self.recorder.len() - self.exported
+            );
+        }
+        if let Some(recording) = &self.recording {
+            let steps = recording.steps.lock().unwrap().clone();
+            write_steps(&recording.path, self.recorder.start_url(), &steps)?;
+            eprintln!(
+                "Recorded {} step(s) to: {}",
+                steps.len(),
+                recording.path.display()
+            );
+        }
+        Ok(())
+    }
+
+    /// Run one command, returning a message to show
+    pub async fn execute(&mut self, command: ReplCommand) -> Result<Option<String>> {
+        match command {
+            ReplCommand::Goto(url) => {
+                self.browser.navigate(&self.tab, &url)?;
+                if self.recorder.url().is_some() {
+                    self.push_step(Step::Goto(url.clone()));
+                }
+                self.recorder.goto(&url);
+                Ok(Some(self.tab.get_url()))
+            }
+            ReplCommand::Wait(target) => {
+                match &target {
+                    WaitTarget::Selector(selector) => {
+                        self.browser
+                            .wait_for_element(&self.tab, selector, self.options.timeout)
+                            .await?
+                    }
+                    WaitTarget::Seconds(seconds) => sleep(Duration::from_secs(*seconds)).await,
+                }
+                self.push_step(match &target {
+                    WaitTarget::Selector(selector) => Step::WaitFor(selector.clone()),
+                    WaitTarget::Seconds(seconds) => Step::Wait(seconds * 1000),
+                });
+                self.recorder.wait(&target);
+                Ok(None)
+            }
+            ReplCommand::Click(selector) => {
+                self.tab
+                    .find_element(&selector)
+                    .map_err(|_e| WebshotError::element_not_found(selector.clone()))?
+                    .click()
+                    .map_err(WebshotError::Browser)?;
+                self.recorder.click(&selector);
+                Ok(None)
+            }
+            ReplCommand::Shot { output, selector } => {
+                self.require_page()?;
+                let options = ScreenshotOptions {
+                    selector: selector.clone(),
+                    ..self.options.clone()
+                };
+                let saved = self
+                    .browser
+                    .capture_page(&self.tab, &output, &options)
+                    .await?;
+                self.recorder.capture(&output, selector.as_deref())?;
+                Ok(Some(format!("Screenshot saved to: {}", saved.display())))
+            }
+            ReplCommand::Pdf(output) => {
+                self.require_page()?;
+                self.browser.save_pdf(
+                    &self.tab,
+                    &output,
+                    &PdfOptions {
+                        paper: PaperSize::default(),
+                        background: true,
+                        ..PdfOptions::default()
+                    },
+                )?;
+                self.recorder.capture(&output, None)?;
+                Ok(Some(format!("PDF saved to: {}", output.display())))
+            }
+            ReplCommand::Eval(script) => {
+                let result = self
+                    .tab
+                    .evaluate(&script, true)
+                    .map_err(|e| WebshotError::javascript(e.to_string()))?;
+                self.recorder.eval(&script);
+                Ok(Some(match result.value {
+                    Some(value) => value.to_string(),
+                    None => result
+                        .description
+                        .unwrap_or_else(|| "undefined".to_string()),
+                }))
+            }
+            ReplCommand::Compare(first, second) => {
+                let result =
+                    ImageComparator::compare_files(&first, &second, &ComparisonOptions::new())?;
+                Ok(Some(format!(
+                    "{} (similarity: {:.2}%)",
+                    if result.similar {
+                        "Similar"
+                    } else {
+                        "Different"
+                    },
+                    result.similarity * 100.0
+                )))
+            }
+            ReplCommand::Export(path) => {
+                OutputHandler::ensure_output_dir(&path)?;
+                std::fs::write(&path, self.recorder.to_yaml()?)?;
+                self.exported = self.recorder.len();
+                Ok(Some(format!(
+                    "Exported {} capture(s) to: {}",
+                    self.recorder.len(),
+                    path.display()
+                )))
+            }
+            ReplCommand::Help => Ok(Some(HELP.to_string())),
+            ReplCommand::Quit => Ok(None),
+        }
+    }
+
+    fn require_page(&self) -> Result<()> {
+        self.recorder
+            .url()
+            .map(|_| ())
+            .ok_or_else(|| WebshotError::config("No page loaded; use goto <url> first".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!(
+            "goto https://example.com".parse::<ReplCommand>().unwrap(),
+            ReplCommand::Goto("https://example.com".to_string())
+        );
+        assert_eq!(
+            "wait 2".parse::<ReplCommand>().unwrap(),
+            ReplCommand::Wait(WaitTarget::Seconds(2))
+        );
+        assert_eq!(
+            "wait .content".parse::<ReplCommand>().unwrap(),
+            ReplCommand::Wait(WaitTarget::Selector(".content".to_string()))
+        );
+        assert_eq!(
+            "shot hero.png  header .hero"
+                .parse::<ReplCommand>()
+                .unwrap(),
+            ReplCommand::Shot {
+                output: PathBuf::from("hero.png"),
+                selector: Some("header .hero".to_string()),
+            }
+        );
+        assert_eq!(
+            "eval  document.title ".parse::<ReplCommand>().unwrap(),
+            ReplCommand::Eval("document.title".to_string())
+        );
+        assert_eq!(":quit".parse::<ReplCommand>().unwrap(), ReplCommand::Quit);
+
+        for invalid in ["click", "compare a.png", "screenshot x.png"] {
+            assert!(invalid.parse::<ReplCommand>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_recorder_exports_loadable_config() {
+        let mut recorder = SessionRecorder::new(1280, 1024);
+        assert!(recorder.capture(Path::new("early.png"), None).is_err());
+
+        recorder.goto("https://example.com");
+        recorder.click("button[data-tab=\"pricing\"]");
+        recorder.wait(&WaitTarget::Selector(".pricing".to_string()));
+        recorder.wait(&WaitTarget::Seconds(1));
+        recorder
+            .capture(Path::new("pricing.png"), Some(".pricing"))
+            .unwrap();
+        recorder.goto("https://example.org");
+        recorder.capture(Path::new("org.pdf"), None).unwrap();
+
+        let config: Config = serde_yaml::from_str(&recorder.to_yaml().unwrap()).unwrap();
+        assert_eq!(config.screenshots.len(), 2);
+
+        let pricing = &config.screenshots[0];
+        assert_eq!(pricing.height, 1024);
+        assert_eq!(pricing.selector.as_deref(), Some(".pricing"));
+        assert_eq!(
+            pricing.javascript.as_deref(),
+            Some(r#"document.querySelector("button[data-tab=\"pricing\"]").click();"#)
+        );
+        assert_eq!(pricing.wait_for.as_deref(), Some(".pricing"));
+        assert_eq!(pricing.wait, Some(1));
+
+        let org = &config.screenshots[1];
+        assert_eq!(org.url, "https://example.org");
+        assert!(org.javascript.is_none() && org.wait_for.is_none());
+    }
+}
@@ -0,0 +1,472 @@
+//! Pruning of archive directories that collect captures over time.
+//!
+//! Monitoring setups capture into the same directory on every run (often
+//! with `{date}` in output paths), which grows until the disk is full. A
+//! [`RetentionPolicy`] removes captures and their artifacts older than a
+//! maximum age and then, oldest first, until the directory fits a size
+//! budget.
+//!
+//! Some files are never pruned: baselines (files stored next to a
+//! `<name>.version` file, as `webshot baseline` writes them), webshot's own
+//! manifests and state files, and every file in a flagged run, a directory
+//! holding a [`KEEP_MARKER`] file. Directories left empty are removed.
+//!
+//! Hard links to one file are counted once, and pruning a link only frees
+//! space once the file's last link is gone.
+
+use crate::crawl::DEFAULT_STATE_FILE;
+use crate::dedupe::DEDUPE_MANIFEST;
+use crate::error::{Result, WebshotError};
+use crate::manifest::RUN_MANIFEST;
+use crate::output::format_file_size;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// File flagging its directory as a run to keep, e.g. one whose captures
+/// showed a regression
+pub const KEEP_MARKER: &str = ".webshot-keep";
+
+/// Files webshot keeps alongside captures, which are never pruned
+const METADATA_FILES: [&str; 4] = [
+    KEEP_MARKER,
+    DEDUPE_MANIFEST,
+    RUN_MANIFEST,
+    DEFAULT_STATE_FILE,
+];
+
+/// How long captures are kept and how much space they may take
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Captures older than this are pruned
+    pub max_age: Option<Duration>,
+    /// Oldest captures are pruned until the directory is no larger, in bytes
+    pub max_size: Option<u64>,
+}
+
+impl RetentionPolicy {
+    pub fn is_set(&self) -> bool {
+        self.max_age.is_some() || self.max_size.is_some()
+    }
+}
+
+/// Parse a retention age such as `30d`, `12h`, `90m` or `2w`; bare numbers
+/// are days
+pub fn parse_age(value: &str) -> Result<Duration> {
+    let value = value.trim();
+    let (number, unit_secs) = match value.char_indices().last() {
+        Some((index, 'w')) => (&value[..index], 7 * 86_400),
+        Some((index, 'd')) => (&value[..index], 86_400),
+        Some((index, 'h')) => (&value[..index], 3_600),
+        Some((index, 'm')) => (&value[..index], 60),
+        _ => (value, 86_400),
+    };
+    match number.trim().parse::<f64>() {
+        Ok(number) if number.is_finite() && number > 0.0 => {
+            Ok(Duration::from_secs_f64(number * unit_secs as f64))
+        }
+        _ => Err(WebshotError::config(format!(
+            "Invalid retention: {}. Use e.g. 30d, 12h or 2w",
+            value
+        ))),
+    }
+}
+
+/// Parse a size such as `10GB`, `500MB` or `1.5G` in binary units (1 KB =
+/// 1024 bytes, as sizes are printed); bare numbers are bytes
+pub fn parse_size(value: &str) -> Result<u64> {
+    let value = value.trim();
+    let upper = value.to_ascii_uppercase();
+    let digits = upper.trim_end_matches(|c: char| c.is_ascii_alphabetic() || c == ' ');
+    let unit = upper[digits.len()..].trim_start();
+    let exponent = match unit.trim_end_matches("IB").trim_end_matches('B') {
+        "" => 0,
+        "K" => 1,
+        "M" => 2,
+        "G" => 3,
+        "T" => 4,
+        _ => -1,
+    };
+    match digits.parse::<f64>() {
+        Ok(number) if exponent >= 0 && number.is_finite() && number >= 0.0 => {
+            Ok((number * 1024f64.powi(exponent)) as u64)
+        }
+        _ => Err(WebshotError::config(format!(
+            "Invalid size: {}. Use e.g. 500MB or 10GB",
+            value
+        ))),
+    }
+}
+
+/// Why a file was pruned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PruneReason {
+    /// Older than the maximum age
+    Age,
+    /// Among the oldest files while the directory exceeded its size budget
+    Size,
+}
+
+impl fmt::Display for PruneReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PruneReason::Age => f.write_str("expired"),
+            PruneReason::Size => f.write_str("over size budget"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrunedFile {
+    pub path: PathBuf,
+    /// Bytes freed by removing the file: its size, or 0 for a hard link
+    /// whose file has other links left
+    pub size: u64,
+    pub reason: PruneReason,
+}
+
+/// Files pruned from an archive directory, or that would be on a dry run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PruneReport {
+    pub pruned: Vec<PrunedFile>,
+    /// Files left in place, protected ones included
+    pub kept: usize,
+    /// Baselines, metadata and files of flagged runs, which are never pruned
+    pub protected: usize,
+    /// Size of the files left in place
+    pub remaining_bytes: u64,
+    pub dry_run: bool,
+}
+
+impl PruneReport {
+    pub fn freed_bytes(&self) -> u64 {
+        self.pruned.iter().map(|file| file.size).sum()
+    }
+}
+
+/// A file found in an archive directory
+#[derive(Debug, Clone)]
+struct ArchiveFile {
+    path: PathBuf,
+    size: u64,
+    modified: SystemTime,
+    protected: bool,
+    /// Device and inode, shared by hard links to the same file
+    id: Option<(u64, u64)>,
+    /// Hard links to the file, inside the directory or not
+    links: u64,
+}
+
+/// Size of `files`, counting hard links to one file once
+fn total_size<'a>(files: impl IntoIterator<Item = &'a ArchiveFile>) -> u64 {
+    let mut seen = HashSet::new();
+    files
+        .into_iter()
+        .filter(|file| file.id.is_none_or(|id| seen.insert(id)))
+        .map(|file| file.size)
+        .sum()
+}
+
+/// Prune `dir` by `policy`, or only report what would be pruned on a
+/// `dry_run`
+pub fn prune(dir: &Path, policy: &RetentionPolicy, dry_run: bool) -> Result<PruneReport> {
+    if !dir.is_dir() {
+        return Err(WebshotError::InvalidPath {
+            path: dir.to_path_buf(),
+        });
+    }
+
+    let mut files = Vec::new();
+    collect(dir, false, &mut files)?;
+    let pruned = plan(&files, policy, SystemTime::now());
+
+    if !dry_run {
+        for file in &pruned {
+            fs::remove_file(&file.path)?;
+            remove_empty_parents(dir, &file.path)?;
+        }
+    }
+
+    let protected = files.iter().filter(|file| file.protected).count();
+    let pruned_paths: HashSet<&Path> = pruned.iter().map(|file| file.path.as_path()).collect();
+    let remaining_bytes = total_size(
+        files
+            .iter()
+            .filter(|file| !pruned_paths.contains(file.path.as_path())),
+    );
+    Ok(PruneReport {
+        kept: files.len() - pruned.len(),
+        protected,
+        remaining_bytes,
+        pruned,
+        dry_run,
+    })
+}
+
+/// Files to prune: unprotected ones older than the maximum age, then the
+/// oldest remaining ones until the rest fits the size budget
+fn plan(files: &[ArchiveFile], policy: &RetentionPolicy, now: SystemTime) -> Vec<PrunedFile> {
+    let mut candidates: Vec<&ArchiveFile> = files.iter().filter(|file| !file.protected).collect();
+    candidates.sort_by_key(|file| file.modified);
+
+    // Links of each hard-linked file left in the directory, and anywhere
+    let mut links_here: HashMap<(u64, u64), u64> = HashMap::new();
+    let mut links_anywhere: HashMap<(u64, u64), u64> = HashMap::new();
+    for file in files {
+        if let Some(id) = file.id {
+            *links_here.entry(id).or_default() += 1;
+            links_anywhere.insert(id, file.links);
+        }
+    }
+
+    let mut pruned = Vec::new();
+    let mut remaining = total_size(files);
+    for file in candidates {
+        let age = now.duration_since(file.modified).unwrap_or_default();
+        let reason = if policy.max_age.is_some_and(|max_age| age > max_age) {
+            PruneReason::Age
+        } else if policy.max_size.is_some_and(|max_size| remaining > max_size) {
+            PruneReason::Size
+        } else {
+            continue;
+        };
+        let (last_here, last_anywhere) = match file.id {
+            Some(id) => {
+                let unlink = |links: &mut HashMap<(u64, u64), u64>| {
+                    let left = links.get_mut(&id).expect("every file id is counted");
+                    *left = left.saturating_sub(1);
+                    *left == 0
+                };
+                (unlink(&mut links_here), unlink(&mut links_anywhere))
+            }
+            None => (true, true),
+        };
+        if last_here {
+            remaining -= file.size;
+        }
+        pruned.push(PrunedFile {
+            path: file.path.clone(),
+            size: if last_anywhere { file.size } else { 0 },
+            reason,
+        });
+    }
+    pruned
+}
+
+fn collect(dir: &Path, protected: bool, files: &mut Vec<ArchiveFile>) -> Result<()> {
+    let protected = protected || dir.join(KEEP_MARKER).exists();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let path = entry.path();
+        if file_type.is_dir() {
+            collect(&path, protected, files)?;
+        } else if file_type.is_file() {
+            let metadata = entry.metadata()?;
+            let (id, links) = link_info(&metadata);
+            files.push(ArchiveFile {
+                protected: protected || is_protected(&path),
+                path,
+                size: metadata.len(),
+                modified: metadata.modified()?,
+                id,
+                links,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Device and inode of a file and its number of hard links
+#[cfg(unix)]
+fn link_info(metadata: &fs::Metadata) -> (Option<(u64, u64)>, u64) {
+    use std::os::unix::fs::MetadataExt;
+    (Some((metadata.dev(), metadata.ino())), metadata.nlink())
+}
+
+#[cfg(not(unix))]
+fn link_info(_metadata: &fs::Metadata) -> (Option<(u64, u64)>, u64) {
+    (None, 1)
+}
+
+/// Whether `path` is webshot metadata or part of a stored baseline
+fn is_protected(path: &Path) -> bool {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    METADATA_FILES.contains(&name.as_str())
+        || name.ends_with(".version")
+        || name.ends_with(".lock")
+        || path.with_file_name(format!("{}.version", name)).exists()
+}
+
+/// Remove the directories between `root` and `path` that pruning emptied
+fn remove_empty_parents(root: &Path, path: &Path) -> Result<()> {
+    for dir in path.ancestors().skip(1) {
+        if dir == root || !dir.starts_with(root) || fs::read_dir(dir)?.next().is_some() {
+            break;
+        }
+        fs::remove_dir(dir)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for PruneReport {
+    /// A `pruned`/`would prune` line per file, then a summary line
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let verb = if self.dry_run {
+            "would prune"
+        } else {
+            "pruned"
+        };
+        for file in &self.pruned {
+            writeln!(
+                f,
+                "{} {} ({}, {})",
+                verb,
+                file.path.display(),
+                format_file_size(file.size),
+                file.reason
+            )?;
+        }
+        writeln!(
+            f,
+            "{} {} files, freeing {}; {} files ({} protected) remain, {}",
+            if self.dry_run {
+                "Would prune"
+            } else {
+                "Pruned"
+            },
+            self.pruned.len(),
+            format_file_size(self.freed_bytes()),
+            self.kept,
+            self.protected,
+            format_file_size(self.remaining_bytes)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: Duration = Duration::from_secs(86_400);
+
+    #[test]
+    fn test_parse_age_and_size() {
+        assert_eq!(parse_age("30d").unwrap(), 30 * DAY);
+        assert_eq!(parse_age("2w").unwrap(), 14 * DAY);
+        assert_eq!(parse_age("12h").unwrap(), DAY / 2);
+        assert_eq!(parse_age("7").unwrap(), 7 * DAY);
+        assert!(parse_age("0d").is_err());
+        assert!(parse_age("soon").is_err());
+
+        assert_eq!(parse_size("10GB").unwrap(), 10 << 30);
+        assert_eq!(parse_size("1.5g").unwrap(), 3 << 29);
+        assert_eq!(parse_size("500 MiB").unwrap(), 500 << 20);
+        assert_eq!(parse_size("4096").unwrap(), 4096);
+        assert!(parse_size("10XB").is_err());
+        assert!(parse_size("GB").is_err());
+    }
+
+    #[test]
+    fn test_plan_prunes_expired_then_oldest() {
+        let now = SystemTime::now();
+        let file = |name: &str, days: u32, size: u64, protected: bool| ArchiveFile {
+            path: PathBuf::from(name),
+            size,
+            modified: now - DAY * days,
+            protected,
+            id: None,
+            links: 1,
+        };
+        let files = [
+            file("baseline.png", 90, 100, true),
+            file("old.png", 40, 100, false),
+            file("older.png", 35, 100, false),
+            file("recent.png", 5, 100, false),
+            file("today.png", 0, 100, false),
+        ];
+
+        let policy = RetentionPolicy {
+            max_age: Some(30 * DAY),
+            max_size: Some(250),
+        };
+        let pruned = plan(&files, &policy, now);
+        let pruned: Vec<_> = pruned
+            .iter()
+            .map(|file| (file.path.to_str().unwrap(), file.reason))
+            .collect();
+        assert_eq!(
+            pruned,
+            [
+                ("old.png", PruneReason::Age),
+                ("older.png", PruneReason::Age),
+                ("recent.png", PruneReason::Size),
+            ]
+        );
+
+        assert!(plan(&files, &RetentionPolicy::default(), now).is_empty());
+    }
+
+    #[test]
+    fn test_prune_keeps_baselines_and_flagged_runs() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |path: &str| {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, [0u8; 10]).unwrap();
+        };
+        write("2026-01-01/home.png");
+        write("2026-01-01/home.har");
+        write("2026-01-02/home.png");
+        write(&format!("2026-01-02/{}", KEEP_MARKER));
+        write("baselines/home.png");
+        write("baselines/home.png.version");
+        write(DEDUPE_MANIFEST);
+
+        let policy = RetentionPolicy {
+            max_age: None,
+            max_size: Some(0),
+        };
+        let report = prune(dir.path(), &policy, true).unwrap();
+        assert_eq!(report.pruned.len(), 2);
+        assert_eq!(report.protected, 5);
+        assert!(report
+            .to_string()
+            .contains("Would prune 2 files, freeing 20 B"));
+        assert!(dir.path().join("2026-01-01/home.png").exists());
+
+        let report = prune(dir.path(), &policy, false).unwrap();
+        assert_eq!(report.freed_bytes(), 20);
+        assert!(!dir.path().join("2026-01-01").exists());
+        assert!(dir.path().join("2026-01-02/home.png").exists());
+        assert!(dir.path().join("baselines/home.png").exists());
+        assert!(dir.path().join(DEDUPE_MANIFEST).exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hard_links_are_counted_once() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("home.png"), [0u8; 10]).unwrap();
+        fs::hard_link(dir.path().join("home.png"), dir.path().join("latest.png")).unwrap();
+        fs::write(dir.path().join("about.png"), [0u8; 10]).unwrap();
+
+        let report = prune(dir.path(), &RetentionPolicy::default(), true).unwrap();
+        assert_eq!(report.remaining_bytes, 20);
+
+        let policy = RetentionPolicy {
+            max_age: None,
+            max_size: Some(0),
+        };
+        let report = prune(dir.path(), &policy, false).unwrap();
+        assert_eq!(report.pruned.len(), 3);
+        assert_eq!(report.freed_bytes(), 20);
+        assert_eq!(report.remaining_bytes, 0);
+        assert!(report.to_string().contains("Pruned 3 files, freeing 20 B"));
+    }
+}
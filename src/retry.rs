@@ -0,0 +1,153 @@
+use crate::error::{Result, WebshotError};
+use std::future::Future;
+use std::time::Duration;
+use tracing::warn;
+
+/// Default delay before the first retry, in milliseconds
+pub const DEFAULT_RETRY_DELAY_MS: u64 = 1000;
+
+/// Longest wait between two attempts, however many retries came before
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// How often, and how patiently, to retry an operation that failed with a
+/// [transient](WebshotError::is_transient) error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt; 0 disables retrying
+    pub retries: u32,
+    /// Delay before the first retry, doubled for every retry after it
+    pub delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new(0, Duration::from_millis(DEFAULT_RETRY_DELAY_MS))
+    }
+}
+
+impl RetryPolicy {
+    pub fn new(retries: u32, delay: Duration) -> Self {
+        Self { retries, delay }
+    }
+
+    /// Wait before retrying after failed attempt number `attempt` (from 1)
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.delay.saturating_mul(factor).min(MAX_BACKOFF)
+    }
+
+    /// Run `operation` until it succeeds, fails with a permanent error or
+    /// runs out of retries. The operation receives the attempt number,
+    /// starting at 1; `what` names it in retry warnings.
+    pub async fn run<T, F, Fut>(&self, what: &str, mut operation: F) -> Result<T>
+    where
+        F: FnMut(u32) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut attempt = 1;
+        loop {
+            match operation(attempt).await {
+                Err(e) if attempt <= self.retries && e.is_transient() => {
+                    let delay = self.backoff(attempt);
+                    warn!(
+                        "{} failed (attempt {} of {}), retrying in {:?}: {}",
+                        what,
+                        attempt,
+                        self.retries + 1,
+                        delay,
+                        e
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+impl WebshotError {
+    /// Whether the error may go away on its own, such as a page that did not
+    /// load in time or a dropped DevTools connection, so the operation is
+    /// worth retrying. Configuration and file errors are permanent, as are
+    /// HTTP error statuses other than 429 and 5xx.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            WebshotError::HttpStatus { status, .. } => *status == 429 || *status >= 500,
+            _ => matches!(
+                self,
+                WebshotError::Browser(_)
+                    | WebshotError::Tab(_)
+                    | WebshotError::Navigation(_)
+                    | WebshotError::Timeout { .. }
+                    | WebshotError::Http(_)
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[test]
+    fn test_backoff_doubles_up_to_limit() {
+        let policy = RetryPolicy::new(10, Duration::from_millis(500));
+        assert_eq!(policy.backoff(1), Duration::from_millis(500));
+        assert_eq!(policy.backoff(2), Duration::from_secs(1));
+        assert_eq!(policy.backoff(4), Duration::from_secs(4));
+        assert_eq!(policy.backoff(9), MAX_BACKOFF);
+        assert_eq!(policy.backoff(40), MAX_BACKOFF);
+    }
+
+    #[tokio::test]
+    async fn test_run_retries_transient_errors() {
+        let policy = RetryPolicy::new(3, Duration::from_millis(1));
+        let calls = AtomicU32::new(0);
+
+        let result = policy
+            .run("capture", |attempt| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    if attempt < 3 {
+                        Err(WebshotError::navigation("connection reset"))
+                    } else {
+                        Ok(attempt)
+                    }
+                }
+            })
+            .await;
+        assert_eq!(result.unwrap(), 3);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        let result: Result<()> = policy
+            .run("capture", |_| async {
+                Err(WebshotError::timeout("waiting for #app"))
+            })
+            .await;
+        assert!(matches!(result, Err(WebshotError::Timeout { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_run_fails_fast_on_permanent_errors() {
+        let policy = RetryPolicy::new(3, Duration::from_millis(1));
+        let calls = AtomicU32::new(0);
+
+        let result: Result<()> = policy
+            .run("capture", |_| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                async { Err(WebshotError::config("bad clip region")) }
+            })
+            .await;
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_only_server_side_http_statuses_are_transient() {
+        assert!(WebshotError::http_status("https://example.com", 503).is_transient());
+        assert!(WebshotError::http_status("https://example.com", 429).is_transient());
+        assert!(!WebshotError::http_status("https://example.com", 404).is_transient());
+    }
+}
@@ -1,5 +1,86 @@
+use crate::adblock::FilterList;
+use crate::blocking::BlockedResource;
+use crate::config::AuthConfig;
+use crate::devices::DevicePreset;
 use crate::error::{Result, WebshotError};
-use std::path::Path;
+use crate::mocks::Mocks;
+use crate::network_idle::NetworkIdle;
+use crate::overlays::OverlayDismissal;
+use crate::paper::PaperSize;
+use crate::session::SessionState;
+use crate::steps::Step;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Default cap for `auto_height`, Chrome's largest texture size
+pub const DEFAULT_MAX_HEIGHT: u32 = 16384;
+
+/// Color masked elements are painted with unless another is given
+pub const DEFAULT_MASK_COLOR: &str = "#FF00FF";
+
+/// Rectangular page region to capture, in CSS pixels relative to the document
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClipRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FromStr for ClipRegion {
+    type Err = WebshotError;
+
+    /// Parse a region in `x,y,width,height` form
+    fn from_str(value: &str) -> Result<Self> {
+        let parts = value
+            .split(',')
+            .map(|part| part.trim().parse::<u32>())
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|_| {
+                WebshotError::config(format!(
+                    "Invalid clip region: {}. Expected format: x,y,width,height (e.g., 0,0,800,600)",
+                    value
+                ))
+            })?;
+
+        match parts.as_slice() {
+            [x, y, width, height] => Ok(Self {
+                x: *x,
+                y: *y,
+                width: *width,
+                height: *height,
+            }),
+            _ => Err(WebshotError::config(format!(
+                "Invalid clip region: {}. Expected format: x,y,width,height (e.g., 0,0,800,600)",
+                value
+            ))),
+        }
+    }
+}
+
+/// Read init script files into script sources
+pub fn read_init_scripts<P: AsRef<Path>>(paths: &[P]) -> Result<Vec<String>> {
+    paths
+        .iter()
+        .map(|path| {
+            let path = path.as_ref();
+            std::fs::read_to_string(path).map_err(|e| {
+                WebshotError::config(format!(
+                    "Failed to read init script {}: {}",
+                    path.display(),
+                    e
+                ))
+            })
+        })
+        .collect()
+}
+
+/// Default settle delay after each lazy-load scroll step
+pub const DEFAULT_LAZY_SETTLE_MS: u64 = 100;
 
 /// Screenshot configuration options
 #[derive(Debug, Clone)]
@@ -24,6 +105,84 @@ pub struct ScreenshotOptions {
     pub wait: u64,
     /// Custom user agent
     pub user_agent: Option<String>,
+    /// Whether a capture without a selector or clip region covers the
+    /// viewport or the full scrollable page
+    pub capture: CaptureMode,
+    /// Pin sticky and fixed elements where they are on load for full-page captures
+    pub freeze_sticky: bool,
+    /// Lay the page out for print on this paper and mark its page breaks
+    pub paginate_preview: Option<PaperSize>,
+    /// Resize the viewport to the page's content height before capture
+    pub auto_height: bool,
+    /// Largest viewport height `auto_height` may resize to (default: 16384)
+    pub max_height: Option<u32>,
+    /// Emulate a touch screen with coarse pointer and no hover support
+    pub touch: bool,
+    /// Capture only this region of the page
+    pub clip: Option<ClipRegion>,
+    /// Paint the boxes of elements matching these CSS selectors over
+    /// before capture, so changing content such as ads or dates is hidden
+    pub mask_selectors: Vec<String>,
+    /// CSS color masked elements are painted with
+    pub mask_color: String,
+    /// Scroll through the page before capture to trigger lazy-loaded content
+    pub trigger_lazy: bool,
+    /// Scroll step in CSS pixels for lazy-load scrolling (default: viewport height)
+    pub lazy_scroll_step: Option<u32>,
+    /// Settle delay in milliseconds after each lazy-load scroll step
+    pub lazy_settle_ms: u64,
+    /// Wait for every frame's network to go quiet before capturing
+    pub network_idle: Option<NetworkIdle>,
+    /// Device preset overriding viewport, scale factor and mobile emulation
+    pub device: Option<DevicePreset>,
+    /// Freeze animations, transitions, carets and videos for deterministic output
+    pub stabilize: bool,
+    /// Emulated `prefers-color-scheme`, the browser's own when `None`
+    pub color_scheme: Option<ColorScheme>,
+    /// Hide scrollbars so they take no space in captures (always on with `stabilize`)
+    pub hide_scrollbars: bool,
+    /// Keep the page's own scripts from running, through the DevTools protocol
+    pub disable_javascript: bool,
+    /// Scroll viewport captures to the URL's `#fragment` target after all waits
+    pub anchor_scroll: bool,
+    /// Script sources evaluated in every new document before any page script
+    pub init_scripts: Vec<String>,
+    /// Quantize PNG output to a palette of at most this many colors (2-256)
+    pub png_colors: Option<u16>,
+    /// Write PNG output as 8-bit grayscale
+    pub grayscale: bool,
+    /// Session state (cookies and web storage) restored before navigation
+    pub session: Option<SessionState>,
+    /// Save the session state to this file after capture
+    pub save_session: Option<PathBuf>,
+    /// Compression for TIFF output
+    pub tiff_compression: TiffCompression,
+    /// WebAssembly filter plugins run over image captures before encoding
+    pub image_filters: Vec<PathBuf>,
+    /// HTTP basic auth credentials answered when the page challenges
+    pub auth: Option<AuthConfig>,
+    /// Extra HTTP headers sent with every request
+    pub headers: HashMap<String, String>,
+    /// Page interactions run after navigation, before `javascript`
+    pub steps: Vec<Step>,
+    /// Resource classes whose requests are aborted
+    pub block_resources: Vec<BlockedResource>,
+    /// Ad and tracker filter list whose matching requests are aborted
+    pub adblock: Option<Arc<FilterList>>,
+    /// Canned responses served instead of matching network requests
+    pub mocks: Mocks,
+    /// Consent overlays dismissed after `steps`, before `javascript`
+    pub dismiss_overlays: Option<OverlayDismissal>,
+    /// Fail instead of capturing when the page returns a 4xx or 5xx status
+    pub fail_on_http_error: bool,
+    /// Fail instead of capturing when the page calls `console.error()`
+    pub fail_on_console_error: bool,
+    /// Fail instead of capturing when the page throws an uncaught exception
+    pub fail_on_js_exception: bool,
+    /// File that console messages and exceptions are written to
+    pub console_log: Option<PathBuf>,
+    /// File that the page's network traffic is recorded to as a HAR
+    pub har: Option<PathBuf>,
 }
 
 impl Default for ScreenshotOptions {
@@ -39,6 +198,44 @@ impl Default for ScreenshotOptions {
             quality: None,
             wait: 0,
             user_agent: None,
+            capture: CaptureMode::default(),
+            freeze_sticky: false,
+            paginate_preview: None,
+            auto_height: false,
+            max_height: None,
+            touch: false,
+            clip: None,
+            mask_selectors: Vec::new(),
+            mask_color: DEFAULT_MASK_COLOR.to_string(),
+            trigger_lazy: false,
+            lazy_scroll_step: None,
+            lazy_settle_ms: DEFAULT_LAZY_SETTLE_MS,
+            network_idle: None,
+            device: None,
+            stabilize: false,
+            color_scheme: None,
+            hide_scrollbars: false,
+            disable_javascript: false,
+            anchor_scroll: true,
+            init_scripts: Vec::new(),
+            png_colors: None,
+            grayscale: false,
+            session: None,
+            save_session: None,
+            tiff_compression: TiffCompression::default(),
+            image_filters: Vec::new(),
+            auth: None,
+            headers: HashMap::new(),
+            steps: Vec::new(),
+            block_resources: Vec::new(),
+            adblock: None,
+            mocks: Mocks::default(),
+            dismiss_overlays: None,
+            fail_on_http_error: false,
+            fail_on_console_error: false,
+            fail_on_js_exception: false,
+            console_log: None,
+            har: None,
         }
     }
 }
@@ -104,6 +301,267 @@ impl ScreenshotOptions {
         self
     }
 
+    /// Capture the viewport or the full scrollable page
+    pub fn capture(mut self, mode: CaptureMode) -> Self {
+        self.capture = mode;
+        self
+    }
+
+    /// Capture the full scrollable page, scrolling first to load lazy content
+    pub fn full_page(self) -> Self {
+        self.capture(CaptureMode::FullPage)
+    }
+
+    /// Keep sticky and fixed headers from repeating or floating mid-page in
+    /// full-page captures
+    pub fn freeze_sticky(mut self) -> Self {
+        self.freeze_sticky = true;
+        self
+    }
+
+    /// Capture the full page as printed on `paper`, with a guide where each
+    /// PDF page would end
+    pub fn paginate_preview(mut self, paper: PaperSize) -> Self {
+        self.paginate_preview = Some(paper);
+        self.capture = CaptureMode::FullPage;
+        self
+    }
+
+    /// Fit the viewport height to the page's content after load
+    pub fn auto_height(mut self) -> Self {
+        self.auto_height = true;
+        self
+    }
+
+    /// Cap the viewport height chosen by `auto_height`
+    pub fn max_height(mut self, max_height: u32) -> Self {
+        self.max_height = Some(max_height);
+        self
+    }
+
+    /// Emulate a touch-input device
+    pub fn touch(mut self) -> Self {
+        self.touch = true;
+        self
+    }
+
+    /// Capture only the given page region
+    pub fn clip(mut self, clip: ClipRegion) -> Self {
+        self.clip = Some(clip);
+        self
+    }
+
+    /// Paint every element matching `selector` over before capture
+    pub fn mask<S: Into<String>>(mut self, selector: S) -> Self {
+        self.mask_selectors.push(selector.into());
+        self
+    }
+
+    /// Set the CSS color masked elements are painted with
+    pub fn mask_color<S: Into<String>>(mut self, color: S) -> Self {
+        self.mask_color = color.into();
+        self
+    }
+
+    /// Scroll through the page before capture to trigger lazy-loaded content
+    pub fn trigger_lazy(mut self) -> Self {
+        self.trigger_lazy = true;
+        self
+    }
+
+    /// Set the lazy-load scroll step and per-step settle delay
+    pub fn lazy_scroll(mut self, step: u32, settle_ms: u64) -> Self {
+        self.lazy_scroll_step = Some(step);
+        self.lazy_settle_ms = settle_ms;
+        self
+    }
+
+    /// Emulate a device preset
+    pub fn device(mut self, device: DevicePreset) -> Self {
+        self.device = Some(device);
+        self
+    }
+
+    /// Disable animations, transitions, the text caret and video playback
+    /// before capture, emulate `prefers-reduced-motion: reduce` and hide
+    /// scrollbars
+    pub fn stabilize(mut self) -> Self {
+        self.stabilize = true;
+        self
+    }
+
+    /// Emulate `prefers-color-scheme`
+    pub fn color_scheme(mut self, scheme: ColorScheme) -> Self {
+        self.color_scheme = Some(scheme);
+        self
+    }
+
+    /// Hide scrollbars so they neither show nor take space in captures
+    pub fn hide_scrollbars(mut self) -> Self {
+        self.hide_scrollbars = true;
+        self
+    }
+
+    /// Capture the page as it renders without JavaScript
+    pub fn disable_javascript(mut self) -> Self {
+        self.disable_javascript = true;
+        self
+    }
+
+    /// Leave the scroll position as the page set it when the URL has a
+    /// `#fragment`
+    pub fn no_anchor_scroll(mut self) -> Self {
+        self.anchor_scroll = false;
+        self
+    }
+
+    /// Add a script that runs before any page script in every new document
+    pub fn init_script<S: Into<String>>(mut self, source: S) -> Self {
+        self.init_scripts.push(source.into());
+        self
+    }
+
+    /// Quantize PNG output to a dithered palette of at most `colors` colors
+    pub fn png_colors(mut self, colors: u16) -> Self {
+        self.png_colors = Some(colors);
+        self
+    }
+
+    /// Write PNG output as 8-bit grayscale
+    pub fn grayscale(mut self) -> Self {
+        self.grayscale = true;
+        self
+    }
+
+    /// Restore cookies and web storage from a saved session before navigation
+    pub fn load_session(mut self, session: SessionState) -> Self {
+        self.session = Some(session);
+        self
+    }
+
+    /// Save cookies and web storage to a session file after capture
+    pub fn save_session<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.save_session = Some(path.into());
+        self
+    }
+
+    /// Set the compression used for TIFF output
+    pub fn tiff_compression(mut self, compression: TiffCompression) -> Self {
+        self.tiff_compression = compression;
+        self
+    }
+
+    /// Run image captures through a WebAssembly filter plugin before
+    /// encoding. Filters apply in the order they are added.
+    pub fn image_filter<P: AsRef<Path>>(mut self, plugin: P) -> Self {
+        self.image_filters.push(plugin.as_ref().to_path_buf());
+        self
+    }
+
+    /// Answer HTTP basic auth challenges with these credentials
+    pub fn auth(mut self, auth: AuthConfig) -> Self {
+        self.auth = Some(auth);
+        self
+    }
+
+    /// Send an extra HTTP header with every request
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(name.into(), value.into());
+        self
+    }
+
+    /// Add a page interaction to run after navigation
+    pub fn step(mut self, step: Step) -> Self {
+        self.steps.push(step);
+        self
+    }
+
+    /// Abort every request for a class of resources, such as images
+    pub fn block_resource(mut self, resource: BlockedResource) -> Self {
+        if !self.block_resources.contains(&resource) {
+            self.block_resources.push(resource);
+        }
+        self
+    }
+
+    /// Abort requests matched by an ad and tracker filter list
+    pub fn adblock(mut self, filters: Arc<FilterList>) -> Self {
+        self.adblock = Some(filters);
+        self
+    }
+
+    /// Answer requests matching these rules with canned responses
+    pub fn mocks(mut self, mocks: Mocks) -> Self {
+        self.mocks = mocks;
+        self
+    }
+
+    /// Click away or hide cookie banners and other consent overlays
+    pub fn dismiss_overlays(mut self, dismissal: OverlayDismissal) -> Self {
+        self.dismiss_overlays = Some(dismissal);
+        self
+    }
+
+    /// Fail instead of capturing when the page returns a 4xx or 5xx status
+    pub fn fail_on_http_error(mut self) -> Self {
+        self.fail_on_http_error = true;
+        self
+    }
+
+    /// Fail instead of capturing when the page calls `console.error()`
+    pub fn fail_on_console_error(mut self) -> Self {
+        self.fail_on_console_error = true;
+        self
+    }
+
+    /// Fail instead of capturing when the page throws an uncaught exception
+    pub fn fail_on_js_exception(mut self) -> Self {
+        self.fail_on_js_exception = true;
+        self
+    }
+
+    /// Write the page's console messages and exceptions to `path` (JSON for
+    /// `.json` paths, text otherwise)
+    pub fn console_log<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.console_log = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Record the page's network requests and responses to a HAR 1.2 file
+    pub fn har<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.har = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Wait until no frame has had requests in flight (beyond
+    /// `idle.max_inflight`) for `idle.idle_time` before capturing
+    pub fn network_idle(mut self, idle: NetworkIdle) -> Self {
+        self.network_idle = Some(idle);
+        self
+    }
+
+    /// Whether PNG output is re-encoded with fewer colors after capture
+    pub fn reduces_png(&self) -> bool {
+        self.png_colors.is_some() || self.grayscale
+    }
+
+    /// Whether a lazy-load scroll pass runs before capture. Full-page captures
+    /// always scroll, since content below the fold is part of the output.
+    pub fn loads_lazy_content(&self) -> bool {
+        self.trigger_lazy || self.captures_full_page()
+    }
+
+    /// Whether the capture is of the full page: [`CaptureMode::FullPage`]
+    /// without a selector or clip region, which crop regardless of the mode
+    pub fn captures_full_page(&self) -> bool {
+        self.capture == CaptureMode::FullPage && self.selector.is_none() && self.clip.is_none()
+    }
+
+    /// Whether the capture is of the viewport as shown
+    pub fn captures_viewport(&self) -> bool {
+        self.capture == CaptureMode::Viewport && self.selector.is_none() && self.clip.is_none()
+    }
+
     /// Validate the options
     pub fn validate(&self) -> Result<()> {
         if self.width == 0 || self.height == 0 {
@@ -113,6 +571,21 @@ impl ScreenshotOptions {
             });
         }
 
+        if let Some(clip) = &self.clip {
+            if clip.width == 0 || clip.height == 0 {
+                return Err(WebshotError::config(format!(
+                    "Clip region must have a non-zero size, got: {}x{}",
+                    clip.width, clip.height
+                )));
+            }
+
+            if self.selector.is_some() {
+                return Err(WebshotError::config(
+                    "Clip region cannot be combined with an element selector".to_string(),
+                ));
+            }
+        }
+
         if let Some(quality) = self.quality {
             if !(1..=100).contains(&quality) {
                 return Err(WebshotError::config(format!(
@@ -128,18 +601,119 @@ impl ScreenshotOptions {
             ));
         }
 
+        if let Some(colors) = self.png_colors {
+            if !(2..=256).contains(&colors) {
+                return Err(WebshotError::config(format!(
+                    "PNG palette size must be between 2-256, got: {}",
+                    colors
+                )));
+            }
+
+            if self.grayscale {
+                return Err(WebshotError::config(
+                    "PNG palette quantization cannot be combined with grayscale output".to_string(),
+                ));
+            }
+        }
+
+        if self.freeze_sticky && self.capture != CaptureMode::FullPage {
+            return Err(WebshotError::config(
+                "Freezing sticky elements only applies to full-page captures".to_string(),
+            ));
+        }
+
+        if self.auto_height && self.capture == CaptureMode::FullPage {
+            return Err(WebshotError::config(
+                "Auto height fits the viewport and cannot be combined with full-page captures"
+                    .to_string(),
+            ));
+        }
+
+        if self.paginate_preview.is_some() && !self.captures_full_page() {
+            return Err(WebshotError::config(
+                "Page-break previews capture the full page, without a selector or clip region"
+                    .to_string(),
+            ));
+        }
+
+        if self.lazy_scroll_step == Some(0) {
+            return Err(WebshotError::config(
+                "Lazy-load scroll step must be greater than 0".to_string(),
+            ));
+        }
+
+        if self
+            .mask_selectors
+            .iter()
+            .any(|selector| selector.trim().is_empty())
+        {
+            return Err(WebshotError::config(
+                "Mask selectors cannot be empty".to_string(),
+            ));
+        }
+
+        if self.mask_color.trim().is_empty() {
+            return Err(WebshotError::config(
+                "Mask color cannot be empty".to_string(),
+            ));
+        }
+
         Ok(())
     }
 
-    /// Get device scale factor based on retina setting
+    /// Get device scale factor from the device preset or retina setting
     pub fn device_scale_factor(&self) -> f64 {
-        if self.retina {
+        if let Some(device) = &self.device {
+            device.device_scale_factor
+        } else if self.retina {
             2.0
         } else {
             1.0
         }
     }
 
+    /// Viewport size, taking a device preset over the configured dimensions.
+    /// Page-break previews use the printable width of their paper.
+    pub fn viewport_size(&self) -> (u32, u32) {
+        let (width, height) = match &self.device {
+            Some(device) => (device.width, device.height),
+            None => (self.width, self.height),
+        };
+        match self.paginate_preview {
+            Some(paper) => (paper.printable_size().0.round() as u32, height),
+            None => (width, height),
+        }
+    }
+
+    /// Viewport height fitting `content_height` CSS pixels, within
+    /// `1..=max_height`
+    pub fn fitted_height(&self, content_height: f64) -> u32 {
+        let max_height = self.max_height.unwrap_or(DEFAULT_MAX_HEIGHT).max(1);
+        (content_height.ceil().max(1.0) as u32).min(max_height)
+    }
+
+    /// Whether scrollbars should be hidden, as deterministic captures do
+    pub fn hides_scrollbars(&self) -> bool {
+        self.hide_scrollbars || self.stabilize
+    }
+
+    /// Whether the viewport should use mobile emulation
+    pub fn is_mobile(&self) -> bool {
+        self.device.is_some_and(|device| device.mobile)
+    }
+
+    /// Whether touch input should be emulated
+    pub fn emulates_touch(&self) -> bool {
+        self.touch || self.device.is_some_and(|device| device.touch)
+    }
+
+    /// User agent to send; an explicit user agent wins over the device preset
+    pub fn effective_user_agent(&self) -> Option<&str> {
+        self.user_agent
+            .as_deref()
+            .or(self.device.as_ref().map(|device| device.user_agent))
+    }
+
     /// Determine output format from file path
     pub fn output_format<P: AsRef<Path>>(&self, path: P) -> Result<ImageFormat> {
         let extension = path
@@ -154,6 +728,8 @@ impl ScreenshotOptions {
             "jpg" | "jpeg" => Ok(ImageFormat::Jpeg),
             "pdf" => Ok(ImageFormat::Pdf),
             "webp" => Ok(ImageFormat::WebP),
+            "gif" => Ok(ImageFormat::Gif),
+            "tif" | "tiff" => Ok(ImageFormat::Tiff),
             _ => Err(WebshotError::UnsupportedFormat { format: extension }),
         }
     }
@@ -165,6 +741,8 @@ pub enum ImageFormat {
     Png,
     Jpeg,
     WebP,
+    Gif,
+    Tiff,
     Pdf,
 }
 
@@ -175,6 +753,8 @@ impl ImageFormat {
             ImageFormat::Png => "png",
             ImageFormat::Jpeg => "jpg",
             ImageFormat::WebP => "webp",
+            ImageFormat::Gif => "gif",
+            ImageFormat::Tiff => "tiff",
             ImageFormat::Pdf => "pdf",
         }
     }
@@ -185,18 +765,154 @@ impl ImageFormat {
             ImageFormat::Png => "image/png",
             ImageFormat::Jpeg => "image/jpeg",
             ImageFormat::WebP => "image/webp",
+            ImageFormat::Gif => "image/gif",
+            ImageFormat::Tiff => "image/tiff",
             ImageFormat::Pdf => "application/pdf",
         }
     }
 
-    /// Check if this format supports quality settings
+    /// Check if this format supports quality settings. For GIF, quality
+    /// controls how carefully the 256-color palette is chosen.
     pub fn supports_quality(&self) -> bool {
-        matches!(self, ImageFormat::Jpeg | ImageFormat::WebP)
+        matches!(
+            self,
+            ImageFormat::Jpeg | ImageFormat::WebP | ImageFormat::Gif
+        )
     }
 
     /// Check if this format supports transparency
     pub fn supports_transparency(&self) -> bool {
-        matches!(self, ImageFormat::Png | ImageFormat::WebP)
+        matches!(
+            self,
+            ImageFormat::Png | ImageFormat::WebP | ImageFormat::Gif | ImageFormat::Tiff
+        )
+    }
+}
+
+/// Part of the page a capture without a selector or clip region covers
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CaptureMode {
+    /// What the viewport shows at its scroll position: `width` × `height`
+    /// CSS pixels, whatever the height of the page. Content below the fold
+    /// is left out and nothing is scrolled to load it.
+    #[default]
+    Viewport,
+    /// The whole document at its laid-out content size, after scrolling
+    /// through it once so lazy content is loaded
+    FullPage,
+}
+
+impl CaptureMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CaptureMode::Viewport => "viewport",
+            CaptureMode::FullPage => "full-page",
+        }
+    }
+
+    /// Mode chosen by a `capture` setting and the older `full_page` switch,
+    /// which is shorthand for [`CaptureMode::FullPage`]
+    pub fn resolve(capture: Option<CaptureMode>, full_page: bool) -> Result<Self> {
+        match (capture, full_page) {
+            (Some(CaptureMode::Viewport), true) => Err(WebshotError::config(
+                "capture: viewport cannot be combined with full_page".to_string(),
+            )),
+            (Some(mode), _) => Ok(mode),
+            (None, true) => Ok(CaptureMode::FullPage),
+            (None, false) => Ok(CaptureMode::Viewport),
+        }
+    }
+}
+
+impl fmt::Display for CaptureMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for CaptureMode {
+    type Err = WebshotError;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_str() {
+            "viewport" => Ok(Self::Viewport),
+            "full-page" | "full_page" | "fullpage" | "full" => Ok(Self::FullPage),
+            _ => Err(WebshotError::config(format!(
+                "Unknown capture mode: {}. Supported: viewport, full-page",
+                value
+            ))),
+        }
+    }
+}
+
+/// Compression applied to TIFF output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TiffCompression {
+    /// Uncompressed strips
+    None,
+    /// LZW, the most widely supported lossless TIFF compression
+    #[default]
+    Lzw,
+    /// Deflate (zlib), usually smaller than LZW
+    Deflate,
+    /// PackBits run-length encoding
+    Packbits,
+}
+
+impl FromStr for TiffCompression {
+    type Err = WebshotError;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_str() {
+            "none" => Ok(Self::None),
+            "lzw" => Ok(Self::Lzw),
+            "deflate" | "zip" => Ok(Self::Deflate),
+            "packbits" => Ok(Self::Packbits),
+            _ => Err(WebshotError::config(format!(
+                "Unknown TIFF compression: {}. Supported: none, lzw, deflate, packbits",
+                value
+            ))),
+        }
+    }
+}
+
+/// Value of the `prefers-color-scheme` media feature
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorScheme {
+    Light,
+    Dark,
+}
+
+impl ColorScheme {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ColorScheme::Light => "light",
+            ColorScheme::Dark => "dark",
+        }
+    }
+}
+
+impl fmt::Display for ColorScheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ColorScheme {
+    type Err = WebshotError;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_str() {
+            "light" => Ok(Self::Light),
+            "dark" => Ok(Self::Dark),
+            _ => Err(WebshotError::config(format!(
+                "Unknown color scheme: {}. Supported: light, dark",
+                value
+            ))),
+        }
     }
 }
 
@@ -216,7 +932,18 @@ mod tests {
             .retina()
             .quality(90)
             .wait(5)
-            .user_agent("Custom Agent");
+            .user_agent("Custom Agent")
+            .full_page()
+            .touch()
+            .disable_javascript()
+            .clip(ClipRegion {
+                x: 10,
+                y: 20,
+                width: 300,
+                height: 200,
+            })
+            .header("X-Preview", "1")
+            .step(Step::Click("#accept".to_string()));
 
         assert_eq!(options.width, 1920);
         assert_eq!(options.height, 1080);
@@ -226,8 +953,16 @@ mod tests {
         assert_eq!(options.timeout, 60);
         assert!(options.retina);
         assert_eq!(options.quality, Some(90));
+        assert_eq!(
+            options.headers.get("X-Preview").map(String::as_str),
+            Some("1")
+        );
         assert_eq!(options.wait, 5);
         assert_eq!(options.user_agent.as_deref(), Some("Custom Agent"));
+        assert!(options.disable_javascript);
+        assert_eq!(options.capture, CaptureMode::FullPage);
+        assert!(options.touch);
+        assert_eq!(options.clip.map(|clip| clip.width), Some(300));
     }
 
     #[test]
@@ -247,6 +982,156 @@ mod tests {
         assert!(options.validate().is_err());
     }
 
+    #[test]
+    fn test_capture_modes() {
+        // Captures are of the viewport unless asked otherwise
+        let options = ScreenshotOptions::new();
+        assert_eq!(options.capture, CaptureMode::Viewport);
+        assert!(options.captures_viewport() && !options.captures_full_page());
+
+        let options = ScreenshotOptions::new().capture(CaptureMode::FullPage);
+        assert_eq!(
+            ScreenshotOptions::new().full_page().capture,
+            options.capture
+        );
+        assert!(options.captures_full_page() && !options.captures_viewport());
+        // A selector or clip region crops whatever the mode
+        let element = options.clone().selector("#card");
+        assert!(!element.captures_full_page() && !element.captures_viewport());
+
+        assert!(options.clone().auto_height().validate().is_err());
+        assert!(options.freeze_sticky().validate().is_ok());
+        assert!(ScreenshotOptions::new().freeze_sticky().validate().is_err());
+        assert!(ScreenshotOptions::new().auto_height().validate().is_ok());
+
+        assert_eq!(
+            "full-page".parse::<CaptureMode>().unwrap(),
+            CaptureMode::FullPage
+        );
+        assert_eq!(
+            "Viewport".parse::<CaptureMode>().unwrap(),
+            CaptureMode::Viewport
+        );
+        assert!("page".parse::<CaptureMode>().is_err());
+        assert_eq!(CaptureMode::FullPage.to_string(), "full-page");
+
+        assert_eq!(
+            CaptureMode::resolve(None, false).unwrap(),
+            CaptureMode::Viewport
+        );
+        assert_eq!(
+            CaptureMode::resolve(None, true).unwrap(),
+            CaptureMode::FullPage
+        );
+        assert_eq!(
+            CaptureMode::resolve(Some(CaptureMode::FullPage), true).unwrap(),
+            CaptureMode::FullPage
+        );
+        assert!(CaptureMode::resolve(Some(CaptureMode::Viewport), true).is_err());
+    }
+
+    #[test]
+    fn test_lazy_content_loading() {
+        assert!(!ScreenshotOptions::new().loads_lazy_content());
+        assert!(ScreenshotOptions::new().full_page().loads_lazy_content());
+        assert!(!ScreenshotOptions::new()
+            .full_page()
+            .selector("h1")
+            .loads_lazy_content());
+        assert!(ScreenshotOptions::new()
+            .trigger_lazy()
+            .selector("h1")
+            .loads_lazy_content());
+
+        let options = ScreenshotOptions::new()
+            .trigger_lazy()
+            .lazy_scroll(400, 250);
+        assert_eq!(options.lazy_scroll_step, Some(400));
+        assert_eq!(options.lazy_settle_ms, 250);
+        assert!(options.validate().is_ok());
+        assert!(options.lazy_scroll(0, 250).validate().is_err());
+    }
+
+    #[test]
+    fn test_png_reduction_validation() {
+        assert!(!ScreenshotOptions::new().reduces_png());
+        assert!(ScreenshotOptions::new().grayscale().reduces_png());
+
+        let options = ScreenshotOptions::new().png_colors(256);
+        assert!(options.reduces_png());
+        assert!(options.validate().is_ok());
+
+        assert!(ScreenshotOptions::new().png_colors(1).validate().is_err());
+        assert!(ScreenshotOptions::new()
+            .png_colors(64)
+            .grayscale()
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn test_clip_region_parsing() {
+        assert_eq!(
+            "0, 100, 640,480".parse::<ClipRegion>().unwrap(),
+            ClipRegion {
+                x: 0,
+                y: 100,
+                width: 640,
+                height: 480,
+            }
+        );
+
+        assert!("0,0,640".parse::<ClipRegion>().is_err());
+        assert!("0,0,640,-1".parse::<ClipRegion>().is_err());
+        assert!("a,b,c,d".parse::<ClipRegion>().is_err());
+    }
+
+    #[test]
+    fn test_read_init_scripts() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let script = temp_dir.path().join("freeze-date.js");
+        std::fs::write(&script, "Date.now = () => 0;").unwrap();
+
+        let sources = read_init_scripts(&[&script]).unwrap();
+        assert_eq!(sources, vec!["Date.now = () => 0;".to_string()]);
+
+        let missing = temp_dir.path().join("missing.js");
+        let error = read_init_scripts(&[&missing]).unwrap_err();
+        assert!(error.to_string().contains("Failed to read init script"));
+    }
+
+    #[test]
+    fn test_clip_validation() {
+        let clip = ClipRegion {
+            x: 0,
+            y: 0,
+            width: 100,
+            height: 100,
+        };
+        assert!(ScreenshotOptions::new().clip(clip).validate().is_ok());
+
+        let empty = ClipRegion { width: 0, ..clip };
+        assert!(ScreenshotOptions::new().clip(empty).validate().is_err());
+
+        let with_selector = ScreenshotOptions::new().clip(clip).selector("h1");
+        assert!(with_selector.validate().is_err());
+    }
+
+    #[test]
+    fn test_mask_validation() {
+        let options = ScreenshotOptions::new().mask(".ad").mask("time");
+        assert_eq!(options.mask_selectors, vec![".ad", "time"]);
+        assert_eq!(options.mask_color, DEFAULT_MASK_COLOR);
+        assert!(options.validate().is_ok());
+
+        assert!(ScreenshotOptions::new().mask(" ").validate().is_err());
+        assert!(ScreenshotOptions::new()
+            .mask(".ad")
+            .mask_color("")
+            .validate()
+            .is_err());
+    }
+
     #[test]
     fn test_output_format_detection() {
         let options = ScreenshotOptions::new();
@@ -271,8 +1156,16 @@ mod tests {
             options.output_format(PathBuf::from("test.webp")).unwrap(),
             ImageFormat::WebP
         );
+        assert_eq!(
+            options.output_format(PathBuf::from("test.gif")).unwrap(),
+            ImageFormat::Gif
+        );
+        assert_eq!(
+            options.output_format(PathBuf::from("scan.TIF")).unwrap(),
+            ImageFormat::Tiff
+        );
 
-        assert!(options.output_format(PathBuf::from("test.gif")).is_err());
+        assert!(options.output_format(PathBuf::from("test.bmp")).is_err());
         assert!(options.output_format(PathBuf::from("test")).is_err());
     }
 
@@ -285,6 +1178,55 @@ mod tests {
         assert_eq!(retina_options.device_scale_factor(), 2.0);
     }
 
+    #[test]
+    fn test_device_preset_overrides_viewport() {
+        let device = DevicePreset::find("Pixel 7").unwrap();
+        let options = ScreenshotOptions::new().viewport(1920, 1080).device(device);
+
+        assert_eq!(options.viewport_size(), (412, 915));
+        assert_eq!(options.device_scale_factor(), 2.625);
+        assert!(options.is_mobile());
+        assert!(options.emulates_touch());
+        assert_eq!(options.effective_user_agent(), Some(device.user_agent));
+
+        let custom = options.user_agent("Custom Agent");
+        assert_eq!(custom.effective_user_agent(), Some("Custom Agent"));
+
+        let desktop = ScreenshotOptions::new();
+        assert_eq!(desktop.viewport_size(), (1280, 800));
+        assert!(!desktop.is_mobile());
+        assert_eq!(desktop.effective_user_agent(), None);
+    }
+
+    #[test]
+    fn test_stabilize_hides_scrollbars() {
+        assert!(!ScreenshotOptions::new().hides_scrollbars());
+        assert!(ScreenshotOptions::new()
+            .hide_scrollbars()
+            .hides_scrollbars());
+        assert!(ScreenshotOptions::new().stabilize().hides_scrollbars());
+    }
+
+    #[test]
+    fn test_fitted_height_is_capped() {
+        let options = ScreenshotOptions::new().auto_height();
+        assert_eq!(options.fitted_height(2400.4), 2401);
+        assert_eq!(options.fitted_height(0.0), 1);
+        assert_eq!(options.fitted_height(50_000.0), DEFAULT_MAX_HEIGHT);
+        assert_eq!(options.max_height(3000).fitted_height(50_000.0), 3000);
+    }
+
+    #[test]
+    fn test_paginate_preview_uses_printable_width() {
+        let options = ScreenshotOptions::new()
+            .viewport(1280, 720)
+            .paginate_preview(PaperSize::A4);
+        assert!(options.captures_full_page());
+        assert_eq!(options.viewport_size(), (717, 720));
+        assert!(options.validate().is_ok());
+        assert!(options.selector(".content").validate().is_err());
+    }
+
     #[test]
     fn test_image_format() {
         assert_eq!(ImageFormat::Png.extension(), "png");
@@ -306,5 +1248,25 @@ mod tests {
         assert!(!ImageFormat::Jpeg.supports_transparency());
         assert!(!ImageFormat::Pdf.supports_transparency());
         assert!(ImageFormat::WebP.supports_transparency());
+
+        assert_eq!(ImageFormat::Gif.extension(), "gif");
+        assert_eq!(ImageFormat::Tiff.extension(), "tiff");
+        assert_eq!(ImageFormat::Tiff.mime_type(), "image/tiff");
+        assert!(ImageFormat::Gif.supports_quality());
+        assert!(!ImageFormat::Tiff.supports_quality());
+    }
+
+    #[test]
+    fn test_tiff_compression_parsing() {
+        assert_eq!(TiffCompression::default(), TiffCompression::Lzw);
+        assert_eq!(
+            "Deflate".parse::<TiffCompression>().unwrap(),
+            TiffCompression::Deflate
+        );
+        assert_eq!(
+            "none".parse::<TiffCompression>().unwrap(),
+            TiffCompression::None
+        );
+        assert!("jpeg".parse::<TiffCompression>().is_err());
     }
 }
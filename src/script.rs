@@ -0,0 +1,312 @@
+use crate::comparison::{ComparisonAlgorithm, ComparisonOptions, ImageComparator};
+use crate::error::{Result, WebshotError};
+use crate::repl::{Repl, ReplCommand, WaitTarget};
+use rhai::{Engine, EvalAltResult, FLOAT, INT};
+use std::path::{Path, PathBuf};
+use tokio::sync::{mpsc, oneshot};
+
+/// Functions available to `webshot script` files. `goto` and `eval` are
+/// reserved words in Rhai, hence `navigate` and `evaluate`.
+pub const SCRIPT_FUNCTIONS: &str = "\
+Functions:
+  navigate(url) -> final URL     Navigate to a page
+  wait(selector) / wait(seconds) Wait for an element or a number of seconds
+  click(selector)                Click an element
+  exists(selector) -> bool       Whether an element is on the page
+  url() -> string                Current page URL
+  shot(file) / shot(file, sel)   Capture the page, or one element
+  pdf(file)                      Print the page to PDF
+  evaluate(javascript) -> string Evaluate JavaScript, returning the result as JSON
+  compare(a, b[, algorithm])     Similarity of two images (0.0-1.0)
+  assert(condition, message)     Fail the script unless condition is true";
+
+/// Rhai operations a script may run, so an endless loop fails instead of
+/// hanging the run. Time spent in session calls such as waits is not counted.
+pub const MAX_SCRIPT_OPERATIONS: u64 = 10_000_000;
+
+type ScriptCall = (ReplCommand, oneshot::Sender<Result<Option<String>>>);
+
+/// Handle through which script functions drive the session tab. Calls block
+/// the script thread until the session has run the command.
+#[derive(Clone)]
+struct SessionHandle {
+    calls: mpsc::Sender<ScriptCall>,
+}
+
+impl SessionHandle {
+    fn call(
+        &self,
+        command: ReplCommand,
+    ) -> std::result::Result<Option<String>, Box<EvalAltResult>> {
+        let (reply, response) = oneshot::channel();
+        self.calls
+            .blocking_send((command, reply))
+            .map_err(|_| "browser session has ended".to_string())?;
+        response
+            .blocking_recv()
+            .map_err(|_| "browser session has ended".to_string())?
+            .map_err(|e| e.to_string().into())
+    }
+
+    fn eval(&self, script: String) -> std::result::Result<String, Box<EvalAltResult>> {
+        Ok(self.call(ReplCommand::Eval(script))?.unwrap_or_default())
+    }
+}
+
+fn script_error(e: impl std::fmt::Display) -> Box<EvalAltResult> {
+    e.to_string().into()
+}
+
+fn compare(
+    first: &str,
+    second: &str,
+    algorithm: &str,
+) -> std::result::Result<FLOAT, Box<EvalAltResult>> {
+    let options = ComparisonOptions::new().algorithm(
+        algorithm
+            .parse::<ComparisonAlgorithm>()
+            .map_err(script_error)?,
+    );
+    let result = ImageComparator::compare_files(first, second, &options).map_err(script_error)?;
+    Ok(result.similarity)
+}
+
+/// Engine with the session functions registered
+fn engine(session: SessionHandle) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_SCRIPT_OPERATIONS);
+
+    let s = session.clone();
+    engine.register_fn("navigate", move |url: &str| {
+        Ok::<_, Box<EvalAltResult>>(
+            s.call(ReplCommand::Goto(url.to_string()))?
+                .unwrap_or_default(),
+        )
+    });
+    let s = session.clone();
+    engine.register_fn("wait", move |selector: &str| {
+        s.call(ReplCommand::Wait(WaitTarget::Selector(
+            selector.to_string(),
+        )))
+        .map(drop)
+    });
+    let s = session.clone();
+    engine.register_fn("wait", move |seconds: INT| {
+        let seconds = u64::try_from(seconds).map_err(|_| {
+            script_error(format!(
+                "wait seconds must not be negative, got {}",
+                seconds
+            ))
+        })?;
+        s.call(ReplCommand::Wait(WaitTarget::Seconds(seconds)))
+            .map(drop)
+    });
+    let s = session.clone();
+    engine.register_fn("click", move |selector: &str| {
+        s.call(ReplCommand::Click(selector.to_string())).map(drop)
+    });
+    let s = session.clone();
+    engine.register_fn("exists", move |selector: &str| {
+        let selector = serde_json::to_string(selector).map_err(script_error)?;
+        Ok::<_, Box<EvalAltResult>>(
+            s.eval(format!("document.querySelector({}) !== null", selector))? == "true",
+        )
+    });
+    let s = session.clone();
+    engine.register_fn("url", move || {
+        let href = s.eval("location.href".to_string())?;
+        serde_json::from_str::<String>(&href).map_err(script_error)
+    });
+    let s = session.clone();
+    engine.register_fn("shot", move |output: &str| {
+        s.call(ReplCommand::Shot {
+            output: PathBuf::from(output),
+            selector: None,
+        })
+        .map(drop)
+    });
+    let s = session.clone();
+    engine.register_fn("shot", move |output: &str, selector: &str| {
+        s.call(ReplCommand::Shot {
+            output: PathBuf::from(output),
+            selector: Some(selector.to_string()),
+        })
+        .map(drop)
+    });
+    let s = session.clone();
+    engine.register_fn("pdf", move |output: &str| {
+        s.call(ReplCommand::Pdf(PathBuf::from(output))).map(drop)
+    });
+    engine.register_fn("evaluate", move |script: &str| {
+        session.eval(script.to_string())
+    });
+
+    engine.register_fn("compare", |first: &str, second: &str| {
+        compare(first, second, "pixel-diff")
+    });
+    engine.register_fn("compare", compare);
+    engine.register_fn("assert", |condition: bool, message: &str| {
+        if condition {
+            Ok(())
+        } else {
+            Err(script_error(format!("assertion failed: {}", message)))
+        }
+    });
+
+    engine
+}
+
+/// Compile and run a script on the current thread, sending session commands
+/// to `calls`
+fn evaluate(source: &str, path: &Path, calls: mpsc::Sender<ScriptCall>) -> Result<()> {
+    let engine = engine(SessionHandle { calls });
+    let ast = engine
+        .compile(source)
+        .map_err(|e| WebshotError::script(format!("{}: {}", path.display(), e)))?;
+    engine
+        .run_ast(&ast)
+        .map_err(|e| WebshotError::script(format!("{}: {}", path.display(), e)))
+}
+
+/// Run a Rhai script file against a session. Session messages (saved files)
+/// are printed to stderr; the script's `print` output goes to stdout.
+pub async fn run_script(repl: &mut Repl<'_>, path: &Path) -> Result<()> {
+    let source = std::fs::read_to_string(path)
+        .map_err(|e| WebshotError::script(format!("Failed to read {}: {}", path.display(), e)))?;
+
+    let mut run = ScriptRun::start(path, source);
+    while let Some((command, reply)) = run.next().await {
+        let announce = matches!(command, ReplCommand::Shot { .. } | ReplCommand::Pdf(_));
+        let result = repl.execute(command).await;
+        if let (true, Ok(Some(message))) = (announce, &result) {
+            eprintln!("{}", message);
+        }
+        let _ = reply.send(result);
+    }
+    run.finish().await
+}
+
+/// A script evaluating on a blocking thread, yielding its session commands
+struct ScriptRun {
+    path: PathBuf,
+    commands: mpsc::Receiver<ScriptCall>,
+    script: tokio::task::JoinHandle<Result<()>>,
+}
+
+impl ScriptRun {
+    fn start(path: &Path, source: String) -> Self {
+        let (calls, commands) = mpsc::channel(1);
+        let script_path = path.to_path_buf();
+        let script = tokio::task::spawn_blocking(move || evaluate(&source, &script_path, calls));
+        Self {
+            path: path.to_path_buf(),
+            commands,
+            script,
+        }
+    }
+
+    /// Next command to run and the channel for its result, or `None` once
+    /// the script has finished
+    async fn next(&mut self) -> Option<ScriptCall> {
+        self.commands.recv().await
+    }
+
+    async fn finish(self) -> Result<()> {
+        self.script
+            .await
+            .map_err(|e| WebshotError::script(format!("{}: {}", self.path.display(), e)))?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run a script against a fake session where `#banner` is present
+    async fn run_fake(source: &str) -> (Result<()>, Vec<ReplCommand>) {
+        let mut commands = Vec::new();
+        let mut run = ScriptRun::start(Path::new("flow.rhai"), source.to_string());
+        while let Some((command, reply)) = run.next().await {
+            commands.push(command.clone());
+            let _ = reply.send(match command {
+                ReplCommand::Goto(url) => Ok(Some(url)),
+                ReplCommand::Eval(script) if script.contains("#banner") => {
+                    Ok(Some("true".to_string()))
+                }
+                ReplCommand::Eval(script) if script == "location.href" => {
+                    Ok(Some("\"https://example.com/account\"".to_string()))
+                }
+                ReplCommand::Eval(_) => Ok(Some("false".to_string())),
+                ReplCommand::Click(selector) if selector == "#missing" => {
+                    Err(WebshotError::element_not_found(selector))
+                }
+                _ => Ok(None),
+            });
+        }
+        (run.finish().await, commands)
+    }
+
+    #[tokio::test]
+    async fn test_conditional_flow() {
+        let (result, commands) = run_fake(
+            r##"
+            navigate("https://example.com");
+            if exists("#banner") {
+                click("#login");
+                wait("#account");
+            }
+            if exists(".promo") {
+                click(".promo-close");
+            }
+            assert(url().ends_with("/account"), "not logged in");
+            shot("account.png", "main");
+            "##,
+        )
+        .await;
+
+        result.unwrap();
+        assert_eq!(commands.len(), 7);
+        assert_eq!(
+            commands[0],
+            ReplCommand::Goto("https://example.com".to_string())
+        );
+        assert_eq!(commands[2], ReplCommand::Click("#login".to_string()));
+        assert_eq!(
+            commands[3],
+            ReplCommand::Wait(WaitTarget::Selector("#account".to_string()))
+        );
+        assert_eq!(
+            commands[6],
+            ReplCommand::Shot {
+                output: PathBuf::from("account.png"),
+                selector: Some("main".to_string()),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failures_stop_the_script() {
+        let (result, commands) = run_fake(r##"click("#missing"); shot("never.png");"##).await;
+        assert!(result.unwrap_err().to_string().contains("#missing"));
+        assert_eq!(commands.len(), 1);
+
+        let (result, commands) = run_fake(r#"assert(exists(".promo"), "promo shown");"#).await;
+        let error = result.unwrap_err().to_string();
+        assert!(error.contains("flow.rhai") && error.contains("assertion failed: promo shown"));
+        assert_eq!(commands.len(), 1);
+
+        let (result, commands) = run_fake(r#"navigate("https://example.com"); loop {}"#).await;
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Too many operations"));
+        assert_eq!(commands.len(), 1);
+
+        let (result, commands) = run_fake("navigate(").await;
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .starts_with("Script error: flow.rhai"));
+        assert!(commands.is_empty());
+    }
+}
@@ -0,0 +1,1333 @@
+//! HTTP API for `webshot serve`.
+//!
+//! Every capture request checks a browser out of a shared [`BrowserPool`],
+//! so pages render in an already running Chrome. Requests are JSON bodies:
+//!
+//! - `POST /screenshot` answers with the image, and `GET /capture` does the
+//!   same for the fields of [`ScreenshotRequest`] given as query parameters;
+//!   both add the page's HTTP status as [`PAGE_STATUS_HEADER`]
+//! - `POST /pdf` answers with the PDF
+//! - `POST /text` answers with the page text
+//! - `POST /compare` compares a base64 image against a second image or a
+//!   fresh capture of `url`, answering with the comparison result as JSON
+//! - `POST /jobs` queues captures to run in the background (see
+//!   [`crate::jobs`]), answering 429 with `Retry-After` while the queue is
+//!   full; `GET /jobs/{id}` reports their state and `GET /jobs/{id}/result`
+//!   returns their output
+//! - `GET /health` reports pool usage
+//! - `GET /healthz` answers 503 unless a browser responds and the jobs
+//!   directory is writable (liveness), and `GET /readyz` also while the
+//!   server is draining or every browser is busy (readiness)
+//!
+//! Errors are answered as `{"error": "..."}` with a status matching the
+//! failure: 400 for bad requests, 503 while every browser is busy, 504 for
+//! page timeouts and 502 when the page could not be captured.
+//!
+//! With a [`UrlSigner`], every request except the health probes must carry the
+//! `expires` and `sig` query parameters `webshot sign-url` adds: unsigned or
+//! tampered requests get 403 and expired ones 401. `POST` requests must also
+//! sign the SHA-256 of their body (`webshot sign-url --body`), so a signed URL
+//! cannot be replayed with another capture.
+
+use crate::backend::{BrowserBackend, PdfOptions, TextOptions};
+use crate::browser::Browser;
+use crate::comparison::{ComparisonAlgorithm, ComparisonOptions, ImageComparator};
+use crate::devices::DevicePreset;
+use crate::error::{Result, WebshotError};
+use crate::health::HealthReport;
+use crate::jobs::{JobBody, JobOutput, JobQueue, JobRequest, JobState, JobSubmission};
+use crate::paper::PaperSize;
+use crate::pool::{BrowserPool, PoolStatus, Poolable, PooledBrowser};
+use crate::screenshot::{CaptureMode, ImageFormat, ScreenshotOptions};
+use crate::signing::{SignatureError, UrlSigner};
+use base64::Engine;
+use hyper::body::HttpBody;
+use hyper::header::{HeaderValue, CONTENT_LENGTH, CONTENT_TYPE, RETRY_AFTER};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncReadExt;
+use tracing::{info, warn};
+
+/// Largest request body accepted, enough for two full-page images in
+/// `/compare`
+pub const MAX_BODY_BYTES: usize = 32 * 1024 * 1024;
+
+/// Size of the chunks files are streamed to clients in
+const RESPONSE_CHUNK_BYTES: usize = 64 * 1024;
+
+/// Seconds clients are asked to wait before submitting to a full job queue
+pub const QUEUE_FULL_RETRY_AFTER_SECS: u64 = 30;
+
+/// Endpoints answered without a signature, so probes need no secret
+const UNSIGNED_PATHS: &[&str] = &["/health", "/healthz", "/readyz"];
+
+/// Response header carrying the HTTP status of a captured page's main
+/// document
+pub const PAGE_STATUS_HEADER: &str = "x-page-status";
+
+/// Body of `POST /screenshot`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScreenshotRequest {
+    /// Page to capture
+    pub url: String,
+    #[serde(default)]
+    pub width: Option<u32>,
+    #[serde(default)]
+    pub height: Option<u32>,
+    /// Image format: png (default), jpeg, webp, gif or tiff
+    #[serde(default)]
+    pub format: Option<String>,
+    /// Same as `"capture": "full-page"`
+    #[serde(default)]
+    pub full_page: bool,
+    /// Part of the page to capture: viewport (default) or full-page
+    #[serde(default)]
+    pub capture: Option<CaptureMode>,
+    #[serde(default)]
+    pub selector: Option<String>,
+    #[serde(default)]
+    pub javascript: Option<String>,
+    #[serde(default)]
+    pub wait_for: Option<String>,
+    /// Timeout in seconds
+    #[serde(default)]
+    pub timeout: Option<u64>,
+    /// Milliseconds to wait before capturing
+    #[serde(default)]
+    pub wait: Option<u64>,
+    #[serde(default)]
+    pub retina: bool,
+    #[serde(default)]
+    pub quality: Option<u8>,
+    /// Device preset name, e.g. "iPhone 14 Pro"
+    #[serde(default)]
+    pub device: Option<String>,
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    #[serde(default)]
+    pub stabilize: bool,
+    #[serde(default)]
+    pub hide_scrollbars: bool,
+    #[serde(default)]
+    pub disable_javascript: bool,
+    /// CSS selectors of elements painted over before capture
+    #[serde(default)]
+    pub mask_selectors: Vec<String>,
+    #[serde(default)]
+    pub mask_color: Option<String>,
+}
+
+impl ScreenshotRequest {
+    /// Capture options and image format for this request
+    pub fn options(&self) -> Result<(ScreenshotOptions, ImageFormat)> {
+        let defaults = ScreenshotOptions::default();
+        let mut options = ScreenshotOptions {
+            width: self.width.unwrap_or(defaults.width),
+            height: self.height.unwrap_or(defaults.height),
+            capture: CaptureMode::resolve(self.capture, self.full_page)?,
+            selector: self.selector.clone(),
+            javascript: self.javascript.clone(),
+            wait_for: self.wait_for.clone(),
+            timeout: self.timeout.unwrap_or(defaults.timeout),
+            wait: self.wait.unwrap_or(defaults.wait),
+            retina: self.retina,
+            quality: self.quality,
+            user_agent: self.user_agent.clone(),
+            headers: self.headers.clone(),
+            stabilize: self.stabilize,
+            hide_scrollbars: self.hide_scrollbars,
+            disable_javascript: self.disable_javascript,
+            mask_selectors: self.mask_selectors.clone(),
+            mask_color: self
+                .mask_color
+                .clone()
+                .unwrap_or(defaults.mask_color.clone()),
+            ..defaults
+        };
+        if let Some(device) = &self.device {
+            options = options.device(device.parse::<DevicePreset>()?);
+        }
+        options.validate()?;
+
+        let extension = self.format.as_deref().unwrap_or("png");
+        let format = options.output_format(format!("capture.{}", extension))?;
+        if format == ImageFormat::Pdf {
+            return Err(WebshotError::config(
+                "Use POST /pdf for PDF output".to_string(),
+            ));
+        }
+        Ok((options, format))
+    }
+}
+
+/// Body of `POST /pdf`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PdfRequest {
+    /// Page to print
+    pub url: String,
+    #[serde(default)]
+    pub paper: PaperSize,
+    #[serde(default)]
+    pub landscape: bool,
+    #[serde(default)]
+    pub background: bool,
+    #[serde(default)]
+    pub scale: Option<f64>,
+    #[serde(default)]
+    pub javascript: Option<String>,
+    #[serde(default)]
+    pub wait_for: Option<String>,
+    /// Timeout in seconds
+    #[serde(default)]
+    pub timeout: Option<u64>,
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+}
+
+impl PdfRequest {
+    pub fn options(&self) -> Result<PdfOptions> {
+        let defaults = PdfOptions::default();
+        let scale = self.scale.unwrap_or(defaults.scale);
+        if !(0.1..=2.0).contains(&scale) {
+            return Err(WebshotError::config(format!(
+                "Scale must be between 0.1 and 2.0, got: {}",
+                scale
+            )));
+        }
+        Ok(PdfOptions {
+            paper: self.paper,
+            landscape: self.landscape,
+            background: self.background,
+            scale,
+            javascript: self.javascript.clone(),
+            wait_for: self.wait_for.clone(),
+            timeout: self.timeout.unwrap_or(defaults.timeout),
+            user_agent: self.user_agent.clone(),
+            headers: self.headers.clone(),
+            ..defaults
+        })
+    }
+}
+
+/// Body of `POST /text`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TextRequest {
+    /// Page to read
+    pub url: String,
+    #[serde(default)]
+    pub selector: Option<String>,
+    #[serde(default)]
+    pub javascript: Option<String>,
+    #[serde(default)]
+    pub wait_for: Option<String>,
+    /// Timeout in seconds
+    #[serde(default)]
+    pub timeout: Option<u64>,
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+}
+
+impl TextRequest {
+    pub fn options(&self) -> TextOptions {
+        let defaults = TextOptions::default();
+        TextOptions {
+            selector: self.selector.clone(),
+            javascript: self.javascript.clone(),
+            wait_for: self.wait_for.clone(),
+            timeout: self.timeout.unwrap_or(defaults.timeout),
+            user_agent: self.user_agent.clone(),
+            headers: self.headers.clone(),
+            ..defaults
+        }
+    }
+}
+
+/// Body of `POST /compare`: a base64 `expected` image compared against
+/// either a base64 `actual` image or a capture of `url`
+#[derive(Debug, Clone, Deserialize)]
+pub struct CompareRequest {
+    pub expected: String,
+    #[serde(default)]
+    pub actual: Option<String>,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub width: Option<u32>,
+    #[serde(default)]
+    pub height: Option<u32>,
+    #[serde(default)]
+    pub full_page: bool,
+    /// pixel-diff (default), ssim, ms-ssim, mse or psnr
+    #[serde(default)]
+    pub algorithm: Option<String>,
+    #[serde(default)]
+    pub threshold: Option<f64>,
+    /// Largest CIEDE2000 difference of matching pixels, for `delta-e`
+    #[serde(default)]
+    pub tolerance: Option<f64>,
+    #[serde(default)]
+    pub ignore_antialiasing: bool,
+}
+
+impl CompareRequest {
+    pub fn options(&self) -> Result<ComparisonOptions> {
+        let algorithm = match &self.algorithm {
+            Some(name) => name.parse::<ComparisonAlgorithm>()?,
+            None => ComparisonAlgorithm::default(),
+        };
+        // Plugins would let clients load arbitrary files from the server
+        if matches!(algorithm, ComparisonAlgorithm::Plugin(_)) {
+            return Err(WebshotError::config(
+                "Plugin algorithms are not available over HTTP".to_string(),
+            ));
+        }
+        let mut options = ComparisonOptions::new().algorithm(algorithm);
+        if let Some(threshold) = self.threshold {
+            options.threshold = threshold;
+        }
+        if let Some(tolerance) = self.tolerance {
+            options = options.delta_e_tolerance(tolerance);
+        }
+        if self.ignore_antialiasing {
+            options = options.ignore_antialiasing();
+        }
+        options.validate()?;
+        Ok(options)
+    }
+}
+
+/// Body of `GET /health`
+#[derive(Debug, Serialize)]
+struct HealthBody {
+    status: &'static str,
+    pool: PoolStatus,
+}
+
+/// Body of `GET /healthz` and `GET /readyz`
+#[derive(Debug, Serialize)]
+struct ProbeBody {
+    status: &'static str,
+    #[serde(flatten)]
+    report: HealthReport,
+    pool: PoolStatus,
+}
+
+/// A failed request and the status it is answered with
+#[derive(Debug)]
+struct ApiError {
+    status: StatusCode,
+    message: String,
+    /// Seconds to send as `Retry-After`
+    retry_after: Option<u64>,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+            retry_after: None,
+        }
+    }
+
+    fn into_response(self) -> Response<Body> {
+        let mut response =
+            json_response(self.status, &serde_json::json!({ "error": self.message }));
+        if let Some(seconds) = self.retry_after {
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(seconds));
+        }
+        response
+    }
+}
+
+impl From<WebshotError> for ApiError {
+    fn from(error: WebshotError) -> Self {
+        let status = match &error {
+            WebshotError::Config(_)
+            | WebshotError::Json(_)
+            | WebshotError::Url(_)
+            | WebshotError::InvalidViewport { .. }
+            | WebshotError::UnsupportedFormat { .. }
+            | WebshotError::Image(_) => StatusCode::BAD_REQUEST,
+            WebshotError::QueueFull { .. } => {
+                return Self {
+                    retry_after: Some(QUEUE_FULL_RETRY_AFTER_SECS),
+                    ..Self::new(StatusCode::TOO_MANY_REQUESTS, error.to_string())
+                }
+            }
+            WebshotError::ElementNotFound { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            WebshotError::Timeout { .. } => StatusCode::GATEWAY_TIMEOUT,
+            WebshotError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::BAD_GATEWAY,
+        };
+        Self::new(status, error.to_string())
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+type ApiResult = std::result::Result<Response<Body>, ApiError>;
+
+/// Pooled browsers and background jobs behind the API
+pub struct Api<B: Poolable = Browser> {
+    pool: BrowserPool<B>,
+    jobs: JobQueue,
+    signer: Option<UrlSigner>,
+    draining: AtomicBool,
+}
+
+impl<B: BrowserBackend + Poolable> Api<B> {
+    pub fn new(pool: BrowserPool<B>, jobs: JobQueue) -> Self {
+        Self {
+            pool,
+            jobs,
+            signer: None,
+            draining: AtomicBool::new(false),
+        }
+    }
+
+    /// Fail readiness checks from now on, so load balancers stop sending
+    /// requests while in-flight ones finish, and stop starting jobs
+    pub fn drain(&self) {
+        self.draining.store(true, Ordering::SeqCst);
+        self.jobs.close();
+    }
+
+    /// Only answer requests signed by `signer`
+    pub fn with_signer(mut self, signer: UrlSigner) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+
+    /// Answer one API request
+    pub async fn handle(&self, request: Request<Body>) -> Response<Body> {
+        let started = Instant::now();
+        let method = request.method().clone();
+        let path = request.uri().path().to_string();
+
+        let result = match self.verify_signature(request).await {
+            Ok(request) => self.route(&method, &path, request).await,
+            Err(e) => Err(e),
+        };
+
+        let response = result.unwrap_or_else(|error| {
+            if error.status.is_server_error() {
+                warn!("{} {} failed: {}", method, path, error.message);
+            }
+            error.into_response()
+        });
+        info!(
+            "{} {} -> {} in {}ms",
+            method,
+            path,
+            response.status().as_u16(),
+            started.elapsed().as_millis()
+        );
+        response
+    }
+
+    /// Check the request's `expires` and `sig` parameters when a signer is
+    /// set, and for `POST` requests the signed hash of the body. The body is
+    /// read to hash it, so the request is handed back rebuilt around it.
+    async fn verify_signature(
+        &self,
+        request: Request<Body>,
+    ) -> std::result::Result<Request<Body>, ApiError> {
+        let Some(signer) = &self.signer else {
+            return Ok(request);
+        };
+        let uri = request.uri().clone();
+        if UNSIGNED_PATHS.contains(&uri.path()) {
+            return Ok(request);
+        }
+
+        let rejected = |e: SignatureError| {
+            let status = match e {
+                SignatureError::Expired => StatusCode::UNAUTHORIZED,
+                _ => StatusCode::FORBIDDEN,
+            };
+            ApiError::new(status, format!("Rejected request: {}", e))
+        };
+        let target = uri
+            .path_and_query()
+            .map_or(uri.path(), |target| target.as_str());
+        signer
+            .verify(target, chrono::Utc::now().timestamp())
+            .map_err(rejected)?;
+        if request.method() != Method::POST {
+            return Ok(request);
+        }
+
+        let (parts, body) = request.into_parts();
+        let body = read_body(&parts.headers, body).await?;
+        signer.verify_body(target, &body).map_err(rejected)?;
+        Ok(Request::from_parts(parts, Body::from(body)))
+    }
+
+    async fn route(&self, method: &Method, path: &str, request: Request<Body>) -> ApiResult {
+        match (method, path) {
+            (&Method::GET, "/health") => Ok(json_response(
+                StatusCode::OK,
+                &HealthBody {
+                    status: "ok",
+                    pool: self.pool.status(),
+                },
+            )),
+            (&Method::GET, "/healthz") => Ok(self.probe(false).await),
+            (&Method::GET, "/readyz") => Ok(self.probe(true).await),
+            (&Method::POST, "/screenshot") => match read_json(request).await {
+                Ok(request) => self.respond(JobRequest::Screenshot(request)).await,
+                Err(e) => Err(e),
+            },
+            (&Method::GET, "/capture") => match read_query(request.uri().query()) {
+                Ok(request) => self.respond(JobRequest::Screenshot(request)).await,
+                Err(e) => Err(e),
+            },
+            (&Method::POST, "/pdf") => match read_json(request).await {
+                Ok(request) => self.respond(JobRequest::Pdf(request)).await,
+                Err(e) => Err(e),
+            },
+            (&Method::POST, "/text") => match read_json(request).await {
+                Ok(request) => self.respond(JobRequest::Text(request)).await,
+                Err(e) => Err(e),
+            },
+            (&Method::POST, "/compare") => self.compare(request).await,
+            (&Method::POST, "/jobs") => self.submit_jobs(request).await,
+            (&Method::GET, job_path) if job_path.starts_with("/jobs/") => self.job(job_path).await,
+            (
+                _,
+                "/health" | "/healthz" | "/readyz" | "/screenshot" | "/capture" | "/pdf" | "/text"
+                | "/compare" | "/jobs",
+            ) => Err(ApiError::new(
+                StatusCode::METHOD_NOT_ALLOWED,
+                format!("{} is not allowed on {}", method, path),
+            )),
+            _ => Err(ApiError::new(
+                StatusCode::NOT_FOUND,
+                format!("No endpoint at {}", path),
+            )),
+        }
+    }
+
+    /// Liveness (or with `ready`, readiness) report, answered with 503 when
+    /// failing
+    async fn probe(&self, ready: bool) -> Response<Body> {
+        let report = HealthReport {
+            browser: self.pool.check_alive().await,
+            disk_writable: crate::health::disk_writable(self.jobs.dir()),
+            draining: self.draining.load(Ordering::SeqCst),
+        };
+        let pool = self.pool.status();
+        let passing = if ready {
+            report.is_ready() && pool.checked_out < pool.size
+        } else {
+            report.is_healthy()
+        };
+
+        let (status, label) = match (passing, ready) {
+            (true, _) => (StatusCode::OK, "ok"),
+            (false, false) => (StatusCode::SERVICE_UNAVAILABLE, "unhealthy"),
+            (false, true) => (StatusCode::SERVICE_UNAVAILABLE, "not ready"),
+        };
+        json_response(
+            status,
+            &ProbeBody {
+                status: label,
+                report,
+                pool,
+            },
+        )
+    }
+
+    /// Run queued jobs until the API is [drained](Api::drain) and the
+    /// running ones have finished
+    pub async fn run_jobs(&self) {
+        self.jobs.run(|request| self.execute(request)).await
+    }
+
+    async fn respond(&self, request: JobRequest) -> ApiResult {
+        let output = self.execute(request).await?;
+        let response = match output.body {
+            JobBody::Bytes(bytes) => bytes_response(&output.content_type, bytes),
+            JobBody::File(file) => {
+                let path = file.to_path_buf();
+                file_response(&output.content_type, &path, file).await?
+            }
+        };
+        Ok(with_page_status(response, output.http_status))
+    }
+
+    /// Capture with a pooled browser
+    async fn execute(&self, request: JobRequest) -> std::result::Result<JobOutput, ApiError> {
+        match request {
+            JobRequest::Screenshot(request) => {
+                let (options, format) = request.options()?;
+                let (image, http_status) =
+                    capture(&self.pool, &request.url, &options, format).await?;
+                Ok(JobOutput {
+                    content_type: format.mime_type().to_string(),
+                    body: JobBody::File(image),
+                    http_status,
+                })
+            }
+            JobRequest::Pdf(request) => {
+                let options = request.options()?;
+                let file = temp_output(ImageFormat::Pdf)?;
+
+                let browser = checkout(&self.pool).await?;
+                browser.pdf(&request.url, file.path(), &options).await?;
+                drop(browser);
+
+                Ok(JobOutput {
+                    content_type: ImageFormat::Pdf.mime_type().to_string(),
+                    body: JobBody::File(file.into_temp_path()),
+                    http_status: None,
+                })
+            }
+            JobRequest::Text(request) => {
+                let browser = checkout(&self.pool).await?;
+                let text = browser
+                    .extract_text(&request.url, &request.options())
+                    .await?;
+                Ok(JobOutput {
+                    content_type: "text/plain; charset=utf-8".to_string(),
+                    body: JobBody::Bytes(text.into_bytes()),
+                    http_status: None,
+                })
+            }
+        }
+    }
+
+    async fn compare(&self, request: Request<Body>) -> ApiResult {
+        let request: CompareRequest = read_json(request).await?;
+        let options = request.options()?;
+        let expected = decode_image(&request.expected, "expected")?;
+
+        let actual = match (&request.actual, &request.url) {
+            (Some(actual), None) => decode_image(actual, "actual")?,
+            (None, Some(url)) => {
+                let capture_request = ScreenshotRequest {
+                    url: url.clone(),
+                    width: request.width.or(Some(expected.width())),
+                    height: request.height.or(Some(expected.height())),
+                    full_page: request.full_page,
+                    ..Default::default()
+                };
+                let (capture_options, format) = capture_request.options()?;
+                let (png, _) = capture(&self.pool, url, &capture_options, format).await?;
+                image::open(&png).map_err(WebshotError::from)?
+            }
+            _ => {
+                return Err(ApiError::new(
+                    StatusCode::BAD_REQUEST,
+                    "Give exactly one of \"actual\" or \"url\"",
+                ))
+            }
+        };
+
+        let result = ImageComparator::compare_images(&expected, &actual, &options)?;
+        Ok(json_response(StatusCode::OK, &result))
+    }
+
+    /// Queue one job (a request object) or several (an array), answering
+    /// with their ids
+    async fn submit_jobs(&self, request: Request<Body>) -> ApiResult {
+        let body: serde_json::Value = read_json(request).await?;
+        let batch = body.is_array();
+        let submissions: Vec<JobSubmission> = if batch {
+            serde_json::from_value(body)
+        } else {
+            serde_json::from_value(body).map(|request| vec![request])
+        }
+        .map_err(WebshotError::from)?;
+
+        let ids = self.jobs.submit(submissions)?;
+        let body = if batch {
+            serde_json::json!({ "ids": ids })
+        } else {
+            serde_json::json!({ "id": ids[0] })
+        };
+        Ok(json_response(StatusCode::ACCEPTED, &body))
+    }
+
+    /// `GET /jobs/{id}` and `GET /jobs/{id}/result`
+    async fn job(&self, path: &str) -> ApiResult {
+        let rest = path.trim_start_matches("/jobs/");
+        let (id, want_result) = match rest.strip_suffix("/result") {
+            Some(id) => (id, true),
+            None => (rest, false),
+        };
+        let job = self.jobs.get(id).ok_or_else(|| {
+            ApiError::new(StatusCode::NOT_FOUND, format!("No job with id {}", id))
+        })?;
+
+        if !want_result {
+            let mut body = serde_json::to_value(&job).map_err(WebshotError::from)?;
+            if job.state == JobState::Done {
+                body["result"] = format!("/jobs/{}/result", job.id).into();
+            }
+            return Ok(json_response(StatusCode::OK, &body));
+        }
+
+        match (job.state, &job.content_type) {
+            (JobState::Done, Some(content_type)) => Ok(with_page_status(
+                file_response(content_type, &self.jobs.result_path(&job.id), ()).await?,
+                job.http_status,
+            )),
+            (JobState::Failed, _) => Err(ApiError::new(
+                StatusCode::CONFLICT,
+                format!(
+                    "Job {} failed: {}",
+                    job.id,
+                    job.error.as_deref().unwrap_or("unknown error")
+                ),
+            )),
+            _ => Err(ApiError::new(
+                StatusCode::CONFLICT,
+                format!("Job {} has not finished", job.id),
+            )),
+        }
+    }
+}
+
+/// Capture `url` with a pooled browser, returning the temporary file
+/// holding the encoded image and the page's HTTP status
+async fn capture<B>(
+    pool: &BrowserPool<B>,
+    url: &str,
+    options: &ScreenshotOptions,
+    format: ImageFormat,
+) -> std::result::Result<(tempfile::TempPath, Option<u16>), ApiError>
+where
+    B: BrowserBackend + Poolable,
+{
+    let file = temp_output(format)?;
+    let browser = checkout(pool).await?;
+    let written = browser.capture(url, file.path(), options).await?;
+    drop(browser);
+
+    if written.path != file.path() {
+        tokio::fs::rename(&written.path, file.path())
+            .await
+            .map_err(WebshotError::from)?;
+    }
+    Ok((file.into_temp_path(), written.status))
+}
+
+/// Take a browser, answering 503 when all stay busy past the checkout
+/// timeout
+async fn checkout<B: Poolable>(
+    pool: &BrowserPool<B>,
+) -> std::result::Result<PooledBrowser<'_, B>, ApiError> {
+    pool.checkout()
+        .await
+        .map_err(|e| ApiError::new(StatusCode::SERVICE_UNAVAILABLE, e.to_string()))
+}
+
+/// Temporary file that a capture is written to, removed on drop
+fn temp_output(format: ImageFormat) -> Result<tempfile::NamedTempFile> {
+    Ok(tempfile::Builder::new()
+        .prefix("webshot-serve-")
+        .suffix(&format!(".{}", format.extension()))
+        .tempfile()?)
+}
+
+async fn read_json<T: DeserializeOwned>(
+    request: Request<Body>,
+) -> std::result::Result<T, ApiError> {
+    let (parts, body) = request.into_parts();
+    let body = read_body(&parts.headers, body).await?;
+    Ok(serde_json::from_slice(&body).map_err(WebshotError::from)?)
+}
+
+/// Read a request body of at most [`MAX_BODY_BYTES`]
+async fn read_body(
+    headers: &hyper::HeaderMap,
+    body: Body,
+) -> std::result::Result<hyper::body::Bytes, ApiError> {
+    let too_large = || {
+        ApiError::new(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("Request bodies are limited to {} bytes", MAX_BODY_BYTES),
+        )
+    };
+    let declared = headers
+        .get(CONTENT_LENGTH)
+        .and_then(|length| length.to_str().ok()?.parse::<usize>().ok());
+    if declared.is_some_and(|length| length > MAX_BODY_BYTES) {
+        return Err(too_large());
+    }
+
+    // Chunked bodies carry no length, so stop reading once past the limit
+    let mut body = body;
+    let mut received = Vec::with_capacity(declared.unwrap_or(0));
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, e.to_string()))?;
+        if received.len() + chunk.len() > MAX_BODY_BYTES {
+            return Err(too_large());
+        }
+        received.extend_from_slice(&chunk);
+    }
+    Ok(received.into())
+}
+
+/// Parse the query string of `GET /capture`; the signature parameters are
+/// ignored as unknown fields
+fn read_query<T: DeserializeOwned>(query: Option<&str>) -> std::result::Result<T, ApiError> {
+    serde_urlencoded::from_str(query.unwrap_or("")).map_err(|e| {
+        ApiError::new(
+            StatusCode::BAD_REQUEST,
+            format!("Invalid query parameters: {}", e),
+        )
+    })
+}
+
+fn decode_image(encoded: &str, field: &str) -> std::result::Result<image::DynamicImage, ApiError> {
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(encoded.trim())
+        .map_err(|e| {
+            ApiError::new(
+                StatusCode::BAD_REQUEST,
+                format!("\"{}\" is not valid base64: {}", field, e),
+            )
+        })?;
+    image::load_from_memory(&bytes).map_err(|e| {
+        ApiError::new(
+            StatusCode::BAD_REQUEST,
+            format!("\"{}\" is not a readable image: {}", field, e),
+        )
+    })
+}
+
+fn json_response<T: Serialize>(status: StatusCode, body: &T) -> Response<Body> {
+    let json = serde_json::to_vec(body).expect("API responses serialize");
+    let mut response = bytes_response("application/json", json);
+    *response.status_mut() = status;
+    response
+}
+
+/// Add the captured page's HTTP status, when there is one
+fn with_page_status(mut response: Response<Body>, status: Option<u16>) -> Response<Body> {
+    if let Some(status) = status {
+        response
+            .headers_mut()
+            .insert(PAGE_STATUS_HEADER, HeaderValue::from(status));
+    }
+    response
+}
+
+fn bytes_response(content_type: &str, bytes: Vec<u8>) -> Response<Body> {
+    body_response(content_type, Body::from(bytes))
+}
+
+fn body_response(content_type: &str, body: Body) -> Response<Body> {
+    let mut response = Response::new(body);
+    if let Ok(content_type) = HeaderValue::from_str(content_type) {
+        response.headers_mut().insert(CONTENT_TYPE, content_type);
+    }
+    response
+}
+
+/// Stream the file at `path` to the client a chunk at a time, keeping
+/// `guard` (the temporary file it may be) alive until it has been sent
+async fn file_response<G: Send + 'static>(
+    content_type: &str,
+    path: &Path,
+    guard: G,
+) -> std::result::Result<Response<Body>, ApiError> {
+    let mut file = tokio::fs::File::open(path)
+        .await
+        .map_err(WebshotError::from)?;
+    let length = file.metadata().await.map_err(WebshotError::from)?.len();
+
+    let (mut sender, body) = Body::channel();
+    let path = path.to_path_buf();
+    tokio::spawn(async move {
+        let _guard = guard;
+        let mut buffer = vec![0; RESPONSE_CHUNK_BYTES];
+        loop {
+            match file.read(&mut buffer).await {
+                Ok(0) => break,
+                Ok(read) => {
+                    let chunk = hyper::body::Bytes::copy_from_slice(&buffer[..read]);
+                    if sender.send_data(chunk).await.is_err() {
+                        // The client went away
+                        break;
+                    }
+                }
+                Err(e) => {
+                    warn!("Failed to stream {}: {}", path.display(), e);
+                    sender.abort();
+                    break;
+                }
+            }
+        }
+    });
+
+    let mut response = body_response(content_type, body);
+    response
+        .headers_mut()
+        .insert(CONTENT_LENGTH, HeaderValue::from(length));
+    Ok(response)
+}
+
+/// Serve the API on `addr` and run its jobs until SIGTERM or Ctrl-C. The
+/// server then [drains](Api::drain): it keeps answering for `drain_delay`
+/// while `/readyz` fails, stops listening once in-flight requests and
+/// running jobs have finished, and leaves queued jobs for the next start
+/// with the same jobs directory.
+pub async fn serve(addr: SocketAddr, api: Arc<Api<Browser>>, drain_delay: Duration) -> Result<()> {
+    let service_api = Arc::clone(&api);
+    let make_service = make_service_fn(move |_connection| {
+        let api = Arc::clone(&service_api);
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let api = Arc::clone(&api);
+                async move { Ok::<_, Infallible>(api.handle(request).await) }
+            }))
+        }
+    });
+
+    let server = Server::try_bind(&addr)
+        .map_err(|e| WebshotError::config(format!("Cannot listen on {}: {}", addr, e)))?
+        .serve(make_service);
+    info!("Listening on http://{}", server.local_addr());
+
+    let shutdown = async {
+        crate::health::shutdown_signal().await;
+        info!(
+            "Draining for {}s before closing the listener",
+            drain_delay.as_secs()
+        );
+        api.drain();
+        tokio::time::sleep(drain_delay).await;
+    };
+    let served = async {
+        let served = server.with_graceful_shutdown(shutdown).await;
+        // Also stops the job workers when the server failed
+        api.drain();
+        served
+    };
+    let (served, ()) = tokio::join!(served, api.run_jobs());
+    served.map_err(|e| WebshotError::Io(std::io::Error::other(e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::Capture;
+    use crate::signing::with_body_hash;
+    use std::path::Path;
+
+    /// Writes a small image for every capture
+    struct FakeBrowser;
+
+    impl Poolable for FakeBrowser {
+        fn is_alive(&self) -> bool {
+            true
+        }
+
+        fn recycle(&self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    impl BrowserBackend for FakeBrowser {
+        fn engine(&self) -> &str {
+            "fake"
+        }
+
+        async fn capture(
+            &self,
+            url: &str,
+            output_path: &Path,
+            options: &ScreenshotOptions,
+        ) -> Result<Capture> {
+            if url.contains("slow") {
+                return Err(WebshotError::timeout("page load"));
+            }
+            image::RgbImage::from_pixel(options.width, options.height, image::Rgb([255; 3]))
+                .save(output_path)?;
+            Ok(Capture {
+                path: output_path.to_path_buf(),
+                status: Some(if url.contains("missing") { 404 } else { 200 }),
+            })
+        }
+
+        async fn pdf(&self, _url: &str, output_path: &Path, _options: &PdfOptions) -> Result<()> {
+            std::fs::write(output_path, b"%PDF-1.4")?;
+            Ok(())
+        }
+
+        async fn extract_text(&self, url: &str, _options: &TextOptions) -> Result<String> {
+            Ok(format!("Text of {}", url))
+        }
+    }
+
+    fn api() -> Api<FakeBrowser> {
+        let pool = BrowserPool::with_launcher(crate::pool::PoolOptions::new().size(1), || {
+            Box::pin(async { Ok(FakeBrowser) })
+        });
+        Api::new(pool, JobQueue::open(None, Default::default()).unwrap())
+    }
+
+    fn post(path: &str, body: serde_json::Value) -> Request<Body> {
+        Request::builder()
+            .method(Method::POST)
+            .uri(path)
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    async fn body(response: Response<Body>) -> Vec<u8> {
+        hyper::body::to_bytes(response.into_body())
+            .await
+            .unwrap()
+            .to_vec()
+    }
+
+    fn png(width: u32, height: u32, color: u8) -> String {
+        let mut bytes = Vec::new();
+        image::DynamicImage::ImageRgb8(image::RgbImage::from_pixel(
+            width,
+            height,
+            image::Rgb([color; 3]),
+        ))
+        .write_to(
+            &mut std::io::Cursor::new(&mut bytes),
+            image::ImageFormat::Png,
+        )
+        .unwrap();
+        base64::engine::general_purpose::STANDARD.encode(bytes)
+    }
+
+    #[tokio::test]
+    async fn test_captures_are_answered_with_their_bytes() {
+        let api = api();
+        let response = api.handle(
+            post(
+                "/screenshot",
+                serde_json::json!({"url": "https://example.com", "width": 40, "height": 30, "format": "jpeg"}),
+            ),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_TYPE], "image/jpeg");
+        assert_eq!(response.headers()[PAGE_STATUS_HEADER], "200");
+        let length = response.headers()[CONTENT_LENGTH].clone();
+        let bytes = body(response).await;
+        assert_eq!(length, bytes.len().to_string());
+        let image = image::load_from_memory(&bytes).unwrap();
+        assert_eq!((image.width(), image.height()), (40, 30));
+
+        let response = api
+            .handle(post(
+                "/pdf",
+                serde_json::json!({"url": "https://example.com", "paper": "a4"}),
+            ))
+            .await;
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/pdf");
+        assert_eq!(body(response).await, b"%PDF-1.4");
+
+        let response = api
+            .handle(post(
+                "/text",
+                serde_json::json!({"url": "https://example.com"}),
+            ))
+            .await;
+        assert_eq!(body(response).await, b"Text of https://example.com");
+        assert_eq!(api.pool.status().checked_out, 0);
+    }
+
+    #[tokio::test]
+    async fn test_compare_against_image_or_capture() {
+        let api = api();
+        let response = api
+            .handle(post(
+                "/compare",
+                serde_json::json!({"expected": png(8, 8, 255), "actual": png(8, 8, 0)}),
+            ))
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let result: serde_json::Value = serde_json::from_slice(&body(response).await).unwrap();
+        assert_eq!(result["similar"], false);
+
+        // The capture takes the expected image's size
+        let response = api
+            .handle(post(
+                "/compare",
+                serde_json::json!({"expected": png(8, 6, 255), "url": "https://example.com"}),
+            ))
+            .await;
+        let result: serde_json::Value = serde_json::from_slice(&body(response).await).unwrap();
+        assert_eq!(result["similar"], true);
+        assert_eq!(result["total_pixels"], 48);
+    }
+
+    #[tokio::test]
+    async fn test_failures_map_to_statuses() {
+        let api = api();
+        let status = |request| async { api.handle(request).await.status() };
+
+        assert_eq!(
+            status(post("/screenshot", serde_json::json!({"width": 10}))).await,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            status(post(
+                "/screenshot",
+                serde_json::json!({"url": "https://example.com", "format": "bmp"})
+            ))
+            .await,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            status(post(
+                "/screenshot",
+                serde_json::json!({"url": "https://slow.example.com"})
+            ))
+            .await,
+            StatusCode::GATEWAY_TIMEOUT
+        );
+        assert_eq!(
+            status(post(
+                "/compare",
+                serde_json::json!({"expected": png(2, 2, 0), "algorithm": "plugin:x.wasm", "actual": png(2, 2, 0)})
+            ))
+            .await,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            status(post("/missing", serde_json::json!({}))).await,
+            StatusCode::NOT_FOUND
+        );
+
+        let response = api
+            .handle(Request::get("/screenshot").body(Body::empty()).unwrap())
+            .await;
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        let error: serde_json::Value = serde_json::from_slice(&body(response).await).unwrap();
+        assert!(error["error"].as_str().unwrap().contains("GET"));
+    }
+
+    #[tokio::test]
+    async fn test_chunked_bodies_stop_at_the_limit() {
+        // No Content-Length, and more data than the limit keeps coming
+        let (mut sender, request_body) = Body::channel();
+        tokio::spawn(async move {
+            let chunk = hyper::body::Bytes::from(vec![b' '; 1024 * 1024]);
+            while sender.send_data(chunk.clone()).await.is_ok() {}
+        });
+        let request = Request::post("/screenshot").body(request_body).unwrap();
+
+        let response = tokio::time::timeout(Duration::from_secs(10), api().handle(request))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn test_jobs_run_in_the_background() {
+        let api = api();
+        let response = api
+            .handle(post(
+                "/jobs",
+                serde_json::json!([
+                    {"kind": "text", "url": "https://example.com"},
+                    {"kind": "screenshot", "url": "https://slow.example.com"},
+                    {"kind": "screenshot", "url": "https://example.com/missing"},
+                ]),
+            ))
+            .await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        let submitted: serde_json::Value = serde_json::from_slice(&body(response).await).unwrap();
+        let ids: Vec<String> = serde_json::from_value(submitted["ids"].clone()).unwrap();
+
+        let get = |path: String| api.handle(Request::get(path).body(Body::empty()).unwrap());
+        assert_eq!(
+            get(format!("/jobs/{}/result", ids[0])).await.status(),
+            StatusCode::CONFLICT
+        );
+
+        let finished = async {
+            for id in &ids {
+                while !api.jobs.get(id).unwrap().state.is_finished() {
+                    tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+                }
+            }
+        };
+        tokio::select! {
+            _ = api.run_jobs() => unreachable!(),
+            _ = finished => {}
+        }
+
+        let status: serde_json::Value =
+            serde_json::from_slice(&body(get(format!("/jobs/{}", ids[0])).await).await).unwrap();
+        assert_eq!(status["state"], "done");
+        assert_eq!(status["result"], format!("/jobs/{}/result", ids[0]));
+        let result = get(format!("/jobs/{}/result", ids[0])).await;
+        assert_eq!(result.headers()[CONTENT_TYPE], "text/plain; charset=utf-8");
+        assert_eq!(body(result).await, b"Text of https://example.com");
+
+        let status: serde_json::Value =
+            serde_json::from_slice(&body(get(format!("/jobs/{}", ids[1])).await).await).unwrap();
+        assert_eq!(status["state"], "failed");
+        assert!(status["error"].as_str().unwrap().contains("page load"));
+
+        let status: serde_json::Value =
+            serde_json::from_slice(&body(get(format!("/jobs/{}", ids[2])).await).await).unwrap();
+        assert_eq!(status["http_status"], 404);
+        let result = get(format!("/jobs/{}/result", ids[2])).await;
+        assert_eq!(result.headers()[PAGE_STATUS_HEADER], "404");
+        assert_eq!(
+            get("/jobs/unknown".to_string()).await.status(),
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[tokio::test]
+    async fn test_signed_api_rejects_unsigned_and_expired_requests() {
+        let signer = UrlSigner::new("shared-secret");
+        let api = api().with_signer(UrlSigner::new("shared-secret"));
+        let request = serde_json::json!({"url": "https://example.com"});
+        let now = chrono::Utc::now().timestamp();
+
+        for (path, status) in [
+            ("/text".to_string(), StatusCode::FORBIDDEN),
+            (
+                UrlSigner::new("other-secret").sign("/text", now + 60),
+                StatusCode::FORBIDDEN,
+            ),
+            (
+                signer.sign("/text", now + 60).replace("/text", "/pdf"),
+                StatusCode::FORBIDDEN,
+            ),
+            (signer.sign("/text", now - 1), StatusCode::UNAUTHORIZED),
+            (signer.sign("/text", now + 60), StatusCode::FORBIDDEN),
+            (
+                signer.sign(&with_body_hash("/text", b"{}"), now + 60),
+                StatusCode::FORBIDDEN,
+            ),
+            (
+                signer.sign(
+                    &with_body_hash("/text", request.to_string().as_bytes()),
+                    now + 60,
+                ),
+                StatusCode::OK,
+            ),
+        ] {
+            let response = api.handle(post(&path, request.clone())).await;
+            assert_eq!(response.status(), status, "{}", path);
+        }
+
+        let capture = signer.sign(
+            "/capture?url=https%3A%2F%2Fexample.com&width=40&height=30",
+            now + 60,
+        );
+        let response = api
+            .handle(Request::get(capture).body(Body::empty()).unwrap())
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let image = image::load_from_memory(&body(response).await).unwrap();
+        assert_eq!((image.width(), image.height()), (40, 30));
+        let tampered = signer
+            .sign("/capture?url=https%3A%2F%2Fexample.com", now + 60)
+            .replace("example.com", "evil.example");
+        let response = api
+            .handle(Request::get(tampered).body(Body::empty()).unwrap())
+            .await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let health = api
+            .handle(Request::get("/health").body(Body::empty()).unwrap())
+            .await;
+        assert_eq!(health.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_probes_report_health_and_readiness() {
+        let api = api();
+        let get = |path: &str| api.handle(Request::get(path).body(Body::empty()).unwrap());
+        assert_eq!(get("/healthz").await.status(), StatusCode::OK);
+        assert_eq!(get("/readyz").await.status(), StatusCode::OK);
+
+        let held = api.pool.checkout().await.unwrap();
+        let response = get("/readyz").await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let report: serde_json::Value = serde_json::from_slice(&body(response).await).unwrap();
+        assert_eq!(report["status"], "not ready");
+        assert_eq!(report["pool"]["checked_out"], 1);
+        assert_eq!(get("/healthz").await.status(), StatusCode::OK);
+        drop(held);
+
+        api.drain();
+        assert_eq!(
+            get("/readyz").await.status(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(get("/healthz").await.status(), StatusCode::OK);
+
+        let pool: BrowserPool<FakeBrowser> =
+            BrowserPool::with_launcher(crate::pool::PoolOptions::new(), || {
+                Box::pin(async { Err(WebshotError::config("no Chrome")) })
+            });
+        let broken = Api::new(pool, JobQueue::open(None, Default::default()).unwrap());
+        assert!(broken.pool.checkout().await.is_err());
+        let response = broken
+            .handle(Request::get("/healthz").body(Body::empty()).unwrap())
+            .await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let report: serde_json::Value = serde_json::from_slice(&body(response).await).unwrap();
+        assert_eq!(report["status"], "unhealthy");
+        assert_eq!(report["browser"], false);
+    }
+
+    #[tokio::test]
+    async fn test_full_job_queue_asks_clients_to_retry() {
+        let pool = BrowserPool::with_launcher(crate::pool::PoolOptions::new().size(1), || {
+            Box::pin(async { Ok(FakeBrowser) })
+        });
+        let jobs = JobQueue::open(
+            None,
+            crate::jobs::JobQueueOptions {
+                concurrency: 1,
+                capacity: 1,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let api = Api::new(pool, jobs);
+        let job = serde_json::json!({"kind": "text", "url": "https://example.com", "priority": 2});
+
+        let response = api.handle(post("/jobs", job.clone())).await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        let response = api.handle(post("/jobs", job)).await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(
+            response.headers()[RETRY_AFTER],
+            QUEUE_FULL_RETRY_AFTER_SECS.to_string()
+        );
+    }
+}
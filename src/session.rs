@@ -0,0 +1,277 @@
+use crate::error::{Result, WebshotError};
+use headless_chrome::protocol::cdp::Network::{Cookie, CookieParam, CookieSameSite};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Web storage entries for one origin
+pub type StorageEntries = BTreeMap<String, String>;
+
+/// Browser session state saved after a run and restored before navigation,
+/// so a login can be reused across screenshot runs
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SessionState {
+    /// Cookies across all domains
+    #[serde(default)]
+    pub cookies: Vec<SessionCookie>,
+    /// `localStorage` entries keyed by origin (e.g. `https://example.com`)
+    #[serde(default)]
+    pub local_storage: BTreeMap<String, StorageEntries>,
+    /// `sessionStorage` entries keyed by origin
+    #[serde(default)]
+    pub session_storage: BTreeMap<String, StorageEntries>,
+}
+
+/// A saved cookie
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionCookie {
+    pub name: String,
+    pub value: String,
+    pub domain: String,
+    pub path: String,
+    /// Expiry as seconds since the epoch; `None` for session cookies
+    #[serde(default)]
+    pub expires: Option<f64>,
+    #[serde(default)]
+    pub secure: bool,
+    #[serde(default)]
+    pub http_only: bool,
+    #[serde(default)]
+    pub same_site: Option<CookieSameSite>,
+}
+
+impl From<&Cookie> for SessionCookie {
+    fn from(cookie: &Cookie) -> Self {
+        Self {
+            name: cookie.name.clone(),
+            value: cookie.value.clone(),
+            domain: cookie.domain.clone(),
+            path: cookie.path.clone(),
+            expires: (!cookie.session).then_some(cookie.expires),
+            secure: cookie.secure,
+            http_only: cookie.http_only,
+            same_site: cookie.same_site.clone(),
+        }
+    }
+}
+
+impl SessionCookie {
+    /// CDP parameters that recreate this cookie
+    pub fn to_param(&self) -> CookieParam {
+        CookieParam {
+            name: self.name.clone(),
+            value: self.value.clone(),
+            url: None,
+            domain: Some(self.domain.clone()),
+            path: Some(self.path.clone()),
+            secure: Some(self.secure),
+            http_only: Some(self.http_only),
+            same_site: self.same_site.clone(),
+            expires: self.expires,
+            priority: None,
+            same_party: None,
+            source_scheme: None,
+            source_port: None,
+            partition_key: None,
+        }
+    }
+}
+
+/// Restores saved storage entries for the document's origin. Runs before any
+/// page script, so the page sees the restored values on first access.
+const RESTORE_STORAGE_SCRIPT: &str = r#"
+(() => {
+    const restore = (storage, entries) => {
+        for (const [key, value] of Object.entries(entries || {})) {
+            storage.setItem(key, value);
+        }
+    };
+    try {
+        restore(window.localStorage, __LOCAL__[location.origin]);
+        restore(window.sessionStorage, __SESSION__[location.origin]);
+    } catch (e) {
+        // Storage is unavailable on opaque origins such as about:blank
+    }
+})()
+"#;
+
+/// Returns the document's origin with its `localStorage` and `sessionStorage`
+/// entries as a JSON string
+pub const SNAPSHOT_STORAGE_SCRIPT: &str = r#"
+(() => {
+    const entries = (storage) => {
+        const result = {};
+        for (let i = 0; i < storage.length; i++) {
+            const key = storage.key(i);
+            result[key] = storage.getItem(key);
+        }
+        return result;
+    };
+    try {
+        return JSON.stringify({
+            origin: location.origin,
+            local: entries(window.localStorage),
+            session: entries(window.sessionStorage),
+        });
+    } catch (e) {
+        return JSON.stringify({ origin: location.origin, local: {}, session: {} });
+    }
+})()
+"#;
+
+/// Storage snapshot returned by `SNAPSHOT_STORAGE_SCRIPT`
+#[derive(Debug, Deserialize)]
+pub struct StorageSnapshot {
+    pub origin: String,
+    pub local: StorageEntries,
+    pub session: StorageEntries,
+}
+
+impl SessionState {
+    /// Load session state from a JSON file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            WebshotError::config(format!(
+                "Failed to read session file {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// Save session state as JSON, replacing the file atomically
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let temp_path = path.with_extension("tmp");
+        std::fs::write(&temp_path, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+
+    /// Replace the stored entries for a snapshot's origin. Opaque origins
+    /// (`null`) are ignored since their storage cannot be restored.
+    pub fn record_storage(&mut self, snapshot: StorageSnapshot) {
+        if snapshot.origin == "null" {
+            return;
+        }
+
+        for (storage, entries) in [
+            (&mut self.local_storage, snapshot.local),
+            (&mut self.session_storage, snapshot.session),
+        ] {
+            if entries.is_empty() {
+                storage.remove(&snapshot.origin);
+            } else {
+                storage.insert(snapshot.origin.clone(), entries);
+            }
+        }
+    }
+
+    /// Script that restores saved storage entries in every new document, or
+    /// `None` when no storage was saved
+    pub fn restore_storage_script(&self) -> Result<Option<String>> {
+        if self.local_storage.is_empty() && self.session_storage.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(
+            RESTORE_STORAGE_SCRIPT
+                .replace("__LOCAL__", &serde_json::to_string(&self.local_storage)?)
+                .replace(
+                    "__SESSION__",
+                    &serde_json::to_string(&self.session_storage)?,
+                ),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn snapshot(origin: &str, local: &[(&str, &str)]) -> StorageSnapshot {
+        StorageSnapshot {
+            origin: origin.to_string(),
+            local: local
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            session: StorageEntries::new(),
+        }
+    }
+
+    #[test]
+    fn test_session_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("state.json");
+
+        let mut state = SessionState::default();
+        state.cookies.push(SessionCookie {
+            name: "sid".to_string(),
+            value: "abc".to_string(),
+            domain: ".example.com".to_string(),
+            path: "/".to_string(),
+            expires: None,
+            secure: true,
+            http_only: true,
+            same_site: Some(CookieSameSite::Lax),
+        });
+        state.record_storage(snapshot("https://example.com", &[("token", "t1")]));
+        state.save(&path).unwrap();
+
+        assert_eq!(SessionState::load(&path).unwrap(), state);
+        assert!(SessionState::load(temp_dir.path().join("missing.json"))
+            .unwrap_err()
+            .to_string()
+            .contains("Failed to read session file"));
+    }
+
+    #[test]
+    fn test_record_storage_replaces_origin_entries() {
+        let mut state = SessionState::default();
+        state.record_storage(snapshot("https://example.com", &[("a", "1")]));
+        state.record_storage(snapshot("https://other.example", &[("b", "2")]));
+        state.record_storage(snapshot("https://example.com", &[]));
+        state.record_storage(snapshot("null", &[("c", "3")]));
+
+        assert_eq!(state.local_storage.len(), 1);
+        assert!(state.local_storage.contains_key("https://other.example"));
+    }
+
+    #[test]
+    fn test_restore_storage_script_embeds_entries() {
+        assert!(SessionState::default()
+            .restore_storage_script()
+            .unwrap()
+            .is_none());
+
+        let mut state = SessionState::default();
+        state.record_storage(snapshot("https://example.com", &[("token", "it's")]));
+        let script = state.restore_storage_script().unwrap().unwrap();
+
+        assert!(script.contains(r#"{"https://example.com":{"token":"it's"}}"#));
+        assert!(!script.contains("__SESSION__"));
+    }
+
+    #[test]
+    fn test_cookie_param_keeps_domain_and_expiry() {
+        let cookie = SessionCookie {
+            name: "sid".to_string(),
+            value: "abc".to_string(),
+            domain: "example.com".to_string(),
+            path: "/app".to_string(),
+            expires: Some(1_900_000_000.0),
+            secure: false,
+            http_only: false,
+            same_site: None,
+        };
+        let param = cookie.to_param();
+
+        assert_eq!(param.domain.as_deref(), Some("example.com"));
+        assert_eq!(param.path.as_deref(), Some("/app"));
+        assert_eq!(param.expires, Some(1_900_000_000.0));
+    }
+}
@@ -0,0 +1,286 @@
+use crate::error::{Result, WebshotError};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine as _;
+use ring::{digest, hmac};
+use std::fmt;
+
+/// Query parameter holding the expiry time in Unix seconds
+pub const EXPIRES_PARAM: &str = "expires";
+/// Query parameter holding the signature
+pub const SIGNATURE_PARAM: &str = "sig";
+/// Query parameter binding a signed URL to one request body
+pub const BODY_HASH_PARAM: &str = "body_sha256";
+
+/// Why a signed request target was rejected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignatureError {
+    /// No `expires` or `sig` parameter
+    Missing,
+    /// The `expires` or `sig` parameter could not be decoded
+    Malformed(String),
+    /// The expiry time has passed
+    Expired,
+    /// The signature does not match the request target
+    Mismatch,
+    /// The request body is not the one whose hash was signed
+    BodyMismatch,
+}
+
+impl fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignatureError::Missing => write!(
+                f,
+                "request is not signed (missing `{}` or `{}`)",
+                EXPIRES_PARAM, SIGNATURE_PARAM
+            ),
+            SignatureError::Malformed(what) => write!(f, "malformed signature: {}", what),
+            SignatureError::Expired => f.write_str("signed URL has expired"),
+            SignatureError::Mismatch => f.write_str("signature does not match"),
+            SignatureError::BodyMismatch => write!(
+                f,
+                "request body does not match the signed `{}`",
+                BODY_HASH_PARAM
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SignatureError {}
+
+/// Base64url SHA-256 of a request body, as sent in [`BODY_HASH_PARAM`]
+pub fn body_hash(body: &[u8]) -> String {
+    URL_SAFE_NO_PAD.encode(digest::digest(&digest::SHA256, body))
+}
+
+/// Add a [`BODY_HASH_PARAM`] parameter for `body` to a URL or request target,
+/// so that once signed it only authorizes requests with that exact body
+pub fn with_body_hash(url: &str, body: &[u8]) -> String {
+    let separator = if url.contains('?') { '&' } else { '?' };
+    format!(
+        "{}{}{}={}",
+        url,
+        separator,
+        BODY_HASH_PARAM,
+        body_hash(body)
+    )
+}
+
+/// Signs and verifies request targets (`/path?query`) with HMAC-SHA256 over a
+/// shared secret. The signature covers the path and every query parameter,
+/// including `expires`, in the order they appear. Targets carrying a
+/// [`BODY_HASH_PARAM`] also pin the request body; see
+/// [`UrlSigner::verify_body`].
+pub struct UrlSigner {
+    key: hmac::Key,
+}
+
+impl fmt::Debug for UrlSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UrlSigner").finish_non_exhaustive()
+    }
+}
+
+impl UrlSigner {
+    pub fn new(secret: impl AsRef<[u8]>) -> Self {
+        Self {
+            key: hmac::Key::new(hmac::HMAC_SHA256, secret.as_ref()),
+        }
+    }
+
+    /// Append `expires` and `sig` parameters to a request target such as
+    /// `/capture?url=https%3A%2F%2Fexample.com`
+    pub fn sign(&self, target: &str, expires: i64) -> String {
+        let separator = if target.contains('?') { '&' } else { '?' };
+        let unsigned = format!("{}{}{}={}", target, separator, EXPIRES_PARAM, expires);
+        let tag = hmac::sign(&self.key, unsigned.as_bytes());
+        format!(
+            "{}&{}={}",
+            unsigned,
+            SIGNATURE_PARAM,
+            URL_SAFE_NO_PAD.encode(tag.as_ref())
+        )
+    }
+
+    /// Sign a full `http(s)://host/path?query` URL or a bare request target
+    /// starting with `/`. Only the path and query are covered, so the URL
+    /// stays valid behind proxies that rewrite the host.
+    pub fn sign_url(&self, url: &str, expires: i64) -> Result<String> {
+        if url.starts_with('/') {
+            return Ok(self.sign(url, expires));
+        }
+
+        let parsed = url::Url::parse(url)
+            .map_err(|e| WebshotError::config(format!("Invalid URL to sign `{}`: {}", url, e)))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err(WebshotError::config(format!(
+                "Invalid URL to sign `{}`: expected an http(s) URL or a path starting with /",
+                url
+            )));
+        }
+        let target = match parsed.query() {
+            Some(query) => format!("{}?{}", parsed.path(), query),
+            None => parsed.path().to_string(),
+        };
+        Ok(format!(
+            "{}{}",
+            parsed.origin().ascii_serialization(),
+            self.sign(&target, expires)
+        ))
+    }
+
+    /// Check a signed request target at Unix time `now`
+    pub fn verify(&self, target: &str, now: i64) -> std::result::Result<(), SignatureError> {
+        let (path, query) = target.split_once('?').ok_or(SignatureError::Missing)?;
+
+        let mut signature = None;
+        let mut expires = None;
+        let mut signed_params = Vec::new();
+        for param in query.split('&') {
+            match param.split_once('=') {
+                Some((SIGNATURE_PARAM, value)) => signature = Some(value),
+                Some((EXPIRES_PARAM, value)) => {
+                    expires = Some(value);
+                    signed_params.push(param);
+                }
+                _ => signed_params.push(param),
+            }
+        }
+        let (Some(signature), Some(expires)) = (signature, expires) else {
+            return Err(SignatureError::Missing);
+        };
+
+        let signature = URL_SAFE_NO_PAD.decode(signature).map_err(|_| {
+            SignatureError::Malformed(format!("`{}` is not base64url", SIGNATURE_PARAM))
+        })?;
+        let expires: i64 = expires.parse().map_err(|_| {
+            SignatureError::Malformed(format!("`{}` is not a Unix timestamp", EXPIRES_PARAM))
+        })?;
+
+        let unsigned = format!("{}?{}", path, signed_params.join("&"));
+        hmac::verify(&self.key, unsigned.as_bytes(), &signature)
+            .map_err(|_| SignatureError::Mismatch)?;
+        if now >= expires {
+            return Err(SignatureError::Expired);
+        }
+        Ok(())
+    }
+
+    /// Check that a request target, already [verified](UrlSigner::verify),
+    /// signed the hash of `body`. Targets without a [`BODY_HASH_PARAM`] are
+    /// rejected, so a signed URL cannot be replayed with another body.
+    pub fn verify_body(
+        &self,
+        target: &str,
+        body: &[u8],
+    ) -> std::result::Result<(), SignatureError> {
+        let query = target.split_once('?').map_or("", |(_, query)| query);
+        let signed_hash = query
+            .split('&')
+            .find_map(|param| match param.split_once('=') {
+                Some((BODY_HASH_PARAM, value)) => Some(value),
+                _ => None,
+            })
+            .ok_or(SignatureError::BodyMismatch)?;
+
+        // The hash is covered by the signature, so it needs no constant-time
+        // comparison
+        if signed_hash == body_hash(body) {
+            Ok(())
+        } else {
+            Err(SignatureError::BodyMismatch)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TARGET: &str = "/capture?url=https%3A%2F%2Fexample.com&width=1200";
+
+    #[test]
+    fn test_signed_target_verifies_until_expiry() {
+        let signer = UrlSigner::new("shared-secret");
+        let signed = signer.sign(TARGET, 1_700_000_600);
+        assert!(signed.starts_with(&format!("{}&expires=1700000600&sig=", TARGET)));
+
+        assert_eq!(signer.verify(&signed, 1_700_000_000), Ok(()));
+        assert_eq!(
+            signer.verify(&signed, 1_700_000_600),
+            Err(SignatureError::Expired)
+        );
+        assert_eq!(
+            UrlSigner::new("other-secret").verify(&signed, 1_700_000_000),
+            Err(SignatureError::Mismatch)
+        );
+
+        let signed_path = signer.sign("/capture", 1_700_000_600);
+        assert!(signed_path.starts_with("/capture?expires="));
+        assert_eq!(signer.verify(&signed_path, 1_700_000_000), Ok(()));
+    }
+
+    #[test]
+    fn test_sign_full_url() {
+        let signer = UrlSigner::new("shared-secret");
+        let signed = signer
+            .sign_url(
+                &format!("https://shots.example.com{}", TARGET),
+                1_700_000_600,
+            )
+            .unwrap();
+        let target = signed.strip_prefix("https://shots.example.com").unwrap();
+        assert_eq!(target, signer.sign(TARGET, 1_700_000_600));
+        assert_eq!(signer.verify(target, 1_700_000_000), Ok(()));
+
+        assert!(signer.sign_url("ftp://example.com/capture", 0).is_err());
+        assert!(signer.sign_url("capture?url=x", 0).is_err());
+    }
+
+    #[test]
+    fn test_body_hash_pins_the_request_body() {
+        let signer = UrlSigner::new("shared-secret");
+        let body = br#"{"url": "https://example.com"}"#;
+        let signed = signer.sign(&with_body_hash("/screenshot", body), 1_700_000_600);
+        assert_eq!(signer.verify(&signed, 1_700_000_000), Ok(()));
+
+        assert_eq!(signer.verify_body(&signed, body), Ok(()));
+        assert_eq!(
+            signer.verify_body(&signed, br#"{"url": "https://evil.example"}"#),
+            Err(SignatureError::BodyMismatch)
+        );
+        assert_eq!(
+            signer.verify_body(&signer.sign("/screenshot", 1_700_000_600), body),
+            Err(SignatureError::BodyMismatch)
+        );
+    }
+
+    #[test]
+    fn test_tampered_targets_are_rejected() {
+        let signer = UrlSigner::new("shared-secret");
+        let signed = signer.sign(TARGET, 1_700_000_600);
+
+        for tampered in [
+            signed.replace("width=1200", "width=4000"),
+            signed.replace("expires=1700000600", "expires=1900000000"),
+            signed.replace("/capture", "/pdf"),
+            format!("{}&full_page=true", signed),
+        ] {
+            assert_eq!(
+                signer.verify(&tampered, 1_700_000_000),
+                Err(SignatureError::Mismatch),
+                "{}",
+                tampered
+            );
+        }
+
+        assert_eq!(
+            signer.verify(TARGET, 1_700_000_000),
+            Err(SignatureError::Missing)
+        );
+        assert!(matches!(
+            signer.verify(&format!("{}&expires=soon&sig=abc", TARGET), 0),
+            Err(SignatureError::Malformed(_))
+        ));
+    }
+}
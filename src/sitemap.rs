@@ -0,0 +1,217 @@
+use crate::config::validate_navigation_url;
+use crate::error::{Result, WebshotError};
+use std::collections::{HashSet, VecDeque};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// Maximum nesting of sitemap indexes followed during expansion
+const MAX_SITEMAP_DEPTH: usize = 5;
+
+/// Options controlling how a sitemap is expanded into page URLs
+#[derive(Debug, Clone, Default)]
+pub struct SitemapOptions {
+    /// Maximum number of page URLs to return
+    pub limit: Option<usize>,
+    /// Glob patterns a page must match (any of them) to be included
+    pub include: Vec<String>,
+    /// Request timeout in seconds for each sitemap fetch
+    pub timeout: u64,
+}
+
+/// Parsed contents of a single sitemap document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SitemapDocument {
+    /// A `<urlset>` listing page URLs
+    UrlSet(Vec<String>),
+    /// A `<sitemapindex>` listing further sitemaps
+    Index(Vec<String>),
+}
+
+/// Parse a sitemap or sitemap index XML document
+pub fn parse_sitemap(xml: &str) -> Result<SitemapDocument> {
+    let document = roxmltree::Document::parse(xml)
+        .map_err(|e| WebshotError::config(format!("Invalid sitemap XML: {}", e)))?;
+    let root = document.root_element();
+
+    let locations = root
+        .descendants()
+        .filter(|node| node.has_tag_name("loc"))
+        .filter_map(|node| node.text())
+        .map(|text| text.trim().to_string())
+        .filter(|text| !text.is_empty())
+        .collect();
+
+    match root.tag_name().name() {
+        "urlset" => Ok(SitemapDocument::UrlSet(locations)),
+        "sitemapindex" => Ok(SitemapDocument::Index(locations)),
+        other => Err(WebshotError::config(format!(
+            "Unsupported sitemap root element: <{}>. Expected <urlset> or <sitemapindex>",
+            other
+        ))),
+    }
+}
+
+/// Fetch a sitemap and recursively expand nested sitemap indexes into page URLs
+pub async fn fetch_sitemap_urls(
+    sitemap_url: &str,
+    options: &SitemapOptions,
+) -> Result<Vec<String>> {
+    validate_navigation_url(sitemap_url, "sitemap")?;
+
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(options.timeout.max(1)))
+        .build()?;
+
+    let mut queue = VecDeque::from([(sitemap_url.to_string(), 0usize)]);
+    let mut visited_sitemaps = HashSet::new();
+    let mut seen_pages = HashSet::new();
+    let mut pages = Vec::new();
+
+    while let Some((url, depth)) = queue.pop_front() {
+        if !visited_sitemaps.insert(url.clone()) {
+            continue;
+        }
+
+        info!("Fetching sitemap: {}", url);
+        let body = client
+            .get(&url)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+
+        match parse_sitemap(&body)? {
+            SitemapDocument::Index(sitemaps) => {
+                if depth >= MAX_SITEMAP_DEPTH {
+                    warn!("Sitemap index nesting too deep, skipping: {}", url);
+                    continue;
+                }
+                debug!("Sitemap index {} lists {} sitemaps", url, sitemaps.len());
+                queue.extend(sitemaps.into_iter().map(|child| (child, depth + 1)));
+            }
+            SitemapDocument::UrlSet(urls) => {
+                for page in urls {
+                    if !matches_any(&page, &options.include) || !seen_pages.insert(page.clone()) {
+                        continue;
+                    }
+                    pages.push(page);
+
+                    if options.limit.is_some_and(|limit| pages.len() >= limit) {
+                        return Ok(pages);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(pages)
+}
+
+/// Check a page URL against include globs; an empty list includes everything.
+///
+/// Patterns starting with a scheme are matched against the full URL, all
+/// others against the URL path.
+pub(crate) fn matches_any(url: &str, patterns: &[String]) -> bool {
+    if patterns.is_empty() {
+        return true;
+    }
+
+    let path = url::Url::parse(url)
+        .map(|parsed| parsed.path().to_string())
+        .unwrap_or_default();
+
+    patterns.iter().any(|pattern| {
+        if pattern.contains("://") {
+            glob_matches(pattern, url)
+        } else {
+            glob_matches(pattern, &path)
+        }
+    })
+}
+
+/// Minimal glob matching: `*` matches any run of characters, `?` one character
+pub(crate) fn glob_matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_urlset() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <url><loc>https://example.com/</loc></url>
+  <url><loc> https://example.com/blog/a?x=1&amp;y=2 </loc><lastmod>2024-01-01</lastmod></url>
+</urlset>"#;
+
+        assert_eq!(
+            parse_sitemap(xml).unwrap(),
+            SitemapDocument::UrlSet(vec![
+                "https://example.com/".to_string(),
+                "https://example.com/blog/a?x=1&y=2".to_string(),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_sitemap_index() {
+        let xml = r#"<sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <sitemap><loc>https://example.com/sitemap-blog.xml</loc></sitemap>
+</sitemapindex>"#;
+
+        assert_eq!(
+            parse_sitemap(xml).unwrap(),
+            SitemapDocument::Index(vec!["https://example.com/sitemap-blog.xml".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_non_sitemap_documents() {
+        assert!(parse_sitemap("<html><body/></html>").is_err());
+        assert!(parse_sitemap("not xml").is_err());
+    }
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("/blog/*", "/blog/post-1"));
+        assert!(glob_matches("/blog/*", "/blog/"));
+        assert!(!glob_matches("/blog/*", "/docs/blog/post"));
+        assert!(glob_matches("*/guide?", "/docs/guide1"));
+        assert!(glob_matches("*", ""));
+        assert!(!glob_matches("/a", "/ab"));
+    }
+
+    #[test]
+    fn test_matches_any_uses_path_or_full_url() {
+        let url = "https://example.com/blog/post";
+
+        assert!(matches_any(url, &[]));
+        assert!(matches_any(url, &["/blog/*".to_string()]));
+        assert!(matches_any(url, &["https://example.com/*".to_string()]));
+        assert!(!matches_any(url, &["/docs/*".to_string()]));
+    }
+}
@@ -0,0 +1,316 @@
+//! Windowed structural similarity (SSIM) and its multi-scale variant.
+//!
+//! SSIM compares the luminance, contrast and structure of two grayscale
+//! images in a Gaussian window (11×11, σ = 1.5, as in Wang et al.) around
+//! every pixel. The result is an [`SsimMap`] whose mean is the SSIM score.
+//! Windows are clamped at the image edges, so the map covers every pixel and
+//! shows where two images differ, not only how much.
+//!
+//! [`ms_ssim`] repeats the contrast and structure comparison on up to five
+//! successively halved copies of the images and weights the scales as Wang,
+//! Simoncelli and Bovik do. Differences then count by how visible they are
+//! both up close and at a glance, so a shifted layout scores lower than
+//! noise of the same energy.
+
+use image::GrayImage;
+use rayon::prelude::*;
+
+/// Pixels on each side of the center of the Gaussian window
+pub const WINDOW_RADIUS: usize = 5;
+
+/// Standard deviation of the Gaussian window, in pixels
+const WINDOW_SIGMA: f64 = 1.5;
+
+/// Stabilizes the luminance term of dark areas: (0.01 × 255)²
+const C1: f64 = 6.5025;
+
+/// Stabilizes the contrast-structure term of flat areas: (0.03 × 255)²
+const C2: f64 = 58.5225;
+
+/// Weights of the scales of MS-SSIM, finest first
+pub const MS_SSIM_WEIGHTS: [f64; 5] = [0.0448, 0.2856, 0.3001, 0.2363, 0.1333];
+
+/// Local SSIM of two images of the same size at every pixel
+#[derive(Debug, Clone, PartialEq)]
+pub struct SsimMap {
+    width: u32,
+    height: u32,
+    values: Vec<f64>,
+}
+
+impl SsimMap {
+    /// Compare two grayscale images of the same size
+    pub fn compute(img1: &GrayImage, img2: &GrayImage) -> Self {
+        let (a, b) = (Plane::from_gray(img1), Plane::from_gray(img2));
+        let (luminance, contrast_structure) = compare_planes(&a, &b);
+        Self {
+            width: img1.width(),
+            height: img1.height(),
+            values: luminance
+                .iter()
+                .zip(&contrast_structure)
+                .map(|(l, cs)| l * cs)
+                .collect(),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Local SSIM at (`x`, `y`), 1.0 where the images match
+    pub fn get(&self, x: u32, y: u32) -> f64 {
+        self.values[(y * self.width + x) as usize]
+    }
+
+    /// Local SSIM of every pixel, row by row
+    pub fn values(&self) -> &[f64] {
+        &self.values
+    }
+
+    /// Mean SSIM, the SSIM score of the images
+    pub fn mean(&self) -> f64 {
+        mean(&self.values)
+    }
+}
+
+/// Multi-scale SSIM of two grayscale images of the same size, over as many
+/// of the five scales as leave both sides at least a window wide
+pub fn ms_ssim(img1: &GrayImage, img2: &GrayImage) -> f64 {
+    if img1.width() == 0 || img1.height() == 0 {
+        return 1.0;
+    }
+    let window = 2 * WINDOW_RADIUS + 1;
+    let (mut a, mut b) = (Plane::from_gray(img1), Plane::from_gray(img2));
+    let mut terms = Vec::with_capacity(MS_SSIM_WEIGHTS.len());
+    for scale in 0..MS_SSIM_WEIGHTS.len() {
+        let (luminance, contrast_structure) = compare_planes(&a, &b);
+        let next = a.downsample();
+        let last = scale + 1 == MS_SSIM_WEIGHTS.len() || next.width.min(next.height) < window;
+        if last {
+            // The coarsest scale adds the luminance comparison
+            let ssim: Vec<f64> = luminance
+                .iter()
+                .zip(&contrast_structure)
+                .map(|(l, cs)| l * cs)
+                .collect();
+            terms.push(mean(&ssim));
+            break;
+        }
+        terms.push(mean(&contrast_structure));
+        (a, b) = (next, b.downsample());
+    }
+
+    let weights = &MS_SSIM_WEIGHTS[..terms.len()];
+    let total: f64 = weights.iter().sum();
+    terms
+        .iter()
+        .zip(weights)
+        .map(|(term, weight)| term.max(0.0).powf(weight / total))
+        .product()
+}
+
+fn mean(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 1.0;
+    }
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// Normalized weights of the Gaussian window along one axis
+fn gaussian_kernel() -> Vec<f64> {
+    let weights: Vec<f64> = (0..=2 * WINDOW_RADIUS)
+        .map(|i| {
+            let offset = i as f64 - WINDOW_RADIUS as f64;
+            (-(offset * offset) / (2.0 * WINDOW_SIGMA * WINDOW_SIGMA)).exp()
+        })
+        .collect();
+    let total: f64 = weights.iter().sum();
+    weights.iter().map(|weight| weight / total).collect()
+}
+
+/// Luminance and contrast-structure terms of SSIM at every pixel of two
+/// planes of the same size
+fn compare_planes(a: &Plane, b: &Plane) -> (Vec<f64>, Vec<f64>) {
+    let kernel = gaussian_kernel();
+    let blurred = |f: fn(f64, f64) -> f64| {
+        Plane {
+            width: a.width,
+            height: a.height,
+            data: a.data.iter().zip(&b.data).map(|(&x, &y)| f(x, y)).collect(),
+        }
+        .blur(&kernel)
+    };
+    let mean1 = a.blur(&kernel);
+    let mean2 = b.blur(&kernel);
+    let squares1 = blurred(|x, _| x * x);
+    let squares2 = blurred(|_, y| y * y);
+    let products = blurred(|x, y| x * y);
+
+    (0..a.data.len())
+        .into_par_iter()
+        .map(|i| {
+            let (mu1, mu2) = (mean1.data[i], mean2.data[i]);
+            let variance1 = squares1.data[i] - mu1 * mu1;
+            let variance2 = squares2.data[i] - mu2 * mu2;
+            let covariance = products.data[i] - mu1 * mu2;
+            (
+                (2.0 * mu1 * mu2 + C1) / (mu1 * mu1 + mu2 * mu2 + C1),
+                (2.0 * covariance + C2) / (variance1 + variance2 + C2),
+            )
+        })
+        .unzip()
+}
+
+/// Grayscale image as floating point values, row by row
+struct Plane {
+    width: usize,
+    height: usize,
+    data: Vec<f64>,
+}
+
+impl Plane {
+    fn from_gray(img: &GrayImage) -> Self {
+        Self {
+            width: img.width() as usize,
+            height: img.height() as usize,
+            data: img.as_raw().iter().map(|&value| f64::from(value)).collect(),
+        }
+    }
+
+    /// Convolve with `kernel` along both axes, repeating edge pixels. Rows
+    /// are convolved in parallel.
+    fn blur(&self, kernel: &[f64]) -> Self {
+        let radius = kernel.len() / 2;
+        let (width, height) = (self.width, self.height);
+        let mut horizontal = vec![0.0; self.data.len()];
+        let mut data = vec![0.0; self.data.len()];
+        if width == 0 {
+            return Self {
+                width,
+                height,
+                data,
+            };
+        }
+        horizontal
+            .par_chunks_mut(width)
+            .zip(self.data.par_chunks(width))
+            .for_each(|(out, row)| {
+                for (x, value) in out.iter_mut().enumerate() {
+                    *value = if x >= radius && x + radius < width {
+                        // Away from the edges the window is a plain slice
+                        row[x - radius..=x + radius]
+                            .iter()
+                            .zip(kernel)
+                            .map(|(input, weight)| weight * input)
+                            .sum()
+                    } else {
+                        kernel
+                            .iter()
+                            .enumerate()
+                            .map(|(k, weight)| {
+                                weight * row[(x + k).saturating_sub(radius).min(width - 1)]
+                            })
+                            .sum()
+                    };
+                }
+            });
+        data.par_chunks_mut(width).enumerate().for_each(|(y, out)| {
+            // Add whole source rows, each scaled by its weight
+            for (k, weight) in kernel.iter().enumerate() {
+                let source = (y + k).saturating_sub(radius).min(height - 1);
+                let row = &horizontal[source * width..(source + 1) * width];
+                for (value, &input) in out.iter_mut().zip(row) {
+                    *value += weight * input;
+                }
+            }
+        });
+        Self {
+            width,
+            height,
+            data,
+        }
+    }
+
+    /// Halve both sides by averaging 2×2 blocks, dropping an odd last row
+    /// or column
+    fn downsample(&self) -> Self {
+        let (width, height) = ((self.width / 2).max(1), (self.height / 2).max(1));
+        let at = |x: usize, y: usize| {
+            self.data[y.min(self.height - 1) * self.width + x.min(self.width - 1)]
+        };
+        let data = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                (at(2 * x, 2 * y)
+                    + at(2 * x + 1, 2 * y)
+                    + at(2 * x, 2 * y + 1)
+                    + at(2 * x + 1, 2 * y + 1))
+                    / 4.0
+            })
+            .collect();
+        Self {
+            width,
+            height,
+            data,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Luma;
+
+    /// Deterministic texture, so structure changes are measurable
+    fn texture(width: u32, height: u32) -> GrayImage {
+        GrayImage::from_fn(width, height, |x, y| {
+            Luma([((x * 37 + y * 91 + (x * y) % 13 * 17) % 256) as u8])
+        })
+    }
+
+    #[test]
+    fn test_ssim_map_localizes_changes() {
+        let original = texture(64, 64);
+        let map = SsimMap::compute(&original, &original);
+        assert!((map.mean() - 1.0).abs() < 1e-9);
+
+        let mut changed = original.clone();
+        for y in 40..48 {
+            for x in 40..48 {
+                changed.put_pixel(x, y, Luma([255]));
+            }
+        }
+        let map = SsimMap::compute(&original, &changed);
+        assert!(map.get(44, 44) < 0.5, "{}", map.get(44, 44));
+        // Beyond the window the images match again
+        assert!((map.get(10, 10) - 1.0).abs() < 1e-9);
+        assert!(map.mean() < 1.0 && map.mean() > 0.8, "{}", map.mean());
+    }
+
+    #[test]
+    fn test_ms_ssim() {
+        let original = texture(200, 200);
+        assert!((ms_ssim(&original, &original) - 1.0).abs() < 1e-9);
+
+        // A uniform brightness shift keeps the structure
+        let brighter = GrayImage::from_fn(200, 200, |x, y| {
+            Luma([original.get_pixel(x, y)[0].saturating_add(8)])
+        });
+        // Moving the content two pixels over does not
+        let shifted =
+            GrayImage::from_fn(200, 200, |x, y| *original.get_pixel(x.saturating_sub(2), y));
+        let (bright, moved) = (ms_ssim(&original, &brighter), ms_ssim(&original, &shifted));
+        assert!(bright > 0.9, "{}", bright);
+        assert!(moved < bright, "{} {}", moved, bright);
+
+        // Images too small for more scales still compare
+        let small = texture(12, 12);
+        assert!((ms_ssim(&small, &small) - 1.0).abs() < 1e-9);
+        assert!((ms_ssim(&GrayImage::new(1, 1), &GrayImage::new(1, 1)) - 1.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,185 @@
+use crate::error::{Result, WebshotError};
+use crate::picker::SELECTOR_FUNCTIONS;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Name of the page binding the step recorder reports through
+pub const RECORDER_BINDING: &str = "__webshotRecordStep";
+
+/// Reports trusted clicks, text entered into form fields and Enter key
+/// presses to the recorder binding as JSON-encoded steps. Field values are
+/// reported once per change, just before an Enter press or on `change`.
+const RECORDER_SCRIPT: &str = r#"
+(() => {
+    if (window.__webshotRecorderInstalled) return;
+    window.__webshotRecorderInstalled = true;
+
+    __SELECTOR_FUNCTIONS__
+
+    const record = (step) => {
+        try {
+            window.__BINDING__(JSON.stringify(step));
+        } catch (e) {
+            // The binding is missing in frames the recorder was not attached to
+        }
+    };
+
+    const isTextField = (el) =>
+        el.matches('textarea, input:not([type=checkbox]):not([type=radio]):not([type=file])' +
+            ':not([type=submit]):not([type=button]):not([type=reset])');
+
+    const typed = new WeakMap();
+    const recordValue = (el) => {
+        if (!isTextField(el) || typed.get(el) === el.value) return;
+        typed.set(el, el.value);
+        record({ type: { selector: selectorFor(el), text: el.value } });
+    };
+
+    document.addEventListener('click', (event) => {
+        if (event.isTrusted) record({ click: selectorFor(event.target) });
+    }, true);
+    document.addEventListener('change', (event) => {
+        if (event.isTrusted) recordValue(event.target);
+    }, true);
+    document.addEventListener('keydown', (event) => {
+        if (!event.isTrusted || event.key !== 'Enter') return;
+        recordValue(event.target);
+        record({ press: 'Enter' });
+    }, true);
+})()
+"#;
+
+/// Step recorder script with the selector functions and binding filled in
+pub fn recorder_script() -> String {
+    RECORDER_SCRIPT
+        .replace("__SELECTOR_FUNCTIONS__", SELECTOR_FUNCTIONS)
+        .replace("__BINDING__", RECORDER_BINDING)
+}
+
+/// A page interaction run after navigation and before `javascript`, written
+/// in configs as a single-key map such as `- click: "#accept"`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Step {
+    /// Navigate to another URL and wait for it to load
+    Goto(String),
+    /// Click the element matching a selector
+    Click(String),
+    /// Focus a field and type text into it
+    Type { selector: String, text: String },
+    /// Press a key such as `Enter` or `Tab`
+    Press(String),
+    /// Wait until a selector matches
+    WaitFor(String),
+    /// Pause for a number of milliseconds
+    Wait(u64),
+}
+
+/// Binding call as sent by the wrapper `Tab::expose_function` installs in
+/// new documents: `{"name": ..., "seq": ..., "args": [payload]}`
+#[derive(Deserialize)]
+struct WrappedBindingCall {
+    args: (String,),
+}
+
+impl Step {
+    /// Parse a step reported by the recorder script, either directly or
+    /// through the exposed-function wrapper
+    pub fn from_recorder_payload(payload: &str) -> Result<Self> {
+        let step = match serde_json::from_str::<WrappedBindingCall>(payload) {
+            Ok(call) => call.args.0,
+            Err(_) => payload.to_string(),
+        };
+        serde_json::from_str(&step)
+            .map_err(|e| WebshotError::config(format!("Invalid recorded step {}: {}", step, e)))
+    }
+}
+
+#[derive(Serialize)]
+struct RecordedSteps<'a> {
+    #[serde(with = "serde_yaml::with::singleton_map_recursive")]
+    steps: &'a [Step],
+}
+
+/// YAML `steps:` document for recorded steps, ready to paste into a batch
+/// config entry for `url`
+pub fn steps_yaml(url: Option<&str>, steps: &[Step]) -> Result<String> {
+    let mut yaml = String::new();
+    if let Some(url) = url {
+        yaml.push_str(&format!("# Recorded from {}\n", url));
+    }
+    yaml.push_str(&serde_yaml::to_string(&RecordedSteps { steps })?);
+    Ok(yaml)
+}
+
+/// Write recorded steps to a YAML file
+pub fn write_steps<P: AsRef<Path>>(path: P, url: Option<&str>, steps: &[Step]) -> Result<()> {
+    crate::output::OutputHandler::ensure_output_dir(&path)?;
+    std::fs::write(path, steps_yaml(url, steps)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ScreenshotConfig;
+
+    #[test]
+    fn test_recorder_payloads() {
+        assert_eq!(
+            Step::from_recorder_payload(r##"{"click":"#accept"}"##).unwrap(),
+            Step::Click("#accept".to_string())
+        );
+        assert_eq!(
+            Step::from_recorder_payload(r##"{"type":{"selector":"#user","text":"alice"}}"##)
+                .unwrap(),
+            Step::Type {
+                selector: "#user".to_string(),
+                text: "alice".to_string(),
+            }
+        );
+        assert_eq!(
+            Step::from_recorder_payload(
+                r##"{"name":"__webshotRecordStep","seq":1,"args":["{\"press\":\"Enter\"}"]}"##
+            )
+            .unwrap(),
+            Step::Press("Enter".to_string())
+        );
+        assert!(Step::from_recorder_payload(r#"{"hover":"a"}"#)
+            .unwrap_err()
+            .to_string()
+            .contains("Invalid recorded step"));
+    }
+
+    #[test]
+    fn test_steps_yaml_loads_into_config_entry() {
+        let steps = vec![
+            Step::Click("#accept-cookies".to_string()),
+            Step::Type {
+                selector: "#user".to_string(),
+                text: "alice".to_string(),
+            },
+            Step::Press("Enter".to_string()),
+            Step::WaitFor(".dashboard".to_string()),
+            Step::Wait(500),
+        ];
+        let yaml = steps_yaml(Some("https://example.com/login"), &steps).unwrap();
+        assert!(yaml.starts_with("# Recorded from https://example.com/login\n"));
+        assert!(yaml.contains("- click: '#accept-cookies'"));
+
+        let entry = format!(
+            "url: https://example.com/login\noutput: login.png\n{}",
+            yaml
+        );
+        let config: ScreenshotConfig = serde_yaml::from_str(&entry).unwrap();
+        assert_eq!(config.steps, steps);
+    }
+
+    #[test]
+    fn test_recorder_script_is_complete() {
+        let script = recorder_script();
+        assert!(script.contains(RECORDER_BINDING));
+        assert!(!script.contains("__SELECTOR_FUNCTIONS__"));
+        assert!(!script.contains("__BINDING__"));
+    }
+}
@@ -0,0 +1,275 @@
+//! Weighted scoring of the baseline comparisons of a batch run.
+//!
+//! Entries of a batch config with a `comparison.baseline_path` are compared
+//! with their baseline once the run is over. Each page has a `weight`
+//! (default 1), so a checkout page can count for more than dozens of
+//! rarely visited ones. The suite score is the weighted mean similarity of
+//! all compared pages, with pages that failed to capture or compare scoring
+//! 0, and the failed weight is the share of the total weight on pages that
+//! are not similar to their baseline.
+//!
+//! The `suite` section of the config decides whether the run passes: the
+//! score must reach `min_score` and the failed weight must not exceed
+//! `max_failed_weight`, which defaults to 0 so any failing page fails the
+//! run. The [`SuiteReport`] is written to [`SUITE_REPORT`] next to the run
+//! manifest.
+
+use crate::comparison::ImageComparator;
+use crate::config::{Config, ScreenshotConfig};
+use crate::error::{Result, WebshotError};
+use crate::monitor::{JobEntry, JobStatus};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// File name of the suite report written next to batch outputs
+pub const SUITE_REPORT: &str = "suite-report.json";
+
+/// When the comparisons of a batch run pass as a whole
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct SuiteThresholds {
+    /// Lowest weighted mean similarity (0.0 to 1.0) that passes
+    pub min_score: Option<f64>,
+    /// Largest share of the total weight (0.0 to 1.0) that may be on pages
+    /// differing from their baseline
+    #[serde(default)]
+    pub max_failed_weight: f64,
+}
+
+impl SuiteThresholds {
+    pub fn validate(&self) -> Result<()> {
+        let in_range = |value: f64| (0.0..=1.0).contains(&value);
+        if !self.min_score.is_none_or(in_range) {
+            return Err(WebshotError::config(format!(
+                "Suite min_score must be between 0.0 and 1.0, got: {}",
+                self.min_score.unwrap_or_default()
+            )));
+        }
+        if !in_range(self.max_failed_weight) {
+            return Err(WebshotError::config(format!(
+                "Suite max_failed_weight must be between 0.0 and 1.0, got: {}",
+                self.max_failed_weight
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Comparison of one page with its baseline
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PageScore {
+    pub url: String,
+    pub output: PathBuf,
+    pub baseline: PathBuf,
+    pub weight: f64,
+    /// 0 when the page could not be captured or compared
+    pub similarity: f64,
+    pub similar: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff_image: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl PageScore {
+    /// Compare the capture of `job`, configured by `config` and written to
+    /// `output`, with its baseline, or `None` when it has none
+    pub fn compare(config: &ScreenshotConfig, job: &JobEntry, output: &Path) -> Option<Self> {
+        let comparison = config.comparison.as_ref()?;
+        let baseline = PathBuf::from(comparison.baseline_path.as_ref()?);
+        let mut score = Self {
+            url: config.url.clone(),
+            output: output.to_path_buf(),
+            baseline,
+            weight: comparison.weight,
+            similarity: 0.0,
+            similar: false,
+            diff_image: None,
+            error: None,
+        };
+
+        let result = match &job.status {
+            JobStatus::Done => comparison.options().and_then(|options| {
+                ImageComparator::compare_files(&score.baseline, output, &options)
+            }),
+            JobStatus::Failed(error) => Err(WebshotError::screenshot(error.clone())),
+            status => Err(WebshotError::screenshot(format!(
+                "capture {}",
+                status.label()
+            ))),
+        };
+        match result {
+            Ok(result) => {
+                score.similarity = result.similarity;
+                score.similar = result.similar;
+                score.diff_image = result.diff_image_path;
+            }
+            Err(error) => score.error = Some(error.to_string()),
+        }
+        Some(score)
+    }
+}
+
+/// Compare the captures of a run of `config` with their baselines, given
+/// the run's jobs and outputs in job order (see [`Config::jobs`]), or `None`
+/// when no entry has a baseline
+pub fn score_run(config: &Config, jobs: &[JobEntry], outputs: &[PathBuf]) -> Option<SuiteReport> {
+    let pages: Vec<PageScore> = config
+        .jobs()
+        .iter()
+        .zip(jobs)
+        .zip(outputs)
+        .filter_map(|((entry, job), output)| PageScore::compare(entry, job, output))
+        .collect();
+    if pages.is_empty() {
+        return None;
+    }
+    Some(SuiteReport::new(pages, config.suite.clone()))
+}
+
+/// Weighted outcome of the comparisons of a batch run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SuiteReport {
+    /// Weighted mean similarity of all pages
+    pub score: f64,
+    pub total_weight: f64,
+    /// Share of the total weight on pages that are not similar
+    pub failed_weight: f64,
+    pub thresholds: SuiteThresholds,
+    pub passed: bool,
+    pub pages: Vec<PageScore>,
+}
+
+impl SuiteReport {
+    pub fn new(pages: Vec<PageScore>, thresholds: SuiteThresholds) -> Self {
+        let total_weight: f64 = pages.iter().map(|page| page.weight).sum();
+        let (score, failed_weight) = if total_weight > 0.0 {
+            let weighted: f64 = pages.iter().map(|page| page.weight * page.similarity).sum();
+            let failed: f64 = pages
+                .iter()
+                .filter(|page| !page.similar)
+                .map(|page| page.weight)
+                .sum();
+            (weighted / total_weight, failed / total_weight)
+        } else {
+            (1.0, 0.0)
+        };
+        let passed = thresholds.min_score.is_none_or(|min| score >= min)
+            && failed_weight <= thresholds.max_failed_weight;
+        Self {
+            score,
+            total_weight,
+            failed_weight,
+            thresholds,
+            passed,
+            pages,
+        }
+    }
+
+    /// Number of pages that are not similar to their baseline
+    pub fn failed(&self) -> usize {
+        self.pages.iter().filter(|page| !page.similar).count()
+    }
+
+    /// Write the report as JSON
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for SuiteReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Suite score {:.4} over {} pages, {} differing ({:.1}% of the weight)",
+            self.score,
+            self.pages.len(),
+            self.failed(),
+            self.failed_weight * 100.0
+        )?;
+        if let Some(min) = self.thresholds.min_score {
+            write!(f, ", minimum score {}", min)?;
+        }
+        write!(
+            f,
+            ", allowed failed weight {:.1}%: {}",
+            self.thresholds.max_failed_weight * 100.0,
+            if self.passed { "passed" } else { "failed" }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(weight: f64, similarity: f64, similar: bool) -> PageScore {
+        PageScore {
+            url: "https://example.com/".to_string(),
+            output: PathBuf::from("page.png"),
+            baseline: PathBuf::from("baseline/page.png"),
+            weight,
+            similarity,
+            similar,
+            diff_image: None,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_weights_decide_the_outcome() {
+        // A critical page failing outweighs many passing minor pages
+        let mut pages = vec![page(10.0, 0.5, false)];
+        pages.extend((0..10).map(|_| page(1.0, 1.0, true)));
+        let report = SuiteReport::new(
+            pages,
+            SuiteThresholds {
+                min_score: Some(0.9),
+                max_failed_weight: 0.25,
+            },
+        );
+        assert!((report.score - 0.75).abs() < 1e-9);
+        assert!((report.failed_weight - 0.5).abs() < 1e-9);
+        assert_eq!(report.failed(), 1);
+        assert!(!report.passed);
+        assert!(report.to_string().ends_with("failed"));
+
+        // Minor pages failing stay within the allowed weight
+        let mut pages = vec![page(10.0, 1.0, true)];
+        pages.extend((0..2).map(|_| page(1.0, 0.8, false)));
+        let report = SuiteReport::new(
+            pages,
+            SuiteThresholds {
+                min_score: Some(0.95),
+                max_failed_weight: 0.2,
+            },
+        );
+        assert!(report.passed, "{}", report);
+
+        // By default any differing page fails the suite
+        let pages = vec![page(1.0, 1.0, true), page(0.5, 0.99, false)];
+        assert!(!SuiteReport::new(pages, SuiteThresholds::default()).passed);
+        assert!(SuiteReport::new(Vec::new(), SuiteThresholds::default()).passed);
+    }
+
+    #[test]
+    fn test_threshold_validation() {
+        assert!(SuiteThresholds::default().validate().is_ok());
+        let thresholds: SuiteThresholds =
+            serde_yaml::from_str("min_score: 0.98\nmax_failed_weight: 0.1").unwrap();
+        assert!(thresholds.validate().is_ok());
+        assert!(SuiteThresholds {
+            min_score: Some(1.5),
+            ..Default::default()
+        }
+        .validate()
+        .is_err());
+        assert!(SuiteThresholds {
+            max_failed_weight: -0.1,
+            ..Default::default()
+        }
+        .validate()
+        .is_err());
+    }
+}
@@ -0,0 +1,287 @@
+//! Visual tests against checked-in golden images, for downstream crates.
+//!
+//! A test starts the pages under test (or webshot's own fixtures with
+//! [`fixtures`]), captures them with a browser from [`launch_browser`] and
+//! checks each capture against a golden PNG kept next to the test sources
+//! with [`assert_golden`].
+//!
+//! Goldens are only ever written on request: run the tests with
+//! `WEBSHOT_UPDATE_GOLDENS=1` to create missing goldens and replace
+//! changed ones. A capture that does not match its golden leaves
+//! `<name>.actual.png` and `<name>.diff.png` next to it for inspection;
+//! both are removed again once the capture matches.
+//!
+//! Font rasterization differs between operating systems and GPUs, so the
+//! browser renders text without subpixel positioning or hinting, and the
+//! default [`GoldenOptions`] tolerate small per-pixel color differences and
+//! a few differing pixels.
+
+use crate::browser::{Browser, FONT_RENDERING_FLAGS};
+use crate::comparison::{
+    ComparisonAlgorithm, ComparisonOptions, ComparisonResult, ImageComparator,
+};
+use crate::error::{Result, WebshotError};
+use crate::screenshot::ScreenshotOptions;
+use image::DynamicImage;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+pub use crate::dev_server::DevServer;
+
+/// Environment variable that makes golden checks write goldens
+pub const UPDATE_GOLDENS_ENV: &str = "WEBSHOT_UPDATE_GOLDENS";
+
+/// Share of pixels that may differ from the golden by default
+pub const DEFAULT_GOLDEN_THRESHOLD: f64 = 0.001;
+
+/// How a capture is checked against its golden
+#[derive(Debug, Clone)]
+pub struct GoldenOptions {
+    /// Comparison of the capture with the golden
+    pub comparison: ComparisonOptions,
+    /// Write the capture as the golden instead of comparing
+    pub update: bool,
+}
+
+impl Default for GoldenOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GoldenOptions {
+    /// Pixel comparison tolerating anti-aliasing differences and
+    /// [`DEFAULT_GOLDEN_THRESHOLD`] of differing pixels, updating goldens
+    /// when [`UPDATE_GOLDENS_ENV`] is set to anything but `0` or `false`
+    pub fn new() -> Self {
+        let update = std::env::var(UPDATE_GOLDENS_ENV)
+            .map(|value| !matches!(value.as_str(), "" | "0" | "false"))
+            .unwrap_or(false);
+        Self {
+            comparison: ComparisonOptions::new()
+                .algorithm(ComparisonAlgorithm::PixelDiff)
+                .threshold(DEFAULT_GOLDEN_THRESHOLD)
+                .ignore_antialiasing(),
+            update,
+        }
+    }
+
+    /// Share of pixels (0.0 to 1.0) that may differ, or for other
+    /// algorithms how far the similarity may drop below 1.0
+    pub fn threshold(mut self, threshold: f64) -> Self {
+        self.comparison.threshold = threshold;
+        self
+    }
+
+    pub fn algorithm(mut self, algorithm: ComparisonAlgorithm) -> Self {
+        self.comparison.algorithm = algorithm;
+        self
+    }
+
+    pub fn update(mut self, update: bool) -> Self {
+        self.update = update;
+        self
+    }
+}
+
+/// Serve webshot's fixture pages (see [`crate::dev_server`]) on a free
+/// localhost port until the server is dropped
+pub fn fixtures() -> Result<DevServer> {
+    DevServer::start()
+}
+
+/// Launch a headless browser that renders text the same way everywhere
+pub async fn launch_browser() -> Result<Browser> {
+    let flags = FONT_RENDERING_FLAGS.iter().map(|flag| flag.to_string());
+    Browser::new(None, flags.collect(), true).await
+}
+
+/// Capture `url` as an image
+pub async fn capture(
+    browser: &Browser,
+    url: &str,
+    options: &ScreenshotOptions,
+) -> Result<DynamicImage> {
+    let dir = tempfile::tempdir()?;
+    let path = browser
+        .screenshot(url, dir.path().join("capture.png"), options)
+        .await?;
+    Ok(image::open(path)?)
+}
+
+/// Check `actual` against the golden PNG at `golden`, writing the golden
+/// instead when `options.update` is set.
+///
+/// Fails when the golden is missing or has other dimensions; a capture that
+/// differs too much comes back as a result that is not `similar`.
+pub fn check_golden(
+    actual: &DynamicImage,
+    golden: impl AsRef<Path>,
+    options: &GoldenOptions,
+) -> Result<ComparisonResult> {
+    let golden = golden.as_ref();
+    let (actual_path, diff_path) = artifact_paths(golden);
+
+    if options.update {
+        if let Some(dir) = golden.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        actual.save(golden)?;
+        info!("Golden written: {}", golden.display());
+        remove_artifacts(&actual_path, &diff_path)?;
+        return ImageComparator::compare_images(actual, actual, &options.comparison);
+    }
+
+    if !golden.exists() {
+        return Err(WebshotError::baseline(format!(
+            "Golden image {} does not exist; run with {}=1 to create it",
+            golden.display(),
+            UPDATE_GOLDENS_ENV
+        )));
+    }
+    let expected = image::open(golden)?;
+
+    let mut comparison = options.comparison.clone();
+    comparison.generate_diff_image = false;
+    let result = match ImageComparator::compare_images(&expected, actual, &comparison) {
+        Ok(result) if result.similar => {
+            remove_artifacts(&actual_path, &diff_path)?;
+            return Ok(result);
+        }
+        Ok(result) => result,
+        Err(e) => {
+            actual.save(&actual_path)?;
+            return Err(e);
+        }
+    };
+
+    actual.save(&actual_path)?;
+    comparison = comparison.generate_diff_image(&diff_path);
+    ImageComparator::compare_images(&expected, actual, &comparison)?;
+    Ok(result)
+}
+
+/// Panic unless `actual` matches the golden PNG at `golden` under the
+/// default [`GoldenOptions`]
+pub fn assert_golden(actual: &DynamicImage, golden: impl AsRef<Path>) {
+    assert_golden_with(actual, golden, &GoldenOptions::new())
+}
+
+/// Panic unless `actual` matches the golden PNG at `golden`
+pub fn assert_golden_with(
+    actual: &DynamicImage,
+    golden: impl AsRef<Path>,
+    options: &GoldenOptions,
+) {
+    let golden = golden.as_ref();
+    match check_golden(actual, golden, options) {
+        Ok(result) if result.similar => {}
+        Ok(result) => {
+            let (actual_path, diff_path) = artifact_paths(golden);
+            panic!(
+                "capture differs from golden {}: {:.4}% similar{}; see {} and {}, or run with {}=1 to accept it",
+                golden.display(),
+                result.similarity * 100.0,
+                result
+                    .different_pixels
+                    .map(|pixels| format!(", {} of {} pixels differ", pixels, result.total_pixels))
+                    .unwrap_or_default(),
+                actual_path.display(),
+                diff_path.display(),
+                UPDATE_GOLDENS_ENV
+            )
+        }
+        Err(e) => panic!("golden check of {} failed: {}", golden.display(), e),
+    }
+}
+
+/// Where a mismatching capture and its diff are written: next to the
+/// golden as `<name>.actual.png` and `<name>.diff.png`
+fn artifact_paths(golden: &Path) -> (PathBuf, PathBuf) {
+    let stem = golden
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    (
+        golden.with_file_name(format!("{}.actual.png", stem)),
+        golden.with_file_name(format!("{}.diff.png", stem)),
+    )
+}
+
+fn remove_artifacts(actual_path: &Path, diff_path: &Path) -> Result<()> {
+    for path in [actual_path, diff_path] {
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    fn image_with_square(color: [u8; 3]) -> DynamicImage {
+        let mut image = RgbImage::from_pixel(100, 100, Rgb([255, 255, 255]));
+        for x in 10..30 {
+            for y in 10..30 {
+                image.put_pixel(x, y, Rgb(color));
+            }
+        }
+        DynamicImage::ImageRgb8(image)
+    }
+
+    #[test]
+    fn test_golden_lifecycle() {
+        let dir = tempfile::tempdir().unwrap();
+        let golden = dir.path().join("goldens/home.png");
+        let compare = GoldenOptions::new().update(false);
+        let original = image_with_square([0, 0, 0]);
+
+        let missing = check_golden(&original, &golden, &compare).unwrap_err();
+        assert!(missing.to_string().contains(UPDATE_GOLDENS_ENV));
+
+        check_golden(&original, &golden, &compare.clone().update(true)).unwrap();
+        assert!(golden.exists());
+
+        // Anti-aliased edges pass
+        let mut smoothed = original.to_rgb8();
+        for y in 10..30 {
+            smoothed.put_pixel(30, y, Rgb([128, 128, 128]));
+        }
+        let smoothed = DynamicImage::ImageRgb8(smoothed);
+        assert!(check_golden(&smoothed, &golden, &compare).unwrap().similar);
+
+        // A changed region fails and leaves the capture and a diff behind
+        let changed = image_with_square([255, 0, 0]);
+        let result = check_golden(&changed, &golden, &compare).unwrap();
+        assert!(!result.similar);
+        assert_eq!(result.different_pixels, Some(400));
+        assert!(dir.path().join("goldens/home.actual.png").exists());
+        assert!(dir.path().join("goldens/home.diff.png").exists());
+
+        // ...which a loose enough threshold accepts, clearing them up
+        let loose = compare.clone().threshold(0.05);
+        assert!(check_golden(&changed, &golden, &loose).unwrap().similar);
+        assert!(!dir.path().join("goldens/home.actual.png").exists());
+        assert!(!dir.path().join("goldens/home.diff.png").exists());
+
+        let resized = DynamicImage::ImageRgb8(RgbImage::new(50, 50));
+        assert!(check_golden(&resized, &golden, &compare).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "capture differs from golden")]
+    fn test_assert_golden_panics_on_mismatch() {
+        let dir = tempfile::tempdir().unwrap();
+        let golden = dir.path().join("home.png");
+        image_with_square([0, 0, 0]).save(&golden).unwrap();
+
+        assert_golden_with(
+            &image_with_square([255, 0, 0]),
+            &golden,
+            &GoldenOptions::new().update(false),
+        );
+    }
+}
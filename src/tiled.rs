@@ -0,0 +1,493 @@
+//! Band-by-band comparison of tall PNG images.
+//!
+//! A full-page capture of a long page can be 1280×60000 pixels, and decoding
+//! two of them whole, plus their difference image, takes over a gigabyte.
+//! With [`ComparisonOptions::band_height`] set, PNG files are instead decoded
+//! a band of rows at a time and compared band by band, so memory use follows
+//! the band height rather than the page length.
+//!
+//! Each band is compared together with a few rows of its neighbors, enough
+//! for anti-aliasing detection and the SSIM window to see the same pixels as
+//! in a whole-image comparison. The changed pixel counts, squared error sums
+//! and SSIM sums of the bands then add up to the same [`ComparisonResult`],
+//! regions are clustered over the whole image, and the difference image is
+//! encoded row by row. MS-SSIM and plugin algorithms need the whole image
+//! and are not available in bands.
+
+use crate::comparison::{
+    draw_outline, paint_changes, ComparisonAlgorithm, ComparisonOptions, ComparisonResult,
+    DiffStyle, ImageComparator, RegionGrid,
+};
+use crate::error::{Result, WebshotError};
+use crate::output::OutputHandler;
+use crate::ssim::WINDOW_RADIUS;
+use image::{Rgb, RgbImage};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use tracing::{debug, info};
+
+/// Signature every PNG file starts with
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+/// Rows of context above and below a band: anti-aliasing detection looks
+/// two rows away, the SSIM window `WINDOW_RADIUS` rows
+fn context_rows(options: &ComparisonOptions) -> u32 {
+    match options.algorithm {
+        ComparisonAlgorithm::SSIM => WINDOW_RADIUS as u32,
+        _ if options.ignore_antialiasing => 2,
+        _ => 0,
+    }
+}
+
+/// Whether the file at `path` is a PNG image
+pub fn is_png(path: &Path) -> Result<bool> {
+    let mut signature = [0; 8];
+    let read = File::open(path)?.read(&mut signature)?;
+    Ok(read == signature.len() && signature == PNG_SIGNATURE)
+}
+
+/// PNG file decoded a few rows at a time into RGB
+struct BandReader {
+    reader: png::Reader<BufReader<File>>,
+    width: u32,
+    height: u32,
+    /// Rows decoded so far
+    read: u32,
+    /// RGB rows from `first` on, kept until they are no longer needed
+    rows: Vec<u8>,
+    first: u32,
+}
+
+impl BandReader {
+    /// Open the PNG at `path`; `label` names it in errors
+    fn open(path: &Path, label: &str) -> Result<Self> {
+        let error = |e: png::DecodingError| {
+            WebshotError::config(format!("Failed to load {} image: {}", label, e))
+        };
+        let mut decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let reader = decoder.read_info().map_err(error)?;
+        let info = reader.info();
+        if info.interlaced {
+            return Err(WebshotError::config(format!(
+                "The {} image is an interlaced PNG, which cannot be compared in bands",
+                label
+            )));
+        }
+        let (width, height) = (info.width, info.height);
+        Ok(Self {
+            reader,
+            width,
+            height,
+            read: 0,
+            rows: Vec::new(),
+            first: 0,
+        })
+    }
+
+    /// Decode rows until row `end` (exclusive, capped at the image height)
+    /// is buffered
+    fn fill(&mut self, end: u32) -> Result<()> {
+        let (color_type, _) = self.reader.output_color_type();
+        while self.read < end.min(self.height) {
+            let row = self
+                .reader
+                .next_row()
+                .map_err(|e| WebshotError::config(format!("Failed to decode PNG row: {}", e)))?
+                .ok_or_else(|| WebshotError::config("PNG image ended early"))?;
+            let data = row.data();
+            // The same conversions as `DynamicImage::to_rgb8`: alpha is
+            // dropped and gray is repeated in every channel
+            match color_type {
+                png::ColorType::Rgb => self.rows.extend_from_slice(data),
+                png::ColorType::Rgba => data
+                    .chunks_exact(4)
+                    .for_each(|pixel| self.rows.extend_from_slice(&pixel[..3])),
+                png::ColorType::Grayscale => data
+                    .iter()
+                    .for_each(|&gray| self.rows.extend_from_slice(&[gray; 3])),
+                png::ColorType::GrayscaleAlpha => data
+                    .chunks_exact(2)
+                    .for_each(|pixel| self.rows.extend_from_slice(&[pixel[0]; 3])),
+                png::ColorType::Indexed => {
+                    return Err(WebshotError::config("Unexpected indexed PNG row"));
+                }
+            }
+            self.read += 1;
+        }
+        Ok(())
+    }
+
+    /// Buffered rows `top..bottom`, which must have been filled and not
+    /// dropped
+    fn rows(&self, top: u32, bottom: u32) -> &[u8] {
+        let row_bytes = self.width as usize * 3;
+        &self.rows
+            [(top - self.first) as usize * row_bytes..(bottom - self.first) as usize * row_bytes]
+    }
+
+    /// Forget the rows before row `top`
+    fn drop_before(&mut self, top: u32) {
+        if top > self.first {
+            let row_bytes = self.width as usize * 3;
+            self.rows.drain(..(top - self.first) as usize * row_bytes);
+            self.first = top;
+        }
+    }
+}
+
+/// Compare two PNG files `band_height` rows at a time
+pub fn compare_png_bands<P1: AsRef<Path>, P2: AsRef<Path>>(
+    image1_path: P1,
+    image2_path: P2,
+    options: &ComparisonOptions,
+    band_height: u32,
+) -> Result<ComparisonResult> {
+    if matches!(
+        options.algorithm,
+        ComparisonAlgorithm::MSSSIM | ComparisonAlgorithm::Plugin(_)
+    ) {
+        return Err(WebshotError::config(format!(
+            "The {:?} algorithm compares whole images and cannot run in bands",
+            options.algorithm
+        )));
+    }
+
+    let mut reader1 = BandReader::open(image1_path.as_ref(), "first")?;
+    let mut reader2 = BandReader::open(image2_path.as_ref(), "second")?;
+    if (reader1.width, reader1.height) != (reader2.width, reader2.height) {
+        return Err(WebshotError::config(format!(
+            "Image dimensions don't match: {:?} vs {:?}",
+            (reader1.width, reader1.height),
+            (reader2.width, reader2.height)
+        )));
+    }
+    let (width, height) = (reader1.width, reader1.height);
+    let band_height = band_height.max(1);
+    let context = context_rows(options);
+    info!(
+        "Comparing {}x{} images using {:?} algorithm in bands of {} rows",
+        width, height, options.algorithm, band_height
+    );
+
+    let diff_path = options
+        .diff_output_path
+        .as_ref()
+        .filter(|_| options.generate_diff_image);
+    // Changed pixels are painted as they are found; boxes need every
+    // region first and are drawn in a second pass
+    let mut painter = match diff_path {
+        Some(path) if options.diff_style == DiffStyle::Pixels => {
+            Some(DiffWriter::create(path, width, height)?)
+        }
+        _ => None,
+    };
+    let (r, g, b) = options.diff_color;
+
+    let mut grid = RegionGrid::new(width, height, options.region_gap);
+    let (mut different_pixels, mut squared_error, mut ssim_sum) = (0u64, 0u64, 0.0);
+    let mut top = 0;
+    while top < height {
+        let bottom = (top + band_height).min(height);
+        let (window_top, window_bottom) = (top.saturating_sub(context), bottom + context);
+        reader1.fill(window_bottom)?;
+        reader2.fill(window_bottom)?;
+        let window_bottom = window_bottom.min(height);
+        let window = |reader: &BandReader| {
+            RgbImage::from_raw(
+                width,
+                window_bottom - window_top,
+                reader.rows(window_top, window_bottom).to_vec(),
+            )
+            .expect("buffered rows fill the window")
+        };
+        let (window1, window2) = (window(&reader1), window(&reader2));
+
+        let ssim_map = ImageComparator::ssim_map(&window1, &window2, options);
+        let changed =
+            ImageComparator::changed_pixels(&window1, &window2, options, ssim_map.as_ref());
+        // The band's own rows within the window
+        let band = (top - window_top) as usize * width as usize
+            ..(bottom - window_top) as usize * width as usize;
+        let changed = &changed[band.clone()];
+        grid.add_rows(changed, top);
+        different_pixels += changed.iter().filter(|&&changed| changed).count() as u64;
+        if let Some(map) = &ssim_map {
+            ssim_sum += map.values()[band].iter().sum::<f64>();
+        }
+        let (rows1, rows2) = (reader1.rows(top, bottom), reader2.rows(top, bottom));
+        if matches!(
+            options.algorithm,
+            ComparisonAlgorithm::MSE | ComparisonAlgorithm::PSNR
+        ) {
+            squared_error += ImageComparator::squared_error_sum(rows1, rows2);
+        }
+        if let Some(painter) = &mut painter {
+            let mut painted = vec![0; rows1.len()];
+            paint_changes(&mut painted, rows1, changed, [r, g, b]);
+            painter.write(&painted)?;
+        }
+
+        debug!("Compared rows {}..{}", top, bottom);
+        // Rows above the next band's context are done with
+        reader1.drop_before(bottom.saturating_sub(context));
+        reader2.drop_before(bottom.saturating_sub(context));
+        top = bottom;
+    }
+
+    let total_pixels = u64::from(width) * u64::from(height);
+    let mean = |sum: f64| {
+        if total_pixels == 0 {
+            1.0
+        } else {
+            sum / total_pixels as f64
+        }
+    };
+    let mse = mean(squared_error as f64) / 3.0;
+    let (similarity, counted) = match &options.algorithm {
+        ComparisonAlgorithm::SSIM => (mean(ssim_sum).clamp(0.0, 1.0), None),
+        ComparisonAlgorithm::MSE => (ImageComparator::mse_similarity(mse), None),
+        ComparisonAlgorithm::PSNR => (ImageComparator::psnr_similarity(mse), None),
+        _ => (
+            1.0 - mean(different_pixels as f64),
+            Some(different_pixels as u32),
+        ),
+    };
+    let regions = grid.into_regions();
+
+    let diff_image_path = match diff_path {
+        Some(path) => {
+            match painter {
+                Some(painter) => painter.finish()?,
+                None => write_boxes(image1_path.as_ref(), path, &regions, band_height, options)?,
+            }
+            info!("Difference image saved to: {}", path.display());
+            Some(path.clone())
+        }
+        None => None,
+    };
+
+    Ok(ComparisonResult {
+        similar: similarity >= (1.0 - options.threshold),
+        similarity,
+        different_pixels: counted,
+        total_pixels: total_pixels as u32,
+        algorithm: options.algorithm.clone(),
+        threshold: options.threshold,
+        diff_image_path,
+        baseline_version: None,
+        regions,
+    })
+}
+
+/// Difference image encoded as RGB PNG a band of rows at a time
+struct DiffWriter {
+    writer: png::StreamWriter<'static, BufWriter<File>>,
+}
+
+impl DiffWriter {
+    fn create(path: &Path, width: u32, height: u32) -> Result<Self> {
+        OutputHandler::ensure_output_dir(path)?;
+        let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), width, height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let writer = encoder
+            .write_header()
+            .and_then(png::Writer::into_stream_writer)
+            .map_err(diff_error)?;
+        Ok(Self { writer })
+    }
+
+    fn write(&mut self, rows: &[u8]) -> Result<()> {
+        self.writer
+            .write_all(rows)
+            .map_err(|e| WebshotError::config(format!("Failed to save diff image: {}", e)))
+    }
+
+    fn finish(self) -> Result<()> {
+        self.writer.finish().map_err(diff_error)
+    }
+}
+
+fn diff_error(e: png::EncodingError) -> WebshotError {
+    WebshotError::config(format!("Failed to save diff image: {}", e))
+}
+
+/// Write the first image with a box around every region, reading it again
+/// band by band
+fn write_boxes(
+    image_path: &Path,
+    diff_path: &Path,
+    regions: &[crate::comparison::DiffRegion],
+    band_height: u32,
+    options: &ComparisonOptions,
+) -> Result<()> {
+    let mut reader = BandReader::open(image_path, "first")?;
+    let (width, height) = (reader.width, reader.height);
+    let mut writer = DiffWriter::create(diff_path, width, height)?;
+    let (r, g, b) = options.diff_color;
+    let mut top = 0;
+    while top < height {
+        let bottom = (top + band_height).min(height);
+        reader.fill(bottom)?;
+        let mut band = RgbImage::from_raw(width, bottom - top, reader.rows(top, bottom).to_vec())
+            .expect("buffered rows fill the band");
+        for region in regions {
+            draw_outline(&mut band, region, Rgb([r, g, b]), top);
+        }
+        writer.write(band.as_raw())?;
+        reader.drop_before(bottom);
+        top = bottom;
+    }
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::comparison::DiffRegion;
+    use image::DynamicImage;
+    use tempfile::TempDir;
+
+    /// Textured tall image with dark text-like strokes
+    fn page(width: u32, height: u32) -> RgbImage {
+        RgbImage::from_fn(width, height, |x, y| {
+            if (y / 9) % 4 == 1 && x % 7 < 4 {
+                Rgb([20, 20, 30])
+            } else {
+                let value = 200 + ((x * 3 + y * 5) % 40) as u8;
+                Rgb([value, value, 255 - value / 2])
+            }
+        })
+    }
+
+    #[test]
+    fn test_bands_match_whole_image_comparison() {
+        let temp_dir = TempDir::new().unwrap();
+        let (width, height) = (48, 500);
+        let expected = page(width, height);
+        let mut actual = expected.clone();
+        // Changes inside, across and at the edges of 64-row bands
+        for (x, y) in [(5, 0), (10, 63), (11, 64), (30, 200), (47, 499)] {
+            actual.put_pixel(x, y, Rgb([255, 0, 0]));
+        }
+        for y in 120..140 {
+            for x in 20..28 {
+                actual.put_pixel(x, y, Rgb([0, 90, 0]));
+            }
+        }
+        let expected_path = temp_dir.path().join("expected.png");
+        let actual_path = temp_dir.path().join("actual.png");
+        expected.save(&expected_path).unwrap();
+        // Stored with alpha, which is dropped like in a whole comparison
+        DynamicImage::ImageRgb8(actual.clone())
+            .to_rgba8()
+            .save(&actual_path)
+            .unwrap();
+
+        for options in [
+            ComparisonOptions::new(),
+            ComparisonOptions::new().ignore_antialiasing(),
+            ComparisonOptions::new().algorithm(ComparisonAlgorithm::DeltaE),
+            ComparisonOptions::new().algorithm(ComparisonAlgorithm::SSIM),
+            ComparisonOptions::new().algorithm(ComparisonAlgorithm::MSE),
+            ComparisonOptions::new().algorithm(ComparisonAlgorithm::PSNR),
+        ] {
+            let whole = ImageComparator::compare_images(
+                &expected.clone().into(),
+                &actual.clone().into(),
+                &options,
+            )
+            .unwrap();
+            let banded = ImageComparator::compare_files(
+                &expected_path,
+                &actual_path,
+                &options.clone().band_height(64),
+            )
+            .unwrap();
+            assert!(
+                (whole.similarity - banded.similarity).abs() < 1e-9,
+                "{:?}: {} vs {}",
+                options.algorithm,
+                whole.similarity,
+                banded.similarity
+            );
+            assert_eq!(whole.different_pixels, banded.different_pixels);
+            assert_eq!(whole.regions, banded.regions, "{:?}", options.algorithm);
+            assert_eq!(banded.total_pixels, width * height);
+        }
+
+        let error = ImageComparator::compare_files(
+            &expected_path,
+            &actual_path,
+            &ComparisonOptions::new()
+                .algorithm(ComparisonAlgorithm::MSSSIM)
+                .band_height(64),
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("cannot run in bands"));
+    }
+
+    #[test]
+    fn test_banded_diff_images() {
+        let temp_dir = TempDir::new().unwrap();
+        let expected = page(40, 300);
+        let mut actual = expected.clone();
+        for y in 95..105 {
+            for x in 10..15 {
+                actual.put_pixel(x, y, Rgb([255, 255, 255]));
+            }
+        }
+        let expected_path = temp_dir.path().join("expected.png");
+        let actual_path = temp_dir.path().join("actual.png");
+        expected.save(&expected_path).unwrap();
+        actual.save(&actual_path).unwrap();
+
+        for style in [DiffStyle::Pixels, DiffStyle::Boxes] {
+            let whole_path = temp_dir.path().join("whole.png");
+            let banded_path = temp_dir.path().join("banded.png");
+            let options = ComparisonOptions::new().diff_style(style);
+            ImageComparator::compare_files(
+                &expected_path,
+                &actual_path,
+                &options.clone().generate_diff_image(&whole_path),
+            )
+            .unwrap();
+            let result = ImageComparator::compare_files(
+                &expected_path,
+                &actual_path,
+                &options.generate_diff_image(&banded_path).band_height(32),
+            )
+            .unwrap();
+            assert_eq!(result.diff_image_path, Some(banded_path.clone()));
+            assert_eq!(
+                result.regions,
+                vec![DiffRegion {
+                    x: 10,
+                    y: 95,
+                    width: 5,
+                    height: 10,
+                    pixels: 50
+                }]
+            );
+            assert_eq!(
+                image::open(&whole_path).unwrap().to_rgb8(),
+                image::open(&banded_path).unwrap().to_rgb8(),
+                "{:?}",
+                style
+            );
+        }
+    }
+
+    #[test]
+    fn test_png_detection() {
+        let temp_dir = TempDir::new().unwrap();
+        let png_path = temp_dir.path().join("image.png");
+        let jpeg_path = temp_dir.path().join("image.jpg");
+        page(8, 8).save(&png_path).unwrap();
+        page(8, 8).save(&jpeg_path).unwrap();
+        assert!(is_png(&png_path).unwrap());
+        assert!(!is_png(&jpeg_path).unwrap());
+    }
+}
@@ -0,0 +1,166 @@
+//! Per-phase capture timings.
+//!
+//! Each capture records how long it spent in every phase, so slow runs can
+//! be traced to the site (navigation, waits) or to webshot (capture,
+//! encoding, writing).
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A step of a capture, in the order captures go through them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    /// Starting or connecting to the browser
+    Launch,
+    /// Opening and setting up the tab
+    NewTab,
+    /// Loading the page until its load event
+    Navigate,
+    /// Steps, overlay dismissal and custom JavaScript
+    Js,
+    /// Waiting for `wait_for` conditions
+    WaitFor,
+    /// Fixed waits, lazy-content scrolling and page stabilization
+    Settle,
+    /// Taking the screenshot or printing the PDF
+    Capture,
+    /// Filtering and encoding the image
+    Encode,
+    /// Writing the output file
+    Write,
+}
+
+impl Phase {
+    pub fn label(self) -> &'static str {
+        match self {
+            Phase::Launch => "launch",
+            Phase::NewTab => "new_tab",
+            Phase::Navigate => "navigate",
+            Phase::Js => "js",
+            Phase::WaitFor => "wait_for",
+            Phase::Settle => "settle",
+            Phase::Capture => "capture",
+            Phase::Encode => "encode",
+            Phase::Write => "write",
+        }
+    }
+}
+
+/// Milliseconds spent in each phase. Serializes as a JSON object keyed by
+/// phase name, in phase order.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PhaseTimings(BTreeMap<Phase, u64>);
+
+impl PhaseTimings {
+    /// Add `duration` to a phase
+    pub fn record(&mut self, phase: Phase, duration: Duration) {
+        *self.0.entry(phase).or_default() += duration.as_millis() as u64;
+    }
+
+    /// Add every phase of `other`
+    pub fn add(&mut self, other: &PhaseTimings) {
+        for (phase, ms) in &other.0 {
+            *self.0.entry(*phase).or_default() += ms;
+        }
+    }
+
+    /// Milliseconds spent in a phase
+    pub fn get(&self, phase: Phase) -> u64 {
+        self.0.get(&phase).copied().unwrap_or_default()
+    }
+
+    /// Milliseconds spent in all phases
+    pub fn total(&self) -> u64 {
+        self.0.values().sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Display for PhaseTimings {
+    /// `navigate 412ms, capture 95ms, ... (total 640ms)`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (phase, ms)) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{} {}ms", phase.label(), ms)?;
+        }
+        write!(f, " (total {}ms)", self.total())
+    }
+}
+
+/// Attributes the time between marks to phases
+#[derive(Debug)]
+pub struct PhaseTimer {
+    state: Mutex<(Instant, PhaseTimings)>,
+}
+
+impl PhaseTimer {
+    pub fn start() -> Self {
+        Self {
+            state: Mutex::new((Instant::now(), PhaseTimings::default())),
+        }
+    }
+
+    /// Record the time since the previous mark (or the start) as `phase`
+    pub fn mark(&self, phase: Phase) {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let elapsed = now - state.0;
+        state.1.record(phase, elapsed);
+        state.0 = now;
+    }
+
+    /// Timings recorded so far
+    pub fn timings(&self) -> PhaseTimings {
+        self.state.lock().unwrap().1.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timings_serialize_in_phase_order() {
+        let mut timings = PhaseTimings::default();
+        timings.record(Phase::Write, Duration::from_millis(3));
+        timings.record(Phase::Navigate, Duration::from_millis(400));
+        timings.record(Phase::NewTab, Duration::from_millis(20));
+        timings.record(Phase::Navigate, Duration::from_millis(12));
+
+        assert_eq!(
+            serde_json::to_string(&timings).unwrap(),
+            r#"{"new_tab":20,"navigate":412,"write":3}"#
+        );
+        assert_eq!(
+            timings.to_string(),
+            "new_tab 20ms, navigate 412ms, write 3ms (total 435ms)"
+        );
+
+        let mut totals = timings.clone();
+        totals.add(&timings);
+        assert_eq!(totals.get(Phase::Navigate), 824);
+        assert_eq!(totals.get(Phase::Capture), 0);
+    }
+
+    #[test]
+    fn test_marks_split_elapsed_time() {
+        let timer = PhaseTimer::start();
+        std::thread::sleep(Duration::from_millis(15));
+        timer.mark(Phase::Navigate);
+        timer.mark(Phase::Capture);
+
+        let timings = timer.timings();
+        assert!(timings.get(Phase::Navigate) >= 15);
+        assert!(timings.get(Phase::Capture) < timings.get(Phase::Navigate));
+    }
+}
@@ -0,0 +1,219 @@
+use crate::error::{Result, WebshotError};
+use base64::Engine;
+use std::path::Path;
+
+/// Chrome switch that accepts certificate chains containing a listed key
+const SPKI_LIST_FLAG: &str = "--ignore-certificate-errors-spki-list=";
+
+/// Chrome switch that accepts every certificate
+pub const INSECURE_FLAG: &str = "--ignore-certificate-errors";
+
+/// Chrome launch flag trusting the CA certificates in a PEM file.
+///
+/// This is not a trust store: Chrome ignores certificate errors for any
+/// chain the server sends that contains one of the certificates' public
+/// keys, so the server must include the CA (or the listed certificate) in
+/// its chain, and name mismatches or expiry in such chains are ignored as
+/// well. Chrome only honours the flag with a user data directory, which
+/// every Chrome webshot launches has; browsers attached with `--connect`
+/// need it on their own command line.
+pub fn ca_cert_flag<P: AsRef<Path>>(path: P) -> Result<String> {
+    let path = path.as_ref();
+    let pem = std::fs::read_to_string(path).map_err(|e| {
+        WebshotError::config(format!(
+            "Failed to read CA certificate {}: {}",
+            path.display(),
+            e
+        ))
+    })?;
+
+    let hashes = spki_hashes(&pem).map_err(|e| match e {
+        WebshotError::Config(message) => WebshotError::config(format!(
+            "Invalid CA certificate {}: {}",
+            path.display(),
+            message
+        )),
+        other => other,
+    })?;
+    Ok(format!("{}{}", SPKI_LIST_FLAG, hashes.join(",")))
+}
+
+/// Base64 SHA-256 hashes of the SubjectPublicKeyInfo of every certificate
+/// in a PEM bundle
+pub fn spki_hashes(pem: &str) -> Result<Vec<String>> {
+    let certificates = pem_certificates(pem)?;
+    if certificates.is_empty() {
+        return Err(WebshotError::config("no PEM certificate found".to_string()));
+    }
+
+    certificates
+        .iter()
+        .map(|der| {
+            let spki = subject_public_key_info(der)?;
+            let hash = ring::digest::digest(&ring::digest::SHA256, spki);
+            Ok(base64::engine::general_purpose::STANDARD.encode(hash.as_ref()))
+        })
+        .collect()
+}
+
+/// DER bodies of the `CERTIFICATE` blocks in a PEM document
+fn pem_certificates(pem: &str) -> Result<Vec<Vec<u8>>> {
+    let mut certificates = Vec::new();
+    let mut body: Option<String> = None;
+
+    for line in pem.lines().map(str::trim) {
+        match line {
+            "-----BEGIN CERTIFICATE-----" => body = Some(String::new()),
+            "-----END CERTIFICATE-----" => {
+                let encoded = body.take().ok_or_else(|| {
+                    WebshotError::config("unmatched END CERTIFICATE line".to_string())
+                })?;
+                let der = base64::engine::general_purpose::STANDARD
+                    .decode(encoded)
+                    .map_err(|e| WebshotError::config(format!("invalid base64: {}", e)))?;
+                certificates.push(der);
+            }
+            _ => {
+                if let Some(body) = &mut body {
+                    body.push_str(line);
+                }
+            }
+        }
+    }
+
+    Ok(certificates)
+}
+
+/// One DER element split off the front of a byte string
+struct DerElement<'a> {
+    tag: u8,
+    /// Header and contents
+    encoded: &'a [u8],
+    contents: &'a [u8],
+    /// Bytes after the element
+    rest: &'a [u8],
+}
+
+fn der_element(data: &[u8]) -> Result<DerElement<'_>> {
+    let invalid = || WebshotError::config("malformed DER certificate".to_string());
+
+    let (&tag, rest) = data.split_first().ok_or_else(invalid)?;
+    let (&first, rest) = rest.split_first().ok_or_else(invalid)?;
+    let (length, rest) = if first < 0x80 {
+        (first as usize, rest)
+    } else {
+        let count = (first & 0x7f) as usize;
+        if count == 0 || count > 4 || rest.len() < count {
+            return Err(invalid());
+        }
+        let length = rest[..count]
+            .iter()
+            .fold(0usize, |length, &byte| (length << 8) | byte as usize);
+        (length, &rest[count..])
+    };
+    if rest.len() < length {
+        return Err(invalid());
+    }
+
+    let header_len = data.len() - rest.len();
+    Ok(DerElement {
+        tag,
+        encoded: &data[..header_len + length],
+        contents: &rest[..length],
+        rest: &rest[length..],
+    })
+}
+
+/// Encoded SubjectPublicKeyInfo of an X.509 certificate: the seventh field
+/// of the TBSCertificate, or sixth when the explicit version is omitted
+fn subject_public_key_info(der: &[u8]) -> Result<&[u8]> {
+    const SEQUENCE: u8 = 0x30;
+    const VERSION: u8 = 0xa0;
+
+    let certificate = der_element(der)?;
+    let tbs = der_element(certificate.contents)?;
+    if certificate.tag != SEQUENCE || tbs.tag != SEQUENCE {
+        return Err(WebshotError::config("not an X.509 certificate".to_string()));
+    }
+
+    let mut fields = tbs.contents;
+    let first = der_element(fields)?;
+    if first.tag == VERSION {
+        fields = first.rest;
+    }
+    // serialNumber, signature, issuer, validity, subject
+    for _ in 0..5 {
+        fields = der_element(fields)?.rest;
+    }
+
+    let spki = der_element(fields)?;
+    if spki.tag != SEQUENCE {
+        return Err(WebshotError::config(
+            "certificate has no public key".to_string(),
+        ));
+    }
+    Ok(spki.encoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Self-signed EC P-256 certificate for "webshot test CA"
+    const TEST_CA: &str = "-----BEGIN CERTIFICATE-----
+MIIBjDCCATGgAwIBAgIUdSieuz6EixLDMZqMgQy5D8i3MSIwCgYIKoZIzj0EAwIw
+GjEYMBYGA1UEAwwPd2Vic2hvdCB0ZXN0IENBMCAXDTI2MTAxNjEwMjczOFoYDzIx
+MjYwOTIyMTAyNzM4WjAaMRgwFgYDVQQDDA93ZWJzaG90IHRlc3QgQ0EwWTATBgcq
+hkjOPQIBBggqhkjOPQMBBwNCAATkvSK2xKG1Te3etSuUEW79NmcziBFxJnSJbKEy
+Td/oGKSACRzyC+6ihhrLHNKFYN/LwOBOL81EFESpeGWb7Kfso1MwUTAdBgNVHQ4E
+FgQUA+MlJRrijWXaKR/Ccqs8mIe3ZlYwHwYDVR0jBBgwFoAUA+MlJRrijWXaKR/C
+cqs8mIe3ZlYwDwYDVR0TAQH/BAUwAwEB/zAKBggqhkjOPQQDAgNJADBGAiEAiCw9
+vCbJpD32tBdcrUA6NZ+KULtiPZur8kY40FMarvkCIQDMRmmKe39HRijlZ6E1zuNf
+WPJ6jtk7VCpFa6w+jvilGA==
+-----END CERTIFICATE-----
+";
+
+    #[test]
+    fn test_spki_hash_of_certificate() {
+        let hashes = spki_hashes(TEST_CA).unwrap();
+        assert_eq!(
+            hashes,
+            vec!["P/XKJXSFwuJGMfByhCZ4x+rBNH+kA5Rk+1exy0q3IZ0=".to_string()]
+        );
+
+        let bundle = format!("{}\n{}", TEST_CA, TEST_CA);
+        assert_eq!(spki_hashes(&bundle).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_invalid_certificates_are_rejected() {
+        assert!(spki_hashes("no certificates here")
+            .unwrap_err()
+            .to_string()
+            .contains("no PEM certificate"));
+        assert!(
+            spki_hashes("-----BEGIN CERTIFICATE-----\nMAMCAQE=\n-----END CERTIFICATE-----")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_ca_cert_flag() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("ca.pem");
+        std::fs::write(&path, TEST_CA).unwrap();
+
+        assert_eq!(
+            ca_cert_flag(&path).unwrap(),
+            format!(
+                "{}P/XKJXSFwuJGMfByhCZ4x+rBNH+kA5Rk+1exy0q3IZ0=",
+                SPKI_LIST_FLAG
+            )
+        );
+        assert!(ca_cert_flag(temp_dir.path().join("missing.pem"))
+            .unwrap_err()
+            .to_string()
+            .contains("Failed to read CA certificate"));
+    }
+}
@@ -0,0 +1,450 @@
+use crate::error::{Result, WebshotError};
+use crate::monitor::{BatchMonitor, BatchStats, JobEntry, JobStatus};
+use std::io::{IsTerminal, Read, Write};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// How often the dashboard redraws while nothing changes
+const REFRESH_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Width of the `#`, status and time columns including separators
+const ROW_PREFIX_WIDTH: usize = 25;
+
+/// Lines used by the header, table heading, detail panel and footer
+const CHROME_LINES: usize = 9;
+
+const KEY_HELP: &str = "↑/↓ select  f next failure  r retry  R retry all failed  s skip  q quit";
+
+/// A key press read from the terminal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Key {
+    Up,
+    Down,
+    Char(char),
+    Interrupt,
+}
+
+/// Selection and status line of the dashboard
+#[derive(Debug, Default)]
+struct View {
+    selected: usize,
+    message: Option<String>,
+    quitting: bool,
+}
+
+impl View {
+    fn handle(&mut self, key: Key, jobs: &[JobEntry], monitor: &BatchMonitor) {
+        let last = jobs.len().saturating_sub(1);
+        match key {
+            Key::Up | Key::Char('k') => self.selected = self.selected.saturating_sub(1),
+            Key::Down | Key::Char('j') => self.selected = (self.selected + 1).min(last),
+            Key::Char('f') => {
+                let next = (1..=jobs.len())
+                    .map(|offset| (self.selected + offset) % jobs.len())
+                    .find(|&index| matches!(jobs[index].status, JobStatus::Failed(_)));
+                match next {
+                    Some(index) => self.selected = index,
+                    None => self.message = Some("No failed jobs".to_string()),
+                }
+            }
+            Key::Char('r') => {
+                self.message = Some(if monitor.retry(self.selected) {
+                    format!("Retrying #{}", self.selected + 1)
+                } else {
+                    "Only failed or skipped jobs can be retried".to_string()
+                });
+            }
+            Key::Char('R') => {
+                self.message = Some(format!("Retrying {} failed jobs", monitor.retry_failed()));
+            }
+            Key::Char('s') => {
+                self.message = Some(if monitor.skip(self.selected) {
+                    format!("Skipped #{}", self.selected + 1)
+                } else {
+                    "Only pending jobs can be skipped".to_string()
+                });
+            }
+            Key::Char('q') | Key::Interrupt => {
+                monitor.close();
+                self.quitting = true;
+                self.message = Some("Skipping pending jobs, waiting for running jobs".to_string());
+            }
+            Key::Char(_) => {}
+        }
+    }
+}
+
+/// Show a live dashboard of a batch run until the user quits, or until every
+/// job has finished without failures. Failed jobs keep the dashboard open so
+/// they can be retried. The monitor is closed when the dashboard exits, so
+/// workers stop once their running jobs finish.
+pub async fn run_dashboard(monitor: &BatchMonitor) -> Result<()> {
+    let result = dashboard(monitor).await;
+    monitor.close();
+    result
+}
+
+/// Fail early when the dashboard cannot be shown
+pub fn ensure_terminal() -> Result<()> {
+    if std::io::stdout().is_terminal() && std::io::stdin().is_terminal() {
+        Ok(())
+    } else {
+        Err(WebshotError::config(
+            "--tui requires an interactive terminal on stdin and stdout",
+        ))
+    }
+}
+
+async fn dashboard(monitor: &BatchMonitor) -> Result<()> {
+    let _terminal = TerminalGuard::enter()?;
+    let mut keys = spawn_key_reader();
+    let mut view = View::default();
+    let mut ticker = tokio::time::interval(REFRESH_INTERVAL);
+    let mut input_open = true;
+
+    loop {
+        let jobs = monitor.jobs();
+        let stats = monitor.stats();
+        let finished = stats.pending == 0 && stats.running == 0;
+        if finished && (view.quitting || stats.failed == 0) {
+            return Ok(());
+        }
+
+        let (width, height) = terminal_size();
+        let frame = render(&jobs, &stats, monitor.elapsed(), &view, width, height);
+        let mut stdout = std::io::stdout().lock();
+        write!(stdout, "\x1b[H{}\x1b[J", frame.join("\x1b[K\r\n"))?;
+        stdout.flush()?;
+        drop(stdout);
+
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = monitor.changed() => {}
+            key = keys.recv(), if input_open => match key {
+                Some(key) => view.handle(key, &jobs, monitor),
+                // Input closed, keep monitoring without key bindings
+                None => input_open = false,
+            },
+        }
+    }
+}
+
+/// Render one dashboard frame as terminal lines
+fn render(
+    jobs: &[JobEntry],
+    stats: &BatchStats,
+    elapsed: Duration,
+    view: &View,
+    width: usize,
+    height: usize,
+) -> Vec<String> {
+    let mut lines = Vec::with_capacity(height);
+
+    let summary = format!(
+        " webshot  {}/{} done · {} failed · {} skipped · {} running · {} pending  {:.1}/min  elapsed {}",
+        stats.done,
+        stats.total,
+        stats.failed,
+        stats.skipped,
+        stats.running,
+        stats.pending,
+        stats.per_minute,
+        format_duration(elapsed)
+    );
+    lines.push(format!("\x1b[1m{}\x1b[0m", fit(&summary, width)));
+    lines.push("─".repeat(width));
+    lines.push(fit(
+        &format!("{:>4}  {:<8}  {:>7}  URL -> OUTPUT", "#", "STATUS", "TIME"),
+        width,
+    ));
+
+    let rows = height.saturating_sub(CHROME_LINES).max(1);
+    let first = view
+        .selected
+        .saturating_sub(rows - 1)
+        .min(jobs.len().saturating_sub(rows));
+    for (index, job) in jobs.iter().enumerate().skip(first).take(rows) {
+        let time = job.elapsed.map(format_duration).unwrap_or_default();
+        let tail = format!("{} -> {}", job.url, job.output.display());
+        let row = format!(
+            "{:>4}  {}{:<8}\x1b[39m  {:>7}  {}",
+            index + 1,
+            status_color(&job.status),
+            job.status.label(),
+            time,
+            fit(&tail, width.saturating_sub(ROW_PREFIX_WIDTH))
+        );
+        if index == view.selected {
+            lines.push(format!("\x1b[7m{}\x1b[0m", row));
+        } else {
+            lines.push(row);
+        }
+    }
+    lines.resize(3 + rows, String::new());
+
+    lines.push("─".repeat(width));
+    match jobs.get(view.selected) {
+        Some(job) => {
+            lines.push(fit(&format!("URL:    {}", job.url), width));
+            lines.push(fit(&format!("Output: {}", job.output.display()), width));
+            let detail = match &job.status {
+                JobStatus::Failed(error) => format!("Error:  {}", error),
+                status => format!("Status: {} ({} attempts)", status.label(), job.attempts),
+            };
+            lines.push(fit(&detail, width));
+        }
+        None => lines.extend([String::new(), String::new(), String::new()]),
+    }
+
+    lines.push("─".repeat(width));
+    let footer = match &view.message {
+        Some(message) => format!("{}  | {}", KEY_HELP, message),
+        None => KEY_HELP.to_string(),
+    };
+    lines.push(fit(&footer, width));
+
+    lines
+}
+
+fn status_color(status: &JobStatus) -> &'static str {
+    match status {
+        JobStatus::Pending => "\x1b[39m",
+        JobStatus::Running => "\x1b[33m",
+        JobStatus::Done => "\x1b[32m",
+        JobStatus::Failed(_) => "\x1b[31m",
+        JobStatus::Skipped => "\x1b[90m",
+    }
+}
+
+/// Truncate text to `width` characters, marking cut text with an ellipsis
+fn fit(text: &str, width: usize) -> String {
+    let text = text.replace(['\n', '\r'], " ");
+    if text.chars().count() <= width {
+        return text;
+    }
+    let mut fitted: String = text.chars().take(width.saturating_sub(1)).collect();
+    if width > 0 {
+        fitted.push('…');
+    }
+    fitted
+}
+
+/// Format a duration as `m:ss`, or `h:mm:ss` from one hour
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    if seconds >= 3600 {
+        format!(
+            "{}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    } else {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+/// Read key presses from stdin on a background thread
+fn spawn_key_reader() -> mpsc::UnboundedReceiver<Key> {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        let mut bytes = std::io::stdin().lock().bytes();
+        while let Some(Ok(byte)) = bytes.next() {
+            let key = match byte {
+                0x03 => Key::Interrupt,
+                0x1b => {
+                    // Arrow keys arrive as ESC [ A / ESC [ B
+                    match (bytes.next(), bytes.next()) {
+                        (Some(Ok(b'[')), Some(Ok(b'A'))) => Key::Up,
+                        (Some(Ok(b'[')), Some(Ok(b'B'))) => Key::Down,
+                        _ => continue,
+                    }
+                }
+                byte => Key::Char(byte as char),
+            };
+            if sender.send(key).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+/// Switches to the alternate screen with raw key input, restoring the
+/// terminal when dropped
+struct TerminalGuard {
+    #[cfg(unix)]
+    original: libc::termios,
+}
+
+impl TerminalGuard {
+    fn enter() -> Result<Self> {
+        #[cfg(unix)]
+        let original = raw_mode()?;
+
+        print!("\x1b[?1049h\x1b[?25l");
+        std::io::stdout().flush()?;
+
+        Ok(Self {
+            #[cfg(unix)]
+            original,
+        })
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        print!("\x1b[?25h\x1b[?1049l");
+        let _ = std::io::stdout().flush();
+
+        #[cfg(unix)]
+        // SAFETY: restores the attributes read from the same descriptor in `raw_mode`
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.original);
+        }
+    }
+}
+
+/// Disable line buffering, echo and signal keys on stdin, returning the
+/// previous terminal attributes
+#[cfg(unix)]
+fn raw_mode() -> Result<libc::termios> {
+    // SAFETY: `termios` is plain data filled in by `tcgetattr` before use
+    unsafe {
+        let mut termios: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(libc::STDIN_FILENO, &mut termios) != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        let original = termios;
+
+        termios.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG);
+        termios.c_cc[libc::VMIN] = 1;
+        termios.c_cc[libc::VTIME] = 0;
+        if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios) != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        Ok(original)
+    }
+}
+
+/// Terminal width and height, falling back to 80x24
+fn terminal_size() -> (usize, usize) {
+    #[cfg(unix)]
+    {
+        // SAFETY: `winsize` is plain data filled in by the ioctl
+        let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+        if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0
+            && size.ws_col > 0
+            && size.ws_row > 0
+        {
+            return (size.ws_col as usize, size.ws_row as usize);
+        }
+    }
+    (80, 24)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ScreenshotConfig;
+
+    fn monitor(count: usize) -> BatchMonitor {
+        let configs = (0..count)
+            .map(|index| {
+                ScreenshotConfig::new(
+                    format!("https://example.com/{}", index),
+                    format!("{}.png", index),
+                )
+            })
+            .collect();
+        BatchMonitor::new(configs, true)
+    }
+
+    #[test]
+    fn test_fit_truncates_with_ellipsis() {
+        assert_eq!(fit("short", 10), "short");
+        assert_eq!(fit("a long line", 6), "a lon…");
+        assert_eq!(fit("line\nbreak", 20), "line break");
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_secs(75)), "1:15");
+        assert_eq!(format_duration(Duration::from_secs(3725)), "1:02:05");
+    }
+
+    #[tokio::test]
+    async fn test_render_shows_selected_failure_detail() {
+        let monitor = monitor(30);
+        let (index, _) = monitor.next_job().await.unwrap();
+        monitor.finish(
+            index,
+            &Err(WebshotError::navigation("net::ERR_NAME_NOT_RESOLVED")),
+        );
+
+        let mut view = View::default();
+        view.handle(Key::Char('f'), &monitor.jobs(), &monitor);
+        assert_eq!(view.selected, 0);
+
+        let lines = render(
+            &monitor.jobs(),
+            &monitor.stats(),
+            Duration::from_secs(90),
+            &view,
+            120,
+            20,
+        );
+        assert_eq!(lines.len(), 20);
+        assert!(lines[0].contains("0/30 done · 1 failed"));
+        assert!(lines[0].contains("elapsed 1:30"));
+        assert!(lines[3].starts_with("\x1b[7m"));
+        assert!(lines
+            .iter()
+            .any(|line| line.contains("Error:  Navigation failed: net::ERR_NAME_NOT_RESOLVED")));
+    }
+
+    #[test]
+    fn test_render_scrolls_to_selection() {
+        let monitor = monitor(30);
+        let view = View {
+            selected: 25,
+            ..Default::default()
+        };
+
+        let lines = render(
+            &monitor.jobs(),
+            &monitor.stats(),
+            Duration::ZERO,
+            &view,
+            80,
+            20,
+        );
+        let rows = &lines[3..3 + 20 - CHROME_LINES];
+        assert!(rows
+            .last()
+            .unwrap()
+            .contains("https://example.com/25 -> 25.png"));
+        assert!(rows.last().unwrap().starts_with("\x1b[7m"));
+    }
+
+    #[test]
+    fn test_keys_retry_and_skip_jobs() {
+        let monitor = monitor(3);
+        let mut view = View {
+            selected: 2,
+            ..Default::default()
+        };
+
+        view.handle(Key::Char('s'), &monitor.jobs(), &monitor);
+        assert_eq!(monitor.jobs()[2].status, JobStatus::Skipped);
+        view.handle(Key::Char('r'), &monitor.jobs(), &monitor);
+        assert_eq!(monitor.jobs()[2].status, JobStatus::Pending);
+        assert_eq!(view.message.as_deref(), Some("Retrying #3"));
+
+        view.handle(Key::Up, &monitor.jobs(), &monitor);
+        view.handle(Key::Char('q'), &monitor.jobs(), &monitor);
+        assert_eq!(view.selected, 1);
+        assert!(view.quitting);
+        assert_eq!(monitor.stats().skipped, 3);
+    }
+}
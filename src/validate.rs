@@ -0,0 +1,253 @@
+//! Checking a batch config without capturing, for `webshot validate`.
+//!
+//! A [`ValidationReport`] goes beyond [`Config::validate`]: it resolves every
+//! entry the way a batch run would (defaults applied, breakpoints expanded,
+//! outputs placed under the output directory) and lists each entry's output
+//! paths, their formats and its effective options with where they came
+//! from. Every invalid entry is reported rather than only the first, along
+//! with outputs written by more than one capture. No browser is launched.
+
+use crate::config::Config;
+use crate::error::Result;
+use crate::explain::{explain_config, Explanation, OptionSource};
+use crate::output::OutputHandler;
+use crate::screenshot::ImageFormat;
+use serde_yaml::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// One output a capture writes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedOutput {
+    pub path: PathBuf,
+    /// Format implied by the extension, `None` when it is not supported
+    pub format: Option<ImageFormat>,
+}
+
+/// One entry of the config, resolved
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryReport {
+    /// The entry's effective options and their sources
+    pub explanation: Explanation,
+    /// One output per breakpoint, or the entry's only output
+    pub outputs: Vec<ResolvedOutput>,
+    /// What would make the entry fail
+    pub errors: Vec<String>,
+    /// What would make the entry misbehave without failing it
+    pub warnings: Vec<String>,
+}
+
+/// Every entry of a config, resolved
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationReport {
+    pub entries: Vec<EntryReport>,
+}
+
+impl ValidationReport {
+    /// Number of captures a batch run would make
+    pub fn capture_count(&self) -> usize {
+        self.entries.iter().map(|entry| entry.outputs.len()).sum()
+    }
+
+    pub fn error_count(&self) -> usize {
+        self.entries.iter().map(|entry| entry.errors.len()).sum()
+    }
+
+    pub fn warning_count(&self) -> usize {
+        self.entries.iter().map(|entry| entry.warnings.len()).sum()
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.error_count() == 0
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    /// Per entry its URL, `-> output (format)` lines, its options as in
+    /// `--explain` and its errors and warnings, then a summary line
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, entry) in self.entries.iter().enumerate() {
+            writeln!(f, "[{}] {}", index + 1, entry.explanation.url)?;
+            for output in &entry.outputs {
+                let format = output
+                    .format
+                    .map_or("unsupported", |format| format.extension());
+                writeln!(f, "  -> {} ({})", output.path.display(), format)?;
+            }
+            for option in &entry.explanation.options {
+                writeln!(
+                    f,
+                    "  {} = {}  ({})",
+                    option.name, option.value, option.source
+                )?;
+            }
+            for error in &entry.errors {
+                writeln!(f, "  error: {}", error)?;
+            }
+            for warning in &entry.warnings {
+                writeln!(f, "  warning: {}", warning)?;
+            }
+        }
+        writeln!(
+            f,
+            "{}, {}, {}, {}",
+            counted(self.entries.len(), "entry", "entries"),
+            counted(self.capture_count(), "capture", "captures"),
+            counted(self.error_count(), "error", "errors"),
+            counted(self.warning_count(), "warning", "warnings")
+        )
+    }
+}
+
+/// `count` followed by the singular or plural noun
+pub fn counted(count: usize, singular: &str, plural: &str) -> String {
+    format!("{} {}", count, if count == 1 { singular } else { plural })
+}
+
+/// Validate `config` and resolve each of its entries, placing outputs under
+/// `output_dir` when one is given.
+///
+/// `layers` are the config files merged into `config` (see
+/// [`explain_config`]). Fails when [`Config::validate_shared`] does; invalid
+/// entries are reported in their [`EntryReport`] instead.
+pub fn validate_config(
+    config: &Config,
+    layers: &[(PathBuf, Value)],
+    output_dir: Option<&Path>,
+) -> Result<ValidationReport> {
+    config.validate_shared()?;
+    let explanations = explain_config(config, layers, &HashMap::<String, OptionSource>::new())?;
+
+    let outputs: Vec<Vec<PathBuf>> = config
+        .screenshots
+        .iter()
+        .map(|screenshot| {
+            screenshot
+                .expand_breakpoints()
+                .iter()
+                .map(|job| job.resolved_output(output_dir))
+                .collect()
+        })
+        .collect();
+    let mut writers: HashMap<&Path, usize> = HashMap::new();
+    for path in outputs.iter().flatten() {
+        *writers.entry(path.as_path()).or_default() += 1;
+    }
+
+    let entries = explanations
+        .into_iter()
+        .zip(&outputs)
+        .zip(&config.screenshots)
+        .enumerate()
+        .map(|(index, ((explanation, paths), screenshot))| {
+            let errors: Vec<String> = screenshot
+                .validate(index)
+                .err()
+                .map(|e| e.to_string())
+                .into_iter()
+                .collect();
+            let mut warnings = Vec::new();
+            let outputs = paths
+                .iter()
+                .map(|path| {
+                    if writers[path.as_path()] > 1 {
+                        warnings.push(format!(
+                            "{} is written by {} captures; the last one wins",
+                            path.display(),
+                            writers[path.as_path()]
+                        ));
+                    }
+                    ResolvedOutput {
+                        path: path.clone(),
+                        format: OutputHandler::validate_output_path(path).ok(),
+                    }
+                })
+                .collect();
+            EntryReport {
+                explanation,
+                outputs,
+                errors,
+                warnings,
+            }
+        })
+        .collect();
+
+    Ok(ValidationReport { entries })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entries_are_resolved_and_checked() {
+        let raw: Value = serde_yaml::from_str(
+            r#"
+defaults:
+  width: 1440
+screenshots:
+  - url: https://example.com
+    output: home.png
+    breakpoints: [375, 1280]
+  - url: https://example.com/about
+    output: about.bmp
+  - url: https://example.com/team
+    output: shared.jpg
+    timeout: 0
+  - url: https://example.com/jobs
+    output: shared.jpg
+"#,
+        )
+        .unwrap();
+        let config = Config::from_yaml(raw.clone()).unwrap();
+        let layers = [(PathBuf::from("webshot.yaml"), raw)];
+        let report = validate_config(&config, &layers, Some(Path::new("out"))).unwrap();
+
+        assert_eq!(report.entries.len(), 4);
+        assert_eq!(report.capture_count(), 5);
+        assert_eq!(
+            report.entries[0].outputs,
+            vec![
+                ResolvedOutput {
+                    path: PathBuf::from("out/home-375.png"),
+                    format: Some(ImageFormat::Png),
+                },
+                ResolvedOutput {
+                    path: PathBuf::from("out/home-1280.png"),
+                    format: Some(ImageFormat::Png),
+                },
+            ]
+        );
+        assert_eq!(
+            report.entries[1].explanation.source("width"),
+            Some(&OptionSource::Defaults(PathBuf::from("webshot.yaml")))
+        );
+
+        // Each invalid entry is an error, a shared output a warning
+        assert_eq!(report.entries[1].outputs[0].format, None);
+        assert!(!report.is_valid());
+        assert_eq!(report.error_count(), 2);
+        assert!(report.entries[2].errors[0].contains("Timeout"));
+        assert_eq!(report.warning_count(), 2);
+
+        let text = report.to_string();
+        assert!(text.contains("[2] https://example.com/about\n  -> out/about.bmp (unsupported)\n"));
+        assert!(text.contains("  width = 1440  (defaults in webshot.yaml)\n"));
+        assert!(text.ends_with("4 entries, 5 captures, 2 errors, 2 warnings\n"));
+    }
+
+    #[test]
+    fn test_counts_use_the_singular_for_one() {
+        assert_eq!(counted(1, "error", "errors"), "1 error");
+        assert_eq!(counted(0, "error", "errors"), "0 errors");
+        assert_eq!(counted(1, "entry", "entries"), "1 entry");
+    }
+
+    #[test]
+    fn test_invalid_config_fails() {
+        let raw: Value = serde_yaml::from_str("screenshots: []").unwrap();
+        let config = Config::from_yaml(raw).unwrap();
+        assert!(validate_config(&config, &[], None).is_err());
+    }
+}
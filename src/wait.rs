@@ -0,0 +1,311 @@
+use crate::error::{Result, WebshotError};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Page state a capture waits for, parsed from `--wait-for` / `wait_for`:
+///
+/// - `.selector` - an element matching the selector exists
+/// - `visible:.selector` - a matching element is rendered and not hidden
+/// - `hidden:.selector` - no matching element is visible (or none exists)
+/// - `text:.selector=Some text` - a matching element contains the text
+///
+/// Conditions joined with ` && ` must all hold; joined with ` || `, any one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WaitCondition {
+    Present(String),
+    Visible(String),
+    Hidden(String),
+    Text { selector: String, text: String },
+    All(Vec<WaitCondition>),
+    Any(Vec<WaitCondition>),
+}
+
+/// Promise resolving to `true` once `__CONDITION__` holds, rechecked on
+/// every DOM mutation and every `__POLL_MS__` milliseconds (for changes no
+/// mutation reports, such as stylesheets loading), or to `false` after
+/// `__TIMEOUT_MS__` milliseconds
+const OBSERVER_SCRIPT: &str = r#"
+new Promise((resolve) => {
+    const check = () => __CONDITION__;
+    if (check()) {
+        resolve(true);
+        return;
+    }
+    let observer, interval, timer;
+    const finish = (met) => {
+        observer.disconnect();
+        clearInterval(interval);
+        clearTimeout(timer);
+        resolve(met);
+    };
+    const recheck = () => { if (check()) finish(true); };
+    observer = new MutationObserver(recheck);
+    observer.observe(document, { childList: true, subtree: true, attributes: true, characterData: true });
+    interval = setInterval(recheck, __POLL_MS__);
+    timer = setTimeout(() => finish(false), __TIMEOUT_MS__);
+})
+"#;
+
+/// Visibility check shared by the `visible` and `hidden` conditions
+const IS_VISIBLE: &str = "((el) => { const rect = el.getBoundingClientRect(); \
+const style = getComputedStyle(el); return rect.width > 0 && rect.height > 0 && \
+style.visibility !== 'hidden' && style.display !== 'none' && style.opacity !== '0'; })";
+
+impl WaitCondition {
+    /// JavaScript expression that evaluates to `true` once the condition holds
+    pub fn script(&self) -> String {
+        let all = |selector: &str| {
+            format!(
+                "Array.from(document.querySelectorAll({}))",
+                js_string(selector)
+            )
+        };
+        match self {
+            WaitCondition::Present(selector) => {
+                format!("(document.querySelector({}) !== null)", js_string(selector))
+            }
+            WaitCondition::Visible(selector) => {
+                format!("{}.some({})", all(selector), IS_VISIBLE)
+            }
+            WaitCondition::Hidden(selector) => {
+                format!("!{}.some({})", all(selector), IS_VISIBLE)
+            }
+            WaitCondition::Text { selector, text } => format!(
+                "{}.some((el) => (el.innerText || el.textContent || '').includes({}))",
+                all(selector),
+                js_string(text)
+            ),
+            WaitCondition::All(conditions) => join_scripts(conditions, " && "),
+            WaitCondition::Any(conditions) => join_scripts(conditions, " || "),
+        }
+    }
+
+    /// JavaScript promise that resolves to `true` as soon as the condition
+    /// holds, or `false` after `timeout`, waiting in the page with a
+    /// `MutationObserver` backed by polling every `poll_interval`
+    pub fn observer_script(&self, timeout: Duration, poll_interval: Duration) -> String {
+        OBSERVER_SCRIPT
+            .replace("__CONDITION__", &self.script())
+            .replace("__POLL_MS__", &poll_interval.as_millis().max(1).to_string())
+            .replace("__TIMEOUT_MS__", &timeout.as_millis().to_string())
+    }
+
+    fn parse_single(value: &str) -> Result<Self> {
+        let value = value.trim();
+        let required = |selector: &str, kind: &str| {
+            let selector = selector.trim();
+            if selector.is_empty() {
+                Err(WebshotError::config(format!(
+                    "Wait condition `{}` needs a selector, e.g. {}:.content",
+                    value, kind
+                )))
+            } else {
+                Ok(selector.to_string())
+            }
+        };
+
+        if let Some(selector) = value.strip_prefix("visible:") {
+            Ok(Self::Visible(required(selector, "visible")?))
+        } else if let Some(selector) = value.strip_prefix("hidden:") {
+            Ok(Self::Hidden(required(selector, "hidden")?))
+        } else if let Some(rest) = value.strip_prefix("text:") {
+            let (selector, text) = split_text_condition(rest).ok_or_else(|| {
+                WebshotError::config(format!(
+                    "Wait condition `{}` needs a selector and text, e.g. text:#status=Ready",
+                    value
+                ))
+            })?;
+            Ok(Self::Text {
+                selector: required(selector, "text")?,
+                text: text.to_string(),
+            })
+        } else if value.is_empty() {
+            Err(WebshotError::config("Wait condition must not be empty"))
+        } else {
+            Ok(Self::Present(value.to_string()))
+        }
+    }
+}
+
+fn join_scripts(conditions: &[WaitCondition], operator: &str) -> String {
+    let scripts: Vec<_> = conditions.iter().map(WaitCondition::script).collect();
+    format!("({})", scripts.join(operator))
+}
+
+fn js_string(value: &str) -> String {
+    serde_json::Value::from(value).to_string()
+}
+
+/// Split `selector=text` at the first `=` outside attribute brackets and
+/// quotes, so `text:a[href="/x"]=Docs` keeps the attribute selector intact
+fn split_text_condition(value: &str) -> Option<(&str, &str)> {
+    let mut depth = 0usize;
+    let mut quote = None;
+    for (index, c) in value.char_indices() {
+        match (c, quote) {
+            ('"' | '\'', None) => quote = Some(c),
+            (c, Some(open)) if c == open => quote = None,
+            (_, Some(_)) => {}
+            ('[', None) => depth += 1,
+            (']', None) => depth = depth.saturating_sub(1),
+            ('=', None) if depth == 0 => return Some((&value[..index], &value[index + 1..])),
+            _ => {}
+        }
+    }
+    None
+}
+
+impl FromStr for WaitCondition {
+    type Err = WebshotError;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let (all, any) = (value.contains(" && "), value.contains(" || "));
+        if all && any {
+            return Err(WebshotError::config(format!(
+                "Wait condition `{}` cannot mix && and ||",
+                value
+            )));
+        }
+
+        if all {
+            value
+                .split(" && ")
+                .map(Self::parse_single)
+                .collect::<Result<_>>()
+                .map(Self::All)
+        } else if any {
+            value
+                .split(" || ")
+                .map(Self::parse_single)
+                .collect::<Result<_>>()
+                .map(Self::Any)
+        } else {
+            Self::parse_single(value)
+        }
+    }
+}
+
+impl fmt::Display for WaitCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let join = |f: &mut fmt::Formatter<'_>, conditions: &[WaitCondition], operator| {
+            for (index, condition) in conditions.iter().enumerate() {
+                if index > 0 {
+                    f.write_str(operator)?;
+                }
+                write!(f, "{}", condition)?;
+            }
+            Ok(())
+        };
+        match self {
+            WaitCondition::Present(selector) => write!(f, "element: {}", selector),
+            WaitCondition::Visible(selector) => write!(f, "visible: {}", selector),
+            WaitCondition::Hidden(selector) => write!(f, "hidden: {}", selector),
+            WaitCondition::Text { selector, text } => {
+                write!(f, "text {:?} in: {}", text, selector)
+            }
+            WaitCondition::All(conditions) => join(f, conditions, " && "),
+            WaitCondition::Any(conditions) => join(f, conditions, " || "),
+        }
+    }
+}
+
+/// Parse a duration such as `500ms`, `2s` or `1.5s`; bare numbers are
+/// milliseconds
+pub fn parse_duration(value: &str) -> Result<Duration> {
+    let value = value.trim();
+    let (number, unit_ms) = if let Some(ms) = value.strip_suffix("ms") {
+        (ms, 1.0)
+    } else if let Some(seconds) = value.strip_suffix('s') {
+        (seconds, 1000.0)
+    } else {
+        (value, 1.0)
+    };
+    match number.trim().parse::<f64>() {
+        Ok(number) if number.is_finite() && number >= 0.0 => {
+            Ok(Duration::from_secs_f64(number * unit_ms / 1000.0))
+        }
+        _ => Err(WebshotError::config(format!(
+            "Invalid duration: {}. Use e.g. 500ms or 2s",
+            value
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_wait_conditions() {
+        assert_eq!(
+            ".content".parse::<WaitCondition>().unwrap(),
+            WaitCondition::Present(".content".to_string())
+        );
+        assert_eq!(
+            "visible:.chart && hidden: .spinner"
+                .parse::<WaitCondition>()
+                .unwrap(),
+            WaitCondition::All(vec![
+                WaitCondition::Visible(".chart".to_string()),
+                WaitCondition::Hidden(".spinner".to_string()),
+            ])
+        );
+        assert_eq!(
+            r#"text:a[href="/a=b"]=Docs = home || #empty"#.parse::<WaitCondition>().unwrap(),
+            WaitCondition::Any(vec![
+                WaitCondition::Text {
+                    selector: r#"a[href="/a=b"]"#.to_string(),
+                    text: "Docs = home".to_string(),
+                },
+                WaitCondition::Present("#empty".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_invalid_wait_conditions() {
+        for (value, message) in [
+            ("visible:", "needs a selector"),
+            ("text:#status", "needs a selector and text"),
+            ("text:=Ready", "needs a selector"),
+            (".a && .b || .c", "cannot mix"),
+            ("  ", "must not be empty"),
+        ] {
+            let error = value.parse::<WaitCondition>().unwrap_err().to_string();
+            assert!(error.contains(message), "{}: {}", value, error);
+        }
+    }
+
+    #[test]
+    fn test_condition_scripts_escape_values() {
+        let condition: WaitCondition =
+            r#"text:#status=Say "hi" && hidden:.spinner"#.parse().unwrap();
+        let script = condition.script();
+        assert!(script.contains(r#"includes("Say \"hi\"")"#));
+        assert!(script.contains(r#"!Array.from(document.querySelectorAll(".spinner"))"#));
+        assert_eq!(
+            condition.to_string(),
+            r#"text "Say \"hi\"" in: #status && hidden: .spinner"#
+        );
+    }
+
+    #[test]
+    fn test_observer_script_waits_in_page() {
+        let condition: WaitCondition = "visible:.chart".parse().unwrap();
+        let script = condition.observer_script(Duration::from_secs(30), Duration::from_millis(250));
+        assert!(script.contains(&format!("const check = () => {};", condition.script())));
+        assert!(script.contains("new MutationObserver(recheck)"));
+        assert!(script.contains("setInterval(recheck, 250)"));
+        assert!(script.contains("finish(false), 30000)"));
+    }
+
+    #[test]
+    fn test_durations_parse_with_units() {
+        assert_eq!(parse_duration("500ms").unwrap(), Duration::from_millis(500));
+        assert_eq!(parse_duration("1.5s").unwrap(), Duration::from_millis(1500));
+        assert_eq!(parse_duration("250").unwrap(), Duration::from_millis(250));
+        assert!(parse_duration("-1s").is_err());
+        assert!(parse_duration("soon").is_err());
+    }
+}
@@ -2,17 +2,23 @@ use assert_cmd::Command;
 use predicates::prelude::*;
 use std::{fs, path::Path};
 use tempfile::TempDir;
+use webshot::dev_server::DevServer;
 
-const TEST_URL: &str = "https://httpbin.org/html";
+/// Serve webshot's bundled fixture pages on a free localhost port
+fn fixtures() -> DevServer {
+    DevServer::start().unwrap()
+}
 
 #[tokio::test]
-#[ignore = "requires Chrome/Chromium and network access"]
+#[ignore = "requires Chrome/Chromium"]
 async fn test_basic_screenshot() {
+    let server = fixtures();
+    let url = server.url("/static");
     let temp_dir = TempDir::new().unwrap();
     let output_path = temp_dir.path().join("test.png");
 
     let mut cmd = Command::cargo_bin("webshot").unwrap();
-    cmd.arg(TEST_URL)
+    cmd.arg(&url)
         .arg("-o")
         .arg(&output_path)
         .arg("-w")
@@ -29,13 +35,15 @@ async fn test_basic_screenshot() {
 }
 
 #[tokio::test]
-#[ignore = "requires Chrome/Chromium and network access"]
+#[ignore = "requires Chrome/Chromium"]
 async fn test_pdf_generation() {
+    let server = fixtures();
+    let url = server.url("/static");
     let temp_dir = TempDir::new().unwrap();
     let output_path = temp_dir.path().join("test.pdf");
 
     let mut cmd = Command::cargo_bin("webshot").unwrap();
-    cmd.arg("pdf").arg(TEST_URL).arg("-o").arg(&output_path);
+    cmd.arg("pdf").arg(&url).arg("-o").arg(&output_path);
 
     cmd.assert().success();
     assert!(output_path.exists());
@@ -47,13 +55,15 @@ async fn test_pdf_generation() {
 }
 
 #[tokio::test]
-#[ignore = "requires Chrome/Chromium and network access"]
+#[ignore = "requires Chrome/Chromium"]
 async fn test_element_screenshot() {
+    let server = fixtures();
+    let url = server.url("/static");
     let temp_dir = TempDir::new().unwrap();
     let output_path = temp_dir.path().join("element.png");
 
     let mut cmd = Command::cargo_bin("webshot").unwrap();
-    cmd.arg(TEST_URL)
+    cmd.arg(&url)
         .arg("-o")
         .arg(&output_path)
         .arg("-s")
@@ -64,13 +74,15 @@ async fn test_element_screenshot() {
 }
 
 #[tokio::test]
-#[ignore = "requires Chrome/Chromium and network access"]
+#[ignore = "requires Chrome/Chromium"]
 async fn test_javascript_execution() {
+    let server = fixtures();
+    let url = server.url("/static");
     let temp_dir = TempDir::new().unwrap();
     let output_path = temp_dir.path().join("js-test.png");
 
     let mut cmd = Command::cargo_bin("webshot").unwrap();
-    cmd.arg(TEST_URL)
+    cmd.arg(&url)
         .arg("-o")
         .arg(&output_path)
         .arg("-j")
@@ -81,10 +93,12 @@ async fn test_javascript_execution() {
 }
 
 #[tokio::test]
-#[ignore = "requires Chrome/Chromium and network access"]
+#[ignore = "requires Chrome/Chromium"]
 async fn test_text_extraction() {
+    let server = fixtures();
+    let url = server.url("/static");
     let mut cmd = Command::cargo_bin("webshot").unwrap();
-    cmd.arg("text").arg(TEST_URL).arg("-s").arg("h1");
+    cmd.arg("text").arg(&url).arg("-s").arg("h1");
 
     cmd.assert()
         .success()
@@ -92,8 +106,10 @@ async fn test_text_extraction() {
 }
 
 #[tokio::test]
-#[ignore = "requires Chrome/Chromium and network access"]
+#[ignore = "requires Chrome/Chromium"]
 async fn test_config_processing() {
+    let server = fixtures();
+    let url = server.url("/static");
     let temp_dir = TempDir::new().unwrap();
 
     // Create a simple config file
@@ -109,7 +125,7 @@ screenshots:
     width: 1200
     height: 800
 "#,
-        TEST_URL, TEST_URL
+        url, url
     );
 
     let config_path = temp_dir.path().join("config.yaml");
@@ -130,13 +146,15 @@ screenshots:
 }
 
 #[tokio::test]
-#[ignore = "requires Chrome/Chromium and network access"]
+#[ignore = "requires Chrome/Chromium"]
 async fn test_jpeg_quality() {
+    let server = fixtures();
+    let url = server.url("/static");
     let temp_dir = TempDir::new().unwrap();
     let output_path = temp_dir.path().join("quality.jpg");
 
     let mut cmd = Command::cargo_bin("webshot").unwrap();
-    cmd.arg(TEST_URL)
+    cmd.arg(&url)
         .arg("-o")
         .arg(&output_path)
         .arg("-q")
@@ -151,46 +169,235 @@ async fn test_jpeg_quality() {
 }
 
 #[tokio::test]
-#[ignore = "requires Chrome/Chromium and network access"]
+#[ignore = "requires Chrome/Chromium"]
 async fn test_retina_mode() {
+    let server = fixtures();
+    let url = server.url("/static");
     let temp_dir = TempDir::new().unwrap();
     let output_path = temp_dir.path().join("retina.png");
 
     let mut cmd = Command::cargo_bin("webshot").unwrap();
-    cmd.arg(TEST_URL)
+    cmd.arg(&url).arg("-o").arg(&output_path).arg("--retina");
+
+    cmd.assert().success();
+    assert!(output_path.exists());
+}
+
+#[tokio::test]
+#[ignore = "requires Chrome/Chromium"]
+async fn test_element_screenshot_format_and_retina() {
+    let server = fixtures();
+    let url = server.url("/static");
+    let temp_dir = TempDir::new().unwrap();
+    let png_path = temp_dir.path().join("card.png");
+    let jpeg_path = temp_dir.path().join("card.jpg");
+
+    let mut cmd = Command::cargo_bin("webshot").unwrap();
+    cmd.arg(&url)
         .arg("-o")
-        .arg(&output_path)
+        .arg(&png_path)
+        .arg("--selector")
+        .arg("#card");
+    cmd.assert().success();
+
+    let mut cmd = Command::cargo_bin("webshot").unwrap();
+    cmd.arg(&url)
+        .arg("-o")
+        .arg(&jpeg_path)
+        .arg("--selector")
+        .arg("#card")
+        .arg("--quality")
+        .arg("60")
         .arg("--retina");
+    cmd.assert().success();
+
+    let content = fs::read(&jpeg_path).unwrap();
+    assert!(content.starts_with(&[0xFF, 0xD8, 0xFF]));
+    let (width, height) = image::image_dimensions(&png_path).unwrap();
+    let (retina_width, retina_height) = image::image_dimensions(&jpeg_path).unwrap();
+    assert_eq!((retina_width, retina_height), (width * 2, height * 2));
+}
+
+#[tokio::test]
+#[ignore = "requires Chrome/Chromium"]
+async fn test_full_page_screenshot() {
+    let server = fixtures();
+    let url = server.url("/static");
+    let temp_dir = TempDir::new().unwrap();
+    let output_path = temp_dir.path().join("full-page.png");
+
+    let mut cmd = Command::cargo_bin("webshot").unwrap();
+    cmd.arg(&url)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("-H")
+        .arg("300")
+        .arg("--full-page");
 
     cmd.assert().success();
-    assert!(output_path.exists());
+
+    let image = image::open(&output_path).unwrap();
+    assert!(image.height() > 300);
+}
+
+#[tokio::test]
+#[ignore = "requires Chrome/Chromium"]
+async fn test_capture_modes() {
+    let server = fixtures();
+    let url = server.url("/static");
+    let temp_dir = TempDir::new().unwrap();
+    let viewport_path = temp_dir.path().join("viewport.png");
+    let full_page_path = temp_dir.path().join("full-page.png");
+
+    // The page is taller than 300 pixels, and a viewport capture leaves the
+    // rest out
+    for (mode, path) in [("viewport", &viewport_path), ("full-page", &full_page_path)] {
+        let mut cmd = Command::cargo_bin("webshot").unwrap();
+        cmd.arg(&url)
+            .arg("-o")
+            .arg(path)
+            .arg("-w")
+            .arg("800")
+            .arg("-H")
+            .arg("300")
+            .arg("--capture")
+            .arg(mode);
+        cmd.assert().success();
+    }
+
+    assert_eq!(image::image_dimensions(&viewport_path).unwrap(), (800, 300));
+    let (_, full_height) = image::image_dimensions(&full_page_path).unwrap();
+    assert!(full_height > 300);
+
+    let mut cmd = Command::cargo_bin("webshot").unwrap();
+    cmd.arg(&url)
+        .arg("--capture")
+        .arg("viewport")
+        .arg("--full-page");
+    cmd.assert().failure();
 }
 
 #[tokio::test]
-#[ignore = "requires Chrome/Chromium and network access"]
+#[ignore = "requires Chrome/Chromium"]
+async fn test_freeze_sticky_keeps_fixed_elements_in_place() {
+    let temp_dir = TempDir::new().unwrap();
+    let output_path = temp_dir.path().join("frozen.png");
+    // A fixed bar inside a positioned wrapper 200 pixels down the page
+    let page = "data:text/html,<body style='margin:0'><div style='height:200px'></div>\
+        <div style='position:relative;height:2000px'>\
+        <div style='position:fixed;top:0;left:0;width:100px;height:50px;background:red'></div>\
+        </div></body>";
+
+    let mut cmd = Command::cargo_bin("webshot").unwrap();
+    cmd.arg(page)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("-w")
+        .arg("400")
+        .arg("-H")
+        .arg("300")
+        .arg("--full-page")
+        .arg("--freeze-sticky");
+    cmd.assert().success();
+
+    let image = image::open(&output_path).unwrap().to_rgba8();
+    assert_eq!(image.get_pixel(10, 10).0, [255, 0, 0, 255]);
+    assert_ne!(image.get_pixel(10, 210).0, [255, 0, 0, 255]);
+}
+
+#[tokio::test]
+#[ignore = "requires Chrome/Chromium"]
 async fn test_wait_for_element() {
+    let server = fixtures();
+    let url = server.url("/spa");
     let temp_dir = TempDir::new().unwrap();
     let output_path = temp_dir.path().join("wait.png");
 
     let mut cmd = Command::cargo_bin("webshot").unwrap();
-    cmd.arg(TEST_URL)
+    cmd.arg(&url)
         .arg("-o")
         .arg(&output_path)
         .arg("--wait-for")
-        .arg("h1");
+        .arg("#items");
 
     cmd.assert().success();
     assert!(output_path.exists());
 }
 
 #[tokio::test]
-#[ignore = "requires Chrome/Chromium and network access"]
+#[ignore = "requires Chrome/Chromium"]
+async fn test_no_javascript_keeps_page_scripts_from_running() {
+    let server = fixtures();
+    let url = server.url("/spa?delay=0");
+
+    // The page's script would replace the loading message with the items
+    let mut cmd = Command::cargo_bin("webshot").unwrap();
+    cmd.arg("--no-javascript").arg("text").arg(&url);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Loading..."))
+        .stdout(predicate::str::contains("Items").not());
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut cmd = Command::cargo_bin("webshot").unwrap();
+    cmd.arg("--no-javascript")
+        .arg(&url)
+        .arg("-o")
+        .arg(temp_dir.path().join("spa.png"))
+        .arg("--wait-for")
+        .arg("#items")
+        .arg("--timeout")
+        .arg("3");
+    cmd.assert().failure();
+}
+
+#[tokio::test]
+#[ignore = "requires Chrome/Chromium"]
+async fn test_batch_entry_disables_javascript() {
+    let server = fixtures();
+    let url = server.url("/spa?delay=0");
+    let temp_dir = TempDir::new().unwrap();
+    let config_content = format!(
+        r##"
+screenshots:
+  - url: "{}"
+    output: "scripted.png"
+    wait_for: "#items"
+    timeout: 5
+  - url: "{}"
+    output: "static.png"
+    wait_for: "#items"
+    timeout: 3
+    disable_javascript: true
+"##,
+        url, url
+    );
+    let config_path = temp_dir.path().join("config.yaml");
+    fs::write(&config_path, config_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("webshot").unwrap();
+    cmd.arg("multi")
+        .arg(&config_path)
+        .arg("-o")
+        .arg(temp_dir.path());
+    cmd.output().unwrap();
+
+    // Only the entry without scripts never renders the items, so its wait
+    // times out
+    assert!(temp_dir.path().join("scripted.png").exists());
+    assert!(!temp_dir.path().join("static.png").exists());
+}
+
+#[tokio::test]
+#[ignore = "requires Chrome/Chromium"]
 async fn test_custom_user_agent() {
+    let server = fixtures();
+    let url = server.url("/headers");
     let temp_dir = TempDir::new().unwrap();
     let output_path = temp_dir.path().join("user-agent.png");
 
     let mut cmd = Command::cargo_bin("webshot").unwrap();
-    cmd.arg("https://httpbin.org/user-agent")
+    cmd.arg(&url)
         .arg("-o")
         .arg(&output_path)
         .arg("--user-agent")
@@ -200,6 +407,63 @@ async fn test_custom_user_agent() {
     assert!(output_path.exists());
 }
 
+#[tokio::test]
+#[ignore = "requires Chrome/Chromium"]
+async fn test_testkit_captures_match_their_golden() {
+    use webshot::{screenshot::ScreenshotOptions, testkit};
+
+    let server = testkit::fixtures().unwrap();
+    let browser = testkit::launch_browser().await.unwrap();
+    let options = ScreenshotOptions::new().viewport(800, 600);
+    let temp_dir = TempDir::new().unwrap();
+    let golden = temp_dir.path().join("static.png");
+
+    let first = testkit::capture(&browser, &server.url("/static"), &options)
+        .await
+        .unwrap();
+    testkit::check_golden(&first, &golden, &testkit::GoldenOptions::new().update(true)).unwrap();
+
+    let second = testkit::capture(&browser, &server.url("/static"), &options)
+        .await
+        .unwrap();
+    testkit::assert_golden_with(
+        &second,
+        &golden,
+        &testkit::GoldenOptions::new().update(false),
+    );
+}
+
+#[tokio::test]
+#[ignore = "requires Chrome/Chromium"]
+async fn test_capture_cache_serves_other_output_paths() {
+    use std::sync::Arc;
+    use std::time::Duration;
+    use webshot::{cache::CaptureCache, screenshot::ScreenshotOptions, testkit};
+
+    let server = fixtures();
+    let cache = Arc::new(CaptureCache::new(8, Duration::from_secs(60)));
+    let browser = testkit::launch_browser()
+        .await
+        .unwrap()
+        .with_capture_cache(Arc::clone(&cache));
+    let options = ScreenshotOptions::new().viewport(640, 480);
+    let temp_dir = TempDir::new().unwrap();
+
+    let first = temp_dir.path().join("first.png");
+    let second = temp_dir.path().join("second.png");
+    let url = server.url("/static");
+    assert_eq!(
+        browser.screenshot(&url, &first, &options).await.unwrap(),
+        first
+    );
+    assert_eq!(
+        browser.screenshot(&url, &second, &options).await.unwrap(),
+        second
+    );
+    assert_eq!(cache.stats().hits, 1);
+    assert_eq!(fs::read(&first).unwrap(), fs::read(&second).unwrap());
+}
+
 #[tokio::test]
 #[ignore = "requires Chrome/Chromium startup before validation completes"]
 async fn test_error_handling_invalid_url() {
@@ -210,13 +474,15 @@ async fn test_error_handling_invalid_url() {
 }
 
 #[tokio::test]
-#[ignore = "requires Chrome/Chromium and network access"]
+#[ignore = "requires Chrome/Chromium"]
 async fn test_error_handling_invalid_selector() {
+    let server = fixtures();
+    let url = server.url("/static");
     let temp_dir = TempDir::new().unwrap();
     let output_path = temp_dir.path().join("invalid.png");
 
     let mut cmd = Command::cargo_bin("webshot").unwrap();
-    cmd.arg(TEST_URL)
+    cmd.arg(&url)
         .arg("-o")
         .arg(&output_path)
         .arg("-s")
@@ -265,6 +531,451 @@ async fn test_cli_rejects_non_web_url_before_browser_startup() {
     }
 }
 
+#[tokio::test]
+async fn test_cli_rejects_unknown_device_before_browser_startup() {
+    let mut cmd = Command::cargo_bin("webshot").unwrap();
+    cmd.args(["https://example.com", "--device", "Unknown Phone"]);
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown device"))
+        .stderr(predicate::str::contains("iPhone 14 Pro"));
+}
+
+#[test]
+fn test_png_colors_rejects_out_of_range_and_grayscale_combination() {
+    let mut cmd = Command::cargo_bin("webshot").unwrap();
+    cmd.args(["https://example.com", "--png-colors", "1000"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("2..=256"));
+
+    let mut cmd = Command::cargo_bin("webshot").unwrap();
+    cmd.args(["https://example.com", "--png-colors", "64", "--grayscale"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+}
+
+#[tokio::test]
+async fn test_list_rejects_invalid_urls_before_browser_startup() {
+    let temp_dir = TempDir::new().unwrap();
+    let list_path = temp_dir.path().join("urls.txt");
+    fs::write(&list_path, "https://example.com\nfile:///etc/passwd\n").unwrap();
+
+    let mut cmd = Command::cargo_bin("webshot").unwrap();
+    cmd.arg("list").arg(&list_path);
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Unsupported URL scheme"));
+}
+
+#[test]
+fn test_freeze_sticky_requires_full_page() {
+    for args in [&[][..], &["--capture", "viewport"]] {
+        let mut cmd = Command::cargo_bin("webshot").unwrap();
+        cmd.args(["https://example.com", "--freeze-sticky"])
+            .args(args);
+        cmd.assert().failure().stderr(predicate::str::contains(
+            "--freeze-sticky requires --full-page",
+        ));
+    }
+
+    let mut cmd = Command::cargo_bin("webshot").unwrap();
+    cmd.args(["screenshot", "https://example.com", "--freeze-sticky"]);
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "--freeze-sticky requires --full-page",
+    ));
+}
+
+#[test]
+fn test_widths_conflicts_with_device() {
+    let mut cmd = Command::cargo_bin("webshot").unwrap();
+    cmd.arg("https://example.com")
+        .arg("--widths")
+        .arg("375,1280")
+        .arg("--device")
+        .arg("Pixel 7");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+}
+
+#[test]
+fn test_crawl_rejects_non_http_seed_before_browser_startup() {
+    let temp_dir = TempDir::new().unwrap();
+
+    let mut cmd = Command::cargo_bin("webshot").unwrap();
+    cmd.arg("crawl")
+        .arg("file:///etc/passwd")
+        .arg("-o")
+        .arg(temp_dir.path());
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Unsupported URL scheme"));
+}
+
+#[tokio::test]
+#[ignore = "requires Chrome/Chromium"]
+async fn test_crawl_writes_resumable_state() {
+    let server = fixtures();
+    let url = server.url("/");
+    let temp_dir = TempDir::new().unwrap();
+
+    let mut cmd = Command::cargo_bin("webshot").unwrap();
+    cmd.arg("crawl")
+        .arg(&url)
+        .arg("-o")
+        .arg(temp_dir.path())
+        .arg("--max-pages")
+        .arg("2");
+
+    cmd.assert().success();
+    assert!(temp_dir.path().join("crawl-state.json").exists());
+}
+
+#[test]
+fn test_dedupe_links_identical_files_and_writes_manifest() {
+    let temp_dir = TempDir::new().unwrap();
+    let nested = temp_dir.path().join("2024-01-02");
+    fs::create_dir(&nested).unwrap();
+    fs::write(temp_dir.path().join("home.png"), b"identical").unwrap();
+    fs::write(nested.join("home.png"), b"identical").unwrap();
+    fs::write(nested.join("about.png"), b"different").unwrap();
+
+    let mut cmd = Command::cargo_bin("webshot").unwrap();
+    cmd.arg("dedupe").arg(temp_dir.path());
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Deduplicated 1 of 3 files"));
+
+    let manifest = fs::read_to_string(temp_dir.path().join("dedupe-manifest.json")).unwrap();
+    assert!(manifest.contains("\"matched\": \"bytes\""));
+    assert_eq!(fs::read(nested.join("home.png")).unwrap(), b"identical");
+}
+
+#[test]
+fn test_missing_init_script_fails_before_browser_startup() {
+    let temp_dir = TempDir::new().unwrap();
+
+    let mut cmd = Command::cargo_bin("webshot").unwrap();
+    cmd.arg("https://example.com")
+        .arg("--init-script")
+        .arg(temp_dir.path().join("missing.js"));
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Failed to read init script"));
+}
+
+#[test]
+fn test_unknown_tiff_compression_is_rejected() {
+    let mut cmd = Command::cargo_bin("webshot").unwrap();
+    cmd.args([
+        "https://example.com",
+        "-o",
+        "scan.tiff",
+        "--tiff-compression",
+        "jpeg",
+    ]);
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown TIFF compression"));
+}
+
+#[test]
+fn test_tui_requires_interactive_terminal() {
+    let temp_dir = TempDir::new().unwrap();
+    let config_path = temp_dir.path().join("config.yaml");
+    fs::write(
+        &config_path,
+        "screenshots:\n  - url: https://example.com\n    output: home.png\n",
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("webshot").unwrap();
+    cmd.arg("multi").arg(&config_path).arg("--tui");
+
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "--tui requires an interactive terminal",
+    ));
+}
+
+#[test]
+fn test_unknown_color_profile_is_rejected() {
+    let mut cmd = Command::cargo_bin("webshot").unwrap();
+    cmd.args(["https://example.com", "--color-profile", "adobe-rgb"]);
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown color profile"));
+}
+
+#[test]
+fn test_unsupported_proxy_scheme_is_rejected() {
+    let mut cmd = Command::cargo_bin("webshot").unwrap();
+    cmd.args(["https://example.com", "--proxy", "ftp://proxy.internal:21"]);
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Unsupported proxy scheme"));
+}
+
+#[test]
+fn test_browser_options_are_accepted_after_any_subcommand() {
+    for args in [
+        vec!["pdf", "https://example.com"],
+        vec!["text", "https://example.com"],
+        vec!["outline", "https://example.com"],
+        vec!["crawl", "https://example.com"],
+        vec!["repl"],
+    ] {
+        let mut cmd = Command::cargo_bin("webshot").unwrap();
+        cmd.args(args).args([
+            "--user-agent",
+            "WebshotBot/1.0",
+            "--proxy",
+            "ftp://proxy.internal:21",
+        ]);
+
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("Unsupported proxy scheme"));
+    }
+}
+
+#[test]
+fn test_retry_options_are_accepted_after_the_subcommand() {
+    let mut cmd = Command::cargo_bin("webshot").unwrap();
+    cmd.args([
+        "pdf",
+        "https://example.com",
+        "--retries",
+        "3",
+        "--retry-delay",
+        "10",
+        "--host-delay",
+        "5",
+        "--proxy",
+        "ftp://proxy.internal:21",
+    ]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Unsupported proxy scheme"));
+
+    let mut cmd = Command::cargo_bin("webshot").unwrap();
+    cmd.args(["crawl", "https://example.com", "--rate-limit", "0"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("must be a positive number"));
+}
+
+#[test]
+fn test_quality_is_range_checked_on_every_subcommand() {
+    for args in [
+        vec!["screenshot", "https://example.com"],
+        vec!["crawl", "https://example.com"],
+    ] {
+        let mut cmd = Command::cargo_bin("webshot").unwrap();
+        cmd.args(args).args(["--quality", "101"]);
+
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("1..=100"));
+    }
+}
+
+#[test]
+fn test_malformed_header_is_rejected() {
+    let mut cmd = Command::cargo_bin("webshot").unwrap();
+    cmd.args(["pdf", "https://example.com", "--header", "X-Missing-Colon"]);
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid header"));
+}
+
+#[test]
+fn test_pick_rejects_non_http_url() {
+    let mut cmd = Command::cargo_bin("webshot").unwrap();
+    cmd.args(["pick", "file:///etc/passwd"]);
+
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "Unsupported URL scheme in pick command",
+    ));
+}
+
+#[test]
+fn test_repl_rejects_non_http_start_url() {
+    let mut cmd = Command::cargo_bin("webshot").unwrap();
+    cmd.args(["repl", "javascript:alert(1)"]);
+
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "Unsupported URL scheme in repl command",
+    ));
+}
+
+#[test]
+fn test_ca_cert_must_contain_a_certificate() {
+    let temp_dir = TempDir::new().unwrap();
+    let ca_path = temp_dir.path().join("ca.pem");
+    fs::write(&ca_path, "not a certificate").unwrap();
+
+    let mut cmd = Command::cargo_bin("webshot").unwrap();
+    cmd.args(["https://example.com", "--ca-cert"]).arg(&ca_path);
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid CA certificate"));
+}
+
+#[test]
+fn test_unknown_blocked_resource_type_is_rejected() {
+    let mut cmd = Command::cargo_bin("webshot").unwrap();
+    cmd.args([
+        "text",
+        "https://example.com",
+        "--block-resources",
+        "images,videos",
+    ]);
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown resource type: videos"));
+}
+
+#[test]
+fn test_adblock_list_without_network_filters_is_rejected() {
+    let temp_dir = TempDir::new().unwrap();
+    let list_path = temp_dir.path().join("list.txt");
+    fs::write(&list_path, "! comments only\nexample.com##.ad\n").unwrap();
+
+    let mut cmd = Command::cargo_bin("webshot").unwrap();
+    cmd.args(["https://example.com", "--adblock", "--adblock-list"])
+        .arg(&list_path);
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("contains no network filters"));
+}
+
+#[test]
+fn test_overlay_rules_require_auto_dismiss() {
+    let mut cmd = Command::cargo_bin("webshot").unwrap();
+    cmd.args(["https://example.com", "--overlay-rules", "rules.yaml"]);
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("--auto-dismiss-overlays"));
+}
+
+#[test]
+fn test_script_requires_existing_file() {
+    let mut cmd = Command::cargo_bin("webshot").unwrap();
+    cmd.args(["script", "missing-flow.rhai"]);
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Script not found"));
+}
+
+#[test]
+fn test_health_fails_for_unwritable_output_dir() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut cmd = Command::cargo_bin("webshot").unwrap();
+    cmd.args(["health", "--output-dir"])
+        .arg(temp_dir.path().join("missing"));
+
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("\"disk_writable\": false"));
+}
+
+#[test]
+fn test_baseline_accept_refuses_newer_baseline_without_force() {
+    let temp_dir = TempDir::new().unwrap();
+    let image = temp_dir.path().join("home.png");
+    fs::write(&image, b"not checked when storing").unwrap();
+    let store = temp_dir.path().join("baselines");
+
+    let accept = |extra: &[&str]| {
+        let mut cmd = Command::cargo_bin("webshot").unwrap();
+        cmd.args(["baseline", "accept", "home.png"])
+            .arg(&image)
+            .arg("--store")
+            .arg(&store)
+            .args(extra);
+        cmd.assert()
+    };
+
+    accept(&[])
+        .success()
+        .stdout(predicate::str::contains("accepted as version 1"));
+    accept(&["--expected-version", "1"]).success();
+    accept(&["--expected-version", "1"])
+        .failure()
+        .stderr(predicate::str::contains("not the version expected"));
+    accept(&["--force"])
+        .success()
+        .stdout(predicate::str::contains("accepted as version 3"));
+}
+
+#[test]
+fn test_auth_flags_require_complete_credentials() {
+    let mut cmd = Command::cargo_bin("webshot").unwrap();
+    cmd.args(["https://example.com", "--auth-user", "admin"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("--auth-pass"));
+
+    let mut cmd = Command::cargo_bin("webshot").unwrap();
+    cmd.args(["pdf", "https://example.com", "--auth", "admin"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("user:pass"));
+}
+
+#[test]
+fn test_missing_session_file_fails_before_browser_startup() {
+    let temp_dir = TempDir::new().unwrap();
+
+    let mut cmd = Command::cargo_bin("webshot").unwrap();
+    cmd.arg("https://example.com")
+        .arg("--load-session")
+        .arg(temp_dir.path().join("state.json"));
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Failed to read session file"));
+}
+
+#[test]
+fn test_multi_requires_config_or_sitemap() {
+    let mut cmd = Command::cargo_bin("webshot").unwrap();
+    cmd.arg("multi");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("<CONFIG_FILE>"));
+}
+
+#[test]
+fn test_multi_rejects_non_http_sitemap_url() {
+    let mut cmd = Command::cargo_bin("webshot").unwrap();
+    cmd.arg("multi")
+        .arg("--from-sitemap")
+        .arg("file:///etc/sitemap.xml");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Unsupported URL scheme"));
+}
+
 #[test]
 fn test_readme_uses_actual_height_short_flag() {
     let readme_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("README.md");
@@ -296,7 +1007,7 @@ fn test_readme_documents_batch_output_behavior() {
     for detail in [
         "#### Output Behavior",
         "Target HTTP(S) URL (required)",
-        "Supported output extensions are `.png`, `.jpg`, `.jpeg`, `.webp`, and `.pdf`.",
+        "Supported output extensions are `.png`, `.jpg`, `.jpeg`, `.webp`, `.gif`, `.tif`, `.tiff`, and `.pdf`.",
         "Webshot chooses the runtime output format from the `output` filename extension.",
         "Relative screenshot `output` paths are resolved under `defaults.output_dir` when it is set.",
         "The `multi` command's `-o, --output-dir` option is prepended at runtime to each loaded output path",
@@ -477,27 +1188,31 @@ async fn test_version_output() {
 }
 
 #[tokio::test]
-#[ignore = "requires Chrome/Chromium and network access"]
+#[ignore = "requires Chrome/Chromium"]
 async fn test_verbose_logging() {
+    let server = fixtures();
+    let url = server.url("/static");
     let temp_dir = TempDir::new().unwrap();
     let output_path = temp_dir.path().join("verbose.png");
 
     let mut cmd = Command::cargo_bin("webshot").unwrap();
-    cmd.arg(TEST_URL).arg("-o").arg(&output_path).arg("-v");
+    cmd.arg(&url).arg("-o").arg(&output_path).arg("-v");
 
     cmd.assert().success();
     assert!(output_path.exists());
 }
 
 #[tokio::test]
-#[ignore = "requires Chrome/Chromium and network access"]
+#[ignore = "requires Chrome/Chromium"]
 async fn test_timeout_handling() {
+    let server = fixtures();
+    let url = server.url("/static");
     let temp_dir = TempDir::new().unwrap();
     let output_path = temp_dir.path().join("timeout.png");
 
     let mut cmd = Command::cargo_bin("webshot").unwrap();
     // Test timeout by waiting for an element that doesn't exist
-    cmd.arg("https://httpbin.org/html")
+    cmd.arg(&url)
         .arg("-o")
         .arg(&output_path)
         .arg("--wait-for")
@@ -533,30 +1248,31 @@ screenshots:
 }
 
 #[tokio::test]
-#[ignore = "requires Chrome/Chromium and network access"]
+#[ignore = "requires Chrome/Chromium"]
 async fn test_subcommand_screenshot() {
+    let server = fixtures();
+    let url = server.url("/static");
     let temp_dir = TempDir::new().unwrap();
     let output_path = temp_dir.path().join("subcommand.png");
 
     let mut cmd = Command::cargo_bin("webshot").unwrap();
-    cmd.arg("screenshot")
-        .arg(TEST_URL)
-        .arg("-o")
-        .arg(&output_path);
+    cmd.arg("screenshot").arg(&url).arg("-o").arg(&output_path);
 
     cmd.assert().success();
     assert!(output_path.exists());
 }
 
 #[tokio::test]
-#[ignore = "requires Chrome/Chromium and network access"]
+#[ignore = "requires Chrome/Chromium"]
 async fn test_subcommand_pdf() {
+    let server = fixtures();
+    let url = server.url("/static");
     let temp_dir = TempDir::new().unwrap();
     let output_path = temp_dir.path().join("subcommand.pdf");
 
     let mut cmd = Command::cargo_bin("webshot").unwrap();
     cmd.arg("pdf")
-        .arg(TEST_URL)
+        .arg(&url)
         .arg("-o")
         .arg(&output_path)
         .arg("--landscape");
@@ -569,8 +1285,10 @@ async fn test_subcommand_pdf() {
 }
 
 #[tokio::test]
-#[ignore = "requires Chrome/Chromium and network access"]
+#[ignore = "requires Chrome/Chromium"]
 async fn test_parallel_processing() {
+    let server = fixtures();
+    let url = server.url("/static");
     let temp_dir = TempDir::new().unwrap();
 
     // Create config with multiple screenshots
@@ -586,7 +1304,7 @@ screenshots:
   - url: "{}"
     output: "parallel4.png"
 "#,
-        TEST_URL, TEST_URL, TEST_URL, TEST_URL
+        url, url, url, url
     );
 
     let config_path = temp_dir.path().join("parallel-config.yaml");
@@ -1037,3 +1755,17 @@ async fn test_compare_text_output_format() {
     assert!(text_output.contains("Similarity:"));
     assert!(text_output.contains("Similar:"));
 }
+
+#[test]
+fn test_porcelain_reports_failures_as_one_record() {
+    let mut cmd = Command::cargo_bin("webshot").unwrap();
+    cmd.args(["--porcelain", "pdf", "file:///etc/passwd"]);
+
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::starts_with(
+            "pdf\tfailed\tfile:///etc/passwd\t\t",
+        ))
+        .stdout(predicate::str::contains("Unsupported URL scheme"))
+        .stdout(predicate::function(|out: &str| out.lines().count() == 1));
+}